@@ -8,9 +8,9 @@ from fusion_hawking.idl import service, method, event
 from .types import FusedTrack
 
 
-@service(id=0x7002)
+@service(id=0x7002, eventgroups={"tracks": 1})
 class FusionService:
-    @event(id=0x8001)
+    @event(id=0x8001, eventgroups=["tracks"])
     def on_track_updated(self, tracks: List[FusedTrack]):
         """Event: Track list updated after fusion."""
         ...
@@ -9,14 +9,14 @@ from fusion_hawking.idl import service, event, field
 from .types import RadarObject
 
 
-@service(id=0x7001)
+@service(id=0x7001, eventgroups={"objects": 1})
 class RadarService:
-    @event(id=0x8001)
+    @event(id=0x8001, eventgroups=["objects"])
     def on_object_detected(self, objects: List[RadarObject]):
         """Event: New radar objects detected (published periodically)."""
         ...
 
-    @field(id=1, get_id=0x10, notifier_id=0x12)
+    @field(id=1, get_id=0x10, notifier_id=0x8012)
     def detection_count(self) -> int:
         """Field: Total number of detections since startup."""
         ...
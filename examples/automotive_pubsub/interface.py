@@ -73,7 +73,7 @@ class RadarService:
         """Event: New radar objects detected (published periodically)."""
         ...
     
-    @field(id=1, get_id=0x10, notifier_id=0x12)
+    @field(id=1, get_id=0x10, notifier_id=0x8012)
     def detection_count(self) -> int:
         """Field: Total number of detections since startup."""
         ...
@@ -16,16 +16,13 @@ use std::thread;
 use std::time::Duration;
 
 pub mod generated {
-    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/../../../build/generated/automotive_pubsub/rust/mod.rs"));
+    include!(concat!(env!("OUT_DIR"), "/automotive_pubsub.rs"));
 }
 
 use generated::{
     FusionServiceProvider, FusionServiceServer,
-    RadarServiceClient, FusedTrack,
-    RadarServiceOnObjectDetectedEvent,
+    RadarServiceClient, FusedTrack, RadarObject,
 };
-use fusion_hawking::codec::SomeIpDeserialize;
-use fusion_hawking::runtime::RequestHandler;
 
 // --- Fusion Service Implementation ---
 struct FusionImpl {
@@ -42,13 +39,13 @@ impl FusionImpl {
     }
 
     /// Process incoming radar objects and update tracks
-    fn process_radar_data(&self, _objects: Vec<generated::RadarObject>) {
+    fn process_radar_data(&self, _objects: impl Iterator<Item = RadarObject>) {
         // Simple fusion: convert radar polar to cartesian
         let mut tracks = self.active_tracks.lock().unwrap();
         tracks.clear();
 
         // Mock fusion logic - in real system this would be Kalman filter etc.
-        for (i, _obj) in _objects.iter().enumerate() {
+        for (i, _obj) in _objects.enumerate() {
             let track = FusedTrack {
                 track_id: i as i32,
                 position_x: _obj.range_m * _obj.azimuth_deg.to_radians().cos(),
@@ -80,26 +77,6 @@ impl FusionServiceProvider for FusionImpl {
     }
 }
 
-// --- Manual Radar Notification Handler ---
-struct RadarHandler {
-    fusion: Arc<FusionImpl>,
-}
-
-impl RequestHandler for RadarHandler {
-    fn service_id(&self) -> u16 { RadarServiceClient::SERVICE_ID }
-    fn major_version(&self) -> u8 { RadarServiceClient::MAJOR_VERSION as u8 }
-    fn minor_version(&self) -> u32 { RadarServiceClient::MINOR_VERSION }
-    fn handle(&self, header: &fusion_hawking::codec::SomeIpHeader, payload: &[u8]) -> Option<Vec<u8>> {
-        if header.method_id == 0x8001 { // on_object_detected
-             let mut cursor = std::io::Cursor::new(payload);
-             if let Ok(event) = RadarServiceOnObjectDetectedEvent::deserialize(&mut cursor) {
-                 self.fusion.process_radar_data(event.objects);
-             }
-        }
-        None
-    }
-}
-
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     let config_path = if args.len() > 1 {
@@ -128,18 +105,14 @@ fn main() {
     let fusion = FusionServiceServer::new(fusion_impl.clone());
     rt.offer_service("fusion-service", Box::new(fusion));
 
-    // Subscribe to RadarService events
-    rt.subscribe_eventgroup(
-        RadarServiceClient::SERVICE_ID,
-        1,  // instance_id
-        1,  // eventgroup_id
-        100, // TTL
-        "primary"
-    );
-
-    // Register notification handler
-    let radar_handler = Box::new(RadarHandler { fusion: fusion_impl.clone() });
-    rt.register_notification_handler(RadarServiceClient::SERVICE_ID, radar_handler);
+    // Fuse each radar event as it arrives, decoded by the runtime
+    let fusion = fusion_impl.clone();
+    RadarServiceClient::on_object_detected(&rt, move |event| fusion.process_radar_data(event.objects.into_iter()));
+
+    // Subscribe to RadarService's objects (instance and interface from the "radar-client" config)
+    if let Err(e) = rt.subscribe_required("radar-client", RadarServiceClient::EVENTGROUP_OBJECTS, 100) {
+        logger.log(LogLevel::Warn, "Main", &format!("Not subscribed to RadarService: {}", e));
+    }
 
     logger.log(LogLevel::Info, "Main", "FusionService offered. Waiting for radar events...");
 
@@ -8,7 +8,7 @@ from fusion_hawking.idl import service, field, event
 
 @service(id=0x6001)
 class SensorService:
-    @field(id=1, get_id=0x10, notifier_id=0x12)
+    @field(id=1, get_id=0x10, notifier_id=0x8012)
     def temperature(self) -> float: ...
 
     @event(id=0x8001)
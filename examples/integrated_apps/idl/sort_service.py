@@ -18,5 +18,5 @@ class SortService:
     @event(id=0x8001)
     def on_sort_completed(self, count: int): ...
 
-    @field(id=10, get_id=0x10, set_id=0x11, notifier_id=0x12)
+    @field(id=10, get_id=0x10, set_id=0x11, notifier_id=0x8012)
     def status(self) -> str: ...
@@ -63,7 +63,7 @@ class SortService:
     @event(id=0x8001)
     def on_sort_completed(self, count: int): ...
     
-    @field(id=10, get_id=0x10, set_id=0x11, notifier_id=0x12)
+    @field(id=10, get_id=0x10, set_id=0x11, notifier_id=0x8012)
     def status(self) -> str: ...
 
 # Service 4: Global Complex Type Service (Hosted by Rust)
@@ -96,7 +96,7 @@ class DiagnosticService:
 # Service 6: C++ Sensor Service
 @service(id=0x6001)
 class SensorService:
-    @field(id=1, get_id=0x10, notifier_id=0x12)
+    @field(id=1, get_id=0x10, notifier_id=0x8012)
     def temperature(self) -> float: ...
     
     @event(id=0x8001)
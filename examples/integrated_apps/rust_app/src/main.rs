@@ -1,6 +1,6 @@
 use fusion_hawking::runtime::SomeIpRuntime;
 pub mod generated {
-    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/../../../build/generated/integrated_apps/rust/mod.rs"));
+    include!(concat!(env!("OUT_DIR"), "/integrated_apps.rs"));
 }
 use generated::{
     MathServiceProvider, MathServiceServer,
@@ -63,7 +63,7 @@ fn main() {
     };
 
     println!("Loading config from: {}", config_path);
-    let rt = SomeIpRuntime::load(config_path, "rust_app_instance");
+    let rt = SomeIpRuntime::load(config_path, "rust_app_instance").handle();
     let logger = rt.get_logger();
     logger.log(LogLevel::Info, "Main", &format!("--- Rust Runtime Expanded Demo (Config: {}) ---", config_path));
     
@@ -109,25 +109,42 @@ fn main() {
     // Subscribe using constants
     rt.subscribe_eventgroup(SortServiceClient::SERVICE_ID, 1, 1, 100, "primary");
 
+    // Client proxies are async; responses are matched by the runtime thread started above
+    let executor = tokio::runtime::Builder::new_current_thread().enable_time().build().expect("Failed to build executor");
     while running.load(Ordering::Relaxed) {
         if let Some(c) = rt.get_client::<MathServiceClient>("math-client-v2") {
-             let _ = c.add(10, 20);
+            match executor.block_on(c.add(10, 20)) {
+                Ok(sum) => logger.log(LogLevel::Info, "Main", &format!("math-client-v2: add(10, 20) = {}", sum)),
+                Err(e) => logger.log(LogLevel::Warn, "Main", &format!("math-client-v2: add failed: {}", e)),
+            }
         }
         
         if let Some(c) = rt.get_client::<MathServiceClient>("math-client-v1-inst2") {
-             let _ = c.add(100, 200);
+            match executor.block_on(c.add(100, 200)) {
+                Ok(sum) => logger.log(LogLevel::Info, "Main", &format!("math-client-v1-inst2: add(100, 200) = {}", sum)),
+                Err(e) => logger.log(LogLevel::Warn, "Main", &format!("math-client-v1-inst2: add failed: {}", e)),
+            }
         }
 
         if let Some(c) = rt.get_client::<StringServiceClient>("string-client") {
-             let _ = c.reverse("Rust App".to_string());
+            match executor.block_on(c.reverse("Rust App".to_string())) {
+                Ok(s) => logger.log(LogLevel::Info, "Main", &format!("string-client: reverse = '{}'", s)),
+                Err(e) => logger.log(LogLevel::Warn, "Main", &format!("string-client: reverse failed: {}", e)),
+            }
         }
 
         if let Some(c) = rt.get_client::<SortServiceClient>("sort-client") {
-             let _ = c.sort_asc(vec![9, 8, 7]);
+            match executor.block_on(c.sort_asc(vec![9, 8, 7])) {
+                Ok(sorted) => logger.log(LogLevel::Info, "Main", &format!("sort-client: sort_asc = {:?}", sorted)),
+                Err(e) => logger.log(LogLevel::Warn, "Main", &format!("sort-client: sort_asc failed: {}", e)),
+            }
         }
         
         if let Some(c) = rt.get_client::<DiagnosticServiceClient>("diag-client") {
-             let _ = c.get_version();
+            match executor.block_on(c.get_version()) {
+                Ok(v) => logger.log(LogLevel::Info, "Main", &format!("diag-client: version = '{}'", v)),
+                Err(e) => logger.log(LogLevel::Warn, "Main", &format!("diag-client: get_version failed: {}", e)),
+            }
         }
 
         thread::sleep(Duration::from_secs(2));
@@ -1,4 +1,4 @@
-use fusion_hawking::runtime::SomeIpRuntime;
+use fusion_hawking::runtime::AsyncSomeIpRuntime;
 use std::net::SocketAddr;
 use std::time::Duration;
 
@@ -9,13 +9,8 @@ async fn main() {
     
     println!("Starting Rust TP Client with config: {}", config_path);
     
-    let runtime = SomeIpRuntime::load(config_path, "tp_client");
-    
-    // Run runtime in background thread
-    let rt_clone = runtime.clone();
-    std::thread::spawn(move || {
-        rt_clone.run();
-    });
+    // The event loop runs on tokio's blocking pool
+    let runtime = AsyncSomeIpRuntime::load(config_path, "tp_client");
 
     println!("Waiting for runtime initialization...");
     tokio::time::sleep(Duration::from_secs(2)).await;
@@ -27,7 +22,7 @@ async fn main() {
     // 1. GET Request
     println!("Client: Sending GET Request (0x0001) to {}...", target);
     let payload = vec![];
-    match runtime.send_request_and_wait(service_id, 0x0001, &payload, target).await {
+    match runtime.request_to(service_id, 0x0001, &payload, target).await {
         Some(response) => {
             println!("Client: Received Response size: {}", response.len());
             if response.len() == 5000 {
@@ -54,7 +49,7 @@ async fn main() {
     let mut large_payload = Vec::with_capacity(5000);
     for i in 0..5000 { large_payload.push((i % 256) as u8); }
 
-    match runtime.send_request_and_wait(service_id, 0x0002, &large_payload, target).await {
+    match runtime.request_to(service_id, 0x0002, &large_payload, target).await {
         Some(response) => {
             println!("Client: Received ECHO Response size: {}", response.len());
              if response.len() == 5000 {
@@ -75,5 +70,5 @@ async fn main() {
         None => println!("FAILURE: ECHO Request Timed Out"),
     }
     
-    runtime.stop();
+    runtime.stop().await;
 }
@@ -1,5 +1,5 @@
-use fusion_hawking::runtime::{SomeIpRuntime, RequestHandler};
-use fusion_hawking::codec::SomeIpHeader;
+use fusion_hawking::runtime::{SomeIpRuntime, RequestHandler, HandlerResult};
+use fusion_hawking::codec::{ReturnCode, SomeIpHeader};
 
 const SERVICE_ID: u16 = 0x5000;
 const METHOD_ID_GET: u16 = 0x0001;
@@ -16,7 +16,7 @@ impl RequestHandler for LargePayloadService {
     fn major_version(&self) -> u8 { 1 }
     fn minor_version(&self) -> u32 { 0 }
 
-    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> Option<Vec<u8>> {
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
         let mid = header.method_id;
         
         if mid == METHOD_ID_GET {
@@ -25,16 +25,16 @@ impl RequestHandler for LargePayloadService {
             for i in 0..LARGE_PAYLOAD_SIZE {
                 data.push((i % 256) as u8);
             }
-            return Some(data);
+            return Ok(Some(data));
         } else if mid == METHOD_ID_ECHO {
             println!("Received ECHO Request, size={}", payload.len());
             if payload.len() != LARGE_PAYLOAD_SIZE {
                 println!("WARNING: Received size {} != Expected {}", payload.len(), LARGE_PAYLOAD_SIZE);
             }
-            return Some(payload.to_vec());
+            return Ok(Some(payload.to_vec()));
         }
         
-        None
+        Err(ReturnCode::UnknownMethod)
     }
 }
 
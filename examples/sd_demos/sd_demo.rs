@@ -20,8 +20,8 @@ fn main() {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(transport_v4),
-            transport_v6: Some(transport_v6),
+            transport_v4: Some(Box::new(transport_v4)),
+            transport_v6: Some(Box::new(transport_v6)),
             multicast_group_v4: Some(m_v4),
             multicast_group_v6: Some(m_v6),
             local_ip_v4: Some(local_ip),
@@ -50,8 +50,8 @@ fn main() {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(transport_v4),
-            transport_v6: Some(transport_v6),
+            transport_v4: Some(Box::new(transport_v4)),
+            transport_v6: Some(Box::new(transport_v6)),
             multicast_group_v4: Some(m_v4),
             multicast_group_v6: Some(m_v6),
             local_ip_v4: Some(local_ip),
@@ -48,9 +48,30 @@ class GenericClient:
         )
         return response
 
+def run_on_rust_core(config_path):
+    """Same exchange, through the Rust runtime (needs `cargo build --release --features python`)."""
+    from fusion_hawking.rust_core import RustRuntime
+
+    with RustRuntime(config_path, "python_client") as runtime:
+        runtime.start()
+        print(f"[Fusion Python Client] Waiting for someipy service ({hex(SOMEIPY_SERVICE_ID)}) via the Rust core...")
+        client = runtime.get_client("someipy_svc")
+        if not client:
+            print("[Fusion Python Client] Could not discover service.")
+            return
+        message = "Hello from Fusion Python!"
+        print(f"[Fusion Python Client] Sending Echo: '{message}'")
+        try:
+            response = client.request(SOMEIPY_METHOD_ECHO, message.encode('utf-8'))
+            print(f"[Fusion Python Client] Got Response: '{response.decode()}'")
+        except TimeoutError:
+            print("[Fusion Python Client] Failed to get response.")
+
 def main():
-    if len(sys.argv) > 1:
-        config_path = sys.argv[1]
+    rust_core = "--rust-core" in sys.argv
+    args = [a for a in sys.argv[1:] if a != "--rust-core"]
+    if args:
+        config_path = args[0]
     else:
         config_path = os.path.join(os.path.dirname(__file__), "client_config.json")
     
@@ -59,6 +80,10 @@ def main():
         print(f"Error: custom config missing at {config_path}")
         return
 
+    if rust_core:
+        run_on_rust_core(config_path)
+        return
+
     runtime = SomeIpRuntime(config_path, "python_client")
     runtime.start()
     
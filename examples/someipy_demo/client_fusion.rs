@@ -1,30 +1,18 @@
-use fusion_hawking::runtime::{SomeIpRuntime, ServiceClient};
-use fusion_hawking::transport::SomeIpTransport;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use fusion_hawking::runtime::{ClientContext, SomeIpRuntime, ServiceClient};
 use std::thread;
 use std::time::Duration;
 
-#[derive(Clone)]
 struct GenericClient {
-    #[allow(dead_code)]
-    transport: Arc<dyn SomeIpTransport>,
-    target: SocketAddr,
+    context: ClientContext,
 }
 
-// ServiceClient usually requires Send + Sync if it's stored in the runtime or used across threads
 impl ServiceClient for GenericClient {
     const SERVICE_ID: u16 = 0x1234;
-    fn new(transport: Arc<dyn SomeIpTransport>, target: SocketAddr) -> Self {
-        Self { transport, target }
+    fn new(context: ClientContext) -> Self {
+        Self { context }
     }
 }
 
-// Explicitly implement Send and Sync if compiler is unsure, 
-// though Arc<dyn SomeIpTransport> should already be Send+Sync if the trait is.
-unsafe impl Send for GenericClient {}
-unsafe impl Sync for GenericClient {}
-
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = std::env::args().collect();
@@ -44,19 +32,19 @@ async fn main() {
     thread::sleep(Duration::from_secs(1));
 
     if let Some(client) = runtime.get_client::<GenericClient>("someipy_svc") {
-        println!("[Fusion Rust Client] Discovered service at {}", client.target);
+        println!("[Fusion Rust Client] Discovered service at {}", client.context.target());
         
         let msg = "Hello from Fusion Rust!";
         let payload = msg.as_bytes().to_vec();
         
         println!("[Fusion Rust Client] Sending Echo: '{}'", msg);
-        match runtime.send_request_and_wait(0x1234, 0x0001, &payload, client.target).await {
-            Some(response) => {
+        match client.context.request(0x0001, &payload).await {
+            Ok(response) => {
                 let res_str = String::from_utf8_lossy(&response);
                 println!("[Fusion Rust Client] Got Response: '{}'", res_str);
             }
-            None => {
-                println!("[Fusion Rust Client] RPC Error: No response received");
+            Err(e) => {
+                println!("[Fusion Rust Client] RPC Error: {}", e);
             }
         }
     } else {
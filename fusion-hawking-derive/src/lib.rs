@@ -0,0 +1,307 @@
+//! # Fusion Hawking Derive Macros
+//!
+//! `#[derive(SomeIpSerialize, SomeIpDeserialize)]` for structs, re-exported by
+//! `fusion_hawking::codec`. Members are encoded in declaration order with their
+//! own codec impls, which `#[someip(..)]` attributes adjust:
+//!
+//! | Attribute | On | Effect |
+//! |-----------|----|--------|
+//! | `length_field = 1\|2\|4` | struct | Length field of that many bytes before the members; receivers skip trailing members they do not know |
+//! | `length_field = 1\|2\|4` | `Vec` / `String` / map member | Length field of that many bytes instead of 4 |
+//! | `align = 1\|2\|4\|8` | struct | Pad every member to a multiple of that many bytes from the start of the struct |
+//! | `align = 1\|2\|4\|8` | member | Pad this member the same way |
+//! | `optional` | `Option<T>` member | Present, with its padding, only if `Some`; a bit field before the members flags which optional members are present, the first in the least significant bit |
+//!
+//! ```ignore
+//! use fusion_hawking::codec::{SomeIpDeserialize, SomeIpSerialize};
+//!
+//! #[derive(SomeIpSerialize, SomeIpDeserialize)]
+//! #[someip(length_field = 2)]
+//! struct Status {
+//!     code: u8,
+//!     #[someip(length_field = 1)]
+//!     text: String,
+//!     #[someip(optional, align = 4)]
+//!     detail: Option<u32>,
+//! }
+//! ```
+//!
+//! `#[derive(SomeIpDeserializeRef)]` decodes the members with
+//! `SomeIpDeserializeRef` instead, so a struct with a lifetime can hold `&str`,
+//! `&[u8]` and `SeqRef` members borrowing from the received bytes. It takes no
+//! `#[someip(..)]` attributes.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, GenericArgument, Index, LitInt, PathArguments, Result, Type};
+
+#[proc_macro_derive(SomeIpSerialize, attributes(someip))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, true).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(SomeIpDeserialize, attributes(someip))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, false).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(SomeIpDeserializeRef, attributes(someip))]
+pub fn derive_deserialize_ref(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_ref(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct Options {
+    length_field: Option<usize>,
+    align: Option<usize>,
+    optional: bool,
+}
+
+fn options(attrs: &[Attribute], member: bool) -> Result<Options> {
+    let mut opts = Options::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("someip")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("length_field") {
+                let lit: LitInt = meta.value()?.parse()?;
+                match lit.base10_parse()? {
+                    width @ (1 | 2 | 4) => opts.length_field = Some(width),
+                    _ => return Err(Error::new_spanned(lit, "length_field must be 1, 2 or 4 (bytes)")),
+                }
+            } else if meta.path.is_ident("align") {
+                let lit: LitInt = meta.value()?.parse()?;
+                match lit.base10_parse()? {
+                    align @ (1 | 2 | 4 | 8) => opts.align = Some(align),
+                    _ => return Err(Error::new_spanned(lit, "align must be 1, 2, 4 or 8 (bytes)")),
+                }
+            } else if meta.path.is_ident("optional") && member {
+                opts.optional = true;
+            } else {
+                return Err(meta.error("unsupported someip attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(opts)
+}
+
+/// `T` of `Option<T>`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+struct Member<'a> {
+    /// `self.<access>` reaches the member
+    access: TokenStream2,
+    /// Local holding the deserialized value
+    local: syn::Ident,
+    /// Type read from the wire: `T` for optional `Option<T>` members
+    ty: &'a Type,
+    opts: Options,
+}
+
+fn expand(input: &DeriveInput, serialize: bool) -> Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(input, "SOME/IP derives support structs only"));
+    };
+    let struct_opts = options(&input.attrs, false)?;
+    let mut members = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let mut opts = options(&field.attrs, true)?;
+        opts.align = opts.align.or(struct_opts.align);
+        let ty = if opts.optional {
+            option_inner(&field.ty).ok_or_else(|| Error::new_spanned(&field.ty, "optional members must be Option<T>"))?
+        } else {
+            &field.ty
+        };
+        let access = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(i);
+                quote!(#index)
+            }
+        };
+        members.push(Member { access, local: format_ident!("__member_{}", i), ty, opts });
+    }
+
+    let core = quote!(::fusion_hawking::codec);
+    let optional_count = members.iter().filter(|m| m.opts.optional).count();
+    let flag_width = match optional_count {
+        0..=8 => 1usize,
+        9..=16 => 2,
+        17..=32 => 4,
+        33..=64 => 8,
+        _ => return Err(Error::new_spanned(input, "at most 64 optional members are supported")),
+    };
+    let aligned = members.iter().any(|m| m.opts.align.is_some());
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    let bound = if serialize { quote!(#core::SomeIpSerialize) } else { quote!(#core::SomeIpDeserialize) };
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse2(bound.clone())?);
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    if serialize {
+        let mut body = Vec::new();
+        if optional_count > 0 {
+            let flags = members.iter().filter(|m| m.opts.optional).map(|m| {
+                let access = &m.access;
+                quote!(self.#access.is_some())
+            });
+            body.push(quote!(#core::repr::write_bits(&[#(#flags),*], #flag_width, __w)?;));
+        }
+        for m in &members {
+            let access = &m.access;
+            let pad = m.opts.align.map(|align| quote!(#core::layout::write_padding(__w, #align)?;));
+            let write = match m.opts.length_field {
+                Some(width) => quote!(#pad #core::layout::write_resized(__value, #width, __w)?;),
+                None => quote!(#pad #core::SomeIpSerialize::serialize(__value, __w)?;),
+            };
+            body.push(if m.opts.optional {
+                quote!(if let Some(__value) = &self.#access { #write })
+            } else {
+                quote!({ let __value = &self.#access; #write })
+            });
+        }
+        let counted = aligned.then(|| quote!(let __w = &mut #core::layout::Counted::new(__w);));
+        let body = match struct_opts.length_field {
+            Some(width) => quote! {
+                #core::layout::write_framed(#width, writer, |__w| {
+                    #counted
+                    #(#body)*
+                    Ok(())
+                })
+            },
+            None => quote! {
+                let __w = writer;
+                #counted
+                #(#body)*
+                Ok(())
+            },
+        };
+        Ok(quote! {
+            impl #impl_generics #core::SomeIpSerialize for #name #ty_generics #where_clause {
+                fn serialize<__W: ::std::io::Write>(&self, writer: &mut __W) -> ::std::io::Result<()> {
+                    #body
+                }
+            }
+        })
+    } else {
+        let mut body = Vec::new();
+        if optional_count > 0 {
+            body.push(quote!(let __present = #core::repr::read_bits(#optional_count, #flag_width, __r)?;));
+        }
+        let mut optional_index = 0usize;
+        for m in &members {
+            let (local, ty) = (&m.local, m.ty);
+            let pad = m.opts.align.map(|align| quote!(#core::layout::skip_padding(__r, #align)?;));
+            let read = match m.opts.length_field {
+                Some(width) => quote!({ #pad #core::layout::read_resized::<#ty, _>(#width, __r)? }),
+                None => quote!({ #pad <#ty as #core::SomeIpDeserialize>::deserialize(__r)? }),
+            };
+            body.push(if m.opts.optional {
+                let index = optional_index;
+                optional_index += 1;
+                quote!(let #local = if __present[#index] { Some(#read) } else { None };)
+            } else {
+                quote!(let #local = #read;)
+            });
+        }
+        let locals = members.iter().map(|m| &m.local);
+        let construct = match &data.fields {
+            Fields::Named(_) => {
+                let fields = members.iter().map(|m| &m.access);
+                quote!(Self { #(#fields: #locals),* })
+            }
+            Fields::Unnamed(_) => quote!(Self(#(#locals),*)),
+            Fields::Unit => quote!(Self),
+        };
+        let counted = aligned.then(|| quote!(let __r = &mut #core::layout::Counted::new(__r);));
+        let body = match struct_opts.length_field {
+            Some(width) => quote! {
+                #core::layout::read_framed(#width, reader, |__r| {
+                    #counted
+                    #(#body)*
+                    Ok(#construct)
+                })
+            },
+            None => quote! {
+                let __r = reader;
+                #counted
+                #(#body)*
+                Ok(#construct)
+            },
+        };
+        Ok(quote! {
+            impl #impl_generics #core::SomeIpDeserialize for #name #ty_generics #where_clause {
+                fn deserialize<__R: ::std::io::Read>(reader: &mut __R) -> ::std::io::Result<Self> {
+                    #body
+                }
+            }
+        })
+    }
+}
+
+fn expand_ref(input: &DeriveInput) -> Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(input, "SOME/IP derives support structs only"));
+    };
+    let attrs = input.attrs.iter().chain(data.fields.iter().flat_map(|f| &f.attrs));
+    if let Some(attr) = attrs.into_iter().find(|a| a.path().is_ident("someip")) {
+        return Err(Error::new_spanned(attr, "SomeIpDeserializeRef supports the default layout only"));
+    }
+
+    let core = quote!(::fusion_hawking::codec);
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    // Borrow from the buffer for the struct's own lifetime, if it has one
+    let lifetime = match generics.lifetimes().next() {
+        Some(param) => param.lifetime.clone(),
+        None => {
+            let lifetime: syn::Lifetime = syn::parse_quote!('__a);
+            generics.params.insert(0, syn::parse_quote!(#lifetime));
+            lifetime
+        }
+    };
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(#core::SomeIpDeserializeRef<#lifetime>));
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let locals: Vec<_> = (0..data.fields.len()).map(|i| format_ident!("__member_{}", i)).collect();
+    let reads = data.fields.iter().zip(&locals).map(|(field, local)| {
+        let ty = &field.ty;
+        quote!(let #local = <#ty as #core::SomeIpDeserializeRef<#lifetime>>::deserialize_ref(buf)?;)
+    });
+    let construct = match &data.fields {
+        Fields::Named(_) => {
+            let fields = data.fields.iter().map(|f| &f.ident);
+            quote!(Self { #(#fields: #locals),* })
+        }
+        Fields::Unnamed(_) => quote!(Self(#(#locals),*)),
+        Fields::Unit => quote!(Self),
+    };
+    Ok(quote! {
+        impl #impl_generics #core::SomeIpDeserializeRef<#lifetime> for #name #ty_generics #where_clause {
+            fn deserialize_ref(buf: &mut &#lifetime [u8]) -> ::std::io::Result<Self> {
+                #(#reads)*
+                Ok(#construct)
+            }
+        }
+    })
+}
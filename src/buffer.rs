@@ -0,0 +1,191 @@
+//! # Buffer Pool
+//!
+//! [`BufferPool`] recycles the byte buffers messages are copied into (TP
+//! reassembly, Magic Cookie framing, sends over transports without vectored
+//! I/O), so a runtime in steady state stops allocating per message.
+//! A [`PooledBuffer`] derefs to `Vec<u8>` and goes back to its pool, emptied,
+//! when dropped.
+//!
+//! The runtime, the TCP transport and TP reassembly share the process-wide pool
+//! returned by [`shared`]; its counters are part of `RuntimeStats`.
+//!
+//! ```ignore
+//! let mut msg = fusion_hawking::buffer::shared().get();
+//! msg.extend_from_slice(&header.serialize());
+//! msg.extend_from_slice(payload);
+//! transport.send(&msg, Some(target))?;
+//! ```
+
+use serde::Serialize;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Default number of idle buffers a pool keeps
+pub const DEFAULT_MAX_IDLE: usize = 256;
+/// Default capacity above which a returned buffer is freed instead of kept
+pub const DEFAULT_MAX_CAPACITY: usize = 64 * 1024;
+
+/// Counters of a [`BufferPool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "serde-types", derive(serde::Deserialize))]
+pub struct BufferPoolStats {
+    /// Buffers handed out that had to be allocated
+    pub allocated: u64,
+    /// Buffers handed out from the pool
+    pub reused: u64,
+    /// Returned buffers freed because the pool was full or they were too large
+    pub discarded: u64,
+    /// Buffers in the pool now
+    pub idle: usize,
+}
+
+/// Mutex-guarded free list of byte buffers.
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+    max_idle: usize,
+    max_capacity: usize,
+    allocated: AtomicU64,
+    reused: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_IDLE, DEFAULT_MAX_CAPACITY)
+    }
+}
+
+impl BufferPool {
+    pub fn new(max_idle: usize, max_capacity: usize) -> Self {
+        BufferPool {
+            free: Mutex::new(Vec::new()),
+            max_idle,
+            max_capacity,
+            allocated: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+
+    /// An empty buffer, returned to the pool when dropped.
+    pub fn get(&self) -> PooledBuffer<'_> {
+        self.adopt(self.take())
+    }
+
+    /// Return `buf` to this pool when the [`PooledBuffer`] is dropped.
+    pub fn adopt(&self, buf: Vec<u8>) -> PooledBuffer<'_> {
+        PooledBuffer { buf, pool: self }
+    }
+
+    /// An empty buffer to hand back with [`put`](Self::put) when done.
+    pub fn take(&self) -> Vec<u8> {
+        match self.free.lock().unwrap().pop() {
+            Some(buf) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                buf
+            }
+            None => {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Keep `buf` for reuse, unless the pool is full or `buf` grew too large.
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 {
+            return;
+        }
+        if buf.capacity() <= self.max_capacity {
+            let mut free = self.free.lock().unwrap();
+            if free.len() < self.max_idle {
+                buf.clear();
+                free.push(buf);
+                return;
+            }
+        }
+        self.discarded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            allocated: self.allocated.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+            idle: self.free.lock().unwrap().len(),
+        }
+    }
+}
+
+/// The pool shared by runtimes, transports and TP reassembly in this process.
+pub fn shared() -> &'static BufferPool {
+    static POOL: OnceLock<BufferPool> = OnceLock::new();
+    POOL.get_or_init(BufferPool::default)
+}
+
+/// A buffer of a [`BufferPool`], returned to it on drop.
+pub struct PooledBuffer<'a> {
+    buf: Vec<u8>,
+    pool: &'a BufferPool,
+}
+
+impl PooledBuffer<'_> {
+    /// Keep the buffer instead of returning it to the pool.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = BufferPool::new(2, 64);
+        {
+            let mut buf = pool.get();
+            buf.extend_from_slice(&[1, 2, 3]);
+        }
+        let buf = pool.get();
+        assert!(buf.is_empty());
+        assert!(buf.capacity() >= 3);
+        drop(buf);
+        assert_eq!(pool.stats(), BufferPoolStats { allocated: 1, reused: 1, discarded: 0, idle: 1 });
+
+        // Buffers never written to are not worth keeping
+        let _ = pool.get().into_vec();
+        drop(pool.get());
+        assert_eq!(pool.stats().idle, 0);
+    }
+
+    #[test]
+    fn test_pool_limits() {
+        let pool = BufferPool::new(1, 64);
+        pool.put(vec![0u8; 128]);
+        pool.put(vec![0u8; 8]);
+        pool.put(vec![0u8; 8]);
+        assert_eq!(pool.stats(), BufferPoolStats { allocated: 0, reused: 0, discarded: 2, idle: 1 });
+    }
+}
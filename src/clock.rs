@@ -0,0 +1,125 @@
+//! # Time Source
+//!
+//! Components with timers (e.g. the SD state machine) read time through [`Clock`]
+//! so tests and simulations can substitute a virtual clock.
+//!
+//! [`Timestamp`] is the common unit for anything that records *when* something
+//! happened (log lines, SD phase changes, event arrival, the shutdown report):
+//! monotonic nanoseconds since a process-wide anchor taken on first use, so
+//! values from different modules can be compared directly. The anchor also
+//! records the wall-clock time it was taken, for [`Timestamp::to_system_time`].
+
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Current time of this clock on the shared timestamp scale.
+    fn timestamp(&self) -> Timestamp {
+        Timestamp::from_instant(self.now())
+    }
+}
+
+/// Wall-clock time (`Instant::now()`); the default everywhere.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Monotonic and wall-clock readings taken together on first use.
+fn anchor() -> &'static (Instant, SystemTime) {
+    static ANCHOR: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+    ANCHOR.get_or_init(|| (Instant::now(), SystemTime::now()))
+}
+
+/// Monotonic point in time, in nanoseconds since the process-wide anchor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    /// Current time.
+    pub fn now() -> Self {
+        Self::from_instant(Instant::now())
+    }
+
+    /// Place an `Instant` on the timestamp scale. Instants before the anchor map to zero.
+    pub fn from_instant(instant: Instant) -> Self {
+        Timestamp(instant.saturating_duration_since(anchor().0).as_nanos() as u64)
+    }
+
+    pub fn from_nanos(nanos: u64) -> Self {
+        Timestamp(nanos)
+    }
+
+    pub fn as_nanos(&self) -> u64 {
+        self.0
+    }
+
+    pub fn as_micros(&self) -> u64 {
+        self.0 / 1_000
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.0 / 1_000_000
+    }
+
+    /// Time from `earlier` to `self`, zero if `earlier` is later.
+    pub fn duration_since(&self, earlier: Timestamp) -> Duration {
+        Duration::from_nanos(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Wall-clock time corresponding to this timestamp.
+    pub fn to_system_time(&self) -> SystemTime {
+        anchor().1 + Duration::from_nanos(self.0)
+    }
+}
+
+/// Seconds since the anchor with microsecond precision, e.g. `+12.000345`.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{}.{:06}", self.0 / 1_000_000_000, self.0 % 1_000_000_000 / 1_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_share_anchor() {
+        Timestamp::now(); // take the anchor before `start`
+        let start = Instant::now();
+        let a = Timestamp::from_instant(start);
+        let b = Timestamp::from_instant(start + Duration::from_millis(250));
+        assert_eq!(b.duration_since(a), Duration::from_millis(250));
+        assert_eq!(a.duration_since(b), Duration::ZERO);
+        assert!(Timestamp::now() >= a);
+        assert!(SystemClock.timestamp() >= a);
+    }
+
+    #[test]
+    fn test_wall_clock_conversion() {
+        let ts = Timestamp::now();
+        let wall = SystemTime::now();
+        let converted = ts.to_system_time();
+        let skew = wall.duration_since(converted).unwrap_or_else(|e| e.duration());
+        assert!(skew < Duration::from_secs(1));
+        assert_eq!(
+            Timestamp::from_nanos(2_500_000_000).to_system_time().duration_since(Timestamp::default().to_system_time()).unwrap(),
+            Duration::from_millis(2500),
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Timestamp::from_nanos(12_000_345_678).to_string(), "+12.000345");
+        assert_eq!(Timestamp::from_nanos(1_500_000).as_millis(), 1);
+    }
+}
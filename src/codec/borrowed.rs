@@ -0,0 +1,159 @@
+//! # Borrowed Views
+//!
+//! [`SomeIpDeserializeRef`] decodes from the receive buffer without copying:
+//! `&str` and `&[u8]` borrow the bytes of a string or byte array, and
+//! [`SeqRef`] decodes the elements of an array one at a time while iterating.
+//! Every [`SomeIpDeserialize`] type is also a [`SomeIpDeserializeRef`], so views
+//! can hold primitives and generated structs directly.
+//!
+//! ```ignore
+//! use fusion_hawking::codec::{SeqRef, SomeIpDeserializeRef};
+//!
+//! #[derive(SomeIpDeserializeRef)]
+//! struct ObjectsView<'a> {
+//!     objects: SeqRef<'a, RadarObject>,
+//! }
+//!
+//! let view = ObjectsView::deserialize_ref(&mut &payload[..])?;
+//! for object in view.objects.iter() {
+//!     let object = object?;
+//! }
+//! ```
+
+use super::traits::{SomeIpDeserialize, SomeIpDeserializeRef};
+use std::io::{Error, ErrorKind, Result};
+use std::marker::PhantomData;
+
+impl<'a, T: SomeIpDeserialize> SomeIpDeserializeRef<'a> for T {
+    fn deserialize_ref(buf: &mut &'a [u8]) -> Result<Self> {
+        T::deserialize(buf)
+    }
+}
+
+/// Take the bytes behind a 32-bit length field, as written for `Vec` and `String`
+fn length_prefixed<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = u32::deserialize(buf)? as usize;
+    if len > buf.len() {
+        return Err(Error::new(ErrorKind::UnexpectedEof, format!("{} bytes announced, {} left", len, buf.len())));
+    }
+    let (data, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(data)
+}
+
+// &[u8] - The bytes of a Vec<u8>
+impl<'a> SomeIpDeserializeRef<'a> for &'a [u8] {
+    fn deserialize_ref(buf: &mut &'a [u8]) -> Result<Self> {
+        length_prefixed(buf)
+    }
+}
+
+// &str - The UTF-8 bytes of a String
+impl<'a> SomeIpDeserializeRef<'a> for &'a str {
+    fn deserialize_ref(buf: &mut &'a [u8]) -> Result<Self> {
+        std::str::from_utf8(length_prefixed(buf)?).map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid UTF-8"))
+    }
+}
+
+/// A `Vec<T>` on the wire, decoded element by element on iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeqRef<'a, T> {
+    bytes: &'a [u8],
+    element: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: SomeIpDeserializeRef<'a>> SeqRef<'a, T> {
+    /// Encoded elements, without the length field
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Decode the elements in order; iteration ends after the first error
+    pub fn iter(&self) -> SeqIter<'a, T> {
+        SeqIter { bytes: self.bytes, element: PhantomData }
+    }
+
+    /// Decode all elements into a vector
+    pub fn to_vec(&self) -> Result<Vec<T>> {
+        self.iter().collect()
+    }
+}
+
+impl<'a, T> SomeIpDeserializeRef<'a> for SeqRef<'a, T> {
+    fn deserialize_ref(buf: &mut &'a [u8]) -> Result<Self> {
+        Ok(SeqRef { bytes: length_prefixed(buf)?, element: PhantomData })
+    }
+}
+
+impl<'a, T: SomeIpDeserializeRef<'a>> IntoIterator for SeqRef<'a, T> {
+    type Item = Result<T>;
+    type IntoIter = SeqIter<'a, T>;
+
+    fn into_iter(self) -> SeqIter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterator of [`SeqRef`]
+pub struct SeqIter<'a, T> {
+    bytes: &'a [u8],
+    element: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: SomeIpDeserializeRef<'a>> Iterator for SeqIter<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let item = T::deserialize_ref(&mut self.bytes);
+        if item.is_err() {
+            self.bytes = &[];
+        }
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpSerialize;
+
+    #[test]
+    fn test_views_borrow_from_the_buffer() {
+        let mut buf = Vec::new();
+        String::from("radar").serialize(&mut buf).unwrap();
+        vec![1u8, 2, 3].serialize(&mut buf).unwrap();
+        vec![String::from("a"), String::from("bc")].serialize(&mut buf).unwrap();
+        7u16.serialize(&mut buf).unwrap();
+
+        let mut reader = &buf[..];
+        let name = <&str>::deserialize_ref(&mut reader).unwrap();
+        assert_eq!(name, "radar");
+        assert!(std::ptr::eq(name.as_ptr(), buf[4..].as_ptr()));
+        assert_eq!(<&[u8]>::deserialize_ref(&mut reader).unwrap(), [1, 2, 3]);
+        let names = SeqRef::<&str>::deserialize_ref(&mut reader).unwrap();
+        assert_eq!(names.iter().collect::<Result<Vec<_>>>().unwrap(), vec!["a", "bc"]);
+        assert_eq!(u16::deserialize_ref(&mut reader).unwrap(), 7);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_truncated_views() {
+        assert!(<&[u8]>::deserialize_ref(&mut &[0, 0, 0, 4, 1][..]).is_err());
+        assert!(<&str>::deserialize_ref(&mut &[0, 0, 0, 1, 0xFF][..]).is_err());
+
+        // An element cut short ends the iteration with an error
+        let seq = SeqRef::<u16>::deserialize_ref(&mut &[0, 0, 0, 3, 0, 1, 2][..]).unwrap();
+        let items: Vec<_> = seq.iter().collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap(), &1);
+        assert!(items[1].is_err());
+        assert!(seq.to_vec().is_err());
+    }
+}
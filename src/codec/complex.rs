@@ -1,4 +1,7 @@
+use super::repr;
 use super::traits::{SomeIpSerialize, SomeIpDeserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
 use std::io::{Result, Write, Read};
 
 // Strings are typically UTF-8 with a BOM or length prefix in SOME/IP,
@@ -66,3 +69,112 @@ impl<T: SomeIpDeserialize> SomeIpDeserialize for Vec<T> {
         Ok(vec)
     }
 }
+
+// [T; N] - Fixed size, elements back to back without a length prefix
+impl<T: SomeIpSerialize, const N: usize> SomeIpSerialize for [T; N] {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for item in self {
+            item.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: SomeIpDeserialize, const N: usize> SomeIpDeserialize for [T; N] {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            items.push(T::deserialize(reader)?);
+        }
+        items.try_into().map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "array length mismatch"))
+    }
+}
+
+// Option<T> - 1-byte presence bit field (bit 0 set if present), then the value
+// if present. Same layout as a derived struct with a single optional member.
+impl<T: SomeIpSerialize> SomeIpSerialize for Option<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        repr::write_bits(&[self.is_some()], 1, writer)?;
+        match self {
+            Some(value) => value.serialize(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: SomeIpDeserialize> SomeIpDeserialize for Option<T> {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {
+        let present = repr::read_bits(1, 1, reader)?;
+        if present[0] {
+            Ok(Some(T::deserialize(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// Maps - 32-bit Length (Bytes) followed by key/value pairs
+fn serialize_map<'a, K, V, W>(entries: impl Iterator<Item = (&'a K, &'a V)>, writer: &mut W) -> Result<()>
+where
+    K: SomeIpSerialize + 'a,
+    V: SomeIpSerialize + 'a,
+    W: Write,
+{
+    let mut buffer = Vec::new();
+    for (key, value) in entries {
+        key.serialize(&mut buffer)?;
+        value.serialize(&mut buffer)?;
+    }
+    writer.write_all(&(buffer.len() as u32).to_be_bytes())?;
+    writer.write_all(&buffer)
+}
+
+fn deserialize_map<K, V, R>(reader: &mut R, mut insert: impl FnMut(K, V) -> Option<V>) -> Result<()>
+where
+    K: SomeIpDeserialize,
+    V: SomeIpDeserialize,
+    R: Read,
+{
+    let mut length_bytes = [0u8; 4];
+    reader.read_exact(&mut length_bytes)?;
+    let mut buffer = vec![0u8; u32::from_be_bytes(length_bytes) as usize];
+    reader.read_exact(&mut buffer)?;
+
+    let mut cursor = &buffer[..];
+    while !cursor.is_empty() {
+        let key = K::deserialize(&mut cursor)?;
+        let value = V::deserialize(&mut cursor)?;
+        if insert(key, value).is_some() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Duplicate map key"));
+        }
+    }
+    Ok(())
+}
+
+impl<K: SomeIpSerialize, V: SomeIpSerialize, S> SomeIpSerialize for HashMap<K, V, S> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        serialize_map(self.iter(), writer)
+    }
+}
+
+impl<K: SomeIpDeserialize + Eq + Hash, V: SomeIpDeserialize, S: BuildHasher + Default> SomeIpDeserialize for HashMap<K, V, S> {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut map = HashMap::default();
+        deserialize_map(reader, |k, v| map.insert(k, v))?;
+        Ok(map)
+    }
+}
+
+impl<K: SomeIpSerialize, V: SomeIpSerialize> SomeIpSerialize for BTreeMap<K, V> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        serialize_map(self.iter(), writer)
+    }
+}
+
+impl<K: SomeIpDeserialize + Ord, V: SomeIpDeserialize> SomeIpDeserialize for BTreeMap<K, V> {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut map = BTreeMap::new();
+        deserialize_map(reader, |k, v| map.insert(k, v))?;
+        Ok(map)
+    }
+}
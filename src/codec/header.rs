@@ -1,8 +1,10 @@
 use std::convert::TryInto;
+use super::RequestId;
 
 /// SOME/IP Message Types as defined in AUTOSAR SOME/IP Protocol Specification
 /// [PRS_SOMEIP_00044]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum MessageType {
     /// Request expecting a response
@@ -77,6 +79,7 @@ impl From<MessageType> for u8 {
 /// SOME/IP Return Codes as defined in AUTOSAR SOME/IP Protocol Specification
 /// [PRS_SOMEIP_00043]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ReturnCode {
     /// No error occurred
@@ -144,7 +147,34 @@ impl From<ReturnCode> for u8 {
     }
 }
 
+impl std::fmt::Display for ReturnCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ReturnCode::Ok => "E_OK",
+            ReturnCode::NotOk => "E_NOT_OK",
+            ReturnCode::UnknownService => "E_UNKNOWN_SERVICE",
+            ReturnCode::UnknownMethod => "E_UNKNOWN_METHOD",
+            ReturnCode::NotReady => "E_NOT_READY",
+            ReturnCode::NotReachable => "E_NOT_REACHABLE",
+            ReturnCode::Timeout => "E_TIMEOUT",
+            ReturnCode::WrongProtocolVersion => "E_WRONG_PROTOCOL_VERSION",
+            ReturnCode::WrongInterfaceVersion => "E_WRONG_INTERFACE_VERSION",
+            ReturnCode::MalformedMessage => "E_MALFORMED_MESSAGE",
+            ReturnCode::WrongMessageType => "E_WRONG_MESSAGE_TYPE",
+            ReturnCode::E2eRepeated => "E_E2E_REPEATED",
+            ReturnCode::E2eWrongSequence => "E_E2E_WRONG_SEQUENCE",
+            ReturnCode::E2eNotAvailable => "E_E2E_NOT_AVAILABLE",
+            ReturnCode::E2eNoNewData => "E_E2E_NO_NEW_DATA",
+        };
+        write!(f, "{} (0x{:02x})", name, *self as u8)
+    }
+}
+
+/// A provider's ERROR response carries its return code as the error.
+impl std::error::Error for ReturnCode {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 pub struct SomeIpHeader {
     /// [PRS_SOMEIP_00032] Service ID (16-bit)
     pub service_id: u16,
@@ -198,6 +228,45 @@ impl SomeIpHeader {
         header
     }
     
+    /// [PRS_SOMEIP_00245] Method IDs with the highest bit set identify events/notifiers
+    pub const EVENT_ID_FLAG: u16 = 0x8000;
+
+    /// Whether `method_id` lies in the event range (0x8000-0xFFFF)
+    pub fn is_event_id(method_id: u16) -> bool {
+        method_id & Self::EVENT_ID_FLAG != 0
+    }
+
+    /// [PRS_SOMEIP_00160] Magic Cookie sent on TCP connections so the receiver can find
+    /// message boundaries again after a framing error; `server` selects the server variant.
+    pub fn magic_cookie(server: bool) -> Self {
+        SomeIpHeader {
+            service_id: 0xFFFF,
+            method_id: if server { 0x8000 } else { 0x0000 },
+            length: 8,
+            client_id: 0xDEAD,
+            session_id: 0xBEEF,
+            protocol_version: Self::SOMEIP_PROTOCOL_VERSION,
+            interface_version: 0x01,
+            message_type: if server { 0x02 } else { 0x01 },
+            return_code: 0x00,
+        }
+    }
+
+    /// Whether this is the client or server Magic Cookie
+    pub fn is_magic_cookie(&self) -> bool {
+        *self == Self::magic_cookie(false) || *self == Self::magic_cookie(true)
+    }
+
+    /// Client ID and Session ID as one value
+    pub fn request_id(&self) -> RequestId {
+        RequestId::new(self.client_id, self.session_id)
+    }
+
+    pub fn set_request_id(&mut self, id: RequestId) {
+        self.client_id = id.client_id();
+        self.session_id = id.session_id();
+    }
+
     /// Get the message type as an enum
     pub fn message_type_enum(&self) -> Option<MessageType> {
         MessageType::from_u8(self.message_type)
@@ -267,7 +336,7 @@ impl SomeIpHeader {
             0x81 => "ERR",
             _ => "UNKNOWN",
         };
-        log::debug!(target: "DUMP", "\n[DUMP] --- SOME/IP Message from {} ---", addr);
+        log::debug!(target: "DUMP", "\n[DUMP] [{}] --- SOME/IP Message from {} ---", crate::clock::Timestamp::now(), addr);
         log::debug!(target: "DUMP", "  [Header] Service:0x{:04X} Method:0x{:04X} Len:{} Client:0x{:04X} Session:0x{:04X}",
             self.service_id, self.method_id, self.length, self.client_id, self.session_id);
         log::debug!(target: "DUMP", "  [Header] Proto:v{} Iface:v{} Type:{} Return:0x{:02X}",
@@ -0,0 +1,167 @@
+//! # Struct Layout
+//!
+//! Helpers behind `#[derive(SomeIpSerialize, SomeIpDeserialize)]` and generated
+//! codec impls for layouts other than members back to back:
+//!
+//! - [`write_framed`] / [`read_framed`] - a struct preceded by a length field;
+//!   a receiver skips members it does not know
+//! - [`write_resized`] / [`read_resized`] - a `Vec`, `String` or map with a 1- or
+//!   2-byte length field instead of the default 4 bytes
+//! - [`Counted`] with [`write_padding`] / [`skip_padding`] - zero padding so a
+//!   member starts at a multiple of its alignment, counted from the start of the
+//!   struct
+//!
+//! Widths and alignments are in bytes.
+
+use super::repr;
+use super::traits::{SomeIpDeserialize, SomeIpSerialize};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+/// Reader or writer tracking how many bytes went through it
+pub struct Counted<T> {
+    inner: T,
+    offset: usize,
+}
+
+impl<T> Counted<T> {
+    pub fn new(inner: T) -> Self {
+        Counted { inner, offset: 0 }
+    }
+
+    /// Bytes read or written so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+fn padding(offset: usize, align: usize) -> Result<usize> {
+    match align {
+        1 | 2 | 4 | 8 => Ok((align - offset % align) % align),
+        _ => Err(Error::new(ErrorKind::InvalidInput, format!("unsupported alignment: {} bytes", align))),
+    }
+}
+
+/// Write zero bytes up to the next multiple of `align`.
+pub fn write_padding<W: Write>(writer: &mut Counted<W>, align: usize) -> Result<()> {
+    let pad = padding(writer.offset, align)?;
+    writer.write_all(&[0u8; 8][..pad])
+}
+
+/// Skip the padding [`write_padding`] wrote.
+pub fn skip_padding<R: Read>(reader: &mut Counted<R>, align: usize) -> Result<()> {
+    let pad = padding(reader.offset, align)?;
+    reader.read_exact(&mut [0u8; 8][..pad])
+}
+
+/// Write what `body` produces, preceded by its length as a `width`-byte field.
+pub fn write_framed<W: Write>(width: usize, writer: &mut W, body: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> Result<()> {
+    let mut buf = Vec::new();
+    body(&mut buf)?;
+    repr::write_uint(buf.len() as u64, width, writer)?;
+    writer.write_all(&buf)
+}
+
+/// Read a `width`-byte length field and pass that many bytes to `body`. Bytes
+/// `body` leaves unread are skipped.
+pub fn read_framed<R: Read, T>(width: usize, reader: &mut R, body: impl FnOnce(&mut &[u8]) -> Result<T>) -> Result<T> {
+    let len = repr::read_uint(width, reader)? as usize;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    body(&mut &buf[..])
+}
+
+/// Write a value whose default encoding has a 4-byte length prefix (`Vec`,
+/// `String`, maps) with a `width`-byte length field instead.
+pub fn write_resized<T: SomeIpSerialize + ?Sized, W: Write>(value: &T, width: usize, writer: &mut W) -> Result<()> {
+    let mut buf = Vec::new();
+    value.serialize(&mut buf)?;
+    let prefixed = buf.len() >= 4 && u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize == buf.len() - 4;
+    if !prefixed {
+        return Err(Error::new(ErrorKind::InvalidInput, "value is not length-prefixed"));
+    }
+    repr::write_uint((buf.len() - 4) as u64, width, writer)?;
+    writer.write_all(&buf[4..])
+}
+
+/// Read a value written by [`write_resized`].
+pub fn read_resized<T: SomeIpDeserialize, R: Read>(width: usize, reader: &mut R) -> Result<T> {
+    let len = repr::read_uint(width, reader)?;
+    let len = u32::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidData, "length field too large"))?;
+    let mut buf = vec![0u8; 4 + len as usize];
+    buf[..4].copy_from_slice(&len.to_be_bytes());
+    reader.read_exact(&mut buf[4..])?;
+    T::deserialize(&mut &buf[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padding() {
+        let mut writer = Counted::new(Vec::new());
+        1u8.serialize(&mut writer).unwrap();
+        write_padding(&mut writer, 4).unwrap();
+        2u16.serialize(&mut writer).unwrap();
+        write_padding(&mut writer, 2).unwrap();
+        assert_eq!(writer.offset(), 6);
+        assert_eq!(writer.inner, vec![1, 0, 0, 0, 0, 2]);
+
+        let mut reader = Counted::new(&writer.inner[..]);
+        assert_eq!(u8::deserialize(&mut reader).unwrap(), 1);
+        skip_padding(&mut reader, 4).unwrap();
+        assert_eq!(u16::deserialize(&mut reader).unwrap(), 2);
+        assert!(write_padding(&mut writer, 3).is_err());
+    }
+
+    #[test]
+    fn test_framed_skips_unknown_bytes() {
+        let mut buf = Vec::new();
+        write_framed(2, &mut buf, |w| {
+            7u16.serialize(w)?;
+            9u8.serialize(w)
+        }).unwrap();
+        buf.push(0xAA);
+        assert_eq!(buf, vec![0, 3, 0, 7, 9, 0xAA]);
+
+        // A receiver knowing only the first member
+        let mut reader = &buf[..];
+        assert_eq!(read_framed(2, &mut reader, |r| u16::deserialize(r)).unwrap(), 7);
+        assert_eq!(reader, [0xAA]);
+    }
+
+    #[test]
+    fn test_resized_length_fields() {
+        let mut buf = Vec::new();
+        write_resized(&vec![1u16, 2], 1, &mut buf).unwrap();
+        write_resized(&"hi".to_string(), 2, &mut buf).unwrap();
+        assert_eq!(buf, vec![4, 0, 1, 0, 2, 0, 2, b'h', b'i']);
+
+        let mut reader = &buf[..];
+        assert_eq!(read_resized::<Vec<u16>, _>(1, &mut reader).unwrap(), vec![1, 2]);
+        assert_eq!(read_resized::<String, _>(2, &mut reader).unwrap(), "hi");
+
+        assert!(write_resized(&vec![0u8; 256], 1, &mut Vec::new()).is_err());
+        assert!(write_resized(&5u32, 1, &mut Vec::new()).is_err());
+    }
+}
@@ -6,9 +6,16 @@
 //!
 //! - [`SomeIpHeader`] - 16-byte SOME/IP header with message metadata
 //! - [`SomeIpSerialize`] / [`SomeIpDeserialize`] - Traits for payload encoding
+//! - [`SomeIpDeserializeRef`] / [`SeqRef`] - Decoding into views of the received bytes
 //! - [`MessageType`] - Request, Response, Notification, Error types
 //! - [`ReturnCode`] - Standard AUTOSAR return codes
 //! - [`SessionIdManager`] - Thread-safe session ID generation
+//! - [`RequestId`] - Client ID and Session ID as one 32-bit value
+//! - [`SchemaRegistry`] - Payload schema fingerprints for diagnostic mode
+//! - [`repr`] - Configurable bool/enum wire widths and bit fields
+//! - [`layout`] - Struct length fields, container length widths and padding,
+//!   used by `#[derive(SomeIpSerialize, SomeIpDeserialize)]`
+//! - [`tlv`] - Tag-length-value struct members for extensible structs
 //!
 //! ## Example
 //!
@@ -23,12 +30,22 @@ pub mod header;
 pub mod traits;
 pub mod primitives;
 pub mod complex;
+pub mod borrowed;
 pub mod session;
+pub mod request_id;
 pub mod tp;
+pub mod schema;
+pub mod repr;
+pub mod layout;
+pub mod tlv;
 
 pub use header::*;
-pub use traits::{SomeIpSerialize, SomeIpDeserialize};
+pub use traits::{SomeIpSerialize, SomeIpDeserialize, SomeIpDeserializeRef};
+pub use fusion_hawking_derive::{SomeIpSerialize, SomeIpDeserialize, SomeIpDeserializeRef};
+pub use borrowed::{SeqRef, SeqIter};
 pub use header::{MessageType, ReturnCode};
 pub use session::SessionIdManager;
+pub use request_id::RequestId;
+pub use schema::{SomeIpSchema, SchemaRegistry, TypeSchema};
 
 mod tests;
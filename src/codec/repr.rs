@@ -0,0 +1,286 @@
+//! # Wire Representation Options
+//!
+//! Peers disagree on how some values look on the wire: booleans may be a
+//! single byte, a wider integer, or one bit of a bit field; enumerations may
+//! be 8, 16 or 32 bits wide; strings may carry a byte order mark and a
+//! terminating zero, be UTF-16, or have a fixed size. The default
+//! [`SomeIpSerialize`] impls encode a `bool` as one byte and a `String` as a
+//! length field and its UTF-8 bytes; the helpers here implement the
+//! alternatives and are called by generated code for types whose IDL selects
+//! them.
+//!
+//! All widths are in bytes (1, 2, 4 or 8) and values are big-endian.
+//!
+//! [`SomeIpSerialize`]: super::SomeIpSerialize
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+fn check_width(width: usize) -> Result<()> {
+    match width {
+        1 | 2 | 4 | 8 => Ok(()),
+        _ => Err(Error::new(ErrorKind::InvalidInput, format!("unsupported wire width: {} bytes", width))),
+    }
+}
+
+/// Write `value` as a big-endian unsigned integer of `width` bytes.
+pub fn write_uint<W: Write>(value: u64, width: usize, writer: &mut W) -> Result<()> {
+    check_width(width)?;
+    if width < 8 && value >> (width * 8) != 0 {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("value {} does not fit in {} bytes", value, width)));
+    }
+    writer.write_all(&value.to_be_bytes()[8 - width..])
+}
+
+/// Read a big-endian unsigned integer of `width` bytes.
+pub fn read_uint<R: Read>(width: usize, reader: &mut R) -> Result<u64> {
+    check_width(width)?;
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf[8 - width..])?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+/// Write a boolean as a `width`-byte integer (0 or 1).
+pub fn write_bool<W: Write>(value: bool, width: usize, writer: &mut W) -> Result<()> {
+    write_uint(value as u64, width, writer)
+}
+
+/// Read a boolean stored as a `width`-byte integer; any non-zero value is `true`.
+pub fn read_bool<R: Read>(width: usize, reader: &mut R) -> Result<bool> {
+    Ok(read_uint(width, reader)? != 0)
+}
+
+/// Pack booleans into one `width`-byte bit field, the first into the least significant bit.
+pub fn write_bits<W: Write>(bits: &[bool], width: usize, writer: &mut W) -> Result<()> {
+    check_width(width)?;
+    if bits.len() > width * 8 {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("{} flags do not fit in a {}-byte bit field", bits.len(), width)));
+    }
+    let value = bits.iter().enumerate().fold(0u64, |acc, (i, &b)| acc | ((b as u64) << i));
+    write_uint(value, width, writer)
+}
+
+/// Unpack `count` booleans from a `width`-byte bit field written by [`write_bits`].
+pub fn read_bits<R: Read>(count: usize, width: usize, reader: &mut R) -> Result<Vec<bool>> {
+    check_width(width)?;
+    if count > width * 8 {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("{} flags do not fit in a {}-byte bit field", count, width)));
+    }
+    let value = read_uint(width, reader)?;
+    Ok((0..count).map(|i| value >> i & 1 == 1).collect())
+}
+
+/// Character encoding of a string on the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringEncoding {
+    /// UTF-8 bytes only, as the `String` codec writes them
+    #[default]
+    Raw,
+    /// Byte order mark EF BB BF, UTF-8 bytes, one zero byte [PRS_SOMEIP_00084]
+    Utf8,
+    /// Byte order mark FF FE, UTF-16LE code units, two zero bytes
+    Utf16Le,
+    /// Byte order mark FE FF, UTF-16BE code units, two zero bytes
+    Utf16Be,
+}
+
+/// How a string is encoded: its character encoding, and either the width of
+/// its length field or, for fixed-length strings, its size in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StringFormat {
+    pub encoding: StringEncoding,
+    /// Zero padded to this many bytes, byte order mark and terminator included
+    pub fixed_length: Option<usize>,
+    /// Bytes of the length field of dynamic-length strings (1, 2 or 4)
+    pub length_width: usize,
+}
+
+impl Default for StringFormat {
+    fn default() -> Self {
+        StringFormat::new(StringEncoding::Raw)
+    }
+}
+
+impl StringFormat {
+    pub const fn new(encoding: StringEncoding) -> Self {
+        StringFormat { encoding, fixed_length: None, length_width: 4 }
+    }
+
+    pub const fn fixed(encoding: StringEncoding, length: usize) -> Self {
+        StringFormat { encoding, fixed_length: Some(length), length_width: 4 }
+    }
+
+    pub const fn with_length_width(self, length_width: usize) -> Self {
+        StringFormat { length_width, ..self }
+    }
+}
+
+fn encode_string(value: &str, encoding: StringEncoding) -> Vec<u8> {
+    match encoding {
+        StringEncoding::Raw => value.as_bytes().to_vec(),
+        StringEncoding::Utf8 => [&[0xEF, 0xBB, 0xBF], value.as_bytes(), &[0]].concat(),
+        StringEncoding::Utf16Le => {
+            let mut out = vec![0xFF, 0xFE];
+            out.extend(value.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+            out
+        }
+        StringEncoding::Utf16Be => {
+            let mut out = vec![0xFE, 0xFF];
+            out.extend(value.encode_utf16().chain([0]).flat_map(u16::to_be_bytes));
+            out
+        }
+    }
+}
+
+/// Decode up to the terminator; a byte order mark, if present, overrides the
+/// configured UTF-16 byte order
+fn decode_string(bytes: &[u8], encoding: StringEncoding, terminated: bool) -> Result<String> {
+    let invalid = |what: &str| Error::new(ErrorKind::InvalidData, format!("Invalid {}", what));
+    match encoding {
+        StringEncoding::Raw | StringEncoding::Utf8 => {
+            let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).filter(|_| encoding == StringEncoding::Utf8).unwrap_or(bytes);
+            let end = if terminated { bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len()) } else { bytes.len() };
+            String::from_utf8(bytes[..end].to_vec()).map_err(|_| invalid("UTF-8"))
+        }
+        StringEncoding::Utf16Le | StringEncoding::Utf16Be => {
+            let (little, bytes) = match bytes {
+                [0xFF, 0xFE, rest @ ..] => (true, rest),
+                [0xFE, 0xFF, rest @ ..] => (false, rest),
+                _ => (encoding == StringEncoding::Utf16Le, bytes),
+            };
+            if bytes.len() % 2 != 0 {
+                return Err(invalid("UTF-16 length"));
+            }
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| if little { u16::from_le_bytes([c[0], c[1]]) } else { u16::from_be_bytes([c[0], c[1]]) })
+                .take_while(|&u| u != 0)
+                .collect();
+            String::from_utf16(&units).map_err(|_| invalid("UTF-16"))
+        }
+    }
+}
+
+/// Write a string in `format`: a length field and the encoded bytes, or the
+/// encoded bytes zero padded to the fixed length.
+pub fn write_string<W: Write>(value: &str, format: &StringFormat, writer: &mut W) -> Result<()> {
+    let bytes = encode_string(value, format.encoding);
+    match format.fixed_length {
+        Some(length) => {
+            if bytes.len() > length {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("string of {} bytes does not fit in {} bytes", bytes.len(), length)));
+            }
+            writer.write_all(&bytes)?;
+            writer.write_all(&vec![0u8; length - bytes.len()])
+        }
+        None => {
+            write_uint(bytes.len() as u64, format.length_width, writer)?;
+            writer.write_all(&bytes)
+        }
+    }
+}
+
+/// Read a string written by [`write_string`] with the same format.
+pub fn read_string<R: Read>(format: &StringFormat, reader: &mut R) -> Result<String> {
+    let length = match format.fixed_length {
+        Some(length) => length,
+        None => read_uint(format.length_width, reader)? as usize,
+    };
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    // Raw dynamic strings keep any zero bytes, like the String codec
+    let terminated = format.encoding != StringEncoding::Raw || format.fixed_length.is_some();
+    decode_string(&bytes, format.encoding, terminated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uint_widths() {
+        let mut buf = Vec::new();
+        write_uint(0x0102, 2, &mut buf).unwrap();
+        write_uint(7, 4, &mut buf).unwrap();
+        assert_eq!(buf, vec![0x01, 0x02, 0, 0, 0, 7]);
+
+        let mut reader = &buf[..];
+        assert_eq!(read_uint(2, &mut reader).unwrap(), 0x0102);
+        assert_eq!(read_uint(4, &mut reader).unwrap(), 7);
+
+        assert!(write_uint(0x100, 1, &mut Vec::new()).is_err());
+        assert!(write_uint(1, 3, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_wide_bool() {
+        let mut buf = Vec::new();
+        write_bool(true, 4, &mut buf).unwrap();
+        assert_eq!(buf, vec![0, 0, 0, 1]);
+        assert!(read_bool(4, &mut &buf[..]).unwrap());
+        assert!(!read_bool(2, &mut &[0u8, 0][..]).unwrap());
+    }
+
+    #[test]
+    fn test_bit_field_roundtrip() {
+        let flags = [true, false, true, true];
+        let mut buf = Vec::new();
+        write_bits(&flags, 1, &mut buf).unwrap();
+        assert_eq!(buf, vec![0b0000_1101]);
+        assert_eq!(read_bits(4, 1, &mut &buf[..]).unwrap(), flags);
+
+        assert!(write_bits(&[false; 9], 1, &mut Vec::new()).is_err());
+        let mut buf = Vec::new();
+        write_bits(&[false; 9], 2, &mut buf).unwrap();
+        assert_eq!(buf.len(), 2);
+    }
+
+    #[test]
+    fn test_string_encodings() {
+        let cases: [(StringEncoding, &[u8]); 3] = [
+            (StringEncoding::Utf8, &[0, 0, 0, 7, 0xEF, 0xBB, 0xBF, b'h', 0xC3, 0xA9, 0]),
+            (StringEncoding::Utf16Le, &[0, 0, 0, 8, 0xFF, 0xFE, b'h', 0, 0xE9, 0, 0, 0]),
+            (StringEncoding::Utf16Be, &[0, 0, 0, 8, 0xFE, 0xFF, 0, b'h', 0, 0xE9, 0, 0]),
+        ];
+        for (encoding, expected) in cases {
+            let mut buf = Vec::new();
+            write_string("h\u{e9}", &StringFormat::new(encoding), &mut buf).unwrap();
+            assert_eq!(buf, expected, "{:?}", encoding);
+            assert_eq!(read_string(&StringFormat::new(encoding), &mut &buf[..]).unwrap(), "h\u{e9}");
+        }
+
+        // Raw matches the String codec
+        let mut buf = Vec::new();
+        write_string("hi", &StringFormat::default(), &mut buf).unwrap();
+        assert_eq!(buf, vec![0, 0, 0, 2, b'h', b'i']);
+
+        // One-byte length field
+        let short = StringFormat::new(StringEncoding::Utf8).with_length_width(1);
+        let mut buf = Vec::new();
+        write_string("a", &short, &mut buf).unwrap();
+        assert_eq!(buf, vec![5, 0xEF, 0xBB, 0xBF, b'a', 0]);
+        assert_eq!(read_string(&short, &mut &buf[..]).unwrap(), "a");
+        assert!(write_string(&"x".repeat(300), &short, &mut Vec::new()).is_err());
+
+        // The byte order mark wins over the configured byte order
+        let le = [0, 0, 0, 6, 0xFF, 0xFE, b'a', 0, 0, 0];
+        assert_eq!(read_string(&StringFormat::new(StringEncoding::Utf16Be), &mut &le[..]).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_fixed_length_strings() {
+        let format = StringFormat::fixed(StringEncoding::Utf8, 8);
+        let mut buf = Vec::new();
+        write_string("ab", &format, &mut buf).unwrap();
+        assert_eq!(buf, vec![0xEF, 0xBB, 0xBF, b'a', b'b', 0, 0, 0]);
+        assert_eq!(read_string(&format, &mut &buf[..]).unwrap(), "ab");
+
+        assert!(write_string("abcde", &format, &mut Vec::new()).is_err());
+        let raw = StringFormat::fixed(StringEncoding::Raw, 4);
+        let mut buf = Vec::new();
+        write_string("abcd", &raw, &mut buf).unwrap();
+        write_string("x", &raw, &mut buf).unwrap();
+        let mut reader = &buf[..];
+        assert_eq!(read_string(&raw, &mut reader).unwrap(), "abcd");
+        assert_eq!(read_string(&raw, &mut reader).unwrap(), "x");
+    }
+}
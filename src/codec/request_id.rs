@@ -0,0 +1,97 @@
+//! # Request IDs
+//!
+//! [`RequestId`] is the 32-bit Request ID of a SOME/IP message: Client ID in
+//! the upper 16 bits, Session ID in the lower 16 bits [PRS_SOMEIP_00038].
+//! The runtime correlates responses by (service, method, request ID), and
+//! FFI consumers receive the same `u32` through [`crate::ffi`].
+
+use std::fmt;
+
+/// Client ID and Session ID of a message as one value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestId(u32);
+
+impl RequestId {
+    pub const fn new(client_id: u16, session_id: u16) -> Self {
+        RequestId((client_id as u32) << 16 | session_id as u32)
+    }
+
+    pub const fn from_raw(raw: u32) -> Self {
+        RequestId(raw)
+    }
+
+    pub const fn raw(self) -> u32 {
+        self.0
+    }
+
+    pub const fn client_id(self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+
+    pub const fn session_id(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// First request ID of `client_id` (Session ID 1).
+    pub const fn first(client_id: u16) -> Self {
+        Self::new(client_id, 1)
+    }
+
+    /// The following request ID of the same client. The Session ID wraps from
+    /// 0xFFFF to 1, since 0 means session handling is inactive.
+    pub const fn next(self) -> Self {
+        let session = if self.session_id() == 0xFFFF { 1 } else { self.session_id() + 1 };
+        Self::new(self.client_id(), session)
+    }
+
+    /// Decode the Request ID from bytes 8..12 of a serialized SOME/IP header.
+    pub fn parse(header: &[u8]) -> Option<Self> {
+        let bytes = header.get(8..12)?;
+        Some(RequestId(u32::from_be_bytes(bytes.try_into().ok()?)))
+    }
+}
+
+impl From<u32> for RequestId {
+    fn from(raw: u32) -> Self {
+        RequestId(raw)
+    }
+}
+
+impl From<RequestId> for u32 {
+    fn from(id: RequestId) -> u32 {
+        id.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04x}:{:04x}", self.client_id(), self.session_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+
+    #[test]
+    fn test_request_id_layout() {
+        let id = RequestId::new(0x1234, 0x5678);
+        assert_eq!(id.raw(), 0x1234_5678);
+        assert_eq!((id.client_id(), id.session_id()), (0x1234, 0x5678));
+        assert_eq!(RequestId::from(0x1234_5678), id);
+        assert_eq!(id.to_string(), "0x1234:5678");
+
+        let header = SomeIpHeader::new(0x1001, 0x0001, 0x1234, 0x5678, 0x00, 0);
+        assert_eq!(RequestId::parse(&header.serialize()), Some(id));
+        assert_eq!(header.request_id(), id);
+        assert_eq!(RequestId::parse(&[0u8; 11]), None);
+    }
+
+    #[test]
+    fn test_request_id_next_skips_zero() {
+        assert_eq!(RequestId::first(7).next(), RequestId::new(7, 2));
+        assert_eq!(RequestId::new(7, 0xFFFF).next(), RequestId::new(7, 1));
+    }
+}
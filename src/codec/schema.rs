@@ -0,0 +1,221 @@
+//! Payload schema fingerprints for diagnosing mismatched generated code between peers.
+//!
+//! Generated types describe their layout via [`SomeIpSchema`]. In diagnostic mode the
+//! runtime appends a trailer with the fingerprint of every struct in the payload
+//! (depth-first) and validates it on receipt against the [`SchemaRegistry`], so a
+//! mismatch names the exact struct that diverged instead of decoding garbage.
+//!
+//! Trailer layout (appended after the payload):
+//! `fingerprint[0..n] (u32 BE each) | n (u8) | "FHS1"`
+
+use std::collections::HashMap;
+use std::fmt;
+
+const TRAILER_MAGIC: &[u8; 4] = b"FHS1";
+
+/// Layout description of a generated struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeSchema {
+    pub name: &'static str,
+    /// Canonical field list, e.g. `"id:u16,pos:Position,active:bool"`
+    pub fields: &'static str,
+    /// Schemas of struct-typed fields, in field order
+    pub nested: Vec<TypeSchema>,
+}
+
+impl TypeSchema {
+    /// FNV-1a hash over `name{fields}`. Nested structs are fingerprinted separately.
+    pub fn fingerprint(&self) -> u32 {
+        let mut hash: u32 = 0x811c9dc5;
+        for b in self.name.bytes().chain(b"{".iter().copied()).chain(self.fields.bytes()).chain(b"}".iter().copied()) {
+            hash ^= b as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+        hash
+    }
+
+    /// This struct and all nested structs, depth-first, each listed once.
+    pub fn components(&self) -> Vec<&TypeSchema> {
+        let mut out = Vec::new();
+        self.collect(&mut out);
+        out
+    }
+
+    fn collect<'a>(&'a self, out: &mut Vec<&'a TypeSchema>) {
+        if out.iter().any(|s| s.name == self.name) {
+            return;
+        }
+        out.push(self);
+        for n in &self.nested {
+            n.collect(out);
+        }
+    }
+}
+
+/// Implemented by generated types to expose their layout.
+pub trait SomeIpSchema {
+    fn schema() -> TypeSchema;
+}
+
+/// A fingerprint check that failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaMismatch {
+    pub service_id: u16,
+    pub method_id: u16,
+    pub response: bool,
+    /// Local struct whose fingerprint differs from the peer's
+    pub struct_name: &'static str,
+    /// Local layout of that struct
+    pub local_layout: String,
+    pub local_fingerprint: u32,
+    pub remote_fingerprint: Option<u32>,
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.response { "response" } else { "request/event" };
+        write!(f, "Schema mismatch on 0x{:04x}.0x{:04x} ({}): struct '{}' differs (local 0x{:08x}, remote ",
+            self.service_id, self.method_id, kind, self.struct_name, self.local_fingerprint)?;
+        match self.remote_fingerprint {
+            Some(fp) => write!(f, "0x{:08x}", fp)?,
+            None => write!(f, "missing")?,
+        }
+        write!(f, "); local layout: {}", self.local_layout)
+    }
+}
+
+/// Append the fingerprint trailer for `schema` to `payload`.
+pub fn append_trailer(payload: &mut Vec<u8>, schema: &TypeSchema) {
+    let components = schema.components();
+    for c in &components {
+        payload.extend_from_slice(&c.fingerprint().to_be_bytes());
+    }
+    payload.push(components.len() as u8);
+    payload.extend_from_slice(TRAILER_MAGIC);
+}
+
+/// Split a fingerprint trailer off `payload`, if it carries one.
+pub fn strip_trailer(payload: &[u8]) -> (&[u8], Option<Vec<u32>>) {
+    if payload.len() < 5 || &payload[payload.len() - 4..] != TRAILER_MAGIC {
+        return (payload, None);
+    }
+    let count = payload[payload.len() - 5] as usize;
+    let trailer_len = 5 + count * 4;
+    if payload.len() < trailer_len {
+        return (payload, None);
+    }
+    let body_len = payload.len() - trailer_len;
+    let fingerprints = payload[body_len..body_len + count * 4]
+        .chunks_exact(4)
+        .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    (&payload[..body_len], Some(fingerprints))
+}
+
+/// Schemas of the payload types exchanged per (service, method/event, direction).
+#[derive(Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<(u16, u16, bool), TypeSchema>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the payload type of a request/event (`response == false`) or response.
+    pub fn register(&mut self, service_id: u16, method_id: u16, response: bool, schema: TypeSchema) {
+        self.schemas.insert((service_id, method_id, response), schema);
+    }
+
+    pub fn get(&self, service_id: u16, method_id: u16, response: bool) -> Option<&TypeSchema> {
+        self.schemas.get(&(service_id, method_id, response))
+    }
+
+    /// Validate the fingerprints received from a peer. Unregistered messages always pass.
+    pub fn check(&self, service_id: u16, method_id: u16, response: bool, remote: &[u32]) -> Result<(), SchemaMismatch> {
+        let Some(schema) = self.get(service_id, method_id, response) else {
+            return Ok(());
+        };
+        let components = schema.components();
+        for (i, local) in components.iter().enumerate() {
+            let remote_fp = remote.get(i).copied();
+            if remote_fp != Some(local.fingerprint()) {
+                return Err(SchemaMismatch {
+                    service_id,
+                    method_id,
+                    response,
+                    struct_name: local.name,
+                    local_layout: format!("{}{{{}}}", local.name, local.fields),
+                    local_fingerprint: local.fingerprint(),
+                    remote_fingerprint: remote_fp,
+                });
+            }
+        }
+        if remote.len() != components.len() {
+            // Peer has extra nested structs: the top-level layout must differ somewhere
+            return Err(SchemaMismatch {
+                service_id,
+                method_id,
+                response,
+                struct_name: schema.name,
+                local_layout: format!("{}{{{}}}", schema.name, schema.fields),
+                local_fingerprint: schema.fingerprint(),
+                remote_fingerprint: remote.first().copied(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fields: &'static str) -> TypeSchema {
+        TypeSchema { name: "Position", fields, nested: vec![] }
+    }
+
+    fn device(pos: TypeSchema) -> TypeSchema {
+        TypeSchema { name: "DeviceStatus", fields: "id:u16,pos:Position,active:bool", nested: vec![pos] }
+    }
+
+    #[test]
+    fn test_trailer_roundtrip() {
+        let schema = device(position("x:i32,y:i32"));
+        let mut payload = vec![1, 2, 3];
+        append_trailer(&mut payload, &schema);
+
+        let (body, fps) = strip_trailer(&payload);
+        assert_eq!(body, &[1, 2, 3]);
+        let fps = fps.unwrap();
+        assert_eq!(fps, vec![schema.fingerprint(), schema.nested[0].fingerprint()]);
+    }
+
+    #[test]
+    fn test_payload_without_trailer_untouched() {
+        let payload = [0u8, 0, 0, 5];
+        let (body, fps) = strip_trailer(&payload);
+        assert_eq!(body, &payload);
+        assert!(fps.is_none());
+    }
+
+    #[test]
+    fn test_mismatch_names_nested_struct() {
+        let mut registry = SchemaRegistry::new();
+        registry.register(0x1234, 0x0001, false, device(position("x:i32,y:i32")));
+
+        // Peer's generated Position has an extra field
+        let remote = device(position("x:i32,y:i32,z:i32"));
+        let remote_fps: Vec<u32> = remote.components().iter().map(|c| c.fingerprint()).collect();
+
+        let err = registry.check(0x1234, 0x0001, false, &remote_fps).unwrap_err();
+        assert_eq!(err.struct_name, "Position");
+        assert!(err.to_string().contains("Position{x:i32,y:i32}"));
+
+        let ok: Vec<u32> = device(position("x:i32,y:i32")).components().iter().map(|c| c.fingerprint()).collect();
+        assert!(registry.check(0x1234, 0x0001, false, &ok).is_ok());
+        // Unregistered messages are not checked
+        assert!(registry.check(0x1234, 0x0002, false, &remote_fps).is_ok());
+    }
+}
@@ -16,6 +16,16 @@ mod tests {
         assert_eq!(bytes[7], 108); // 100 + 8
     }
 
+    #[test]
+    fn test_magic_cookies() {
+        assert_eq!(SomeIpHeader::magic_cookie(false).serialize(), [0xFF, 0xFF, 0x00, 0x00, 0, 0, 0, 8, 0xDE, 0xAD, 0xBE, 0xEF, 1, 1, 0x01, 0]);
+        assert_eq!(SomeIpHeader::magic_cookie(true).serialize(), [0xFF, 0xFF, 0x80, 0x00, 0, 0, 0, 8, 0xDE, 0xAD, 0xBE, 0xEF, 1, 1, 0x02, 0]);
+        assert!(SomeIpHeader::magic_cookie(true).is_magic_cookie());
+        let mut header = SomeIpHeader::magic_cookie(false);
+        header.session_id = 0xBEEE;
+        assert!(!header.is_magic_cookie());
+    }
+
     #[test]
     fn test_primitive_serialization() {
         let val: u32 = 0xDEADBEEF;
@@ -183,7 +193,66 @@ mod tests {
         let decoded = String::deserialize(&mut reader).unwrap();
         assert_eq!(decoded, "");
     }
-    
+
+    #[test]
+    fn test_array_serialization() {
+        let val: [u16; 3] = [1, 2, 0xABCD];
+        let mut buf = Vec::new();
+        val.serialize(&mut buf).unwrap();
+
+        // Fixed arrays have no length field
+        assert_eq!(buf, vec![0, 1, 0, 2, 0xAB, 0xCD]);
+
+        let mut reader = Cursor::new(&buf);
+        assert_eq!(<[u16; 3]>::deserialize(&mut reader).unwrap(), val);
+        assert!(<[u16; 4]>::deserialize(&mut Cursor::new(&buf)).is_err());
+
+        let nested = [vec![1u8], vec![]];
+        let mut buf = Vec::new();
+        nested.serialize(&mut buf).unwrap();
+        assert_eq!(buf, vec![0, 0, 0, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(<[Vec<u8>; 2]>::deserialize(&mut Cursor::new(&buf)).unwrap(), nested);
+    }
+
+    #[test]
+    fn test_option_serialization() {
+        let mut buf = Vec::new();
+        Some(0x1234u16).serialize(&mut buf).unwrap();
+        None::<u16>.serialize(&mut buf).unwrap();
+
+        // Presence bit field, then the value only if present
+        assert_eq!(buf, vec![0x01, 0x12, 0x34, 0x00]);
+
+        let mut reader = Cursor::new(&buf);
+        assert_eq!(Option::<u16>::deserialize(&mut reader).unwrap(), Some(0x1234));
+        assert_eq!(Option::<u16>::deserialize(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_map_serialization() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let map = BTreeMap::from([(1u8, String::from("a")), (2, String::from("bc"))]);
+        let mut buf = Vec::new();
+        map.serialize(&mut buf).unwrap();
+
+        // Map format: length in bytes (4 bytes) + key/value pairs
+        assert_eq!(buf, vec![0, 0, 0, 13, 1, 0, 0, 0, 1, b'a', 2, 0, 0, 0, 2, b'b', b'c']);
+        assert_eq!(BTreeMap::<u8, String>::deserialize(&mut Cursor::new(&buf)).unwrap(), map);
+
+        let hash: HashMap<u8, String> = map.clone().into_iter().collect();
+        let mut buf = Vec::new();
+        hash.serialize(&mut buf).unwrap();
+        assert_eq!(HashMap::<u8, String>::deserialize(&mut Cursor::new(&buf)).unwrap(), hash);
+
+        let empty = vec![0, 0, 0, 0];
+        assert!(HashMap::<u8, u8>::deserialize(&mut Cursor::new(&empty)).unwrap().is_empty());
+
+        // Same key twice
+        let duplicate = vec![0, 0, 0, 4, 1, 10, 1, 11];
+        assert!(BTreeMap::<u8, u8>::deserialize(&mut Cursor::new(&duplicate)).is_err());
+    }
+
     #[test]
     fn test_boundary_values() {
         // Test i32 min/max
@@ -319,6 +388,15 @@ mod tests {
         assert_eq!(header.protocol_version, 0x01);
     }
     
+    #[test]
+    fn test_event_id_range() {
+        // [PRS_SOMEIP_00245] high bit separates events from methods
+        assert!(!SomeIpHeader::is_event_id(0x0001));
+        assert!(!SomeIpHeader::is_event_id(0x7FFF));
+        assert!(SomeIpHeader::is_event_id(0x8000));
+        assert!(SomeIpHeader::is_event_id(0x8012));
+    }
+
     #[test]
     fn test_header_interface_version() {
         // Default interface version
@@ -0,0 +1,168 @@
+//! # Tag-Length-Value Encoding
+//!
+//! Struct members in the SOME/IP TLV format: each member is preceded by a
+//! 16-bit tag holding its wire type (bits 14-12) and data ID (bits 11-0). Receivers skip members with unknown data IDs, so providers can
+//! add and drop optional members without breaking older peers.
+//!
+//! | Wire type | Member |
+//! |-----------|--------|
+//! | 0-3 | Base type of 8, 16, 32 or 64 bits |
+//! | 4 | Complex type with its own 4-byte length field (`Vec`, `String`, maps, TLV structs) |
+//! | 5-7 | Complex type preceded by a 1-, 2- or 4-byte length field |
+//!
+//! Generated code for structs with `"tlv": true` calls [`write_base`],
+//! [`write_prefixed`] or [`write_struct`] per member, and [`read_member`] in a
+//! loop until the struct's bytes are used up.
+
+use super::traits::{SomeIpDeserialize, SomeIpSerialize};
+use super::repr;
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// Largest data ID that fits in a tag
+pub const MAX_DATA_ID: u16 = 0x0FFF;
+
+/// Wire type of complex members with their own length field
+const WIRE_PREFIXED: u8 = 4;
+/// Wire type of complex members preceded by a 4-byte length field
+const WIRE_LENGTH_32: u8 = 7;
+
+fn write_tag<W: Write>(wire_type: u8, data_id: u16, writer: &mut W) -> Result<()> {
+    if data_id > MAX_DATA_ID {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("data ID {} does not fit in 12 bits", data_id)));
+    }
+    writer.write_all(&(((wire_type as u16) << 12) | data_id).to_be_bytes())
+}
+
+/// Write a base type member (integer, float, bool) as wire type 0-3.
+pub fn write_base<T: SomeIpSerialize, W: Write>(data_id: u16, value: &T, writer: &mut W) -> Result<()> {
+    let mut buf = Vec::new();
+    value.serialize(&mut buf)?;
+    let wire_type = match buf.len() {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        8 => 3,
+        n => return Err(Error::new(ErrorKind::InvalidInput, format!("{} bytes is not a base type", n))),
+    };
+    write_tag(wire_type, data_id, writer)?;
+    writer.write_all(&buf)
+}
+
+/// Write a member whose encoding starts with its own 4-byte length field as
+/// wire type 4.
+pub fn write_prefixed<T: SomeIpSerialize + ?Sized, W: Write>(data_id: u16, value: &T, writer: &mut W) -> Result<()> {
+    let mut buf = Vec::new();
+    value.serialize(&mut buf)?;
+    let prefixed = buf.len() >= 4 && u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize == buf.len() - 4;
+    if !prefixed {
+        return Err(Error::new(ErrorKind::InvalidInput, "value is not length-prefixed"));
+    }
+    write_tag(WIRE_PREFIXED, data_id, writer)?;
+    writer.write_all(&buf)
+}
+
+/// Write a complex member without a length field of its own (struct, fixed
+/// array) as wire type 7.
+pub fn write_struct<T: SomeIpSerialize, W: Write>(data_id: u16, value: &T, writer: &mut W) -> Result<()> {
+    let mut buf = Vec::new();
+    value.serialize(&mut buf)?;
+    write_tag(WIRE_LENGTH_32, data_id, writer)?;
+    writer.write_all(&(buf.len() as u32).to_be_bytes())?;
+    writer.write_all(&buf)
+}
+
+/// A member read by [`read_member`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlvMember {
+    pub data_id: u16,
+    pub wire_type: u8,
+    /// Value of a base type; content of a complex type without its length field
+    pub data: Vec<u8>,
+}
+
+impl TlvMember {
+    /// Decode a member written by [`write_base`] or [`write_struct`].
+    pub fn decode<T: SomeIpDeserialize>(&self) -> Result<T> {
+        T::deserialize(&mut &self.data[..])
+    }
+
+    /// Decode a member written by [`write_prefixed`], whatever length field
+    /// width the sender used.
+    pub fn decode_prefixed<T: SomeIpDeserialize>(&self) -> Result<T> {
+        if self.wire_type < WIRE_PREFIXED {
+            return Err(Error::new(ErrorKind::InvalidData, format!("data ID {}: expected a complex type", self.data_id)));
+        }
+        let mut buf = (self.data.len() as u32).to_be_bytes().to_vec();
+        buf.extend_from_slice(&self.data);
+        T::deserialize(&mut &buf[..])
+    }
+}
+
+/// Read the next member; wire type 4 length fields are taken as 4 bytes.
+pub fn read_member(reader: &mut &[u8]) -> Result<TlvMember> {
+    let tag = repr::read_uint(2, reader)? as u16;
+    let wire_type = ((tag >> 12) & 0x7) as u8;
+    let len = match wire_type {
+        0..=3 => 1usize << wire_type,
+        4 | 7 => repr::read_uint(4, reader)? as usize,
+        5 => repr::read_uint(1, reader)? as usize,
+        _ => repr::read_uint(2, reader)? as usize,
+    };
+    if len > reader.len() {
+        return Err(Error::new(ErrorKind::UnexpectedEof, format!("data ID {}: {} bytes announced, {} left", tag & MAX_DATA_ID, len, reader.len())));
+    }
+    let (data, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(TlvMember { data_id: tag & MAX_DATA_ID, wire_type, data: data.to_vec() })
+}
+
+/// The value of a member that must be present.
+pub fn required<T>(value: Option<T>, name: &str) -> Result<T> {
+    value.ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("missing member '{}'", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_and_wire_types() {
+        let mut buf = Vec::new();
+        write_base(1, &0x12u8, &mut buf).unwrap();
+        write_base(2, &0x0102u16, &mut buf).unwrap();
+        write_base(3, &true, &mut buf).unwrap();
+        write_prefixed(0x10, &String::from("ab"), &mut buf).unwrap();
+        write_struct(0xFFF, &[7u8, 8], &mut buf).unwrap();
+        assert_eq!(buf, vec![
+            0x00, 0x01, 0x12,
+            0x10, 0x02, 0x01, 0x02,
+            0x00, 0x03, 0x01,
+            0x40, 0x10, 0, 0, 0, 2, b'a', b'b',
+            0x7F, 0xFF, 0, 0, 0, 2, 7, 8,
+        ]);
+
+        let mut reader = &buf[..];
+        assert_eq!(read_member(&mut reader).unwrap().decode::<u8>().unwrap(), 0x12);
+        assert_eq!(read_member(&mut reader).unwrap().decode::<u16>().unwrap(), 0x0102);
+        assert!(read_member(&mut reader).unwrap().decode::<bool>().unwrap());
+        let text = read_member(&mut reader).unwrap();
+        assert_eq!((text.data_id, text.wire_type), (0x10, 4));
+        assert_eq!(text.decode_prefixed::<String>().unwrap(), "ab");
+        assert_eq!(read_member(&mut reader).unwrap().decode::<[u8; 2]>().unwrap(), [7, 8]);
+        assert!(reader.is_empty());
+
+        assert!(write_base(0x1000, &0u8, &mut Vec::new()).is_err());
+        assert!(write_base(1, &[0u8; 3], &mut Vec::new()).is_err());
+        assert!(write_prefixed(1, &[0u8; 2], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_shorter_length_fields() {
+        // Wire types 5 and 6 replace the 4-byte length field of a vector
+        let mut reader = &[0x50, 0x01, 2, 0, 9, 0x60, 0x02, 0, 1, 5][..];
+        assert_eq!(read_member(&mut reader).unwrap().decode_prefixed::<Vec<u16>>().unwrap(), vec![9]);
+        assert_eq!(read_member(&mut reader).unwrap().decode_prefixed::<Vec<u8>>().unwrap(), vec![5]);
+        assert!(read_member(&mut &[0x50, 0x01, 3, 0][..]).is_err());
+        assert!(required::<u8>(None, "x").is_err());
+    }
+}
@@ -1,4 +1,10 @@
-// use crate::codec::SomeIpHeader;
+use crate::buffer;
+use crate::codec::SomeIpHeader;
+use crate::trace;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, IoSlice};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
 /// [PRS_SOMEIP_00705] SOME/IP-TP Header (4 bytes)
 /// Located after the SOME/IP Header in TP messages.
@@ -7,6 +13,7 @@
 /// - Reserved: 3 bits
 /// - More Segments: 1 bit
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 pub struct TpHeader {
     pub offset: u32,
     pub more_segments: bool,
@@ -44,49 +51,129 @@ impl TpHeader {
 
 /// Helper to segment a payload into chunks with TP headers.
 pub fn segment_payload(payload: &[u8], max_payload_per_segment: usize) -> Vec<(TpHeader, Vec<u8>)> {
-    // max_payload_per_segment must be multiple of 16 for alignment, 
-    // but the last segment can be smaller.
-    // However, the caller usually provides the MTU-based max size.
-    // The TP offset unit is 16 bytes.
-    // So all segments except last MUST be a multiple of 16 bytes.
-    // We enforce this by rounding down max_payload_per_segment to nearest 16.
-    
-    let alignment = 16;
-    let aligned_max = (max_payload_per_segment / alignment) * alignment;
-    
-    let mut segments = Vec::new();
-    let total_len = payload.len();
+    segments(payload, max_payload_per_segment).map(|(tp, chunk)| (tp, chunk.to_vec())).collect()
+}
+
+/// [`segment_payload`] without copying: the chunks are slices of `payload`.
+pub fn segments(payload: &[u8], max_payload_per_segment: usize) -> impl Iterator<Item = (TpHeader, &[u8])> {
+    // The TP offset unit is 16 bytes, so all segments except the last
+    // MUST be a multiple of 16 bytes: round the maximum down.
+    let aligned_max = (max_payload_per_segment / 16 * 16).max(16);
     let mut cursor = 0;
+    // An empty payload still takes one (empty) segment
+    let mut first = true;
+    std::iter::from_fn(move || {
+        if cursor >= payload.len() && !std::mem::take(&mut first) {
+            return None;
+        }
+        first = false;
+        let chunk_len = (payload.len() - cursor).min(aligned_max);
+        let more = cursor + chunk_len < payload.len();
+        let segment = (TpHeader::new(cursor as u32, more), &payload[cursor..cursor + chunk_len]);
+        cursor += chunk_len;
+        Some(segment)
+    })
+}
 
-    // Special case: empty payload
-    if total_len == 0 {
-        return vec![(TpHeader::new(0, false), Vec::new())];
+/// Default largest UDP datagram sent, including the SOME/IP header (bytes)
+pub const DEFAULT_MTU: usize = 1400;
+/// SOME/IP and TP header bytes in front of each segment's payload
+const SEGMENT_OVERHEAD: usize = 16 + 4;
+/// Smallest MTU: the headers and one 16-byte segment
+pub const MIN_MTU: usize = SEGMENT_OVERHEAD + 16;
+/// Largest MTU: the most payload a UDP datagram over IPv4 carries
+pub const MAX_MTU: usize = 65507;
+/// Bit of the message type marking SOME/IP-TP segments
+const TP_FLAG: u8 = 0x20;
+
+/// First delay before retrying a datagram the send buffer had no room for
+const MIN_BACKOFF: Duration = Duration::from_micros(100);
+/// Longest delay before a retry; a datagram still refused after it fails the send
+const MAX_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Sends a SOME/IP message over a datagram transport: as is when it fits the MTU,
+/// otherwise as SOME/IP-TP segments of the TP variant of its message type
+/// (REQUEST 0x20, REQUEST_NO_RETURN 0x21, NOTIFICATION 0x22, RESPONSE 0xA0, ERROR 0xA1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpSender {
+    /// Largest datagram, including the SOME/IP header
+    pub mtu: usize,
+    /// Pause after each burst of segments
+    pub pacing: Option<Duration>,
+    /// Segments sent back to back before pausing; at least 1
+    pub burst: usize,
+    /// Retry datagrams refused with `WouldBlock` after a delay that doubles from 100 µs up
+    /// to 10 ms, and pause at least that long between the remaining bursts
+    pub adaptive: bool,
+}
+
+impl Default for TpSender {
+    fn default() -> Self {
+        TpSender { mtu: DEFAULT_MTU, pacing: None, burst: 1, adaptive: false }
     }
+}
 
-    while cursor < total_len {
-        let remaining = total_len - cursor;
-        let chunk_len = if remaining > aligned_max {
-            aligned_max
-        } else {
-            remaining
-        };
+impl TpSender {
+    pub fn new(mtu: usize, pacing: Option<Duration>) -> Self {
+        TpSender { mtu, pacing, ..Self::default() }
+    }
 
-        // If we have remaining data after this chunk, set more=true
-        let more = (cursor + chunk_len) < total_len;
-        
-        let chunk = payload[cursor..cursor+chunk_len].to_vec();
-        
-        segments.push((TpHeader::new(cursor as u32, more), chunk));
-        
-        cursor += chunk_len;
+    /// Payload bytes per segment: what fits in the MTU after the headers, in multiples of 16.
+    pub fn max_segment_payload(&self) -> usize {
+        (self.mtu.saturating_sub(SEGMENT_OVERHEAD) / 16 * 16).max(16)
+    }
+
+    /// Whether `payload` is sent segmented.
+    pub fn segments(&self, payload: &[u8]) -> bool {
+        SomeIpHeader::HEADER_LENGTH as usize + payload.len() > self.mtu
+    }
+
+    /// Send `header` and `payload`, calling `send` once per datagram; `header.length` is set
+    /// here. Stops at the first error. Returns the number of datagrams sent.
+    pub fn send(&self, header: &SomeIpHeader, payload: &[u8], mut send: impl FnMut(&[IoSlice<'_>]) -> io::Result<usize>) -> io::Result<usize> {
+        let mut header = header.clone();
+        let mut pacing = self.pacing;
+        if !self.segments(payload) {
+            header.length = 8 + payload.len() as u32;
+            let header = header.serialize();
+            self.send_datagram(&[IoSlice::new(&header), IoSlice::new(payload)], &mut send, &mut pacing)?;
+            return Ok(1);
+        }
+        header.message_type |= TP_FLAG;
+        let mut sent = 0;
+        let mut segments = segments(payload, self.max_segment_payload()).peekable();
+        while let Some((tp_header, chunk)) = segments.next() {
+            header.length = 8 + 4 + chunk.len() as u32;
+            let (header, tp_header) = (header.serialize(), tp_header.serialize());
+            self.send_datagram(&[IoSlice::new(&header), IoSlice::new(&tp_header), IoSlice::new(chunk)], &mut send, &mut pacing)?;
+            sent += 1;
+            if let Some(pacing) = pacing.filter(|_| sent % self.burst.max(1) == 0 && segments.peek().is_some()) {
+                std::thread::sleep(pacing);
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Send one datagram, backing off while the send buffer is full if adaptive; a delay
+    /// that was needed raises `pacing`.
+    fn send_datagram(&self, msg: &[IoSlice<'_>], send: &mut impl FnMut(&[IoSlice<'_>]) -> io::Result<usize>, pacing: &mut Option<Duration>) -> io::Result<usize> {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match send(msg) {
+                Err(e) if self.adaptive && e.kind() == io::ErrorKind::WouldBlock && backoff <= MAX_BACKOFF => {
+                    std::thread::sleep(backoff);
+                    *pacing = Some(pacing.map_or(backoff, |pacing| pacing.max(backoff)));
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
     }
-    
-    segments
 }
 
 /// Helper to reassemble a payload from stored segments.
 /// Expects a map of Offset -> Data.
-pub fn reassemble_payload(segments: &std::collections::BTreeMap<u32, Vec<u8>>) -> Result<Vec<u8>, &'static str> {
+pub fn reassemble_payload(segments: &BTreeMap<u32, Vec<u8>>) -> Result<Vec<u8>, &'static str> {
     let mut buffer = Vec::new();
     let mut next_offset = 0;
     
@@ -101,18 +188,62 @@ pub fn reassemble_payload(segments: &std::collections::BTreeMap<u32, Vec<u8>>) -
     Ok(buffer)
 }
 
-/// Manages reassembly of TP packets.
-/// Key: (Message ID, Request ID) match [PRS_SOMEIP_00724]
-/// Note: Real implementation should also track Source Address if possible, but this struct is generic.
+/// Default time a message may take to arrive completely
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default limit on the bytes buffered across all incomplete messages
+pub const DEFAULT_MAX_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// Counters of a [`TpReassembler`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct TpReassemblyStats {
+    /// Messages with segments buffered
+    pub in_progress: usize,
+    pub buffered_bytes: usize,
+    pub completed: u64,
+    /// Messages discarded because they were not complete within the timeout
+    pub expired: u64,
+    /// Messages discarded because their segments did not fit in the buffer limit
+    pub dropped: u64,
+}
+
+/// Segments of one message received so far.
+struct Partial {
+    segments: BTreeMap<u32, (Vec<u8>, bool)>,
+    first_seen: Instant,
+    bytes: usize,
+    /// `tp.reassemble`, closed when the message is completed or discarded
+    span: trace::Span,
+}
+
+/// Source address, Message ID and Request ID of the message a segment belongs to
+/// ([PRS_SOMEIP_00724] plus the sender, so senders reusing client and session IDs stay apart)
+type ReassemblyKey = (SocketAddr, u32, u32);
+
+/// Manages reassembly of TP packets, keyed by [`ReassemblyKey`].
+///
+/// Incomplete messages are discarded once they are older than the reassembly timeout
+/// (see [`cleanup`](Self::cleanup)) or when buffering them would exceed the buffer limit.
 pub struct TpReassembler {
-    // Map<(MessageID, RequestID), Map<Offset, (Data, MoreFlag)>>
-    buffers: std::collections::HashMap<(u32, u32), std::collections::BTreeMap<u32, (Vec<u8>, bool)>>,
+    buffers: HashMap<ReassemblyKey, Partial>,
+    timeout: Duration,
+    max_buffer_bytes: usize,
+    buffered_bytes: usize,
+    stats: TpReassemblyStats,
 }
 
 impl TpReassembler {
     pub fn new() -> Self {
+        Self::with_limits(DEFAULT_REASSEMBLY_TIMEOUT, DEFAULT_MAX_BUFFER_BYTES)
+    }
+
+    pub fn with_limits(timeout: Duration, max_buffer_bytes: usize) -> Self {
         TpReassembler {
-            buffers: std::collections::HashMap::new(),
+            buffers: HashMap::new(),
+            timeout,
+            max_buffer_bytes,
+            buffered_bytes: 0,
+            stats: TpReassemblyStats::default(),
         }
     }
 
@@ -120,12 +251,38 @@ impl TpReassembler {
     /// Returns:
     /// - `Ok(Some(payload))` if assembly matches completion.
     /// - `Ok(None)` if stored but incomplete.
-    /// - `Err` if invalid.
-    pub fn process_segment(&mut self, message_id: u32, request_id: u32, tp_header: &TpHeader, payload: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
-        let key = (message_id, request_id);
-        
-        let segments = self.buffers.entry(key).or_insert_with(std::collections::BTreeMap::new);
-        segments.insert(tp_header.offset, (payload.to_vec(), tp_header.more_segments));
+    /// - `Err` if invalid, or if the message was dropped for exceeding the buffer limit.
+    pub fn process_segment(&mut self, source: SocketAddr, message_id: u32, request_id: u32, tp_header: &TpHeader, payload: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+        self.process_segment_at(Instant::now(), source, message_id, request_id, tp_header, payload)
+    }
+
+    /// [`process_segment`](Self::process_segment) with the arrival time given by the caller.
+    pub fn process_segment_at(&mut self, now: Instant, source: SocketAddr, message_id: u32, request_id: u32, tp_header: &TpHeader, payload: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+        let key = (source, message_id, request_id);
+
+        let replaced = self.buffers.get(&key)
+            .and_then(|partial| partial.segments.get(&tp_header.offset))
+            .map_or(0, |(data, _)| data.len());
+        if self.buffered_bytes - replaced + payload.len() > self.max_buffer_bytes {
+            self.discard(&key, "dropped");
+            self.stats.dropped += 1;
+            return Err("TP reassembly buffer limit exceeded");
+        }
+
+        let partial = self.buffers.entry(key).or_insert_with(|| Partial {
+            segments: BTreeMap::new(),
+            first_seen: now,
+            bytes: 0,
+            span: trace::tp_reassemble(source, message_id, request_id),
+        });
+        let mut data = buffer::shared().take();
+        data.extend_from_slice(payload);
+        if let Some((old, _)) = partial.segments.insert(tp_header.offset, (data, tp_header.more_segments)) {
+            buffer::shared().put(old);
+        }
+        partial.bytes = partial.bytes - replaced + payload.len();
+        self.buffered_bytes = self.buffered_bytes - replaced + payload.len();
+        let segments = &partial.segments;
         
         // Check for completion
         // 1. Must have offset 0
@@ -151,26 +308,71 @@ impl TpReassembler {
         }
         
         if complete {
-            // Reassemble
-            let mut buffer = Vec::new();
+            // Reassemble into a pooled buffer; callers may hand it back with `buffer::shared().put`
+            let mut message = buffer::shared().take();
             for (_, (data, _)) in segments.iter() {
-                buffer.extend_from_slice(data);
+                message.extend_from_slice(data);
             }
             
             // Cleanup
-            self.buffers.remove(&key);
+            self.discard(&key, "complete");
+            self.stats.completed += 1;
             
-            Ok(Some(buffer))
+            Ok(Some(message))
         } else {
             Ok(None)
         }
     }
+
+    /// Discard messages whose first segment arrived more than the timeout before `now`.
+    /// Returns how many were discarded. Called periodically by the runtime.
+    pub fn cleanup(&mut self, now: Instant) -> usize {
+        let expired: Vec<ReassemblyKey> = self.buffers.iter()
+            .filter(|(_, partial)| now.saturating_duration_since(partial.first_seen) > self.timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &expired {
+            self.discard(key, "expired");
+        }
+        self.stats.expired += expired.len() as u64;
+        expired.len()
+    }
+
+    /// Discard the segments of one message received so far, e.g. the response to a request
+    /// that is no longer awaited. Returns whether any were buffered.
+    pub fn abandon(&mut self, source: SocketAddr, message_id: u32, request_id: u32) -> bool {
+        let key = (source, message_id, request_id);
+        let buffered = self.buffers.contains_key(&key);
+        self.discard(&key, "abandoned");
+        buffered
+    }
+
+    pub fn stats(&self) -> TpReassemblyStats {
+        TpReassemblyStats {
+            in_progress: self.buffers.len(),
+            buffered_bytes: self.buffered_bytes,
+            ..self.stats
+        }
+    }
+
+    fn discard(&mut self, key: &ReassemblyKey, outcome: &str) {
+        if let Some(partial) = self.buffers.remove(key) {
+            partial.span.record("outcome", outcome);
+            self.buffered_bytes -= partial.bytes;
+            for (data, _) in partial.segments.into_values() {
+                buffer::shared().put(data);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::BTreeMap;
+
+    fn sender(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 10], port))
+    }
 
     #[test]
     fn test_tp_header_serialization() {
@@ -209,6 +411,80 @@ mod tests {
         assert_eq!(segments[2].0.offset, 32);
         assert_eq!(segments[2].0.more_segments, false);
         assert_eq!(segments[2].1.len(), 8);
+
+        // Borrowed chunks, and a single empty segment for an empty payload
+        let chunks: Vec<_> = super::segments(&payload, 20).collect();
+        assert_eq!(chunks.len(), 3);
+        assert!(std::ptr::eq(chunks[1].1.as_ptr(), payload[16..].as_ptr()));
+        let empty: Vec<_> = super::segments(&[], 16).collect();
+        assert_eq!(empty, vec![(TpHeader::new(0, false), &[][..])]);
+    }
+
+    #[test]
+    fn test_sender_segments_above_mtu() {
+        let header = SomeIpHeader::new(0x1234, 0x8001, 0, 1, 0x02, 0);
+        let tp_sender = TpSender::new(100, None);
+        assert_eq!(tp_sender.max_segment_payload(), 80);
+
+        let mut datagrams = Vec::new();
+        let mut record = |msg: &[IoSlice<'_>]| {
+            datagrams.push(msg.iter().flat_map(|slice| slice.to_vec()).collect::<Vec<u8>>());
+            Ok(datagrams.last().unwrap().len())
+        };
+        // 84 bytes fit with the header; 85 do not
+        assert_eq!(tp_sender.send(&header, &[7u8; 84], &mut record).unwrap(), 1);
+        assert_eq!(tp_sender.send(&header, &[7u8; 200], &mut record).unwrap(), 3);
+
+        let whole = SomeIpHeader::deserialize(&datagrams[0]).unwrap();
+        assert_eq!((whole.message_type, whole.length, datagrams[0].len()), (0x02, 92, 100));
+        let mut reassembler = TpReassembler::new();
+        let mut payload = None;
+        for datagram in &datagrams[1..] {
+            let segment = SomeIpHeader::deserialize(datagram).unwrap();
+            assert_eq!(segment.message_type, 0x22);
+            assert_eq!(segment.length as usize, datagram.len() - 8);
+            assert!(datagram.len() <= 100);
+            let tp = TpHeader::deserialize(&datagram[16..20]).unwrap();
+            payload = reassembler.process_segment(sender(30501), 0x12348001, 1, &tp, &datagram[20..]).unwrap();
+        }
+        assert_eq!(payload.unwrap().as_slice(), &[7u8; 200][..]);
+
+        // A failed send stops the message
+        let failing = |_: &[IoSlice<'_>]| Err(io::Error::from(io::ErrorKind::WouldBlock));
+        assert!(tp_sender.send(&header, &[0u8; 200], failing).is_err());
+    }
+
+    #[test]
+    fn test_sender_bursts_and_backs_off() {
+        let header = SomeIpHeader::new(0x1234, 0x0001, 0, 1, 0x01, 0);
+        let count = |tp_sender: &TpSender| tp_sender.send(&header, &[0u8; 300], |msg| Ok(msg.len())).unwrap();
+
+        // Four segments in bursts of two pause once
+        let paced = TpSender { mtu: 100, pacing: Some(Duration::from_millis(20)), burst: 2, adaptive: false };
+        let started = Instant::now();
+        assert_eq!(count(&paced), 4);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(count(&TpSender { burst: 0, ..paced }), 4);
+
+        // A full send buffer fails the message unless adaptive
+        let full_twice = || {
+            let mut refusals = 2;
+            move |msg: &[IoSlice<'_>]| if refusals > 0 {
+                refusals -= 1;
+                Err(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                Ok(msg.len())
+            }
+        };
+        let tp_sender = TpSender::new(100, None);
+        assert_eq!(tp_sender.send(&header, &[0u8; 300], full_twice()).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        let adaptive = TpSender { adaptive: true, ..tp_sender };
+        assert_eq!(adaptive.send(&header, &[0u8; 300], full_twice()).unwrap(), 4);
+        assert_eq!(adaptive.send(&header, &[0u8; 50], full_twice()).unwrap(), 1);
+
+        // ...but gives up once the delay reaches its limit
+        let always_full = |_: &[IoSlice<'_>]| Err(io::Error::from(io::ErrorKind::WouldBlock));
+        assert!(adaptive.send(&header, &[0u8; 50], always_full).is_err());
     }
 
     #[test]
@@ -271,15 +547,15 @@ mod tests {
         let s3 = (TpHeader::new(32, false), vec![2u8; 8]);
         
         // 1. Process S1 -> Incomplete
-        let res = reassembler.process_segment(msg_id, req_id, &s1.0, &s1.1).unwrap();
+        let res = reassembler.process_segment(sender(30501), msg_id, req_id, &s1.0, &s1.1).unwrap();
         assert!(res.is_none());
         
         // 2. Process S3 (Out of order) -> Incomplete (missing S2)
-        let res = reassembler.process_segment(msg_id, req_id, &s3.0, &s3.1).unwrap();
+        let res = reassembler.process_segment(sender(30501), msg_id, req_id, &s3.0, &s3.1).unwrap();
         assert!(res.is_none());
         
         // 3. Process S2 -> Complete!
-        let res = reassembler.process_segment(msg_id, req_id, &s2.0, &s2.1).unwrap();
+        let res = reassembler.process_segment(sender(30501), msg_id, req_id, &s2.0, &s2.1).unwrap();
         assert!(res.is_some());
         
         let full_payload = res.unwrap();
@@ -289,6 +565,68 @@ mod tests {
         assert_eq!(full_payload[32..40], vec![2u8; 8]);
         
         // Buffer should be cleared
-        assert!(reassembler.buffers.get(&(msg_id, req_id)).is_none());
+        assert!(reassembler.buffers.get(&(sender(30501), msg_id, req_id)).is_none());
+    }
+
+    #[test]
+    fn test_interleaved_senders_reassemble_separately() {
+        let mut reassembler = TpReassembler::new();
+        let (a, b) = (sender(30501), sender(30502));
+        let (msg_id, req_id) = (0x1234_8001, 0x0001_0001);
+
+        // Both senders use the same client and session IDs
+        assert!(reassembler.process_segment(a, msg_id, req_id, &TpHeader::new(0, true), &[0xA; 16]).unwrap().is_none());
+        assert!(reassembler.process_segment(b, msg_id, req_id, &TpHeader::new(0, true), &[0xB; 16]).unwrap().is_none());
+        assert!(reassembler.process_segment(b, msg_id, req_id, &TpHeader::new(16, true), &[0xB; 16]).unwrap().is_none());
+        assert_eq!(reassembler.stats().in_progress, 2);
+
+        let from_a = reassembler.process_segment(a, msg_id, req_id, &TpHeader::new(16, false), &[0xA; 4]).unwrap().unwrap();
+        assert_eq!((from_a.len(), from_a.iter().all(|byte| *byte == 0xA)), (20, true));
+        let from_b = reassembler.process_segment(b, msg_id, req_id, &TpHeader::new(32, false), &[0xB; 8]).unwrap().unwrap();
+        assert_eq!((from_b.len(), from_b.iter().all(|byte| *byte == 0xB)), (40, true));
+        assert_eq!(reassembler.stats().in_progress, 0);
+    }
+
+    #[test]
+    fn test_abandoned_message_discarded() {
+        let mut reassembler = TpReassembler::new();
+        assert!(reassembler.process_segment(sender(30501), 1, 1, &TpHeader::new(0, true), &[0u8; 16]).unwrap().is_none());
+        assert!(!reassembler.abandon(sender(30502), 1, 1));
+        assert!(reassembler.abandon(sender(30501), 1, 1));
+        let stats = reassembler.stats();
+        assert_eq!((stats.in_progress, stats.buffered_bytes, stats.expired, stats.dropped), (0, 0, 0, 0));
+        // A late segment starts over
+        assert!(reassembler.process_segment(sender(30501), 1, 1, &TpHeader::new(16, false), &[0u8; 8]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_incomplete_message_expires() {
+        let mut reassembler = TpReassembler::with_limits(Duration::from_millis(100), 1024);
+        let start = Instant::now();
+        let first = TpHeader::new(0, true);
+        assert!(reassembler.process_segment_at(start, sender(30501), 1, 1, &first, &[0u8; 16]).unwrap().is_none());
+        assert!(reassembler.process_segment_at(start + Duration::from_millis(60), sender(30501), 1, 2, &first, &[0u8; 16]).unwrap().is_none());
+
+        assert_eq!(reassembler.cleanup(start + Duration::from_millis(100)), 0);
+        assert_eq!(reassembler.cleanup(start + Duration::from_millis(101)), 1);
+        let stats = reassembler.stats();
+        assert_eq!((stats.in_progress, stats.buffered_bytes, stats.expired), (1, 16, 1));
+    }
+
+    #[test]
+    fn test_buffer_limit_drops_message() {
+        let mut reassembler = TpReassembler::with_limits(Duration::from_secs(5), 40);
+        let now = Instant::now();
+        assert!(reassembler.process_segment_at(now, sender(30501), 1, 1, &TpHeader::new(0, true), &[0u8; 32]).unwrap().is_none());
+        // A retransmitted segment replaces, rather than adds to, the buffered bytes
+        assert!(reassembler.process_segment_at(now, sender(30501), 1, 1, &TpHeader::new(0, true), &[0u8; 32]).unwrap().is_none());
+
+        // Another message does not fit; its partial segments are discarded
+        assert!(reassembler.process_segment_at(now, sender(30501), 2, 1, &TpHeader::new(0, true), &[0u8; 16]).is_err());
+        assert_eq!(reassembler.stats().buffered_bytes, 32);
+
+        assert_eq!(reassembler.process_segment_at(now, sender(30501), 1, 1, &TpHeader::new(32, false), &[1u8; 8]).unwrap().unwrap().len(), 40);
+        let stats = reassembler.stats();
+        assert_eq!((stats.in_progress, stats.buffered_bytes, stats.completed, stats.dropped), (0, 0, 1, 1));
     }
 }
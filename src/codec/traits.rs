@@ -9,3 +9,9 @@ pub trait SomeIpSerialize {
 pub trait SomeIpDeserialize: Sized {
     fn deserialize<R: Read>(reader: &mut R) -> Result<Self>;
 }
+
+// Trait for Types that can be deserialized as views borrowing from the received
+// bytes instead of allocating (see codec::borrowed)
+pub trait SomeIpDeserializeRef<'a>: Sized {
+    fn deserialize_ref(buf: &mut &'a [u8]) -> Result<Self>;
+}
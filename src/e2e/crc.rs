@@ -0,0 +1,47 @@
+//! CRC routines used by the E2E profiles.
+
+/// Advance a CRC-8 SAE J1850 (polynomial 0x1D) over `data`, without initial or final XOR.
+pub fn crc8_update(mut crc: u8, data: &[u8]) -> u8 {
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x1D } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// CRC-8 SAE J1850: start value and final XOR 0xFF.
+pub fn crc8_sae_j1850(data: &[u8]) -> u8 {
+    crc8_update(0xFF, data) ^ 0xFF
+}
+
+/// Advance a CRC-32P4 (polynomial 0xF4ACFB13, reflected) over `data`, without initial or final XOR.
+pub fn crc32p4_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xC8DF_352F } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// CRC-32P4: start value and final XOR 0xFFFFFFFF.
+pub fn crc32p4(data: &[u8]) -> u32 {
+    crc32p4_update(0xFFFF_FFFF, data) ^ 0xFFFF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        assert_eq!(crc8_sae_j1850(b"123456789"), 0x4B);
+        assert_eq!(crc32p4(b"123456789"), 0x1697_D06A);
+        // Updating in pieces matches a single pass
+        let split = crc32p4_update(crc32p4_update(0xFFFF_FFFF, b"1234"), b"56789") ^ 0xFFFF_FFFF;
+        assert_eq!(split, 0x1697_D06A);
+    }
+}
@@ -0,0 +1,175 @@
+//! # End-to-End (E2E) Protection
+//!
+//! AUTOSAR E2E profiles that let a receiver detect corrupted, repeated, lost
+//! or misrouted payloads. A protector adds a header (CRC, counter, Data ID)
+//! to outgoing payloads and checks and strips it from incoming ones.
+//!
+//! The runtime applies the profiles configured under `e2e` of a `providing`
+//! or `required` entry to the listed methods and events, and plain
+//! [`E2eProtector`] implementations can be plugged in with
+//! `SomeIpRuntime::set_e2e_protection`.
+//!
+//! ## Profiles
+//!
+//! - [`Profile01`] - CRC-8 SAE J1850 over Data ID and payload, 4-bit counter (2-byte header)
+//! - [`Profile04`] - CRC-32P4, 16-bit counter, 32-bit Data ID and length (12-byte header)
+
+pub mod crc;
+pub mod p01;
+pub mod p04;
+
+pub use p01::Profile01;
+pub use p04::Profile04;
+
+use crate::codec::ReturnCode;
+use crate::runtime::config::{E2eConfig, E2eProfileKind};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why a received payload failed its E2E check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum E2eError {
+    /// Too short for the header, or not the length the header states
+    Length,
+    /// Sent with another Data ID
+    DataId,
+    /// CRC mismatch: corrupted, or (Profile 01) sent with another Data ID
+    Crc,
+    /// Same counter as the previous payload
+    Repeated,
+    /// More payloads lost than the configured maximum counter delta allows
+    WrongSequence,
+}
+
+impl E2eError {
+    /// Return code reported for the failure. Integrity failures map to 0x0D, the
+    /// generic E2E error code.
+    pub fn return_code(self) -> ReturnCode {
+        match self {
+            E2eError::Repeated => ReturnCode::E2eRepeated,
+            E2eError::WrongSequence => ReturnCode::E2eWrongSequence,
+            E2eError::Length | E2eError::DataId | E2eError::Crc => ReturnCode::E2eNotAvailable,
+        }
+    }
+}
+
+impl fmt::Display for E2eError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            E2eError::Length => write!(f, "wrong length"),
+            E2eError::DataId => write!(f, "wrong Data ID"),
+            E2eError::Crc => write!(f, "CRC mismatch"),
+            E2eError::Repeated => write!(f, "repeated counter"),
+            E2eError::WrongSequence => write!(f, "wrong sequence"),
+        }
+    }
+}
+
+impl std::error::Error for E2eError {}
+
+/// Protection of one data element (method or event) in one direction.
+///
+/// The runtime keeps one instance for what it sends and one for what it
+/// receives, so implementations only track the counter of their direction.
+pub trait E2eProtector: Send {
+    /// Protect an outgoing payload, advancing the send counter.
+    fn protect(&mut self, payload: &[u8]) -> Vec<u8>;
+    /// Check an incoming payload and return it without the E2E header.
+    fn check(&mut self, data: &[u8]) -> Result<Vec<u8>, E2eError>;
+}
+
+/// Receiver-side counter tracking shared by the profiles.
+pub(crate) struct SequenceCheck {
+    last: Option<u32>,
+    modulus: u32,
+    max_delta: u32,
+}
+
+impl SequenceCheck {
+    pub(crate) fn new(modulus: u32, max_delta: u32) -> Self {
+        SequenceCheck { last: None, modulus, max_delta: max_delta.max(1) }
+    }
+
+    /// Accept the first counter seen, then any counter at most `max_delta` ahead of the previous one.
+    pub(crate) fn check(&mut self, counter: u32) -> Result<(), E2eError> {
+        let Some(last) = self.last.replace(counter) else {
+            return Ok(());
+        };
+        let delta = (counter + self.modulus - last) % self.modulus;
+        if delta == 0 {
+            Err(E2eError::Repeated)
+        } else if delta > self.max_delta {
+            Err(E2eError::WrongSequence)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Send and receive protectors of one method or event.
+pub struct E2eGuard {
+    pub sender: Box<dyn E2eProtector>,
+    pub receiver: Box<dyn E2eProtector>,
+}
+
+impl E2eGuard {
+    pub fn from_config(cfg: &E2eConfig) -> Self {
+        let make = || -> Box<dyn E2eProtector> {
+            match cfg.profile {
+                E2eProfileKind::P01 => Box::new(Profile01::new(cfg.data_id as u16, cfg.max_delta_counter)),
+                E2eProfileKind::P04 => Box::new(Profile04::new(cfg.data_id, cfg.max_delta_counter)),
+            }
+        };
+        E2eGuard { sender: make(), receiver: make() }
+    }
+}
+
+/// Guards for the `e2e` map of a service entry, keyed by method/event ID. Keys may be
+/// decimal or `0x`-prefixed hex; unparsable keys are returned so the caller can report them.
+pub fn guards_from_config(entries: &HashMap<String, E2eConfig>) -> (HashMap<u16, E2eGuard>, Vec<String>) {
+    let mut guards = HashMap::new();
+    let mut invalid = Vec::new();
+    for (key, cfg) in entries {
+        let parsed = match key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => key.parse::<u16>().ok(),
+        };
+        match parsed {
+            Some(id) => { guards.insert(id, E2eGuard::from_config(cfg)); }
+            None => invalid.push(key.clone()),
+        }
+    }
+    (guards, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_check_wraps() {
+        let mut seq = SequenceCheck::new(15, 1);
+        assert_eq!(seq.check(14), Ok(()));
+        assert_eq!(seq.check(0), Ok(()));
+        assert_eq!(seq.check(0), Err(E2eError::Repeated));
+        assert_eq!(seq.check(2), Err(E2eError::WrongSequence));
+        assert_eq!(seq.check(3), Ok(()));
+    }
+
+    #[test]
+    fn test_guards_from_config() {
+        let entries: HashMap<String, E2eConfig> = serde_json::from_str(r#"{
+            "0x8001": { "profile": "P04", "data_id": 1234 },
+            "1": { "profile": "P01", "data_id": 17, "max_delta_counter": 3 },
+            "bogus": { "profile": "P01", "data_id": 1 }
+        }"#).unwrap();
+        let (mut guards, invalid) = guards_from_config(&entries);
+        assert_eq!(invalid, vec!["bogus".to_string()]);
+        let mut event = guards.remove(&0x8001).unwrap();
+        let protected = event.sender.protect(&[5]);
+        assert_eq!(protected.len(), p04::HEADER_LEN + 1);
+        assert_eq!(event.receiver.check(&protected), Ok(vec![5]));
+        assert_eq!(guards.remove(&1).unwrap().sender.protect(&[5]).len(), p01::HEADER_LEN + 1);
+        assert_eq!(E2eError::Crc.return_code(), ReturnCode::E2eNotAvailable);
+    }
+}
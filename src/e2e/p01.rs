@@ -0,0 +1,87 @@
+//! E2E Profile 01: CRC-8 SAE J1850 over the Data ID and payload, 4-bit counter.
+//!
+//! The 2-byte header precedes the payload: byte 0 is the CRC, the low nibble
+//! of byte 1 the counter (0..=14). The 16-bit Data ID is not transmitted but
+//! included in the CRC, low byte first (Data ID mode "both").
+
+use super::crc::crc8_update;
+use super::{E2eError, E2eProtector, SequenceCheck};
+
+pub const HEADER_LEN: usize = 2;
+const COUNTER_MODULUS: u32 = 15;
+
+pub struct Profile01 {
+    data_id: u16,
+    counter: u8,
+    sequence: SequenceCheck,
+}
+
+impl Profile01 {
+    pub fn new(data_id: u16, max_delta_counter: u32) -> Self {
+        Profile01 { data_id, counter: 0, sequence: SequenceCheck::new(COUNTER_MODULUS, max_delta_counter) }
+    }
+
+    fn crc(&self, counter_byte: u8, payload: &[u8]) -> u8 {
+        let [id_high, id_low] = self.data_id.to_be_bytes();
+        let crc = crc8_update(0x00, &[id_low, id_high, counter_byte]);
+        crc8_update(crc, payload)
+    }
+}
+
+impl E2eProtector for Profile01 {
+    fn protect(&mut self, payload: &[u8]) -> Vec<u8> {
+        let counter_byte = self.counter;
+        let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+        out.push(self.crc(counter_byte, payload));
+        out.push(counter_byte);
+        out.extend_from_slice(payload);
+        self.counter = ((self.counter as u32 + 1) % COUNTER_MODULUS) as u8;
+        out
+    }
+
+    fn check(&mut self, data: &[u8]) -> Result<Vec<u8>, E2eError> {
+        if data.len() < HEADER_LEN {
+            return Err(E2eError::Length);
+        }
+        let counter_byte = data[1];
+        let payload = &data[HEADER_LEN..];
+        if data[0] != self.crc(counter_byte, payload) {
+            return Err(E2eError::Crc);
+        }
+        let counter = (counter_byte & 0x0F) as u32;
+        if counter >= COUNTER_MODULUS {
+            return Err(E2eError::Crc);
+        }
+        self.sequence.check(counter)?;
+        Ok(payload.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_counter_wrap() {
+        let mut tx = Profile01::new(0x0123, 1);
+        let mut rx = Profile01::new(0x0123, 1);
+        for i in 0..20u8 {
+            let protected = tx.protect(&[i, 0xAA]);
+            assert_eq!(protected.len(), 4);
+            assert_eq!(protected[1], i % 15);
+            assert_eq!(rx.check(&protected), Ok(vec![i, 0xAA]));
+        }
+    }
+
+    #[test]
+    fn test_detects_corruption_and_foreign_data_id() {
+        let mut tx = Profile01::new(0x0123, 1);
+        let protected = tx.protect(&[1, 2, 3]);
+
+        let mut corrupted = protected.clone();
+        corrupted[3] ^= 0x01;
+        assert_eq!(Profile01::new(0x0123, 1).check(&corrupted), Err(E2eError::Crc));
+        assert_eq!(Profile01::new(0x0124, 1).check(&protected), Err(E2eError::Crc));
+        assert_eq!(Profile01::new(0x0123, 1).check(&[0x00]), Err(E2eError::Length));
+    }
+}
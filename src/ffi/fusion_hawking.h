@@ -0,0 +1,69 @@
+/*
+ * C API of the fusion-hawking Rust runtime (src/ffi).
+ *
+ * Link against the static or shared library built by `cargo build --release`
+ * (libfusion_hawking.a / libfusion_hawking.so). See "C API" in docs/user_guide.md.
+ */
+#ifndef FUSION_HAWKING_H
+#define FUSION_HAWKING_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FusionRuntime FusionRuntime;
+typedef struct FusionClient FusionClient;
+typedef struct FusionResponse FusionResponse;
+
+/* Returns the SOME/IP return code: 0 (E_OK) sends the payload set with
+ * fusion_response_set, anything else an ERROR with that code. */
+typedef uint8_t (*FusionRequestCallback)(void *user_data, uint16_t method_id, uint32_t request_id,
+                                         const uint8_t *payload, size_t len, FusionResponse *response);
+/* ok is false, with no payload, if no response arrived in time. */
+typedef void (*FusionResponseCallback)(void *user_data, bool ok, const uint8_t *payload, size_t len);
+typedef void (*FusionEventCallback)(void *user_data, uint16_t service_id, uint16_t event_id,
+                                    const uint8_t *payload, size_t len);
+
+/* Runtime */
+FusionRuntime *fusion_runtime_create(const char *config_path, const char *instance_name);
+void fusion_runtime_destroy(FusionRuntime *rt);
+bool fusion_runtime_start(FusionRuntime *rt);
+void fusion_runtime_run(FusionRuntime *rt);
+void fusion_runtime_stop(FusionRuntime *rt);
+
+/* Providing services */
+bool fusion_runtime_offer_service(FusionRuntime *rt, const char *alias, FusionRequestCallback callback, void *user_data);
+bool fusion_response_set(FusionResponse *response, const uint8_t *data, size_t len);
+bool fusion_runtime_fire_event(FusionRuntime *rt, uint16_t service_id, uint16_t event_id, const uint8_t *payload, size_t len);
+
+/* Events of required services */
+bool fusion_runtime_subscribe_eventgroup(FusionRuntime *rt, uint16_t service_id, uint16_t instance_id,
+                                         uint16_t eventgroup_id, uint32_t ttl, const char *iface_alias);
+bool fusion_runtime_on_event(FusionRuntime *rt, uint16_t service_id, FusionEventCallback callback, void *user_data);
+
+/* Requests */
+bool fusion_runtime_send_request_to(FusionRuntime *rt, uint16_t service_id, uint16_t method_id, const char *address,
+                                    const uint8_t *payload, size_t len, FusionResponseCallback callback, void *user_data);
+FusionClient *fusion_runtime_get_client(FusionRuntime *rt, const char *alias);
+void fusion_client_destroy(FusionClient *client);
+bool fusion_client_request(FusionClient *client, uint16_t method_id, const uint8_t *payload, size_t len,
+                           FusionResponseCallback callback, void *user_data);
+bool fusion_client_send(FusionClient *client, uint16_t method_id, const uint8_t *payload, size_t len);
+
+/* Request IDs: Client ID in the upper 16 bits, Session ID in the lower */
+uint32_t fusion_request_id_make(uint16_t client_id, uint16_t session_id);
+uint16_t fusion_request_id_client(uint32_t id);
+uint16_t fusion_request_id_session(uint32_t id);
+uint32_t fusion_request_id_next(uint32_t id);
+bool fusion_request_id_parse(const uint8_t *msg, size_t len, uint32_t *out);
+bool fusion_request_id_matches(uint32_t id, const uint8_t *msg, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FUSION_HAWKING_H */
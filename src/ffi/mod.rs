@@ -0,0 +1,89 @@
+//! # FFI Helpers
+//!
+//! C-ABI functions for consumers that correlate asynchronous responses
+//! themselves. A request ID crosses the boundary as a plain `u32` laid out
+//! like [`RequestId`]: Client ID in the upper 16 bits, Session ID in the lower.
+//!
+//! The [`runtime`] module exposes the runtime itself to C.
+
+pub mod runtime;
+
+use crate::codec::RequestId;
+
+/// Combine a Client ID and Session ID into a request ID.
+#[unsafe(no_mangle)]
+pub extern "C" fn fusion_request_id_make(client_id: u16, session_id: u16) -> u32 {
+    RequestId::new(client_id, session_id).raw()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fusion_request_id_client(id: u32) -> u16 {
+    RequestId::from_raw(id).client_id()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fusion_request_id_session(id: u32) -> u16 {
+    RequestId::from_raw(id).session_id()
+}
+
+/// Request ID to use after `id` for the same client; the Session ID wraps from 0xFFFF to 1.
+#[unsafe(no_mangle)]
+pub extern "C" fn fusion_request_id_next(id: u32) -> u32 {
+    RequestId::from_raw(id).next().raw()
+}
+
+/// Decode the request ID of the serialized SOME/IP message at `msg`.
+/// Returns false, leaving `out` untouched, if the message is shorter than a header.
+///
+/// # Safety
+///
+/// `msg` must point to `len` readable bytes and `out` must be valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_request_id_parse(msg: *const u8, len: usize, out: *mut u32) -> bool {
+    if msg.is_null() || out.is_null() {
+        return false;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(msg, len) };
+    match RequestId::parse(bytes) {
+        Some(id) => {
+            unsafe { *out = id.raw() };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Whether the serialized SOME/IP message at `msg` carries request ID `id`.
+///
+/// # Safety
+///
+/// `msg` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_request_id_matches(id: u32, msg: *const u8, len: usize) -> bool {
+    if msg.is_null() {
+        return false;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(msg, len) };
+    RequestId::parse(bytes) == Some(RequestId::from_raw(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+
+    #[test]
+    fn test_request_id_round_trip() {
+        let id = fusion_request_id_make(0x0102, 0xFFFF);
+        assert_eq!((fusion_request_id_client(id), fusion_request_id_session(id)), (0x0102, 0xFFFF));
+        assert_eq!(fusion_request_id_session(fusion_request_id_next(id)), 1);
+
+        let msg = SomeIpHeader::new(0x1001, 0x0001, 0x0102, 0xFFFF, 0x80, 0).serialize();
+        let mut parsed = 0;
+        assert!(unsafe { fusion_request_id_parse(msg.as_ptr(), msg.len(), &mut parsed) });
+        assert_eq!(parsed, id);
+        assert!(unsafe { fusion_request_id_matches(id, msg.as_ptr(), msg.len()) });
+        assert!(!unsafe { fusion_request_id_matches(fusion_request_id_next(id), msg.as_ptr(), msg.len()) });
+        assert!(!unsafe { fusion_request_id_parse(msg.as_ptr(), 8, &mut parsed) });
+    }
+}
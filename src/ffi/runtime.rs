@@ -0,0 +1,476 @@
+//! # C Runtime API
+//!
+//! `extern "C"` surface over [`SomeIpRuntime`] for C and C++ applications; the
+//! declarations are in `fusion_hawking.h` next to this file. A runtime is an
+//! opaque [`FusionRuntime`] pointer from `fusion_runtime_create`, released with
+//! `fusion_runtime_destroy`. Functions return false (or NULL) for null
+//! arguments, unknown aliases and invalid strings instead of panicking.
+//!
+//! Callbacks receive the `user_data` pointer they were registered with and run
+//! on runtime threads: request and event callbacks on the event loop, response
+//! callbacks on a worker thread of the runtime. Payload pointers are only valid
+//! during the call. Callbacks must not destroy the runtime.
+
+use crate::codec::{ReturnCode, SomeIpHeader};
+use crate::runtime::{ClientContext, HandlerResult, RequestHandler, ServiceClient, SomeIpHandle, SomeIpRuntime};
+use std::ffi::{c_char, c_void, CStr};
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Runtime created by `fusion_runtime_create`.
+pub struct FusionRuntime {
+    runtime: Arc<SomeIpRuntime>,
+    handle: SomeIpHandle,
+    event_loop: Mutex<Option<JoinHandle<()>>>,
+    /// Awaits the responses of requests sent through the C API
+    executor: tokio::runtime::Runtime,
+}
+
+/// Client of one discovered service, from `fusion_runtime_get_client`.
+pub struct FusionClient {
+    context: ClientContext,
+    executor: tokio::runtime::Handle,
+}
+
+/// Response payload of a request callback, set with `fusion_response_set`.
+pub struct FusionResponse {
+    payload: Vec<u8>,
+}
+
+/// Handles a request to an offered service. Returns the SOME/IP return code: 0 (`E_OK`)
+/// sends the payload set on `response`, anything else an ERROR with that code.
+pub type FusionRequestCallback = unsafe extern "C" fn(user_data: *mut c_void, method_id: u16, request_id: u32, payload: *const u8, len: usize, response: *mut FusionResponse) -> u8;
+/// Receives the response to a request; `ok` is false, with no payload, if none arrived in time.
+pub type FusionResponseCallback = unsafe extern "C" fn(user_data: *mut c_void, ok: bool, payload: *const u8, len: usize);
+/// Receives a notification of a subscribed service.
+pub type FusionEventCallback = unsafe extern "C" fn(user_data: *mut c_void, service_id: u16, event_id: u16, payload: *const u8, len: usize);
+
+/// `user_data` of a callback. The caller vouches that it may be used from runtime threads.
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Offered service whose requests go to a C callback.
+struct CallbackHandler {
+    service_id: u16,
+    major_version: u8,
+    minor_version: u32,
+    callback: FusionRequestCallback,
+    user_data: UserData,
+}
+
+impl RequestHandler for CallbackHandler {
+    fn service_id(&self) -> u16 { self.service_id }
+    fn major_version(&self) -> u8 { self.major_version }
+    fn minor_version(&self) -> u32 { self.minor_version }
+
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        let mut response = FusionResponse { payload: Vec::new() };
+        let code = unsafe {
+            (self.callback)(self.user_data.get(), header.method_id, header.request_id().raw(), payload.as_ptr(), payload.len(), &mut response)
+        };
+        match ReturnCode::from_u8(code) {
+            Some(ReturnCode::Ok) => Ok(Some(response.payload)),
+            code => Err(code.unwrap_or(ReturnCode::NotOk)),
+        }
+    }
+}
+
+/// Notification handler of a required service that forwards to a C callback.
+struct EventCallback {
+    service_id: u16,
+    callback: FusionEventCallback,
+    user_data: UserData,
+}
+
+impl RequestHandler for EventCallback {
+    fn service_id(&self) -> u16 { self.service_id }
+    fn major_version(&self) -> u8 { 0 }
+    fn minor_version(&self) -> u32 { 0 }
+
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        unsafe { (self.callback)(self.user_data.get(), header.service_id, header.method_id, payload.as_ptr(), payload.len()) };
+        Ok(None)
+    }
+}
+
+/// Keeps the context `get_client` builds.
+pub(crate) struct Attached(pub(crate) ClientContext);
+
+impl ServiceClient for Attached {
+    const SERVICE_ID: u16 = 0;
+    fn new(context: ClientContext) -> Self { Attached(context) }
+}
+
+/// UTF-8 string at `s`, or `None` if it is null or invalid.
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+/// `len` bytes at `data`; null is accepted for an empty payload.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(unsafe { std::slice::from_raw_parts(data, len) }),
+    }
+}
+
+/// Load `instance_name` from the config file at `config_path`.
+/// Returns NULL if the file cannot be read, is invalid, or an endpoint cannot be bound.
+///
+/// # Safety
+///
+/// Both arguments must be null or NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_create(config_path: *const c_char, instance_name: *const c_char) -> *mut FusionRuntime {
+    let (Some(config_path), Some(instance_name)) = (unsafe { c_str(config_path) }, unsafe { c_str(instance_name) }) else {
+        return ptr::null_mut();
+    };
+    // Panics must not unwind into C
+    let Ok(Ok(runtime)) = panic::catch_unwind(|| SomeIpRuntime::try_load(config_path, instance_name)) else {
+        return ptr::null_mut();
+    };
+    let Ok(executor) = tokio::runtime::Builder::new_multi_thread().worker_threads(1).thread_name("fusion-ffi").enable_time().build() else {
+        return ptr::null_mut();
+    };
+    let handle = runtime.handle();
+    Box::into_raw(Box::new(FusionRuntime { runtime, handle, event_loop: Mutex::new(None), executor }))
+}
+
+/// Stop the runtime and release it. Responses still outstanding are not delivered.
+///
+/// # Safety
+///
+/// `rt` must be null or come from `fusion_runtime_create`, and must not be used afterwards.
+/// Clients of the runtime must be destroyed first.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_destroy(rt: *mut FusionRuntime) {
+    if rt.is_null() {
+        return;
+    }
+    unsafe { fusion_runtime_stop(rt) };
+    let rt = unsafe { Box::from_raw(rt) };
+    rt.executor.shutdown_background();
+}
+
+/// Run the event loop on a new thread. Returns false if it was already started.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_start(rt: *mut FusionRuntime) -> bool {
+    let Some(rt) = (unsafe { rt.as_ref() }) else { return false };
+    let mut event_loop = rt.event_loop.lock().unwrap();
+    if event_loop.is_some() {
+        return false;
+    }
+    let runtime = rt.runtime.clone();
+    *event_loop = Some(thread::spawn(move || runtime.run()));
+    true
+}
+
+/// Run the event loop on the calling thread until `fusion_runtime_stop` is called.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_run(rt: *mut FusionRuntime) {
+    if let Some(rt) = unsafe { rt.as_ref() } {
+        rt.runtime.run();
+    }
+}
+
+/// Stop the event loop and wait for a loop started with `fusion_runtime_start` to end.
+/// A stopped runtime cannot be started again.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_stop(rt: *mut FusionRuntime) {
+    let Some(rt) = (unsafe { rt.as_ref() }) else { return };
+    rt.runtime.stop();
+    if let Some(event_loop) = rt.event_loop.lock().unwrap().take() {
+        let _ = event_loop.join();
+    }
+}
+
+/// Offer the service configured under `alias`, handling its requests with `callback`.
+/// Returns false if the alias is not a provided service.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime and `alias` null or a NUL-terminated string.
+/// `user_data` is passed to `callback` on the event loop thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_offer_service(rt: *mut FusionRuntime, alias: *const c_char, callback: Option<FusionRequestCallback>, user_data: *mut c_void) -> bool {
+    let (Some(rt), Some(alias), Some(callback)) = (unsafe { rt.as_ref() }, unsafe { c_str(alias) }, callback) else {
+        return false;
+    };
+    let cfg = rt.runtime.instance_config();
+    let Some(svc) = cfg.as_ref().and_then(|cfg| cfg.providing.get(alias)) else {
+        return false;
+    };
+    let handler = CallbackHandler {
+        service_id: svc.service_id,
+        major_version: svc.major_version,
+        minor_version: svc.minor_version,
+        callback,
+        user_data: UserData(user_data),
+    };
+    rt.handle.offer_service(alias, Box::new(handler));
+    true
+}
+
+/// Set the payload of the response to the request being handled.
+///
+/// # Safety
+///
+/// `response` must be the pointer passed to the running request callback and `data`
+/// must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_response_set(response: *mut FusionResponse, data: *const u8, len: usize) -> bool {
+    let (Some(response), Some(data)) = (unsafe { response.as_mut() }, unsafe { bytes(data, len) }) else {
+        return false;
+    };
+    response.payload = data.to_vec();
+    true
+}
+
+/// Send an event to the subscribers of every eventgroup of the service that carries it.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime and `payload` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_fire_event(rt: *mut FusionRuntime, service_id: u16, event_id: u16, payload: *const u8, len: usize) -> bool {
+    let (Some(rt), Some(payload)) = (unsafe { rt.as_ref() }, unsafe { bytes(payload, len) }) else {
+        return false;
+    };
+    rt.handle.fire_event(service_id, event_id, payload);
+    true
+}
+
+/// Subscribe to an eventgroup of a remote service on the interface `iface_alias`.
+/// Notifications go to the callback set with `fusion_runtime_on_event`.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime and `iface_alias` null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_subscribe_eventgroup(rt: *mut FusionRuntime, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: *const c_char) -> bool {
+    let (Some(rt), Some(iface_alias)) = (unsafe { rt.as_ref() }, unsafe { c_str(iface_alias) }) else {
+        return false;
+    };
+    rt.handle.subscribe_eventgroup(service_id, instance_id, eventgroup_id, ttl, iface_alias);
+    true
+}
+
+/// Deliver the notifications of `service_id` to `callback`.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime. `user_data` is passed to `callback` on the
+/// event loop thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_on_event(rt: *mut FusionRuntime, service_id: u16, callback: Option<FusionEventCallback>, user_data: *mut c_void) -> bool {
+    let (Some(rt), Some(callback)) = (unsafe { rt.as_ref() }, callback) else {
+        return false;
+    };
+    rt.handle.register_notification_handler(service_id, Box::new(EventCallback { service_id, callback, user_data: UserData(user_data) }));
+    true
+}
+
+/// Send a request to the service at `address` ("ip:port") without discovery, and pass the
+/// response to `callback`. Returns false, without calling it, if the request was not sent.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime, `address` null or a NUL-terminated string and
+/// `payload` must point to `len` readable bytes. `user_data` is passed to `callback` on a
+/// runtime thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_send_request_to(
+    rt: *mut FusionRuntime, service_id: u16, method_id: u16, address: *const c_char,
+    payload: *const u8, len: usize, callback: Option<FusionResponseCallback>, user_data: *mut c_void,
+) -> bool {
+    let (Some(rt), Some(address), Some(payload), Some(callback)) = (unsafe { rt.as_ref() }, unsafe { c_str(address) }, unsafe { bytes(payload, len) }, callback) else {
+        return false;
+    };
+    let Ok(target) = address.parse::<SocketAddr>() else { return false };
+    let (handle, payload, user_data) = (rt.handle.clone(), payload.to_vec(), UserData(user_data));
+    rt.executor.spawn(async move {
+        let response = handle.send_request_and_wait(service_id, method_id, &payload, target).await;
+        deliver(callback, user_data, response);
+    });
+    true
+}
+
+/// Wait for the required service configured under `alias` to be discovered and return a
+/// client for it. Blocks for up to the SD request timeout; returns NULL if the alias is not
+/// a required service or the service was not found.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime and `alias` null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_runtime_get_client(rt: *mut FusionRuntime, alias: *const c_char) -> *mut FusionClient {
+    let (Some(rt), Some(alias)) = (unsafe { rt.as_ref() }, unsafe { c_str(alias) }) else {
+        return ptr::null_mut();
+    };
+    if !rt.runtime.instance_config().is_some_and(|cfg| cfg.required.contains_key(alias)) {
+        return ptr::null_mut();
+    }
+    let client = panic::catch_unwind(AssertUnwindSafe(|| rt.handle.get_client::<Attached>(alias)));
+    match client {
+        Ok(Some(Attached(context))) => Box::into_raw(Box::new(FusionClient { context, executor: rt.executor.handle().clone() })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Release a client.
+///
+/// # Safety
+///
+/// `client` must be null or come from `fusion_runtime_get_client`, and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_client_destroy(client: *mut FusionClient) {
+    if !client.is_null() {
+        drop(unsafe { Box::from_raw(client) });
+    }
+}
+
+/// Send a request for `method_id` and pass the response to `callback`, with the timeout and
+/// retries of the alias's QoS profile.
+///
+/// # Safety
+///
+/// `client` must be null or a live client and `payload` must point to `len` readable bytes.
+/// `user_data` is passed to `callback` on a runtime thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_client_request(client: *mut FusionClient, method_id: u16, payload: *const u8, len: usize, callback: Option<FusionResponseCallback>, user_data: *mut c_void) -> bool {
+    let (Some(client), Some(payload), Some(callback)) = (unsafe { client.as_ref() }, unsafe { bytes(payload, len) }, callback) else {
+        return false;
+    };
+    let (context, payload, user_data) = (client.context.clone(), payload.to_vec(), UserData(user_data));
+    client.executor.spawn(async move {
+        let response = context.request(method_id, &payload).await.ok();
+        deliver(callback, user_data, response);
+    });
+    true
+}
+
+/// Send a fire-and-forget request (REQUEST_NO_RETURN) for `method_id`.
+///
+/// # Safety
+///
+/// `client` must be null or a live client and `payload` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fusion_client_send(client: *mut FusionClient, method_id: u16, payload: *const u8, len: usize) -> bool {
+    let (Some(client), Some(payload)) = (unsafe { client.as_ref() }, unsafe { bytes(payload, len) }) else {
+        return false;
+    };
+    client.context.send(method_id, payload).is_ok()
+}
+
+fn deliver(callback: FusionResponseCallback, user_data: UserData, response: Option<Vec<u8>>) {
+    match response {
+        Some(payload) => unsafe { callback(user_data.get(), true, payload.as_ptr(), payload.len()) },
+        None => unsafe { callback(user_data.get(), false, ptr::null(), 0) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Answers method 1 with the payload reversed and fails every other method with E_NOT_READY
+    unsafe extern "C" fn reverse(_user_data: *mut c_void, method_id: u16, _request_id: u32, payload: *const u8, len: usize, response: *mut FusionResponse) -> u8 {
+        if method_id != 1 {
+            return ReturnCode::NotReady as u8;
+        }
+        let mut reversed = unsafe { std::slice::from_raw_parts(payload, len) }.to_vec();
+        reversed.reverse();
+        unsafe { fusion_response_set(response, reversed.as_ptr(), reversed.len()) };
+        0
+    }
+
+    unsafe extern "C" fn collect(user_data: *mut c_void, ok: bool, payload: *const u8, len: usize) {
+        let responses = unsafe { &*(user_data as *const Mutex<mpsc::Sender<Option<Vec<u8>>>>) };
+        let payload = ok.then(|| unsafe { bytes(payload, len) }.unwrap().to_vec());
+        responses.lock().unwrap().send(payload).unwrap();
+    }
+
+    #[test]
+    fn test_c_api_serves_and_sends_requests() {
+        let path = std::env::temp_dir().join(format!("fh_ffi_runtime_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{
+            "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "server_ep",
+                "providing": { "reverse": { "service_id": 16976, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+            } }
+        }"#).unwrap();
+        let config_path = CString::new(path.to_str().unwrap()).unwrap();
+        let missing = CString::new("missing").unwrap();
+        assert!(unsafe { fusion_runtime_create(config_path.as_ptr(), missing.as_ptr()) }.is_null());
+        let rt = unsafe { fusion_runtime_create(config_path.as_ptr(), c"test".as_ptr()) };
+        let _ = std::fs::remove_file(&path);
+        assert!(!rt.is_null());
+
+        assert!(!unsafe { fusion_runtime_offer_service(rt, c"unknown".as_ptr(), Some(reverse), ptr::null_mut()) });
+        assert!(unsafe { fusion_runtime_offer_service(rt, c"reverse".as_ptr(), Some(reverse), ptr::null_mut()) });
+        assert!(unsafe { fusion_runtime_get_client(rt, c"reverse".as_ptr()) }.is_null());
+        assert!(unsafe { fusion_runtime_start(rt) });
+        assert!(!unsafe { fusion_runtime_start(rt) });
+
+        // The runtime requests its own service
+        let server = unsafe { &*rt }.runtime.get_transport_v4().unwrap().local_addr().unwrap();
+        let address = CString::new(server.to_string()).unwrap();
+        let (tx, rx) = mpsc::channel::<Option<Vec<u8>>>();
+        let responses = Mutex::new(tx);
+        let user_data = &responses as *const _ as *mut c_void;
+        assert!(unsafe { fusion_runtime_send_request_to(rt, 0x4250, 1, address.as_ptr(), [1u8, 2, 3].as_ptr(), 3, Some(collect), user_data) });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), Some(vec![3, 2, 1]));
+
+        // Return codes other than E_OK become ERROR responses
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        socket.send_to(&SomeIpHeader::new(0x4250, 2, 0, 1, 0x00, 0).serialize(), server).unwrap();
+        let mut buf = [0u8; 1500];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        let answer = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        assert_eq!((answer.message_type, answer.return_code), (0x81, ReturnCode::NotReady as u8));
+
+        unsafe { fusion_runtime_destroy(rt) };
+    }
+
+    #[test]
+    fn test_callback_return_codes() {
+        let header = SomeIpHeader::new(0x4250, 1, 0x0102, 0x0304, 0x00, 0);
+        let handler = CallbackHandler { service_id: 0x4250, major_version: 1, minor_version: 0, callback: reverse, user_data: UserData(ptr::null_mut()) };
+        assert_eq!(handler.handle(&header, &[1, 2]), Ok(Some(vec![2, 1])));
+        let other = SomeIpHeader::new(0x4250, 7, 0x0102, 0x0304, 0x00, 0);
+        assert_eq!(handler.handle(&other, &[]), Err(ReturnCode::NotReady));
+    }
+}
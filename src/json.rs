@@ -0,0 +1,109 @@
+//! # JSON Views
+//!
+//! Pretty-printed JSON of codec, SD, configuration and runtime types for
+//! tooling: config dumps, golden tests and dashboards. Requires the
+//! `serde-types` feature, which also derives `Serialize`/`Deserialize` on
+//! those types and on generated payload structs.
+//!
+//! ```ignore
+//! use fusion_hawking::json;
+//!
+//! // Decode a captured datagram (SOME/IP header plus payload)
+//! println!("{}", json::decode_message(&datagram)?);
+//! ```
+
+use crate::codec::{SomeIpDeserialize, SomeIpHeader};
+use crate::sd::SdPacket;
+use serde::Serialize;
+use std::io;
+
+/// Service/method ID pair carrying SOME/IP-SD messages.
+const SD_SERVICE_ID: u16 = 0xFFFF;
+const SD_METHOD_ID: u16 = 0x8100;
+
+/// Render any serializable value as pretty-printed JSON.
+pub fn to_pretty<T: Serialize + ?Sized>(value: &T) -> io::Result<String> {
+    serde_json::to_string_pretty(value).map_err(io::Error::other)
+}
+
+#[derive(Serialize)]
+struct MessageView<'a> {
+    header: &'a SomeIpHeader,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sd: Option<SdPacket>,
+    payload_len: usize,
+}
+
+/// Decode one SOME/IP message (16-byte header plus payload) into JSON.
+/// SD messages are expanded into their entries and options.
+pub fn decode_message(bytes: &[u8]) -> io::Result<String> {
+    if bytes.len() < 16 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message shorter than a SOME/IP header"));
+    }
+    let header = SomeIpHeader::deserialize(&bytes[..16])
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let payload = &bytes[16..];
+    let sd = if header.service_id == SD_SERVICE_ID && header.method_id == SD_METHOD_ID {
+        Some(SdPacket::deserialize(&mut &payload[..])?)
+    } else {
+        None
+    };
+    to_pretty(&MessageView { header: &header, sd, payload_len: payload.len() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpSerialize;
+    use crate::sd::{EntryType, SdEntry, SdOption};
+    use std::net::Ipv4Addr;
+
+    fn offer_packet() -> SdPacket {
+        SdPacket {
+            flags: 0xC0,
+            entries: vec![SdEntry {
+                entry_type: EntryType::OfferService,
+                index_1: 0,
+                index_2: 0,
+                number_of_opts_1: 1,
+                number_of_opts_2: 0,
+                service_id: 0x1234,
+                instance_id: 1,
+                major_version: 1,
+                ttl: 3,
+                minor_version: 10,
+            }],
+            options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x11, port: 30500 }],
+        }
+    }
+
+    #[test]
+    fn test_sd_packet_json_roundtrip() {
+        let packet = offer_packet();
+        let json = to_pretty(&packet).unwrap();
+        assert!(json.contains("\"OfferService\""));
+        assert!(json.contains("\"10.0.0.2\""));
+
+        let back: SdPacket = serde_json::from_str(&json).unwrap();
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        SomeIpSerialize::serialize(&packet, &mut a).unwrap();
+        SomeIpSerialize::serialize(&back, &mut b).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_decode_sd_message() {
+        let mut payload = Vec::new();
+        SomeIpSerialize::serialize(&offer_packet(), &mut payload).unwrap();
+        let header = SomeIpHeader::new(SD_SERVICE_ID, SD_METHOD_ID, 0, 1, 0x02, payload.len() as u32);
+        let mut msg = header.serialize().to_vec();
+        msg.extend_from_slice(&payload);
+
+        let json: serde_json::Value = serde_json::from_str(&decode_message(&msg).unwrap()).unwrap();
+        assert_eq!(json["header"]["service_id"], 0xFFFF);
+        assert_eq!(json["sd"]["entries"][0]["service_id"], 0x1234);
+        assert_eq!(json["payload_len"], payload.len());
+
+        assert!(decode_message(&msg[..10]).is_err());
+    }
+}
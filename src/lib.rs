@@ -1,15 +1,25 @@
+pub mod buffer;
+pub mod clock;
 pub mod codec;
+pub mod e2e;
 pub mod logging;
 pub mod ffi;
 pub mod runtime;
 pub mod sd;
 pub mod transport;
+mod trace;
+#[cfg(feature = "test-utils")]
+pub mod sim;
+#[cfg(feature = "serde-types")]
+pub mod json;
+#[cfg(feature = "python")]
+pub mod py;
 
 pub use transport::{SomeIpTransport, UdpTransport, TcpTransport};
 // Removed SomeIpPacket as it likely doesn't exist or isn't needed.
 pub use codec::{SomeIpHeader, SomeIpSerialize, SomeIpDeserialize};
 
-pub use sd::machine::{ServiceDiscovery, RemoteService};
+pub use sd::machine::{ServiceDiscovery, RemoteService, DiscoveredService, SdSnapshot};
 pub use sd::entries::{SdEntry, EntryType};
 pub use sd::options::SdOption;
 pub use runtime::*;
@@ -1,15 +1,100 @@
-use std::sync::Arc;
+use crate::clock::Timestamp;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Severity of a log line, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub enum LogLevel {
+    #[serde(alias = "debug", alias = "DEBUG")]
     Debug,
+    #[serde(alias = "info", alias = "INFO")]
     Info,
+    #[serde(alias = "warn", alias = "WARN")]
     Warn,
+    #[serde(alias = "error", alias = "ERROR")]
     Error,
 }
 
+/// Where a log line comes from: runtime instance, interface alias and service.
+/// Unset fields are unknown or not applicable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogContext {
+    pub instance: Option<String>,
+    pub interface: Option<String>,
+    pub service_id: Option<u16>,
+}
+
+impl LogContext {
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    pub fn with_interface(mut self, iface_alias: &str) -> Self {
+        self.interface = Some(iface_alias.to_string());
+        self
+    }
+
+    pub fn with_service(mut self, service_id: u16) -> Self {
+        self.service_id = Some(service_id);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instance.is_none() && self.interface.is_none() && self.service_id.is_none()
+    }
+
+    /// `self`, with the fields set in `other` taking precedence.
+    pub fn merged(&self, other: &LogContext) -> LogContext {
+        LogContext {
+            instance: other.instance.clone().or_else(|| self.instance.clone()),
+            interface: other.interface.clone().or_else(|| self.interface.clone()),
+            service_id: other.service_id.or(self.service_id),
+        }
+    }
+}
+
+impl fmt::Display for LogContext {
+    /// `inst=<name> iface=<alias> svc=0x<id>`, leaving out unset fields
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(instance) = &self.instance {
+            write!(f, "inst={}", instance)?;
+            sep = " ";
+        }
+        if let Some(interface) = &self.interface {
+            write!(f, "{}iface={}", sep, interface)?;
+            sep = " ";
+        }
+        if let Some(service_id) = self.service_id {
+            write!(f, "{}svc=0x{:04x}", sep, service_id)?;
+        }
+        Ok(())
+    }
+}
+
 pub trait FusionLogger: Send + Sync {
     fn log(&self, level: LogLevel, component: &str, msg: &str);
+
+    /// Log with structured context. Loggers that do not override this receive the
+    /// context rendered in front of the message.
+    fn log_with(&self, level: LogLevel, component: &str, context: &LogContext, msg: &str) {
+        if context.is_empty() {
+            self.log(level, component, msg);
+        } else {
+            self.log(level, component, &format!("[{}] {}", context, msg));
+        }
+    }
+
+    /// Drop lines of `component` below `level`. Loggers without filtering ignore this.
+    fn set_level(&self, _component: &str, _level: LogLevel) {}
+
+    /// Drop lines below `level` from components without a level of their own.
+    /// Loggers without filtering ignore this.
+    fn set_default_level(&self, _level: LogLevel) {}
 }
 
 pub struct ConsoleLogger;
@@ -22,14 +107,20 @@ impl ConsoleLogger {
 
 impl FusionLogger for ConsoleLogger {
     fn log(&self, level: LogLevel, component: &str, msg: &str) {
+        self.log_with(level, component, &LogContext::default(), msg);
+    }
+
+    fn log_with(&self, level: LogLevel, component: &str, context: &LogContext, msg: &str) {
         let level_str = match level {
             LogLevel::Debug => "DEBUG",
             LogLevel::Info => "INFO ",
             LogLevel::Warn => "WARN ",
             LogLevel::Error => "ERROR",
         };
-        // Timestamp using system time (seconds since program start would need static, so using epoch millis % day)
-        let now = std::time::SystemTime::now()
+        // Wall-clock time of day (UTC) derived from the shared monotonic timestamp,
+        // which is printed too so lines line up with SD traces and stats
+        let ts = Timestamp::now();
+        let now = ts.to_system_time()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default();
         let secs = now.as_secs() % 86400; // seconds in day
@@ -37,7 +128,129 @@ impl FusionLogger for ConsoleLogger {
         let h = secs / 3600;
         let m = (secs % 3600) / 60;
         let s = secs % 60;
-        println!("[{:02}:{:02}:{:02}.{:03}] [{}] [{}] {}", h, m, s, millis, level_str, component, msg);
+        if context.is_empty() {
+            println!("[{:02}:{:02}:{:02}.{:03} {}] [{}] [{}] {}", h, m, s, millis, ts, level_str, component, msg);
+        } else {
+            println!("[{:02}:{:02}:{:02}.{:03} {}] [{}] [{}] [{}] {}", h, m, s, millis, ts, level_str, component, context, msg);
+        }
+    }
+}
+
+/// Logger that attaches a fixed context (e.g. the runtime instance) to everything
+/// logged through it before passing it on.
+pub struct ContextLogger {
+    inner: Arc<dyn FusionLogger>,
+    context: LogContext,
+}
+
+impl ContextLogger {
+    pub fn new(inner: Arc<dyn FusionLogger>, context: LogContext) -> Self {
+        ContextLogger { inner, context }
+    }
+
+    pub fn context(&self) -> &LogContext {
+        &self.context
+    }
+}
+
+impl FusionLogger for ContextLogger {
+    fn log(&self, level: LogLevel, component: &str, msg: &str) {
+        self.inner.log_with(level, component, &self.context, msg);
+    }
+
+    fn log_with(&self, level: LogLevel, component: &str, context: &LogContext, msg: &str) {
+        self.inner.log_with(level, component, &self.context.merged(context), msg);
+    }
+
+    fn set_level(&self, component: &str, level: LogLevel) {
+        self.inner.set_level(component, level);
+    }
+
+    fn set_default_level(&self, level: LogLevel) {
+        self.inner.set_default_level(level);
+    }
+}
+
+/// Logger that passes on only lines at or above the minimum level of their component.
+/// Levels can be changed at any time through [`FusionLogger::set_level`].
+pub struct LevelFilter {
+    inner: Arc<dyn FusionLogger>,
+    default: RwLock<LogLevel>,
+    components: RwLock<HashMap<String, LogLevel>>,
+}
+
+impl LevelFilter {
+    /// Filter in front of `inner`, passing on lines at or above `default` until levels are set.
+    pub fn new(inner: Arc<dyn FusionLogger>, default: LogLevel) -> Self {
+        LevelFilter { inner, default: RwLock::new(default), components: RwLock::new(HashMap::new()) }
+    }
+
+    /// Minimum level of `component`.
+    pub fn level(&self, component: &str) -> LogLevel {
+        self.components.read().unwrap().get(component).copied().unwrap_or(*self.default.read().unwrap())
+    }
+
+    /// Whether a line of `component` at `level` is passed on.
+    pub fn enabled(&self, level: LogLevel, component: &str) -> bool {
+        level >= self.level(component)
+    }
+}
+
+impl FusionLogger for LevelFilter {
+    fn log(&self, level: LogLevel, component: &str, msg: &str) {
+        if self.enabled(level, component) {
+            self.inner.log(level, component, msg);
+        }
+    }
+
+    fn log_with(&self, level: LogLevel, component: &str, context: &LogContext, msg: &str) {
+        if self.enabled(level, component) {
+            self.inner.log_with(level, component, context, msg);
+        }
+    }
+
+    fn set_level(&self, component: &str, level: LogLevel) {
+        self.components.write().unwrap().insert(component.to_string(), level);
+    }
+
+    fn set_default_level(&self, level: LogLevel) {
+        *self.default.write().unwrap() = level;
+    }
+}
+
+/// Logger that emits every line as a `tracing` event with target `fusion_hawking`, so
+/// lines logged while serving a request or running SD land in the current span.
+/// The component and context become the `component`, `instance`, `interface` and
+/// `service` fields. Filtering is left to the subscriber.
+#[cfg(feature = "tracing")]
+pub struct TracingLogger;
+
+#[cfg(feature = "tracing")]
+impl TracingLogger {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self)
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl FusionLogger for TracingLogger {
+    fn log(&self, level: LogLevel, component: &str, msg: &str) {
+        self.log_with(level, component, &LogContext::default(), msg);
+    }
+
+    fn log_with(&self, level: LogLevel, component: &str, context: &LogContext, msg: &str) {
+        let (instance, interface, service) = (context.instance.as_deref(), context.interface.as_deref(), context.service_id);
+        macro_rules! emit {
+            ($level:expr) => {
+                tracing::event!(target: "fusion_hawking", $level, component, instance, interface, service, "{}", msg)
+            };
+        }
+        match level {
+            LogLevel::Debug => emit!(tracing::Level::DEBUG),
+            LogLevel::Info => emit!(tracing::Level::INFO),
+            LogLevel::Warn => emit!(tracing::Level::WARN),
+            LogLevel::Error => emit!(tracing::Level::ERROR),
+        }
     }
 }
 
@@ -148,6 +361,63 @@ mod tests {
         assert_eq!(logs[0].2, "");
     }
     
+    #[test]
+    fn test_context_logger_attaches_fields() {
+        struct Structured(Mutex<Vec<LogContext>>);
+        impl FusionLogger for Structured {
+            fn log(&self, _level: LogLevel, _component: &str, _msg: &str) {
+                panic!("context must arrive as fields");
+            }
+            fn log_with(&self, _level: LogLevel, _component: &str, context: &LogContext, _msg: &str) {
+                self.0.lock().unwrap().push(context.clone());
+            }
+        }
+        let sink = Arc::new(Structured(Mutex::new(Vec::new())));
+        let logger = ContextLogger::new(sink.clone(), LogContext::default().with_instance("ecu1"));
+
+        logger.log(LogLevel::Info, "Runtime", "started");
+        logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_interface("eth0").with_service(0x1234), "offered");
+        let contexts = sink.0.lock().unwrap().clone();
+        assert_eq!(contexts[0], LogContext::default().with_instance("ecu1"));
+        assert_eq!(contexts[1].to_string(), "inst=ecu1 iface=eth0 svc=0x1234");
+    }
+
+    #[test]
+    fn test_plain_logger_receives_rendered_context() {
+        let logger = MockLogger::new();
+        logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(0x1234), "busy");
+        logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default(), "idle");
+
+        let logs = logger.get_logs();
+        assert_eq!(logs[0].2, "[svc=0x1234] busy");
+        assert_eq!(logs[1].2, "idle");
+    }
+
+    #[test]
+    fn test_level_filter() {
+        let sink = MockLogger::new();
+        let logger: Arc<dyn FusionLogger> = Arc::new(ContextLogger::new(
+            Arc::new(LevelFilter::new(sink.clone(), LogLevel::Info)),
+            LogContext::default().with_instance("ecu1"),
+        ));
+        logger.set_level("Runtime", LogLevel::Warn);
+        logger.set_level("SD", LogLevel::Debug);
+
+        logger.log(LogLevel::Debug, "Main", "dropped");
+        logger.log(LogLevel::Info, "Main", "kept");
+        logger.log(LogLevel::Info, "Runtime", "dropped");
+        logger.log(LogLevel::Error, "Runtime", "kept");
+        logger.log(LogLevel::Debug, "SD", "kept");
+        logger.set_default_level(LogLevel::Error);
+        logger.log(LogLevel::Warn, "Main", "dropped");
+        logger.log(LogLevel::Warn, "Runtime", "kept");
+
+        let logs = sink.get_logs();
+        assert_eq!(logs.len(), 4);
+        assert!(logs.iter().all(|(_, _, msg)| msg.ends_with("kept")));
+        assert!(LogLevel::Debug < LogLevel::Info && LogLevel::Warn < LogLevel::Error);
+    }
+
     #[test]
     fn test_unicode_in_logs() {
         let logger = MockLogger::new();
@@ -0,0 +1,293 @@
+//! # Python Bindings
+//!
+//! PyO3 extension module `fusion_hawking._rust_core`, built with the `python`
+//! feature, that runs [`SomeIpRuntime`] from Python. `fusion_hawking.rust_core`
+//! imports it, either installed next to the package or from the library in
+//! `target/`.
+//!
+//! Handlers are Python callables. Request handlers and event callbacks run on
+//! runtime threads and take the GIL while they run; calls that wait for the
+//! network or the event loop release it.
+
+use crate::codec::{ReturnCode, SomeIpHeader};
+use crate::ffi::runtime::Attached;
+use crate::runtime::{error_return_code, ClientContext, HandlerResult, RequestHandler, SomeIpHandle, SomeIpRuntime};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyKeyError, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+create_exception!(_rust_core, ErrorResponse, PyException, "The provider answered with an ERROR message; `args` holds its return code and description.");
+
+/// Offered service whose requests go to a Python callable `handler(method_id, payload)`.
+struct PyHandler {
+    service_id: u16,
+    major_version: u8,
+    minor_version: u32,
+    handler: Py<PyAny>,
+}
+
+impl RequestHandler for PyHandler {
+    fn service_id(&self) -> u16 { self.service_id }
+    fn major_version(&self) -> u8 { self.major_version }
+    fn minor_version(&self) -> u32 { self.minor_version }
+
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        Python::attach(|py| {
+            let handler = self.handler.bind(py);
+            let result = handler.call1((header.method_id, PyBytes::new(py, payload))).and_then(|result| handler_result(&result));
+            result.unwrap_or_else(|e| {
+                e.write_unraisable(py, Some(handler));
+                Err(ReturnCode::NotOk)
+            })
+        })
+    }
+}
+
+/// Response to send for what a Python handler returned: bytes or None for the payload,
+/// or an integer return code (`ReturnCode`) to answer with an ERROR.
+fn handler_result(result: &Bound<'_, PyAny>) -> PyResult<HandlerResult> {
+    if result.is_none() {
+        return Ok(Ok(Some(Vec::new())));
+    }
+    if let Ok(bytes) = result.cast::<PyBytes>() {
+        return Ok(Ok(Some(bytes.as_bytes().to_vec())));
+    }
+    if let Ok(bytes) = result.cast::<PyByteArray>() {
+        return Ok(Ok(Some(bytes.to_vec())));
+    }
+    let code: u8 = result.extract()?;
+    Ok(match ReturnCode::from_u8(code) {
+        Some(ReturnCode::Ok) => Ok(Some(Vec::new())),
+        code => Err(code.unwrap_or(ReturnCode::NotOk)),
+    })
+}
+
+/// Notification handler of a required service that calls `callback(event_id, payload)`.
+struct PyEventCallback {
+    service_id: u16,
+    callback: Py<PyAny>,
+}
+
+impl RequestHandler for PyEventCallback {
+    fn service_id(&self) -> u16 { self.service_id }
+    fn major_version(&self) -> u8 { 0 }
+    fn minor_version(&self) -> u32 { 0 }
+
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        Python::attach(|py| {
+            let callback = self.callback.bind(py);
+            if let Err(e) = callback.call1((header.method_id, PyBytes::new(py, payload))) {
+                e.write_unraisable(py, Some(callback));
+            }
+        });
+        Ok(None)
+    }
+}
+
+/// Python exception for a request that failed with `error`.
+fn request_error(error: std::io::Error) -> PyErr {
+    match error_return_code(&error) {
+        Some(code) => ErrorResponse::new_err((code as u8, code.to_string())),
+        None => error.into(),
+    }
+}
+
+/// The Rust `SomeIpRuntime` of `instance_name` in the config file at `config_path`.
+#[pyclass(name = "RustRuntime", module = "fusion_hawking._rust_core")]
+pub struct PyRuntime {
+    runtime: Arc<SomeIpRuntime>,
+    handle: SomeIpHandle,
+    event_loop: Mutex<Option<JoinHandle<()>>>,
+    /// Awaits the responses of requests sent from Python
+    executor: Arc<tokio::runtime::Runtime>,
+}
+
+impl PyRuntime {
+    fn stop_event_loop(&self) {
+        self.runtime.stop();
+        if let Some(event_loop) = self.event_loop.lock().unwrap().take() {
+            let _ = event_loop.join();
+        }
+    }
+}
+
+impl Drop for PyRuntime {
+    fn drop(&mut self) {
+        self.stop_event_loop();
+    }
+}
+
+#[pymethods]
+impl PyRuntime {
+    /// Raises `ValueError` if the file cannot be read, is invalid, or an endpoint cannot be bound.
+    #[new]
+    fn new(py: Python<'_>, config_path: &str, instance_name: &str) -> PyResult<Self> {
+        let runtime = py.detach(|| SomeIpRuntime::try_load(config_path, instance_name))
+            .map_err(|e| PyValueError::new_err(format!("cannot load instance '{}' from {}: {}", instance_name, config_path, e)))?;
+        let executor = tokio::runtime::Builder::new_multi_thread().worker_threads(1).thread_name("fusion-py").enable_time().build()?;
+        let handle = runtime.handle();
+        Ok(PyRuntime { runtime, handle, event_loop: Mutex::new(None), executor: Arc::new(executor) })
+    }
+
+    /// Run the event loop on a background thread. Returns False if it was already started.
+    fn start(&self) -> bool {
+        let mut event_loop = self.event_loop.lock().unwrap();
+        if event_loop.is_some() {
+            return false;
+        }
+        let runtime = self.runtime.clone();
+        *event_loop = Some(thread::spawn(move || runtime.run()));
+        true
+    }
+
+    /// Stop the event loop and wait for it to end. A stopped runtime cannot be started again.
+    fn stop(&self, py: Python<'_>) {
+        // Handlers waiting for the GIL must be able to finish
+        py.detach(|| self.stop_event_loop());
+    }
+
+    /// Same as `stop`; the runtime is released with the object.
+    fn close(&self, py: Python<'_>) {
+        self.stop(py);
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&self, py: Python<'_>, _exc_type: Py<PyAny>, _exc: Py<PyAny>, _traceback: Py<PyAny>) {
+        self.close(py);
+    }
+
+    /// Offer the provided service `alias`. `handler(method_id, payload)` returns the response
+    /// payload (bytes, or None for an empty one), or a `ReturnCode` to answer with an ERROR.
+    /// Exceptions are answered with E_NOT_OK. Raises `KeyError` for an unknown alias.
+    fn offer_service(&self, alias: &str, handler: Py<PyAny>) -> PyResult<()> {
+        let cfg = self.runtime.instance_config();
+        let Some(svc) = cfg.as_ref().and_then(|cfg| cfg.providing.get(alias)) else {
+            return Err(PyKeyError::new_err(format!("'{}' is not a provided service", alias)));
+        };
+        let handler = PyHandler { service_id: svc.service_id, major_version: svc.major_version, minor_version: svc.minor_version, handler };
+        self.handle.offer_service(alias, Box::new(handler));
+        Ok(())
+    }
+
+    /// Send an event to the subscribers of every eventgroup of the service that carries it.
+    fn fire_event(&self, service_id: u16, event_id: u16, payload: &[u8]) {
+        self.handle.fire_event(service_id, event_id, payload);
+    }
+
+    /// Pass the notifications of `service_id` to `callback(event_id, payload)`.
+    fn on_event(&self, service_id: u16, callback: Py<PyAny>) {
+        self.handle.register_notification_handler(service_id, Box::new(PyEventCallback { service_id, callback }));
+    }
+
+    /// Subscribe to an eventgroup of a remote service on the interface `iface_alias`.
+    #[pyo3(signature = (service_id, instance_id, eventgroup_id, iface_alias, ttl = 0xFFFFFF))]
+    fn subscribe_eventgroup(&self, service_id: u16, instance_id: u16, eventgroup_id: u16, iface_alias: &str, ttl: u32) {
+        self.handle.subscribe_eventgroup(service_id, instance_id, eventgroup_id, ttl, iface_alias);
+    }
+
+    /// Wait for the required service `alias` to be discovered; None if it is not found.
+    fn get_client(&self, py: Python<'_>, alias: &str) -> Option<PyClient> {
+        if !self.runtime.instance_config().is_some_and(|cfg| cfg.required.contains_key(alias)) {
+            return None;
+        }
+        let Attached(context) = py.detach(|| self.handle.get_client::<Attached>(alias))?;
+        Some(PyClient { context, executor: self.executor.clone() })
+    }
+
+    /// Send a request to "ip:port" without discovery and wait for the response payload.
+    /// Raises `TimeoutError` if none arrives within `timeout` seconds or the runtime's own timeout.
+    #[pyo3(signature = (service_id, method_id, address, payload = b"".to_vec(), timeout = None))]
+    fn send_request_to(&self, py: Python<'_>, service_id: u16, method_id: u16, address: &str, payload: Vec<u8>, timeout: Option<f64>) -> PyResult<Py<PyBytes>> {
+        let target: SocketAddr = address.parse().map_err(|_| PyValueError::new_err(format!("'{}' is not an ip:port address", address)))?;
+        let response = py.detach(|| {
+            let request = self.handle.send_request_and_wait(service_id, method_id, &payload, target);
+            match timeout {
+                Some(secs) => self.executor.block_on(async { tokio::time::timeout(Duration::from_secs_f64(secs), request).await.ok().flatten() }),
+                None => self.executor.block_on(request),
+            }
+        });
+        match response {
+            Some(payload) => Ok(PyBytes::new(py, &payload).unbind()),
+            None => Err(PyTimeoutError::new_err(format!("no response from {}", target))),
+        }
+    }
+}
+
+/// Client of one discovered service; see `RustRuntime.get_client`.
+#[pyclass(name = "RustClient", module = "fusion_hawking._rust_core")]
+pub struct PyClient {
+    context: ClientContext,
+    executor: Arc<tokio::runtime::Runtime>,
+}
+
+#[pymethods]
+impl PyClient {
+    /// Send a request and wait for the response payload, with the timeout and retries of the
+    /// alias's QoS profile, or at most `timeout` seconds. Raises `TimeoutError` if no response
+    /// arrives and `ErrorResponse` if the provider answers with an ERROR.
+    #[pyo3(signature = (method_id, payload = b"".to_vec(), timeout = None))]
+    fn request(&self, py: Python<'_>, method_id: u16, payload: Vec<u8>, timeout: Option<f64>) -> PyResult<Py<PyBytes>> {
+        let response = py.detach(|| {
+            let request = self.context.request(method_id, &payload);
+            match timeout {
+                Some(secs) => self.executor.block_on(async {
+                    tokio::time::timeout(Duration::from_secs_f64(secs), request).await
+                        .unwrap_or_else(|_| Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no response within the timeout")))
+                }),
+                None => self.executor.block_on(request),
+            }
+        });
+        response.map(|payload| PyBytes::new(py, &payload).unbind()).map_err(request_error)
+    }
+
+    /// Send a fire-and-forget request (REQUEST_NO_RETURN). Returns False if it was not sent.
+    #[pyo3(signature = (method_id, payload = b"".to_vec()))]
+    fn send(&self, method_id: u16, payload: Vec<u8>) -> bool {
+        self.context.send(method_id, &payload).is_ok()
+    }
+
+    /// Kept for symmetry with `RustRuntime.close`; the client is released with the object.
+    fn close(&self) {}
+}
+
+#[pymodule(name = "_rust_core")]
+mod rust_core {
+    #[pymodule_export]
+    use super::{ErrorResponse, PyClient, PyRuntime};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn eval<'py>(py: Python<'py>, code: &str) -> Bound<'py, PyAny> {
+        py.eval(&CString::new(code).unwrap(), None, None).unwrap()
+    }
+
+    #[test]
+    fn test_python_handler_results() {
+        Python::initialize();
+        let header = |method_id| SomeIpHeader::new(0x4252, method_id, 0x0102, 0x0304, 0x00, 0);
+        let handler = Python::attach(|py| PyHandler {
+            service_id: 0x4252, major_version: 1, minor_version: 0,
+            handler: eval(py, "lambda method_id, payload: [payload[::-1], None, 3, 0, bytearray(b'x'), 'text'][method_id] if method_id < 6 else 1 / 0").unbind(),
+        });
+        assert_eq!(handler.handle(&header(0), &[1, 2]), Ok(Some(vec![2, 1])));
+        assert_eq!(handler.handle(&header(1), &[]), Ok(Some(vec![])));
+        assert_eq!(handler.handle(&header(2), &[]), Err(ReturnCode::UnknownMethod));
+        assert_eq!(handler.handle(&header(3), &[]), Ok(Some(vec![])));
+        assert_eq!(handler.handle(&header(4), &[]), Ok(Some(b"x".to_vec())));
+        // Neither bytes nor a return code, or an exception
+        assert_eq!(handler.handle(&header(5), &[]), Err(ReturnCode::NotOk));
+        assert_eq!(handler.handle(&header(6), &[]), Err(ReturnCode::NotOk));
+    }
+}
@@ -15,7 +15,7 @@ Usage (in an IDL file):
         temperature: float
         pressure: float
 
-    @service(id=0x1001)
+    @service(id=0x1001, eventgroups={"alarms": 1})
     class SensorService:
         @method(id=1)
         def get_reading(self) -> SensorData: ...
@@ -23,20 +23,35 @@ Usage (in an IDL file):
         @method(id=2, fire_and_forget=True)
         def calibrate(self, offset: float): ...
 
-        @event(id=0x8001)
+        @event(id=0x8001, eventgroups=["alarms"])
         def on_threshold_exceeded(self, value: float): ...
 
-        @field(id=1, get_id=0x10, set_id=0x11, notifier_id=0x12)
+        @field(id=1, get_id=0x10, set_id=0x11, notifier_id=0x8012)
         def sample_rate(self) -> int: ...
 
+Wire representations that differ between peers are selected per type with
+@wire, e.g. flags packed into a bit field or a 16-bit enumeration:
+
+    @wire(bools="bits", bool_width=1)
+    @dataclass
+    class Status:
+        ready: bool
+        fault: bool
+
+    @wire(width=2)
+    class Gear(IntEnum):
+        PARK = 0
+        DRIVE = 1
+
 SPDX-License-Identifier: MIT
 Copyright (c) 2026 Fusion Hawking Contributors
 """
 
+import enum
 import inspect
 import typing
 import dataclasses
-from typing import List, Optional, Dict, Any, get_type_hints
+from typing import List, Optional, Dict, Any, Sequence, get_type_hints
 
 
 # =============================================================================
@@ -53,6 +68,7 @@ def resolve_type_info(annotation) -> dict:
       - Sized floats: float32, float64
       - List[T] (recursive)
       - @dataclass structs (by name, with fields introspected)
+      - IntEnum types, as an unsigned integer of their @wire width (default 1 byte)
       - Nested combinations: List[List[int]], List[MyStruct], struct-in-struct
       - None / NoneType for fire-and-forget methods
 
@@ -78,7 +94,11 @@ def resolve_type_info(annotation) -> dict:
                     'name': f.name,
                     'type': resolve_type_info(f.type)
                 })
-            return {'name': annotation.__name__, 'inner': None, 'is_dataclass': True, 'fields': fields}
+            return {'name': annotation.__name__, 'inner': None, 'is_dataclass': True, 'fields': fields,
+                    'wire': getattr(annotation, '_fusion_wire', {})}
+
+        if issubclass(annotation, enum.IntEnum):
+            return {'name': enum_wire_type(annotation), 'inner': None, 'is_dataclass': False, 'enum': annotation.__name__}
 
         mapping = {
             int: 'int', float: 'float', str: 'string', bool: 'bool', bytes: 'bytes'
@@ -95,11 +115,54 @@ def resolve_type_info(annotation) -> dict:
     return {'name': 'Unknown', 'inner': None, 'is_dataclass': False}
 
 
+def enum_wire_type(cls) -> str:
+    """Unsigned integer type an IntEnum is encoded as, checking that every member fits."""
+    width = getattr(cls, '_fusion_wire', {}).get('width', 1)
+    for member in cls:
+        if not 0 <= member.value < (1 << (8 * width)):
+            raise ValueError(f"{cls.__name__}.{member.name} = {member.value} does not fit in {width} byte(s)")
+    return f"uint{8 * width}"
+
+
 # =============================================================================
 # Decorators
 # =============================================================================
 
-def service(id: int, major_version: int = 1, minor_version: int = 0):
+WIRE_WIDTHS = (1, 2, 4, 8)
+
+
+def wire(bools: Optional[str] = None, bool_width: Optional[int] = None, width: Optional[int] = None):
+    """
+    Select the wire representation of a type, to match third-party interface descriptions.
+
+    On a @dataclass:
+        bools: "byte" (default) encodes each bool as an integer of bool_width bytes;
+               "bits" packs consecutive bool fields into bit fields of bool_width bytes,
+               the first field in the least significant bit.
+        bool_width: Width in bytes (1, 2, 4 or 8; default 1)
+
+    On an IntEnum:
+        width: Width in bytes (1, 2, 4 or 8; default 1)
+    """
+    options = {}
+    if bools is not None:
+        if bools not in ("byte", "bits"):
+            raise ValueError(f"bools must be 'byte' or 'bits', not {bools!r}")
+        options['bools'] = bools
+    for key, value in (('bool_width', bool_width), ('width', width)):
+        if value is not None:
+            if value not in WIRE_WIDTHS:
+                raise ValueError(f"{key} must be one of {WIRE_WIDTHS}, not {value!r}")
+            options[key] = value
+
+    def wrapper(cls):
+        cls._fusion_wire = options
+        return cls
+    return wrapper
+
+
+def service(id: int, major_version: int = 1, minor_version: int = 0,
+            eventgroups: Optional[Dict[str, int]] = None):
     """
     Mark a class as a SOME/IP Service.
 
@@ -110,11 +173,13 @@ def service(id: int, major_version: int = 1, minor_version: int = 0):
         id: SOME/IP Service ID (uint16)
         major_version: Major interface version (default 1)
         minor_version: Minor interface version (default 0)
+        eventgroups: Eventgroup IDs by name, for the eventgroups of @event
     """
     def wrapper(cls):
         cls._fusion_service_id = id
         cls._fusion_major = major_version
         cls._fusion_minor = minor_version
+        cls._fusion_eventgroups = dict(eventgroups or {})
         cls._fusion_methods = {}
         cls._fusion_events = {}
         cls._fusion_fields = {}
@@ -174,6 +239,7 @@ def service(id: int, major_version: int = 1, minor_version: int = 0):
                     cls._fusion_events[name] = {
                         'id': fn._fusion_event_id,
                         'args': args,
+                        'eventgroups': list(getattr(fn, '_fusion_eventgroups', [])),
                     }
 
                 elif hasattr(fn, '_fusion_field_id'):
@@ -211,7 +277,7 @@ def method(id: int, fire_and_forget: bool = False):
     return wrapper
 
 
-def event(id: int):
+def event(id: int, eventgroups: Sequence[str] = ()):
     """
     Mark a function as a SOME/IP Event.
 
@@ -220,9 +286,11 @@ def event(id: int):
 
     Args:
         id: Event ID (uint16, typically >= 0x8000 per AUTOSAR)
+        eventgroups: Names of the service's eventgroups carrying the event
     """
     def wrapper(fn):
         fn._fusion_event_id = id
+        fn._fusion_eventgroups = list(eventgroups)
         return fn
     return wrapper
 
@@ -555,7 +555,8 @@ class SomeIpRuntime:
                 if key in self.pending_requests: self.request_results[key] = payload; self.pending_requests.pop(key).set()
             elif sid in self.services:
                 res = self.services[sid].handle({'method_id': mid}, payload)
-                if res:
+                # Fire-and-forget calls are never answered
+                if res and mt != MessageType.REQUEST_NO_RETURN:
                     rc_val = 0
                     pld = res
                     if isinstance(res, tuple):
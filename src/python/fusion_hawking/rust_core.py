@@ -0,0 +1,94 @@
+"""Drive the Rust runtime from Python through its PyO3 extension module.
+
+The module is `fusion_hawking._rust_core`, built into libfusion_hawking with the
+`python` feature (`cargo build --release --features python`). It is imported from
+the package if installed there, otherwise from the library in `target/`. Set
+FUSION_HAWKING_LIB to the library path to use another build.
+
+    rt = RustRuntime("config.json", "python_client")
+    rt.offer_service("math-service", lambda method_id, payload: payload[::-1])
+    rt.start()
+    client = rt.get_client("someipy_svc")
+    print(client.request(0x0001, b"hello"))
+    rt.close()
+
+Handlers and event callbacks run on runtime threads.
+"""
+import importlib.machinery
+import importlib.util
+import os
+import sys
+from typing import Optional
+
+_MODULE = "fusion_hawking._rust_core"
+
+_core = None
+
+
+def _library_candidates():
+    root = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", ".."))
+    if sys.platform == "win32":
+        name = "fusion_hawking.dll"
+    elif sys.platform == "darwin":
+        name = "libfusion_hawking.dylib"
+    else:
+        name = "libfusion_hawking.so"
+    return [os.path.join(root, "target", profile, name) for profile in ("release", "debug")]
+
+
+def find_library() -> Optional[str]:
+    """Path of the Rust runtime library, or None if it has not been built."""
+    return next(iter(_libraries()), None)
+
+
+def _libraries():
+    path = os.environ.get("FUSION_HAWKING_LIB")
+    if path:
+        return [path] if os.path.exists(path) else []
+    return [p for p in _library_candidates() if os.path.exists(p)]
+
+
+def _load_from(path):
+    loader = importlib.machinery.ExtensionFileLoader(_MODULE, path)
+    spec = importlib.util.spec_from_file_location(_MODULE, path, loader=loader)
+    core = importlib.util.module_from_spec(spec)
+    loader.exec_module(core)
+    return core
+
+
+def _load():
+    global _core
+    if _core is not None:
+        return _core
+    try:
+        from . import _rust_core as core
+    except ImportError:
+        core = None
+        # A library built without the python feature has no module to import
+        for path in _libraries():
+            try:
+                core = _load_from(path)
+                break
+            except ImportError:
+                continue
+        if core is None:
+            raise ImportError("no libfusion_hawking with the Python module; run `cargo build --release --features python` or set FUSION_HAWKING_LIB")
+        sys.modules[_MODULE] = core
+    _core = core
+    return core
+
+
+def available() -> bool:
+    """Whether the extension module can be imported."""
+    try:
+        _load()
+    except ImportError:
+        return False
+    return True
+
+
+def __getattr__(name):
+    # RustRuntime, RustClient and ErrorResponse come from the extension module
+    if name in ("RustRuntime", "RustClient", "ErrorResponse"):
+        return getattr(_load(), name)
+    raise AttributeError(f"module {__name__!r} has no attribute {name!r}")
@@ -0,0 +1,190 @@
+//! # Access Control
+//!
+//! Offered services configured with `access` rules only serve the sources the
+//! rules allow. Each rule names a source address or subnet and, optionally, the
+//! methods it may call and the eventgroups it may subscribe to. A request that
+//! no rule allows is answered with an ERROR carrying `E_NOT_OK` (fire-and-forget
+//! calls are dropped); a Subscribe no rule allows is answered with a NACK.
+//! Both are logged and counted as violations of the service's policy.
+
+use super::config::AccessRule;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Address range in CIDR notation; a plain address is a single-host subnet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subnet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Subnet {
+    /// Parse `"10.0.0.0/8"`, `"fd00::/16"` or a plain address.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+            None => (s.parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Subnet { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(&net.octets(), &ip.octets(), self.prefix),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => prefix_matches(&net.octets(), &ip.octets(), self.prefix),
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(net: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let (bytes, bits) = ((prefix / 8) as usize, prefix % 8);
+    if net[..bytes] != ip[..bytes] {
+        return false;
+    }
+    bits == 0 || (net[bytes] ^ ip[bytes]) >> (8 - bits) == 0
+}
+
+struct Rule {
+    source: Subnet,
+    methods: Option<HashSet<u16>>,
+    eventgroups: Option<HashSet<u16>>,
+}
+
+/// Allow-list of one offered service, with its count of refused requests and subscriptions.
+pub struct AccessPolicy {
+    rules: Vec<Rule>,
+    violations: AtomicU64,
+}
+
+impl AccessPolicy {
+    /// Build the policy of a service's rules. Also returns the sources that are not a valid
+    /// address or subnet; their rules are left out.
+    pub fn from_config(rules: &[AccessRule]) -> (Self, Vec<String>) {
+        let mut invalid = Vec::new();
+        let rules = rules.iter().filter_map(|rule| {
+            let Some(source) = Subnet::parse(&rule.source) else {
+                invalid.push(rule.source.clone());
+                return None;
+            };
+            Some(Rule {
+                source,
+                methods: rule.methods.as_ref().map(|m| m.iter().copied().collect()),
+                eventgroups: rule.eventgroups.as_ref().map(|e| e.iter().copied().collect()),
+            })
+        }).collect();
+        (AccessPolicy { rules, violations: AtomicU64::new(0) }, invalid)
+    }
+
+    /// Whether `src` may call `method_id`; counts a violation if not.
+    pub fn allows_method(&self, src: IpAddr, method_id: u16) -> bool {
+        self.check(|rule| rule.source.contains(src) && rule.methods.as_ref().is_none_or(|m| m.contains(&method_id)))
+    }
+
+    /// Whether `src` may subscribe to `eventgroup_id`; counts a violation if not.
+    pub fn allows_eventgroup(&self, src: IpAddr, eventgroup_id: u16) -> bool {
+        self.check(|rule| rule.source.contains(src) && rule.eventgroups.as_ref().is_none_or(|e| e.contains(&eventgroup_id)))
+    }
+
+    fn check(&self, matches: impl Fn(&Rule) -> bool) -> bool {
+        let allowed = self.rules.iter().any(matches);
+        if !allowed {
+            self.violations.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+
+    /// Requests and subscriptions refused so far.
+    pub fn violations(&self) -> u64 {
+        self.violations.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+    use crate::runtime::tests::runtime_with;
+
+    #[test]
+    fn test_subnets() {
+        let net = Subnet::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains("10.1.200.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        // IPv4-mapped sources of dual-stack sockets
+        assert!(net.contains("::ffff:10.1.0.9".parse().unwrap()));
+        assert!(Subnet::parse("192.168.1.0/25").unwrap().contains("192.168.1.127".parse().unwrap()));
+        assert!(!Subnet::parse("192.168.1.0/25").unwrap().contains("192.168.1.128".parse().unwrap()));
+        assert!(Subnet::parse("fd00::/8").unwrap().contains("fd12::1".parse().unwrap()));
+        assert!(Subnet::parse("0.0.0.0/0").unwrap().contains("8.8.8.8".parse().unwrap()));
+        assert!(!Subnet::parse("127.0.0.1").unwrap().contains("127.0.0.2".parse().unwrap()));
+        assert_eq!(Subnet::parse("10.0.0.0/33"), None);
+        assert_eq!(Subnet::parse("eth0"), None);
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let rules: Vec<AccessRule> = serde_json::from_str(r#"[
+            { "source": "10.0.0.0/8", "methods": [1, 2], "eventgroups": [] },
+            { "source": "192.168.1.20" },
+            { "source": "bogus" }
+        ]"#).unwrap();
+        let (policy, invalid) = AccessPolicy::from_config(&rules);
+        assert_eq!(invalid, vec!["bogus".to_string()]);
+
+        let internal: IpAddr = "10.0.0.5".parse().unwrap();
+        let trusted: IpAddr = "192.168.1.20".parse().unwrap();
+        assert!(policy.allows_method(internal, 2));
+        assert!(!policy.allows_method(internal, 3));
+        assert!(!policy.allows_eventgroup(internal, 1));
+        assert!(policy.allows_method(trusted, 3));
+        assert!(policy.allows_eventgroup(trusted, 1));
+        assert!(!policy.allows_method("192.168.1.21".parse().unwrap(), 1));
+        assert_eq!(policy.violations(), 3);
+    }
+
+    #[test]
+    fn test_access_policy_refuses_requests() {
+        use crate::codec::ReturnCode;
+        use crate::runtime::echo::{EchoService, METHOD_DELAY, METHOD_ECHO};
+
+        let runtime = runtime_with("access", r#"{
+            "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "server_ep",
+                "providing": { "echo": { "service_id": 16969, "instance_id": 1, "major_version": 1, "endpoint": "server_ep",
+                    "access": [ { "source": "127.0.0.1/32", "methods": [1] } ] } }
+            } }
+        }"#);
+        runtime.offer_service("echo", Box::new(EchoService::new(0x4249, 1, 0)));
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+        // Returns (message type, return code) of the answer
+        let send = |from: &str, method_id: u16| {
+            let socket = std::net::UdpSocket::bind((from, 0)).unwrap();
+            socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+            let mut msg = SomeIpHeader::new(0x4249, method_id, 0, 1, 0x00, 1).serialize().to_vec();
+            msg.push(7);
+            socket.send_to(&msg, server).unwrap();
+            let mut buf = [0u8; 1500];
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            let answer = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+            (answer.message_type, answer.return_code)
+        };
+
+        assert_eq!(send("127.0.0.1", METHOD_ECHO), (0x80, ReturnCode::Ok as u8));
+        assert_eq!(send("127.0.0.1", METHOD_DELAY), (0x81, ReturnCode::NotOk as u8));
+        assert_eq!(send("127.0.0.2", METHOD_ECHO), (0x81, ReturnCode::NotOk as u8));
+        assert_eq!(runtime.handle().access_violations("echo"), Some(2));
+
+        runtime.stop();
+        event_loop.join().unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+//! # Async Runtime
+//!
+//! [`AsyncSomeIpRuntime`] is the entry point for applications that already run
+//! on tokio. Loading it starts the event loop as a task on tokio's blocking
+//! pool, so the application does not manage a thread for `run()`; offers and
+//! requests are awaited, and notifications arrive as a [`Notifications`]
+//! stream.
+//!
+//! Sockets are still driven by the runtime's own readiness-based event loop
+//! (see [`readiness`](super::readiness)) rather than tokio's reactor, which
+//! keeps the crate free of tokio's `net` feature.
+//!
+//! ```ignore
+//! let runtime = AsyncSomeIpRuntime::load("config.json", "client");
+//! let mut radar = runtime.notifications("radar").unwrap();
+//! let reply = runtime.request("math", 0x0001, &payload).await;
+//! while let Some(event) = radar.next().await { /* ... */ }
+//! runtime.shutdown(Duration::from_secs(1)).await;
+//! ```
+
+use super::{HandlerResult, PendingRequest, RequestHandler, RuntimeError, SomeIpHandle, SomeIpRuntime};
+use crate::codec::SomeIpHeader;
+use crate::logging::FusionLogger;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// A notification received for a required service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub service_id: u16,
+    pub event_id: u16,
+    pub session_id: u16,
+    pub payload: Vec<u8>,
+}
+
+/// Stream of the notifications of one service, in arrival order.
+/// Ends once the runtime is gone or another stream is opened for the same service.
+pub struct Notifications {
+    rx: UnboundedReceiver<Notification>,
+}
+
+impl Notifications {
+    /// Wait for the next notification; `None` once the stream has ended.
+    pub async fn next(&mut self) -> Option<Notification> {
+        self.rx.recv().await
+    }
+
+    /// Poll for the next notification, with the signature of `Stream::poll_next`.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Notification>> {
+        self.rx.poll_recv(cx)
+    }
+
+    /// The next notification if one has already arrived.
+    pub fn try_next(&mut self) -> Option<Notification> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// Notification handler feeding a [`Notifications`] stream.
+struct Forwarder {
+    service_id: u16,
+    major_version: u8,
+    tx: UnboundedSender<Notification>,
+}
+
+impl RequestHandler for Forwarder {
+    fn service_id(&self) -> u16 { self.service_id }
+    fn major_version(&self) -> u8 { self.major_version }
+    fn minor_version(&self) -> u32 { 0 }
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        let _ = self.tx.send(Notification {
+            service_id: header.service_id,
+            event_id: header.method_id,
+            session_id: header.session_id,
+            payload: payload.to_vec(),
+        });
+        Ok(None)
+    }
+}
+
+/// A [`SomeIpRuntime`] whose event loop runs on the current tokio runtime.
+pub struct AsyncSomeIpRuntime {
+    runtime: Arc<SomeIpRuntime>,
+    event_loop: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AsyncSomeIpRuntime {
+    /// Load `instance_name` from `config_path` and start its event loop.
+    /// Must be called from within a tokio runtime.
+    pub fn load(config_path: &str, instance_name: &str) -> Self {
+        Self::start(SomeIpRuntime::load(config_path, instance_name))
+    }
+
+    /// Like [`load`](Self::load), logging through `logger`.
+    pub fn load_with_logger(config_path: &str, instance_name: &str, logger: Arc<dyn FusionLogger>) -> Self {
+        Self::start(SomeIpRuntime::load_with_logger(config_path, instance_name, logger))
+    }
+
+    /// Like [`load`](Self::load), returning configuration and bind errors instead of panicking.
+    pub fn try_load(config_path: &str, instance_name: &str) -> Result<Self, RuntimeError> {
+        SomeIpRuntime::try_load(config_path, instance_name).map(Self::start)
+    }
+
+    /// Like [`try_load`](Self::try_load), logging through `logger`.
+    pub fn try_load_with_logger(config_path: &str, instance_name: &str, logger: Arc<dyn FusionLogger>) -> Result<Self, RuntimeError> {
+        SomeIpRuntime::try_load_with_logger(config_path, instance_name, logger).map(Self::start)
+    }
+
+    fn start(runtime: Arc<SomeIpRuntime>) -> Self {
+        let event_loop = tokio::task::spawn_blocking({
+            let runtime = runtime.clone();
+            move || runtime.run()
+        });
+        AsyncSomeIpRuntime { runtime, event_loop: Mutex::new(Some(event_loop)) }
+    }
+
+    /// Offer the service configured under `alias`; returns once it is registered.
+    pub async fn offer_service(&self, alias: &str, handler: Box<dyn RequestHandler>) {
+        let runtime = self.runtime.clone();
+        let alias = alias.to_string();
+        // Offering takes runtime locks the event loop may be holding
+        let _ = tokio::task::spawn_blocking(move || runtime.offer_service(&alias, handler)).await;
+    }
+
+    /// Send a request to the required service `alias`; see [`SomeIpRuntime::request_service`].
+    pub async fn request(&self, alias: &str, method_id: u16, payload: &[u8]) -> Option<Vec<u8>> {
+        self.runtime.request_service(alias, method_id, payload).await
+    }
+
+    /// Send a request to `target` directly; see [`SomeIpRuntime::send_request_and_wait`].
+    pub async fn request_to(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> Option<Vec<u8>> {
+        self.runtime.send_request_and_wait(service_id, method_id, payload, target).await
+    }
+
+    /// Send a fire-and-forget request to `target`; see [`SomeIpRuntime::send_fire_and_forget`].
+    pub fn send_to(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> std::io::Result<()> {
+        self.runtime.send_fire_and_forget(service_id, method_id, payload, target)
+    }
+
+    /// Send a request to `target` to await with a deadline or cancel; see [`SomeIpRuntime::start_request`].
+    pub async fn start_request(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> std::io::Result<PendingRequest> {
+        self.runtime.start_request(service_id, method_id, payload, target).await
+    }
+
+    /// Stream of notifications of the required service `alias`, or `None` if the alias
+    /// is not configured. Replaces any notification handler registered for that service.
+    pub fn notifications(&self, alias: &str) -> Option<Notifications> {
+        let (service_id, major_version) = {
+            let cfg = self.runtime.instance_config()?;
+            let req = cfg.required.get(alias)?;
+            (req.service_id, req.major_version)
+        };
+        let (tx, rx) = unbounded_channel();
+        self.handle().register_notification_handler(service_id, Box::new(Forwarder { service_id, major_version, tx }));
+        Some(Notifications { rx })
+    }
+
+    /// Synchronous handle for everything else (subscriptions, statistics, interfaces).
+    pub fn handle(&self) -> SomeIpHandle {
+        self.runtime.handle()
+    }
+
+    /// Stop the event loop and wait for it to finish.
+    pub async fn stop(&self) {
+        self.runtime.stop();
+        let event_loop = self.event_loop.lock().unwrap().take();
+        if let Some(event_loop) = event_loop {
+            let _ = event_loop.await;
+        }
+    }
+
+    /// Withdraw offers and subscriptions, then stop; see [`SomeIpRuntime::shutdown`].
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        let runtime = self.runtime.clone();
+        let drained = tokio::task::spawn_blocking(move || runtime.shutdown(timeout)).await.unwrap_or(false);
+        let event_loop = self.event_loop.lock().unwrap().take();
+        if let Some(event_loop) = event_loop {
+            let _ = event_loop.await;
+        }
+        drained
+    }
+}
+
+impl Drop for AsyncSomeIpRuntime {
+    fn drop(&mut self) {
+        if self.event_loop.get_mut().unwrap().is_some() {
+            self.runtime.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+
+    #[test]
+    fn test_async_requests_and_notifications() {
+        let path = std::env::temp_dir().join(format!("fh_async_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{
+            "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "server_ep",
+                "providing": { "echo": { "service_id": 16965, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } },
+                "required": { "radar": { "service_id": 16966, "instance_id": 1, "major_version": 1 } }
+            } }
+        }"#).unwrap();
+        let rt = tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_time().build().unwrap();
+        rt.block_on(async {
+            let runtime = AsyncSomeIpRuntime::load(path.to_str().unwrap(), "test");
+            let _ = std::fs::remove_file(&path);
+            let server: SocketAddr = format!("127.0.0.1:{}", runtime.runtime.bound_port("server_ep").unwrap()).parse().unwrap();
+
+            runtime.offer_service("echo", Box::new(EchoService::new(0x4245, 1, 0))).await;
+            assert_eq!(runtime.request_to(0x4245, METHOD_ECHO, &[1, 2, 3], server).await, Some(vec![1, 2, 3]));
+
+            assert!(runtime.notifications("unknown").is_none());
+            let mut radar = runtime.notifications("radar").unwrap();
+            let mut msg = SomeIpHeader::new(0x4246, 0x8001, 0, 7, 0x02, 2).serialize().to_vec();
+            msg.extend_from_slice(&[9, 9]);
+            // The handler is registered by the event loop; resend until it is in place
+            let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            let mut event = None;
+            for _ in 0..20 {
+                socket.send_to(&msg, server).unwrap();
+                if let Ok(received) = tokio::time::timeout(std::time::Duration::from_millis(100), radar.next()).await {
+                    event = received;
+                    break;
+                }
+            }
+            assert_eq!(event.unwrap(), Notification { service_id: 0x4246, event_id: 0x8001, session_id: 7, payload: vec![9, 9] });
+
+            runtime.stop().await;
+            assert!(runtime.event_loop.lock().unwrap().is_none());
+        });
+    }
+}
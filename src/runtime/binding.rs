@@ -0,0 +1,331 @@
+//! # Endpoint Binding
+//!
+//! Which endpoints an instance binds, and the transports bound for them.
+//! [`plan`] resolves the interfaces and endpoints an [`InstanceConfig`] uses;
+//! [`bind`] opens the socket of one endpoint. `load` binds every planned
+//! endpoint, and `reload_config` binds only those that are new or changed,
+//! keeping the sockets of the rest.
+
+use super::config::{EndpointConfig, InstanceConfig, SystemConfig};
+use super::error::RuntimeError;
+use crate::codec::tp::TpSender;
+use crate::logging::{FusionLogger, LogLevel};
+use crate::transport::{SomeIpTransport, TcpServer, TcpServerTransport, UdpTransport};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Interfaces and endpoints an instance uses.
+pub(crate) struct EndpointPlan {
+    /// Interface aliases, in the order SD listeners are created
+    pub interfaces: Vec<String>,
+    /// Endpoint definitions by name: the global ones and those of `interfaces`
+    pub endpoints: HashMap<String, EndpointConfig>,
+    /// Names of the endpoints to bind, in order; may repeat
+    pub to_bind: Vec<String>,
+}
+
+/// Aliases of the interfaces `instance` uses, defined or not, in the order SD listeners are created.
+pub(crate) fn interfaces(system: &SystemConfig, instance: &InstanceConfig) -> Vec<String> {
+    let mut interfaces: Vec<String> = Vec::new();
+    let used = instance.unicast_bind.keys()
+        .chain(instance.providing.values().flat_map(|svc| svc.offer_on.keys()))
+        .chain(instance.required.values().flat_map(|req| req.find_on.iter()));
+    for iface in used {
+        if !interfaces.contains(iface) { interfaces.push(iface.clone()); }
+    }
+    // Legacy support
+    if interfaces.is_empty() {
+        for iface in &instance.interfaces {
+            if !interfaces.contains(iface) { interfaces.push(iface.clone()); }
+        }
+    }
+    if interfaces.is_empty() && !system.interfaces.is_empty() {
+        // Fallback pattern
+        if system.interfaces.contains_key("primary") {
+            interfaces.push("primary".to_string());
+        } else if let Some(first) = system.interfaces.keys().next() {
+            interfaces.push(first.clone());
+        }
+    }
+    interfaces
+}
+
+/// Resolve what `instance` binds. References to undefined interfaces and endpoints are
+/// skipped; [`SystemConfig::validate`] reports them.
+pub(crate) fn plan(system: &SystemConfig, instance: &InstanceConfig) -> EndpointPlan {
+    let interfaces: Vec<String> = interfaces(system, instance).into_iter()
+        .filter(|alias| system.interfaces.contains_key(alias))
+        .collect();
+    let mut endpoints = system.endpoints.clone();
+    for alias in &interfaces {
+        endpoints.extend(system.interfaces[alias].endpoints.iter().map(|(name, ep)| (name.clone(), ep.clone())));
+    }
+
+    // Control endpoints (unicast_bind), data endpoints (offer_on) and the legacy endpoint
+    let mut to_bind: Vec<String> = instance.unicast_bind.values().cloned().collect();
+    to_bind.extend(instance.providing.values().flat_map(|svc| svc.offer_on.values().cloned()));
+    to_bind.extend(instance.endpoint.iter().cloned());
+
+    // Local endpoints that required services are pinned to. TCP ones are bound per
+    // connection in `get_client` rather than as a server.
+    for req in instance.required.values() {
+        let Some(name) = &req.local_endpoint else { continue };
+        if endpoints.get(name).is_some_and(|ep| !ep.is_stream()) {
+            to_bind.push(name.clone());
+        }
+    }
+
+    EndpointPlan { interfaces, endpoints, to_bind }
+}
+
+/// Socket address of `ep`, or `None` for multicast endpoints, which are joined rather than bound.
+pub(crate) fn unicast_addr(ep: &EndpointConfig) -> Option<SocketAddr> {
+    let ip: IpAddr = ep.ip.parse().ok()?;
+    (!ip.is_multicast()).then_some(SocketAddr::new(ip, ep.port))
+}
+
+/// Bind `ep`: a TCP server, with TLS for a TLS endpoint, or a UDP socket, with DTLS for a
+/// DTLS endpoint, non-blocking.
+pub(crate) fn bind(ep: &EndpointConfig) -> std::io::Result<Arc<dyn SomeIpTransport>> {
+    let addr = unicast_addr(ep).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("'{}' is not a unicast address", ep.ip)))?;
+    if ep.is_stream() {
+        let mut server = TcpServer::bind(addr)?;
+        server.set_magic_cookie_interval(ep.magic_cookie_interval_ms.map(Duration::from_millis));
+        if ep.is_tls() {
+            #[cfg(feature = "tls")]
+            server.set_tls(Some(tls_server(ep)?));
+            #[cfg(not(feature = "tls"))]
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "TLS endpoints need the 'tls' feature"));
+        }
+        let transport = Arc::new(TcpServerTransport::new(server));
+        transport.set_nonblocking(true)?;
+        Ok(transport)
+    } else {
+        let transport = UdpTransport::new(addr)?;
+        // Events sent to multicast groups leave from the endpoint's interface
+        if let IpAddr::V4(v4) = addr.ip() && !v4.is_unspecified() {
+            let _ = transport.set_multicast_if_v4(&v4);
+        }
+        transport.set_nonblocking(true)?;
+        if ep.is_dtls() {
+            #[cfg(feature = "dtls")]
+            return Ok(Arc::new(dtls_transport(ep, transport)?));
+            #[cfg(not(feature = "dtls"))]
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "DTLS endpoints need the 'dtls' feature"));
+        }
+        Ok(Arc::new(transport))
+    }
+}
+
+#[cfg(any(feature = "tls", feature = "dtls"))]
+fn tls_settings(ep: &EndpointConfig) -> std::io::Result<&super::config::TlsConfig> {
+    ep.tls.as_ref().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "TLS endpoint without 'tls' settings"))
+}
+
+#[cfg(any(feature = "tls", feature = "dtls"))]
+fn tls_file<'a>(file: &'a Option<String>, name: &str) -> std::io::Result<&'a std::path::Path> {
+    file.as_deref().map(std::path::Path::new).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("TLS endpoint without 'tls.{}'", name)))
+}
+
+/// Server side of the TLS endpoint `ep`: its `cert` and `key`, and with `client_auth`
+/// clients verified against its `ca`.
+#[cfg(feature = "tls")]
+pub(crate) fn tls_server(ep: &EndpointConfig) -> std::io::Result<Arc<rustls::ServerConfig>> {
+    let tls = tls_settings(ep)?;
+    let client_ca = if tls.client_auth { Some(tls_file(&tls.ca, "ca")?) } else { None };
+    crate::transport::tls::server_config(tls_file(&tls.cert, "cert")?, tls_file(&tls.key, "key")?, client_ca)
+}
+
+/// Client side of a connection to the TLS endpoint `target`: the server is verified
+/// against its `ca` and name, and `identity`, the TLS endpoint the connection is
+/// pinned to, if any, supplies the client certificate.
+#[cfg(feature = "tls")]
+pub(crate) fn tls_client(target: &EndpointConfig, identity: Option<&EndpointConfig>) -> std::io::Result<(Arc<rustls::ClientConfig>, rustls::pki_types::ServerName<'static>)> {
+    let tls = tls_settings(target)?;
+    let identity = match identity.and_then(|ep| ep.tls.as_ref()) {
+        Some(own) if own.cert.is_some() => Some((tls_file(&own.cert, "cert")?, tls_file(&own.key, "key")?)),
+        _ => None,
+    };
+    let config = crate::transport::tls::client_config(tls_file(&tls.ca, "ca")?, identity)?;
+    let name = crate::transport::tls::server_name(tls.server_name.as_deref().unwrap_or(&target.ip))?;
+    Ok((config, name))
+}
+
+/// DTLS over `udp` for the DTLS endpoint `ep`: its `cert` and `key`, peers verified
+/// against its `ca`, and with `client_auth` peers starting a handshake required to
+/// present a certificate.
+#[cfg(feature = "dtls")]
+fn dtls_transport(ep: &EndpointConfig, udp: UdpTransport) -> std::io::Result<crate::transport::DtlsTransport> {
+    let tls = tls_settings(ep)?;
+    let context = crate::transport::dtls::context(tls_file(&tls.cert, "cert")?, tls_file(&tls.key, "key")?, tls_file(&tls.ca, "ca")?)?;
+    let mut transport = crate::transport::DtlsTransport::new(udp, context);
+    transport.set_client_auth(tls.client_auth);
+    transport.set_server_name(tls.server_name.clone());
+    if let Some(mtu) = ep.mtu {
+        transport.set_handshake_mtu(mtu as u32);
+    }
+    Ok(transport)
+}
+
+/// Bind the endpoints named in `to_bind`, reusing the transport of `previous` for every
+/// endpoint whose definition is unchanged. Returns the new bindings and the names bound anew.
+pub(crate) fn bind_all(endpoints: HashMap<String, EndpointConfig>, to_bind: &[String], previous: &Bindings, logger: &dyn FusionLogger) -> Result<(Bindings, Vec<String>), RuntimeError> {
+    let mut bindings = Bindings { endpoints, ..Bindings::default() };
+    let mut bound = Vec::new();
+    for name in to_bind {
+        let Some(ep) = bindings.endpoints.get(name).cloned() else { continue };
+        if bindings.transports.contains_key(name) || unicast_addr(&ep).is_none() {
+            continue;
+        }
+        let kept = previous.transports.get(name).filter(|_| previous.endpoints.get(name) == Some(&ep)).cloned();
+        let transport = match kept.or_else(|| bindings.bound_at(&ep)) {
+            Some(transport) => transport,
+            None => {
+                let what = || format!("endpoint '{}' ({}:{}/{})", name, ep.ip, ep.port, ep.protocol);
+                let transport = bind(&ep).map_err(|e| RuntimeError::bind(what(), e))?;
+                let addr = transport.local_addr().map_err(|e| RuntimeError::bind(what(), e))?;
+                let kind = if ep.is_tls() { "tls server" } else if ep.is_stream() { "tcp server" } else if ep.is_dtls() { "dtls transport" } else { "udp transport" };
+                logger.log(LogLevel::Info, "Runtime", &format!("Bound {} on {}", kind, addr));
+                bound.push(name.clone());
+                transport
+            }
+        };
+        bindings.insert(name, &ep, transport);
+    }
+    Ok((bindings, bound))
+}
+
+/// Endpoints and the transports bound for them, replaced as a whole by `reload_config`.
+#[derive(Clone, Default)]
+pub(crate) struct Bindings {
+    pub endpoints: HashMap<String, EndpointConfig>,
+    /// Maps endpoint names to their actual bound ports (resolves ephemeral port 0)
+    pub ports: HashMap<String, u16>,
+    /// Transport of each bound endpoint name; names with the same address share one
+    pub transports: HashMap<String, Arc<dyn SomeIpTransport>>,
+    pub udp: Vec<Arc<dyn SomeIpTransport>>,
+    pub tcp: Vec<Arc<dyn SomeIpTransport>>,
+    /// Transports of DTLS endpoints, also listed in `udp`
+    pub dtls: Vec<Arc<dyn SomeIpTransport>>,
+}
+
+impl Bindings {
+    /// Record `transport` as bound for `name`, listing it once per socket.
+    pub fn insert(&mut self, name: &str, ep: &EndpointConfig, transport: Arc<dyn SomeIpTransport>) {
+        if let Ok(addr) = transport.local_addr() {
+            self.ports.insert(name.to_string(), addr.port());
+        }
+        let list = if ep.is_stream() { &mut self.tcp } else { &mut self.udp };
+        if !list.iter().any(|t| Arc::ptr_eq(t, &transport)) {
+            list.push(transport.clone());
+        }
+        if ep.is_dtls() && !self.dtls.iter().any(|t| Arc::ptr_eq(t, &transport)) {
+            self.dtls.push(transport.clone());
+        }
+        self.transports.insert(name.to_string(), transport);
+    }
+
+    /// How to send from the UDP endpoints bound at `local`. Where several share the socket,
+    /// the most cautious setting of each wins.
+    pub fn tp_sender(&self, local: SocketAddr) -> TpSender {
+        let endpoints: Vec<&EndpointConfig> = self.transports.iter()
+            .filter(|(_, transport)| transport.local_addr().is_ok_and(|addr| addr == local))
+            .filter_map(|(name, _)| self.endpoints.get(name))
+            .filter(|ep| !ep.is_stream())
+            .collect();
+        let default = TpSender::default();
+        TpSender {
+            mtu: endpoints.iter().filter_map(|ep| ep.mtu).min().unwrap_or(default.mtu),
+            pacing: endpoints.iter().filter_map(|ep| ep.tp_pacing_us).max().map(Duration::from_micros),
+            burst: endpoints.iter().filter_map(|ep| ep.tp_burst).min().unwrap_or(default.burst),
+            adaptive: endpoints.iter().any(|ep| ep.tp_adaptive),
+        }
+    }
+
+    /// Whether `transport` runs DTLS.
+    pub fn is_dtls(&self, transport: &Arc<dyn SomeIpTransport>) -> bool {
+        self.dtls.iter().any(|t| Arc::ptr_eq(t, transport))
+    }
+
+    /// Transport already bound for an endpoint with the same address as `ep`, under another name.
+    pub fn bound_at(&self, ep: &EndpointConfig) -> Option<Arc<dyn SomeIpTransport>> {
+        // Ephemeral ports are never shared
+        if ep.port == 0 {
+            return None;
+        }
+        self.transports.iter()
+            .find(|(name, _)| self.endpoints.get(*name).is_some_and(|other| other.ip == ep.ip && other.port == ep.port && other.protocol.eq_ignore_ascii_case(&ep.protocol)))
+            .map(|(_, transport)| transport.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    fn system(json: &str) -> SystemConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_plan_collects_interfaces_and_endpoints() {
+        let system = system(r#"{
+            "endpoints": { "legacy": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "interfaces": {
+                "lo": { "name": "lo", "endpoints": {
+                    "ctrl": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" },
+                    "data": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "tcp" } } },
+                "unused": { "name": "eth9", "endpoints": {} }
+            },
+            "instances": { "test": {
+                "unicast_bind": { "lo": "ctrl" },
+                "providing": { "svc": { "service_id": 1, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "data" } } },
+                "endpoint": "legacy"
+            } }
+        }"#);
+        let plan = plan(&system, &system.instances["test"]);
+        assert_eq!(plan.interfaces, ["lo"]);
+        assert_eq!(plan.endpoints.len(), 3);
+        assert_eq!(plan.to_bind, ["ctrl", "data", "legacy"]);
+
+        // Undefined interfaces are left to validation
+        let mut broken = system.instances["test"].clone();
+        broken.unicast_bind.insert("eth1".to_string(), "ctrl".to_string());
+        assert_eq!(super::plan(&system, &broken).interfaces, ["lo"]);
+    }
+
+    #[test]
+    fn test_client_pinned_to_local_endpoint() {
+        let runtime = runtime_with("local_ep", r#"{
+            "endpoints": {
+                "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" },
+                "client_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" }
+            },
+            "instances": { "test": {
+                "endpoint": "server_ep",
+                "required": {
+                    "pinned": { "service_id": 4660, "instance_id": 1, "major_version": 1, "local_endpoint": "client_ep" },
+                    "plain": { "service_id": 4660, "instance_id": 1, "major_version": 1 }
+                }
+            } }
+        }"#);
+        let local = runtime.local_endpoint_addr("pinned").unwrap();
+        assert_eq!(local.port(), runtime.bound_port("client_ep").unwrap());
+        assert!(runtime.local_endpoint_addr("plain").is_none());
+
+        let target = "127.0.0.1:30509".parse().unwrap();
+        let transport = runtime.pinned_transport(local, target, 0x11).unwrap();
+        assert_eq!(transport.local_addr().unwrap(), local);
+    }
+
+    #[test]
+    #[should_panic(expected = "instances.test.required.pinned.local_endpoint: endpoint 'missing' not found")]
+    fn test_unknown_local_endpoint_rejected() {
+        runtime_with("local_ep_missing", r#"{ "instances": { "test": { "required": {
+            "pinned": { "service_id": 4660, "instance_id": 1, "major_version": 1, "local_endpoint": "missing" }
+        } } } }"#);
+    }
+}
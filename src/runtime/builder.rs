@@ -0,0 +1,162 @@
+//! # Runtime Builder
+//!
+//! [`SomeIpRuntimeBuilder`] configures a runtime in code instead of a
+//! `config.json`, for test harnesses and deployments that compute their
+//! configuration. It assembles the same [`SystemConfig`] a file would hold,
+//! so everything a file can express is available, and validates it like
+//! `try_load` does before binding.
+//!
+//! ```ignore
+//! let runtime = SomeIpRuntime::builder("server")
+//!     .interface("lo", InterfaceConfig::new("lo")
+//!         .endpoint("sd", EndpointConfig::udp("224.224.224.245", 30490))
+//!         .endpoint("data", EndpointConfig::udp("127.0.0.1", 0))
+//!         .sd_v4("sd"))
+//!     .unicast_bind("lo", "data")
+//!     .provide("math", ServiceConfig::new(0x1001, 1, 1).offer_on("lo", "data"))
+//!     .require("sort", ClientConfig::new(0x1002, 1, 1).find_on("lo"))
+//!     .sd(SdConfig { cyclic_delay_ms: 500, ..SdConfig::default() })
+//!     .build()?;
+//! ```
+
+use super::config::{ClientConfig, EndpointConfig, InstanceConfig, InterfaceConfig, QosProfile, SdConfig, ServiceConfig, SystemConfig};
+use super::error::RuntimeError;
+use super::SomeIpRuntime;
+use crate::logging::{ConsoleLogger, FusionLogger, LogLevel};
+use std::sync::Arc;
+
+/// Configuration of one runtime instance, assembled in code.
+pub struct SomeIpRuntimeBuilder {
+    instance_name: String,
+    system: SystemConfig,
+    instance: InstanceConfig,
+    logger: Option<Arc<dyn FusionLogger>>,
+}
+
+impl SomeIpRuntimeBuilder {
+    pub fn new(instance_name: &str) -> Self {
+        SomeIpRuntimeBuilder {
+            instance_name: instance_name.to_string(),
+            system: SystemConfig::default(),
+            instance: InstanceConfig::default(),
+            logger: None,
+        }
+    }
+
+    /// Endpoint not tied to an interface (`endpoints` of a config file).
+    pub fn endpoint(mut self, name: &str, endpoint: EndpointConfig) -> Self {
+        self.system.endpoints.insert(name.to_string(), endpoint);
+        self
+    }
+
+    pub fn interface(mut self, alias: &str, interface: InterfaceConfig) -> Self {
+        self.system.interfaces.insert(alias.to_string(), interface);
+        self
+    }
+
+    pub fn qos_profile(mut self, name: &str, profile: QosProfile) -> Self {
+        self.system.qos_profiles.insert(name.to_string(), profile);
+        self
+    }
+
+    /// Bind the control endpoint `endpoint` on interface `iface`.
+    pub fn unicast_bind(mut self, iface: &str, endpoint: &str) -> Self {
+        self.instance.unicast_bind.insert(iface.to_string(), endpoint.to_string());
+        self
+    }
+
+    /// Service offered under `alias`, as passed to `offer_service`.
+    pub fn provide(mut self, alias: &str, service: ServiceConfig) -> Self {
+        self.instance.providing.insert(alias.to_string(), service);
+        self
+    }
+
+    /// Service used under `alias`, as passed to `get_client`.
+    pub fn require(mut self, alias: &str, client: ClientConfig) -> Self {
+        self.instance.required.insert(alias.to_string(), client);
+        self
+    }
+
+    /// Service Discovery timing and limits.
+    pub fn sd(mut self, sd: SdConfig) -> Self {
+        self.instance.sd = sd;
+        self
+    }
+
+    /// Set any other instance setting (TP limits, worker threads, logging, ...).
+    pub fn configure(mut self, f: impl FnOnce(&mut InstanceConfig)) -> Self {
+        f(&mut self.instance);
+        self
+    }
+
+    /// Log through `logger` instead of the console.
+    pub fn logger(mut self, logger: Arc<dyn FusionLogger>) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// The configuration assembled so far, as it would appear in a file.
+    pub fn config(&self) -> SystemConfig {
+        let mut system = self.system.clone();
+        system.instances.insert(self.instance_name.clone(), self.instance.clone());
+        system
+    }
+
+    /// Validate the configuration and bind the runtime, as `try_load` does for a file.
+    pub fn build(self) -> Result<Arc<SomeIpRuntime>, RuntimeError> {
+        let system = self.config();
+        let logger = SomeIpRuntime::instance_logger(self.logger.unwrap_or_else(|| ConsoleLogger::new()), &self.instance_name);
+        logger.log(LogLevel::Info, "Runtime", "Building config in code");
+        system.validate(&self.instance_name)?;
+        SomeIpRuntime::from_config(system, &self.instance_name, logger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{ConfigError, ConfigIssue};
+
+    #[test]
+    fn test_builder_matches_file_config() {
+        let builder = SomeIpRuntimeBuilder::new("server")
+            .interface("lo", InterfaceConfig::new("lo")
+                .endpoint("sd", EndpointConfig::udp("224.224.224.245", 30490))
+                .endpoint("data", EndpointConfig::tcp("::1", 0))
+                .sd_v4("sd"))
+            .unicast_bind("lo", "data")
+            .provide("math", ServiceConfig::new(0x1001, 1, 1).offer_on("lo", "data"))
+            .require("sort", ClientConfig::new(0x1002, 1, 1).find_on("lo"))
+            .configure(|instance| instance.worker_threads = Some(2));
+        let file: SystemConfig = serde_json::from_str(r#"{
+            "interfaces": { "lo": { "name": "lo", "sd": { "endpoint_v4": "sd" }, "endpoints": {
+                "sd": { "ip": "224.224.224.245", "port": 30490, "version": 4, "protocol": "udp" },
+                "data": { "ip": "::1", "port": 0, "version": 6, "protocol": "tcp" } } } },
+            "instances": { "server": {
+                "unicast_bind": { "lo": "data" },
+                "providing": { "math": { "service_id": 4097, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "data" } } },
+                "required": { "sort": { "service_id": 4098, "instance_id": 1, "major_version": 1, "find_on": ["lo"] } },
+                "worker_threads": 2
+            } }
+        }"#).unwrap();
+        assert_eq!(builder.config(), file);
+    }
+
+    #[test]
+    fn test_build_binds_and_validates() {
+        let runtime = SomeIpRuntimeBuilder::new("test")
+            .endpoint("server_ep", EndpointConfig::udp("127.0.0.1", 0))
+            .provide("echo", ServiceConfig::new(0x4248, 1, 1))
+            .configure(|instance| instance.endpoint = Some("server_ep".to_string()))
+            .build()
+            .unwrap();
+        assert!(runtime.bound_port("server_ep").is_some_and(|port| port != 0));
+        assert!(runtime.instance_config().unwrap().providing.contains_key("echo"));
+
+        let result = SomeIpRuntimeBuilder::new("test")
+            .require("ctrl", ClientConfig { qos: Some("fast".to_string()), ..ClientConfig::new(0x1234, 1, 1) })
+            .build();
+        let Err(RuntimeError::Config(ConfigError::Invalid(issues))) = result else { panic!("expected an invalid config") };
+        assert!(matches!(&issues[..], [ConfigIssue::UnknownQosProfile { name, .. }] if name == "fast"));
+    }
+}
@@ -0,0 +1,824 @@
+//! # Client Requests
+//!
+//! [`ClientContext`] lets a client proxy obtained from `get_client` send a
+//! request and await its response through the runtime: the runtime assigns
+//! the instance's Client ID and the next Session ID, segments large payloads with SOME/IP-TP, matches the
+//! response in its event loop and applies the service's [`RequestPolicy`].
+//! Generated clients are built around it by `get_client` and expose one
+//! `async` method per IDL method; responses are only received while `run()`
+//! is running.
+//!
+//! A [`RequestPolicy`] comes from the required service's `request_retry`, or
+//! else from the timeout of its QoS profile. Only methods marked `idempotent`
+//! in `request_retry` are retried: a request whose response was lost may
+//! still have been executed, and sending it again must be harmless.
+//!
+//! To abort a slow call, start it as a [`PendingRequest`] instead: it is sent
+//! once and awaited until its own deadline, and a [`RequestHandle`] cancels it
+//! from elsewhere, e.g. when the caller's deadline upstream has passed.
+//! Cancelling, timing out or dropping it forgets the request in the runtime,
+//! along with any response segments received so far.
+//!
+//! A provider's ERROR response completes the request at once: it fails with
+//! `Other`, and [`error_return_code`] recovers the provider's [`ReturnCode`].
+//! Such a request is not retried, since the provider did receive it.
+//!
+//! A required service with a `request_limit` has at most `max_in_flight`
+//! requests awaiting their response. Beyond that, requests fail with
+//! `WouldBlock`, or wait for a slot if the limit is set to `wait`.
+//!
+//! Applications without an async executor call `call_blocking` instead: the
+//! calling thread waits on a [`ResponseSlot`] in the same pending-request
+//! table, which the event loop fills and signals through a condvar.
+
+use super::config::{QosProfile, RequestLimitConfig, RequestRetryConfig};
+use super::{Route, SomeIpRuntime};
+use crate::codec::{RequestId, ReturnCode, SomeIpDeserialize};
+use crate::transport::SomeIpTransport;
+use std::collections::HashSet;
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::task::{Context, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How requests to one service wait for their response and are retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestPolicy {
+    /// How long each attempt waits for the response
+    pub timeout: Duration,
+    /// Further attempts after a timeout, of methods that may be retried
+    pub max_retries: u32,
+    /// Pause before the first retry; doubles before each further one
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Methods that may be retried; `None` retries every method
+    pub idempotent: Option<HashSet<u16>>,
+}
+
+impl RequestPolicy {
+    /// Build the policy from a `request_retry` entry. Method keys may be decimal or
+    /// `0x`-prefixed hex; unparsable keys are returned so the caller can report them.
+    pub fn from_config(cfg: &RequestRetryConfig) -> (Self, Vec<String>) {
+        let mut invalid = Vec::new();
+        let mut idempotent = HashSet::new();
+        for (key, method) in &cfg.methods {
+            let parsed = match key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+                Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                None => key.parse::<u16>().ok(),
+            };
+            match parsed {
+                Some(method_id) if method.idempotent => { idempotent.insert(method_id); }
+                Some(_) => {}
+                None => invalid.push(key.clone()),
+            }
+        }
+        let policy = RequestPolicy {
+            timeout: Duration::from_millis(cfg.timeout_ms),
+            max_retries: cfg.max_retries,
+            backoff: Duration::from_millis(cfg.backoff_ms),
+            max_backoff: Duration::from_millis(cfg.max_backoff_ms),
+            idempotent: Some(idempotent),
+        };
+        (policy, invalid)
+    }
+
+    /// The timeout of a QoS profile. Requests are sent once, since no method is marked idempotent.
+    pub fn from_qos(qos: &QosProfile) -> Self {
+        Self::once(Duration::from_millis(qos.request_timeout_ms))
+    }
+
+    /// A single attempt waiting at most `timeout`.
+    pub fn once(timeout: Duration) -> Self {
+        RequestPolicy { timeout, max_retries: 0, backoff: Duration::ZERO, max_backoff: Duration::ZERO, idempotent: None }
+    }
+
+    /// Attempts a request for `method_id` gets: one, plus the retries if it may be retried.
+    pub fn attempts(&self, method_id: u16) -> u32 {
+        let retryable = self.idempotent.as_ref().is_none_or(|methods| methods.contains(&method_id));
+        1 + if retryable { self.max_retries } else { 0 }
+    }
+
+    /// Pause before retry number `retry` (1 for the first).
+    pub fn backoff_before(&self, retry: u32) -> Duration {
+        let doublings = retry.saturating_sub(1).min(31);
+        self.backoff.saturating_mul(1 << doublings).min(self.max_backoff.max(self.backoff))
+    }
+}
+
+/// Bound on the outstanding requests to one required service.
+pub(crate) struct RequestLimit {
+    slots: Arc<Semaphore>,
+    max_in_flight: u32,
+    wait: bool,
+}
+
+impl RequestLimit {
+    pub fn new(cfg: &RequestLimitConfig) -> Self {
+        RequestLimit { slots: Arc::new(Semaphore::new(cfg.max_in_flight as usize)), max_in_flight: cfg.max_in_flight, wait: cfg.wait }
+    }
+
+    pub fn max_in_flight(&self) -> u32 {
+        self.max_in_flight
+    }
+
+    /// Take a slot, held until the request is dropped: waiting for one if configured to,
+    /// else `None` if all are taken.
+    pub async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        if self.wait {
+            self.slots.clone().acquire_owned().await.ok()
+        } else {
+            self.slots.clone().try_acquire_owned().ok()
+        }
+    }
+
+    /// Like [`acquire`](Self::acquire), blocking the thread until `deadline` at the most.
+    pub fn acquire_until(&self, deadline: Instant) -> Option<OwnedSemaphorePermit> {
+        if self.wait {
+            block_until(self.slots.clone().acquire_owned(), deadline)?.ok()
+        } else {
+            self.slots.clone().try_acquire_owned().ok()
+        }
+    }
+}
+
+/// Wakes a thread parked in [`block_until`].
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Poll `future` on this thread until it completes, or give up at `deadline`. Only for
+/// futures that need no tokio driver, like acquiring a semaphore.
+fn block_until<F: Future>(future: F, deadline: Instant) -> Option<F::Output> {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        thread::park_timeout(deadline - now);
+    }
+}
+
+#[derive(Default)]
+enum SlotState {
+    #[default]
+    Waiting,
+    Answered(std::result::Result<Vec<u8>, ReturnCode>),
+    /// Removed from the pending requests without a response
+    Closed,
+}
+
+#[derive(Default)]
+struct Slot {
+    state: Mutex<SlotState>,
+    ready: Condvar,
+}
+
+/// Receives the response of a request for a thread blocked in [`BlockingResponse::wait_until`].
+/// Dropping it unanswered wakes the thread, as dropping a oneshot sender does.
+pub(crate) struct ResponseSlot(Arc<Slot>);
+
+/// The waiting side of a [`ResponseSlot`].
+pub(crate) struct BlockingResponse(Arc<Slot>);
+
+/// A slot for the response of one request and the side that waits for it.
+pub(crate) fn response_slot() -> (ResponseSlot, BlockingResponse) {
+    let slot = Arc::new(Slot::default());
+    (ResponseSlot(slot.clone()), BlockingResponse(slot))
+}
+
+impl ResponseSlot {
+    pub fn send(self, response: std::result::Result<Vec<u8>, ReturnCode>) {
+        *self.0.state.lock().unwrap() = SlotState::Answered(response);
+        self.0.ready.notify_one();
+    }
+}
+
+impl Drop for ResponseSlot {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        if matches!(*state, SlotState::Waiting) {
+            *state = SlotState::Closed;
+            self.0.ready.notify_one();
+        }
+    }
+}
+
+impl BlockingResponse {
+    /// Block until the response or the provider's error arrives, fails with `Timeout` at
+    /// `deadline`, or with `Disconnected` once the request is dropped unanswered.
+    pub fn wait_until(&self, deadline: Instant) -> std::result::Result<std::result::Result<Vec<u8>, ReturnCode>, RecvTimeoutError> {
+        let mut state = self.0.state.lock().unwrap();
+        loop {
+            match std::mem::take(&mut *state) {
+                SlotState::Answered(response) => return Ok(response),
+                SlotState::Closed => return Err(RecvTimeoutError::Disconnected),
+                SlotState::Waiting => {}
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self.0.ready.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+}
+
+/// A request that was sent and whose response has not been awaited yet. Dropping it
+/// abandons the request.
+pub struct PendingRequest {
+    handle: RequestHandle,
+    response: tokio::sync::oneshot::Receiver<std::result::Result<Vec<u8>, ReturnCode>>,
+    deadline: Instant,
+    /// Slot of the service's `request_limit`, freed with the request
+    _slot: Option<OwnedSemaphorePermit>,
+}
+
+impl PendingRequest {
+    pub(super) fn new(handle: RequestHandle, response: tokio::sync::oneshot::Receiver<std::result::Result<Vec<u8>, ReturnCode>>, timeout: Duration, slot: Option<OwnedSemaphorePermit>) -> Self {
+        PendingRequest { handle, response, deadline: Instant::now() + timeout, _slot: slot }
+    }
+
+    pub fn request_id(&self) -> RequestId {
+        self.handle.request_id
+    }
+
+    /// When waiting for the response gives up.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Wait until `deadline` instead.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Wait at most `timeout` from now instead.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// A handle to cancel this request while its response is awaited.
+    pub fn handle(&self) -> RequestHandle {
+        self.handle.clone()
+    }
+
+    /// Wait for the response payload. Fails with `TimedOut` once the deadline passes,
+    /// `Interrupted` if the request was cancelled, `NotConnected` once the runtime has been
+    /// dropped, and `Other` if the response was refused (e.g. by its E2E check) or the provider
+    /// answered with an error, whose return code [`error_return_code`] recovers.
+    pub async fn response(mut self) -> Result<Vec<u8>> {
+        let (service_id, method_id, target) = (self.handle.service_id, self.handle.method_id, self.handle.target);
+        match tokio::time::timeout_at(self.deadline.into(), &mut self.response).await {
+            Ok(Ok(Ok(payload))) => Ok(payload),
+            Ok(Ok(Err(code))) => Err(Error::other(code)),
+            Ok(Err(_)) if self.handle.is_cancelled() => {
+                Err(Error::new(ErrorKind::Interrupted, format!("request for method 0x{:04x} of service 0x{:04x} was cancelled", method_id, service_id)))
+            }
+            Ok(Err(_)) if self.handle.runtime.strong_count() == 0 => Err(Error::new(ErrorKind::NotConnected, "runtime has been dropped")),
+            Ok(Err(_)) => Err(Error::other(format!("response from {} to method 0x{:04x} of service 0x{:04x} was refused", target, method_id, service_id))),
+            Err(_) => {
+                if let Some(runtime) = self.handle.runtime.upgrade() {
+                    runtime.counters.record_timeout();
+                }
+                Err(Error::new(ErrorKind::TimedOut, format!("no response from {} to method 0x{:04x} of service 0x{:04x}", target, method_id, service_id)))
+            }
+        }
+    }
+}
+
+/// The return code of the provider's ERROR response that failed a request, or `None` if the
+/// request failed for another reason.
+pub fn error_return_code(error: &Error) -> Option<ReturnCode> {
+    error.get_ref()?.downcast_ref::<ReturnCode>().copied()
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        self.handle.forget();
+    }
+}
+
+/// Cancels a [`PendingRequest`]; cheap to clone and send to other tasks.
+#[derive(Clone)]
+pub struct RequestHandle {
+    runtime: Weak<SomeIpRuntime>,
+    service_id: u16,
+    method_id: u16,
+    request_id: RequestId,
+    target: SocketAddr,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RequestHandle {
+    pub(super) fn new(runtime: Weak<SomeIpRuntime>, service_id: u16, method_id: u16, request_id: RequestId, target: SocketAddr) -> Self {
+        RequestHandle { runtime, service_id, method_id, request_id, target, cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    pub fn request_id(&self) -> RequestId {
+        self.request_id
+    }
+
+    /// Stop waiting for the response: the awaiting [`PendingRequest::response`] fails with
+    /// `Interrupted`, and a response arriving later is dropped. Returns whether the request
+    /// was still outstanding.
+    pub fn cancel(&self) -> bool {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.forget()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn forget(&self) -> bool {
+        self.runtime.upgrade().is_some_and(|runtime| runtime.abandon_request(self.service_id, self.method_id, self.request_id, self.target))
+    }
+}
+
+/// Request path of one client proxy to one discovered service instance.
+#[derive(Clone)]
+pub struct ClientContext {
+    runtime: Weak<SomeIpRuntime>,
+    /// Required service the client was created for
+    alias: String,
+    service_id: u16,
+    instance_id: u16,
+    target: SocketAddr,
+    /// Transport chosen by `get_client`: pooled TCP connection, pinned, shared-memory or default UDP
+    transport: Arc<dyn SomeIpTransport>,
+    route: Route,
+    policy: RequestPolicy,
+}
+
+impl ClientContext {
+    pub(super) fn new(runtime: Weak<SomeIpRuntime>, alias: &str, (service_id, instance_id): (u16, u16), target: SocketAddr, transport: Arc<dyn SomeIpTransport>, route: Route, policy: RequestPolicy) -> Self {
+        ClientContext { runtime, alias: alias.to_string(), service_id, instance_id, target, transport, route, policy }
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// Local address requests are sent from.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+
+    /// Send a request for `method_id` and wait for the response payload.
+    /// Fails with `TimedOut` if no response arrives within the timeout on any attempt,
+    /// with `WouldBlock` if the service's `request_limit` is reached, and with
+    /// `NotConnected` once the runtime has been dropped.
+    pub async fn request(&self, method_id: u16, payload: &[u8]) -> Result<Vec<u8>> {
+        let runtime = self.runtime.upgrade().ok_or_else(|| Error::new(ErrorKind::NotConnected, "runtime has been dropped"))?;
+        runtime.send_request_with_policy((self.service_id, self.instance_id), method_id, payload, self.target, self.route.clone(), &self.policy).await
+    }
+
+    /// Send a request for `method_id` once, to await or cancel; it waits at most the
+    /// policy's timeout unless given another deadline. Fails as
+    /// [`SomeIpRuntime::start_request`] does, and with `NotConnected` once the runtime has
+    /// been dropped.
+    pub async fn start(&self, method_id: u16, payload: &[u8]) -> Result<PendingRequest> {
+        let runtime = self.runtime.upgrade().ok_or_else(|| Error::new(ErrorKind::NotConnected, "runtime has been dropped"))?;
+        runtime.start_request_via((self.service_id, self.instance_id), method_id, payload, self.target, self.route.clone(), self.policy.timeout).await
+    }
+
+    /// Send a fire-and-forget request (REQUEST_NO_RETURN) for `method_id`.
+    /// Fails with `NotConnected` once the runtime has been dropped.
+    pub fn send(&self, method_id: u16, payload: &[u8]) -> Result<()> {
+        let runtime = self.runtime.upgrade().ok_or_else(|| Error::new(ErrorKind::NotConnected, "runtime has been dropped"))?;
+        runtime.send_fire_and_forget_via((self.service_id, self.instance_id), method_id, payload, self.target, self.route.clone())
+    }
+
+    /// Subscribe to `eventgroup_id` with the instance and interface configured for the
+    /// required service; see [`SomeIpRuntime::subscribe_required`]. Fails with
+    /// `NotConnected` once the runtime has been dropped.
+    pub fn subscribe(&self, eventgroup_id: u16, ttl: u32) -> Result<()> {
+        let runtime = self.runtime.upgrade().ok_or_else(|| Error::new(ErrorKind::NotConnected, "runtime has been dropped"))?;
+        runtime.subscribe_required(&self.alias, eventgroup_id, ttl)
+    }
+
+    /// Call `callback` with each notification of `event_id`, decoded as `T`; see
+    /// [`SomeIpRuntime::on_event`]. Fails with `NotConnected` once the runtime has been dropped.
+    pub fn on_event<T, F>(&self, event_id: u16, callback: F) -> Result<()>
+    where
+        T: SomeIpDeserialize + 'static,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let runtime = self.runtime.upgrade().ok_or_else(|| Error::new(ErrorKind::NotConnected, "runtime has been dropped"))?;
+        runtime.on_event(self.service_id, event_id, callback);
+        Ok(())
+    }
+
+    /// Client ID of the runtime's requests.
+    pub fn client_id(&self) -> Option<u16> {
+        self.runtime.upgrade().map(|r| r.client_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+    use crate::runtime::tests::runtime_with;
+    use crate::runtime::ServiceClient;
+
+    #[test]
+    fn test_request_policy_retries_idempotent_methods() {
+        let cfg: RequestRetryConfig = serde_json::from_str(r#"{
+            "timeout_ms": 500, "max_retries": 4, "backoff_ms": 100, "max_backoff_ms": 300,
+            "methods": { "0x0001": { "idempotent": true }, "2": { "idempotent": false }, "get": { "idempotent": true } }
+        }"#).unwrap();
+        let (policy, invalid) = RequestPolicy::from_config(&cfg);
+        assert_eq!(invalid, vec!["get".to_string()]);
+        assert_eq!(policy.timeout, Duration::from_millis(500));
+        assert_eq!(policy.attempts(0x0001), 5);
+        // Marked non-idempotent, or not listed at all
+        assert_eq!(policy.attempts(0x0002), 1);
+        assert_eq!(policy.attempts(0x0003), 1);
+
+        let pauses: Vec<u64> = (1..=4).map(|retry| policy.backoff_before(retry).as_millis() as u64).collect();
+        assert_eq!(pauses, vec![100, 200, 300, 300]);
+
+        // A QoS profile only sets the timeout
+        let qos = QosProfile { request_timeout_ms: 300, ..QosProfile::default() };
+        let policy = RequestPolicy::from_qos(&qos);
+        assert_eq!((policy.attempts(0x0003), policy.timeout), (1, Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn test_response_slot_wakes_blocked_thread() {
+        let deadline = || Instant::now() + Duration::from_secs(5);
+        let (reply, response) = response_slot();
+        let sender = thread::spawn(move || reply.send(Ok(vec![1, 2])));
+        assert_eq!(response.wait_until(deadline()), Ok(Ok(vec![1, 2])));
+        sender.join().unwrap();
+
+        let (reply, response) = response_slot();
+        thread::spawn(move || reply.send(Err(ReturnCode::NotReady)));
+        assert_eq!(response.wait_until(deadline()), Ok(Err(ReturnCode::NotReady)));
+
+        // Dropped unanswered, e.g. when its response failed the E2E check
+        let (reply, response) = response_slot();
+        thread::spawn(move || drop(reply));
+        assert_eq!(response.wait_until(deadline()), Err(RecvTimeoutError::Disconnected));
+
+        let (_reply, response) = response_slot();
+        assert_eq!(response.wait_until(Instant::now() + Duration::from_millis(20)), Err(RecvTimeoutError::Timeout));
+    }
+
+    #[test]
+    fn test_qos_profiles_resolved_by_alias() {
+        let runtime = runtime_with("qos", r#"{
+            "qos_profiles": {
+                "control": { "request_timeout_ms": 100, "priority": 7 },
+                "bulk": { "transport": "tcp", "tp_pacing_us": 500 }
+            },
+            "instances": { "test": { "required": {
+                "ctrl": { "service_id": 4660, "instance_id": 1, "major_version": 1, "qos": "control" },
+                "data": { "service_id": 4661, "instance_id": 1, "major_version": 1, "qos": "bulk" },
+                "plain": { "service_id": 4662, "instance_id": 1, "major_version": 1 }
+            } } }
+        }"#);
+        let handle = runtime.handle();
+
+        let control = handle.qos_profile("ctrl").unwrap();
+        assert_eq!((control.request_timeout_ms, control.priority), (100, 7));
+        let bulk = handle.qos_profile("data").unwrap();
+        assert_eq!(bulk.transport_proto(), Some(0x06));
+        assert_eq!(bulk.request_timeout_ms, 2000);
+        assert!(handle.qos_profile("plain").is_none());
+    }
+
+    #[test]
+    fn test_client_context_awaits_response() {
+        use crate::runtime::ClientContext;
+        use crate::sd::machine::RemoteService;
+        use crate::sd::options::SdOption;
+
+        struct EchoClient { context: ClientContext }
+        impl ServiceClient for EchoClient {
+            const SERVICE_ID: u16 = 0x1234;
+            fn new(context: ClientContext) -> Self { EchoClient { context } }
+        }
+
+        // Answers with the payload reversed, except requests starting with 0xFF
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        let target = socket.local_addr().unwrap();
+        let provider = std::thread::spawn(move || {
+            let mut ignored = 0;
+            let mut buf = [0u8; 1500];
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                let header = SomeIpHeader::deserialize(&buf[..16]).unwrap();
+                let mut payload = buf[16..len].to_vec();
+                if payload.first() == Some(&0xFF) {
+                    ignored += 1;
+                    continue;
+                }
+                payload.reverse();
+                let mut res = SomeIpHeader::new(header.service_id, header.method_id, header.client_id, header.session_id, 0x80, payload.len() as u32)
+                    .serialize().to_vec();
+                res.extend(payload);
+                socket.send_to(&res, from).unwrap();
+            }
+            ignored
+        });
+
+        let runtime = runtime_with("client_context", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "qos_profiles": { "fast": { "request_timeout_ms": 200 } },
+            "instances": { "test": {
+                "endpoint": "ep",
+                "required": { "echo": { "service_id": 4660, "instance_id": 1, "major_version": 1, "qos": "fast" } }
+            } }
+        }"#);
+        runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), RemoteService {
+            service_id: 0x1234,
+            instance_id: 1,
+            version_major: 1,
+            version_minor: 0,
+            endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: target.port(), transport_proto: 0x11 }],
+            last_seen: std::time::Instant::now(),
+            ttl: 0xFFFFFF,
+        });
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let client = runtime.get_client::<EchoClient>("echo").unwrap();
+        let context = client.context;
+        assert_eq!(context.target(), target);
+        assert!(context.local_addr().unwrap().ip().is_loopback());
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let response = rt.block_on(context.request(0x0001, &[1, 2, 3]));
+        let unanswered = rt.block_on(context.request(0x0001, &[0xFF]));
+        runtime.stop();
+        event_loop.join().unwrap();
+
+        assert_eq!(response.unwrap(), vec![3, 2, 1]);
+        assert_eq!(unanswered.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        // Not retried: the QoS profile marks no method idempotent
+        assert_eq!(provider.join().unwrap(), 1);
+        assert!(runtime.pending_requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_call_blocking_without_executor() {
+        use crate::sd::machine::RemoteService;
+        use crate::sd::options::SdOption;
+
+        // Answers with the payload reversed, except requests starting with 0xFF
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        let target = socket.local_addr().unwrap();
+        let provider = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                let header = SomeIpHeader::deserialize(&buf[..16]).unwrap();
+                let mut payload = buf[16..len].to_vec();
+                if payload.first() == Some(&0xFF) {
+                    continue;
+                }
+                payload.reverse();
+                let mut res = SomeIpHeader::new(header.service_id, header.method_id, header.client_id, header.session_id, 0x80, payload.len() as u32)
+                    .serialize().to_vec();
+                res.extend(payload);
+                socket.send_to(&res, from).unwrap();
+            }
+        });
+
+        let runtime = runtime_with("call_blocking", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "ep",
+                "required": { "echo": { "service_id": 4660, "instance_id": 1, "major_version": 1 } }
+            } }
+        }"#);
+        runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), RemoteService {
+            service_id: 0x1234,
+            instance_id: 1,
+            version_major: 1,
+            version_minor: 0,
+            endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: target.port(), transport_proto: 0x11 }],
+            last_seen: std::time::Instant::now(),
+            ttl: 0xFFFFFF,
+        });
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let timeout = Duration::from_millis(300);
+        let handle = runtime.handle();
+        let response = handle.call_blocking(0x1234, 0x0001, &[1, 2, 3], timeout);
+        let unanswered = handle.call_blocking(0x1234, 0x0001, &[0xFF], timeout);
+        let unknown = handle.call_blocking(0x4321, 0x0001, &[], timeout);
+        runtime.stop();
+        event_loop.join().unwrap();
+        provider.join().unwrap();
+
+        assert_eq!(response.unwrap(), vec![3, 2, 1]);
+        assert_eq!(unanswered.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(unknown.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(runtime.stats().request_timeouts, 1);
+        assert!(runtime.pending_requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_request_retry_only_for_idempotent_methods() {
+        let runtime = runtime_with("request_retry", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": { "endpoint": "ep", "required": {
+                "peer": { "service_id": 4660, "instance_id": 1, "major_version": 1,
+                          "request_retry": { "timeout_ms": 50, "max_retries": 2, "backoff_ms": 10,
+                                             "methods": { "0x0001": { "idempotent": true }, "0x0002": { "idempotent": false } } } }
+            } } }
+        }"#);
+        // Nothing answers, so every attempt times out
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        silent.set_read_timeout(Some(std::time::Duration::from_millis(100))).unwrap();
+        let handle = runtime.handle();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let received = |method_id: u16| {
+            assert!(rt.block_on(handle.send_request_and_wait(0x1234, method_id, &[], silent.local_addr().unwrap())).is_none());
+            let mut buf = [0u8; 64];
+            let mut sessions = Vec::new();
+            while let Ok(len) = silent.recv(&mut buf) {
+                let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+                assert_eq!(header.method_id, method_id);
+                sessions.push(header.session_id);
+            }
+            sessions
+        };
+        let sessions = received(0x0001);
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions.windows(2).all(|pair| pair[0] != pair[1]), "each attempt is a new request: {:?}", sessions);
+        assert_eq!(received(0x0002).len(), 1);
+        assert_eq!(runtime.stats().request_timeouts, 4);
+    }
+
+    #[test]
+    fn test_provider_errors_complete_requests() {
+        use crate::codec::ReturnCode;
+        use crate::runtime::error_return_code;
+
+        let runtime = runtime_with("provider_error", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": { "endpoint": "ep", "required": {
+                "peer": { "service_id": 4660, "instance_id": 1, "major_version": 1,
+                          "request_retry": { "timeout_ms": 2000, "max_retries": 2, "methods": { "0x0001": { "idempotent": true } } } }
+            } } }
+        }"#);
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+        // The provider answers every request with E_NOT_READY
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        let target = peer.local_addr().unwrap();
+        let provider = std::thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            let mut answered = 0;
+            while let Ok((len, from)) = peer.recv_from(&mut buf) {
+                let request = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+                let mut error = SomeIpHeader::new(request.service_id, request.method_id, request.client_id, request.session_id, 0x81, 0);
+                error.return_code = ReturnCode::NotReady.into();
+                peer.send_to(&error.serialize(), from).unwrap();
+                answered += 1;
+            }
+            answered
+        });
+        let handle = runtime.handle();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+
+        let started = std::time::Instant::now();
+        let error = rt.block_on(async { handle.start_request(0x1234, 0x0001, &[], target).await.unwrap().response().await }).unwrap_err();
+        assert_eq!(error_return_code(&error), Some(ReturnCode::NotReady));
+        // Idempotent, but the provider did receive it: not retried
+        assert!(rt.block_on(handle.send_request_and_wait(0x1234, 0x0001, &[], target)).is_none());
+        assert!(started.elapsed() < std::time::Duration::from_secs(1), "waited for the timeout: {:?}", started.elapsed());
+        assert_eq!(provider.join().unwrap(), 2);
+        assert_eq!(runtime.stats().request_timeouts, 0);
+        assert_eq!(runtime.stats().pending_requests, 0);
+
+        runtime.stop();
+        event_loop.join().unwrap();
+    }
+
+    #[test]
+    fn test_outstanding_requests_cancelled_or_timed_out() {
+        use crate::codec::tp::TpHeader;
+
+        let runtime = runtime_with("cancel", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": { "endpoint": "ep" } }
+        }"#);
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        let target = peer.local_addr().unwrap();
+        let handle = runtime.handle();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+
+        // The peer answers with the first segment of a response, then stalls
+        let request = rt.block_on(handle.start_request(0x1234, 0x0001, &[], target)).unwrap();
+        let mut buf = [0u8; 64];
+        let (len, from) = peer.recv_from(&mut buf).unwrap();
+        let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        assert_eq!(header.request_id(), request.request_id());
+        let segment = SomeIpHeader::new(0x1234, 0x0001, header.client_id, header.session_id, 0xA0, 4 + 16).serialize();
+        peer.send_to(&[&segment[..], &TpHeader::new(0, true).serialize(), &[0u8; 16]].concat(), from).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while runtime.tp_stats().in_progress == 0 {
+            assert!(std::time::Instant::now() < deadline, "segment was not buffered");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // Cancelled from elsewhere while awaited; the partial response is discarded
+        let canceller = request.handle();
+        let started = std::time::Instant::now();
+        let result = rt.block_on(async {
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                assert!(canceller.cancel());
+            });
+            request.response().await
+        });
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Interrupted);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(runtime.stats().pending_requests, 0);
+        assert_eq!(runtime.tp_stats().in_progress, 0);
+
+        // A per-call deadline shorter than the default
+        let request = rt.block_on(handle.start_request(0x1234, 0x0001, &[], target)).unwrap().with_timeout(std::time::Duration::from_millis(30));
+        let stale = request.handle();
+        assert_eq!(rt.block_on(request.response()).unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        assert!(!stale.cancel());
+        assert_eq!(runtime.stats().request_timeouts, 1);
+
+        // Dropping a request abandons it
+        drop(rt.block_on(handle.start_request(0x1234, 0x0001, &[], target)).unwrap());
+        assert_eq!(runtime.stats().pending_requests, 0);
+
+        runtime.stop();
+        event_loop.join().unwrap();
+    }
+
+    #[test]
+    fn test_outstanding_requests_limited() {
+        let runtime = runtime_with("request_limit", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": { "endpoint": "ep", "required": {
+                "strict": { "service_id": 4660, "instance_id": 1, "major_version": 1, "request_limit": { "max_in_flight": 2 } },
+                "patient": { "service_id": 4661, "instance_id": 1, "major_version": 1, "request_limit": { "max_in_flight": 1, "wait": true } }
+            } } }
+        }"#);
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = silent.local_addr().unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        rt.block_on(async {
+            // Beyond the limit, requests fail until one completes
+            let first = runtime.start_request(0x1234, 1, &[], target).await.unwrap();
+            let _second = runtime.start_request(0x1234, 1, &[], target).await.unwrap();
+            let refused = runtime.start_request(0x1234, 1, &[], target).await.err().unwrap();
+            assert_eq!(refused.kind(), std::io::ErrorKind::WouldBlock);
+            assert_eq!(runtime.send_request_and_wait(0x1234, 1, &[], target).await, None);
+            drop(first);
+            assert!(runtime.start_request(0x1234, 1, &[], target).await.is_ok());
+            assert_eq!(runtime.stats().requests_rejected, 2);
+
+            // ...or wait for one to complete
+            let first = runtime.start_request(0x1235, 1, &[], target).await.unwrap();
+            let waiting = tokio::time::timeout(std::time::Duration::from_millis(50), runtime.start_request(0x1235, 1, &[], target)).await;
+            assert!(waiting.is_err(), "request started beyond the limit");
+            drop(first);
+            assert!(runtime.start_request(0x1235, 1, &[], target).await.is_ok());
+            assert_eq!(runtime.stats().requests_rejected, 2);
+        });
+        // Dropped requests are forgotten
+        assert_eq!(runtime.stats().pending_requests, 0);
+    }
+}
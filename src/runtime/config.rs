@@ -1,35 +1,145 @@
 use serde::Deserialize;
 use std::collections::HashMap;
+use crate::logging::LogLevel;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct EndpointConfig {
     pub interface: Option<String>,
     pub ip: String,
     pub version: u8,
     pub port: u16,
     pub protocol: String,
+    /// TCP: send Magic Cookies on connections of this endpoint, at most this often (ms)
+    pub magic_cookie_interval_ms: Option<u64>,
+    /// UDP: largest datagram sent from this endpoint, SOME/IP header included (bytes, default: 1400).
+    /// Larger messages are segmented with SOME/IP-TP.
+    pub mtu: Option<usize>,
+    /// UDP: pause between bursts of TP segments (µs); a service's QoS `tp_pacing_us` takes precedence
+    pub tp_pacing_us: Option<u64>,
+    /// UDP: TP segments sent back to back before each pause (default: 1)
+    pub tp_burst: Option<usize>,
+    /// UDP: when the send buffer is full, wait and retry with a growing delay, and pause
+    /// longer for the rest of the message, instead of dropping it
+    #[serde(default)]
+    pub tp_adaptive: bool,
+    /// TLS and DTLS: certificates of a `"protocol": "tls"` or `"dtls"` endpoint
+    pub tls: Option<TlsConfig>,
+}
+
+impl EndpointConfig {
+    /// UDP endpoint on `ip`; `version` follows the address family. Port 0 binds an ephemeral port.
+    pub fn udp(ip: &str, port: u16) -> Self {
+        Self::with_protocol(ip, port, "udp")
+    }
+
+    /// TCP endpoint on `ip`; `version` follows the address family. Port 0 binds an ephemeral port.
+    pub fn tcp(ip: &str, port: u16) -> Self {
+        Self::with_protocol(ip, port, "tcp")
+    }
+
+    /// TLS endpoint on `ip` with the certificates in `tls`. Port 0 binds an ephemeral port.
+    pub fn tls(ip: &str, port: u16, tls: TlsConfig) -> Self {
+        EndpointConfig { tls: Some(tls), ..Self::with_protocol(ip, port, "tls") }
+    }
+
+    /// DTLS endpoint on `ip` with the certificates in `tls`. Port 0 binds an ephemeral port.
+    pub fn dtls(ip: &str, port: u16, tls: TlsConfig) -> Self {
+        EndpointConfig { tls: Some(tls), ..Self::with_protocol(ip, port, "dtls") }
+    }
+
+    fn with_protocol(ip: &str, port: u16, protocol: &str) -> Self {
+        let version = if ip.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_ipv6()) { 6 } else { 4 };
+        EndpointConfig { interface: None, ip: ip.to_string(), version, port, protocol: protocol.to_string(), magic_cookie_interval_ms: None, mtu: None, tp_pacing_us: None, tp_burst: None, tp_adaptive: false, tls: None }
+    }
+
+    /// Whether the endpoint is a TCP stream, plain or TLS.
+    pub fn is_stream(&self) -> bool {
+        self.protocol.eq_ignore_ascii_case("tcp") || self.is_tls()
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.protocol.eq_ignore_ascii_case("tls")
+    }
+
+    pub fn is_dtls(&self) -> bool {
+        self.protocol.eq_ignore_ascii_case("dtls")
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Certificates of a TLS or DTLS endpoint, as paths of PEM files. The provider binding the
+/// endpoint presents `cert` and `key`; clients connecting to it verify it against `ca`.
+/// A DTLS endpoint is bound by both sides, so each presents its own `cert` and `key`
+/// and verifies its peers against its own `ca`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct TlsConfig {
+    /// Certificate chain presented by the side holding the endpoint
+    pub cert: Option<String>,
+    /// Private key of `cert`
+    pub key: Option<String>,
+    /// CAs trusted to issue the peer's certificate
+    pub ca: Option<String>,
+    /// Server: require clients to present a certificate issued by `ca`. A client presents
+    /// the certificate of the TLS `local_endpoint` its connection is pinned to.
+    #[serde(default)]
+    pub client_auth: bool,
+    /// Client: name the server certificate must be valid for (default: the endpoint's `ip`).
+    /// DTLS: name the certificate of every peer this endpoint starts a handshake with must
+    /// be valid for (default: the peer's IP address)
+    pub server_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct MulticastConfig {
     pub ip: String,
     pub port: u16,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct InterfaceSdConfig {
     pub endpoint_v4: Option<String>,
     pub endpoint_v6: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct InterfaceConfig {
     pub name: String,
     pub endpoints: HashMap<String, EndpointConfig>,
     pub sd: Option<InterfaceSdConfig>,
+    /// Network (e.g. `192.168.1.0/24`) identifying the host interface when `name` differs between hosts
+    pub subnet: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl InterfaceConfig {
+    /// Interface on the network device `name`, without endpoints or SD.
+    pub fn new(name: &str) -> Self {
+        InterfaceConfig { name: name.to_string(), ..Default::default() }
+    }
+
+    pub fn endpoint(mut self, name: &str, endpoint: EndpointConfig) -> Self {
+        self.endpoints.insert(name.to_string(), endpoint);
+        self
+    }
+
+    /// Run IPv4 SD on the multicast endpoint `name`, defined with [`endpoint`](Self::endpoint).
+    pub fn sd_v4(mut self, name: &str) -> Self {
+        self.sd.get_or_insert_with(Default::default).endpoint_v4 = Some(name.to_string());
+        self
+    }
+
+    /// Run IPv6 SD on the multicast endpoint `name`, defined with [`endpoint`](Self::endpoint).
+    pub fn sd_v6(mut self, name: &str) -> Self {
+        self.sd.get_or_insert_with(Default::default).endpoint_v6 = Some(name.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct ServiceConfig {
     pub service_id: u16,
     pub instance_id: u16,
@@ -43,22 +153,335 @@ pub struct ServiceConfig {
     pub endpoint: Option<String>,
     #[serde(default)]
     pub interfaces: Vec<String>,
+    /// Execution deadline for every method of this service, in milliseconds
+    pub deadline_ms: Option<u64>,
+    /// Per-method deadlines (method ID as decimal or `0x` hex -> milliseconds)
+    #[serde(default)]
+    pub method_deadlines_ms: HashMap<String, u64>,
+    /// Withdraw the offer after this many consecutive deadline overruns
+    pub isolate_after_overruns: Option<u32>,
+    /// Name of a QoS profile from `qos_profiles`
+    pub qos: Option<String>,
+    /// Deployment metadata (e.g. ECU name, build hash) sent as SD configuration items with each offer
+    #[serde(default)]
+    pub extended_info: HashMap<String, String>,
+    /// Offer the built-in echo/heartbeat service under this entry
+    pub echo: Option<EchoConfig>,
+    /// Resource limits isolating this service from others in the same runtime
+    pub quota: Option<QuotaConfig>,
+    /// E2E protection of methods and events (method/event ID as decimal or `0x` hex)
+    #[serde(default)]
+    pub e2e: HashMap<String, E2eConfig>,
+    /// Sources allowed to use the service. If any rules are listed, requests and
+    /// subscriptions no rule allows are refused; otherwise everyone is served.
+    #[serde(default)]
+    pub access: Vec<AccessRule>,
+    /// Eventgroups of the service by name. If any are listed, subscriptions to other
+    /// eventgroups are answered with a NACK; otherwise every eventgroup is accepted.
+    #[serde(default)]
+    pub eventgroups: HashMap<String, OfferedEventgroupConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl ServiceConfig {
+    pub fn new(service_id: u16, instance_id: u16, major_version: u8) -> Self {
+        ServiceConfig { service_id, instance_id, major_version, ..Default::default() }
+    }
+
+    /// Offer the service on interface `iface` from its endpoint `endpoint`.
+    pub fn offer_on(mut self, iface: &str, endpoint: &str) -> Self {
+        self.offer_on.insert(iface.to_string(), endpoint.to_string());
+        self
+    }
+}
+
+/// One eventgroup of an offered service
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct OfferedEventgroupConfig {
+    pub eventgroup_id: u16,
+    /// Event IDs in the eventgroup
+    #[serde(default)]
+    pub events: Vec<u16>,
+    /// UDP subscribers from which events go to the service's `multicast` endpoint (default: 0, never)
+    #[serde(default)]
+    pub multicast_threshold: u16,
+}
+
+/// Source allowed to use an offered service
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct AccessRule {
+    /// Source address or subnet in CIDR notation, e.g. `"10.0.0.0/8"`
+    pub source: String,
+    /// Method IDs the source may call (default: all)
+    pub methods: Option<Vec<u16>>,
+    /// Eventgroup IDs the source may subscribe to (default: all)
+    pub eventgroups: Option<Vec<u16>>,
+}
+
+/// E2E profile protecting one method or event
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct E2eConfig {
+    pub profile: E2eProfileKind,
+    /// Data ID identifying the data element (16 bits for P01, 32 bits for P04)
+    pub data_id: u32,
+    /// Largest accepted counter jump between two received payloads (default: 1, no loss)
+    #[serde(default = "default_max_delta_counter")]
+    pub max_delta_counter: u32,
+}
+
+fn default_max_delta_counter() -> u32 { 1 }
+
+/// Supported AUTOSAR E2E profiles
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub enum E2eProfileKind {
+    P01,
+    P04,
+}
+
+/// Per-service resource limits; unset limits are not enforced
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct QuotaConfig {
+    /// Requests executing at once, including handlers still running after a deadline overrun
+    pub max_in_flight: Option<u32>,
+    /// Subscribers across all eventgroups of the service
+    pub max_subscribers: Option<u32>,
+    /// Bytes a single response or notification may queue for sending
+    pub max_queue_bytes: Option<u64>,
+}
+
+/// Settings of the built-in echo service
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct EchoConfig {
+    /// Heartbeat period (ms, default: 1000, 0 disables heartbeats)
+    #[serde(default = "default_heartbeat_ms")]
+    pub heartbeat_ms: u64,
+    /// Eventgroup carrying the heartbeat event (default: 1)
+    #[serde(default = "default_heartbeat_eventgroup")]
+    pub heartbeat_eventgroup: u16,
+}
+
+fn default_heartbeat_ms() -> u64 { 1000 }
+fn default_heartbeat_eventgroup() -> u16 { 1 }
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct ClientConfig {
     pub service_id: u16,
     pub instance_id: u16,
     pub major_version: u8,
+    /// Lowest minor version accepted of the provider's offers
+    #[serde(default)]
+    pub minor_version: u32,
     #[serde(default)]
     pub find_on: Vec<String>, // List of interfaces
     pub endpoint: Option<String>,
     pub preferred_interface: Option<String>,
+    /// Named endpoint the client sends from, so requests leave from a fixed source IP/port
+    pub local_endpoint: Option<String>,
+    /// Queue requests while the service is unavailable and send them once it is offered again
+    pub store_and_forward: Option<StoreAndForwardConfig>,
+    /// Name of a QoS profile from `qos_profiles`
+    pub qos: Option<String>,
+    /// Deliver this service's events to handlers in session order
+    pub reorder: Option<ReorderConfig>,
+    /// Subscription settings of named eventgroups
+    #[serde(default)]
+    pub eventgroups: HashMap<String, EventgroupConfig>,
+    /// Acknowledgement timeout and automatic retries of `subscribe`
+    pub subscription_retry: Option<SubscriptionRetryConfig>,
+    /// Response timeout and automatic retries of requests; replaces those of the QoS profile
+    pub request_retry: Option<RequestRetryConfig>,
+    /// Bound on the requests awaiting a response at once
+    pub request_limit: Option<RequestLimitConfig>,
+    /// E2E protection of methods and events (method/event ID as decimal or `0x` hex)
+    #[serde(default)]
+    pub e2e: HashMap<String, E2eConfig>,
+}
+
+impl ClientConfig {
+    pub fn new(service_id: u16, instance_id: u16, major_version: u8) -> Self {
+        ClientConfig { service_id, instance_id, major_version, ..Default::default() }
+    }
+
+    /// Look for the service on interface `iface`.
+    pub fn find_on(mut self, iface: &str) -> Self {
+        self.find_on.push(iface.to_string());
+        self
+    }
+
+    /// Interface to subscribe to `eventgroup_id` on: the eventgroup's `interface`, then
+    /// `preferred_interface`, then the first `find_on` entry.
+    pub fn subscription_interface(&self, eventgroup_id: u16) -> Option<&String> {
+        self.eventgroups.values().find(|eg| eg.eventgroup_id == eventgroup_id).and_then(|eg| eg.interface.as_ref())
+            .or(self.preferred_interface.as_ref())
+            .or(self.find_on.first())
+    }
+}
+
+/// Subscription settings of one eventgroup of a required service
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct EventgroupConfig {
+    pub eventgroup_id: u16,
+    /// Subscribe with a TCP endpoint option (true) or a UDP one (false, default). Eventgroups
+    /// without an entry follow the offer: TCP if the service is offered over TCP only.
+    #[serde(default)]
+    pub reliable: bool,
+    /// Subscribe automatically once the service is discovered, and again after a provider reboot
+    #[serde(default)]
+    pub subscribe: bool,
+    /// TTL of the automatic subscription (seconds, default: the instance's `sd.ttl`)
+    pub ttl: Option<u32>,
+    /// Interface of the automatic subscription (default: `preferred_interface`, then the first `find_on` entry)
+    pub interface: Option<String>,
+}
+
+/// How `subscribe` waits for and retries a subscription
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct SubscriptionRetryConfig {
+    /// How long to wait for the SubscribeEventgroupAck (ms, default: 2000)
+    #[serde(default = "default_ack_timeout")]
+    pub ack_timeout_ms: u64,
+    /// Further attempts after a timeout or NACK (default: 3)
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Pause before each retry (ms, default: 500)
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay_ms: u64,
+    /// Retry after a NACK as well as after a timeout (default: true)
+    #[serde(default = "default_retry_on_nack")]
+    pub retry_on_nack: bool,
+}
+
+/// How requests to a required service wait for their response and are retried
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct RequestRetryConfig {
+    /// How long each attempt waits for the response (ms, default: 2000)
+    #[serde(default = "default_request_timeout")]
+    pub timeout_ms: u64,
+    /// Further attempts of idempotent methods after a timeout (default: 3)
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Pause before the first retry, doubling before each further one (ms, default: 100)
+    #[serde(default = "default_backoff")]
+    pub backoff_ms: u64,
+    /// Longest pause before a retry (ms, default: 2000)
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff_ms: u64,
+    /// Retry settings of methods (method ID as decimal or `0x` hex). Methods without an
+    /// entry are not idempotent and are never retried.
+    #[serde(default)]
+    pub methods: HashMap<String, MethodRetryConfig>,
+}
+
+/// Retry settings of one method
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct MethodRetryConfig {
+    /// Calling the method twice has the same effect as calling it once, so a request whose
+    /// response was lost may be sent again (default: false)
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+/// Bound on the requests to a required service awaiting their response
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct RequestLimitConfig {
+    /// Requests awaiting a response at once
+    pub max_in_flight: u32,
+    /// When all are taken, wait for one to complete instead of failing the request (default: false)
+    #[serde(default)]
+    pub wait: bool,
+}
+
+fn default_backoff() -> u64 { 100 }
+fn default_max_backoff() -> u64 { 2000 }
+fn default_ack_timeout() -> u64 { 2000 }
+fn default_max_retries() -> u32 { 3 }
+fn default_retry_delay() -> u64 { 500 }
+fn default_retry_on_nack() -> bool { true }
+
+/// Per-event reordering of notifications
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct ReorderConfig {
+    /// How many sessions ahead an event may arrive and still be held (default: 16)
+    #[serde(default = "default_reorder_window")]
+    pub window: u16,
+    /// How long a gap is waited for before held events are released (ms, default: 50)
+    #[serde(default = "default_reorder_max_hold")]
+    pub max_hold_ms: u64,
+}
+
+fn default_reorder_window() -> u16 { 16 }
+fn default_reorder_max_hold() -> u64 { 50 }
+
+/// Bounds of a required service's store-and-forward queue
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct StoreAndForwardConfig {
+    /// Maximum number of queued requests (default: 32)
+    #[serde(default = "default_forward_queue_size")]
+    pub max_queued: usize,
+    /// How long a request may wait for the service (ms, default: 5000)
+    #[serde(default = "default_forward_ttl")]
+    pub ttl_ms: u64,
+}
+
+fn default_forward_queue_size() -> usize { 32 }
+fn default_forward_ttl() -> u64 { 5000 }
+
+/// Named bundle of timing, retry and transport settings (e.g. "control", "bulk", "safety"),
+/// defined once under `qos_profiles` and referenced by services via `qos`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct QosProfile {
+    /// Response timeout for requests (ms, default: 2000)
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout_ms: u64,
+    /// Preferred transport ("udp" or "tcp") when a service is offered over both
+    pub transport: Option<String>,
+    /// Delay between outgoing SOME/IP-TP segments (us). Unset keeps the runtime default.
+    pub tp_pacing_us: Option<u64>,
+    /// Services with higher priority are served first when competing (default: 0)
+    #[serde(default)]
+    pub priority: u8,
+}
+
+impl Default for QosProfile {
+    fn default() -> Self {
+        QosProfile {
+            request_timeout_ms: default_request_timeout(),
+            transport: None,
+            tp_pacing_us: None,
+            priority: 0,
+        }
+    }
+}
+
+impl QosProfile {
+    /// SD transport protocol ID of the preferred transport, if any
+    pub fn transport_proto(&self) -> Option<u8> {
+        match self.transport.as_deref().map(str::to_lowercase).as_deref() {
+            Some("tcp") => Some(0x06),
+            Some("udp") => Some(0x11),
+            _ => None,
+        }
+    }
 }
 
 /// Service Discovery Configuration
 /// All timing values are in milliseconds unless otherwise specified
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct SdConfig {
     pub multicast_endpoint: Option<String>,
     pub multicast_endpoint_v6: Option<String>,
@@ -92,6 +515,19 @@ pub struct SdConfig {
     /// Multicast hops (default: 1)
     #[serde(default = "default_multicast_hops")]
     pub multicast_hops: u8,
+    /// Process our own offers when they loop back via multicast (default: false).
+    /// Offers from other instances on the same host are always processed.
+    #[serde(default)]
+    pub accept_self_offers: bool,
+    /// SD packets processed per poll cycle; the rest wait for later cycles (default: 64)
+    #[serde(default = "default_max_packets_per_poll")]
+    pub max_packets_per_poll: usize,
+    /// SD packets buffered for later cycles before reading pauses (default: 1024)
+    #[serde(default = "default_max_backlog")]
+    pub max_backlog: usize,
+    /// Renew our eventgroup subscriptions after this percentage of their TTL (default: 50)
+    #[serde(default = "default_subscription_renewal_percent")]
+    pub subscription_renewal_percent: u32,
 }
 
 impl Default for SdConfig {
@@ -109,6 +545,10 @@ impl Default for SdConfig {
             request_response_delay_max_ms: default_request_response_delay_max(),
             request_timeout_ms: default_request_timeout(),
             multicast_hops: default_multicast_hops(),
+            accept_self_offers: false,
+            max_packets_per_poll: default_max_packets_per_poll(),
+            max_backlog: default_max_backlog(),
+            subscription_renewal_percent: default_subscription_renewal_percent(),
         }
     }
 }
@@ -123,8 +563,12 @@ fn default_request_response_delay_min() -> u64 { 10 }
 fn default_request_response_delay_max() -> u64 { 100 }
 fn default_request_timeout() -> u64 { 2000 }
 fn default_multicast_hops() -> u8 { 1 }
+fn default_max_packets_per_poll() -> usize { 64 }
+fn default_max_backlog() -> usize { 1024 }
+fn default_subscription_renewal_percent() -> u32 { 50 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct InstanceConfig {
     #[serde(default)]
     pub unicast_bind: HashMap<String, String>, // Interface -> Endpoint
@@ -135,17 +579,127 @@ pub struct InstanceConfig {
     /// Service Discovery configuration
     #[serde(default)]
     pub sd: SdConfig,
+    /// Append and validate payload schema fingerprints (diagnostic mode, default: false)
+    #[serde(default)]
+    pub schema_diagnostics: bool,
+    /// File the shutdown report is written to as JSON (it is always logged)
+    pub shutdown_report_path: Option<String>,
+    /// Exchange UDP traffic with peers on the same host through shared memory (Linux)
+    pub shared_memory: Option<SharedMemoryConfig>,
+    /// Limits of SOME/IP-TP reassembly
+    #[serde(default)]
+    pub tp: TpConfig,
+    /// Reconnection of TCP client connections
+    #[serde(default)]
+    pub tcp: TcpConfig,
+    /// Client ID in the requests of this instance (default: allocated by the runtime)
+    pub client_id: Option<u16>,
+    /// Threads running request handlers (default: handlers run on the event loop)
+    pub worker_threads: Option<usize>,
+    /// Serve Prometheus metrics over HTTP (needs the `prometheus` feature)
+    pub metrics: Option<MetricsConfig>,
+    /// Minimum log levels, globally and per component
+    #[serde(default)]
+    pub logging: LoggingConfig,
     // Legacy support
     pub endpoint: Option<String>,
     #[serde(default)]
     pub interfaces: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Shared-memory transport between co-located instances
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct SharedMemoryConfig {
+    /// Size of each inbox ring buffer (KiB, default: 1024)
+    #[serde(default = "default_shm_ring_size")]
+    pub ring_size_kb: usize,
+}
+
+fn default_shm_ring_size() -> usize { 1024 }
+
+/// Limits of SOME/IP-TP reassembly
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct TpConfig {
+    /// Time from a message's first segment until it is discarded as incomplete (ms, default: 5000)
+    #[serde(default = "default_reassembly_timeout")]
+    pub reassembly_timeout_ms: u64,
+    /// Bytes buffered across all incomplete messages (default: 1 MiB)
+    #[serde(default = "default_max_buffer_bytes")]
+    pub max_buffer_bytes: usize,
+}
+
+impl Default for TpConfig {
+    fn default() -> Self {
+        TpConfig {
+            reassembly_timeout_ms: default_reassembly_timeout(),
+            max_buffer_bytes: default_max_buffer_bytes(),
+        }
+    }
+}
+
+fn default_reassembly_timeout() -> u64 { 5000 }
+
+/// HTTP endpoint serving Prometheus metrics at `/metrics`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct MetricsConfig {
+    /// Address to listen on (default: 127.0.0.1)
+    #[serde(default = "default_metrics_bind")]
+    pub bind: String,
+    pub port: u16,
+}
+
+fn default_metrics_bind() -> String { "127.0.0.1".to_string() }
+
+/// Minimum levels of the lines the runtime's logger passes on
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct LoggingConfig {
+    /// Level of components not listed in `components` (default: Debug, everything)
+    pub level: Option<LogLevel>,
+    /// Level by component name, e.g. `"Runtime": "Warn"`
+    #[serde(default)]
+    pub components: HashMap<String, LogLevel>,
+}
+
+/// Reconnection of TCP client connections
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct TcpConfig {
+    /// Delay after a failed connection attempt before the next one (ms, default: 100)
+    #[serde(default = "default_reconnect_backoff_min")]
+    pub reconnect_backoff_min_ms: u64,
+    /// Longest delay between attempts, reached by doubling (ms, default: 10000)
+    #[serde(default = "default_reconnect_backoff_max")]
+    pub reconnect_backoff_max_ms: u64,
+    /// Send Magic Cookies on client connections not opened from a configured endpoint (ms)
+    pub magic_cookie_interval_ms: Option<u64>,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        TcpConfig {
+            reconnect_backoff_min_ms: default_reconnect_backoff_min(),
+            reconnect_backoff_max_ms: default_reconnect_backoff_max(),
+            magic_cookie_interval_ms: None,
+        }
+    }
+}
+
+fn default_reconnect_backoff_min() -> u64 { 100 }
+fn default_reconnect_backoff_max() -> u64 { 10000 }
+fn default_max_buffer_bytes() -> usize { 1024 * 1024 }
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct SystemConfig {
     #[serde(default)]
     pub endpoints: HashMap<String, EndpointConfig>,
     #[serde(default)]
     pub interfaces: HashMap<String, InterfaceConfig>,
+    #[serde(default)]
+    pub qos_profiles: HashMap<String, QosProfile>,
     pub instances: HashMap<String, InstanceConfig>,
 }
@@ -0,0 +1,264 @@
+//! # Provider-side Execution Deadlines
+//!
+//! A service can bound how long its handlers may run (`deadline_ms`, or per
+//! method via `method_deadlines_ms`). A handler with a deadline runs on its
+//! own thread while the dispatcher waits at most that long. On overrun the
+//! caller gets an `ERROR` message with return code `E_TIMEOUT`, and the late
+//! result is discarded when the handler eventually finishes.
+//!
+//! Once a service overruns `isolate_after_overruns` times in a row, the
+//! runtime withdraws its offer so that clients fail over to other providers
+//! instead of queueing behind a hung one.
+//!
+//! A handler that overruns keeps its thread until it returns. At most
+//! [`MAX_LINGERING_HANDLERS`] such threads per offered instance are allowed;
+//! beyond that, requests are refused without calling the handler and the
+//! offer is withdrawn, so a hung handler cannot pile up threads.
+
+use super::config::ServiceConfig;
+use super::{HandlerResult, RequestHandler};
+use crate::codec::SomeIpHeader;
+use crate::trace;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Handler threads of one offered instance that may still run past their deadline.
+pub const MAX_LINGERING_HANDLERS: usize = 8;
+
+/// States of a handler thread, as seen by the caller waiting for it
+const RUNNING: u8 = 0;
+const FINISHED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Deadline settings of one provided service.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionPolicy {
+    /// Applies to every method without an entry in `method_deadlines`
+    pub default_deadline: Option<Duration>,
+    pub method_deadlines: HashMap<u16, Duration>,
+    /// Consecutive overruns after which the offer is withdrawn
+    pub isolate_after: Option<u32>,
+}
+
+impl ExecutionPolicy {
+    /// Build the policy from a `providing` entry. Method keys may be decimal or `0x`-prefixed hex;
+    /// unparsable keys are returned so the caller can report them.
+    pub fn from_config(cfg: &ServiceConfig) -> (Self, Vec<String>) {
+        let mut invalid = Vec::new();
+        let mut method_deadlines = HashMap::new();
+        for (key, ms) in &cfg.method_deadlines_ms {
+            let parsed = match key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+                Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                None => key.parse::<u16>().ok(),
+            };
+            match parsed {
+                Some(method_id) => { method_deadlines.insert(method_id, Duration::from_millis(*ms)); }
+                None => invalid.push(key.clone()),
+            }
+        }
+        let policy = ExecutionPolicy {
+            default_deadline: cfg.deadline_ms.map(Duration::from_millis),
+            method_deadlines,
+            isolate_after: cfg.isolate_after_overruns.filter(|n| *n > 0),
+        };
+        (policy, invalid)
+    }
+
+    pub fn deadline_for(&self, method_id: u16) -> Option<Duration> {
+        self.method_deadlines.get(&method_id).copied().or(self.default_deadline)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default_deadline.is_none() && self.method_deadlines.is_empty()
+    }
+}
+
+/// Outcome of a handler call made under a deadline.
+#[derive(Debug, PartialEq)]
+pub enum Execution {
+    Completed(HandlerResult),
+    /// The handler did not return in time (or panicked); its result will be discarded
+    Overrun,
+    /// [`MAX_LINGERING_HANDLERS`] threads are still running past their deadline; the
+    /// handler was not called
+    Saturated,
+}
+
+/// Takes a handler thread off the `lingering` count when it exits, if it overran.
+struct Exit {
+    state: Arc<AtomicU8>,
+    lingering: Arc<AtomicUsize>,
+}
+
+impl Drop for Exit {
+    fn drop(&mut self) {
+        if self.state.swap(FINISHED, Ordering::AcqRel) == ABANDONED {
+            self.lingering.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Run `handler` on a dedicated thread and wait at most `deadline` for its result.
+/// `lingering` counts the instance's handler threads still running past their deadline.
+pub fn execute_with_deadline(handler: Arc<dyn RequestHandler>, header: SomeIpHeader, payload: Vec<u8>, deadline: Duration, lingering: &Arc<AtomicUsize>) -> Execution {
+    if lingering.load(Ordering::Acquire) >= MAX_LINGERING_HANDLERS {
+        return Execution::Saturated;
+    }
+    let (tx, rx) = channel();
+    let state = Arc::new(AtomicU8::new(RUNNING));
+    let exit = Exit { state: state.clone(), lingering: lingering.clone() };
+    // The handler runs in the request's `dispatch` span on its own thread too
+    let span = trace::Span::current();
+    let spawned = thread::Builder::new()
+        .name(format!("handler-0x{:04x}", header.service_id))
+        .spawn(move || {
+            let _exit = exit;
+            let _span = span.entered();
+            let _ = tx.send(handler.handle(&header, &payload));
+        });
+    if spawned.is_err() {
+        return Execution::Overrun;
+    }
+    match rx.recv_timeout(deadline) {
+        Ok(result) => Execution::Completed(result),
+        Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+            // Counted before the thread can see itself abandoned, so its decrement never comes first
+            lingering.fetch_add(1, Ordering::AcqRel);
+            if state.compare_exchange(RUNNING, ABANDONED, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                lingering.fetch_sub(1, Ordering::AcqRel);
+            }
+            Execution::Overrun
+        }
+    }
+}
+
+/// Consecutive overrun counts and lingering handler threads per offered (service, instance).
+#[derive(Debug, Default)]
+pub struct OverrunTracker {
+    counts: HashMap<(u16, u16), u32>,
+    lingering: HashMap<(u16, u16), Arc<AtomicUsize>>,
+}
+
+impl OverrunTracker {
+    /// Record an overrun, returning the number of consecutive overruns so far.
+    pub fn record_overrun(&mut self, instance: (u16, u16)) -> u32 {
+        let count = self.counts.entry(instance).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    pub fn record_success(&mut self, instance: (u16, u16)) {
+        self.counts.remove(&instance);
+    }
+
+    pub fn count(&self, instance: (u16, u16)) -> u32 {
+        self.counts.get(&instance).copied().unwrap_or(0)
+    }
+
+    /// Counter of the instance's handler threads still running past their deadline;
+    /// kept across re-offers, as the threads are.
+    pub fn lingering(&mut self, instance: (u16, u16)) -> Arc<AtomicUsize> {
+        self.lingering.entry(instance).or_default().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sleepy(Duration);
+
+    impl RequestHandler for Sleepy {
+        fn service_id(&self) -> u16 { 0x1234 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+            thread::sleep(self.0);
+            Ok(Some(payload.to_vec()))
+        }
+    }
+
+    fn header() -> SomeIpHeader {
+        SomeIpHeader::new(0x1234, 0x0001, 0x0001, 0x0001, 0x00, 0)
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let cfg: ServiceConfig = serde_json::from_str(r#"{
+            "service_id": 4660, "instance_id": 1, "major_version": 1,
+            "deadline_ms": 200,
+            "method_deadlines_ms": { "1": 50, "0x0002": 75, "bogus": 10 },
+            "isolate_after_overruns": 3
+        }"#).unwrap();
+        let (policy, invalid) = ExecutionPolicy::from_config(&cfg);
+        assert_eq!(policy.deadline_for(1), Some(Duration::from_millis(50)));
+        assert_eq!(policy.deadline_for(2), Some(Duration::from_millis(75)));
+        assert_eq!(policy.deadline_for(3), Some(Duration::from_millis(200)));
+        assert_eq!(policy.isolate_after, Some(3));
+        assert_eq!(invalid, vec!["bogus".to_string()]);
+    }
+
+    #[test]
+    fn test_fast_handler_completes() {
+        let handler: Arc<dyn RequestHandler> = Arc::new(Sleepy(Duration::ZERO));
+        let result = execute_with_deadline(handler, header(), vec![7], Duration::from_secs(5), &Arc::default());
+        assert_eq!(result, Execution::Completed(Ok(Some(vec![7]))));
+    }
+
+    #[test]
+    fn test_slow_handler_overruns() {
+        let handler: Arc<dyn RequestHandler> = Arc::new(Sleepy(Duration::from_millis(500)));
+        let result = execute_with_deadline(handler, header(), vec![7], Duration::from_millis(20), &Arc::default());
+        assert_eq!(result, Execution::Overrun);
+    }
+
+    #[test]
+    fn test_lingering_handler_threads_capped() {
+        struct Panics;
+
+        impl RequestHandler for Panics {
+            fn service_id(&self) -> u16 { 0x1234 }
+            fn major_version(&self) -> u8 { 1 }
+            fn minor_version(&self) -> u32 { 0 }
+            fn handle(&self, _header: &SomeIpHeader, _payload: &[u8]) -> HandlerResult { panic!("handler failed") }
+        }
+
+        let lingering = Arc::new(AtomicUsize::new(0));
+        // A handler that panics is not left counted
+        let handler: Arc<dyn RequestHandler> = Arc::new(Panics);
+        assert_eq!(execute_with_deadline(handler, header(), vec![], Duration::from_secs(5), &lingering), Execution::Overrun);
+        assert_eq!(lingering.load(Ordering::Acquire), 0);
+
+        let handler: Arc<dyn RequestHandler> = Arc::new(Sleepy(Duration::from_millis(300)));
+        for _ in 0..MAX_LINGERING_HANDLERS {
+            assert_eq!(execute_with_deadline(handler.clone(), header(), vec![], Duration::from_millis(1), &lingering), Execution::Overrun);
+        }
+        assert_eq!(lingering.load(Ordering::Acquire), MAX_LINGERING_HANDLERS);
+        assert_eq!(execute_with_deadline(handler.clone(), header(), vec![], Duration::from_secs(5), &lingering), Execution::Saturated);
+
+        // Threads leave the count as their handlers return
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while lingering.load(Ordering::Acquire) > 0 && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(lingering.load(Ordering::Acquire), 0);
+        let handler: Arc<dyn RequestHandler> = Arc::new(Sleepy(Duration::ZERO));
+        assert_eq!(execute_with_deadline(handler, header(), vec![7], Duration::from_secs(5), &lingering), Execution::Completed(Ok(Some(vec![7]))));
+    }
+
+    #[test]
+    fn test_overruns_counted_consecutively() {
+        let mut tracker = OverrunTracker::default();
+        assert_eq!(tracker.record_overrun((0x1234, 1)), 1);
+        assert_eq!(tracker.record_overrun((0x1234, 1)), 2);
+        // Instances of one service are counted apart
+        assert_eq!(tracker.record_overrun((0x1234, 2)), 1);
+        tracker.record_success((0x1234, 1));
+        assert_eq!(tracker.count((0x1234, 1)), 0);
+        assert_eq!(tracker.record_overrun((0x1234, 1)), 1);
+    }
+}
@@ -0,0 +1,170 @@
+//! # Built-in Echo Service
+//!
+//! A standard target for commissioning and network validation. Any node can
+//! offer it by adding an `echo` block to a `providing` entry; the service ID,
+//! instance and endpoints come from that entry as for any other service.
+//!
+//! | ID       | Kind   | Payload                                                   |
+//! |----------|--------|-----------------------------------------------------------|
+//! | `0x0001` | method | `echo`: response is the request payload, unchanged         |
+//! | `0x0002` | method | `delay`: request is `ms: u32`, empty response after `ms`   |
+//! | `0x8001` | event  | `heartbeat`: `sequence: u32`, `requests_served: u32`       |
+//!
+//! Heartbeats are published on the configured eventgroup every `heartbeat_ms`.
+//! All integers are big-endian.
+
+use super::{HandlerResult, RequestHandler};
+use crate::codec::{ReturnCode, SomeIpHeader};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const METHOD_ECHO: u16 = 0x0001;
+pub const METHOD_DELAY: u16 = 0x0002;
+pub const EVENT_HEARTBEAT: u16 = 0x8001;
+
+/// Upper bound for `delay`, so a single request cannot stall the dispatcher indefinitely
+pub const MAX_DELAY_MS: u32 = 10_000;
+
+/// Handler for the built-in echo service.
+pub struct EchoService {
+    service_id: u16,
+    major_version: u8,
+    minor_version: u32,
+    requests_served: Arc<AtomicU32>,
+}
+
+impl EchoService {
+    pub fn new(service_id: u16, major_version: u8, minor_version: u32) -> Self {
+        EchoService { service_id, major_version, minor_version, requests_served: Arc::new(AtomicU32::new(0)) }
+    }
+
+    /// Shared counter of handled requests, reported in heartbeats.
+    pub fn requests_served(&self) -> Arc<AtomicU32> {
+        self.requests_served.clone()
+    }
+}
+
+impl RequestHandler for EchoService {
+    fn service_id(&self) -> u16 { self.service_id }
+    fn major_version(&self) -> u8 { self.major_version }
+    fn minor_version(&self) -> u32 { self.minor_version }
+
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        match header.method_id {
+            METHOD_ECHO => {
+                self.requests_served.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(payload.to_vec()))
+            }
+            METHOD_DELAY => {
+                let ms = payload.get(..4).and_then(|b| b.try_into().ok()).map(u32::from_be_bytes)
+                    .ok_or(ReturnCode::MalformedMessage)?;
+                thread::sleep(Duration::from_millis(ms.min(MAX_DELAY_MS) as u64));
+                self.requests_served.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(Vec::new()))
+            }
+            _ => Err(ReturnCode::UnknownMethod),
+        }
+    }
+
+    fn validate(&self, header: &SomeIpHeader, payload: &[u8]) -> ReturnCode {
+        match header.method_id {
+            METHOD_ECHO => ReturnCode::Ok,
+            METHOD_DELAY if payload.len() >= 4 => ReturnCode::Ok,
+            METHOD_DELAY => ReturnCode::MalformedMessage,
+            _ => ReturnCode::UnknownMethod,
+        }
+    }
+}
+
+/// Periodic heartbeat publication of one echo service.
+pub(crate) struct Heartbeat {
+    pub service_id: u16,
+    pub eventgroup_id: u16,
+    pub interval: Duration,
+    pub next: Instant,
+    pub sequence: u32,
+    pub requests_served: Arc<AtomicU32>,
+}
+
+impl Heartbeat {
+    /// Payload to publish if the heartbeat is due at `now`, advancing the schedule.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if now < self.next {
+            return None;
+        }
+        self.next = now + self.interval;
+        self.sequence = self.sequence.wrapping_add(1);
+        Some(heartbeat_payload(self.sequence, self.requests_served.load(Ordering::Relaxed)))
+    }
+}
+
+/// Encode a heartbeat event payload.
+pub fn heartbeat_payload(sequence: u32, requests_served: u32) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(8);
+    payload.extend_from_slice(&sequence.to_be_bytes());
+    payload.extend_from_slice(&requests_served.to_be_bytes());
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    fn request(method_id: u16) -> SomeIpHeader {
+        SomeIpHeader::new(0xFFF0, method_id, 0x0001, 0x0001, 0x00, 0)
+    }
+
+    #[test]
+    fn test_echo_returns_payload() {
+        let svc = EchoService::new(0xFFF0, 1, 0);
+        assert_eq!(svc.handle(&request(METHOD_ECHO), &[1, 2, 3]), Ok(Some(vec![1, 2, 3])));
+        assert_eq!(svc.handle(&request(METHOD_ECHO), &[]), Ok(Some(vec![])));
+        assert_eq!(svc.requests_served().load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_delay_waits_and_replies_empty() {
+        let svc = EchoService::new(0xFFF0, 1, 0);
+        let start = Instant::now();
+        assert_eq!(svc.handle(&request(METHOD_DELAY), &20u32.to_be_bytes()), Ok(Some(vec![])));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        // Truncated argument and unknown methods are answered with an error
+        assert_eq!(svc.handle(&request(METHOD_DELAY), &[0, 1]), Err(ReturnCode::MalformedMessage));
+        assert_eq!(svc.handle(&request(0x0003), &[]), Err(ReturnCode::UnknownMethod));
+        assert_eq!(svc.requests_served().load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_heartbeat_payload_layout() {
+        assert_eq!(heartbeat_payload(7, 0x0102), vec![0, 0, 0, 7, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_heartbeat_schedule() {
+        let start = Instant::now();
+        let svc = EchoService::new(0xFFF0, 1, 0);
+        let mut hb = Heartbeat {
+            service_id: 0xFFF0, eventgroup_id: 1, interval: Duration::from_millis(100),
+            next: start, sequence: 0, requests_served: svc.requests_served(),
+        };
+        let _ = svc.handle(&request(METHOD_ECHO), &[]);
+
+        assert_eq!(hb.poll(start), Some(heartbeat_payload(1, 1)));
+        assert_eq!(hb.poll(start + Duration::from_millis(50)), None);
+        assert_eq!(hb.poll(start + Duration::from_millis(100)), Some(heartbeat_payload(2, 1)));
+    }
+
+    #[test]
+    fn test_echo_service_enabled_by_config() {
+        let runtime = runtime_with("echo", r#"{ "instances": { "test": { "providing": {
+            "echo": { "service_id": 65520, "instance_id": 1, "major_version": 1, "echo": { "heartbeat_ms": 250 } }
+        } } } }"#);
+        assert!(runtime.services.read().unwrap().contains_key(&(0xFFF0, 1)));
+        let heartbeats = runtime.heartbeats.lock().unwrap();
+        assert_eq!(heartbeats.len(), 1);
+        assert_eq!((heartbeats[0].eventgroup_id, heartbeats[0].interval.as_millis()), (1, 250));
+    }
+}
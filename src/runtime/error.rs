@@ -0,0 +1,47 @@
+//! # Runtime Errors
+//!
+//! [`RuntimeError`] is what `try_load` and `reload_config` return instead of
+//! panicking: the configuration could not be used, or a socket it configures
+//! could not be opened.
+
+use super::validate::ConfigError;
+use std::fmt;
+
+/// Why a runtime could not be created or reconfigured.
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// The configuration file could not be read, parsed or validated
+    Config(ConfigError),
+    /// A socket of the configuration could not be opened; `what` names it
+    Bind { what: String, source: std::io::Error },
+}
+
+impl RuntimeError {
+    pub(crate) fn bind(what: impl Into<String>, source: std::io::Error) -> Self {
+        RuntimeError::Bind { what: what.into(), source }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::Config(e) => write!(f, "{}", e),
+            RuntimeError::Bind { what, source } => write!(f, "cannot bind {}: {}", what, source),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuntimeError::Config(e) => Some(e),
+            RuntimeError::Bind { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<ConfigError> for RuntimeError {
+    fn from(e: ConfigError) -> Self {
+        RuntimeError::Config(e)
+    }
+}
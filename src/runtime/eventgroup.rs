@@ -0,0 +1,312 @@
+//! # Offered Eventgroups
+//!
+//! Providers declare the eventgroups of a service and the events each one
+//! carries, either in `providing.<alias>.eventgroups` or at runtime with
+//! `offer_eventgroup`. Service Discovery NACKs subscriptions to eventgroups a
+//! service does not declare; services that declare none accept any.
+//!
+//! `fire_event` sends an event to the subscribers of every eventgroup that
+//! contains it, so generated servers can publish by event ID alone. A
+//! subscriber of several such eventgroups receives the event once.
+//!
+//! ## Multicast threshold
+//!
+//! An eventgroup with a non-zero `multicast_threshold` of a service with a
+//! `multicast` endpoint switches to multicast once that many UDP subscribers
+//! of the endpoint's address family are registered: each event is sent to the
+//! group once instead of to every subscriber. Below the threshold, and for TCP
+//! subscribers, events stay unicast. SubscribeEventgroupAcks of such
+//! eventgroups carry the multicast endpoint, so subscribers join the group
+//! before the switch. A threshold of 1 always uses multicast.
+
+use crate::sd::machine::EventSubscriber;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+
+/// Events and delivery settings of one offered eventgroup.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OfferedEventgroup {
+    pub events: Vec<u16>,
+    /// UDP subscribers from which events go to the service's multicast endpoint; 0 never does
+    pub multicast_threshold: u16,
+}
+
+/// How one notification reaches the subscribers of an eventgroup.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delivery {
+    /// Group receiving a single copy on behalf of `multicast_subscribers`
+    pub multicast: Option<SocketAddr>,
+    pub multicast_subscribers: Vec<EventSubscriber>,
+    pub unicast: Vec<EventSubscriber>,
+}
+
+impl Delivery {
+    /// Send to every subscriber individually.
+    pub fn unicast(subscribers: Vec<EventSubscriber>) -> Self {
+        Delivery { unicast: subscribers, ..Default::default() }
+    }
+
+    /// Add the sends of another eventgroup of the same service, reaching each subscriber once.
+    pub fn merge(&mut self, other: Delivery) {
+        self.multicast = self.multicast.or(other.multicast);
+        for sub in other.multicast_subscribers {
+            if !self.multicast_subscribers.contains(&sub) {
+                self.multicast_subscribers.push(sub);
+            }
+        }
+        for sub in other.unicast {
+            if !self.unicast.contains(&sub) {
+                self.unicast.push(sub);
+            }
+        }
+        let covered = &self.multicast_subscribers;
+        self.unicast.retain(|sub| !covered.contains(sub));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.multicast_subscribers.is_empty() && self.unicast.is_empty()
+    }
+}
+
+impl OfferedEventgroup {
+    /// Choose unicast or multicast delivery to `subscribers`, given the service's multicast `group`.
+    pub fn plan(&self, subscribers: Vec<EventSubscriber>, group: Option<SocketAddr>) -> Delivery {
+        let Some(group) = group.filter(|_| self.multicast_threshold > 0) else {
+            return Delivery::unicast(subscribers);
+        };
+        let (reached, unicast): (Vec<_>, Vec<_>) = subscribers.into_iter()
+            .partition(|sub| sub.transport_proto == 0x11 && sub.endpoint.is_ipv4() == group.is_ipv4());
+        if reached.len() < self.multicast_threshold as usize {
+            return Delivery::unicast(reached.into_iter().chain(unicast).collect());
+        }
+        Delivery { multicast: Some(group), multicast_subscribers: reached, unicast }
+    }
+}
+
+/// Declared eventgroups of offered services.
+#[derive(Debug, Default)]
+pub struct EventgroupRegistry {
+    groups: HashMap<u16, BTreeMap<u16, OfferedEventgroup>>,
+}
+
+impl EventgroupRegistry {
+    /// Declare an eventgroup, replacing an earlier declaration of the same ID.
+    pub fn insert(&mut self, service_id: u16, eventgroup_id: u16, eventgroup: OfferedEventgroup) {
+        self.groups.entry(service_id).or_default().insert(eventgroup_id, eventgroup);
+    }
+
+    pub fn get(&self, service_id: u16, eventgroup_id: u16) -> Option<&OfferedEventgroup> {
+        self.groups.get(&service_id)?.get(&eventgroup_id)
+    }
+
+    /// Eventgroups of `service_id` that carry `event_id`, in ascending order.
+    pub fn containing(&self, service_id: u16, event_id: u16) -> Vec<u16> {
+        self.groups.get(&service_id)
+            .map(|groups| groups.iter().filter(|(_, eg)| eg.events.contains(&event_id)).map(|(id, _)| *id).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+    use crate::runtime::tests::runtime_with;
+    use crate::runtime::SomeIpRuntime;
+
+    #[test]
+    fn test_events_mapped_to_eventgroups() {
+        let mut registry = EventgroupRegistry::default();
+        registry.insert(0x1234, 2, OfferedEventgroup { events: vec![0x8001, 0x8002], multicast_threshold: 0 });
+        registry.insert(0x1234, 1, OfferedEventgroup { events: vec![0x8001], multicast_threshold: 4 });
+        registry.insert(0x4321, 1, OfferedEventgroup { events: vec![0x8002], multicast_threshold: 0 });
+
+        assert_eq!(registry.containing(0x1234, 0x8001), vec![1, 2]);
+        assert_eq!(registry.containing(0x1234, 0x8002), vec![2]);
+        assert!(registry.containing(0x1234, 0x8003).is_empty());
+        assert!(registry.containing(0x9999, 0x8001).is_empty());
+        assert_eq!(registry.get(0x1234, 1).unwrap().multicast_threshold, 4);
+
+        registry.insert(0x1234, 1, OfferedEventgroup { events: vec![0x8003], multicast_threshold: 0 });
+        assert_eq!(registry.containing(0x1234, 0x8001), vec![2]);
+    }
+
+    #[test]
+    fn test_multicast_from_threshold() {
+        let sub = |addr: &str, transport_proto| EventSubscriber { endpoint: addr.parse().unwrap(), transport_proto };
+        let group: SocketAddr = "239.1.1.1:30500".parse().unwrap();
+        let udp = vec![sub("10.0.0.2:40000", 0x11), sub("10.0.0.3:40000", 0x11)];
+        let tcp = sub("10.0.0.4:40000", 0x06);
+        let v6 = sub("[fd00::5]:40000", 0x11);
+        let mut subscribers = udp.clone();
+        subscribers.extend([tcp, v6]);
+        let eventgroup = |multicast_threshold| OfferedEventgroup { events: vec![0x8001], multicast_threshold };
+
+        // TCP and other-family subscribers do not count toward the threshold
+        assert_eq!(eventgroup(3).plan(subscribers.clone(), Some(group)), Delivery::unicast(vec![udp[0], udp[1], tcp, v6]));
+        assert_eq!(eventgroup(2).plan(subscribers.clone(), Some(group)), Delivery {
+            multicast: Some(group),
+            multicast_subscribers: udp.clone(),
+            unicast: vec![tcp, v6],
+        });
+        assert_eq!(eventgroup(1).plan(udp[..1].to_vec(), Some(group)).multicast, Some(group));
+        // Disabled, or no endpoint to send to
+        assert_eq!(eventgroup(0).plan(subscribers.clone(), Some(group)), Delivery::unicast(subscribers.clone()));
+        assert_eq!(eventgroup(1).plan(subscribers.clone(), None), Delivery::unicast(subscribers));
+
+        // A subscriber reached by multicast through one eventgroup is not sent a unicast copy
+        let mut delivery = Delivery::unicast(vec![udp[0], tcp]);
+        delivery.merge(eventgroup(1).plan(vec![udp[0], udp[1]], Some(group)));
+        delivery.merge(Delivery::unicast(vec![tcp, v6]));
+        assert_eq!(delivery, Delivery { multicast: Some(group), multicast_subscribers: udp, unicast: vec![tcp, v6] });
+    }
+
+    #[test]
+    fn test_eventgroup_reliability_selects_endpoint() {
+        use crate::runtime::subscription::{NackReason, SubscriptionState};
+        use crate::sd::machine::RemoteService;
+        use crate::sd::options::SdOption;
+
+        let runtime = runtime_with("eg_reliability", r#"{ "instances": { "test": { "required": {
+            "sensors": { "service_id": 4660, "instance_id": 1, "major_version": 1,
+                         "eventgroups": { "status": { "eventgroup_id": 1, "reliable": true },
+                                          "telemetry": { "eventgroup_id": 2 } } },
+            "tcp_only": { "service_id": 4661, "instance_id": 1, "major_version": 1,
+                          "eventgroups": { "status": { "eventgroup_id": 1, "reliable": false } } }
+        } } } }"#);
+        assert_eq!(runtime.eventgroup_reliability.get(&(0x1234, 2)), Some(&false));
+
+        // Reliable eventgroups cannot be subscribed before the TCP path is known
+        runtime.subscribe_eventgroup(0x1234, 1, 1, 3, "primary");
+        assert_eq!(runtime.sd.lock().unwrap().subscription_state(0x1234, 1), Some(SubscriptionState::Nacked(NackReason::NotOffered)));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp = listener.local_addr().unwrap();
+        let offer = |service_id, endpoint: Vec<SdOption>| RemoteService {
+            service_id, instance_id: 1, version_major: 1, version_minor: 0, endpoint,
+            last_seen: std::time::Instant::now(), ttl: 0xFFFFFF,
+        };
+        {
+            let mut sd = runtime.sd.lock().unwrap();
+            sd.remote_services.insert((0x1234, 1), offer(0x1234, vec![
+                SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: 30501, transport_proto: 0x11 },
+                SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: tcp.port(), transport_proto: 0x06 },
+            ]));
+            sd.remote_services.insert((0x1235, 1), offer(0x1235, vec![
+                SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: tcp.port(), transport_proto: 0x06 },
+            ]));
+        }
+
+        // Offered over both: the reliable eventgroup connects over TCP first, the other stays on UDP
+        runtime.subscribe_eventgroup(0x1234, 1, 2, 3, "primary");
+        assert!(runtime.tcp_clients.connections().is_empty());
+        runtime.subscribe_eventgroup(0x1234, 1, 1, 3, "primary");
+        assert!(runtime.tcp_clients.is_connected(tcp));
+        let sd = runtime.sd.lock().unwrap();
+        assert_eq!(sd.subscription_state(0x1234, 1), Some(SubscriptionState::Pending));
+        assert_eq!(sd.subscription_state(0x1234, 2), Some(SubscriptionState::Pending));
+        drop(sd);
+
+        // Unreliable eventgroup of a TCP-only offer is refused rather than sent with a UDP endpoint
+        runtime.subscribe_eventgroup(0x1235, 1, 1, 3, "primary");
+        assert_eq!(runtime.sd.lock().unwrap().subscription_state(0x1235, 1), Some(SubscriptionState::Nacked(NackReason::NotOffered)));
+    }
+
+    #[test]
+    fn test_events_fired_to_declaring_eventgroups() {
+        use crate::sd::machine::EventSubscriber;
+
+        let runtime = runtime_with("offer_eventgroup", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "ep",
+                "providing": { "radar": { "service_id": 28673, "instance_id": 1, "major_version": 1 } }
+            } }
+        }"#);
+        assert!(!runtime.offer_eventgroup("unknown", 1, &[0x8001], 0));
+        assert!(!runtime.offer_eventgroup("radar", 1, &[0x0001], 0));
+        assert!(!runtime.sd.lock().unwrap().offered_eventgroups.contains_key(&0x7001));
+        assert!(runtime.offer_eventgroup("radar", 1, &[0x8001, 0x8002], 0));
+        assert!(runtime.offer_eventgroup("radar", 2, &[0x8001], 3));
+        assert_eq!(runtime.sd.lock().unwrap().offered_eventgroups[&0x7001], std::collections::HashSet::from([1, 2]));
+
+        let both = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let second = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let subscriber = |socket: &std::net::UdpSocket| EventSubscriber { endpoint: socket.local_addr().unwrap(), transport_proto: 0x11 };
+        {
+            let mut sd = runtime.sd.lock().unwrap();
+            sd.subscriptions.insert((0x7001, 1), vec![subscriber(&both)]);
+            sd.subscriptions.insert((0x7001, 2), vec![subscriber(&both), subscriber(&second)]);
+        }
+        // Subscribers of both eventgroups get the event once
+        assert_eq!(runtime.fire_event(0x7001, 0x8001, &[1]), 2);
+        assert_eq!(runtime.fire_event(0x7001, 0x8002, &[2]), 1);
+        assert_eq!(runtime.fire_event(0x7001, 0x8003, &[3]), 0);
+
+        both.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        let mut buf = [0u8; 64];
+        let events: Vec<u16> = (0..2).map(|_| {
+            let len = both.recv(&mut buf).unwrap();
+            SomeIpHeader::deserialize(&buf[..len]).unwrap().method_id
+        }).collect();
+        assert_eq!(events, vec![0x8001, 0x8002]);
+        both.set_nonblocking(true).unwrap();
+        assert!(both.recv(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_events_multicast_from_threshold() {
+        use crate::sd::machine::EventSubscriber;
+
+        let runtime = runtime_with("multicast_threshold", r#"{
+            "endpoints": {
+                "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" },
+                "events": { "ip": "239.255.42.1", "port": 30542, "version": 4, "protocol": "udp" }
+            },
+            "instances": { "test": {
+                "endpoint": "ep",
+                "providing": { "radar": { "service_id": 28673, "instance_id": 1, "major_version": 1, "multicast": "events",
+                    "eventgroups": { "objects": { "eventgroup_id": 1, "events": [32769], "multicast_threshold": 2 } } } }
+            } }
+        }"#);
+        let group: SocketAddr = "239.255.42.1:30542".parse().unwrap();
+        assert_eq!(runtime.sd.lock().unwrap().eventgroup_multicast.get(&(0x7001, 1)), Some(&group));
+        assert!(runtime.offer_eventgroup("radar", 2, &[0x8002], 0));
+        assert!(!runtime.sd.lock().unwrap().eventgroup_multicast.contains_key(&(0x7001, 2)));
+
+        let receiver = SomeIpRuntime::multicast_receiver(group, Some(std::net::Ipv4Addr::LOCALHOST.into())).unwrap();
+        let subscribers: Vec<_> = (0..2).map(|_| std::net::UdpSocket::bind("127.0.0.1:0").unwrap()).collect();
+        for socket in &subscribers {
+            socket.set_nonblocking(true).unwrap();
+        }
+        let register = |count: usize| {
+            let subs = subscribers[..count].iter().map(|s| EventSubscriber { endpoint: s.local_addr().unwrap(), transport_proto: 0x11 }).collect();
+            runtime.sd.lock().unwrap().subscriptions.insert((0x7001, 1), subs);
+        };
+        let mut buf = [0u8; 64];
+        let wait_multicast = |buf: &mut [u8]| {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_millis(500);
+            while std::time::Instant::now() < deadline {
+                if let Ok((len, _)) = receiver.receive(buf) {
+                    return Some(len);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            None
+        };
+
+        // Below the threshold: unicast
+        register(1);
+        assert_eq!(runtime.send_notification(0x7001, 1, 0x8001, &[1]), 1);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(subscribers[0].recv(&mut buf).is_ok());
+        assert!(receiver.receive(&mut buf).is_err());
+
+        // At the threshold: one copy to the group
+        register(2);
+        assert_eq!(runtime.fire_event(0x7001, 0x8001, &[2]), 2);
+        let len = wait_multicast(&mut buf).expect("event sent to the multicast group");
+        assert_eq!(SomeIpHeader::deserialize(&buf[..len]).unwrap().method_id, 0x8001);
+        assert!(subscribers.iter().all(|s| s.recv(&mut buf).is_err()));
+    }
+}
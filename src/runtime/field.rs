@@ -0,0 +1,76 @@
+//! # Fields
+//!
+//! A SOME/IP field is a value of an offered service with an optional getter,
+//! setter and notifier event. [`FieldHandle`] holds the value on the provider
+//! side: generated servers answer getter requests with [`FieldHandle::get`] and
+//! setter requests with [`FieldHandle::set`], replying with the value now held.
+//!
+//! Once bound to a runtime, each change is sent as the notifier event to the
+//! subscribers of the offered eventgroups that carry it (see
+//! [`fire_event`](super::SomeIpRuntime::fire_event)). A new subscriber of such
+//! an eventgroup is sent the current value right after its subscription is
+//! acknowledged, so it does not wait for the next change.
+
+use super::SomeIpRuntime;
+use crate::codec::SomeIpSerialize;
+use std::io::Result;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Provider-side value of one field.
+pub struct FieldHandle<T> {
+    service_id: u16,
+    /// Event ID of the notifier, if the field has one
+    notifier_id: Option<u16>,
+    value: Mutex<T>,
+    runtime: Mutex<Weak<SomeIpRuntime>>,
+}
+
+impl<T: SomeIpSerialize + Clone + PartialEq> FieldHandle<T> {
+    pub fn new(service_id: u16, notifier_id: Option<u16>, initial: T) -> Self {
+        FieldHandle { service_id, notifier_id, value: Mutex::new(initial), runtime: Mutex::new(Weak::new()) }
+    }
+
+    pub fn service_id(&self) -> u16 {
+        self.service_id
+    }
+
+    pub fn notifier_id(&self) -> Option<u16> {
+        self.notifier_id
+    }
+
+    pub fn get(&self) -> T {
+        self.value.lock().unwrap().clone()
+    }
+
+    /// Replace the value. A changed value is sent to the notifier's subscribers once bound;
+    /// returns the number of subscribers reached.
+    pub fn set(&self, value: T) -> Result<usize> {
+        let mut current = self.value.lock().unwrap();
+        if *current == value {
+            return Ok(0);
+        }
+        let payload = Self::encode(&value)?;
+        *current = value;
+        // Notified under the lock, so concurrent changes reach subscribers in the order they were made
+        Ok(match (self.notifier_id, self.runtime.lock().unwrap().upgrade()) {
+            (Some(notifier_id), Some(runtime)) => runtime.update_field(self.service_id, notifier_id, payload),
+            _ => 0,
+        })
+    }
+
+    /// Send changes through `runtime` from now on, and the current value to new subscribers.
+    pub fn bind(&self, runtime: &Arc<SomeIpRuntime>) -> Result<()> {
+        let current = self.value.lock().unwrap();
+        if let Some(notifier_id) = self.notifier_id {
+            runtime.store_field(self.service_id, notifier_id, Self::encode(&current)?);
+        }
+        *self.runtime.lock().unwrap() = Arc::downgrade(runtime);
+        Ok(())
+    }
+
+    fn encode(value: &T) -> Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        value.serialize(&mut payload)?;
+        Ok(payload)
+    }
+}
@@ -0,0 +1,150 @@
+//! # Store-and-Forward Requests
+//!
+//! Required services configured with `store_and_forward` keep requests made
+//! while the provider is not offered in a bounded [`ForwardQueue`]. The
+//! runtime transmits them as soon as the offer reappears; requests whose TTL
+//! runs out first, or that arrive while the queue is full, are dropped and
+//! their callers get `None`.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// A request waiting for its service to become available.
+pub struct QueuedRequest<T> {
+    pub method_id: u16,
+    pub payload: Vec<u8>,
+    pub expires_at: Instant,
+    /// Where the response is delivered once the request has been sent
+    pub reply: T,
+}
+
+/// Counters of one store-and-forward queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForwardStats {
+    /// Requests accepted into the queue
+    pub queued: u64,
+    /// Queued requests transmitted after the service reappeared
+    pub forwarded: u64,
+    /// Requests rejected because the queue was full, or expired while waiting
+    pub dropped: u64,
+}
+
+/// Bounded FIFO of requests for one required service.
+pub struct ForwardQueue<T> {
+    capacity: usize,
+    ttl: Duration,
+    items: VecDeque<QueuedRequest<T>>,
+    stats: ForwardStats,
+}
+
+impl<T> ForwardQueue<T> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        ForwardQueue { capacity, ttl, items: VecDeque::new(), stats: ForwardStats::default() }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Queue a request, or hand the reply back if the queue is full.
+    pub fn push(&mut self, method_id: u16, payload: Vec<u8>, reply: T, now: Instant) -> Result<(), T> {
+        self.expire(now);
+        if self.items.len() >= self.capacity {
+            self.stats.dropped += 1;
+            return Err(reply);
+        }
+        self.items.push_back(QueuedRequest { method_id, payload, expires_at: now + self.ttl, reply });
+        self.stats.queued += 1;
+        Ok(())
+    }
+
+    /// Drop requests whose TTL has elapsed, returning how many were dropped.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before = self.items.len();
+        self.items.retain(|r| r.expires_at > now);
+        let expired = before - self.items.len();
+        self.stats.dropped += expired as u64;
+        expired
+    }
+
+    /// Take all unexpired requests for transmission, oldest first.
+    pub fn drain(&mut self, now: Instant) -> Vec<QueuedRequest<T>> {
+        self.expire(now);
+        let drained: Vec<_> = self.items.drain(..).collect();
+        self.stats.forwarded += drained.len() as u64;
+        drained
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn stats(&self) -> ForwardStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    #[test]
+    fn test_bounded_queue_drops_when_full() {
+        let now = Instant::now();
+        let mut queue = ForwardQueue::new(2, Duration::from_secs(1));
+        assert!(queue.push(1, vec![1], 'a', now).is_ok());
+        assert!(queue.push(1, vec![2], 'b', now).is_ok());
+        assert_eq!(queue.push(1, vec![3], 'c', now), Err('c'));
+        assert_eq!(queue.stats(), ForwardStats { queued: 2, forwarded: 0, dropped: 1 });
+    }
+
+    #[test]
+    fn test_expired_requests_not_forwarded() {
+        let now = Instant::now();
+        let mut queue = ForwardQueue::new(4, Duration::from_millis(100));
+        queue.push(1, vec![1], 'a', now).unwrap();
+        queue.push(2, vec![2], 'b', now + Duration::from_millis(80)).unwrap();
+
+        let sent = queue.drain(now + Duration::from_millis(150));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].method_id, 2);
+        assert_eq!(sent[0].reply, 'b');
+        assert!(queue.is_empty());
+        assert_eq!(queue.stats(), ForwardStats { queued: 2, forwarded: 1, dropped: 1 });
+    }
+
+    #[test]
+    fn test_expiry_frees_capacity() {
+        let now = Instant::now();
+        let mut queue = ForwardQueue::new(1, Duration::from_millis(10));
+        queue.push(1, vec![], (), now).unwrap();
+        assert!(queue.push(1, vec![], (), now + Duration::from_millis(20)).is_ok());
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_store_and_forward_queue_bounded() {
+        let runtime = runtime_with("forward", r#"{ "instances": { "test": { "required": {
+            "queued": { "service_id": 4660, "instance_id": 1, "major_version": 1,
+                        "store_and_forward": { "max_queued": 0 } },
+            "plain": { "service_id": 4661, "instance_id": 1, "major_version": 1 }
+        } } } }"#);
+        let handle = runtime.handle();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+
+        // Service not offered and the queue has no room: rejected without waiting
+        assert!(rt.block_on(handle.request_service("queued", 0x0001, &[1])).is_none());
+        let stats = handle.forward_stats("queued").unwrap();
+        assert_eq!((stats.queued, stats.dropped), (0, 1));
+
+        // Without store-and-forward an unavailable service fails immediately
+        assert!(rt.block_on(handle.request_service("plain", 0x0001, &[1])).is_none());
+        assert!(handle.forward_stats("plain").is_none());
+    }
+}
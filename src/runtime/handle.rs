@@ -0,0 +1,394 @@
+//! # Runtime Handle
+//!
+//! [`SomeIpHandle`] is the thread-safe, cloneable public facade over a
+//! [`SomeIpRuntime`]. Applications should pass handles around instead of
+//! cloning `Arc<SomeIpRuntime>`.
+//!
+//! ## Locking strategy
+//!
+//! - Mutations (offering and withdrawing services, subscriptions, interface
+//!   control, handler registration, notifications) are sent as [`Command`]s over a channel and
+//!   applied by the runtime thread at the top of each event loop iteration,
+//!   when it holds no locks. Handle calls never block on runtime locks, so they
+//!   are safe to make from request handlers running inside the dispatch path.
+//! - Queries (`get_client`, `is_interface_enabled`), `send_request_and_wait`,
+//!   `start_request` and `subscribe` take at most one runtime lock at a time
+//!   and release it before returning (or awaiting).
+//! - Inside the runtime, locks are only nested in the order
+//!   `sd` -> `tcp_clients`; every other lock is a leaf lock.
+//!
+//! Commands issued before `run()` starts are queued and applied once it does.
+//! Those that can fail return a [`Completion`] that reports the outcome once
+//! the runtime thread has applied them; dropping it ignores the outcome.
+
+use super::config::QosProfile;
+use super::forward::ForwardStats;
+use super::quota::QuotaUsage;
+use super::reorder::ReorderStats;
+use super::subscription::{SubscriptionAdded, SubscriptionNack, SubscriptionResult, SubscriptionState};
+use super::{InitialEvents, MessageAuthenticator, PendingRequest, ReloadSummary, RequestHandler, RuntimeError, RuntimeStats, ServiceClient, ShutdownReport, SomeIpRuntime};
+use crate::clock::Timestamp;
+use crate::codec::tp::TpReassemblyStats;
+use crate::codec::SomeIpDeserialize;
+use crate::e2e::E2eProtector;
+use crate::logging::FusionLogger;
+use crate::sd::{DiscoveredService, SdIngressStats, SdSnapshot};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Where the runtime thread reports the outcome of a command.
+pub(crate) type Done = oneshot::Sender<io::Result<()>>;
+
+/// A mutation queued for the runtime thread.
+pub enum Command {
+    OfferService { alias: String, handler: Box<dyn RequestHandler>, done: Done },
+    StopOfferService { alias: String, done: Done },
+    Subscribe { service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: String, done: Done },
+    SendNotification { service_id: u16, eventgroup_id: u16, event_id: u16, payload: Vec<u8> },
+    OfferEventgroup { alias: String, eventgroup_id: u16, events: Vec<u16>, multicast_threshold: u16, done: Done },
+    FireEvent { service_id: u16, event_id: u16, payload: Vec<u8> },
+    RegisterNotificationHandler { service_id: u16, handler: Box<dyn RequestHandler> },
+    DisableInterface { iface_alias: String, done: Done },
+    EnableInterface { iface_alias: String, done: Done },
+}
+
+/// Outcome of a queued command, known once the runtime thread has applied it.
+pub struct Completion(oneshot::Receiver<io::Result<()>>);
+
+impl Completion {
+    fn new() -> (Done, Self) {
+        let (tx, rx) = oneshot::channel();
+        (tx, Completion(rx))
+    }
+
+    /// Wait until the command has been applied. Fails with the error its method
+    /// documents, or with `Interrupted` if the runtime was dropped before applying it.
+    /// Commands queued before `run()` starts complete once it does.
+    pub async fn wait(self) -> io::Result<()> {
+        self.0.await.unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Interrupted, "runtime dropped before applying the command")))
+    }
+
+    /// The outcome if the command has been applied, without waiting.
+    pub fn try_result(&mut self) -> Option<io::Result<()>> {
+        match self.0.try_recv() {
+            Ok(result) => Some(result),
+            Err(oneshot::error::TryRecvError::Empty) => None,
+            Err(oneshot::error::TryRecvError::Closed) => Some(Err(io::Error::new(io::ErrorKind::Interrupted, "runtime dropped before applying the command"))),
+        }
+    }
+}
+
+/// Cloneable, thread-safe handle to a running [`SomeIpRuntime`].
+#[derive(Clone)]
+pub struct SomeIpHandle {
+    runtime: Arc<SomeIpRuntime>,
+    commands: Sender<Command>,
+}
+
+impl SomeIpHandle {
+    pub(crate) fn new(runtime: Arc<SomeIpRuntime>, commands: Sender<Command>) -> Self {
+        SomeIpHandle { runtime, commands }
+    }
+
+    fn submit(&self, command: Command) {
+        // The receiver lives in the runtime this handle keeps alive, so this cannot fail
+        let _ = self.commands.send(command);
+        self.runtime.wake();
+    }
+
+    /// Queue a service offer; see [`SomeIpRuntime::offer_service`]. Completes with
+    /// `NotFound` if `alias` is not a provided service.
+    pub fn offer_service(&self, alias: &str, handler: Box<dyn RequestHandler>) -> Completion {
+        let (done, completion) = Completion::new();
+        self.submit(Command::OfferService { alias: alias.to_string(), handler, done });
+        completion
+    }
+
+    /// Queue the withdrawal of an offer; see [`SomeIpRuntime::stop_offer_service`].
+    /// Completes with `NotFound` if the alias is unknown or not offered.
+    pub fn stop_offer_service(&self, alias: &str) -> Completion {
+        let (done, completion) = Completion::new();
+        self.submit(Command::StopOfferService { alias: alias.to_string(), done });
+        completion
+    }
+
+    /// Queue an eventgroup subscription; see [`SomeIpRuntime::subscribe_eventgroup`].
+    /// Completes with an error if the SubscribeEventgroup could not be sent.
+    pub fn subscribe_eventgroup(&self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str) -> Completion {
+        let (done, completion) = Completion::new();
+        self.submit(Command::Subscribe { service_id, instance_id, eventgroup_id, ttl, iface_alias: iface_alias.to_string(), done });
+        completion
+    }
+
+    /// Subscribe and wait for the acknowledgement; see [`SomeIpRuntime::subscribe`].
+    pub async fn subscribe(&self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str) -> SubscriptionResult {
+        self.runtime.subscribe(service_id, instance_id, eventgroup_id, ttl, iface_alias).await
+    }
+
+    /// Subscribe to an eventgroup of a required service as configured; see [`SomeIpRuntime::subscribe_required`].
+    pub fn subscribe_required(&self, alias: &str, eventgroup_id: u16, ttl: u32) -> std::io::Result<()> {
+        self.runtime.subscribe_required(alias, eventgroup_id, ttl)
+    }
+
+    /// Queue a notification to all subscribers of the eventgroup.
+    pub fn send_notification(&self, service_id: u16, eventgroup_id: u16, event_id: u16, payload: &[u8]) {
+        self.submit(Command::SendNotification { service_id, eventgroup_id, event_id, payload: payload.to_vec() });
+    }
+
+    /// Queue an eventgroup declaration; see [`SomeIpRuntime::offer_eventgroup`].
+    /// Completes with `InvalidInput` if `alias` is not a provided service or an ID is not an event ID.
+    pub fn offer_eventgroup(&self, alias: &str, eventgroup_id: u16, events: &[u16], multicast_threshold: u16) -> Completion {
+        let (done, completion) = Completion::new();
+        self.submit(Command::OfferEventgroup { alias: alias.to_string(), eventgroup_id, events: events.to_vec(), multicast_threshold, done });
+        completion
+    }
+
+    /// Queue an event for the subscribers of every eventgroup that carries it.
+    pub fn fire_event(&self, service_id: u16, event_id: u16, payload: &[u8]) {
+        self.submit(Command::FireEvent { service_id, event_id, payload: payload.to_vec() });
+    }
+
+    pub fn register_notification_handler(&self, service_id: u16, handler: Box<dyn RequestHandler>) {
+        self.submit(Command::RegisterNotificationHandler { service_id, handler });
+    }
+
+    /// Call `callback` with each notification of an event, decoded as `T`; see [`SomeIpRuntime::on_event`].
+    pub fn on_event<T, F>(&self, service_id: u16, event_id: u16, callback: F)
+    where
+        T: SomeIpDeserialize + 'static,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        self.runtime.on_event(service_id, event_id, callback);
+    }
+
+    /// Queue taking an interface out of service (maintenance mode); see
+    /// [`SomeIpRuntime::disable_interface`]. Completes with `NotFound` for an unknown interface.
+    pub fn disable_interface(&self, iface_alias: &str) -> Completion {
+        let (done, completion) = Completion::new();
+        self.submit(Command::DisableInterface { iface_alias: iface_alias.to_string(), done });
+        completion
+    }
+
+    /// Queue returning an interface to service; see [`SomeIpRuntime::enable_interface`].
+    /// Completes with `NotFound` for an unknown interface.
+    pub fn enable_interface(&self, iface_alias: &str) -> Completion {
+        let (done, completion) = Completion::new();
+        self.submit(Command::EnableInterface { iface_alias: iface_alias.to_string(), done });
+        completion
+    }
+
+    pub fn is_interface_enabled(&self, iface_alias: &str) -> bool {
+        self.runtime.is_interface_enabled(iface_alias)
+    }
+
+    pub fn sd_ingress_stats(&self) -> SdIngressStats {
+        self.runtime.sd_ingress_stats()
+    }
+
+    /// Read-only snapshot of service discovery; see [`SomeIpRuntime::sd_snapshot`].
+    pub fn sd_snapshot(&self) -> SdSnapshot {
+        self.runtime.sd_snapshot()
+    }
+
+    pub fn subscription_state(&self, service_id: u16, eventgroup_id: u16) -> Option<SubscriptionState> {
+        self.runtime.subscription_state(service_id, eventgroup_id)
+    }
+
+    pub fn on_subscription_nack(&self, callback: Box<dyn Fn(&SubscriptionNack) + Send + Sync>) {
+        self.runtime.on_subscription_nack(callback)
+    }
+
+    pub fn on_subscription_added(&self, callback: Box<dyn Fn(&SubscriptionAdded) -> InitialEvents + Send + Sync>) {
+        self.runtime.on_subscription_added(callback)
+    }
+
+    pub fn tp_stats(&self) -> TpReassemblyStats {
+        self.runtime.tp_stats()
+    }
+
+    pub fn client_id(&self) -> u16 {
+        self.runtime.client_id()
+    }
+
+    pub fn set_e2e_protection(&self, service_id: u16, id: u16, sender: Box<dyn E2eProtector>, receiver: Box<dyn E2eProtector>) {
+        self.runtime.set_e2e_protection(service_id, id, sender, receiver)
+    }
+
+    pub fn set_message_authenticator(&self, service_id: u16, authenticator: Box<dyn MessageAuthenticator>) {
+        self.runtime.set_message_authenticator(service_id, authenticator)
+    }
+
+    pub fn get_client<T: ServiceClient>(&self, alias: &str) -> Option<T> {
+        self.runtime.get_client(alias)
+    }
+
+    pub async fn send_request_and_wait(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> Option<Vec<u8>> {
+        self.runtime.send_request_and_wait(service_id, method_id, payload, target).await
+    }
+
+    /// Send a request to await with a deadline or cancel; see [`SomeIpRuntime::start_request`].
+    pub async fn start_request(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> std::io::Result<PendingRequest> {
+        self.runtime.start_request(service_id, method_id, payload, target).await
+    }
+
+    /// Send a fire-and-forget request; see [`SomeIpRuntime::send_fire_and_forget`].
+    pub fn send_fire_and_forget(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> std::io::Result<()> {
+        self.runtime.send_fire_and_forget(service_id, method_id, payload, target)
+    }
+
+    /// Send a request and block this thread for the response; see [`SomeIpRuntime::call_blocking`].
+    pub fn call_blocking(&self, service_id: u16, method_id: u16, payload: &[u8], timeout: Duration) -> std::io::Result<Vec<u8>> {
+        self.runtime.call_blocking(service_id, method_id, payload, timeout)
+    }
+
+    /// Start active discovery of a required service; see [`SomeIpRuntime::find_service`].
+    pub fn find_service(&self, alias: &str) -> bool {
+        self.runtime.find_service(alias)
+    }
+
+    /// Every service instance discovered on the network; see [`SomeIpRuntime::browse_services`].
+    pub fn browse_services(&self) -> Vec<DiscoveredService> {
+        self.runtime.browse_services()
+    }
+
+    /// Send a request to a required service by alias; see [`SomeIpRuntime::request_service`].
+    pub async fn request_service(&self, alias: &str, method_id: u16, payload: &[u8]) -> Option<Vec<u8>> {
+        self.runtime.request_service(alias, method_id, payload).await
+    }
+
+    pub fn forward_stats(&self, alias: &str) -> Option<ForwardStats> {
+        self.runtime.forward_stats(alias)
+    }
+
+    /// Value of a configuration item on the discovered offer of a required service.
+    pub fn remote_config(&self, alias: &str, key: &str) -> Option<String> {
+        self.runtime.remote_service_config(alias)?.into_iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn last_event_at(&self, service_id: u16, event_id: u16) -> Option<Timestamp> {
+        self.runtime.last_event_at(service_id, event_id)
+    }
+
+    pub fn reorder_stats(&self, alias: &str) -> Option<ReorderStats> {
+        self.runtime.reorder_stats(alias)
+    }
+
+    pub fn quota_usage(&self, alias: &str) -> Option<QuotaUsage> {
+        self.runtime.quota_usage(alias)
+    }
+
+    pub fn access_violations(&self, alias: &str) -> Option<u64> {
+        self.runtime.access_violations(alias)
+    }
+
+    pub fn qos_profile(&self, alias: &str) -> Option<QosProfile> {
+        self.runtime.qos_profile(alias)
+    }
+
+    pub fn get_logger(&self) -> Arc<dyn FusionLogger> {
+        self.runtime.get_logger()
+    }
+
+    /// Run the event loop on the calling thread until `stop()` is called.
+    pub fn run(&self) {
+        self.runtime.run();
+    }
+
+    /// Stop the event loop; the shutdown report is emitted on the first call.
+    pub fn stop(&self) {
+        self.runtime.stop();
+    }
+
+    /// Withdraw offers and subscriptions, then stop; see [`SomeIpRuntime::shutdown`].
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        self.runtime.shutdown(timeout)
+    }
+
+    /// Apply a changed configuration file; see [`SomeIpRuntime::reload_config`].
+    pub fn reload_config(&self, config_path: &str) -> Result<ReloadSummary, RuntimeError> {
+        self.runtime.reload_config(config_path)
+    }
+
+    /// Reload `config_path` on SIGHUP; see [`SomeIpRuntime::reload_on_sighup`].
+    #[cfg(target_os = "linux")]
+    pub fn reload_on_sighup(&self, config_path: &str) -> std::io::Result<()> {
+        self.runtime.reload_on_sighup(config_path)
+    }
+
+    pub fn stats(&self) -> RuntimeStats {
+        self.runtime.stats()
+    }
+
+    pub fn shutdown_report(&self) -> ShutdownReport {
+        self.runtime.shutdown_report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::{empty_runtime, Dummy};
+
+    #[test]
+    fn test_commands_applied_by_runtime_thread() {
+        let runtime = empty_runtime("apply");
+        let handle = runtime.handle();
+
+        handle.register_notification_handler(0x4242, Box::new(Dummy));
+        // Queued, not applied on the caller's thread
+        assert!(runtime.notification_handlers.read().unwrap().is_empty());
+
+        runtime.process_commands();
+        assert!(runtime.notification_handlers.read().unwrap().contains_key(&0x4242));
+    }
+
+    #[test]
+    fn test_failed_commands_complete_with_error() {
+        let runtime = empty_runtime("complete");
+        let handle = runtime.handle();
+
+        let mut offer = handle.offer_service("unknown", Box::new(Dummy));
+        let mut stop = handle.stop_offer_service("unknown");
+        let mut disable = handle.disable_interface("unknown");
+        let mut enable = handle.enable_interface("unknown");
+        let mut eventgroup = handle.offer_eventgroup("unknown", 1, &[0x8001], 0);
+        // Known once the runtime thread has applied them
+        assert!(offer.try_result().is_none());
+
+        runtime.process_commands();
+        for completion in [&mut offer, &mut stop, &mut disable, &mut enable] {
+            assert_eq!(completion.try_result().unwrap().unwrap_err().kind(), io::ErrorKind::NotFound);
+        }
+        assert_eq!(eventgroup.try_result().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_completion_of_dropped_runtime_interrupted() {
+        let runtime = empty_runtime("dropped");
+        let completion = runtime.handle().disable_interface("lo");
+        drop(runtime);
+
+        let result = tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(completion.wait());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn test_handle_is_send_and_clone() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<SomeIpHandle>();
+
+        let runtime = empty_runtime("clone");
+        let handle = runtime.handle();
+        let worker = {
+            let handle = handle.clone();
+            std::thread::spawn(move || handle.register_notification_handler(0x4242, Box::new(Dummy)))
+        };
+        worker.join().unwrap();
+        runtime.process_commands();
+        assert!(runtime.notification_handlers.read().unwrap().contains_key(&0x4242));
+    }
+}
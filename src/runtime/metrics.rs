@@ -0,0 +1,173 @@
+//! # Prometheus Exporter
+//!
+//! With the `prometheus` feature, an instance configured with `metrics` serves
+//! its [`RuntimeStats`] in the Prometheus text format at `http://<bind>:<port>/metrics`
+//! while the event loop runs. Every sample carries an `instance` label with the
+//! instance name; per-service samples add a `service` label with the hex ID.
+//!
+//! The server runs on its own thread, answers one scrape at a time and closes
+//! each connection after the response.
+
+use super::stats::{RuntimeStats, ServiceTraffic};
+use super::SomeIpRuntime;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// How often the idle server checks whether the runtime stopped
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// Longest wait for a scraper to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Render `stats` of `instance` in the Prometheus text exposition format.
+pub fn render(instance: &str, stats: &RuntimeStats) -> String {
+    let mut out = String::new();
+    let labels = format!("instance=\"{}\"", escape(instance));
+    let mut sample = |name: &str, kind: &str, help: &str, value: String| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{}{{{}}} {}", name, help, name, kind, name, labels, value);
+    };
+    sample("fusion_uptime_seconds", "gauge", "Time since the runtime was created.", seconds(stats.uptime_ms * 1000));
+    sample("fusion_requests_served_total", "counter", "Requests and fire-and-forget calls handled by local services.", stats.requests_served.to_string());
+    sample("fusion_errors_total", "counter", "Failed sends, TP reassembly failures and handler deadline overruns.", stats.errors.to_string());
+    sample("fusion_request_timeouts_total", "counter", "Outgoing requests whose response did not arrive in time.", stats.request_timeouts.to_string());
+    sample("fusion_requests_rejected_total", "counter", "Outgoing requests refused at the service's in-flight limit.", stats.requests_rejected.to_string());
+    sample("fusion_tp_reassemblies_total", "counter", "SOME/IP-TP messages reassembled.", stats.tp_reassemblies.to_string());
+    sample("fusion_packets_dropped_total", "counter", "Received messages discarded or refused instead of being handled.", stats.packets_dropped.to_string());
+    sample("fusion_sd_offers_sent_total", "counter", "OfferService entries sent.", stats.sd_offers_sent.to_string());
+    sample("fusion_sd_offers_received_total", "counter", "OfferService entries received.", stats.sd_offers_received.to_string());
+    sample("fusion_pending_requests", "gauge", "Outgoing requests waiting for a response.", stats.pending_requests.to_string());
+    sample("fusion_queued_requests", "gauge", "Requests held in store-and-forward queues.", stats.queued_requests.to_string());
+    sample("fusion_tp_in_progress", "gauge", "SOME/IP-TP messages partly received.", stats.tp_in_progress.to_string());
+    sample("fusion_sd_backlog", "gauge", "SD packets waiting for a later poll.", stats.sd_backlog.to_string());
+    sample("fusion_sd_remote_services", "gauge", "Remote service instances currently offered.", stats.sd_remote_services.to_string());
+    sample("fusion_sd_subscribers", "gauge", "Remote subscribers across local eventgroups.", stats.sd_subscribers.to_string());
+    sample("fusion_buffers_allocated_total", "counter", "Message buffers allocated because the pool had none idle.", stats.buffers.allocated.to_string());
+    sample("fusion_buffers_reused_total", "counter", "Message buffers taken from the pool.", stats.buffers.reused.to_string());
+    sample("fusion_buffers_idle", "gauge", "Message buffers idle in the pool.", stats.buffers.idle.to_string());
+
+    let _ = writeln!(out, "# HELP fusion_messages_sent_total Messages sent, by service.\n# TYPE fusion_messages_sent_total counter");
+    per_service(&mut out, &labels, stats, "fusion_messages_sent_total", |t| t.sent.to_string());
+    let _ = writeln!(out, "# HELP fusion_messages_received_total Messages received, by service.\n# TYPE fusion_messages_received_total counter");
+    per_service(&mut out, &labels, stats, "fusion_messages_received_total", |t| t.received.to_string());
+    let _ = writeln!(out, "# HELP fusion_handler_duration_seconds Time spent in request handlers, by service.\n# TYPE fusion_handler_duration_seconds summary");
+    per_service(&mut out, &labels, stats, "fusion_handler_duration_seconds_sum", |t| seconds(t.handler_time_us));
+    per_service(&mut out, &labels, stats, "fusion_handler_duration_seconds_count", |t| t.requests_handled.to_string());
+    out
+}
+
+fn per_service(out: &mut String, labels: &str, stats: &RuntimeStats, name: &str, value: impl Fn(&ServiceTraffic) -> String) {
+    for (service_id, traffic) in &stats.services {
+        let _ = writeln!(out, "{}{{{},service=\"0x{:04x}\"}} {}", name, labels, service_id, value(traffic));
+    }
+}
+
+fn seconds(us: u64) -> String {
+    format!("{}.{:06}", us / 1_000_000, us % 1_000_000)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serve the metrics of `runtime` on `addr` until `running` is cleared or the runtime is dropped.
+pub(crate) fn spawn(runtime: Weak<SomeIpRuntime>, addr: SocketAddr, running: Arc<AtomicBool>) -> io::Result<(SocketAddr, thread::JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let local = listener.local_addr()?;
+    let thread = thread::Builder::new().name(format!("metrics-{}", local.port())).spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let Some(runtime) = runtime.upgrade() else { break };
+                    let _ = serve(stream, &runtime);
+                }
+                Err(_) => thread::sleep(ACCEPT_POLL),
+            }
+        }
+    })?;
+    Ok((local, thread))
+}
+
+/// Answer one HTTP request: the metrics for `GET /metrics`, an error otherwise.
+fn serve(mut stream: TcpStream, runtime: &SomeIpRuntime) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(&runtime.instance_name, &runtime.stats())),
+        (Some("GET"), _) => ("404 Not Found", "Metrics are served at /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    #[test]
+    fn test_render() {
+        let mut stats = RuntimeStats { uptime_ms: 1500, requests_served: 7, requests_rejected: 3, sd_subscribers: 2, ..Default::default() };
+        stats.services.insert(0x1234, ServiceTraffic { sent: 3, received: 4, requests_handled: 2, handler_time_us: 1_250 });
+        let text = render("node \"a\"", &stats);
+
+        assert!(text.contains("# TYPE fusion_requests_served_total counter\nfusion_requests_served_total{instance=\"node \\\"a\\\"\"} 7\n"));
+        assert!(text.contains("fusion_uptime_seconds{instance=\"node \\\"a\\\"\"} 1.500000\n"));
+        assert!(text.contains("fusion_sd_subscribers{instance=\"node \\\"a\\\"\"} 2\n"));
+        assert!(text.contains("fusion_requests_rejected_total{instance=\"node \\\"a\\\"\"} 3\n"));
+        assert!(text.contains("fusion_messages_received_total{instance=\"node \\\"a\\\"\",service=\"0x1234\"} 4\n"));
+        assert!(text.contains("# TYPE fusion_handler_duration_seconds summary\n"));
+        assert!(text.contains("fusion_handler_duration_seconds_sum{instance=\"node \\\"a\\\"\",service=\"0x1234\"} 0.001250\n"));
+        assert!(text.contains("fusion_handler_duration_seconds_count{instance=\"node \\\"a\\\"\",service=\"0x1234\"} 2\n"));
+    }
+
+    #[test]
+    fn test_prometheus_metrics_served() {
+        use std::io::{Read, Write};
+
+        let runtime = runtime_with("metrics", r#"{ "instances": { "test": { "metrics": { "port": 0 } } } }"#);
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        let addr = loop {
+            if let Some(addr) = runtime.metrics_addr() {
+                break addr;
+            }
+            assert!(std::time::Instant::now() < deadline, "metrics exporter did not start");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        let scrape = |path: &str| {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = scrape("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("fusion_requests_served_total{instance=\"test\"} 0\n"));
+        assert!(scrape("/").starts_with("HTTP/1.1 404"));
+
+        runtime.stop();
+        event_loop.join().unwrap();
+    }
+}
@@ -5,205 +5,463 @@
 //! ## Key Types
 //!
 //! - [`SomeIpRuntime`] - Main runtime for service providers and consumers
+//! - [`SomeIpHandle`] - Thread-safe, cloneable facade for application code
 //! - [`RequestHandler`] - Trait for implementing service handlers
 //! - [`ServiceClient`] - Trait for client proxy implementations
+//! - [`ClientContext`] - Request path through the runtime attached to client proxies
+//! - [`FieldHandle`] - Provider-side value of a field, notified on change
 //! - [`ThreadPool`] - Concurrent request handling
 //!
 //! ## Lifecycle
 //!
 //! 1. Load configuration: `SomeIpRuntime::load("config.json", "my_instance")`
-//! 2. Register services: `runtime.offer_service("alias", handler)`
-//! 3. Start runtime: `runtime.run()`
-//! 4. Stop gracefully: `runtime.stop()`
+//! 2. Obtain a handle: `let handle = runtime.handle()`
+//! 3. Register services: `handle.offer_service("alias", handler)`
+//! 4. Start runtime: `handle.run()`
+//! 5. Stop gracefully: `handle.stop()`
 //!
 //! ## Example
 //!
 //! ```ignore
-//! let runtime = SomeIpRuntime::load("config.json", "server");
-//! runtime.offer_service("math", Box::new(MathServiceServer::new(provider)));
-//! runtime.run();
+//! let handle = SomeIpRuntime::load("config.json", "server").handle();
+//! handle.offer_service("math", Box::new(MathServiceServer::new(provider)));
+//! handle.run();
 //! ```
 
 pub mod threadpool;
-pub mod dispatcher;
 pub mod config;
+pub mod handle;
+pub mod deadline;
+pub mod forward;
+pub mod echo;
+pub mod eventgroup;
+pub mod field;
+pub mod reorder;
+pub mod report;
+pub mod stats;
+#[cfg(feature = "prometheus")]
+pub mod metrics;
+pub mod subscription;
+pub mod client;
+pub mod quota;
+pub mod readiness;
+pub mod async_runtime;
+pub mod auth;
+pub mod access;
+pub mod binding;
+pub mod reload;
+pub mod validate;
+pub mod overrides;
+pub mod error;
+pub mod builder;
+pub mod vsomeip;
+pub mod netif;
+
+#[cfg(test)]
+mod tests;
 
 pub use threadpool::*;
-use config::{SystemConfig, InstanceConfig};
-use std::fs::File;
-use std::io::BufReader;
+pub use handle::{Completion, SomeIpHandle};
+pub use async_runtime::AsyncSomeIpRuntime;
+use handle::Command;
+use deadline::{Execution, ExecutionPolicy, OverrunTracker};
+use forward::{ForwardQueue, ForwardStats};
+use echo::{EchoService, Heartbeat};
+use eventgroup::{Delivery, EventgroupRegistry, OfferedEventgroup};
+use reorder::{ReorderBuffer, ReorderStats};
+pub use report::ShutdownReport;
+pub use reload::ReloadSummary;
+pub use validate::{ConfigError, ConfigIssue};
+pub use error::RuntimeError;
+pub use builder::SomeIpRuntimeBuilder;
+pub use stats::RuntimeStats;
+use report::RuntimeCounters;
+use subscription::{NackReason, OwnSubscription, RetryPolicy, SubscriptionAdded, SubscriptionNack, SubscriptionResult, SubscriptionState};
+pub use client::{error_return_code, ClientContext, PendingRequest, RequestHandle, RequestPolicy};
+use client::{RequestLimit, ResponseSlot};
+pub use field::FieldHandle;
+pub use auth::MessageAuthenticator;
+use auth::AuthGuard;
+use access::AccessPolicy;
+use quota::{Guarded, QuotaUsage, ServiceQuota};
+use config::{SystemConfig, InstanceConfig, QosProfile};
+use binding::Bindings;
+use std::io::IoSlice;
 
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, Condvar, Mutex, PoisonError, RwLock, Weak};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::net::{SocketAddr, Ipv4Addr, Ipv6Addr, IpAddr};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::thread;
 use std::time::Duration;
-use std::sync::atomic::{AtomicBool, Ordering};
-use crate::transport::{UdpTransport, SomeIpTransport};
-use crate::sd::machine::{ServiceDiscovery, SdListener};
-use crate::codec::SomeIpHeader;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use crate::transport::{UdpTransport, TcpTransport, TcpConnectionPool, SomeIpTransport, MAX_FRAME_LEN};
+use crate::sd::machine::{DiscoveredService, EventMulticast, ServiceDiscovery, SdIngressStats, SdListener, SdSnapshot};
+use crate::sd::options::SdOption;
+use crate::codec::{RequestId, ReturnCode, SessionIdManager, SomeIpDeserialize, SomeIpHeader};
+use crate::codec::schema::{self, SchemaRegistry, TypeSchema};
+use crate::trace;
+use crate::codec::tp::{TpReassembler, TpReassemblyStats, TpSender};
+use crate::e2e::{E2eGuard, E2eProtector};
+
+/// Outcome of a handler call: the response payload (`None` sends no response), or the
+/// return code of an ERROR response to send instead.
+pub type HandlerResult = Result<Option<Vec<u8>>, ReturnCode>;
 
 pub trait RequestHandler: Send + Sync {
     fn service_id(&self) -> u16;
     fn major_version(&self) -> u8;
     fn minor_version(&self) -> u32;
-    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> Option<Vec<u8>>;
+    /// Handle a request or notification. For requests, `Ok(Some(payload))` is sent back
+    /// in a RESPONSE and `Err(code)` in an ERROR carrying `code`. Errors from
+    /// fire-and-forget calls are only logged; results of notifications are ignored.
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult;
+
+    /// Check a request before `handle` runs. Any code other than `ReturnCode::Ok` is sent
+    /// back in an ERROR response (or the fire-and-forget call dropped) without calling
+    /// `handle`; typically `UnknownMethod` or `MalformedMessage`. Accepts everything by default.
+    fn validate(&self, _header: &SomeIpHeader, _payload: &[u8]) -> ReturnCode {
+        ReturnCode::Ok
+    }
 }
 
 pub trait ServiceClient {
     const SERVICE_ID: u16;
-    fn new(transport: Arc<dyn SomeIpTransport>, target: SocketAddr) -> Self;
+    /// Called by `get_client` with the context that sends requests to the discovered
+    /// service through the runtime.
+    fn new(context: ClientContext) -> Self;
+}
+
+use crate::logging::{FusionLogger, ConsoleLogger, ContextLogger, LevelFilter, LogContext, LogLevel};
+use crate::clock::Timestamp;
+use crate::buffer::{self, PooledBuffer};
+
+/// Client ID in the request IDs of notifications sent by the runtime
+const NOTIFICATION_CLIENT_ID: u16 = 0x0000;
+
+/// Next Client ID handed to an instance that does not configure one
+static NEXT_CLIENT_ID: AtomicU16 = AtomicU16::new(1);
+
+/// Allocate a Client ID unique among the runtimes of this process, skipping 0x0000.
+fn allocate_client_id() -> u16 {
+    loop {
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        if id != NOTIFICATION_CLIENT_ID {
+            return id;
+        }
+    }
+}
+
+/// Longest the SD thread blocks between polls, should a timer be started without waking it
+const SD_MAX_IDLE_WAIT: Duration = Duration::from_secs(1);
+
+/// Size of the event loop's receive buffer: the longest message a TCP stream frames,
+/// which also holds any UDP datagram
+const RECEIVE_BUFFER_LEN: usize = 8 + MAX_FRAME_LEN;
+
+/// Longest the event loop blocks without traffic; bounds how late TP reassemblies expire
+const MAX_IDLE_WAIT: Duration = Duration::from_millis(100);
+
+/// Where the response to an outstanding request, or the provider's error, is delivered.
+enum ResponseSender {
+    /// A task awaiting a [`PendingRequest`] or a store-and-forward request
+    Task(tokio::sync::oneshot::Sender<Result<Vec<u8>, ReturnCode>>),
+    /// A thread blocked in [`SomeIpRuntime::call_blocking`]
+    Thread(ResponseSlot),
 }
 
-use crate::logging::{FusionLogger, ConsoleLogger, LogLevel};
+impl ResponseSender {
+    fn send(self, response: Result<Vec<u8>, ReturnCode>) {
+        match self {
+            ResponseSender::Task(tx) => {
+                let _ = tx.send(response);
+            }
+            ResponseSender::Thread(slot) => slot.send(response),
+        }
+    }
+}
+
+/// How a request reaches its target.
+#[derive(Clone)]
+enum Route {
+    /// Pooled TCP connection to the target, read by the event loop
+    Tcp,
+    /// Given UDP-like transport, or the shared-memory route / default UDP transport if `None`
+    Udp(Option<Arc<dyn SomeIpTransport>>),
+}
+
+impl Route {
+    fn from_proto(proto: u8) -> Self {
+        if proto == 0x06 { Route::Tcp } else { Route::Udp(None) }
+    }
+}
+/// A notification held for in-order delivery
+type HeldEvent = (SomeIpHeader, Vec<u8>);
+
+/// Decodes an event payload and calls the typed callback registered with `on_event`.
+type EventCallback = Arc<dyn Fn(&[u8]) -> std::io::Result<()> + Send + Sync>;
+
+/// Request handlers of offered services, keyed by (service, instance)
+type ServiceHandlers = HashMap<(u16, u16), Arc<dyn RequestHandler>>;
 
 pub struct SomeIpRuntime {
-    udp_transports: Vec<Arc<dyn SomeIpTransport>>,
-    tcp_transports: Vec<Arc<dyn SomeIpTransport>>,
+    /// Handed to client proxies so they can send requests without keeping the runtime alive
+    self_ref: Weak<SomeIpRuntime>,
+    /// Endpoints and their transports; replaced by `reload_config`
+    bindings: RwLock<Arc<Bindings>>,
+    /// Shared-memory inboxes shadowing the UDP transports, for peers on the same host
+    #[cfg(target_os = "linux")]
+    shm_transports: Vec<Arc<crate::transport::ShmTransport>>,
+    /// Host identity advertised in offers while shared memory is enabled
+    shm_host: Option<String>,
     sd: Arc<Mutex<ServiceDiscovery>>,
-    services: Arc<RwLock<HashMap<u16, Box<dyn RequestHandler>>>>,
+    services: Arc<RwLock<ServiceHandlers>>,
+    /// Instance offered at each local address, to tell apart requests to several
+    /// instances of one service; keyed by (service, local address)
+    instance_endpoints: RwLock<HashMap<(u16, SocketAddr), u16>>,
+    /// Handlers of the notifications of required services
+    notification_handlers: RwLock<HashMap<u16, Arc<dyn RequestHandler>>>,
+    /// Typed notification callbacks keyed by (service, event)
+    event_callbacks: RwLock<HashMap<(u16, u16), EventCallback>>,
+    /// Execution deadlines of offered services keyed by (service, instance)
+    execution_policies: Arc<RwLock<HashMap<(u16, u16), ExecutionPolicy>>>,
+    overruns: Mutex<OverrunTracker>,
     running: Arc<AtomicBool>,
-    config: Option<InstanceConfig>,
-    endpoints: HashMap<String, config::EndpointConfig>,
-    /// Maps endpoint names to their actual bound ports (resolves ephemeral port 0)
-    bound_ports: HashMap<String, u16>,
-    pending_requests: Arc<Mutex<HashMap<(u16, u16, u16), tokio::sync::oneshot::Sender<Vec<u8>>>>>,
-    session_manager: Arc<Mutex<HashMap<(u16, u16), u16>>>,
-    tp_reassembler: Arc<Mutex<crate::codec::tp::TpReassembler>>,
+    /// Replaced by `reload_config`
+    config: RwLock<Option<Arc<InstanceConfig>>>,
+    /// Named QoS profiles referenced by `providing`/`required` entries
+    qos_profiles: RwLock<HashMap<String, QosProfile>>,
+    /// Reply channels of outstanding requests, keyed by (service, method, request ID)
+    pending_requests: Arc<Mutex<HashMap<(u16, u16, RequestId), ResponseSender>>>,
+    /// Client ID of the requests sent by this instance
+    client_id: u16,
+    /// Next Session ID per (service, method)
+    session_manager: Arc<Mutex<SessionIdManager>>,
+    /// Store-and-forward queues of required services, keyed by (service, instance)
+    forward_queues: Mutex<HashMap<(u16, u16), ForwardQueue<ResponseSender>>>,
+    /// Outgoing TCP connections keyed by remote endpoint (requests, reliable subscriptions and event delivery)
+    tcp_clients: TcpConnectionPool,
+    /// DTLS endpoints of the system, sent to from a DTLS transport
+    dtls_targets: HashSet<SocketAddr>,
+    tp_reassembler: Arc<Mutex<TpReassembler>>,
+    /// Threads running request handlers, if `worker_threads` is configured
+    workers: Option<ThreadPool>,
     logger: Arc<dyn FusionLogger>,
+    /// Mutations queued by `SomeIpHandle`, applied on the runtime thread
+    command_tx: Sender<Command>,
+    command_rx: Mutex<Receiver<Command>>,
+    /// Wakes the event loop when it blocks waiting for traffic; `None` if it cannot be created
+    waker: Option<readiness::Waker>,
+    /// Wakes the SD thread when it blocks until its next timer; `None` if it cannot be created
+    sd_waker: Option<Arc<readiness::Waker>>,
+    schema_registry: Arc<RwLock<SchemaRegistry>>,
+    schema_diagnostics: Arc<AtomicBool>,
+    /// Heartbeats of built-in echo services, published from the event loop
+    heartbeats: Mutex<Vec<Heartbeat>>,
+    /// Reordering settings (window, max hold) of required services, by service ID
+    reorder_configs: HashMap<u16, (u16, Duration)>,
+    /// Configured reliability of required eventgroups, keyed by (service, eventgroup)
+    eventgroup_reliability: HashMap<(u16, u16), bool>,
+    /// Acknowledgement timeout and retries of `subscribe`, by service ID
+    subscription_policies: HashMap<u16, RetryPolicy>,
+    /// Response timeout and retries of requests from `request_retry`, by service ID
+    request_policies: HashMap<u16, RequestPolicy>,
+    /// Bounds on outstanding requests from `request_limit`, by service ID
+    request_limits: HashMap<u16, RequestLimit>,
+    /// Eventgroups this instance subscribed to, keyed by (service, eventgroup)
+    own_subscriptions: Mutex<HashMap<(u16, u16), OwnSubscription>>,
+    /// Subscriptions sent once their service is offered: configured automatic subscriptions
+    /// before discovery, and subscriptions lost to a provider reboot
+    awaiting_offer: Mutex<HashSet<(u16, u16)>>,
+    /// Set by `on_subscription_nack`
+    subscription_nack_callback: RwLock<Option<SubscriptionNackCallback>>,
+    /// Set by `on_subscription_added`
+    subscription_added_callback: RwLock<Option<SubscriptionAddedCallback>>,
+    /// Eventgroups declared for offered services, and the events they carry
+    eventgroups: RwLock<EventgroupRegistry>,
+    /// `multicast` endpoints of offered services, by service ID
+    multicast_groups: HashMap<u16, SocketAddr>,
+    /// Sockets joined to the multicast groups of eventgroups we subscribed to, by group
+    multicast_receivers: Mutex<HashMap<SocketAddr, Arc<dyn SomeIpTransport>>>,
+    /// Current values of bound fields, keyed by (service, notifier event), sent to new subscribers
+    field_values: Mutex<HashMap<(u16, u16), Vec<u8>>>,
+    /// Resource quotas of offered services, by service ID
+    quotas: HashMap<u16, Arc<ServiceQuota>>,
+    /// Allow-lists of offered services with `access` rules, by service ID
+    access_policies: HashMap<u16, Arc<AccessPolicy>>,
+    /// E2E protection of methods and events, keyed by (service, method/event)
+    e2e_guards: Mutex<HashMap<(u16, u16), E2eGuard>>,
+    /// Message authentication of services, by service ID
+    auth_guards: Mutex<HashMap<u16, AuthGuard>>,
+    /// Per-event reorder buffers, keyed by (service, event)
+    reorder_buffers: Mutex<HashMap<(u16, u16), ReorderBuffer<HeldEvent>>>,
+    /// Arrival time of the latest notification of each (service, event)
+    event_times: Mutex<HashMap<(u16, u16), Timestamp>>,
+    instance_name: String,
+    /// Address the Prometheus exporter listens on while the event loop runs
+    #[cfg(feature = "prometheus")]
+    metrics_addr: Mutex<Option<SocketAddr>>,
+    started_at: Timestamp,
+    counters: RuntimeCounters,
+    /// Set once the shutdown report has been emitted, so stop() and drop report only once
+    report_emitted: AtomicBool,
+    /// Whether `run()` is executing, signalled through `loop_exited` when it returns
+    loop_active: Mutex<bool>,
+    loop_exited: Condvar,
+    /// Config file the event loop reloads on SIGHUP, and the SIGHUP count last handled
+    #[cfg(target_os = "linux")]
+    sighup_reload: Mutex<Option<(String, u64)>>,
+}
+
+/// Called on the runtime thread for every NACK of one of our subscriptions.
+type SubscriptionNackCallback = Arc<dyn Fn(&SubscriptionNack) + Send + Sync>;
+
+/// Events to send to one subscriber, as (event ID, payload).
+pub type InitialEvents = Vec<(u16, Vec<u8>)>;
+
+/// Called on the runtime thread for every subscriber admitted to one of our eventgroups.
+type SubscriptionAddedCallback = Arc<dyn Fn(&SubscriptionAdded) -> InitialEvents + Send + Sync>;
+
+/// Outcome of a queued command that did (`ok`) or did not find what it names.
+fn found(ok: bool, message: impl FnOnce() -> String) -> std::io::Result<()> {
+    if ok { Ok(()) } else { Err(std::io::Error::new(std::io::ErrorKind::NotFound, message())) }
+}
+
+/// Reason to refuse a received message of `size` bytes before it is processed, if any.
+fn check_message(header: &SomeIpHeader, size: usize) -> Option<ReturnCode> {
+    if header.protocol_version != SomeIpHeader::SOMEIP_PROTOCOL_VERSION {
+        return Some(ReturnCode::WrongProtocolVersion);
+    }
+    // The length field covers 8 header bytes plus the payload; more than received means truncation
+    if header.length < 8 || header.length as usize + 8 > size {
+        return Some(ReturnCode::MalformedMessage);
+    }
+    None
 }
 
 impl SomeIpRuntime {
+    /// Load `instance_name` from `config_path`. Panics if the configuration is invalid or an
+    /// endpoint cannot be bound; see [`try_load`](Self::try_load) for handling that instead.
     pub fn load(config_path: &str, instance_name: &str) -> Arc<Self> {
-        let logger = ConsoleLogger::new();
-        logger.log(LogLevel::Info, "Runtime", &format!("Loading config from {}", config_path));
+        Self::try_load(config_path, instance_name).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let file = File::open(config_path).expect("Failed to open config file");
-        let reader = BufReader::new(file);
-        let sys_config: SystemConfig = serde_json::from_reader(reader).expect("Failed to parse config json");
-        
-        let instance_config = sys_config.instances.get(instance_name)
-            .unwrap_or_else(|| panic!("Instance '{}' not found in config", instance_name))
-            .clone();
+    /// Like [`load`](Self::load), logging through `logger`.
+    pub fn load_with_logger(config_path: &str, instance_name: &str, logger: Arc<dyn FusionLogger>) -> Arc<Self> {
+        Self::try_load_with_logger(config_path, instance_name, logger).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let mut udp_transports: Vec<Arc<dyn SomeIpTransport>> = Vec::new();
-        let mut tcp_transports: Vec<Arc<dyn SomeIpTransport>> = Vec::new();
-        let mut bound_endpoints: HashMap<(String, u16, String), Arc<dyn SomeIpTransport>> = HashMap::new();
-        let mut bound_ports: HashMap<String, u16> = HashMap::new();
+    /// Load `instance_name` from `config_path`, returning configuration and bind errors.
+    pub fn try_load(config_path: &str, instance_name: &str) -> Result<Arc<Self>, RuntimeError> {
+        Self::try_load_with_logger(config_path, instance_name, ConsoleLogger::new())
+    }
 
-        // 1. Identify all interfaces used by this instance
-        let mut iface_aliases = Vec::new();
-        // Add interfaces from unicast_bind
-        for iface in instance_config.unicast_bind.keys() {
-            if !iface_aliases.contains(iface) { iface_aliases.push(iface.clone()); }
-        }
-        // Add interfaces from providing.offer_on
-        for svc in instance_config.providing.values() {
-            for iface in svc.offer_on.keys() {
-                if !iface_aliases.contains(iface) { iface_aliases.push(iface.clone()); }
-            }
-        }
-        // Add interfaces from required.find_on
-        for req in instance_config.required.values() {
-             for iface in &req.find_on {
-                 if !iface_aliases.contains(iface) { iface_aliases.push(iface.clone()); }
-             }
-        }
-        // Legacy support
-        if iface_aliases.is_empty() {
-             for iface in &instance_config.interfaces {
-                 if !iface_aliases.contains(iface) { iface_aliases.push(iface.clone()); }
-             }
-        }
+    /// Like [`try_load`](Self::try_load), logging through `logger`. Every line carries the instance
+    /// name, and the interface and service it concerns where known, as a [`LogContext`].
+    pub fn try_load_with_logger(config_path: &str, instance_name: &str, logger: Arc<dyn FusionLogger>) -> Result<Arc<Self>, RuntimeError> {
+        let logger = Self::instance_logger(logger, instance_name);
+        logger.log(LogLevel::Info, "Runtime", &format!("Loading config from {}", config_path));
+        let sys_config = validate::read_config(config_path, instance_name)?;
+        Self::from_config(sys_config, instance_name, logger)
+    }
 
-        if iface_aliases.is_empty() && !sys_config.interfaces.is_empty() {
-             // Fallback pattern
-             if sys_config.interfaces.contains_key("primary") {
-                 iface_aliases.push("primary".to_string());
-             } else {
-                 if let Some(first) = sys_config.interfaces.keys().next() {
-                     iface_aliases.push(first.clone());
-                 }
-             }
-        }
+    /// Load the application `application` from the vsomeip configuration at `config_path`.
+    pub fn try_load_vsomeip(config_path: &str, application: &str) -> Result<Arc<Self>, RuntimeError> {
+        Self::try_load_vsomeip_with_logger(config_path, application, ConsoleLogger::new())
+    }
 
-        let mut all_discovered_endpoints = sys_config.endpoints.clone();
-        
-        // 2. Identify all Endpoints to Bind
-        // We must bind:
-        // - All unicast_bind endpoints (Control)
-        // - All offer_on endpoints (Data)
-        let mut endpoints_to_bind = Vec::new();
-        
-        // From unicast_bind
-        for ep_name in instance_config.unicast_bind.values() {
-            endpoints_to_bind.push(ep_name.clone());
+    /// Like [`try_load_vsomeip`](Self::try_load_vsomeip), logging through `logger`. Settings of the
+    /// file the runtime does not use are logged as warnings.
+    pub fn try_load_vsomeip_with_logger(config_path: &str, application: &str, logger: Arc<dyn FusionLogger>) -> Result<Arc<Self>, RuntimeError> {
+        let logger = Self::instance_logger(logger, application);
+        logger.log(LogLevel::Info, "Runtime", &format!("Loading vsomeip config from {}", config_path));
+        let converted = vsomeip::read_vsomeip(config_path)?;
+        for setting in &converted.ignored {
+            logger.log(LogLevel::Warn, "Runtime", &format!("vsomeip setting '{}' is not supported and is ignored", setting));
         }
-        // From offer_on
-        for svc in instance_config.providing.values() {
-            for ep_name in svc.offer_on.values() {
-                endpoints_to_bind.push(ep_name.clone());
-            }
+        converted.system.validate(application)?;
+        Self::from_config(converted.system, application, logger)
+    }
+
+    /// Start building a runtime configured in code rather than from a file.
+    pub fn builder(instance_name: &str) -> SomeIpRuntimeBuilder {
+        SomeIpRuntimeBuilder::new(instance_name)
+    }
+
+    /// `logger` filtered by level and tagged with the instance name.
+    fn instance_logger(logger: Arc<dyn FusionLogger>, instance_name: &str) -> Arc<dyn FusionLogger> {
+        Arc::new(ContextLogger::new(
+            Arc::new(LevelFilter::new(logger, LogLevel::Debug)),
+            LogContext::default().with_instance(instance_name),
+        ))
+    }
+
+    /// Bind and set up `instance_name` of a validated `sys_config`, logging through `logger`
+    /// (from [`instance_logger`](Self::instance_logger)).
+    fn from_config(mut sys_config: SystemConfig, instance_name: &str, logger: Arc<dyn FusionLogger>) -> Result<Arc<Self>, RuntimeError> {
+        let instance_config = sys_config.instances[instance_name].clone();
+        if let Some(level) = instance_config.logging.level {
+            logger.set_default_level(level);
         }
-        // Legacy Config fallback (if used)
-        if let Some(ep) = &instance_config.endpoint {
-            endpoints_to_bind.push(ep.clone());
+        for (component, level) in &instance_config.logging.components {
+            logger.set_level(component, *level);
         }
 
-        for alias in &iface_aliases {
-            let iface_cfg = sys_config.interfaces.get(alias)
-                .unwrap_or_else(|| panic!("Interface alias '{}' not found", alias));
-            
-            // Merge interface-specific endpoints
-            for (name, ep) in &iface_cfg.endpoints {
-                all_discovered_endpoints.insert(name.clone(), ep.clone());
-            }
+        // 1. Identify all interfaces and endpoints used by this instance, with `auto`
+        //    addresses taken from the host's network interfaces
+        let detected = netif::resolve(&mut sys_config, instance_name)?;
+        for (alias, local) in &detected {
+            logger.log(LogLevel::Debug, "Runtime", &format!("Interface '{}' is '{}' (index {}, IPv4 {:?}, IPv6 {:?})", alias, local.name, local.index, local.v4, local.v6));
         }
+        let binding::EndpointPlan { interfaces: iface_aliases, endpoints: all_discovered_endpoints, to_bind } =
+            binding::plan(&sys_config, &instance_config);
 
-        // Bind gathered endpoints
-        for ep_name in endpoints_to_bind {
-            if let Some(ep) = all_discovered_endpoints.get(&ep_name) {
-                let ip = ep.ip.clone();
-                let port = ep.port;
-                let proto = ep.protocol.to_lowercase();
-                
-                // Heuristic: only bind local unicast IPs
-                if let Ok(addr) = ip.parse::<std::net::IpAddr>() {
-                    if addr.is_multicast() { continue; }
-                }
-
-                let key = (ip.clone(), port, proto.clone());
-                if !bound_endpoints.contains_key(&key) {
-                    let addr_str = if ep.version == 6 { format!("[{}]:{}", ip, port) } else { format!("{}:{}", ip, port) };
-                    let addr: SocketAddr = addr_str.parse().expect("Invalid address");
-
-                    if proto == "tcp" {
-                        let server = crate::transport::TcpServer::bind(addr).expect("STRICT BINDING: Failed to bind TCP server");
-                        let transport = Arc::new(crate::transport::TcpServerTransport::new(server));
-                        transport.set_nonblocking(true).unwrap();
-                        let actual_addr = transport.local_addr().unwrap_or(addr);
-                        bound_ports.insert(ep_name.clone(), actual_addr.port());
-                        bound_endpoints.insert((ip, actual_addr.port(), proto.clone()), transport.clone());
-                        tcp_transports.push(transport);
-                        logger.log(LogLevel::Info, "Runtime", &format!("Bound tcp server on {}", actual_addr));
-                    } else {
-                        let transport = UdpTransport::new(addr).expect("STRICT BINDING: Failed to bind UDP transport");
-                        let transport_arc: Arc<dyn SomeIpTransport> = Arc::new(transport);
-                        transport_arc.set_nonblocking(true).unwrap();
-                        let actual_addr = transport_arc.local_addr().expect("Failed to get local addr");
-                        bound_ports.insert(ep_name.clone(), actual_addr.port());
-                        bound_endpoints.insert((ip, actual_addr.port(), proto.clone()), transport_arc.clone());
-                        udp_transports.push(transport_arc);
-                        logger.log(LogLevel::Info, "Runtime", &format!("Bound udp transport on {}", actual_addr));
+        // 2. Bind them
+        let (bindings, _) = binding::bind_all(all_discovered_endpoints.clone(), &to_bind, &Bindings::default(), logger.as_ref())?;
+
+        // Shadow each UDP transport with a shared-memory inbox under the same address
+        #[cfg(target_os = "linux")]
+        let mut shm_transports = Vec::new();
+        let mut shm_host = None;
+        if let Some(shm_cfg) = &instance_config.shared_memory {
+            #[cfg(target_os = "linux")]
+            {
+                shm_host = crate::transport::shm::host_id();
+                if shm_host.is_none() {
+                    logger.log(LogLevel::Warn, "Runtime", "Shared memory disabled: host identity unavailable");
+                }
+                for udp in bindings.udp.iter().filter(|_| shm_host.is_some()) {
+                    let Ok(addr) = udp.local_addr() else { continue };
+                    let shm = crate::transport::ShmTransport::bind(addr, shm_cfg.ring_size_kb * 1024)
+                        .and_then(|shm| shm.set_nonblocking(true).map(|_| shm));
+                    match shm {
+                        Ok(shm) => {
+                            shm_transports.push(Arc::new(shm));
+                            logger.log(LogLevel::Info, "Runtime", &format!("Bound shared-memory inbox for {}", addr));
+                        }
+                        Err(e) => logger.log(LogLevel::Warn, "Runtime", &format!("No shared-memory inbox for {}, using UDP only: {}", addr, e)),
                     }
                 }
             }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = shm_cfg;
+                logger.log(LogLevel::Warn, "Runtime", "Shared memory is only supported on Linux; using UDP");
+            }
         }
 
         // 3. Initialize SD state machine with listeners
         let mut sd = ServiceDiscovery::new();
+        sd.set_accept_self_offers(instance_config.sd.accept_self_offers);
+        sd.set_timing(&instance_config.sd);
+        sd.set_request_response_delay(
+            Duration::from_millis(instance_config.sd.request_response_delay_min_ms),
+            Duration::from_millis(instance_config.sd.request_response_delay_max_ms),
+        );
+        sd.set_ingress_limits(instance_config.sd.max_packets_per_poll, instance_config.sd.max_backlog);
+        Self::require_versions(&mut sd, &instance_config);
         for alias in &iface_aliases {
-            let iface_cfg = sys_config.interfaces.get(alias).unwrap();
+            let iface_cfg = &sys_config.interfaces[alias];
             let sd_cfg = if let Some(ref s) = iface_cfg.sd { s } else { continue; };
             
             let v4_ep = sd_cfg.endpoint_v4.as_ref().and_then(|name| iface_cfg.endpoints.get(name));
@@ -211,14 +469,16 @@ impl SomeIpRuntime {
             
             if v4_ep.is_none() && v6_ep.is_none() { continue; }
 
-            // Find local unicast IP for this interface
+            // Find local unicast IP for this interface, or else the detected one
             let local_ip_v4 = iface_cfg.endpoints.values()
                 .find(|e| e.version == 4 && e.ip.parse::<IpAddr>().map(|a| !a.is_multicast()).unwrap_or(false))
-                .and_then(|e| e.ip.parse::<Ipv4Addr>().ok());
+                .and_then(|e| e.ip.parse::<Ipv4Addr>().ok())
+                .or_else(|| detected.get(alias).and_then(|local| local.v4));
             
             let local_ip_v6 = iface_cfg.endpoints.values()
                 .find(|e| e.version == 6 && e.ip.parse::<IpAddr>().map(|a| !a.is_multicast()).unwrap_or(false))
-                .and_then(|e| e.ip.parse::<Ipv6Addr>().ok());
+                .and_then(|e| e.ip.parse::<Ipv6Addr>().ok())
+                .or_else(|| detected.get(alias).and_then(|local| local.v6));
 
             let mut transport_v4 = None;
             let mut mcast_v4 = None;
@@ -234,36 +494,34 @@ impl SomeIpRuntime {
                 let bind_ip = instance_bind_ip
                     .or(local_ip_v4);
 
-                let bind_ip = bind_ip.unwrap_or_else(|| {
-                    let msg = format!("STRICT BINDING: No bind IP resolved for SD v4 on {}. Aborting.", alias);
-                    logger.log(LogLevel::Error, "Runtime", &msg);
-                    panic!("{}", msg);
-                });
+                let what = || format!("SD v4 socket on '{}'", alias);
+                let bind_ip = bind_ip.ok_or_else(|| RuntimeError::bind(what(), std::io::Error::new(std::io::ErrorKind::AddrNotAvailable, "no IPv4 unicast endpoint on the interface")))?;
+                let mcast_ip = ep.ip.parse::<Ipv4Addr>()
+                    .map_err(|e| RuntimeError::bind(what(), std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
 
                 let bind_addr = SocketAddr::new(IpAddr::V4(bind_ip), ep.port);
-                let mcast_addr = SocketAddr::new(IpAddr::V4(ep.ip.parse::<Ipv4Addr>().unwrap()), ep.port);
+                let mcast_addr = SocketAddr::new(IpAddr::V4(mcast_ip), ep.port);
                 
                 // Use iface_cfg.name for SO_BINDTODEVICE if available, else alias
                 let if_name = if iface_cfg.name.is_empty() { alias.as_str() } else { iface_cfg.name.as_str() };
 
-                let t = UdpTransport::new_multicast(bind_addr, mcast_addr, Some(if_name)).expect("STRICT BINDING: Failed to create SD v4 transport");
+                let t = UdpTransport::new_multicast(bind_addr, mcast_addr, Some(if_name)).map_err(|e| RuntimeError::bind(what(), e))?;
                 let _ = t.set_multicast_loop_v4(true);
                 let _ = t.set_multicast_ttl_v4(instance_config.sd.multicast_hops as u32);
                 if let (Some(lip), Ok(mip)) = (local_ip_v4, ep.ip.parse::<Ipv4Addr>()) {
-                    t.join_multicast_v4(&mip, &lip).expect("STRICT BINDING: Failed to join SD v4 multicast group");
+                    t.join_multicast_v4(&mip, &lip).map_err(|e| RuntimeError::bind(what(), e))?;
                     let _ = t.set_multicast_if_v4(&lip);
                     mcast_v4 = Some(SocketAddr::new(IpAddr::V4(mip), ep.port));
                 }
-                transport_v4 = Some(t);
+                transport_v4 = Some(Box::new(t) as Box<dyn SomeIpTransport>);
             }
 
             let mut transport_v6 = None;
             let mut mcast_v6 = None;
             if let Some(ep) = v6_ep {
-                let mcast_ip_v6 = ep.ip.parse::<Ipv6Addr>().unwrap_or_else(|e| {
-                    logger.log(LogLevel::Error, "Runtime", &format!("Invalid IPv6 multicast address '{}': {}", ep.ip, e));
-                    panic!("Invalid IPv6 multicast address");
-                });
+                let what = || format!("SD v6 socket on '{}'", alias);
+                let mcast_ip_v6 = ep.ip.parse::<Ipv6Addr>()
+                    .map_err(|e| RuntimeError::bind(what(), std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
                 
                 // Determine bind IP
                 let instance_bind_ip = instance_config.unicast_bind.get(alias)
@@ -279,15 +537,14 @@ impl SomeIpRuntime {
                     let mcast_addr = SocketAddr::new(IpAddr::V6(mcast_ip_v6), ep.port);
                     let if_name = if iface_cfg.name.is_empty() { alias.as_str() } else { iface_cfg.name.as_str() };
                     
-                    let t = UdpTransport::new_multicast(bind_addr, mcast_addr, Some(if_name)).expect("STRICT BINDING: Failed to create SD v6 transport");
+                    let t = UdpTransport::new_multicast(bind_addr, mcast_addr, Some(if_name)).map_err(|e| RuntimeError::bind(what(), e))?;
                     let _ = t.set_multicast_loop_v6(true);
                     let _ = t.set_multicast_hops_v6(instance_config.sd.multicast_hops as u32);
-                    // Need iface index
-                    let idx = Self::resolve_iface_index(&iface_cfg.name);
-                    t.join_multicast_v6(&mcast_ip_v6, idx).expect("STRICT BINDING: Failed to join SD v6 multicast group");
+                    let idx = Self::iface_index(alias, &iface_cfg.name, detected.get(alias), logger.as_ref());
+                    t.join_multicast_v6(&mcast_ip_v6, idx).map_err(|e| RuntimeError::bind(what(), e))?;
                     let _ = t.set_multicast_if_v6(idx);
                     mcast_v6 = Some(SocketAddr::new(IpAddr::V6(mcast_ip_v6), ep.port));
-                    transport_v6 = Some(t);
+                    transport_v6 = Some(Box::new(t) as Box<dyn SomeIpTransport>);
                 }
             }
 
@@ -300,56 +557,558 @@ impl SomeIpRuntime {
                 local_ip_v4,
                 local_ip_v6,
             });
-            logger.log(LogLevel::Info, "Runtime", &format!("SD listener added for interface '{}'", alias));
+            logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_interface(alias), &format!("SD listener added for interface '{}'", alias));
+        }
+
+        let forward_queues = instance_config.required.values()
+            .filter_map(|req| req.store_and_forward.as_ref().map(|sf| {
+                ((req.service_id, req.instance_id), ForwardQueue::new(sf.max_queued, Duration::from_millis(sf.ttl_ms)))
+            }))
+            .collect();
+
+        let reorder_configs = instance_config.required.values()
+            .filter_map(|req| req.reorder.as_ref().map(|r| (req.service_id, (r.window, Duration::from_millis(r.max_hold_ms)))))
+            .collect();
+
+        let eventgroup_reliability = instance_config.required.values()
+            .flat_map(|req| req.eventgroups.values().map(|eg| ((req.service_id, eg.eventgroup_id), eg.reliable)))
+            .collect();
+
+        let quotas: HashMap<u16, Arc<ServiceQuota>> = instance_config.providing.values()
+            .filter_map(|svc| svc.quota.as_ref().map(|q| (svc.service_id, Arc::new(ServiceQuota::new(q.clone())))))
+            .collect();
+        for (service_id, quota) in &quotas {
+            if let Some(max) = quota.max_subscribers() {
+                sd.set_subscriber_limit(*service_id, max as usize);
+            }
+        }
+        let mut access_policies = HashMap::new();
+        for (alias, svc) in instance_config.providing.iter().filter(|(_, svc)| !svc.access.is_empty()) {
+            let (policy, invalid) = AccessPolicy::from_config(&svc.access);
+            for source in invalid {
+                logger.log(LogLevel::Warn, "Runtime", &format!("Ignoring access rule with invalid source '{}' of service '{}'", source, alias));
+            }
+            let policy = Arc::new(policy);
+            sd.set_access_policy(svc.service_id, policy.clone());
+            access_policies.insert(svc.service_id, policy);
+        }
+        let multicast_groups: HashMap<u16, SocketAddr> = instance_config.providing.values()
+            .filter_map(|svc| {
+                let ep = all_discovered_endpoints.get(svc.multicast.as_ref()?)?;
+                Some((svc.service_id, SocketAddr::new(ep.ip.parse().ok()?, ep.port)))
+            })
+            .collect();
+        let mut eventgroups = EventgroupRegistry::default();
+        for svc in instance_config.providing.values().filter(|svc| !svc.eventgroups.is_empty()) {
+            let heartbeat = svc.echo.as_ref().map(|echo| echo.heartbeat_eventgroup);
+            sd.set_offered_eventgroups(svc.service_id, svc.eventgroups.values().map(|eg| eg.eventgroup_id).chain(heartbeat));
+            for eg in svc.eventgroups.values() {
+                let group = multicast_groups.get(&svc.service_id).copied().filter(|_| eg.multicast_threshold > 0);
+                sd.set_eventgroup_multicast(svc.service_id, eg.eventgroup_id, group);
+                eventgroups.insert(svc.service_id, eg.eventgroup_id, OfferedEventgroup { events: eg.events.clone(), multicast_threshold: eg.multicast_threshold });
+            }
+        }
+
+        // Eventgroups with `subscribe` set wait for their service's offer
+        let auto_subscriptions = Self::auto_subscriptions(&instance_config, &iface_aliases, logger.as_ref());
+        let awaiting_offer = auto_subscriptions.keys().copied().collect();
+
+        let subscription_policies = instance_config.required.values()
+            .filter_map(|req| req.subscription_retry.as_ref().map(|r| (req.service_id, RetryPolicy::from_config(r))))
+            .collect();
+
+        let mut request_policies = HashMap::new();
+        for (alias, req) in &instance_config.required {
+            let Some(retry) = &req.request_retry else { continue };
+            let (policy, invalid) = RequestPolicy::from_config(retry);
+            for key in invalid {
+                logger.log(LogLevel::Warn, "Runtime", &format!("Ignoring request retry settings for invalid method ID '{}' of service '{}'", key, alias));
+            }
+            request_policies.insert(req.service_id, policy);
         }
+        let request_limits = instance_config.required.values()
+            .filter_map(|req| req.request_limit.as_ref().map(|limit| (req.service_id, RequestLimit::new(limit))))
+            .collect();
 
-        Arc::new(Self {
-            udp_transports,
-            tcp_transports,
+        let mut e2e_guards = HashMap::new();
+        let e2e_entries = instance_config.providing.iter().map(|(alias, svc)| (alias, svc.service_id, &svc.e2e))
+            .chain(instance_config.required.iter().map(|(alias, req)| (alias, req.service_id, &req.e2e)));
+        for (alias, service_id, entries) in e2e_entries {
+            let (guards, invalid) = crate::e2e::guards_from_config(entries);
+            for key in invalid {
+                logger.log(LogLevel::Warn, "Runtime", &format!("Ignoring E2E protection for invalid method/event ID '{}' of service '{}'", key, alias));
+            }
+            e2e_guards.extend(guards.into_iter().map(|(id, guard)| ((service_id, id), guard)));
+        }
+
+        let (command_tx, command_rx) = channel();
+        let client_id = instance_config.client_id.unwrap_or_else(allocate_client_id);
+        let waker = match readiness::Waker::new() {
+            Ok(waker) => Some(waker),
+            Err(e) => {
+                logger.log(LogLevel::Warn, "Runtime", &format!("Event loop falls back to polling: cannot create waker: {}", e));
+                None
+            }
+        };
+        let sd_waker = match readiness::Waker::new() {
+            Ok(waker) => Some(Arc::new(waker)),
+            Err(e) => {
+                logger.log(LogLevel::Warn, "Runtime", &format!("SD thread falls back to polling: cannot create waker: {}", e));
+                None
+            }
+        };
+        let schema_diagnostics = instance_config.schema_diagnostics;
+        let workers = instance_config.worker_threads.filter(|n| *n > 0).map(ThreadPool::new);
+        let tp_reassembler = TpReassembler::with_limits(
+            Duration::from_millis(instance_config.tp.reassembly_timeout_ms),
+            instance_config.tp.max_buffer_bytes,
+        );
+        let mut tcp_clients = TcpConnectionPool::new();
+        tcp_clients.set_backoff(
+            Duration::from_millis(instance_config.tcp.reconnect_backoff_min_ms),
+            Duration::from_millis(instance_config.tcp.reconnect_backoff_max_ms),
+        );
+        // Magic Cookies of client connections: per pinned local endpoint, else instance-wide
+        let pinned_cookies: HashMap<SocketAddr, Option<u64>> = instance_config.required.values()
+            .filter_map(|req| all_discovered_endpoints.get(req.local_endpoint.as_ref()?))
+            .filter(|ep| ep.is_stream())
+            .filter_map(|ep| Some((SocketAddr::new(ep.ip.parse().ok()?, ep.port), ep.magic_cookie_interval_ms)))
+            .collect();
+        let default_cookies = instance_config.tcp.magic_cookie_interval_ms;
+        // Connections to TLS endpoints of the system, with the pinned TLS endpoints that
+        // identify this instance to them
+        let tls_targets: HashMap<SocketAddr, config::EndpointConfig> = sys_config.endpoints.values()
+            .chain(sys_config.interfaces.values().flat_map(|iface| iface.endpoints.values()))
+            .filter(|ep| ep.is_tls() && ep.port != 0)
+            .filter_map(|ep| Some((binding::unicast_addr(ep)?, ep.clone())))
+            .collect();
+        #[cfg(feature = "tls")]
+        let tls_identities: HashMap<SocketAddr, config::EndpointConfig> = instance_config.required.values()
+            .filter_map(|req| all_discovered_endpoints.get(req.local_endpoint.as_ref()?))
+            .filter(|ep| ep.is_tls())
+            .filter_map(|ep| Some((binding::unicast_addr(ep)?, ep.clone())))
+            .collect();
+        let dtls_targets: HashSet<SocketAddr> = sys_config.endpoints.values()
+            .chain(sys_config.interfaces.values().flat_map(|iface| iface.endpoints.values()))
+            .filter(|ep| ep.is_dtls() && ep.port != 0)
+            .filter_map(binding::unicast_addr)
+            .collect();
+        if !tls_targets.is_empty() || default_cookies.is_some() || pinned_cookies.values().any(Option::is_some) {
+            tcp_clients.set_handshake(Arc::new(move |connection| {
+                let local = connection.local_addr()?;
+                #[cfg(feature = "tls")]
+                if let Some(target) = tls_targets.get(&connection.peer_addr()?) {
+                    let (config, server_name) = binding::tls_client(target, tls_identities.get(&local))?;
+                    connection.start_tls(config, server_name)?;
+                }
+                let interval = pinned_cookies.get(&local).copied().unwrap_or(default_cookies);
+                connection.set_magic_cookie_interval(interval.map(Duration::from_millis));
+                Ok(())
+            }));
+        }
+        let runtime = Arc::new_cyclic(|self_ref| Self {
+            self_ref: self_ref.clone(),
+            bindings: RwLock::new(Arc::new(bindings)),
+            #[cfg(target_os = "linux")]
+            shm_transports,
+            shm_host,
             sd: Arc::new(Mutex::new(sd)),
             services: Arc::new(RwLock::new(HashMap::new())),
+            instance_endpoints: RwLock::new(HashMap::new()),
+            notification_handlers: RwLock::new(HashMap::new()),
+            event_callbacks: RwLock::new(HashMap::new()),
+            execution_policies: Arc::new(RwLock::new(HashMap::new())),
+            overruns: Mutex::new(OverrunTracker::default()),
             running: Arc::new(AtomicBool::new(true)),
-            config: Some(instance_config),
-            endpoints: all_discovered_endpoints,
-            bound_ports,
+            config: RwLock::new(Some(Arc::new(instance_config))),
+            qos_profiles: RwLock::new(sys_config.qos_profiles),
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
-            session_manager: Arc::new(Mutex::new(HashMap::new())),
-            tp_reassembler: Arc::new(Mutex::new(crate::codec::tp::TpReassembler::new())),
+            client_id,
+            session_manager: Arc::new(Mutex::new(SessionIdManager::new())),
+            forward_queues: Mutex::new(forward_queues),
+            tcp_clients,
+            dtls_targets,
+            tp_reassembler: Arc::new(Mutex::new(tp_reassembler)),
+            workers,
             logger,
-        })
+            command_tx,
+            command_rx: Mutex::new(command_rx),
+            waker,
+            sd_waker,
+            schema_registry: Arc::new(RwLock::new(SchemaRegistry::new())),
+            schema_diagnostics: Arc::new(AtomicBool::new(schema_diagnostics)),
+            heartbeats: Mutex::new(Vec::new()),
+            reorder_configs,
+            eventgroup_reliability,
+            subscription_policies,
+            request_policies,
+            request_limits,
+            own_subscriptions: Mutex::new(auto_subscriptions),
+            awaiting_offer: Mutex::new(awaiting_offer),
+            subscription_nack_callback: RwLock::new(None),
+            subscription_added_callback: RwLock::new(None),
+            eventgroups: RwLock::new(eventgroups),
+            multicast_groups,
+            multicast_receivers: Mutex::new(HashMap::new()),
+            field_values: Mutex::new(HashMap::new()),
+            quotas,
+            access_policies,
+            e2e_guards: Mutex::new(e2e_guards),
+            auth_guards: Mutex::new(HashMap::new()),
+            reorder_buffers: Mutex::new(HashMap::new()),
+            event_times: Mutex::new(HashMap::new()),
+            instance_name: instance_name.to_string(),
+            #[cfg(feature = "prometheus")]
+            metrics_addr: Mutex::new(None),
+            started_at: Timestamp::now(),
+            counters: RuntimeCounters::default(),
+            report_emitted: AtomicBool::new(false),
+            loop_active: Mutex::new(false),
+            loop_exited: Condvar::new(),
+            #[cfg(target_os = "linux")]
+            sighup_reload: Mutex::new(None),
+        });
+        runtime.offer_builtin_services();
+        runtime.find_auto_subscribed_services();
+        Ok(runtime)
     }
 
-    fn resolve_iface_index(name: &str) -> u32 {
-        if name.is_empty() { return 0; }
-        // Heuristic or system call
-        let idx = if name.to_lowercase().contains("lo") || name.to_lowercase().contains("loopback") {
-             if cfg!(target_os = "windows") { 1 } else { 1 } // typical lo index
-        } else {
-             0 // fallback
+    /// Start discovery of required services that have automatic subscriptions.
+    fn find_auto_subscribed_services(&self) {
+        let Some(cfg) = self.instance_config() else { return };
+        for (alias, req) in &cfg.required {
+            if req.eventgroups.values().any(|eg| eg.subscribe) {
+                self.find_service(alias);
+            }
+        }
+    }
+
+    /// Offer the built-in services enabled in `providing` (currently the echo service).
+    fn offer_builtin_services(&self) {
+        let Some(cfg) = self.instance_config() else { return };
+        for (alias, svc) in &cfg.providing {
+            let Some(echo) = &svc.echo else { continue };
+            let handler = EchoService::new(svc.service_id, svc.major_version, svc.minor_version);
+            if echo.heartbeat_ms > 0 {
+                self.heartbeats.lock().unwrap().push(Heartbeat {
+                    service_id: svc.service_id,
+                    eventgroup_id: echo.heartbeat_eventgroup,
+                    interval: Duration::from_millis(echo.heartbeat_ms),
+                    next: std::time::Instant::now(),
+                    sequence: 0,
+                    requests_served: handler.requests_served(),
+                });
+            }
+            self.offer_service(alias, Box::new(handler));
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(svc.service_id), &format!("Built-in echo service enabled as '{}' (0x{:04x})", alias, svc.service_id));
+        }
+    }
+
+    /// Deliver held events whose gap has timed out.
+    fn release_held_events(&self) {
+        if self.reorder_configs.is_empty() {
+            return;
+        }
+        let now = std::time::Instant::now();
+        let ready: Vec<HeldEvent> = self.reorder_buffers.lock().unwrap().values_mut()
+            .flat_map(|buf| buf.poll(now))
+            .collect();
+        for (header, payload) in ready {
+            let handler = self.notification_handlers.read().unwrap().get(&header.service_id).cloned();
+            let callback = self.event_callbacks.read().unwrap().get(&(header.service_id, header.method_id)).cloned();
+            self.deliver_notification(handler.as_ref(), callback.as_ref(), &header, &payload);
+        }
+    }
+
+    /// When the latest notification of an event arrived, on the shared timestamp scale.
+    pub fn last_event_at(&self, service_id: u16, event_id: u16) -> Option<Timestamp> {
+        self.event_times.lock().unwrap().get(&(service_id, event_id)).copied()
+    }
+
+    /// Reordering counters of a required service, summed over its events.
+    pub fn reorder_stats(&self, alias: &str) -> Option<ReorderStats> {
+        let service_id = self.instance_config()?.required.get(alias)?.service_id;
+        if !self.reorder_configs.contains_key(&service_id) {
+            return None;
+        }
+        let mut stats = ReorderStats::default();
+        for ((sid, _), buf) in self.reorder_buffers.lock().unwrap().iter() {
+            if *sid == service_id {
+                stats.merge(&buf.stats());
+            }
+        }
+        Some(stats)
+    }
+
+    /// Discard TP messages that were not completed within the reassembly timeout.
+    fn expire_tp_reassemblies(&self) {
+        let expired = self.tp_reassembler.lock().unwrap().cleanup(std::time::Instant::now());
+        if expired > 0 {
+            self.logger.log(LogLevel::Warn, "Runtime", &format!("Discarded {} incomplete TP message(s) after the reassembly timeout", expired));
+        }
+    }
+
+    /// Counters of SOME/IP-TP reassembly: messages in progress, completed, expired and dropped.
+    pub fn tp_stats(&self) -> TpReassemblyStats {
+        self.tp_reassembler.lock().unwrap().stats()
+    }
+
+    /// Drop connections and held events of rebooted peers; our subscriptions to their
+    /// services wait for the next offer.
+    fn recover_from_peer_reboots(&self) {
+        let reboots = self.sd.lock().unwrap().take_peer_reboots();
+        for reboot in reboots {
+            self.logger.log(LogLevel::Warn, "Runtime", &format!("Peer {} rebooted; dropping {} of its service(s) until offered again", reboot.address, reboot.services.len()));
+            self.tcp_clients.retain(|endpoint| endpoint.ip() != reboot.address.ip());
+            let lost = |service_id: u16| reboot.services.iter().any(|(sid, _)| *sid == service_id);
+            // Session IDs restart with the peer, so held events would wait for sessions already seen
+            self.reorder_buffers.lock().unwrap().retain(|(service_id, _), _| !lost(*service_id));
+            let own = self.own_subscriptions.lock().unwrap();
+            self.awaiting_offer.lock().unwrap().extend(own.keys().filter(|(service_id, _)| lost(*service_id)));
+        }
+    }
+
+    /// Send the subscriptions waiting for an offer whose service SD now knows.
+    fn subscribe_when_offered(&self) {
+        let ready: Vec<((u16, u16), OwnSubscription)> = {
+            let mut awaiting = self.awaiting_offer.lock().unwrap();
+            if awaiting.is_empty() {
+                return;
+            }
+            let own = self.own_subscriptions.lock().unwrap();
+            let sd = self.sd.lock().unwrap();
+            let ready: Vec<_> = awaiting.iter()
+                .filter_map(|key| own.get(key).map(|sub| (*key, sub.clone())))
+                .filter(|((service_id, _), sub)| sd.get_service(*service_id, sub.instance_id).is_some())
+                .collect();
+            // Unsubscribed in the meantime, or offered
+            awaiting.retain(|key| own.contains_key(key) && !ready.iter().any(|(k, _)| k == key));
+            ready
         };
-        // Print to stderr (since we don't have logger instance in static method easily) or just return
-        // Note: For real fix we should use if_nametoindex.
-        idx
+        for ((service_id, eventgroup_id), sub) in ready {
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Service 0x{:04x} offered; subscribing to EventGroup {}", service_id, eventgroup_id));
+            let _ = self.try_subscribe(service_id, sub.instance_id, eventgroup_id, sub.ttl, &sub.iface_alias);
+        }
+    }
+
+    /// Log the NACKs SD received for our subscriptions and pass them to the `on_subscription_nack` callback.
+    fn report_subscription_nacks(&self) {
+        let nacks = self.sd.lock().unwrap().take_subscription_nacks();
+        if nacks.is_empty() {
+            return;
+        }
+        let callback = self.subscription_nack_callback.read().unwrap().clone();
+        for nack in nacks {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(nack.service_id), &format!("Subscription to Service 0x{:04x} EventGroup {} refused by {}", nack.service_id, nack.eventgroup_id, nack.provider));
+            if let Some(callback) = &callback {
+                callback(&nack);
+            }
+        }
+    }
+
+    /// Join the multicast groups providers announced for our subscriptions, so events keep
+    /// arriving once they switch from unicast to multicast.
+    fn join_event_multicast(&self) {
+        let announced = self.sd.lock().unwrap().take_event_multicast();
+        for EventMulticast { service_id, eventgroup_id, group, interface } in announced {
+            if self.multicast_receivers.lock().unwrap().contains_key(&group) {
+                continue;
+            }
+            let ctx = LogContext::default().with_service(service_id);
+            match Self::multicast_receiver(group, interface) {
+                Ok(receiver) => {
+                    self.logger.log_with(LogLevel::Info, "Runtime", &ctx, &format!("Joined multicast group {} for Service 0x{:04x} EventGroup {}", group, service_id, eventgroup_id));
+                    self.multicast_receivers.lock().unwrap().insert(group, receiver);
+                }
+                Err(e) => self.logger.log_with(LogLevel::Warn, "Runtime", &ctx, &format!("Cannot join multicast group {} for Service 0x{:04x} EventGroup {}: {}", group, service_id, eventgroup_id, e)),
+            }
+        }
+    }
+
+    /// Send each new subscriber the current value of the bound fields notified in its
+    /// eventgroup, then the events returned by the `on_subscription_added` callback.
+    fn send_initial_events(&self) {
+        let added = self.sd.lock().unwrap().take_subscriptions_added();
+        if added.is_empty() {
+            return;
+        }
+        let callback = self.subscription_added_callback.read().unwrap().clone();
+        for sub in added {
+            let SubscriptionAdded { service_id, eventgroup_id, subscriber, .. } = sub;
+            self.logger.log_with(LogLevel::Debug, "Runtime", &LogContext::default().with_service(service_id), &format!("Subscriber {} added to Service 0x{:04x} EventGroup {}", subscriber.endpoint, service_id, eventgroup_id));
+            let events = self.eventgroups.read().unwrap().get(service_id, eventgroup_id)
+                .map(|eventgroup| eventgroup.events.clone())
+                .unwrap_or_default();
+            let mut initial: InitialEvents = {
+                let values = self.field_values.lock().unwrap();
+                events.into_iter().filter_map(|event_id| values.get(&(service_id, event_id)).map(|payload| (event_id, payload.clone()))).collect()
+            };
+            if let Some(callback) = &callback {
+                initial.extend(callback(&sub));
+            }
+            for (event_id, payload) in initial {
+                self.notify_subscribers(service_id, event_id, &payload, Delivery::unicast(vec![subscriber]));
+            }
+        }
+    }
+
+    /// Record the current value of a field sent to new subscribers of its notifier.
+    pub(crate) fn store_field(&self, service_id: u16, notifier_id: u16, payload: Vec<u8>) {
+        self.field_values.lock().unwrap().insert((service_id, notifier_id), payload);
+    }
+
+    /// Record a changed field value and notify it; returns the number of subscribers reached.
+    pub(crate) fn update_field(&self, service_id: u16, notifier_id: u16, payload: Vec<u8>) -> usize {
+        self.store_field(service_id, notifier_id, payload.clone());
+        self.fire_event(service_id, notifier_id, &payload)
+    }
+
+    /// Socket receiving the datagrams sent to `group` on the interface with address `interface`.
+    fn multicast_receiver(group: SocketAddr, interface: Option<IpAddr>) -> std::io::Result<Arc<dyn SomeIpTransport>> {
+        let bind_ip = interface.unwrap_or(if group.is_ipv4() { IpAddr::V4(Ipv4Addr::UNSPECIFIED) } else { IpAddr::V6(Ipv6Addr::UNSPECIFIED) });
+        let transport = UdpTransport::new_multicast(SocketAddr::new(bind_ip, group.port()), group, None)?;
+        match (group.ip(), interface) {
+            (IpAddr::V4(group_ip), Some(IpAddr::V4(local))) => transport.join_multicast_v4(&group_ip, &local)?,
+            (IpAddr::V4(group_ip), _) => transport.join_multicast_v4(&group_ip, &Ipv4Addr::UNSPECIFIED)?,
+            (IpAddr::V6(group_ip), local) => transport.join_multicast_v6(&group_ip, local.and_then(netif::index_of).unwrap_or(0))?,
+        }
+        transport.set_nonblocking(true)?;
+        Ok(Arc::new(transport))
+    }
+
+    /// Publish heartbeats of built-in echo services that are due.
+    fn publish_heartbeats(&self) {
+        let now = std::time::Instant::now();
+        let due: Vec<(u16, u16, Vec<u8>)> = self.heartbeats.lock().unwrap().iter_mut()
+            .filter_map(|hb| hb.poll(now).map(|p| (hb.service_id, hb.eventgroup_id, p)))
+            .collect();
+        for (service_id, eventgroup_id, payload) in due {
+            self.send_notification(service_id, eventgroup_id, echo::EVENT_HEARTBEAT, &payload);
+        }
+    }
+
+    /// Index of the network interface of `alias` for IPv6 multicast: the detected one, or else
+    /// looked up by `name`. 0 (any interface, chosen by the system) if neither is known.
+    fn iface_index(alias: &str, name: &str, detected: Option<&netif::LocalAddresses>, logger: &dyn FusionLogger) -> u32 {
+        if let Some(local) = detected.filter(|local| local.index != 0) {
+            return local.index;
+        }
+        if name.is_empty() {
+            logger.log(LogLevel::Warn, "Runtime", &format!("Interface '{}' has no name; IPv6 multicast uses the system's default interface", alias));
+            return 0;
+        }
+        match netif::index(name) {
+            Ok(index) => index,
+            Err(e) => {
+                logger.log(LogLevel::Warn, "Runtime", &format!("Interface '{}': {}; IPv6 multicast uses the system's default interface", alias, e));
+                0
+            }
+        }
     }
 
     
     pub fn get_transport_v4(&self) -> Option<Arc<dyn SomeIpTransport>> {
-        self.udp_transports.iter().find(|t| t.local_addr().map(|a| a.is_ipv4()).unwrap_or(false))
-            .cloned()
+        self.udp_transport(false, false)
     }
 
     pub fn get_transport_v6(&self) -> Option<Arc<dyn SomeIpTransport>> {
-        self.udp_transports.iter().find(|t| t.local_addr().map(|a| a.is_ipv6()).unwrap_or(false))
+        self.udp_transport(true, false)
+    }
+
+    /// First UDP transport of the address family, running DTLS or not.
+    fn udp_transport(&self, v6: bool, dtls: bool) -> Option<Arc<dyn SomeIpTransport>> {
+        let bindings = self.bindings();
+        bindings.udp.iter()
+            .filter(|t| bindings.is_dtls(t) == dtls)
+            .find(|t| t.local_addr().is_ok_and(|a| a.is_ipv6() == v6))
             .cloned()
     }
 
+    /// UDP transport to send to `target` from: a DTLS one for a DTLS endpoint of the system.
+    fn transport_to(&self, target: SocketAddr) -> Option<Arc<dyn SomeIpTransport>> {
+        self.udp_transport(target.is_ipv6(), self.dtls_targets.contains(&target))
+    }
+
+    /// Logger of this runtime. Its levels start from the instance's `logging` config and
+    /// can be changed with [`FusionLogger::set_level`] and [`FusionLogger::set_default_level`].
     pub fn get_logger(&self) -> Arc<dyn FusionLogger> {
         self.logger.clone()
     }
     
+    /// Source address a required service is pinned to via `local_endpoint`.
+    fn local_endpoint_addr(&self, alias: &str) -> Option<SocketAddr> {
+        let cfg = self.instance_config()?;
+        let ep_name = cfg.required.get(alias)?.local_endpoint.as_ref()?;
+        let bindings = self.bindings();
+        let ep = bindings.endpoints.get(ep_name)?;
+        let port = bindings.ports.get(ep_name).copied().unwrap_or(ep.port);
+        let addr_str = if ep.version == 6 { format!("[{}]:{}", ep.ip, port) } else { format!("{}:{}", ep.ip, port) };
+        addr_str.parse().ok()
+    }
+
+    /// Transport that sends from `local`: the UDP transport bound there, or a TCP
+    /// connection to `endpoint` opened from it, pooled so the event loop reads its responses.
+    fn pinned_transport(&self, local: SocketAddr, endpoint: SocketAddr, proto: u8) -> Option<Arc<dyn SomeIpTransport>> {
+        if proto == 0x06 {
+            return match self.tcp_clients.get_from(local, endpoint) {
+                Ok(client) => {
+                    self.logger.log(LogLevel::Info, "Runtime", &format!("TCP connected to {} from {}", endpoint, local));
+                    self.wake();
+                    Some(client)
+                }
+                Err(e) => {
+                    self.logger.log(LogLevel::Error, "Runtime", &format!("TCP connect to {} from {} failed: {}", endpoint, local, e));
+                    None
+                }
+            };
+        }
+        let transport = self.bindings().udp.iter().find(|t| t.local_addr().is_ok_and(|a| a == local)).cloned();
+        if transport.is_none() {
+            self.logger.log(LogLevel::Error, "Runtime", &format!("No UDP transport bound on local endpoint {}", local));
+        }
+        transport
+    }
+
+    /// Shared-memory transport to `endpoint` if the offer of `service_id` there was made
+    /// from this host with shared memory enabled and the peer's inbox is live.
+    #[cfg(target_os = "linux")]
+    fn shm_route(&self, sd: &ServiceDiscovery, service_id: u16, endpoint: SocketAddr) -> Option<Arc<dyn SomeIpTransport>> {
+        let host = self.shm_host.as_deref()?;
+        let remote = sd.find_service_at(service_id, 0xFFFF, endpoint)?;
+        if remote.config("shm").as_deref() != Some(host) {
+            return None;
+        }
+        self.shm_transport_to(endpoint)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn shm_route(&self, _sd: &ServiceDiscovery, _service_id: u16, _endpoint: SocketAddr) -> Option<Arc<dyn SomeIpTransport>> {
+        None
+    }
+
+    /// Our shared-memory transport of the matching address family, if `dest` has a live inbox.
+    #[cfg(target_os = "linux")]
+    fn shm_transport_to(&self, dest: SocketAddr) -> Option<Arc<dyn SomeIpTransport>> {
+        self.shm_transports.iter()
+            .find(|t| t.local_addr().is_ok_and(|a| a.is_ipv4() == dest.is_ipv4()) && t.can_reach(dest))
+            .map(|t| t.clone() as Arc<dyn SomeIpTransport>)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn shm_transport_to(&self, _dest: SocketAddr) -> Option<Arc<dyn SomeIpTransport>> {
+        None
+    }
+
+    /// Build a client proxy for `endpoint` around a [`ClientContext`] that sends through
+    /// `transport` with the alias's request policy.
+    fn attached_client<T: ServiceClient>(&self, alias: &str, (service_id, instance_id): (u16, u16), transport: Arc<dyn SomeIpTransport>, endpoint: SocketAddr, proto: u8) -> T {
+        let route = if proto == 0x06 { Route::Tcp } else { Route::Udp(Some(transport.clone())) };
+        T::new(ClientContext::new(self.self_ref.clone(), alias, (service_id, instance_id), endpoint, transport, route, self.request_policy(alias, service_id)))
+    }
+
     pub fn get_client<T: ServiceClient>(&self, alias: &str) -> Option<T> {
         // Resolve Alias
-        let (service_id, instance_id) = if let Some(cfg) = &self.config {
+        let (service_id, instance_id) = if let Some(cfg) = self.instance_config() {
             if let Some(req_cfg) = cfg.required.get(alias) {
                 (req_cfg.service_id, req_cfg.instance_id)
             } else {
@@ -359,7 +1118,7 @@ impl SomeIpRuntime {
             (T::SERVICE_ID, 0xFFFF)
         };
 
-        let timeout_ms = if let Some(cfg) = &self.config {
+        let timeout_ms = if let Some(cfg) = self.instance_config() {
             cfg.sd.request_timeout_ms
         } else {
             2000
@@ -376,37 +1135,40 @@ impl SomeIpRuntime {
             {
                 let sd = self.sd.lock().unwrap();
                 if let Some((endpoint, proto)) = sd.get_service(service_id, instance_id) {
-                    self.logger.log(LogLevel::Info, "Runtime", &format!("Discovered service '{}' (0x{:04x}) at {} (proto 0x{:02x})", alias, service_id, endpoint, proto));
+                    self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Discovered service '{}' (0x{:04x}) at {} (proto 0x{:02x})", alias, service_id, endpoint, proto));
+
+                    if let Some(local) = self.local_endpoint_addr(alias) {
+                        let transport = self.pinned_transport(local, endpoint, proto)?;
+                        return Some(self.attached_client(alias, (service_id, instance_id), transport, endpoint, proto));
+                    }
                     
                     let transport: Arc<dyn SomeIpTransport> = if proto == 0x06 {
-                        // TCP: Connect to the discovered endpoint
-                        match crate::transport::TcpTransport::connect(endpoint) {
-                            Ok(client) => {
-                                client.set_nonblocking(true).ok();
-                                self.logger.log(LogLevel::Info, "Runtime",
-                                    &format!("TCP connected to {}", endpoint));
-                                Arc::new(client)
-                            }
+                        // TCP: pooled connection to the discovered endpoint
+                        match self.tcp_client(endpoint) {
+                            Ok(client) => client,
                             Err(e) => {
                                 self.logger.log(LogLevel::Error, "Runtime",
                                     &format!("TCP connect to {} failed: {}", endpoint, e));
                                 return None;
                             }
                         }
+                    } else if let Some(shm) = self.shm_route(&sd, service_id, endpoint) {
+                        self.logger.log(LogLevel::Info, "Runtime", &format!("Using shared memory for '{}' at {}", alias, endpoint));
+                        shm
                     } else {
                         // UDP (or default)
-                        self.logger.log(LogLevel::Info, "Runtime", &format!("Searching for UDP transport for {}, count={}", endpoint, self.udp_transports.len()));
-                        for (i, t) in self.udp_transports.iter().enumerate() {
+                        self.logger.log(LogLevel::Info, "Runtime", &format!("Searching for UDP transport for {}, count={}", endpoint, self.bindings().udp.len()));
+                        for (i, t) in self.bindings().udp.iter().enumerate() {
                             if let Ok(la) = t.local_addr() {
                                 self.logger.log(LogLevel::Info, "Runtime", &format!("  [{}] local_addr={}", i, la));
                             }
                         }
                         if endpoint.is_ipv4() {
-                            self.udp_transports.iter().find(|t| t.local_addr().map(|a| a.is_ipv4()).unwrap_or(false))
+                            self.bindings().udp.iter().find(|t| t.local_addr().map(|a| a.is_ipv4()).unwrap_or(false))
                                 .cloned()
                                 .expect("No local UDP v4 transport available")
                         } else {
-                            if let Some(t) = self.udp_transports.iter().find(|t| t.local_addr().map(|a| a.is_ipv6()).unwrap_or(false)) {
+                            if let Some(t) = self.bindings().udp.iter().find(|t| t.local_addr().map(|a| a.is_ipv6()).unwrap_or(false)) {
                                 t.clone()
                             } else {
                                 self.logger.log(LogLevel::Error, "Runtime", "No local UDP v6 transport available for discovered v6 service");
@@ -415,12 +1177,12 @@ impl SomeIpRuntime {
                         }
                     };
                     
-                    return Some(T::new(transport, endpoint));
+                    return Some(self.attached_client(alias, (service_id, instance_id), transport, endpoint, proto));
                 }
             }
 
             if start.elapsed() >= timeout {
-                self.logger.log(LogLevel::Warn, "Runtime", &format!("Timeout waiting for service '{}' (0x{:04x})", alias, service_id));
+                self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Timeout waiting for service '{}' (0x{:04x})", alias, service_id));
                 return None;
             }
 
@@ -430,170 +1192,1642 @@ impl SomeIpRuntime {
 
 
     pub fn subscribe_eventgroup(&self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str) {
-        let mut sd = self.sd.lock().unwrap();
-        // Resolve ports from bound transports
-        // This is a bit complex in multi-interface, we might need a better way to find the port
-        // For now, use the first available transport's port for the given interface.
-        let port_v4 = self.udp_transports.iter().find(|t| t.local_addr().map(|a| a.is_ipv4()).unwrap_or(false))
-            .and_then(|t| t.local_addr().ok()).map(|a| a.port()).unwrap_or(0);
-        let port_v6 = self.udp_transports.iter().find(|t| t.local_addr().map(|a| a.is_ipv6()).unwrap_or(false))
-            .and_then(|t| t.local_addr().ok()).map(|a| a.port()).unwrap_or(0);
-        
-        sd.subscribe_eventgroup(service_id, instance_id, eventgroup_id, ttl, iface_alias, port_v4, port_v6);
-        self.logger.log(LogLevel::Info, "Runtime", &format!("Subscribing to Service 0x{:04x} EventGroup {} on {} (v4: {}, v6: {})", service_id, eventgroup_id, iface_alias, port_v4, port_v6));
+        let _ = self.try_subscribe(service_id, instance_id, eventgroup_id, ttl, iface_alias);
     }
 
-    pub fn offer_service(&self, alias: &str, instance: Box<dyn RequestHandler>) {
-        // Resolve Config
-        let (service_id, major, minor, instance_id, offer_on, multicast_name) = if let Some(cfg) = &self.config {
-            if let Some(prov_cfg) = cfg.providing.get(alias) {
-                (prov_cfg.service_id, prov_cfg.major_version, prov_cfg.minor_version, prov_cfg.instance_id, prov_cfg.offer_on.clone(), prov_cfg.multicast.clone())
-            } else {
-                panic!("Alias '{}' not found in config", alias);
-            }
-        } else {
-            panic!("offer_service requires a loaded config");
+    /// Subscribe to an eventgroup of the required service `alias`, with the instance and
+    /// interface of its configuration: the eventgroup's `interface`, `preferred_interface`,
+    /// the first `find_on` entry, or else the first SD interface. A `ttl` of 0 unsubscribes.
+    ///
+    /// Fails with `NotFound` if `alias` is not a required service, and with `Other` if the
+    /// SubscribeEventgroup cannot be sent, e.g. for a reliable eventgroup of a service that
+    /// has not been discovered yet.
+    pub fn subscribe_required(&self, alias: &str, eventgroup_id: u16, ttl: u32) -> std::io::Result<()> {
+        let required = self.instance_config().and_then(|cfg| {
+            let req = cfg.required.get(alias)?;
+            Some((req.service_id, req.instance_id, req.subscription_interface(eventgroup_id).cloned()))
+        });
+        let Some((service_id, instance_id, iface)) = required else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("'{}' is not a required service", alias)));
         };
-        
-        // Register in Dispatch Map
-        {
-            let mut services = self.services.write().unwrap();
-            services.insert(service_id, instance);
-        }
-        
-        // Register in SD for each relevant interface
-        let mut sd = self.sd.lock().unwrap();
-        
-        // Provide on all interfaces defined in offer_on
-        for (iface_alias, endpoint_name) in offer_on {
-            let mut final_port = 0;
-            let mut proto_id = 0x11;
-            
-            // Resolve the actual bound port for this endpoint.
-            if let Some(ep) = self.endpoints.get(&endpoint_name) {
-                 let protocol = ep.protocol.to_lowercase();
-                 proto_id = if protocol == "tcp" { 0x06 } else { 0x11 };
-                 // Use actual bound port (resolves ephemeral), fallback to config
-                 final_port = self.bound_ports.get(&endpoint_name).copied().unwrap_or(ep.port);
-            } else {
-                 self.logger.log(LogLevel::Warn, "Runtime", &format!("Endpoint '{}' not found for service '{}' on '{}'", endpoint_name, alias, iface_alias));
-            }
-
-            // Resolve Multicast
-            let multicast = if let Some(mcast_name) = multicast_name.as_ref() {
-                if let Some(m_ep) = self.endpoints.get(mcast_name) {
-                    if let Ok(m_ip) = m_ep.ip.parse::<std::net::IpAddr>() {
-                        Some((m_ip, m_ep.port))
-                    } else { None }
-                } else { None }
-            } else { None };
+        let iface = iface.or_else(|| self.sd.lock().unwrap().listeners.keys().min().cloned())
+            .ok_or_else(|| std::io::Error::other(format!("no interface to subscribe to EventGroup {} of '{}' on", eventgroup_id, alias)))?;
+        self.try_subscribe(service_id, instance_id, eventgroup_id, ttl, &iface)
+            .map_err(|reason| std::io::Error::other(format!("subscription to EventGroup {} of '{}' not sent: {:?}", eventgroup_id, alias, reason)))
+    }
 
-            sd.offer_service(service_id, instance_id, major, minor, &iface_alias, final_port, proto_id, multicast);
-            self.logger.log(LogLevel::Info, "Runtime", &format!("Offered Service '{}' (0x{:04x}) on {} (port {}, proto 0x{:02x})", 
-                alias, service_id, iface_alias, final_port, proto_id));
+    /// Subscribe to an eventgroup and wait for the provider's answer, retrying as configured
+    /// in the required service's `subscription_retry`. Answers are processed while `run()`
+    /// is running.
+    pub async fn subscribe(&self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str) -> SubscriptionResult {
+        let policy = self.subscription_policies.get(&service_id).copied().unwrap_or_else(|| {
+            RetryPolicy::once(Duration::from_millis(self.instance_config().map(|c| c.sd.request_timeout_ms).unwrap_or(2000)))
+        });
+        let mut attempt = 0;
+        loop {
+            let result = match self.try_subscribe(service_id, instance_id, eventgroup_id, ttl, iface_alias) {
+                Ok(()) => self.await_subscription(service_id, eventgroup_id, policy.ack_timeout).await,
+                Err(reason) => SubscriptionResult::Nacked(reason),
+            };
+            if !policy.should_retry(result, attempt) {
+                if result != SubscriptionResult::Acked {
+                    self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Subscription to Service 0x{:04x} EventGroup {} failed: {:?}", service_id, eventgroup_id, result));
+                }
+                return result;
+            }
+            attempt += 1;
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Subscription to Service 0x{:04x} EventGroup {}: {:?}, retry {}/{}", service_id, eventgroup_id, result, attempt, policy.max_retries));
+            tokio::time::sleep(policy.retry_delay).await;
         }
     }
 
-    pub fn register_notification_handler(&self, service_id: u16, handler: Box<dyn RequestHandler>) {
-        let mut services = self.services.write().unwrap();
-        services.insert(service_id, handler);
-        self.logger.log(LogLevel::Info, "Runtime", &format!("Registered notification handler for Service 0x{:04x}", service_id));
+    /// State of our subscription to an eventgroup; `None` if never subscribed.
+    /// Subscriptions are renewed automatically before their TTL runs out.
+    pub fn subscription_state(&self, service_id: u16, eventgroup_id: u16) -> Option<SubscriptionState> {
+        self.sd.lock().unwrap().subscription_state(service_id, eventgroup_id)
     }
-    
-    pub async fn send_request_and_wait(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> Option<Vec<u8>> {
-        let session_id = {
-            let mut mgr = self.session_manager.lock().unwrap();
-            let counter = mgr.entry((service_id, method_id)).or_insert(1);
-            let val = *counter;
-            *counter = if val == 0xFFFF { 1 } else { val + 1 };
-            val
-        };
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            let mut pending = self.pending_requests.lock().unwrap();
-            pending.insert((service_id, method_id, session_id), tx);
-        }
+    /// Call `callback` for every SubscribeEventgroupNack a provider sends for one of our
+    /// subscriptions, including NACKs of renewals. Replaces an earlier callback.
+    pub fn on_subscription_nack(&self, callback: Box<dyn Fn(&SubscriptionNack) + Send + Sync>) {
+        *self.subscription_nack_callback.write().unwrap() = Some(Arc::from(callback));
+    }
 
-        let mtu = 1400; 
-        let header_len = 20; // 16 (Header) + 4 (TP)
-        let max_segment_payload = (mtu - header_len) / 16 * 16;
-        
-        let transport = if target.is_ipv6() { self.get_transport_v6() } else { self.get_transport_v4() };
-        let transport = transport.expect("Required transport (UDP) not found for target family");
-
-        if payload.len() > max_segment_payload {
-            let segments = crate::codec::tp::segment_payload(payload, max_segment_payload);
-            for (tp_header, chunk) in segments {
-                 let header = SomeIpHeader::new(service_id, method_id, 0, session_id, 0x20, (4 + chunk.len()) as u32);
-                 let mut msg = header.serialize().to_vec();
-                 msg.extend_from_slice(&tp_header.serialize());
-                 msg.extend_from_slice(&chunk);
-                 
-                 if let Err(e) = transport.send(&msg, Some(target)) {
-                     self.logger.log(LogLevel::Error, "Runtime", &format!("Failed to send TP segment: {}", e));
-                     let mut pending = self.pending_requests.lock().unwrap();
-                     pending.remove(&(service_id, method_id, session_id));
-                     return None;
-                 }
-                 // Flow control
-                 thread::sleep(Duration::from_micros(100));
+    /// Wait until the pending subscription is answered or `timeout` passes.
+    async fn await_subscription(&self, service_id: u16, eventgroup_id: u16, timeout: Duration) -> SubscriptionResult {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let state = self.sd.lock().unwrap().subscription_state(service_id, eventgroup_id);
+            match state {
+                Some(SubscriptionState::Acked) => return SubscriptionResult::Acked,
+                Some(SubscriptionState::Nacked(reason)) => return SubscriptionResult::Nacked(reason),
+                Some(SubscriptionState::Pending) | Some(SubscriptionState::Expired) | None => {}
             }
-        } else {
-            let header = SomeIpHeader::new(service_id, method_id, 0, session_id, 0x00, payload.len() as u32);
-            let mut msg = header.serialize().to_vec();
-            msg.extend_from_slice(payload);
-            
-            if let Err(e) = transport.send(&msg, Some(target)) {
-                self.logger.log(LogLevel::Error, "Runtime", &format!("Failed to send request: {}", e));
-                let mut pending = self.pending_requests.lock().unwrap();
-                pending.remove(&(service_id, method_id, session_id));
-                return None;
+            if tokio::time::Instant::now() >= deadline {
+                return SubscriptionResult::Timeout;
             }
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
+    }
 
-        match tokio::time::timeout(Duration::from_secs(2), rx).await {
-            Ok(Ok(res)) => Some(res),
-            _ => {
-                let mut pending = self.pending_requests.lock().unwrap();
-                pending.remove(&(service_id, method_id, session_id));
-                None
+    /// Send a SubscribeEventgroup; failures to send are also recorded as the subscription's state.
+    fn try_subscribe(&self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str) -> Result<(), NackReason> {
+        {
+            let mut own = self.own_subscriptions.lock().unwrap();
+            if ttl > 0 {
+                own.insert((service_id, eventgroup_id), OwnSubscription { instance_id, ttl, iface_alias: iface_alias.to_string() });
+            } else {
+                own.remove(&(service_id, eventgroup_id));
+            }
+        }
+        let mut sd = self.sd.lock().unwrap();
+
+        // Reliable subscriptions connect first, then announce the connection's local endpoint
+        // (TCP endpoint option) so the provider delivers events over it. An eventgroup's
+        // configured reliability decides; otherwise services offered over TCP only are
+        // subscribed reliably, and a QoS transport preference picks when both are offered.
+        let reliable = self.eventgroup_reliability.get(&(service_id, eventgroup_id)).copied();
+        let offered = match reliable.map(|r| if r { 0x06 } else { 0x11 })
+            .or_else(|| self.service_qos(service_id, false).and_then(|q| q.transport_proto())) {
+            Some(proto) => sd.get_service_preferring(service_id, instance_id, proto),
+            None => sd.get_service(service_id, instance_id),
+        };
+        if let (Some(reliable), Some((endpoint, proto))) = (reliable, offered)
+            && reliable != (proto == 0x06) {
+            self.logger.log_with(LogLevel::Error, "Runtime", &LogContext::default().with_service(service_id), &format!("Not subscribing to Service 0x{:04x} EventGroup {}: configured {}, but offered at {} over {} only",
+                service_id, eventgroup_id, if reliable { "reliable" } else { "unreliable" }, endpoint, if proto == 0x06 { "TCP" } else { "UDP" }));
+            sd.reject_subscription(service_id, eventgroup_id, NackReason::NotOffered);
+            return Err(NackReason::NotOffered);
+        }
+        if reliable == Some(true) && offered.is_none() {
+            // The TCP path needs the provider's endpoint; a UDP subscription would be wrong
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Not subscribing to reliable Service 0x{:04x} EventGroup {}: service not discovered yet", service_id, eventgroup_id));
+            sd.reject_subscription(service_id, eventgroup_id, NackReason::NotOffered);
+            return Err(NackReason::NotOffered);
+        }
+        if let Some((endpoint, 0x06)) = offered {
+            match self.tcp_client(endpoint).and_then(|c| c.local_addr()) {
+                Ok(local) => {
+                    sd.subscribe_eventgroup_reliable(service_id, instance_id, eventgroup_id, ttl, local);
+                    self.wake_sd();
+                    self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Subscribing to Service 0x{:04x} EventGroup {} over TCP ({} -> {})", service_id, eventgroup_id, local, endpoint));
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.logger.log(LogLevel::Error, "Runtime", &format!("TCP connect to {} for subscription failed: {}", endpoint, e));
+                    sd.reject_subscription(service_id, eventgroup_id, NackReason::ConnectFailed);
+                    return Err(NackReason::ConnectFailed);
+                }
+            }
+        }
+
+        // Resolve ports from bound transports
+        // This is a bit complex in multi-interface, we might need a better way to find the port
+        // For now, use the first available transport's port for the given interface.
+        // A service offered at a DTLS endpoint delivers events to a DTLS transport
+        let dtls = offered.is_some_and(|(endpoint, _)| self.dtls_targets.contains(&endpoint));
+        let port_v4 = self.udp_transport(false, dtls)
+            .and_then(|t| t.local_addr().ok()).map(|a| a.port()).unwrap_or(0);
+        let port_v6 = self.udp_transport(true, dtls)
+            .and_then(|t| t.local_addr().ok()).map(|a| a.port()).unwrap_or(0);
+        
+        sd.subscribe_eventgroup(service_id, instance_id, eventgroup_id, ttl, iface_alias, port_v4, port_v6);
+        self.wake_sd();
+        self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_interface(iface_alias).with_service(service_id), &format!("Subscribing to Service 0x{:04x} EventGroup {} on {} (v4: {}, v6: {})", service_id, eventgroup_id, iface_alias, port_v4, port_v6));
+        Ok(())
+    }
+
+    /// Get the pooled TCP connection to `endpoint`, (re)connecting if there is none,
+    /// unless an earlier attempt failed within the reconnect backoff.
+    fn tcp_client(&self, endpoint: SocketAddr) -> std::io::Result<Arc<TcpTransport>> {
+        let connected = self.tcp_clients.is_connected(endpoint);
+        let client = self.tcp_clients.get(endpoint)?;
+        if !connected {
+            self.wake();
+            self.logger.log(LogLevel::Info, "Runtime", &format!("TCP connected to {}", endpoint));
+        }
+        Ok(client)
+    }
+
+    /// Send a notification for `event_id` to every subscriber of the eventgroup.
+    ///
+    /// UDP subscribers receive it over the local UDP transport of the matching address family.
+    /// TCP subscribers receive it over the connection they opened to one of our TCP servers,
+    /// falling back to a connection initiated from this side if there is none.
+    /// Returns the number of subscribers the notification was delivered to.
+    pub fn send_notification(&self, service_id: u16, eventgroup_id: u16, event_id: u16, payload: &[u8]) -> usize {
+        let subscribers = self.sd.lock().unwrap().get_subscribers(service_id, eventgroup_id);
+        let delivery = match self.eventgroups.read().unwrap().get(service_id, eventgroup_id) {
+            Some(eventgroup) => eventgroup.plan(subscribers, self.multicast_groups.get(&service_id).copied()),
+            None => Delivery::unicast(subscribers),
+        };
+        self.notify_subscribers(service_id, event_id, payload, delivery)
+    }
+
+    /// Call `callback` for every subscriber admitted to one of our eventgroups, right after it
+    /// was acknowledged (renewals excluded). The events it returns, as (event ID, payload), are
+    /// sent to that subscriber alone, e.g. the current state it would otherwise wait for.
+    /// Replaces an earlier callback.
+    pub fn on_subscription_added(&self, callback: Box<dyn Fn(&SubscriptionAdded) -> InitialEvents + Send + Sync>) {
+        *self.subscription_added_callback.write().unwrap() = Some(Arc::from(callback));
+    }
+
+    /// Declare an eventgroup of the provided service `alias` and the events it carries.
+    /// Subscriptions to eventgroups the service does not declare are NACKed from then on,
+    /// and [`fire_event`](Self::fire_event) delivers the events to its subscribers.
+    /// Once `multicast_threshold` UDP subscribers are registered, events are sent to the service's
+    /// `multicast` endpoint instead of to each of them (0: events are always unicast).
+    /// Returns false if the alias is not a provided service or an ID is not an event ID.
+    pub fn offer_eventgroup(&self, alias: &str, eventgroup_id: u16, events: &[u16], multicast_threshold: u16) -> bool {
+        let cfg = self.instance_config();
+        let Some(svc) = cfg.as_ref().and_then(|c| c.providing.get(alias)) else {
+            self.logger.log(LogLevel::Error, "Runtime", &format!("Cannot offer EventGroup {}: '{}' is not a provided service", eventgroup_id, alias));
+            return false;
+        };
+        if let Some(invalid) = events.iter().find(|id| !SomeIpHeader::is_event_id(**id)) {
+            self.logger.log_with(LogLevel::Error, "Runtime", &LogContext::default().with_service(svc.service_id), &format!("Cannot offer EventGroup {} of '{}': 0x{:04x} is not an event ID (0x8000-0xFFFF)", eventgroup_id, alias, invalid));
+            return false;
+        }
+        {
+            let mut sd = self.sd.lock().unwrap();
+            if !sd.offered_eventgroups.contains_key(&svc.service_id) {
+                // Declaring eventgroups restricts subscriptions; the echo heartbeat stays subscribable
+                sd.set_offered_eventgroups(svc.service_id, svc.echo.as_ref().map(|echo| echo.heartbeat_eventgroup));
+            }
+            sd.add_offered_eventgroup(svc.service_id, eventgroup_id);
+            let group = self.multicast_groups.get(&svc.service_id).copied().filter(|_| multicast_threshold > 0);
+            sd.set_eventgroup_multicast(svc.service_id, eventgroup_id, group);
+        }
+        self.wake_sd();
+        self.eventgroups.write().unwrap().insert(svc.service_id, eventgroup_id, OfferedEventgroup { events: events.to_vec(), multicast_threshold });
+        self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(svc.service_id), &format!("Offered EventGroup {} of '{}' with {} event(s)", eventgroup_id, alias, events.len()));
+        true
+    }
+
+    /// Send an event to the subscribers of every offered eventgroup that carries it.
+    /// Returns the number of subscribers it was delivered to.
+    pub fn fire_event(&self, service_id: u16, event_id: u16, payload: &[u8]) -> usize {
+        let eventgroups = self.eventgroups.read().unwrap().containing(service_id, event_id);
+        if eventgroups.is_empty() {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Not firing event 0x{:04x}: no offered eventgroup of Service 0x{:04x} carries it", event_id, service_id));
+            return 0;
+        }
+        let group = self.multicast_groups.get(&service_id).copied();
+        let mut delivery = Delivery::default();
+        {
+            let sd = self.sd.lock().unwrap();
+            let registry = self.eventgroups.read().unwrap();
+            for eventgroup_id in eventgroups {
+                let subscribers = sd.get_subscribers(service_id, eventgroup_id);
+                if let Some(eventgroup) = registry.get(service_id, eventgroup_id) {
+                    delivery.merge(eventgroup.plan(subscribers, group));
+                }
+            }
+        }
+        self.notify_subscribers(service_id, event_id, payload, delivery)
+    }
+
+    /// Send one notification as planned in `delivery`; returns the number of subscribers reached.
+    fn notify_subscribers(&self, service_id: u16, event_id: u16, payload: &[u8], delivery: Delivery) -> usize {
+        if !SomeIpHeader::is_event_id(event_id) {
+            self.logger.log(LogLevel::Error, "Runtime", &format!("Refusing to notify 0x{:04x}: not an event ID (0x8000-0xFFFF)", event_id));
+            return 0;
+        }
+        if delivery.is_empty() {
+            return 0;
+        }
+
+        if !self.quota_allows(service_id, payload.len()) {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Dropping event 0x{:04x} of {} bytes: exceeds the queue quota of Service 0x{:04x}", event_id, payload.len(), service_id));
+            return 0;
+        }
+
+        let request_id = self.next_request_id(NOTIFICATION_CLIENT_ID, service_id, event_id);
+        let mut payload = {
+            let mut buf = buffer::shared().get();
+            buf.extend_from_slice(payload);
+            buf
+        };
+        self.attach_schema(service_id, event_id, false, &mut payload);
+        self.protect_e2e(service_id, event_id, &mut payload);
+        let header = SomeIpHeader::new(service_id, event_id, request_id.client_id(), request_id.session_id(), 0x02, 0);
+        self.authenticate(&header, &mut payload);
+        // Over UDP, events larger than the endpoint's MTU go out as NOTIFICATION segments with TP (0x22)
+        let send_udp = |transport: &dyn SomeIpTransport, dest: SocketAddr| {
+            self.tp_sender(transport, service_id, true, None).send(&header, &payload, |msg| transport.send_vectored(msg, Some(dest)))
+        };
+
+        let mut delivered = 0;
+        if let Some(group) = delivery.multicast {
+            let transport = if group.is_ipv6() { self.get_transport_v6() } else { self.get_transport_v4() };
+            match transport.map(|t| send_udp(t.as_ref(), group)) {
+                Some(Ok(_)) => {
+                    self.counters.record_sent(service_id);
+                    delivered += delivery.multicast_subscribers.len();
+                }
+                Some(Err(e)) => {
+                    self.counters.record_error();
+                    self.logger.log(LogLevel::Warn, "Runtime", &format!("Failed to send event 0x{:04x} to multicast group {}: {}", event_id, group, e));
+                }
+                None => self.logger.log(LogLevel::Warn, "Runtime", &format!("No UDP transport to send event 0x{:04x} to multicast group {}", event_id, group)),
+            }
+        }
+        for sub in delivery.unicast {
+            let result = if sub.transport_proto == 0x06 {
+                let mut header = header.clone();
+                header.length = 8 + payload.len() as u32;
+                let header = header.serialize();
+                self.send_reliable(&[IoSlice::new(&header), IoSlice::new(&payload)], sub.endpoint)
+            } else {
+                // Subscribers with a live inbox on this host enabled shared memory themselves
+                let transport = if self.dtls_targets.contains(&sub.endpoint) { None } else { self.shm_transport_to(sub.endpoint) }
+                    .or_else(|| self.transport_to(sub.endpoint));
+                match transport {
+                    Some(t) => send_udp(t.as_ref(), sub.endpoint),
+                    None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No UDP transport for subscriber address family")),
+                }
+            };
+            match result {
+                Ok(_) => {
+                    self.counters.record_sent(service_id);
+                    delivered += 1;
+                }
+                Err(e) => {
+                    self.counters.record_error();
+                    self.logger.log(LogLevel::Warn, "Runtime", &format!("Failed to deliver event 0x{:04x} to {}: {}", event_id, sub.endpoint, e));
+                }
+            }
+        }
+        delivered
+    }
+
+    /// Deliver a message over TCP: reuse a subscriber-initiated connection if one of our
+    /// servers has it, otherwise connect to the subscriber.
+    fn send_reliable(&self, msg: &[IoSlice<'_>], endpoint: SocketAddr) -> std::io::Result<usize> {
+        for server in &self.bindings().tcp {
+            if let Ok(n) = server.send_vectored(msg, Some(endpoint)) {
+                return Ok(n);
+            }
+        }
+        let client = self.tcp_client(endpoint)?;
+        client.send_vectored(msg, None).inspect_err(|_| {
+            self.tcp_clients.drop_connection(endpoint);
+        })
+    }
+
+    /// How to send a message of `service_id` from `transport`: as its UDP endpoint is
+    /// configured, paced as the service's QoS profile asks, else as the endpoint asks,
+    /// else by `default_pacing`.
+    fn tp_sender(&self, transport: &dyn SomeIpTransport, service_id: u16, provided: bool, default_pacing: Option<Duration>) -> TpSender {
+        let mut sender = transport.local_addr().map_or_else(|_| TpSender::default(), |local| self.bindings().tp_sender(local));
+        let qos_pacing = self.service_qos(service_id, provided).and_then(|q| q.tp_pacing_us).map(Duration::from_micros);
+        sender.pacing = qos_pacing.or(sender.pacing).or(default_pacing);
+        sender
+    }
+
+    /// Send a response to `dest`, recording failures. A TCP peer that is not draining its
+    /// connection makes the server refuse with `WouldBlock`; the response is dropped then
+    /// rather than queued without bound. Returns whether the message was taken.
+    fn send_response(&self, transport: &dyn SomeIpTransport, msg: &[IoSlice<'_>], dest: SocketAddr) -> bool {
+        self.response_sent(transport.send_vectored(msg, Some(dest)), dest)
+    }
+
+    /// Record the outcome of sending a response to `dest`; see [`Self::send_response`].
+    fn response_sent(&self, result: std::io::Result<usize>, dest: SocketAddr) -> bool {
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                self.counters.record_error();
+                if e.kind() == std::io::ErrorKind::WouldBlock {
+                    self.logger.log(LogLevel::Warn, "Runtime", &format!("Connection to {} is congested, dropping response", dest));
+                } else {
+                    self.logger.log(LogLevel::Warn, "Runtime", &format!("Failed to send response to {}: {}", dest, e));
+                }
+                false
+            }
+        }
+    }
+
+    pub fn offer_service(&self, alias: &str, instance: Box<dyn RequestHandler>) {
+        self.offer_handler(alias, Arc::from(instance));
+    }
+
+    fn offer_handler(&self, alias: &str, handler: Arc<dyn RequestHandler>) {
+        // Resolve Config
+        let cfg = self.instance_config();
+        let (service_id, major, minor, instance_id, offer_on, multicast_name, (policy, invalid_keys), extended_info) = if let Some(cfg) = &cfg {
+            if let Some(prov_cfg) = cfg.providing.get(alias) {
+                (prov_cfg.service_id, prov_cfg.major_version, prov_cfg.minor_version, prov_cfg.instance_id, prov_cfg.offer_on.clone(), prov_cfg.multicast.clone(), ExecutionPolicy::from_config(prov_cfg), &prov_cfg.extended_info)
+            } else {
+                panic!("Alias '{}' not found in config", alias);
+            }
+        } else {
+            panic!("offer_service requires a loaded config");
+        };
+        
+        for key in invalid_keys {
+            self.logger.log(LogLevel::Warn, "Runtime", &format!("Ignoring deadline for invalid method ID '{}' of service '{}'", key, alias));
+        }
+
+        // Register in Dispatch Map
+        {
+            let mut services = self.services.write().unwrap();
+            services.insert((service_id, instance_id), handler);
+        }
+        {
+            let mut policies = self.execution_policies.write().unwrap();
+            if policy.is_empty() {
+                policies.remove(&(service_id, instance_id));
+            } else {
+                policies.insert((service_id, instance_id), policy);
+            }
+        }
+        self.overruns.lock().unwrap().record_success((service_id, instance_id));
+
+        // Requests arriving at an endpoint the instance is offered at are its own
+        let bindings = self.bindings();
+        {
+            let mut endpoints = self.instance_endpoints.write().unwrap();
+            endpoints.retain(|(sid, _), iid| (*sid, *iid) != (service_id, instance_id));
+            for name in offer_on.values() {
+                if let Some(local) = bindings.transports.get(name).and_then(|t| t.local_addr().ok()) {
+                    endpoints.insert((service_id, local), instance_id);
+                }
+            }
+        }
+        
+        // Register in SD for each relevant interface
+        let mut sd = self.sd.lock().unwrap();
+        
+        // Provide on all interfaces defined in offer_on
+        for (iface_alias, endpoint_name) in offer_on {
+            let mut final_port = 0;
+            let mut proto_id = 0x11;
+            
+            // Resolve the actual bound port for this endpoint.
+            if let Some(ep) = bindings.endpoints.get(&endpoint_name) {
+                 // TLS endpoints are advertised as TCP
+                 proto_id = if ep.is_stream() { 0x06 } else { 0x11 };
+                 // Use actual bound port (resolves ephemeral), fallback to config
+                 final_port = bindings.ports.get(&endpoint_name).copied().unwrap_or(ep.port);
+            } else {
+                 self.logger.log(LogLevel::Warn, "Runtime", &format!("Endpoint '{}' not found for service '{}' on '{}'", endpoint_name, alias, iface_alias));
+            }
+
+            // Resolve Multicast
+            let multicast = if let Some(mcast_name) = multicast_name.as_ref() {
+                if let Some(m_ep) = bindings.endpoints.get(mcast_name) {
+                    if let Ok(m_ip) = m_ep.ip.parse::<std::net::IpAddr>() {
+                        Some((m_ip, m_ep.port))
+                    } else { None }
+                } else { None }
+            } else { None };
+
+            sd.offer_service(service_id, instance_id, major, minor, &iface_alias, final_port, proto_id, multicast);
+            self.wake_sd();
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_interface(&iface_alias).with_service(service_id), &format!("Offered Service '{}' (0x{:04x}) on {} (port {}, proto 0x{:02x})", 
+                alias, service_id, iface_alias, final_port, proto_id));
+        }
+
+        let shm_key = String::from("shm");
+        if !extended_info.is_empty() || self.shm_host.is_some() {
+            let mut items: Vec<(&String, &String)> = extended_info.iter().collect();
+            items.sort();
+            // Peers with the same host identity may switch to shared memory
+            if let Some(host) = &self.shm_host {
+                items.push((&shm_key, host));
+            }
+            match SdOption::configuration(&items) {
+                Ok(option) => { sd.set_service_config(service_id, instance_id, option); }
+                Err(e) => self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Not sending extended info for '{}': {}", alias, e)),
+            }
+        }
+    }
+
+    /// Withdraw the service offered under `alias`: a StopOffer is sent on every interface,
+    /// its subscribers are dropped and requests to it are refused until `offer_service`
+    /// offers it again. Returns false if the alias is unknown or not offered.
+    pub fn stop_offer_service(&self, alias: &str) -> bool {
+        let Some((service_id, instance_id)) = self.instance_config().as_ref()
+            .and_then(|cfg| cfg.providing.get(alias))
+            .map(|svc| (svc.service_id, svc.instance_id)) else {
+            self.logger.log(LogLevel::Warn, "Runtime", &format!("Cannot stop offering unknown service '{}'", alias));
+            return false;
+        };
+        let handler = self.services.write().unwrap().remove(&(service_id, instance_id));
+        self.execution_policies.write().unwrap().remove(&(service_id, instance_id));
+        self.instance_endpoints.write().unwrap().retain(|(sid, _), iid| (*sid, *iid) != (service_id, instance_id));
+        let announced = self.sd.lock().unwrap().stop_offer_service(service_id, instance_id);
+        if handler.is_none() && !announced {
+            return false;
+        }
+        self.wake_sd();
+        self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Stopped offering Service '{}' (0x{:04x})", alias, service_id));
+        true
+    }
+
+    /// Re-read `config_path` and apply what changed for this instance without a restart;
+    /// see [`reload`]. Nothing changes if the file cannot be parsed, does not define this
+    /// instance, references undefined entries, or an endpoint cannot be bound.
+    pub fn reload_config(&self, config_path: &str) -> Result<ReloadSummary, RuntimeError> {
+        let mut sys_config = validate::read_config(config_path, &self.instance_name)?;
+        netif::resolve(&mut sys_config, &self.instance_name)?;
+        let new_config = sys_config.instances[&self.instance_name].clone();
+        let plan = binding::plan(&sys_config, &new_config);
+        let old_config = self.instance_config().expect("loaded runtimes keep their configuration");
+        let old_bindings = self.bindings();
+        let (bindings, mut bound) = binding::bind_all(plan.endpoints, &plan.to_bind, &old_bindings, self.logger.as_ref())?;
+
+        let mut summary = ReloadSummary::default();
+        let mut released: Vec<String> = old_bindings.transports.iter()
+            .filter(|(name, old)| !bindings.transports.get(*name).is_some_and(|new| Arc::ptr_eq(new, old)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        released.sort();
+        bound.sort();
+        let endpoint_changed = |name: &String| old_bindings.endpoints.get(name) != bindings.endpoints.get(name)
+            || old_bindings.ports.get(name) != bindings.ports.get(name);
+
+        // Withdraw removed and changed services while the old configuration is in place
+        let (added, removed, mut changed) = reload::diff(&old_config.providing, &new_config.providing);
+        let moved: Vec<String> = new_config.providing.iter()
+            .filter(|(alias, svc)| old_config.providing.contains_key(*alias) && !changed.contains(*alias)
+                && svc.offer_on.values().chain(&svc.multicast).any(endpoint_changed))
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        changed.extend(moved);
+        changed.sort();
+        for alias in removed {
+            if self.stop_offer_service(&alias) {
+                summary.services_withdrawn.push(alias);
+            }
+        }
+        let mut reoffer = Vec::new();
+        for alias in changed {
+            let old = &old_config.providing[&alias];
+            let handler = self.services.read().unwrap().get(&(old.service_id, old.instance_id)).cloned();
+            if let Some(handler) = handler {
+                self.stop_offer_service(&alias);
+                reoffer.push((alias, handler));
+            }
+        }
+        summary.services_added = added;
+
+        // End subscriptions of removed and changed required services
+        let (required_added, required_removed, required_changed) = reload::diff(&old_config.required, &new_config.required);
+        for alias in required_removed.iter().chain(&required_changed) {
+            let service_id = old_config.required[alias].service_id;
+            let subscriptions: Vec<((u16, u16), OwnSubscription)> = {
+                let mut own = self.own_subscriptions.lock().unwrap();
+                let keys: Vec<(u16, u16)> = own.keys().filter(|(sid, _)| *sid == service_id).copied().collect();
+                keys.into_iter().filter_map(|key| own.remove(&key).map(|sub| (key, sub))).collect()
+            };
+            self.awaiting_offer.lock().unwrap().retain(|(sid, _)| *sid != service_id);
+            for ((service_id, eventgroup_id), sub) in subscriptions {
+                let _ = self.try_subscribe(service_id, sub.instance_id, eventgroup_id, 0, &sub.iface_alias);
+            }
+        }
+
+        let listening: Vec<String> = self.sd.lock().unwrap().listeners.keys().cloned().collect();
+        for iface in plan.interfaces.iter().filter(|iface| !listening.contains(iface)) {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_interface(iface), &format!("Interface '{}' takes effect on restart", iface));
+        }
+
+        // Switch over, then offer and subscribe under the new configuration
+        let new_config = Arc::new(new_config);
+        *self.bindings.write().unwrap() = Arc::new(bindings);
+        *self.qos_profiles.write().unwrap() = sys_config.qos_profiles;
+        *self.config.write().unwrap() = Some(new_config.clone());
+        Self::require_versions(&mut self.sd.lock().unwrap(), &new_config);
+        self.wake_sd();
+        for (alias, handler) in reoffer {
+            self.offer_handler(&alias, handler);
+            summary.services_reoffered.push(alias);
+        }
+        let renewed: Vec<u16> = required_added.iter().chain(&required_changed).map(|alias| new_config.required[alias].service_id).collect();
+        let subscriptions: HashMap<(u16, u16), OwnSubscription> = Self::auto_subscriptions(&new_config, &plan.interfaces, self.logger.as_ref())
+            .into_iter()
+            .filter(|((service_id, _), _)| renewed.contains(service_id))
+            .collect();
+        self.awaiting_offer.lock().unwrap().extend(subscriptions.keys().copied());
+        self.own_subscriptions.lock().unwrap().extend(subscriptions);
+        for alias in required_added.iter().chain(&required_changed) {
+            if new_config.required[alias].eventgroups.values().any(|eg| eg.subscribe) {
+                self.find_service(alias);
+            }
+        }
+
+        // Close TCP servers no longer bound; sockets go once the last sender drops them
+        for transport in old_bindings.tcp.iter().filter(|old| !self.bindings().tcp.iter().any(|new| Arc::ptr_eq(new, old))) {
+            let _ = transport.close();
+        }
+        summary.endpoints_bound = bound;
+        summary.endpoints_released = released;
+        summary.required_added = required_added;
+        summary.required_removed = required_removed;
+        summary.required_changed = required_changed;
+        self.wake();
+        self.logger.log(LogLevel::Info, "Runtime", &format!("Reloaded config from {}: {}", config_path, summary.summary()));
+        Ok(summary)
+    }
+
+    /// Reload `config_path` from the event loop whenever the process receives SIGHUP,
+    /// instead of terminating. Errors of such reloads are logged.
+    #[cfg(target_os = "linux")]
+    pub fn reload_on_sighup(&self, config_path: &str) -> std::io::Result<()> {
+        reload::sighup::install()?;
+        *self.sighup_reload.lock().unwrap() = Some((config_path.to_string(), reload::sighup::received()));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn reload_on_signal(&self) {
+        let path = {
+            let mut pending = self.sighup_reload.lock().unwrap();
+            let Some((path, handled)) = pending.as_mut() else { return };
+            let received = reload::sighup::received();
+            if *handled == received {
+                return;
+            }
+            *handled = received;
+            path.clone()
+        };
+        self.logger.log(LogLevel::Info, "Runtime", &format!("SIGHUP received, reloading {}", path));
+        if let Err(e) = self.reload_config(&path) {
+            self.logger.log(LogLevel::Error, "Runtime", &format!("Failed to reload {}: {}", path, e));
+        }
+    }
+
+    /// Take an interface out of service without stopping the node (maintenance mode).
+    /// SD traffic on the interface is suspended and services offered only there are
+    /// withdrawn with a StopOffer until `enable_interface` is called.
+    pub fn disable_interface(&self, iface_alias: &str) -> bool {
+        let mut sd = self.sd.lock().unwrap();
+        if sd.disable_interface(iface_alias) {
+            self.wake_sd();
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_interface(iface_alias), &format!("Interface '{}' disabled", iface_alias));
+            true
+        } else {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_interface(iface_alias), &format!("Cannot disable unknown interface '{}'", iface_alias));
+            false
+        }
+    }
+
+    /// Return an interface to service; suspended offers restart from the Initial Wait phase.
+    pub fn enable_interface(&self, iface_alias: &str) -> bool {
+        let mut sd = self.sd.lock().unwrap();
+        if sd.enable_interface(iface_alias) {
+            self.wake_sd();
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_interface(iface_alias), &format!("Interface '{}' enabled", iface_alias));
+            true
+        } else {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_interface(iface_alias), &format!("Cannot enable unknown interface '{}'", iface_alias));
+            false
+        }
+    }
+
+    pub fn is_interface_enabled(&self, iface_alias: &str) -> bool {
+        self.sd.lock().unwrap().is_interface_enabled(iface_alias)
+    }
+
+    /// Backlog counters of SD packets received faster than they are processed.
+    pub fn sd_ingress_stats(&self) -> SdIngressStats {
+        self.sd.lock().unwrap().ingress_stats()
+    }
+
+    /// Read-only snapshot of service discovery: our offers with their phase and next
+    /// transmission, discovered services with endpoints and remaining TTL, and our
+    /// subscriptions with their acknowledgement state.
+    pub fn sd_snapshot(&self) -> SdSnapshot {
+        self.sd.lock().unwrap().snapshot()
+    }
+
+    /// Register the payload schema of a request/event (`response == false`) or response.
+    /// Generated `register_schemas` functions call this for every method and event.
+    pub fn register_schema(&self, service_id: u16, method_id: u16, response: bool, schema: TypeSchema) {
+        self.schema_registry.write().unwrap().register(service_id, method_id, response, schema);
+    }
+
+    /// Toggle schema diagnostic mode: outgoing payloads of registered types carry a fingerprint
+    /// trailer, and incoming trailers are validated. Both peers must enable it.
+    pub fn set_schema_diagnostics(&self, enabled: bool) {
+        self.schema_diagnostics.store(enabled, Ordering::SeqCst);
+        self.logger.log(LogLevel::Info, "Runtime", &format!("Schema diagnostics {}", if enabled { "enabled" } else { "disabled" }));
+    }
+
+    /// Append the schema trailer to an outgoing payload when diagnostics are on.
+    fn attach_schema(&self, service_id: u16, method_id: u16, response: bool, payload: &mut Vec<u8>) {
+        if !self.schema_diagnostics.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(s) = self.schema_registry.read().unwrap().get(service_id, method_id, response) {
+            schema::append_trailer(payload, s);
+        }
+    }
+
+    /// Strip and validate the schema trailer of an incoming payload. Returns `None` on mismatch.
+    fn verify_schema<'a>(&self, header: &SomeIpHeader, payload: &'a [u8]) -> Option<&'a [u8]> {
+        if !self.schema_diagnostics.load(Ordering::Relaxed) {
+            return Some(payload);
+        }
+        let (body, fingerprints) = schema::strip_trailer(payload);
+        if let Some(fps) = fingerprints {
+            let response = header.message_type & 0x80 != 0;
+            if let Err(mismatch) = self.schema_registry.read().unwrap().check(header.service_id, header.method_id, response, &fps) {
+                self.logger.log(LogLevel::Error, "Runtime", &mismatch.to_string());
+                return None;
+            }
+        }
+        Some(body)
+    }
+
+    /// Replace the E2E protection of a method or event of `service_id` with custom protectors:
+    /// `sender` protects what this runtime sends, `receiver` checks what it receives.
+    pub fn set_e2e_protection(&self, service_id: u16, id: u16, sender: Box<dyn E2eProtector>, receiver: Box<dyn E2eProtector>) {
+        self.e2e_guards.lock().unwrap().insert((service_id, id), E2eGuard { sender, receiver });
+    }
+
+    /// Add the E2E header to an outgoing payload if the method or event is protected.
+    fn protect_e2e(&self, service_id: u16, id: u16, payload: &mut Vec<u8>) {
+        if let Some(guard) = self.e2e_guards.lock().unwrap().get_mut(&(service_id, id)) {
+            *payload = guard.sender.protect(payload);
+        }
+    }
+
+    /// Check and strip the E2E header of an incoming request, response or notification.
+    /// Returns `Ok(None)` if the message is not protected, and the return code of the failure
+    /// if the check fails. ERROR messages carry no E2E header and are not checked.
+    fn check_e2e(&self, header: &SomeIpHeader, payload: &[u8]) -> Result<Option<Vec<u8>>, ReturnCode> {
+        if matches!(header.message_type, 0x81 | 0xA1) {
+            return Ok(None);
+        }
+        let mut guards = self.e2e_guards.lock().unwrap();
+        let Some(guard) = guards.get_mut(&(header.service_id, header.method_id)) else {
+            return Ok(None);
+        };
+        guard.receiver.check(payload).map(Some).map_err(|e| {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id),
+                &format!("E2E check failed for 0x{:04x}/0x{:04x}: {}", header.service_id, header.method_id, e));
+            e.return_code()
+        })
+    }
+
+    /// Authenticate the messages of `service_id` with `authenticator`, in both directions.
+    /// Peers must install a matching authenticator for the service.
+    pub fn set_message_authenticator(&self, service_id: u16, authenticator: Box<dyn MessageAuthenticator>) {
+        self.auth_guards.lock().unwrap().insert(service_id, AuthGuard::new(authenticator));
+    }
+
+    /// Append the freshness value and MAC to an outgoing payload if the service is authenticated.
+    fn authenticate(&self, header: &SomeIpHeader, payload: &mut Vec<u8>) {
+        if let Some(guard) = self.auth_guards.lock().unwrap().get_mut(&header.service_id) {
+            guard.seal(header, payload);
+        }
+    }
+
+    /// Verify and strip the authentication trailer of an incoming message of an authenticated
+    /// service. Returns `None` if verification fails; the message is then dropped without an
+    /// answer. ERROR messages are not authenticated.
+    fn verify_authentication<'a>(&self, header: &SomeIpHeader, payload: &'a [u8], src: SocketAddr) -> Option<&'a [u8]> {
+        if matches!(header.message_type, 0x81 | 0xA1) {
+            return Some(payload);
+        }
+        let mut guards = self.auth_guards.lock().unwrap();
+        let Some(guard) = guards.get_mut(&header.service_id) else {
+            return Some(payload);
+        };
+        guard.open(header, payload, src).map_err(|e| {
+            self.counters.record_error();
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id),
+                &format!("Dropping 0x{:04x}/0x{:04x} from {}: authentication failed: {}", header.service_id, header.method_id, src, e));
+        }).ok()
+    }
+
+    /// Run a request handler, enforcing the service's execution deadline if one is configured.
+    /// Returns `None` on overrun, after logging it and withdrawing the offer if the service
+    /// has now overrun too often in a row or too many of its handlers are still running late.
+    fn execute_handler(&self, handler: Arc<dyn RequestHandler>, instance_id: u16, header: &SomeIpHeader, payload: &[u8]) -> Option<HandlerResult> {
+        let policy = self.execution_policies.read().unwrap().get(&(header.service_id, instance_id))
+            .and_then(|p| p.deadline_for(header.method_id).map(|d| (d, p.isolate_after)));
+        let Some((deadline, isolate_after)) = policy else {
+            return Some(handler.handle(header, payload));
+        };
+
+        let lingering = self.overruns.lock().unwrap().lingering((header.service_id, instance_id));
+        match deadline::execute_with_deadline(handler, header.clone(), payload.to_vec(), deadline, &lingering) {
+            Execution::Completed(result) => {
+                self.overruns.lock().unwrap().record_success((header.service_id, instance_id));
+                Some(result)
+            }
+            Execution::Overrun => {
+                let count = self.overruns.lock().unwrap().record_overrun((header.service_id, instance_id));
+                self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Handler for Service 0x{:04x} Method 0x{:04x} exceeded its {} ms deadline ({} consecutive overruns)",
+                    header.service_id, header.method_id, deadline.as_millis(), count));
+                if isolate_after == Some(count) {
+                    self.sd.lock().unwrap().stop_offer_service(header.service_id, instance_id);
+                    self.wake_sd();
+                    self.logger.log_with(LogLevel::Error, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Isolating Service 0x{:04x}: offer withdrawn after {} consecutive deadline overruns", header.service_id, count));
+                }
+                None
+            }
+            Execution::Saturated => {
+                self.overruns.lock().unwrap().record_overrun((header.service_id, instance_id));
+                if self.sd.lock().unwrap().stop_offer_service(header.service_id, instance_id) {
+                    self.wake_sd();
+                    self.logger.log_with(LogLevel::Error, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Isolating Service 0x{:04x}: offer withdrawn with {} handlers still running past their deadline", header.service_id, deadline::MAX_LINGERING_HANDLERS));
+                }
+                None
+            }
+        }
+    }
+
+    /// Run an admitted request or fire-and-forget call and send its response, on the
+    /// event loop or on a worker thread.
+    fn serve_request(&self, transport: &dyn SomeIpTransport, handler: Arc<dyn RequestHandler>, instance_id: u16, header: &SomeIpHeader, payload: &[u8], src: SocketAddr) {
+        let _span = trace::dispatch(header, instance_id).entered();
+        let is_req = header.message_type == 0x00 || header.message_type == 0x20;
+        let started = std::time::Instant::now();
+        let Some(result) = self.execute_handler(handler, instance_id, header, payload) else {
+            self.counters.record_error();
+            if is_req {
+                self.reply_error(transport, header, ReturnCode::Timeout, src);
+            }
+            return;
+        };
+        self.counters.record_request();
+        self.counters.record_handled(header.service_id, started.elapsed());
+        let result = match result {
+            Ok(result) => result,
+            Err(code) => {
+                self.refuse(transport, header, code, src);
+                return;
+            }
+        };
+        if let Some(mut res_payload) = result {
+            if is_req {
+                // Responses carry the interface version of their request
+                let res_header = SomeIpHeader::with_interface_version(header.service_id, header.method_id, header.client_id, header.session_id, 0x80, 0, header.interface_version);
+                self.attach_schema(header.service_id, header.method_id, true, &mut res_payload);
+                self.protect_e2e(header.service_id, header.method_id, &mut res_payload);
+                self.authenticate(&res_header, &mut res_payload);
+                if !self.quota_allows(header.service_id, res_payload.len()) {
+                    self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Response of {} bytes from Service 0x{:04x} exceeds its queue quota", res_payload.len(), header.service_id));
+                    self.reply_error(transport, header, ReturnCode::NotReady, src);
+                    return;
+                }
+                let sent = if transport.is_stream() {
+                    let mut res_header = res_header;
+                    res_header.length = 8 + res_payload.len() as u32;
+                    self.send_response(transport, &[IoSlice::new(&res_header.serialize()), IoSlice::new(&res_payload)], src)
+                } else {
+                    // Over UDP, large responses go out as RESPONSE segments with TP (0xA0)
+                    let sender = self.tp_sender(transport, header.service_id, true, None);
+                    self.response_sent(sender.send(&res_header, &res_payload, |msg| transport.send_vectored(msg, Some(src))), src)
+                };
+                if sent {
+                    self.counters.record_sent(header.service_id);
+                }
+            }
+        }
+    }
+
+    /// Instance and handler a request to `service_id` received on `transport` is for. A
+    /// service offered as a single instance takes every request; of several instances, the
+    /// one offered at the transport's local address does. The handler is cloned out so no
+    /// lock is held while it runs.
+    fn request_handler(&self, service_id: u16, transport: &dyn SomeIpTransport) -> Option<(u16, Arc<dyn RequestHandler>)> {
+        let services = self.services.read().unwrap();
+        let mut offered = services.iter().filter(|((sid, _), _)| *sid == service_id);
+        let ((_, instance_id), handler) = offered.next()?;
+        if offered.next().is_none() {
+            return Some((*instance_id, handler.clone()));
+        }
+        // TCP connections to a server bound to every address report the concrete one
+        let local = transport.local_addr().ok()?;
+        let instance_id = self.instance_endpoints.read().unwrap().iter()
+            .find(|((sid, addr), _)| *sid == service_id && (*addr == local || (addr.ip().is_unspecified() && addr.port() == local.port())))
+            .map(|(_, iid)| *iid)?;
+        services.get(&(service_id, instance_id)).map(|handler| (instance_id, handler.clone()))
+    }
+
+    /// Take an in-flight slot for a request to a service with a quota. Returns the handler
+    /// to run, holding the slot until it returns, or `None` if the service is at its limit.
+    fn admit_request(&self, handler: Arc<dyn RequestHandler>, header: &SomeIpHeader) -> Option<Arc<dyn RequestHandler>> {
+        let Some(quota) = self.quotas.get(&header.service_id) else {
+            return Some(handler);
+        };
+        match quota.try_begin() {
+            Some(slot) => Some(Arc::new(Guarded::new(handler, slot))),
+            None => {
+                self.counters.record_error();
+                self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Service 0x{:04x} is at its in-flight quota, refusing request 0x{:04x}", header.service_id, header.method_id));
+                None
+            }
+        }
+    }
+
+    /// Whether the access policy of the service, if it has one, lets `src` call the method.
+    fn access_allows(&self, header: &SomeIpHeader, src: SocketAddr) -> bool {
+        let allowed = self.access_policies.get(&header.service_id).is_none_or(|p| p.allows_method(src.ip(), header.method_id));
+        if !allowed {
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id),
+                &format!("Access denied: {} may not call 0x{:04x}/0x{:04x}", src, header.service_id, header.method_id));
+        }
+        allowed
+    }
+
+    /// Whether an outgoing message of `len` payload bytes fits the service's queue quota.
+    fn quota_allows(&self, service_id: u16, len: usize) -> bool {
+        self.quotas.get(&service_id).is_none_or(|q| q.admit_bytes(len))
+    }
+
+    /// Answer `request` with an ERROR message carrying `code`.
+    fn reply_error(&self, transport: &dyn SomeIpTransport, request: &SomeIpHeader, code: ReturnCode, dest: SocketAddr) {
+        let mut err_header = SomeIpHeader::with_return_code(
+            request.service_id,
+            request.method_id,
+            request.client_id,
+            request.session_id,
+            0x81, // ERROR
+            0,
+            code.into(),
+        );
+        err_header.interface_version = request.interface_version;
+        if self.send_response(transport, &[IoSlice::new(&err_header.serialize())], dest) {
+            self.counters.record_sent(request.service_id);
+        }
+    }
+
+    /// Drop a received message that cannot be processed, answering it with an ERROR
+    /// carrying `code` if it is a request [PRS_SOMEIP_00195].
+    fn refuse(&self, transport: &dyn SomeIpTransport, header: &SomeIpHeader, code: ReturnCode, src: SocketAddr) {
+        self.counters.record_drop();
+        self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id),
+            &format!("Refusing message 0x{:04x}/0x{:04x} (type 0x{:02x}) from {}: {:?}", header.service_id, header.method_id, header.message_type, src, code));
+        if header.message_type == 0x00 || header.message_type == 0x20 {
+            self.reply_error(transport, header, code, src);
+        }
+    }
+
+    pub fn register_notification_handler(&self, service_id: u16, handler: Box<dyn RequestHandler>) {
+        self.notification_handlers.write().unwrap().insert(service_id, Arc::from(handler));
+        self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Registered notification handler for Service 0x{:04x}", service_id));
+    }
+    
+    /// Call `callback` with each `event_id` notification of `service_id`, decoded as `T`
+    /// (typically a generated event struct). Replaces an earlier callback for the same event,
+    /// and runs alongside a handler from
+    /// [`register_notification_handler`](Self::register_notification_handler).
+    ///
+    /// With `worker_threads` configured, events are decoded and the callback called on the
+    /// worker threads, in order for each event; otherwise on the event loop. Notifications
+    /// that cannot be decoded as `T` are dropped and logged.
+    pub fn on_event<T, F>(&self, service_id: u16, event_id: u16, callback: F)
+    where
+        T: SomeIpDeserialize + 'static,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let decode: EventCallback = Arc::new(move |payload: &[u8]| {
+            callback(T::deserialize(&mut std::io::Cursor::new(payload))?);
+            Ok(())
+        });
+        self.event_callbacks.write().unwrap().insert((service_id, event_id), decode);
+        self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Registered callback for event 0x{:04x} of Service 0x{:04x}", event_id, service_id));
+    }
+
+    /// Hand a notification to the service's handler and the event's typed callback.
+    fn deliver_notification(&self, handler: Option<&Arc<dyn RequestHandler>>, callback: Option<&EventCallback>, header: &SomeIpHeader, payload: &[u8]) {
+        if let Some(handler) = handler {
+            let _ = handler.handle(header, payload);
+        }
+        let Some(callback) = callback else { return };
+        match (&self.workers, self.self_ref.upgrade()) {
+            // Events of one kind stay in order on one worker
+            (Some(workers), Some(runtime)) => {
+                let (callback, header, payload) = (callback.clone(), header.clone(), payload.to_vec());
+                let key = (header.service_id, header.method_id);
+                workers.execute(move || runtime.run_event_callback(&callback, &header, &payload), Some(key));
+            }
+            _ => self.run_event_callback(callback, header, payload),
+        }
+    }
+
+    fn run_event_callback(&self, callback: &EventCallback, header: &SomeIpHeader, payload: &[u8]) {
+        if let Err(e) = callback(payload) {
+            self.counters.record_drop();
+            self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Dropping event 0x{:04x} of Service 0x{:04x}: cannot decode payload: {}", header.method_id, header.service_id, e));
+        }
+    }
+
+    /// Send a request to `target` and wait for the response. The transport follows the
+    /// discovered offer at that address: TCP if the service is offered there over TCP only,
+    /// UDP otherwise (including targets that were not discovered via SD).
+    ///
+    /// Timeout and retries follow the `request_retry` of the required service with this ID;
+    /// without one there is a single attempt, which waits 2 seconds.
+    pub async fn send_request_and_wait(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> Option<Vec<u8>> {
+        let proto = self.sd.lock().unwrap().endpoint_protocol(service_id, target).unwrap_or(0x11);
+        let policy = self.request_policies.get(&service_id).cloned().unwrap_or_else(|| RequestPolicy::once(Duration::from_secs(2)));
+        self.send_request_with_policy((service_id, 0xFFFF), method_id, payload, target, Route::from_proto(proto), &policy).await.ok()
+    }
+
+    /// Send a fire-and-forget request (REQUEST_NO_RETURN) to `target`. No response is
+    /// expected, so nothing is kept for it once it is sent; the provider runs its handler
+    /// without answering. The route is chosen as for
+    /// [`send_request_and_wait`](Self::send_request_and_wait). Fails with `Other` if it
+    /// cannot be sent.
+    pub fn send_fire_and_forget(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> std::io::Result<()> {
+        let proto = self.sd.lock().unwrap().endpoint_protocol(service_id, target).unwrap_or(0x11);
+        self.send_fire_and_forget_via((service_id, 0xFFFF), method_id, payload, target, Route::from_proto(proto))
+    }
+
+    fn send_fire_and_forget_via(&self, (service_id, instance_id): (u16, u16), method_id: u16, payload: &[u8], target: SocketAddr, route: Route) -> std::io::Result<()> {
+        self.transmit_request((service_id, instance_id), method_id, payload.to_vec(), target, route, None)
+            .map(|_| ())
+            .ok_or_else(|| std::io::Error::other(format!("failed to send method 0x{:04x} to {}", method_id, target)))
+    }
+
+    /// Send a request and wait for the response, retrying after timeouts and failed sends as
+    /// `policy` allows for `method_id`. A request refused at the in-flight limit or answered
+    /// with the provider's error is not retried.
+    async fn send_request_with_policy(&self, (service_id, instance_id): (u16, u16), method_id: u16, payload: &[u8], target: SocketAddr, route: Route, policy: &RequestPolicy) -> std::io::Result<Vec<u8>> {
+        let attempts = policy.attempts(method_id);
+        let mut attempt = 0;
+        loop {
+            let result = match self.start_request_via((service_id, instance_id), method_id, payload, target, route.clone(), policy.timeout).await {
+                Ok(request) => request.response().await,
+                Err(e) => Err(e),
+            };
+            attempt += 1;
+            match result {
+                Err(e) if e.kind() != std::io::ErrorKind::WouldBlock && error_return_code(&e).is_none() && attempt < attempts => {
+                    self.logger.log(LogLevel::Debug, "Runtime", &format!("Retrying request 0x{:04x}/0x{:04x} to {} ({}/{})", service_id, method_id, target, attempt, attempts - 1));
+                    tokio::time::sleep(policy.backoff_before(attempt)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Request policy of the required service `alias` with ID `service_id`: its `request_retry`,
+    /// or else the timeout of its QoS profile.
+    fn request_policy(&self, alias: &str, service_id: u16) -> RequestPolicy {
+        self.request_policies.get(&service_id).cloned()
+            .unwrap_or_else(|| RequestPolicy::from_qos(&self.qos_profile(alias).unwrap_or_default()))
+    }
+
+    /// Send a request to `target` once and return it outstanding, to await its response until
+    /// a deadline of the caller's choosing or cancel it; see [`PendingRequest`]. The route is
+    /// chosen as for [`send_request_and_wait`](Self::send_request_and_wait), and the deadline
+    /// defaults to the timeout of its `request_retry`, else 2 seconds.
+    ///
+    /// Fails with `WouldBlock` if the service has `max_in_flight` requests outstanding (unless
+    /// its `request_limit` waits for one to complete), and with `Other` if it cannot be sent.
+    pub async fn start_request(&self, service_id: u16, method_id: u16, payload: &[u8], target: SocketAddr) -> std::io::Result<PendingRequest> {
+        let proto = self.sd.lock().unwrap().endpoint_protocol(service_id, target).unwrap_or(0x11);
+        let timeout = self.request_policies.get(&service_id).map_or(Duration::from_secs(2), |policy| policy.timeout);
+        self.start_request_via((service_id, 0xFFFF), method_id, payload, target, Route::from_proto(proto), timeout).await
+    }
+
+    async fn start_request_via(&self, (service_id, instance_id): (u16, u16), method_id: u16, payload: &[u8], target: SocketAddr, route: Route, timeout: Duration) -> std::io::Result<PendingRequest> {
+        let slot = match self.request_limits.get(&service_id) {
+            Some(limit) => Some(limit.acquire().await.ok_or_else(|| self.refuse_request(service_id, method_id, limit))?),
+            None => None,
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let request_id = self.transmit_request((service_id, instance_id), method_id, payload.to_vec(), target, route, Some(ResponseSender::Task(tx)))
+            .ok_or_else(|| std::io::Error::other(format!("failed to send method 0x{:04x} to {}", method_id, target)))?;
+        let handle = RequestHandle::new(self.self_ref.clone(), service_id, method_id, request_id, target);
+        Ok(PendingRequest::new(handle, rx, timeout, slot))
+    }
+
+    /// Count and log a request refused at the service's `request_limit`.
+    fn refuse_request(&self, service_id: u16, method_id: u16, limit: &RequestLimit) -> std::io::Error {
+        self.counters.record_rejected();
+        self.logger.log_with(LogLevel::Warn, "Runtime", &LogContext::default().with_service(service_id), &format!("Refusing request 0x{:04x} to Service 0x{:04x}: {} requests are outstanding", method_id, service_id, limit.max_in_flight()));
+        std::io::Error::new(std::io::ErrorKind::WouldBlock, format!("{} requests to service 0x{:04x} are outstanding", limit.max_in_flight(), service_id))
+    }
+
+    /// Send a request to the required service with ID `service_id` and block the calling
+    /// thread until its response arrives or `timeout` passes, for applications without an
+    /// async executor. It goes to the discovered instance of the required service configured
+    /// with this ID, preferring its QoS transport, or to any discovered instance if none is
+    /// configured. The request is sent once; `request_limit` applies as for
+    /// [`start_request`](Self::start_request), waiting for a slot within `timeout`.
+    ///
+    /// Fails with `NotFound` if the service has not been discovered (starting a FindService
+    /// for a configured one), `TimedOut`, `WouldBlock` at the request limit, and `Other` if the
+    /// request cannot be sent, its response is refused, or the provider answers with an error
+    /// (see [`error_return_code`]). Responses are received by the event
+    /// loop, so this must not be called from a request or notification handler.
+    pub fn call_blocking(&self, service_id: u16, method_id: u16, payload: &[u8], timeout: Duration) -> std::io::Result<Vec<u8>> {
+        let deadline = std::time::Instant::now() + timeout;
+        let required = self.instance_config().and_then(|cfg| {
+            cfg.required.iter().find(|(_, req)| req.service_id == service_id).map(|(alias, req)| (alias.clone(), req.instance_id))
+        });
+        let (instance_id, preferred) = match &required {
+            Some((alias, instance_id)) => (*instance_id, self.qos_profile(alias).unwrap_or_default().transport_proto().unwrap_or(0x11)),
+            None => (0xFFFF, 0x11),
+        };
+        let available = self.sd.lock().unwrap().get_service_preferring(service_id, instance_id, preferred);
+        let Some((target, proto)) = available else {
+            if let Some((alias, _)) = &required {
+                self.find_service(alias);
+            }
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("service 0x{:04x} has not been discovered", service_id)));
+        };
+
+        let _slot = match self.request_limits.get(&service_id) {
+            Some(limit) => Some(limit.acquire_until(deadline).ok_or_else(|| self.refuse_request(service_id, method_id, limit))?),
+            None => None,
+        };
+        let (reply, response) = client::response_slot();
+        let request_id = self.transmit_request((service_id, instance_id), method_id, payload.to_vec(), target, Route::from_proto(proto), Some(ResponseSender::Thread(reply)))
+            .ok_or_else(|| std::io::Error::other(format!("failed to send method 0x{:04x} to {}", method_id, target)))?;
+        match response.wait_until(deadline) {
+            Ok(Ok(payload)) => Ok(payload),
+            Ok(Err(code)) => Err(std::io::Error::other(code)),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                self.abandon_request(service_id, method_id, request_id, target);
+                self.counters.record_timeout();
+                Err(std::io::Error::new(std::io::ErrorKind::TimedOut, format!("no response from {} to method 0x{:04x} of service 0x{:04x}", target, method_id, service_id)))
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                Err(std::io::Error::other(format!("response from {} to method 0x{:04x} of service 0x{:04x} was refused", target, method_id, service_id)))
+            }
+        }
+    }
+
+    /// Forget an outstanding request and any segments of its response from `target`.
+    /// Returns whether it was still outstanding.
+    fn abandon_request(&self, service_id: u16, method_id: u16, request_id: RequestId, target: SocketAddr) -> bool {
+        if self.pending_requests.lock().unwrap().remove(&(service_id, method_id, request_id)).is_none() {
+            return false;
+        }
+        self.tp_reassembler.lock().unwrap().abandon(target, (service_id as u32) << 16 | method_id as u32, request_id.raw());
+        true
+    }
+
+    /// Start active discovery of a required service: FindService entries are sent with the
+    /// configured initial wait and repetitions until the service is offered.
+    /// Returns false if the alias is unknown or the service has already been discovered.
+    pub fn find_service(&self, alias: &str) -> bool {
+        let cfg = self.instance_config();
+        let Some(req) = cfg.as_ref().and_then(|c| c.required.get(alias)) else {
+            return false;
+        };
+        let started = self.sd.lock().unwrap().find_service_request(req.service_id, req.instance_id, req.major_version, 0xFFFF_FFFF);
+        if started {
+            self.wake_sd();
+            self.logger.log_with(LogLevel::Debug, "Runtime", &LogContext::default().with_service(req.service_id), &format!("Searching for service '{}' (0x{:04x})", alias, req.service_id));
+        }
+        started
+    }
+
+    /// Every service instance discovered on the network, required or not, with its version,
+    /// endpoints and remaining TTL; for diagnostic tools. Also starts a FindService for all
+    /// services (service ID 0xFFFF) unless one is running, so providers that have not offered
+    /// since we started answer within the FindService repetitions; browse again to see them.
+    pub fn browse_services(&self) -> Vec<DiscoveredService> {
+        let mut sd = self.sd.lock().unwrap();
+        if sd.find_service_request(0xFFFF, 0xFFFF, 0xFF, 0xFFFF_FFFF) {
+            self.wake_sd();
+        }
+        sd.discovered_services()
+    }
+
+    /// Send a request to a required service by alias. If the service is not currently offered,
+    /// a FindService is started (see [`find_service`](Self::find_service)); if the alias also
+    /// has `store_and_forward` configured, the request is queued and sent once the offer
+    /// arrives, otherwise `None` is returned immediately.
+    ///
+    /// The response timeout and retries come from the alias's `request_retry`, or else the
+    /// timeout from its QoS profile.
+    pub async fn request_service(&self, alias: &str, method_id: u16, payload: &[u8]) -> Option<Vec<u8>> {
+        let (service_id, instance_id) = {
+            let cfg = self.instance_config()?;
+            let req = cfg.required.get(alias)?;
+            (req.service_id, req.instance_id)
+        };
+        let qos = self.qos_profile(alias).unwrap_or_default();
+        let policy = self.request_policy(alias, service_id);
+        let preferred = qos.transport_proto().unwrap_or(0x11);
+        let available = self.sd.lock().unwrap().get_service_preferring(service_id, instance_id, preferred);
+        if available.is_none() {
+            self.find_service(alias);
+        }
+        if let Some((target, proto)) = available {
+            return self.send_request_with_policy((service_id, instance_id), method_id, payload, target, Route::from_proto(proto), &policy).await.ok();
+        }
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let ttl = {
+            let mut queues = self.forward_queues.lock().unwrap();
+            let queue = queues.get_mut(&(service_id, instance_id))?;
+            if queue.push(method_id, payload.to_vec(), ResponseSender::Task(tx), std::time::Instant::now()).is_err() {
+                self.logger.log(LogLevel::Warn, "Runtime", &format!("Store-and-forward queue for '{}' is full; dropping request 0x{:04x}", alias, method_id));
+                return None;
+            }
+            queue.ttl()
+        };
+        self.wake();
+        self.logger.log(LogLevel::Debug, "Runtime", &format!("Service '{}' unavailable; queued request 0x{:04x}", alias, method_id));
+
+        // Wait for the queue TTL plus the regular response timeout once forwarded
+        match tokio::time::timeout(ttl + policy.timeout, rx).await {
+            Ok(Ok(Ok(res))) => Some(res),
+            Ok(Ok(Err(_)) | Err(_)) => None,
+            Err(_) => {
+                self.counters.record_timeout();
+                None
+            }
+        }
+    }
+
+    /// Configuration of this instance, if loaded from a file.
+    pub(crate) fn instance_config(&self) -> Option<Arc<InstanceConfig>> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Endpoints and transports currently bound.
+    fn bindings(&self) -> Arc<Bindings> {
+        self.bindings.read().unwrap().clone()
+    }
+
+    /// Port `endpoint` is bound to, if this instance bound it.
+    pub fn bound_port(&self, endpoint: &str) -> Option<u16> {
+        self.bindings().ports.get(endpoint).copied()
+    }
+
+    /// QoS profile referenced by a provided or required service alias.
+    pub fn qos_profile(&self, alias: &str) -> Option<QosProfile> {
+        let cfg = self.instance_config()?;
+        let name = match cfg.providing.get(alias) {
+            Some(svc) => svc.qos.as_ref(),
+            None => cfg.required.get(alias)?.qos.as_ref(),
+        }?;
+        self.qos_profiles.read().unwrap().get(name).cloned()
+    }
+
+    /// QoS profile of the service we provide (`provided`) or require with this ID.
+    fn service_qos(&self, service_id: u16, provided: bool) -> Option<QosProfile> {
+        let cfg = self.instance_config()?;
+        let name = if provided {
+            cfg.providing.values().find(|s| s.service_id == service_id)?.qos.as_ref()
+        } else {
+            cfg.required.values().find(|r| r.service_id == service_id)?.qos.as_ref()
+        }?;
+        self.qos_profiles.read().unwrap().get(name).cloned()
+    }
+
+    /// Configuration items attached to the current offer of a required service, if it is discovered.
+    pub fn remote_service_config(&self, alias: &str) -> Option<Vec<(String, String)>> {
+        let cfg = self.instance_config()?;
+        let req = cfg.required.get(alias)?;
+        let sd = self.sd.lock().unwrap();
+        let remote = if req.instance_id == 0xFFFF {
+            sd.remote_services.values().find(|r| r.service_id == req.service_id)
+        } else {
+            sd.find_service(req.service_id, req.instance_id)
+        }?;
+        Some(remote.config_items())
+    }
+
+    /// Quota usage of an offered service, if it has a `quota` configured.
+    pub fn quota_usage(&self, alias: &str) -> Option<QuotaUsage> {
+        let service_id = self.instance_config()?.providing.get(alias)?.service_id;
+        let quota = self.quotas.get(&service_id)?;
+        let sd = self.sd.lock().unwrap();
+        Some(quota.usage(sd.service_subscriber_count(service_id), sd.subscriptions_rejected(service_id)))
+    }
+
+    /// Requests and subscriptions refused by an offered service's access policy, if it has `access` rules.
+    pub fn access_violations(&self, alias: &str) -> Option<u64> {
+        let service_id = self.instance_config()?.providing.get(alias)?.service_id;
+        self.access_policies.get(&service_id).map(|p| p.violations())
+    }
+
+    /// Counters of a required service's store-and-forward queue.
+    pub fn forward_stats(&self, alias: &str) -> Option<ForwardStats> {
+        let cfg = self.instance_config()?;
+        let req = cfg.required.get(alias)?;
+        self.forward_queues.lock().unwrap().get(&(req.service_id, req.instance_id)).map(|q| q.stats())
+    }
+
+    /// Expire stale store-and-forward requests and transmit the rest for services that are offered again.
+    fn flush_forward_queues(&self) {
+        let now = std::time::Instant::now();
+        let mut waiting: Vec<(u16, u16)> = {
+            let mut queues = self.forward_queues.lock().unwrap();
+            queues.iter_mut()
+                .filter_map(|(key, q)| { q.expire(now); (!q.is_empty()).then_some(*key) })
+                .collect()
+        };
+        // Higher-priority services get their backlog out first
+        waiting.sort_by_key(|(sid, _)| std::cmp::Reverse(self.service_qos(*sid, false).map(|q| q.priority).unwrap_or(0)));
+        for (service_id, instance_id) in waiting {
+            let Some((target, proto)) = self.sd.lock().unwrap().get_service(service_id, instance_id) else {
+                continue;
+            };
+            let requests = match self.forward_queues.lock().unwrap().get_mut(&(service_id, instance_id)) {
+                Some(q) => q.drain(now),
+                None => continue,
+            };
+            self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(service_id), &format!("Service 0x{:04x} available again; forwarding {} queued request(s)", service_id, requests.len()));
+            for request in requests {
+                // The caller stops waiting if transmission fails, since the reply sender is dropped
+                let _ = self.transmit_request((service_id, instance_id), request.method_id, request.payload, target, Route::from_proto(proto), Some(request.reply));
             }
         }
     }
 
+    /// Allocate the request ID of the next message for (service, method) sent as `client_id`.
+    fn next_request_id(&self, client_id: u16, service_id: u16, method_id: u16) -> RequestId {
+        RequestId::new(client_id, self.session_manager.lock().unwrap().next_session_id(service_id, method_id))
+    }
+
+    /// Client ID of the requests sent by this instance: `client_id` from the configuration,
+    /// or one allocated at load time that is unique among the runtimes of this process.
+    pub fn client_id(&self) -> u16 {
+        self.client_id
+    }
+
+    /// Interface version of requests to `service_id`/`instance_id` at `target`: the major version
+    /// of the instance offered there, else that of the required service, else the default.
+    /// Instance 0xFFFF stands for whichever instance is offered at `target`.
+    fn interface_version(&self, service_id: u16, instance_id: u16, target: SocketAddr) -> u8 {
+        let offered = self.sd.lock().unwrap().find_service_at(service_id, instance_id, target).map(|remote| remote.version_major);
+        offered.or_else(|| {
+            self.instance_config()?.required.values()
+                .find(|req| req.service_id == service_id && (instance_id == 0xFFFF || req.instance_id == instance_id))
+                .map(|req| req.major_version)
+        })
+            .filter(|&major| major != 0xFF)
+            .unwrap_or(SomeIpHeader::DEFAULT_INTERFACE_VERSION)
+    }
+
+    /// Send a request and register `reply` for its response. Returns the request ID used.
+    /// Without `reply` the request is sent as fire-and-forget (REQUEST_NO_RETURN).
+    ///
+    /// Over TCP the request goes out as one message on the pooled connection to `target`,
+    /// whose responses the event loop reads; over UDP large payloads are segmented with
+    /// SOME/IP-TP.
+    fn transmit_request(&self, (service_id, instance_id): (u16, u16), method_id: u16, mut payload: Vec<u8>, target: SocketAddr, route: Route, reply: Option<ResponseSender>) -> Option<RequestId> {
+        let request_id = self.next_request_id(self.client_id, service_id, method_id);
+        let (client_id, session_id) = (request_id.client_id(), request_id.session_id());
+        // REQUEST / REQUEST_NO_RETURN; segments are sent as their TP variants
+        let message_type = if reply.is_some() { 0x00 } else { 0x01 };
+        let interface_version = self.interface_version(service_id, instance_id, target);
+        let header = |len| SomeIpHeader::with_interface_version(service_id, method_id, client_id, session_id, message_type, len, interface_version);
+
+        if let Some(reply) = reply {
+            let mut pending = self.pending_requests.lock().unwrap();
+            pending.insert((service_id, method_id, request_id), reply);
+        }
+
+        self.attach_schema(service_id, method_id, false, &mut payload);
+        self.protect_e2e(service_id, method_id, &mut payload);
+        self.authenticate(&header(0), &mut payload);
+        let payload = &payload[..];
+
+        let via = match route {
+            Route::Udp(via) => via,
+            Route::Tcp => {
+                let header = header(payload.len() as u32).serialize();
+                let msg = [IoSlice::new(&header), IoSlice::new(payload)];
+                let sent = self.tcp_client(target).and_then(|client| client.send_vectored(&msg, None).inspect_err(|_| {
+                    self.tcp_clients.drop_connection(target);
+                }));
+                if let Err(e) = sent {
+                    self.counters.record_error();
+                    self.logger.log(LogLevel::Error, "Runtime", &format!("Failed to send request to {} over TCP: {}", target, e));
+                    self.pending_requests.lock().unwrap().remove(&(service_id, method_id, request_id));
+                    return None;
+                }
+                self.counters.record_sent(service_id);
+                return Some(request_id);
+            }
+        };
+
+        // DTLS endpoints are never reached over shared memory
+        let transport = via.or_else(|| if self.dtls_targets.contains(&target) { None } else { self.shm_route(&self.sd.lock().unwrap(), service_id, target) });
+        let transport = transport.or_else(|| self.transport_to(target));
+        let Some(transport) = transport else {
+            self.counters.record_error();
+            self.logger.log(LogLevel::Error, "Runtime", &format!("No local UDP transport for target {}", target));
+            self.pending_requests.lock().unwrap().remove(&(service_id, method_id, request_id));
+            return None;
+        };
+        // Segments are paced even if neither the QoS profile nor the endpoint asks, to not overrun the receiver
+        let sender = self.tp_sender(transport.as_ref(), service_id, false, Some(Duration::from_micros(100)));
+        if let Err(e) = sender.send(&header(0), payload, |msg| transport.send_vectored(msg, Some(target))) {
+            self.counters.record_error();
+            self.logger.log(LogLevel::Error, "Runtime", &format!("Failed to send request: {}", e));
+            self.pending_requests.lock().unwrap().remove(&(service_id, method_id, request_id));
+            return None;
+        }
+
+        self.counters.record_sent(service_id);
+        Some(request_id)
+    }
+
+    /// Create a thread-safe handle for application code; see [`SomeIpHandle`].
+    pub fn handle(self: &Arc<Self>) -> SomeIpHandle {
+        SomeIpHandle::new(self.clone(), self.command_tx.clone())
+    }
+
+    /// Apply mutations queued by handles. Called from the event loop with no locks held.
+    fn process_commands(&self) {
+        let commands: Vec<Command> = self.command_rx.lock().unwrap().try_iter().collect();
+        for command in commands {
+            match command {
+                Command::OfferService { alias, handler, done } => {
+                    let provided = self.instance_config().is_some_and(|cfg| cfg.providing.contains_key(&alias));
+                    if provided {
+                        self.offer_service(&alias, handler);
+                    } else {
+                        self.logger.log(LogLevel::Warn, "Runtime", &format!("Cannot offer unknown service '{}'", alias));
+                    }
+                    let _ = done.send(found(provided, || format!("'{}' is not a provided service", alias)));
+                }
+                Command::StopOfferService { alias, done } => {
+                    let stopped = self.stop_offer_service(&alias);
+                    let _ = done.send(found(stopped, || format!("'{}' is not offered", alias)));
+                }
+                Command::Subscribe { service_id, instance_id, eventgroup_id, ttl, iface_alias, done } => {
+                    let result = self.try_subscribe(service_id, instance_id, eventgroup_id, ttl, &iface_alias)
+                        .map_err(|reason| std::io::Error::other(format!("subscription to Service 0x{:04x} EventGroup {} not sent: {:?}", service_id, eventgroup_id, reason)));
+                    let _ = done.send(result);
+                }
+                Command::SendNotification { service_id, eventgroup_id, event_id, payload } => {
+                    self.send_notification(service_id, eventgroup_id, event_id, &payload);
+                }
+                Command::OfferEventgroup { alias, eventgroup_id, events, multicast_threshold, done } => {
+                    let offered = self.offer_eventgroup(&alias, eventgroup_id, &events, multicast_threshold);
+                    let result = if offered { Ok(()) } else {
+                        Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("EventGroup {} of '{}' not offered", eventgroup_id, alias)))
+                    };
+                    let _ = done.send(result);
+                }
+                Command::FireEvent { service_id, event_id, payload } => {
+                    self.fire_event(service_id, event_id, &payload);
+                }
+                Command::RegisterNotificationHandler { service_id, handler } => {
+                    self.register_notification_handler(service_id, handler)
+                }
+                Command::DisableInterface { iface_alias, done } => {
+                    let disabled = self.disable_interface(&iface_alias);
+                    let _ = done.send(found(disabled, || format!("unknown interface '{}'", iface_alias)));
+                }
+                Command::EnableInterface { iface_alias, done } => {
+                    let enabled = self.enable_interface(&iface_alias);
+                    let _ = done.send(found(enabled, || format!("unknown interface '{}'", iface_alias)));
+                }
+            }
+        }
+    }
+
+    /// Make the event loop return from waiting for traffic, to pick up new work.
+    pub(crate) fn wake(&self) {
+        if let Some(waker) = &self.waker {
+            waker.wake();
+        }
+    }
+
+    /// Make the SD thread poll now, after a change that may have started an SD timer.
+    fn wake_sd(&self) {
+        if let Some(waker) = &self.sd_waker {
+            waker.wake();
+        }
+    }
+
+    /// Longest the event loop may wait for traffic before a timer needs servicing.
+    /// Polling SD here, queued requests waiting for their service, held events and
+    /// subscriptions waiting for an offer are checked on every tick;
+    /// heartbeats are waited for exactly.
+    fn idle_timeout(&self, polls_sd: bool) -> Duration {
+        if polls_sd
+            || self.forward_queues.lock().unwrap().values().any(|q| !q.is_empty())
+            || self.reorder_buffers.lock().unwrap().values().any(|b| b.held() > 0)
+            || !self.awaiting_offer.lock().unwrap().is_empty() {
+            return readiness::FALLBACK_TICK;
+        }
+        let now = std::time::Instant::now();
+        self.heartbeats.lock().unwrap().iter()
+            .map(|hb| hb.next.saturating_duration_since(now))
+            .fold(MAX_IDLE_WAIT, Duration::min)
+    }
+
+    /// Poll SD on a dedicated thread until the runtime stops, so offers, TTL expiry and
+    /// subscription handling keep their timing while request handlers or a flood of data
+    /// traffic occupy the event loop. Between polls it blocks until an SD socket has a
+    /// datagram, the next SD timer is due or it is woken. Returns None if the thread could
+    /// not be started.
+    fn spawn_sd_thread(&self) -> Option<thread::JoinHandle<()>> {
+        let sd = self.sd.clone();
+        let running = self.running.clone();
+        #[cfg(target_os = "linux")]
+        let waker = self.sd_waker.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("sd-{}", self.instance_name))
+            .spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    let mut machine = sd.lock().unwrap_or_else(PoisonError::into_inner);
+                    machine.poll();
+                    let timeout = machine.until_next_timer().map_or(SD_MAX_IDLE_WAIT, |t| t.min(SD_MAX_IDLE_WAIT));
+                    #[cfg(target_os = "linux")]
+                    let fds = machine.poll_fds();
+                    drop(machine);
+                    #[cfg(target_os = "linux")]
+                    if let Some(fds) = fds {
+                        readiness::wait_fds(&fds, waker.as_deref(), timeout);
+                        continue;
+                    }
+                    thread::sleep(timeout.min(readiness::FALLBACK_TICK));
+                }
+            });
+        match spawned {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                self.logger.log(LogLevel::Warn, "Runtime", &format!("Polling SD from the event loop: cannot start SD thread: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Start serving Prometheus metrics if the instance configures `metrics`.
+    #[cfg(feature = "prometheus")]
+    fn spawn_metrics_exporter(&self) -> Option<thread::JoinHandle<()>> {
+        let instance = self.instance_config()?;
+        let cfg = instance.metrics.as_ref()?;
+        let addr = match cfg.bind.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, cfg.port),
+            Err(e) => {
+                self.logger.log(LogLevel::Error, "Runtime", &format!("Not serving metrics: invalid bind address '{}': {}", cfg.bind, e));
+                return None;
+            }
+        };
+        match metrics::spawn(self.self_ref.clone(), addr, self.running.clone()) {
+            Ok((local, thread)) => {
+                *self.metrics_addr.lock().unwrap() = Some(local);
+                self.logger.log(LogLevel::Info, "Runtime", &format!("Serving Prometheus metrics on http://{}/metrics", local));
+                Some(thread)
+            }
+            Err(e) => {
+                self.logger.log(LogLevel::Error, "Runtime", &format!("Not serving metrics: cannot listen on {}: {}", addr, e));
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "prometheus"))]
+    fn spawn_metrics_exporter(&self) -> Option<thread::JoinHandle<()>> {
+        if self.instance_config().is_some_and(|c| c.metrics.is_some()) {
+            self.logger.log(LogLevel::Warn, "Runtime", "Not serving metrics: built without the `prometheus` feature");
+        }
+        None
+    }
+
+    /// Only resolve offers of required services with the configured version.
+    fn require_versions(sd: &mut ServiceDiscovery, instance_config: &InstanceConfig) {
+        sd.clear_required_versions();
+        for req in instance_config.required.values() {
+            sd.set_required_version(req.service_id, req.instance_id, req.major_version, req.minor_version);
+        }
+    }
+
+    /// Subscriptions to eventgroups with `subscribe` set, keyed by (service, eventgroup).
+    fn auto_subscriptions(instance_config: &InstanceConfig, iface_aliases: &[String], logger: &dyn FusionLogger) -> HashMap<(u16, u16), OwnSubscription> {
+        let mut subscriptions = HashMap::new();
+        for (alias, req) in &instance_config.required {
+            for eg in req.eventgroups.values().filter(|eg| eg.subscribe) {
+                let iface = req.subscription_interface(eg.eventgroup_id).or(iface_aliases.first());
+                let Some(iface) = iface else {
+                    logger.log(LogLevel::Warn, "Runtime", &format!("Not subscribing to EventGroup {} of '{}': no interface to subscribe on", eg.eventgroup_id, alias));
+                    continue;
+                };
+                subscriptions.insert((req.service_id, eg.eventgroup_id), OwnSubscription {
+                    instance_id: req.instance_id,
+                    ttl: eg.ttl.unwrap_or(instance_config.sd.ttl),
+                    iface_alias: iface.clone(),
+                });
+            }
+        }
+        subscriptions
+    }
+
+    /// Address the Prometheus exporter listens on, once the event loop has started it.
+    #[cfg(feature = "prometheus")]
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        *self.metrics_addr.lock().unwrap()
+    }
+
     pub fn run(&self) {
         self.logger.log(LogLevel::Info, "Runtime", "Event Loop Started");
-        let mut buf = [0u8; 4096];
+        *self.loop_active.lock().unwrap() = true;
+        // Zeroed on allocation, so only pages a large message reaches are backed by memory
+        let mut buf = vec![0u8; RECEIVE_BUFFER_LEN];
+        let sd_thread = self.spawn_sd_thread();
+        let metrics_thread = self.spawn_metrics_exporter();
         
         while self.running.load(Ordering::Relaxed) {
-            // 1. Poll SD
-            {
+            // 0. Apply mutations queued by handles
+            self.process_commands();
+            #[cfg(target_os = "linux")]
+            self.reload_on_signal();
+
+            // 1. Poll SD, unless its own thread does
+            if sd_thread.is_none() {
                 let mut sd = self.sd.lock().unwrap();
                 sd.poll();
             }
+            self.flush_forward_queues();
+            self.publish_heartbeats();
+            self.release_held_events();
+            self.recover_from_peer_reboots();
+            self.subscribe_when_offered();
+            self.report_subscription_nacks();
+            self.join_event_multicast();
+            self.send_initial_events();
+            self.expire_tp_reassemblies();
             
             // 2. Poll All Transports
-            let mut all_transports: Vec<Arc<dyn SomeIpTransport>> = Vec::new();
-            all_transports.extend(self.udp_transports.iter().cloned());
-            all_transports.extend(self.tcp_transports.iter().cloned());
+            let mut all_transports: Vec<(Arc<dyn SomeIpTransport>, Option<SocketAddr>)> = Vec::new();
+            all_transports.extend(self.bindings().udp.iter().map(|t| (t.clone(), None)));
+            #[cfg(target_os = "linux")]
+            all_transports.extend(self.shm_transports.iter().map(|t| (t.clone() as Arc<dyn SomeIpTransport>, None)));
+            all_transports.extend(self.bindings().tcp.iter().map(|t| (t.clone(), None)));
+            all_transports.extend(self.multicast_receivers.lock().unwrap().values().map(|t| (t.clone(), None)));
+            all_transports.extend(self.tcp_clients.connections().into_iter().map(|(ep, c)| (c as Arc<dyn SomeIpTransport>, Some(ep))));
+            let watched: Vec<Arc<dyn SomeIpTransport>> = all_transports.iter().map(|(t, _)| t.clone()).collect();
+            let mut received = false;
             
-            for transport in all_transports {
+            for (transport, client_endpoint) in all_transports {
                 match transport.receive(&mut buf) {
                     Ok((size, src)) => {
-                        if size < 16 { continue; }
+                        received = true;
+                        if size < 16 {
+                            self.counters.record_drop();
+                            continue;
+                        }
                         if let Ok(header) = SomeIpHeader::deserialize(&buf[..16]) {
+                            if let Some(code) = check_message(&header, size) {
+                                self.refuse(transport.as_ref(), &header, code, src);
+                                continue;
+                            }
+
                             // Check for TP
                             let mt = header.message_type_enum();
                             let is_tp = mt.map(|m| m.uses_tp()).unwrap_or(false);
                             
                             let payload = &buf[16..size];
-                            let mut allocated_payload: Option<Vec<u8>> = None;
+                            let mut allocated_payload: Option<PooledBuffer> = None;
                             
                             if is_tp {
                                 // TP packet structure: Header (16) + TpHeader (4) + Payload
                                 // Check size
                                 if size < 20 {
+                                     self.counters.record_drop();
                                      self.logger.log(LogLevel::Warn, "Runtime", "Received TP packet too short");
                                      continue;
                                 }
@@ -602,25 +2836,30 @@ impl SomeIpRuntime {
                                     let segment_payload = &buf[20..size];
                                     let mut reassembler = self.tp_reassembler.lock().unwrap();
                                     match reassembler.process_segment(
+                                        src,
                                         (header.service_id as u32) << 16 | header.method_id as u32, 
-                                        (header.client_id as u32) << 16 | header.session_id as u32, 
+                                        header.request_id().raw(),
                                         &tp_header, 
                                         segment_payload
                                     ) {
                                         Ok(Some(full_payload)) => {
+                                            self.counters.record_reassembly();
                                             self.logger.log(LogLevel::Info, "Runtime", &format!("Reassembled TP message: {} bytes", full_payload.len()));
-                                            allocated_payload = Some(full_payload);
+                                            allocated_payload = Some(buffer::shared().adopt(full_payload));
                                         },
                                         Ok(None) => {
                                             // Stored, waiting for more
                                             continue;
                                         },
                                         Err(e) => {
+                                            self.counters.record_error();
+                                            self.counters.record_drop();
                                             self.logger.log(LogLevel::Error, "Runtime", &format!("TP Reassembly Error: {}", e));
                                             continue;
                                         }
                                     }
                                 } else {
+                                     self.counters.record_drop();
                                      self.logger.log(LogLevel::Warn, "Runtime", "Failed to deserialize TP header");
                                      continue;
                                 }
@@ -632,79 +2871,126 @@ impl SomeIpRuntime {
                             } else {
                                 payload
                             };
+                            self.counters.record_received(header.service_id);
+                            let Some(effective_payload) = self.verify_authentication(&header, effective_payload, src) else {
+                                self.counters.record_drop();
+                                continue;
+                            };
+                            let e2e_checked;
+                            let effective_payload = match self.check_e2e(&header, effective_payload) {
+                                Ok(Some(checked)) => {
+                                    e2e_checked = checked;
+                                    &e2e_checked[..]
+                                }
+                                Ok(None) => effective_payload,
+                                Err(code) => {
+                                    self.counters.record_error();
+                                    if header.message_type == 0x80 || header.message_type == 0xA0 {
+                                        // Fail the waiting request rather than let it time out
+                                        self.pending_requests.lock().unwrap().remove(&(header.service_id, header.method_id, header.request_id()));
+                                    }
+                                    self.refuse(transport.as_ref(), &header, code, src);
+                                    continue;
+                                }
+                            };
+                            let Some(effective_payload) = self.verify_schema(&header, effective_payload) else {
+                                self.counters.record_drop();
+                                continue;
+                            };
 
-                            self.logger.log(LogLevel::Debug, "Runtime", &format!("Received packet: Service 0x{:04x} Method 0x{:04x} Type 0x{:02x} Length {}", header.service_id, header.method_id, header.message_type, header.length));
+                            self.logger.log_with(LogLevel::Debug, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Received packet: Service 0x{:04x} Method 0x{:04x} Type 0x{:02x} Length {}", header.service_id, header.method_id, header.message_type, header.length));
                             #[cfg(feature = "packet-dump")]
                             header.dump(src);
                              // Handle RESPONSE (0x80) or TP Response (0xA0)
                              if header.message_type == 0x80 || header.message_type == 0xA0 {
                                  let mut pending = self.pending_requests.lock().unwrap();
-                                 if let Some(tx) = pending.remove(&(header.service_id, header.method_id, header.session_id)) {
-                                     let _ = tx.send(effective_payload.to_vec());
+                                 if let Some(tx) = pending.remove(&(header.service_id, header.method_id, header.request_id())) {
+                                     tx.send(Ok(effective_payload.to_vec()));
+                                 }
+                                 continue;
+                             }
+
+                             // Handle ERROR (0x81) or TP Error (0xA1): fail the request with the provider's return code
+                             if header.message_type == 0x81 || header.message_type == 0xA1 {
+                                 let mut pending = self.pending_requests.lock().unwrap();
+                                 if let Some(tx) = pending.remove(&(header.service_id, header.method_id, header.request_id())) {
+                                     tx.send(Err(ReturnCode::from_u8(header.return_code).unwrap_or(ReturnCode::NotOk)));
                                  }
                                  continue;
                              }
     
-                             // Dispatch
-                             let services = self.services.read().unwrap();
-                             
                              // Handle Notification (0x02) or TP Notification (0x22)
                              if header.message_type == 0x02 || header.message_type == 0x22 {
-                                 self.logger.log(LogLevel::Info, "Runtime", &format!("Received Notification: Service 0x{:04x} Event/Method 0x{:04x} Payload {} bytes", header.service_id, header.method_id, effective_payload.len()));
-                                 if let Some(handler) = services.get(&header.service_id) {
-                                     handler.handle(&header, effective_payload);
+                                 if !SomeIpHeader::is_event_id(header.method_id) {
+                                     self.counters.record_drop();
+                                     self.logger.log(LogLevel::Warn, "Runtime", &format!("Dropping notification with method ID 0x{:04x} (events must be 0x8000-0xFFFF)", header.method_id));
+                                     continue;
+                                 }
+                                 self.event_times.lock().unwrap().insert((header.service_id, header.method_id), Timestamp::now());
+                                 self.logger.log_with(LogLevel::Info, "Runtime", &LogContext::default().with_service(header.service_id), &format!("Received Notification: Service 0x{:04x} Event/Method 0x{:04x} Payload {} bytes", header.service_id, header.method_id, effective_payload.len()));
+                                 // Handlers are cloned out so no lock is held while they run
+                                 let handler = self.notification_handlers.read().unwrap().get(&header.service_id).cloned();
+                                 let callback = self.event_callbacks.read().unwrap().get(&(header.service_id, header.method_id)).cloned();
+                                 if handler.is_some() || callback.is_some() {
+                                     match self.reorder_configs.get(&header.service_id) {
+                                         Some(&(window, max_hold)) => {
+                                             let ready = self.reorder_buffers.lock().unwrap()
+                                                 .entry((header.service_id, header.method_id))
+                                                 .or_insert_with(|| ReorderBuffer::new(window, max_hold))
+                                                 .push(header.session_id, (header.clone(), effective_payload.to_vec()), std::time::Instant::now());
+                                             for (h, p) in ready {
+                                                 self.deliver_notification(handler.as_ref(), callback.as_ref(), &h, &p);
+                                             }
+                                         }
+                                         None => self.deliver_notification(handler.as_ref(), callback.as_ref(), &header, effective_payload),
+                                     }
                                  }
                                  continue;
                              }
     
-                             if let Some(handler) = services.get(&header.service_id) {
-                                 // Request (0x00), RequestNoReturn (0x01), TP Request (0x20), TP ReqNoRet (0x21)
-                                 let is_req = header.message_type == 0x00 || header.message_type == 0x20;
-                                 let is_ff = header.message_type == 0x01 || header.message_type == 0x21;
-                                 
+                             // Request (0x00), RequestNoReturn (0x01), TP Request (0x20), TP ReqNoRet (0x21)
+                             let is_req = header.message_type == 0x00 || header.message_type == 0x20;
+                             let is_ff = header.message_type == 0x01 || header.message_type == 0x21;
+                             let handler = if is_req || is_ff { self.request_handler(header.service_id, transport.as_ref()) } else { None };
+                             if handler.is_none() && (is_req || is_ff) {
+                                 self.refuse(transport.as_ref(), &header, ReturnCode::UnknownService, src);
+                                 continue;
+                             }
+
+                             if let Some((instance_id, handler)) = handler {
+                                 if (is_req || is_ff) && SomeIpHeader::is_event_id(header.method_id) {
+                                     self.refuse(transport.as_ref(), &header, ReturnCode::UnknownMethod, src);
+                                     continue;
+                                 }
+                                 // The interface version of a request is the major version it was written against
+                                 if (is_req || is_ff) && header.interface_version != handler.major_version() {
+                                     self.refuse(transport.as_ref(), &header, ReturnCode::WrongInterfaceVersion, src);
+                                     continue;
+                                 }
+                                 if (is_req || is_ff) && !self.access_allows(&header, src) {
+                                     self.refuse(transport.as_ref(), &header, ReturnCode::NotOk, src);
+                                     continue;
+                                 }
                                  if is_req || is_ff {
-                                     if let Some(res_payload) = handler.handle(&header, effective_payload) {
-                                          if is_req {
-                                              // Send Response
-                                              let mtu = 1400; // Conservative MTU
-                                              let header_len = 16 + 4; // SOME/IP + TP
-                                              let max_segment_payload = (mtu - header_len) / 16 * 16; // Align to 16
-                                              
-                                              if res_payload.len() > max_segment_payload {
-                                                  // Segmented Response
-                                                  // Use 0xA0 (ResponseWithTp)
-                                                  let segments = crate::codec::tp::segment_payload(&res_payload, max_segment_payload);
-                                                  for (tp_header, chunk) in segments {
-                                                      let msg_header = SomeIpHeader::new(
-                                                          header.service_id,
-                                                          header.method_id,
-                                                          header.client_id,
-                                                          header.session_id,
-                                                          0xA0, // ResponseWithTp
-                                                          (4 + chunk.len()) as u32 // Length covers TP Header + Payload
-                                                      );
-                                                      let mut msg = msg_header.serialize().to_vec();
-                                                      msg.extend_from_slice(&tp_header.serialize());
-                                                      msg.extend_from_slice(&chunk);
-                                                      let _ = transport.send(&msg, Some(src));
-                                                      // Small delay to avoid flooding UDP buffer
-                                                      // std::thread::sleep(std::time::Duration::from_micros(100)); 
-                                                  }
-                                              } else {
-                                                  // Standard Response
-                                                  let res_header = SomeIpHeader::new(
-                                                      header.service_id,
-                                                      header.method_id,
-                                                      header.client_id,
-                                                      header.session_id,
-                                                      0x80, // RESPONSE
-                                                      res_payload.len() as u32
-                                                  );
-                                                  let mut res_msg = res_header.serialize().to_vec();
-                                                  res_msg.extend(res_payload);
-                                                  let _ = transport.send(&res_msg, Some(src));
-                                              }
-                                          }
+                                     let code = handler.validate(&header, effective_payload);
+                                     if code != ReturnCode::Ok {
+                                         self.refuse(transport.as_ref(), &header, code, src);
+                                         continue;
+                                     }
+                                     let Some(handler) = self.admit_request(handler, &header) else {
+                                         if is_req {
+                                             self.reply_error(transport.as_ref(), &header, ReturnCode::NotReady, src);
+                                         }
+                                         continue;
+                                     };
+                                     match (&self.workers, self.self_ref.upgrade()) {
+                                         // Requests of one client to one service stay in order on one worker
+                                         (Some(workers), Some(runtime)) => {
+                                             let key = (header.service_id, header.client_id);
+                                             let (transport, header, payload) = (transport.clone(), header.clone(), effective_payload.to_vec());
+                                             workers.execute(move || runtime.serve_request(transport.as_ref(), handler, instance_id, &header, &payload, src), Some(key));
+                                         }
+                                         _ => self.serve_request(transport.as_ref(), handler, instance_id, &header, effective_payload, src),
                                      }
                                  }
                              }
@@ -712,16 +2998,164 @@ impl SomeIpRuntime {
                     }
                     Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
                     Err(e) => {
-                        self.logger.log(LogLevel::Error, "Runtime", &format!("Receive error: {}", e));
+                        if let Some(ep) = client_endpoint {
+                            // Outgoing TCP connection went away; drop it so it is re-established on demand
+                            self.tcp_clients.drop_connection(ep);
+                            self.logger.log(LogLevel::Warn, "Runtime", &format!("TCP connection to {} closed: {}", ep, e));
+                        } else {
+                            self.logger.log(LogLevel::Error, "Runtime", &format!("Receive error: {}", e));
+                        }
                     }
                 }
             }
             
-            thread::sleep(Duration::from_millis(10));
+            // 3. Block until traffic or the next timer, unless there may be more to read
+            if !received {
+                readiness::wait(&watched, self.waker.as_ref(), self.idle_timeout(sd_thread.is_none()));
+            }
+        }
+        if let Some(thread) = sd_thread {
+            let _ = thread.join();
+        }
+        if let Some(thread) = metrics_thread {
+            let _ = thread.join();
         }
+        *self.loop_active.lock().unwrap() = false;
+        self.loop_exited.notify_all();
     }
     
+    /// Stop the event loop without telling peers; see [`shutdown`](Self::shutdown).
     pub fn stop(&self) {
         self.running.store(false, Ordering::SeqCst);
+        self.wake();
+        self.wake_sd();
+        self.emit_shutdown_report();
+    }
+
+    /// Leave the network cleanly and stop, for a thread other than the one in `run()`:
+    ///
+    /// 1. send StopOfferService for every offered service
+    /// 2. send StopSubscribeEventgroup for every eventgroup subscribed to
+    /// 3. stop the event loop and wait for `run()` to return
+    /// 4. wait for requests queued on worker threads to be served
+    /// 5. close TCP connections, outgoing and accepted
+    ///
+    /// Steps 3 and 4 share `timeout`. Returns `false` if it ran out before the loop
+    /// exited and the workers drained; the remaining steps run regardless.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+        let withdrawn = self.sd.lock().unwrap().stop_offer_all();
+
+        let subscriptions: Vec<((u16, u16), OwnSubscription)> = self.own_subscriptions.lock().unwrap().drain().collect();
+        self.awaiting_offer.lock().unwrap().clear();
+        for ((service_id, eventgroup_id), sub) in &subscriptions {
+            let _ = self.try_subscribe(*service_id, sub.instance_id, *eventgroup_id, 0, &sub.iface_alias);
+        }
+        self.logger.log(LogLevel::Info, "Runtime", &format!("Shutting down: withdrew {} service(s), unsubscribed {} eventgroup(s)", withdrawn, subscriptions.len()));
+
+        self.running.store(false, Ordering::SeqCst);
+        self.wake();
+        self.wake_sd();
+        let mut drained = {
+            let active = self.loop_active.lock().unwrap();
+            let (active, _) = self.loop_exited
+                .wait_timeout_while(active, deadline.saturating_duration_since(std::time::Instant::now()), |active| *active)
+                .unwrap();
+            !*active
+        };
+        if let Some(workers) = &self.workers {
+            while workers.queued_jobs() > 0 && std::time::Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(5));
+            }
+            drained &= workers.queued_jobs() == 0;
+        }
+        if !drained {
+            self.logger.log(LogLevel::Warn, "Runtime", "Shutdown timed out waiting for the event loop or in-flight requests");
+        }
+
+        for (_, connection) in self.tcp_clients.connections() {
+            let _ = connection.close();
+        }
+        self.tcp_clients.retain(|_| false);
+        for transport in &self.bindings().tcp {
+            if let Err(e) = transport.close() {
+                self.logger.log(LogLevel::Warn, "Runtime", &format!("Failed to close TCP transport: {}", e));
+            }
+        }
+        self.emit_shutdown_report();
+        drained
+    }
+
+    /// Current counters and queue depths; see [`RuntimeStats`].
+    pub fn stats(&self) -> RuntimeStats {
+        let (sd_offers_sent, sd_offers_received, sd_backlog, sd_remote_services, sd_subscribers) = {
+            let sd = self.sd.lock().unwrap();
+            (sd.offers_sent(), sd.offers_received(), sd.ingress_stats().backlog, sd.remote_services.len(), sd.subscriber_count())
+        };
+        let now = Timestamp::now();
+        RuntimeStats {
+            taken_at_us: now.as_micros(),
+            uptime_ms: now.duration_since(self.started_at).as_millis() as u64,
+            services: self.counters.traffic(),
+            requests_served: self.counters.requests_served.load(Ordering::Relaxed),
+            errors: self.counters.errors.load(Ordering::Relaxed),
+            request_timeouts: self.counters.request_timeouts.load(Ordering::Relaxed),
+            requests_rejected: self.counters.requests_rejected.load(Ordering::Relaxed),
+            tp_reassemblies: self.counters.tp_reassemblies.load(Ordering::Relaxed),
+            packets_dropped: self.counters.packets_dropped.load(Ordering::Relaxed),
+            sd_offers_sent,
+            sd_offers_received,
+            pending_requests: self.pending_requests.lock().unwrap().len(),
+            queued_requests: self.forward_queues.lock().unwrap().values().map(|q| q.len()).sum(),
+            tp_in_progress: self.tp_stats().in_progress,
+            sd_backlog,
+            sd_remote_services,
+            sd_subscribers,
+            buffers: buffer::shared().stats(),
+        }
+    }
+
+    /// Digest of this runtime's lifetime so far; see [`ShutdownReport`].
+    pub fn shutdown_report(&self) -> ShutdownReport {
+        // Also called from drop, so tolerate poisoned locks
+        let (sd_offers_sent, subscribers_abandoned) = {
+            let sd = self.sd.lock().unwrap_or_else(PoisonError::into_inner);
+            (sd.offers_sent(), sd.subscriber_count())
+        };
+        let now = Timestamp::now();
+        ShutdownReport {
+            instance: self.instance_name.clone(),
+            taken_at_us: now.as_micros(),
+            uptime_ms: now.duration_since(self.started_at).as_millis() as u64,
+            requests_served: self.counters.requests_served.load(Ordering::Relaxed),
+            errors: self.counters.errors.load(Ordering::Relaxed),
+            tp_reassemblies: self.counters.tp_reassemblies.load(Ordering::Relaxed),
+            sd_offers_sent,
+            pending_requests_dropped: self.pending_requests.lock().unwrap_or_else(PoisonError::into_inner).len(),
+            queued_requests_dropped: self.forward_queues.lock().unwrap_or_else(PoisonError::into_inner).values().map(|q| q.len()).sum(),
+            subscribers_abandoned,
+        }
+    }
+
+    /// Log the shutdown report, and write it to `shutdown_report_path` if configured. Only the first call reports.
+    fn emit_shutdown_report(&self) {
+        if self.report_emitted.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let report = self.shutdown_report();
+        let level = if report.is_clean() { LogLevel::Info } else { LogLevel::Warn };
+        self.logger.log(level, "Runtime", &report.summary());
+        if let Some(path) = self.instance_config().and_then(|c| c.shutdown_report_path.clone()) {
+            match report.write_json(&path) {
+                Ok(()) => self.logger.log(LogLevel::Info, "Runtime", &format!("Shutdown report written to {}", path)),
+                Err(e) => self.logger.log(LogLevel::Error, "Runtime", &format!("Failed to write shutdown report to {}: {}", path, e)),
+            }
+        }
+    }
+}
+
+impl Drop for SomeIpRuntime {
+    fn drop(&mut self) {
+        self.emit_shutdown_report();
     }
 }
@@ -0,0 +1,381 @@
+//! # Network Interface Detection
+//!
+//! Endpoints of an interface may use `"ip": "auto"` instead of a hard-coded
+//! address. When a runtime is loaded, the network interfaces of the host are
+//! enumerated and each configured interface is matched to one of them:
+//!
+//! - with `subnet` (e.g. `"192.168.1.0/24"`), the interface having an address
+//!   in it, so the same file works on hosts whose interface names differ
+//! - otherwise the interface whose name is the configured `name`
+//!
+//! `auto` endpoints then take the matched interface's address of their
+//! `version`, and SD on an interface without unicast endpoints binds and
+//! announces from it. IPv6 link-local addresses are never chosen, as they
+//! cannot be bound without a scope.
+
+use super::access::Subnet;
+use super::binding;
+use super::config::{InterfaceConfig, SystemConfig};
+use super::error::RuntimeError;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Value of an endpoint `ip` filled in from the matched network interface
+pub const AUTO: &str = "auto";
+
+/// A network interface of the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkInterface {
+    pub name: String,
+    /// Index as used for IPv6 scope IDs and multicast (0 if unknown)
+    pub index: u32,
+    /// Addresses with their prefix length
+    pub addresses: Vec<(IpAddr, u8)>,
+}
+
+impl NetworkInterface {
+    /// First usable address of the family of `version`, within `subnet` if given.
+    fn address(&self, version: u8, subnet: Option<&Subnet>) -> Option<IpAddr> {
+        self.addresses.iter()
+            .map(|(ip, _)| *ip)
+            .filter(|ip| if version == 6 { ip.is_ipv6() } else { ip.is_ipv4() })
+            .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.is_unicast_link_local()))
+            .find(|ip| subnet.is_none_or(|net| net.contains(*ip)))
+    }
+}
+
+/// Addresses of the network interface a configured interface was matched to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalAddresses {
+    pub name: String,
+    pub index: u32,
+    pub v4: Option<Ipv4Addr>,
+    pub v6: Option<Ipv6Addr>,
+}
+
+/// Network interfaces of the host.
+#[cfg(target_os = "linux")]
+pub fn list() -> io::Result<Vec<NetworkInterface>> {
+    use std::ffi::CStr;
+
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: on success `head` is a list owned by us until freeifaddrs
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut nics: Vec<NetworkInterface> = Vec::new();
+    let mut cursor = head;
+    while !cursor.is_null() {
+        // SAFETY: entries, their names and addresses stay valid until freeifaddrs
+        let (entry, name) = unsafe { (&*cursor, CStr::from_ptr((*cursor).ifa_name)) };
+        cursor = entry.ifa_next;
+        let position = match nics.iter().position(|nic| nic.name.as_bytes() == name.to_bytes()) {
+            Some(position) => position,
+            None => {
+                // SAFETY: `name` is a NUL-terminated interface name
+                let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+                nics.push(NetworkInterface { name: name.to_string_lossy().into_owned(), index, addresses: Vec::new() });
+                nics.len() - 1
+            }
+        };
+        // SAFETY: as above
+        let (address, netmask) = unsafe { (sockaddr_ip(entry.ifa_addr), sockaddr_ip(entry.ifa_netmask)) };
+        if let Some(address) = address {
+            let prefix = match netmask {
+                Some(IpAddr::V4(mask)) => u32::from(mask).count_ones(),
+                Some(IpAddr::V6(mask)) => u128::from(mask).count_ones(),
+                None => 0,
+            };
+            nics[position].addresses.push((address, prefix as u8));
+        }
+    }
+    // SAFETY: `head` came from getifaddrs and is not used afterwards
+    unsafe { libc::freeifaddrs(head) };
+    Ok(nics)
+}
+
+/// IP address of an IPv4 or IPv6 `sockaddr`, which may be null.
+///
+/// # Safety
+/// `addr` must be null or point to a valid `sockaddr` of its family.
+#[cfg(target_os = "linux")]
+unsafe fn sockaddr_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    if addr.is_null() {
+        return None;
+    }
+    // SAFETY: the family tells which structure `addr` points to
+    unsafe {
+        match (*addr).sa_family as libc::c_int {
+            libc::AF_INET => {
+                let v4 = &*(addr as *const libc::sockaddr_in);
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(v4.sin_addr.s_addr))))
+            }
+            libc::AF_INET6 => {
+                let v6 = &*(addr as *const libc::sockaddr_in6);
+                Some(IpAddr::V6(Ipv6Addr::from(v6.sin6_addr.s6_addr)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Network interfaces of the host.
+#[cfg(not(target_os = "linux"))]
+pub fn list() -> io::Result<Vec<NetworkInterface>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "network interface detection is only supported on Linux"))
+}
+
+/// Index of the network interface `name`, for IPv6 multicast and scope IDs.
+#[cfg(unix)]
+pub fn index(name: &str) -> io::Result<u32> {
+    let c_name = std::ffi::CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `c_name` is a NUL-terminated string
+    match unsafe { libc::if_nametoindex(c_name.as_ptr()) } {
+        0 => Err(io::Error::new(io::ErrorKind::NotFound, format!("no network interface named '{}'", name))),
+        index => Ok(index),
+    }
+}
+
+/// Index of the network interface `name`, for IPv6 multicast and scope IDs. `name` is the
+/// adapter's friendly name (e.g. `Ethernet`) or its adapter name (a GUID).
+#[cfg(windows)]
+pub fn index(name: &str) -> io::Result<u32> {
+    windows::index(name)
+}
+
+/// Index of the network interface `name`, for IPv6 multicast and scope IDs.
+#[cfg(not(any(unix, windows)))]
+pub fn index(name: &str) -> io::Result<u32> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("cannot look up network interface '{}' on this platform", name)))
+}
+
+/// Index of the network interface that has the address `ip`, if it can be found.
+pub fn index_of(ip: IpAddr) -> Option<u32> {
+    list().ok()?.into_iter()
+        .find(|nic| nic.addresses.iter().any(|(address, _)| *address == ip))
+        .map(|nic| nic.index)
+        .filter(|index| *index != 0)
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::{c_char, c_void, CStr};
+    use std::io;
+
+    const AF_UNSPEC: u32 = 0;
+    /// Skip unicast, anycast, multicast and DNS server addresses; only the adapters are needed
+    const GAA_FLAGS: u32 = 0x0001 | 0x0002 | 0x0004 | 0x0008;
+    const ERROR_SUCCESS: u32 = 0;
+    const ERROR_BUFFER_OVERFLOW: u32 = 111;
+
+    /// Leading fields of `IP_ADAPTER_ADDRESSES`, which are the same in every version
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct IpAdapterAddresses {
+        length: u32,
+        if_index: u32,
+        next: *const IpAdapterAddresses,
+        adapter_name: *const c_char,
+        first_unicast_address: *const c_void,
+        first_anycast_address: *const c_void,
+        first_multicast_address: *const c_void,
+        first_dns_server_address: *const c_void,
+        dns_suffix: *const u16,
+        description: *const u16,
+        friendly_name: *const u16,
+        physical_address: [u8; 8],
+        physical_address_length: u32,
+        flags: u32,
+        mtu: u32,
+        if_type: u32,
+        oper_status: i32,
+        ipv6_if_index: u32,
+    }
+
+    #[link(name = "iphlpapi")]
+    unsafe extern "system" {
+        fn GetAdaptersAddresses(family: u32, flags: u32, reserved: *mut c_void, addresses: *mut IpAdapterAddresses, size: *mut u32) -> u32;
+    }
+
+    /// UTF-16 string up to its NUL.
+    ///
+    /// # Safety
+    /// `text` must be null or point to a NUL-terminated UTF-16 string.
+    unsafe fn wide(text: *const u16) -> String {
+        if text.is_null() {
+            return String::new();
+        }
+        let mut len = 0;
+        // SAFETY: the string is NUL-terminated
+        unsafe {
+            while *text.add(len) != 0 {
+                len += 1;
+            }
+            String::from_utf16_lossy(std::slice::from_raw_parts(text, len))
+        }
+    }
+
+    pub fn index(name: &str) -> io::Result<u32> {
+        // u64 elements keep the buffer aligned for the adapter records
+        let mut buffer: Vec<u64> = vec![0; 2048];
+        loop {
+            let mut size = (buffer.len() * 8) as u32;
+            let head = buffer.as_mut_ptr() as *mut IpAdapterAddresses;
+            // SAFETY: `head` points to `size` writable bytes
+            match unsafe { GetAdaptersAddresses(AF_UNSPEC, GAA_FLAGS, std::ptr::null_mut(), head, &mut size) } {
+                ERROR_SUCCESS => break,
+                ERROR_BUFFER_OVERFLOW => buffer.resize((size as usize).div_ceil(8), 0),
+                code => return Err(io::Error::from_raw_os_error(code as i32)),
+            }
+        }
+        let mut cursor = buffer.as_ptr() as *const IpAdapterAddresses;
+        while !cursor.is_null() {
+            // SAFETY: the records and their strings live in `buffer`
+            let (adapter, friendly, adapter_name) = unsafe {
+                let adapter = &*cursor;
+                let adapter_name = if adapter.adapter_name.is_null() { String::new() } else { CStr::from_ptr(adapter.adapter_name).to_string_lossy().into_owned() };
+                (adapter, wide(adapter.friendly_name), adapter_name)
+            };
+            if friendly == name || adapter_name.eq_ignore_ascii_case(name) {
+                return Ok(if adapter.ipv6_if_index != 0 { adapter.ipv6_if_index } else { adapter.if_index });
+            }
+            cursor = adapter.next;
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("no network interface named '{}'", name)))
+    }
+}
+
+/// The network interface among `nics` that `iface` is configured for.
+pub fn find<'a>(nics: &'a [NetworkInterface], iface: &InterfaceConfig) -> Option<&'a NetworkInterface> {
+    match iface.subnet.as_deref().and_then(Subnet::parse) {
+        Some(subnet) => {
+            let mut candidates = nics.iter().filter(|nic| nic.addresses.iter().any(|(ip, _)| subnet.contains(*ip)));
+            let first = candidates.clone().next();
+            candidates.find(|nic| nic.name == iface.name).or(first)
+        }
+        None => nics.iter().find(|nic| !iface.name.is_empty() && nic.name == iface.name),
+    }
+}
+
+/// Match the interfaces `instance_name` uses to the host's network interfaces, fill in
+/// their `auto` endpoints and return the addresses of each matched interface by alias.
+pub(crate) fn resolve(system: &mut SystemConfig, instance_name: &str) -> Result<HashMap<String, LocalAddresses>, RuntimeError> {
+    let needed = system.interfaces.values().any(|iface| iface.endpoints.values().any(|ep| ep.ip == AUTO));
+    let nics = match list() {
+        Ok(nics) => nics,
+        Err(e) if needed => return Err(RuntimeError::bind("'auto' endpoints", e)),
+        Err(_) => Vec::new(),
+    };
+    resolve_with(system, instance_name, &nics)
+}
+
+pub(crate) fn resolve_with(system: &mut SystemConfig, instance_name: &str, nics: &[NetworkInterface]) -> Result<HashMap<String, LocalAddresses>, RuntimeError> {
+    let Some(instance) = system.instances.get(instance_name) else { return Ok(HashMap::new()) };
+    let mut matched = HashMap::new();
+    for alias in binding::interfaces(system, instance) {
+        let Some(iface) = system.interfaces.get_mut(&alias) else { continue };
+        let subnet = iface.subnet.as_deref().and_then(Subnet::parse);
+        let Some(nic) = find(nics, iface) else {
+            if let Some((name, _)) = iface.endpoints.iter().find(|(_, ep)| ep.ip == AUTO) {
+                let wanted = match &iface.subnet {
+                    Some(subnet) => format!("with an address in {}", subnet),
+                    None => format!("named '{}'", iface.name),
+                };
+                let e = io::Error::new(io::ErrorKind::AddrNotAvailable, format!("no network interface {}", wanted));
+                return Err(RuntimeError::bind(format!("endpoint '{}' of interface '{}'", name, alias), e));
+            }
+            continue;
+        };
+        for (name, ep) in iface.endpoints.iter_mut().filter(|(_, ep)| ep.ip == AUTO) {
+            let Some(ip) = nic.address(ep.version, subnet.as_ref()) else {
+                let e = io::Error::new(io::ErrorKind::AddrNotAvailable, format!("'{}' has no usable IPv{} address", nic.name, ep.version));
+                return Err(RuntimeError::bind(format!("endpoint '{}' of interface '{}'", name, alias), e));
+            };
+            ep.ip = ip.to_string();
+        }
+        let v4 = match nic.address(4, subnet.as_ref()) { Some(IpAddr::V4(ip)) => Some(ip), _ => None };
+        let v6 = match nic.address(6, subnet.as_ref()) { Some(IpAddr::V6(ip)) => Some(ip), _ => None };
+        matched.insert(alias, LocalAddresses { name: nic.name.clone(), index: nic.index, v4, v6 });
+    }
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::config::{EndpointConfig, InstanceConfig};
+    use crate::runtime::tests::runtime_with;
+
+    fn nics() -> Vec<NetworkInterface> {
+        vec![
+            NetworkInterface { name: "lo".to_string(), index: 1, addresses: vec![("127.0.0.1".parse().unwrap(), 8), ("::1".parse().unwrap(), 128)] },
+            NetworkInterface { name: "enp3s0".to_string(), index: 2, addresses: vec![
+                ("fe80::1".parse().unwrap(), 64), ("192.168.1.20".parse().unwrap(), 24), ("fd00::20".parse().unwrap(), 64)] },
+        ]
+    }
+
+    fn system(iface: InterfaceConfig) -> SystemConfig {
+        let mut system = SystemConfig::default();
+        system.interfaces.insert("primary".to_string(), iface);
+        let mut instance = InstanceConfig::default();
+        instance.unicast_bind.insert("primary".to_string(), "data".to_string());
+        system.instances.insert("ecu".to_string(), instance);
+        system
+    }
+
+    #[test]
+    fn test_auto_endpoints_by_name_and_subnet() {
+        let mut by_name = system(InterfaceConfig::new("enp3s0")
+            .endpoint("data", EndpointConfig::udp(AUTO, 30500))
+            .endpoint("data6", EndpointConfig { version: 6, ..EndpointConfig::udp(AUTO, 30500) }));
+        let matched = resolve_with(&mut by_name, "ecu", &nics()).unwrap();
+        let endpoints = &by_name.interfaces["primary"].endpoints;
+        assert_eq!((endpoints["data"].ip.as_str(), endpoints["data6"].ip.as_str()), ("192.168.1.20", "fd00::20"));
+        assert_eq!(matched["primary"], LocalAddresses {
+            name: "enp3s0".to_string(), index: 2, v4: Some(Ipv4Addr::new(192, 168, 1, 20)), v6: Some("fd00::20".parse().unwrap()),
+        });
+
+        // The subnet wins over a name that differs on this host
+        let mut by_subnet = system(InterfaceConfig { subnet: Some("192.168.1.0/24".to_string()), ..InterfaceConfig::new("eth0") }
+            .endpoint("data", EndpointConfig::udp(AUTO, 0)));
+        let matched = resolve_with(&mut by_subnet, "ecu", &nics()).unwrap();
+        assert_eq!(by_subnet.interfaces["primary"].endpoints["data"].ip, "192.168.1.20");
+        assert_eq!(matched["primary"].name, "enp3s0");
+    }
+
+    #[test]
+    fn test_missing_interface_is_a_bind_error() {
+        let mut config = system(InterfaceConfig::new("eth9").endpoint("data", EndpointConfig::udp(AUTO, 0)));
+        let err = resolve_with(&mut config, "ecu", &nics()).unwrap_err();
+        assert_eq!(err.to_string(), "cannot bind endpoint 'data' of interface 'primary': no network interface named 'eth9'");
+
+        // Interfaces with fixed addresses do not need a match
+        let mut fixed = system(InterfaceConfig::new("eth9").endpoint("data", EndpointConfig::udp("10.0.0.1", 0)));
+        assert!(resolve_with(&mut fixed, "ecu", &nics()).unwrap().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_list_finds_loopback() {
+        let nics = list().unwrap();
+        let lo = nics.iter().find(|nic| nic.addresses.iter().any(|(ip, _)| ip.is_loopback())).expect("a loopback interface");
+        assert_ne!(lo.index, 0);
+        assert!(lo.addresses.contains(&(IpAddr::V4(Ipv4Addr::LOCALHOST), 8)));
+        assert_eq!(index(&lo.name).unwrap(), lo.index);
+        assert_eq!(index_of(IpAddr::V4(Ipv4Addr::LOCALHOST)), Some(lo.index));
+        assert_eq!(index("fh-missing0").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_auto_addresses_from_host_interfaces() {
+        let runtime = runtime_with("auto_ip", r#"{
+            "interfaces": { "loopback": { "name": "", "subnet": "127.0.0.0/8", "endpoints": {
+                "data": { "ip": "auto", "port": 0, "version": 4, "protocol": "udp" } } } },
+            "instances": { "test": { "unicast_bind": { "loopback": "data" } } }
+        }"#);
+        assert_eq!(runtime.bindings().endpoints["data"].ip, "127.0.0.1");
+        assert!(runtime.bound_port("data").is_some_and(|port| port != 0));
+    }
+}
@@ -0,0 +1,213 @@
+//! # Configuration Includes and Variables
+//!
+//! Before a configuration file is parsed into a [`SystemConfig`](super::config::SystemConfig),
+//! its JSON goes through two steps:
+//!
+//! - `${NAME}` in a string is replaced by the environment variable `NAME`;
+//!   `${NAME:-default}` falls back to `default` when it is unset or empty, and
+//!   `$${` stands for a literal `${`. A string that is nothing but a variable
+//!   becomes a number or boolean if its value is one, so
+//!   `"port": "${SD_PORT:-30490}"` yields a port.
+//! - `"include": ["common.json", ...]` at the top level merges other files
+//!   under this one, in order. Paths are relative to the including file, and
+//!   included files may include others. Objects merge key by key and anything
+//!   else is replaced, so a file overrides what it includes.
+//!
+//! Variables are expanded in each file before its includes are resolved, so
+//! include paths may use them too.
+
+use super::validate::{ConfigError, ConfigIssue};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// JSON of the file at `path` with variables from the process environment and includes resolved.
+pub fn resolve(path: &Path) -> Result<Value, ConfigError> {
+    resolve_with(path, &|name| std::env::var(name).ok())
+}
+
+/// Like [`resolve`], looking variables up with `env`.
+pub(crate) fn resolve_with(path: &Path, env: &dyn Fn(&str) -> Option<String>) -> Result<Value, ConfigError> {
+    load(path, env, &mut Vec::new())
+}
+
+/// `including` holds the files whose includes are being resolved, to detect cycles.
+fn load(path: &Path, env: &dyn Fn(&str) -> Option<String>, including: &mut Vec<PathBuf>) -> Result<Value, ConfigError> {
+    let mut value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let mut issues = Vec::new();
+    substitute(&mut value, "", env, &mut issues);
+    let includes = match value.as_object_mut().and_then(|root| root.remove("include")) {
+        None => Vec::new(),
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Array(names)) if names.iter().all(Value::is_string) => {
+            names.into_iter().filter_map(|name| name.as_str().map(str::to_string)).collect()
+        }
+        Some(_) => {
+            issues.push(ConfigIssue::InvalidInclude { at: "include".to_string() });
+            Vec::new()
+        }
+    };
+    if !issues.is_empty() {
+        return Err(ConfigError::Invalid(issues));
+    }
+    if includes.is_empty() {
+        return Ok(value);
+    }
+
+    including.push(path.canonicalize()?);
+    let mut merged = Value::Object(Map::new());
+    for name in includes {
+        let target = path.parent().unwrap_or(Path::new(".")).join(&name);
+        let included = match target.canonicalize() {
+            Ok(canonical) if including.contains(&canonical) => Err(ConfigError::IncludeCycle { path: target.display().to_string() }),
+            _ => load(&target, env, including),
+        };
+        let included = included.map_err(|e| ConfigError::Include { path: target.display().to_string(), source: Box::new(e) })?;
+        merge(&mut merged, included);
+    }
+    including.pop();
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Merge `overlay` into `base`: objects key by key, anything else replaced.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn substitute(value: &mut Value, at: &str, env: &dyn Fn(&str) -> Option<String>, issues: &mut Vec<ConfigIssue>) {
+    match value {
+        Value::String(text) if text.contains('$') => match expand(text, at, env) {
+            Ok(expanded) => {
+                // A lone variable may stand for a number or boolean
+                let whole = text.starts_with("${") && text.find('}') == Some(text.len() - 1);
+                *value = match serde_json::from_str::<Value>(&expanded) {
+                    Ok(typed @ (Value::Number(_) | Value::Bool(_))) if whole => typed,
+                    _ => Value::String(expanded),
+                };
+            }
+            Err(issue) => issues.push(issue),
+        },
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                substitute(item, &format!("{}[{}]", at, i), env, issues);
+            }
+        }
+        Value::Object(entries) => {
+            for (key, item) in entries.iter_mut() {
+                let at = if at.is_empty() { key.clone() } else { format!("{}.{}", at, key) };
+                substitute(item, &at, env, issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn expand(text: &str, at: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<String, ConfigIssue> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| ConfigIssue::MalformedVariable { at: at.to_string(), text: text.to_string() })?;
+            let (name, default) = match after[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&after[..end], None),
+            };
+            match (env(name).filter(|value| !value.is_empty()), default) {
+                (Some(value), _) => out.push_str(&value),
+                (None, Some(default)) => out.push_str(default),
+                (None, None) => return Err(ConfigIssue::UndefinedVariable { at: at.to_string(), name: name.to_string() }),
+            }
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fh_overrides_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        dir
+    }
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "HOST_IP" => Some("10.0.0.7".to_string()),
+            "SD_PORT" => Some("30491".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_variables() {
+        let text = |s: &str| expand(s, "x", &env);
+        assert_eq!(text("${HOST_IP}:${SD_PORT}").unwrap(), "10.0.0.7:30491");
+        assert_eq!(text("${MISSING:-127.0.0.1}").unwrap(), "127.0.0.1");
+        assert_eq!(text("${EMPTY:-lo}").unwrap(), "lo");
+        assert_eq!(text("cost $5, $${HOST_IP}").unwrap(), "cost $5, ${HOST_IP}");
+        assert_eq!(text("${MISSING}"), Err(ConfigIssue::UndefinedVariable { at: "x".to_string(), name: "MISSING".to_string() }));
+        assert!(matches!(text("${HOST_IP"), Err(ConfigIssue::MalformedVariable { .. })));
+
+        let mut value = serde_json::json!({ "ep": { "port": "${SD_PORT}", "ip": "${HOST_IP}", "name": "port ${SD_PORT}" }, "list": ["${NOPE}"] });
+        let mut issues = Vec::new();
+        substitute(&mut value, "", &env, &mut issues);
+        assert_eq!(value["ep"], serde_json::json!({ "port": 30491, "ip": "10.0.0.7", "name": "port 30491" }));
+        assert_eq!(issues, [ConfigIssue::UndefinedVariable { at: "list[0]".to_string(), name: "NOPE".to_string() }]);
+    }
+
+    #[test]
+    fn test_includes_merge_and_override() {
+        let dir = dir("include");
+        std::fs::write(dir.join("common/interfaces.json"), r#"{
+            "interfaces": { "lo": { "name": "lo", "endpoints": {
+                "data": { "ip": "${HOST_IP}", "port": 30500, "version": 4, "protocol": "udp" } } } }
+        }"#).unwrap();
+        std::fs::write(dir.join("common/base.json"), r#"{ "include": "interfaces.json", "qos_profiles": { "bulk": {} } }"#).unwrap();
+        std::fs::write(dir.join("ecu.json"), r#"{
+            "include": ["common/base.json"],
+            "interfaces": { "lo": { "endpoints": { "data": { "port": "${SD_PORT}" } } } },
+            "instances": { "ecu": {} }
+        }"#).unwrap();
+        let value = resolve_with(&dir.join("ecu.json"), &env).unwrap();
+        assert_eq!(value["interfaces"]["lo"]["endpoints"]["data"], serde_json::json!({ "ip": "10.0.0.7", "port": 30491, "version": 4, "protocol": "udp" }));
+        assert!(value["qos_profiles"]["bulk"].is_object());
+        assert!(value.get("include").is_none());
+
+        // Cycles and missing files name the file they come from
+        std::fs::write(dir.join("common/interfaces.json"), r#"{ "include": "base.json" }"#).unwrap();
+        let err = resolve_with(&dir.join("ecu.json"), &env).unwrap_err();
+        assert!(err.to_string().contains("includes itself"), "{}", err);
+        std::fs::write(dir.join("ecu.json"), r#"{ "include": ["missing.json"] }"#).unwrap();
+        let Err(ConfigError::Include { path, source }) = resolve_with(&dir.join("ecu.json"), &env) else { panic!("expected an include error") };
+        assert!(path.ends_with("missing.json"));
+        assert!(matches!(*source, ConfigError::Io(_)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
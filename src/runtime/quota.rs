@@ -0,0 +1,214 @@
+//! # Service Quotas
+//!
+//! Offered services configured with `quota` are kept from starving the rest of
+//! the runtime. A request beyond `max_in_flight` is answered with an ERROR
+//! carrying `E_NOT_READY`. A response or notification larger than
+//! `max_queue_bytes` is refused: requests get `E_NOT_READY` and events are
+//! dropped. `max_subscribers` is enforced by Service Discovery, which NACKs
+//! new subscribers once the limit is reached.
+//!
+//! In-flight requests include handlers that overran their execution deadline
+//! and are still running on their own thread, so a hung handler cannot pile
+//! up threads without bound.
+
+use super::config::QuotaConfig;
+use super::{HandlerResult, RequestHandler};
+use crate::codec::{ReturnCode, SomeIpHeader};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Usage and limits of one service's quota.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuotaUsage {
+    pub in_flight: u32,
+    pub max_in_flight: Option<u32>,
+    pub subscribers: usize,
+    pub max_subscribers: Option<u32>,
+    pub max_queue_bytes: Option<u64>,
+    /// Requests answered with `E_NOT_READY` because the service was at `max_in_flight`
+    pub requests_rejected: u64,
+    /// Subscriptions NACKed because the service was at `max_subscribers`
+    pub subscriptions_rejected: u64,
+    /// Responses and notifications refused for exceeding `max_queue_bytes`
+    pub messages_dropped: u64,
+}
+
+/// Limits and live counters of one offered service.
+#[derive(Debug, Default)]
+pub struct ServiceQuota {
+    limits: QuotaConfig,
+    in_flight: AtomicU32,
+    requests_rejected: AtomicU64,
+    messages_dropped: AtomicU64,
+}
+
+impl ServiceQuota {
+    pub fn new(limits: QuotaConfig) -> Self {
+        ServiceQuota { limits, ..Default::default() }
+    }
+
+    pub fn max_subscribers(&self) -> Option<u32> {
+        self.limits.max_subscribers
+    }
+
+    /// Take an in-flight slot, or count a rejection if the service is at its limit.
+    pub fn try_begin(self: &Arc<Self>) -> Option<InFlight> {
+        let max = self.limits.max_in_flight.unwrap_or(u32::MAX);
+        let taken = self.in_flight.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < max).then_some(n + 1));
+        if taken.is_err() {
+            self.requests_rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(InFlight(self.clone()))
+    }
+
+    /// Whether a message of `len` bytes may be queued for sending; counts a drop if not.
+    pub fn admit_bytes(&self, len: usize) -> bool {
+        let fits = self.limits.max_queue_bytes.is_none_or(|max| len as u64 <= max);
+        if !fits {
+            self.messages_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        fits
+    }
+
+    /// Current usage; subscriber figures come from Service Discovery.
+    pub fn usage(&self, subscribers: usize, subscriptions_rejected: u64) -> QuotaUsage {
+        QuotaUsage {
+            in_flight: self.in_flight.load(Ordering::Acquire),
+            max_in_flight: self.limits.max_in_flight,
+            subscribers,
+            max_subscribers: self.limits.max_subscribers,
+            max_queue_bytes: self.limits.max_queue_bytes,
+            requests_rejected: self.requests_rejected.load(Ordering::Relaxed),
+            subscriptions_rejected,
+            messages_dropped: self.messages_dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// An in-flight request slot, released on drop.
+pub struct InFlight(Arc<ServiceQuota>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Handler that holds an in-flight slot for as long as it is referenced. A handler
+/// running past its deadline keeps a reference on its thread until it returns.
+pub(crate) struct Guarded {
+    handler: Arc<dyn RequestHandler>,
+    _slot: InFlight,
+}
+
+impl Guarded {
+    pub(crate) fn new(handler: Arc<dyn RequestHandler>, slot: InFlight) -> Self {
+        Guarded { handler, _slot: slot }
+    }
+}
+
+impl RequestHandler for Guarded {
+    fn service_id(&self) -> u16 { self.handler.service_id() }
+    fn major_version(&self) -> u8 { self.handler.major_version() }
+    fn minor_version(&self) -> u32 { self.handler.minor_version() }
+    fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+        self.handler.handle(header, payload)
+    }
+    fn validate(&self, header: &SomeIpHeader, payload: &[u8]) -> ReturnCode {
+        self.handler.validate(header, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    #[test]
+    fn test_in_flight_slots_released_on_drop() {
+        let quota = Arc::new(ServiceQuota::new(QuotaConfig { max_in_flight: Some(2), ..Default::default() }));
+        let first = quota.try_begin().unwrap();
+        let _second = quota.try_begin().unwrap();
+        assert!(quota.try_begin().is_none());
+        assert_eq!(quota.usage(0, 0).in_flight, 2);
+
+        drop(first);
+        assert!(quota.try_begin().is_some());
+        assert_eq!(quota.usage(0, 0).requests_rejected, 1);
+    }
+
+    #[test]
+    fn test_queue_bytes_limit() {
+        let quota = ServiceQuota::new(QuotaConfig { max_queue_bytes: Some(100), ..Default::default() });
+        assert!(quota.admit_bytes(100));
+        assert!(!quota.admit_bytes(101));
+        assert_eq!(quota.usage(0, 0).messages_dropped, 1);
+        assert!(ServiceQuota::default().admit_bytes(usize::MAX));
+    }
+
+    #[test]
+    fn test_quota_limits_enforced() {
+        use crate::codec::ReturnCode;
+
+        struct Stuck;
+
+        impl RequestHandler for Stuck {
+            fn service_id(&self) -> u16 { 0x4242 }
+            fn major_version(&self) -> u8 { 1 }
+            fn minor_version(&self) -> u32 { 0 }
+            fn handle(&self, header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+                if header.method_id == 0x0001 {
+                    std::thread::sleep(std::time::Duration::from_millis(300));
+                }
+                Ok(Some(payload.to_vec()))
+            }
+        }
+
+        let runtime = runtime_with("quota", r#"{
+            "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "server_ep",
+                "providing": { "guarded": {
+                    "service_id": 16962, "instance_id": 1, "major_version": 1, "endpoint": "server_ep", "deadline_ms": 50,
+                    "quota": { "max_in_flight": 1, "max_subscribers": 4, "max_queue_bytes": 8 }
+                } }
+            } }
+        }"#);
+        runtime.offer_service("guarded", Box::new(Stuck));
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+        let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+        // Returns (message type, return code) of the answer
+        let call = |method_id: u16, session_id: u16, payload: &[u8]| {
+            let mut msg = SomeIpHeader::new(0x4242, method_id, 0, session_id, 0x00, payload.len() as u32).serialize().to_vec();
+            msg.extend_from_slice(payload);
+            socket.send_to(&msg, server).unwrap();
+            let mut buf = [0u8; 1500];
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+            assert_eq!(header.session_id, session_id);
+            (header.message_type, header.return_code)
+        };
+
+        // The overrunning handler keeps its slot until it returns
+        assert_eq!(call(0x0001, 1, &[1]), (0x81, ReturnCode::Timeout as u8));
+        assert_eq!(call(0x0002, 2, &[1]), (0x81, ReturnCode::NotReady as u8));
+        std::thread::sleep(std::time::Duration::from_millis(400));
+        assert_eq!(call(0x0002, 3, &[1, 2, 3, 4]), (0x80, 0x00));
+        assert_eq!(call(0x0002, 4, &[0; 16]), (0x81, ReturnCode::NotReady as u8));
+        runtime.stop();
+        event_loop.join().unwrap();
+
+        let usage = runtime.handle().quota_usage("guarded").unwrap();
+        assert_eq!((usage.in_flight, usage.max_in_flight, usage.max_subscribers), (0, Some(1), Some(4)));
+        assert_eq!((usage.requests_rejected, usage.messages_dropped), (1, 1));
+        assert_eq!(runtime.sd.lock().unwrap().subscriber_limits.get(&0x4242), Some(&4));
+    }
+}
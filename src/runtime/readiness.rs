@@ -0,0 +1,136 @@
+//! # Event Loop Readiness
+//!
+//! The event loop blocks until a transport has traffic, a [`Waker`] is woken
+//! (a handle queued a command, the runtime was stopped, a connection was
+//! opened) or its next timer is due, instead of polling on a fixed interval.
+//!
+//! On Linux this is `poll(2)` over the descriptors reported by
+//! [`SomeIpTransport::poll_fds`] plus an eventfd. Transports without
+//! descriptors, such as shared-memory inboxes, are polled every
+//! [`FALLBACK_TICK`]; so is everything on other platforms.
+//!
+//! The SD thread waits the same way on the SD sockets, with [`wait_fds`]
+//! since they are owned by the SD state behind its lock, until the next SD
+//! timer is due.
+
+use crate::transport::SomeIpTransport;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest wait while some transport cannot be waited on
+pub const FALLBACK_TICK: Duration = Duration::from_millis(10);
+
+/// Interrupts a [`wait`] from another thread.
+pub struct Waker {
+    #[cfg(target_os = "linux")]
+    fd: std::os::fd::OwnedFd,
+}
+
+#[cfg(target_os = "linux")]
+impl Waker {
+    pub fn new() -> std::io::Result<Self> {
+        use std::os::fd::FromRawFd;
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Waker { fd: unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) } })
+    }
+
+    /// Make the current or next `wait` return immediately.
+    pub fn wake(&self) {
+        use std::os::fd::AsRawFd;
+        let one: u64 = 1;
+        unsafe { libc::write(self.fd.as_raw_fd(), &one as *const u64 as *const libc::c_void, 8) };
+    }
+
+    fn reset(&self) {
+        use std::os::fd::AsRawFd;
+        let mut count: u64 = 0;
+        unsafe { libc::read(self.fd.as_raw_fd(), &mut count as *mut u64 as *mut libc::c_void, 8) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Waker {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Waker {})
+    }
+
+    pub fn wake(&self) {}
+}
+
+/// Block until one of `transports` may have data to receive, `waker` is woken, or
+/// `timeout` elapses. Without a waker the wait is bounded by [`FALLBACK_TICK`].
+/// Returns early on signals; the caller re-checks its state anyway.
+#[cfg(target_os = "linux")]
+pub fn wait(transports: &[Arc<dyn SomeIpTransport>], waker: Option<&Waker>, timeout: Duration) {
+    let mut timeout = timeout;
+    let mut fds = Vec::new();
+    for transport in transports {
+        match transport.poll_fds() {
+            Some(raw) => fds.extend(raw),
+            None => timeout = timeout.min(FALLBACK_TICK),
+        }
+    }
+    wait_fds(&fds, waker, timeout);
+}
+
+/// Block until one of `fds` is readable, `waker` is woken, or `timeout` elapses, for
+/// transports that cannot be borrowed while waiting, such as the SD sockets behind their lock.
+#[cfg(target_os = "linux")]
+pub fn wait_fds(fds: &[std::os::fd::RawFd], waker: Option<&Waker>, timeout: Duration) {
+    use std::os::fd::AsRawFd;
+    let mut timeout = timeout;
+    let mut fds: Vec<libc::pollfd> = fds.iter().map(|&fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 }).collect();
+    match waker {
+        Some(waker) => fds.insert(0, libc::pollfd { fd: waker.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 }),
+        None => timeout = timeout.min(FALLBACK_TICK),
+    }
+    // Round up so a timer due in under a millisecond is not spun on
+    let ms = timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int;
+    let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, ms) };
+    if let Some(waker) = waker && ready > 0 && fds[0].revents != 0 {
+        waker.reset();
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wait(_transports: &[Arc<dyn SomeIpTransport>], _waker: Option<&Waker>, timeout: Duration) {
+    std::thread::sleep(timeout.min(FALLBACK_TICK));
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::transport::UdpTransport;
+    use std::time::Instant;
+
+    #[test]
+    fn test_wait_returns_on_traffic_and_wake() {
+        let transport = Arc::new(UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap());
+        let transports: Vec<Arc<dyn SomeIpTransport>> = vec![transport.clone()];
+        let waker = Waker::new().unwrap();
+
+        let start = Instant::now();
+        wait(&transports, Some(&waker), Duration::from_millis(50));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(&[1, 2, 3], transport.local_addr().unwrap()).unwrap();
+        let start = Instant::now();
+        wait(&transports, Some(&waker), Duration::from_secs(5));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let mut buf = [0u8; 16];
+        transport.receive(&mut buf).unwrap();
+        waker.wake();
+        let start = Instant::now();
+        wait(&transports, Some(&waker), Duration::from_secs(5));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // The wake was consumed
+        let start = Instant::now();
+        wait(&transports, Some(&waker), Duration::from_millis(50));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
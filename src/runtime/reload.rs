@@ -0,0 +1,217 @@
+//! # Configuration Reload
+//!
+//! `SomeIpRuntime::reload_config` re-reads the configuration file and applies
+//! the differences for the running instance without a restart:
+//!
+//! - endpoints that are new or whose definition changed are bound, and those
+//!   no longer used are released; unchanged endpoints keep their sockets
+//! - provided services that were removed are withdrawn, and those whose
+//!   definition or endpoint changed are re-announced with their handler
+//! - subscriptions of removed required services are ended, and added ones
+//!   start discovery (and their automatic subscriptions)
+//!
+//! Settings derived once at load (interfaces and SD listeners, quotas, access
+//! policies, E2E, reordering, store-and-forward, request retries, worker threads,
+//! shared memory, metrics) keep their loaded values until the runtime is restarted.
+//!
+//! On Linux, `reload_on_sighup` makes the event loop reload whenever the
+//! process receives SIGHUP.
+
+use serde::Serialize;
+
+/// What a reload changed. Names are endpoint names and service aliases.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "serde-types", derive(serde::Deserialize))]
+pub struct ReloadSummary {
+    /// Endpoints bound anew
+    pub endpoints_bound: Vec<String>,
+    /// Endpoints no longer bound, or bound anew because their definition changed
+    pub endpoints_released: Vec<String>,
+    /// Provided services withdrawn because they were removed
+    pub services_withdrawn: Vec<String>,
+    /// Provided services withdrawn and offered again with their changed definition
+    pub services_reoffered: Vec<String>,
+    /// Provided services new in the configuration, announced once `offer_service` is called
+    pub services_added: Vec<String>,
+    /// Required services new in the configuration
+    pub required_added: Vec<String>,
+    /// Required services removed from the configuration; their subscriptions were ended
+    pub required_removed: Vec<String>,
+    /// Required services whose definition changed; their automatic subscriptions were renewed
+    pub required_changed: Vec<String>,
+}
+
+impl ReloadSummary {
+    pub fn is_empty(&self) -> bool {
+        *self == ReloadSummary::default()
+    }
+
+    /// One-line digest for the log.
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = [
+            ("bound", &self.endpoints_bound),
+            ("released", &self.endpoints_released),
+            ("withdrew", &self.services_withdrawn),
+            ("re-offered", &self.services_reoffered),
+            ("new services", &self.services_added),
+            ("now requires", &self.required_added),
+            ("no longer requires", &self.required_removed),
+            ("updated requirements", &self.required_changed),
+        ].iter()
+            .filter(|(_, names)| !names.is_empty())
+            .map(|(what, names)| format!("{} {}", what, names.join(", ")))
+            .collect();
+        if parts.is_empty() { "no changes".to_string() } else { parts.join("; ") }
+    }
+}
+
+/// Aliases in `new` but not `old`, in `old` but not `new`, and in both with different values; each sorted.
+pub(crate) fn diff<V: PartialEq>(old: &std::collections::HashMap<String, V>, new: &std::collections::HashMap<String, V>) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut added: Vec<String> = new.keys().filter(|k| !old.contains_key(*k)).cloned().collect();
+    let mut removed: Vec<String> = old.keys().filter(|k| !new.contains_key(*k)).cloned().collect();
+    let mut changed: Vec<String> = new.iter().filter(|(k, v)| old.get(*k).is_some_and(|o| o != *v)).map(|(k, _)| k.clone()).collect();
+    added.sort();
+    removed.sort();
+    changed.sort();
+    (added, removed, changed)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) mod sighup {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Once;
+
+    /// SIGHUPs received by the process
+    static RECEIVED: AtomicU64 = AtomicU64::new(0);
+    static INSTALL: Once = Once::new();
+
+    extern "C" fn on_sighup(_: libc::c_int) {
+        RECEIVED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Count SIGHUPs instead of terminating on them. Installed once per process.
+    pub fn install() -> std::io::Result<()> {
+        let mut result = Ok(());
+        INSTALL.call_once(|| {
+            // SAFETY: the handler only touches an atomic, which is async-signal-safe
+            let previous = unsafe { libc::signal(libc::SIGHUP, on_sighup as *const () as libc::sighandler_t) };
+            if previous == libc::SIG_ERR {
+                result = Err(std::io::Error::last_os_error());
+            }
+        });
+        result
+    }
+
+    pub fn received() -> u64 {
+        RECEIVED.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::codec::SomeIpHeader;
+    use crate::runtime::tests::runtime_with;
+    use crate::runtime::RuntimeError;
+
+    #[test]
+    fn test_diff() {
+        let old: HashMap<String, u8> = [("a".to_string(), 1), ("b".to_string(), 2), ("c".to_string(), 3)].into();
+        let new: HashMap<String, u8> = [("b".to_string(), 2), ("c".to_string(), 4), ("d".to_string(), 5)].into();
+        assert_eq!(diff(&old, &new), (vec!["d".to_string()], vec!["a".to_string()], vec!["c".to_string()]));
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(ReloadSummary::default().summary(), "no changes");
+        let summary = ReloadSummary {
+            endpoints_bound: vec!["data".to_string()],
+            services_reoffered: vec!["math".to_string(), "echo".to_string()],
+            ..ReloadSummary::default()
+        };
+        assert!(!summary.is_empty());
+        assert_eq!(summary.summary(), "bound data; re-offered math, echo");
+    }
+
+    #[test]
+    fn test_reload_config_while_running() {
+        use crate::codec::ReturnCode;
+        use crate::runtime::echo::{EchoService, METHOD_ECHO};
+        use crate::runtime::ConfigError;
+
+        let config = |echo_minor: u32, extra: &str| format!(r#"{{
+            "endpoints": {{
+                "server_ep": {{ "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" }},
+                "client_ep": {{ "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" }}
+            }},
+            "instances": {{ "test": {{
+                "endpoint": "server_ep",
+                "providing": {{ "echo": {{ "service_id": 16968, "instance_id": 1, "major_version": 1, "minor_version": {}, "endpoint": "server_ep" }} }}
+                {}
+            }} }}
+        }}"#, echo_minor, extra);
+        let path = std::env::temp_dir().join(format!("fh_runtime_reload_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let runtime = runtime_with("reload", &config(0, ""));
+        let handle = runtime.handle();
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+        let port = runtime.bound_port("server_ep").unwrap();
+        let call = |session_id: u16| {
+            let mut msg = SomeIpHeader::new(0x4248, METHOD_ECHO, 1, session_id, 0x00, 1).serialize().to_vec();
+            msg.push(7);
+            socket.send_to(&msg, ("127.0.0.1", port)).unwrap();
+            let mut buf = [0u8; 64];
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            SomeIpHeader::deserialize(&buf[..len]).unwrap().return_code
+        };
+        let settle = || std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.offer_service("echo", Box::new(EchoService::new(0x4248, 1, 0)));
+        settle();
+        assert_eq!(call(1), ReturnCode::Ok as u8);
+
+        // Unchanged file: nothing to do
+        std::fs::write(path, config(0, "")).unwrap();
+        assert!(handle.reload_config(path).unwrap().is_empty());
+
+        // A changed offer is re-announced on the socket it kept, a new requirement binds its endpoint
+        std::fs::write(path, config(1, r#", "required": { "math": { "service_id": 4097, "instance_id": 1, "major_version": 1, "local_endpoint": "client_ep" } }"#)).unwrap();
+        let summary = handle.reload_config(path).unwrap();
+        assert_eq!(summary.endpoints_bound, ["client_ep"]);
+        assert_eq!(summary.services_reoffered, ["echo"]);
+        assert_eq!(summary.required_added, ["math"]);
+        assert_eq!(runtime.bound_port("server_ep"), Some(port));
+        assert!(runtime.bound_port("client_ep").is_some());
+        assert_eq!(runtime.instance_config().unwrap().providing["echo"].minor_version, 1);
+        settle();
+        assert_eq!(call(2), ReturnCode::Ok as u8);
+
+        // Removing them withdraws the offer and releases the endpoint
+        std::fs::write(path, r#"{
+            "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": { "endpoint": "server_ep" } }
+        }"#).unwrap();
+        let summary = handle.reload_config(path).unwrap();
+        assert_eq!(summary.services_withdrawn, ["echo"]);
+        assert_eq!(summary.endpoints_released, ["client_ep"]);
+        assert_eq!(summary.required_removed, ["math"]);
+        assert_eq!(runtime.bound_port("client_ep"), None);
+        settle();
+        assert_eq!(call(3), ReturnCode::UnknownService as u8);
+
+        // A broken file changes nothing
+        std::fs::write(path, r#"{ "instances": { "other": {} } }"#).unwrap();
+        assert!(matches!(handle.reload_config(path), Err(RuntimeError::Config(ConfigError::UnknownInstance { .. }))));
+        assert_eq!(runtime.bound_port("server_ep"), Some(port));
+        let _ = std::fs::remove_file(path);
+
+        runtime.stop();
+        event_loop.join().unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+//! # Event Reordering
+//!
+//! Notifications of one event carry consecutive session IDs (1..=0xFFFF,
+//! wrapping to 1). When they arrive out of order, e.g. over multiple paths,
+//! a [`ReorderBuffer`] holds early arrivals until the gap is filled, so the
+//! handler sees them in sequence.
+//!
+//! A gap is given up when it is older than `max_hold`, or when an event
+//! arrives more than `window` sessions ahead; held events are then released
+//! in order. Events for sessions that were already delivered are discarded
+//! as duplicates, events for sessions that were skipped as late arrivals.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Counters of one or more reorder buffers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReorderStats {
+    pub delivered: u64,
+    /// Events that were held back and delivered after an earlier one arrived
+    pub reordered: u64,
+    pub duplicates: u64,
+    /// Events whose session had already been skipped
+    pub late: u64,
+    /// Sessions given up on because they never arrived in time
+    pub skipped: u64,
+}
+
+impl ReorderStats {
+    pub fn merge(&mut self, other: &ReorderStats) {
+        self.delivered += other.delivered;
+        self.reordered += other.reordered;
+        self.duplicates += other.duplicates;
+        self.late += other.late;
+        self.skipped += other.skipped;
+    }
+}
+
+/// Next session ID, skipping 0.
+fn next_session(session: u16) -> u16 {
+    if session == 0xFFFF { 1 } else { session + 1 }
+}
+
+/// Signed distance from `from` to `to` in the 1..=0xFFFF session space.
+fn distance(from: u16, to: u16) -> i32 {
+    const SPAN: i32 = 0xFFFF;
+    let d = (to as i32 - from as i32).rem_euclid(SPAN);
+    if d > SPAN / 2 { d - SPAN } else { d }
+}
+
+/// Orders the notifications of one event by session ID.
+pub struct ReorderBuffer<T> {
+    window: u16,
+    max_hold: Duration,
+    next_expected: Option<u16>,
+    /// Held events keyed by distance from `next_expected`
+    pending: BTreeMap<u16, (u16, T, Instant)>,
+    /// Recently delivered sessions, to tell duplicates from late arrivals
+    delivered: VecDeque<u16>,
+    stats: ReorderStats,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(window: u16, max_hold: Duration) -> Self {
+        ReorderBuffer {
+            window: window.max(1),
+            max_hold,
+            next_expected: None,
+            pending: BTreeMap::new(),
+            delivered: VecDeque::new(),
+            stats: ReorderStats::default(),
+        }
+    }
+
+    /// Accept an event, returning the events that are now ready, in order.
+    pub fn push(&mut self, session: u16, item: T, now: Instant) -> Vec<T> {
+        let mut ready = Vec::new();
+        let Some(expected) = self.next_expected else {
+            self.deliver(session, item, false, &mut ready);
+            return ready;
+        };
+
+        let d = distance(expected, session);
+        if d < 0 {
+            if self.delivered.contains(&session) {
+                self.stats.duplicates += 1;
+            } else {
+                self.stats.late += 1;
+            }
+        } else if d == 0 {
+            self.deliver(session, item, false, &mut ready);
+            self.drain_consecutive(&mut ready);
+        } else if d <= self.window as i32 {
+            if self.pending.values().any(|(s, _, _)| *s == session) {
+                self.stats.duplicates += 1;
+            } else {
+                self.pending.insert(d as u16, (session, item, now));
+            }
+        } else {
+            // Too far ahead: give up on the gap and resynchronise on this event
+            self.release_all(&mut ready);
+            self.stats.skipped += distance(self.next_expected.unwrap_or(session), session).max(0) as u64;
+            self.deliver(session, item, false, &mut ready);
+        }
+        ready
+    }
+
+    /// Release held events whose gap has been open longer than `max_hold`.
+    pub fn poll(&mut self, now: Instant) -> Vec<T> {
+        let mut ready = Vec::new();
+        while self.pending.values().map(|(_, _, since)| *since).min()
+            .is_some_and(|since| now.duration_since(since) >= self.max_hold)
+        {
+            let Some((&offset, _)) = self.pending.iter().next() else { break };
+            self.stats.skipped += offset as u64;
+            let (session, item, _) = self.pending.remove(&offset).unwrap();
+            self.rebase(distance(self.next_expected.unwrap_or(session), session));
+            self.deliver(session, item, true, &mut ready);
+            self.drain_consecutive(&mut ready);
+        }
+        ready
+    }
+
+    pub fn held(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn stats(&self) -> ReorderStats {
+        self.stats
+    }
+
+    fn deliver(&mut self, session: u16, item: T, reordered: bool, ready: &mut Vec<T>) {
+        ready.push(item);
+        self.stats.delivered += 1;
+        if reordered {
+            self.stats.reordered += 1;
+        }
+        self.delivered.push_back(session);
+        if self.delivered.len() > self.window as usize {
+            self.delivered.pop_front();
+        }
+        self.next_expected = Some(next_session(session));
+    }
+
+    /// Deliver held events that directly follow the last delivered one.
+    fn drain_consecutive(&mut self, ready: &mut Vec<T>) {
+        self.rebase(1);
+        while let Some((session, item, _)) = self.pending.remove(&0) {
+            self.deliver(session, item, true, ready);
+            self.rebase(1);
+        }
+    }
+
+    /// Release every held event in order, counting the gaps between them as skipped.
+    fn release_all(&mut self, ready: &mut Vec<T>) {
+        let mut last = 0;
+        for (offset, (session, item, _)) in std::mem::take(&mut self.pending) {
+            self.stats.skipped += (offset - last) as u64;
+            last = offset + 1;
+            self.deliver(session, item, true, ready);
+        }
+    }
+
+    /// Shift pending offsets after `next_expected` advanced by `by` sessions.
+    fn rebase(&mut self, by: i32) {
+        if by <= 0 || self.pending.is_empty() {
+            return;
+        }
+        self.pending = std::mem::take(&mut self.pending).into_iter()
+            .map(|(offset, v)| ((offset as i32 - by) as u16, v))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    fn buffer() -> ReorderBuffer<u16> {
+        ReorderBuffer::new(8, Duration::from_millis(50))
+    }
+
+    #[test]
+    fn test_in_order_passthrough() {
+        let now = Instant::now();
+        let mut buf = buffer();
+        for s in 1..=3 {
+            assert_eq!(buf.push(s, s, now), vec![s]);
+        }
+        assert_eq!(buf.stats().delivered, 3);
+    }
+
+    #[test]
+    fn test_out_of_order_delivered_in_sequence() {
+        let now = Instant::now();
+        let mut buf = buffer();
+        assert_eq!(buf.push(1, 1, now), vec![1]);
+        assert!(buf.push(3, 3, now).is_empty());
+        assert!(buf.push(4, 4, now).is_empty());
+        assert_eq!(buf.push(2, 2, now), vec![2, 3, 4]);
+        assert_eq!(buf.held(), 0);
+        assert_eq!(buf.stats().reordered, 2);
+    }
+
+    #[test]
+    fn test_duplicates_and_late_arrivals_discarded() {
+        let now = Instant::now();
+        let mut buf = buffer();
+        buf.push(1, 1, now);
+        assert!(buf.push(1, 1, now).is_empty());
+        buf.push(3, 3, now);
+        assert!(buf.push(3, 3, now).is_empty());
+        assert_eq!(buf.stats().duplicates, 2);
+
+        // Session 2 never comes in time: 3 is released, then 2 is late
+        assert_eq!(buf.poll(now + Duration::from_millis(60)), vec![3]);
+        assert_eq!(buf.stats().skipped, 1);
+        assert!(buf.push(2, 2, now).is_empty());
+        assert_eq!(buf.stats().late, 1);
+    }
+
+    #[test]
+    fn test_jump_beyond_window_resyncs() {
+        let now = Instant::now();
+        let mut buf = buffer();
+        buf.push(1, 1, now);
+        buf.push(3, 3, now);
+        assert_eq!(buf.push(20, 20, now), vec![3, 20]);
+        assert_eq!(buf.push(21, 21, now), vec![21]);
+        // 2, and 4..=19
+        assert_eq!(buf.stats().skipped, 17);
+    }
+
+    #[test]
+    fn test_session_wraparound() {
+        let now = Instant::now();
+        let mut buf = buffer();
+        buf.push(0xFFFE, 0xFFFE, now);
+        assert!(buf.push(1, 1, now).is_empty());
+        assert_eq!(buf.push(0xFFFF, 0xFFFF, now), vec![0xFFFF, 1]);
+    }
+
+    #[test]
+    fn test_reorder_enabled_per_required_service() {
+        let runtime = runtime_with("reorder", r#"{ "instances": { "test": { "required": {
+            "ordered": { "service_id": 4660, "instance_id": 1, "major_version": 1, "reorder": { "window": 4 } },
+            "plain": { "service_id": 4661, "instance_id": 1, "major_version": 1 }
+        } } } }"#);
+        assert_eq!(runtime.reorder_configs.get(&0x1234), Some(&(4, std::time::Duration::from_millis(50))));
+        let handle = runtime.handle();
+        assert_eq!(handle.reorder_stats("ordered"), Some(ReorderStats::default()));
+        assert!(handle.reorder_stats("plain").is_none());
+    }
+}
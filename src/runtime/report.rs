@@ -0,0 +1,205 @@
+//! # Shutdown Report
+//!
+//! Counters the runtime keeps over its lifetime, and the digest produced from
+//! them when it stops: traffic handled, errors, and the work that was still in
+//! flight (pending requests, queued requests, subscribers left without events).
+
+use super::stats::ServiceTraffic;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct TrafficCounters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    handled: AtomicU64,
+    handler_time_us: AtomicU64,
+}
+
+/// Lifetime counters updated from the event loop and request paths.
+#[derive(Debug, Default)]
+pub(crate) struct RuntimeCounters {
+    pub requests_served: AtomicU64,
+    pub errors: AtomicU64,
+    pub tp_reassemblies: AtomicU64,
+    /// Requests whose response did not arrive in time
+    pub request_timeouts: AtomicU64,
+    /// Received messages discarded or refused instead of being handled
+    pub packets_dropped: AtomicU64,
+    /// Outgoing requests refused because their service had `max_in_flight` outstanding
+    pub requests_rejected: AtomicU64,
+    /// Messages sent and received, by service ID
+    traffic: RwLock<HashMap<u16, Arc<TrafficCounters>>>,
+}
+
+impl RuntimeCounters {
+    pub fn record_request(&self) {
+        self.requests_served.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reassembly(&self) {
+        self.tp_reassemblies.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_timeout(&self) {
+        self.request_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_drop(&self) {
+        self.packets_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rejected(&self) {
+        self.requests_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self, service_id: u16) {
+        self.service(service_id).sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, service_id: u16) {
+        self.service(service_id).received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A request or fire-and-forget call to `service_id` whose handler ran for `elapsed`.
+    pub fn record_handled(&self, service_id: u16, elapsed: Duration) {
+        let counters = self.service(service_id);
+        counters.handled.fetch_add(1, Ordering::Relaxed);
+        counters.handler_time_us.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn service(&self, service_id: u16) -> Arc<TrafficCounters> {
+        if let Some(counters) = self.traffic.read().unwrap_or_else(PoisonError::into_inner).get(&service_id) {
+            return counters.clone();
+        }
+        self.traffic.write().unwrap_or_else(PoisonError::into_inner).entry(service_id).or_default().clone()
+    }
+
+    /// Messages sent and received so far, by service ID.
+    pub fn traffic(&self) -> BTreeMap<u16, ServiceTraffic> {
+        self.traffic.read().unwrap_or_else(PoisonError::into_inner).iter()
+            .map(|(id, c)| (*id, ServiceTraffic {
+                sent: c.sent.load(Ordering::Relaxed),
+                received: c.received.load(Ordering::Relaxed),
+                requests_handled: c.handled.load(Ordering::Relaxed),
+                handler_time_us: c.handler_time_us.load(Ordering::Relaxed),
+            }))
+            .collect()
+    }
+}
+
+/// Digest of a runtime's lifetime, emitted on `stop()` or drop.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "serde-types", derive(serde::Deserialize))]
+pub struct ShutdownReport {
+    pub instance: String,
+    /// When the report was taken, in microseconds on the shared [`Timestamp`] scale
+    ///
+    /// [`Timestamp`]: crate::clock::Timestamp
+    pub taken_at_us: u64,
+    pub uptime_ms: u64,
+    /// Requests and fire-and-forget calls handled by local services
+    pub requests_served: u64,
+    /// Failed sends, TP reassembly failures and handler deadline overruns
+    pub errors: u64,
+    pub tp_reassemblies: u64,
+    pub sd_offers_sent: u64,
+    /// Outgoing requests still waiting for a response
+    pub pending_requests_dropped: usize,
+    /// Requests still held in store-and-forward queues
+    pub queued_requests_dropped: usize,
+    /// Remote subscribers to our eventgroups that will receive no further events
+    pub subscribers_abandoned: usize,
+}
+
+impl ShutdownReport {
+    /// Whether the runtime stopped with nothing left in flight.
+    pub fn is_clean(&self) -> bool {
+        self.pending_requests_dropped == 0 && self.queued_requests_dropped == 0 && self.subscribers_abandoned == 0
+    }
+
+    /// One-line summary for the log.
+    pub fn summary(&self) -> String {
+        format!(
+            "Shutdown report for '{}': uptime {} ms, {} requests served, {} errors, {} TP reassemblies, {} SD offers sent; dropped {} pending and {} queued requests, abandoned {} subscribers",
+            self.instance, self.uptime_ms, self.requests_served, self.errors, self.tp_reassemblies, self.sd_offers_sent,
+            self.pending_requests_dropped, self.queued_requests_dropped, self.subscribers_abandoned,
+        )
+    }
+
+    /// Write the report as pretty-printed JSON.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    #[test]
+    fn test_clean_and_unclean_reports() {
+        let mut report = ShutdownReport { instance: "node".into(), requests_served: 3, ..Default::default() };
+        assert!(report.is_clean());
+        report.subscribers_abandoned = 2;
+        assert!(!report.is_clean());
+        assert!(report.summary().contains("3 requests served"));
+        assert!(report.summary().contains("abandoned 2 subscribers"));
+    }
+
+    #[test]
+    fn test_report_written_as_json() {
+        let counters = RuntimeCounters::default();
+        counters.record_request();
+        counters.record_error();
+        let report = ShutdownReport {
+            instance: "node".into(),
+            requests_served: counters.requests_served.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+
+        let path = std::env::temp_dir().join(format!("fh_report_{}.json", std::process::id()));
+        report.write_json(&path).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(json["instance"], "node");
+        assert_eq!((json["requests_served"].as_u64(), json["errors"].as_u64()), (Some(1), Some(1)));
+        assert_eq!(json["pending_requests_dropped"], 0);
+    }
+
+    #[test]
+    fn test_shutdown_report_written_once() {
+        let path = std::env::temp_dir().join(format!("fh_shutdown_{}.json", std::process::id()));
+        let runtime = runtime_with("shutdown", &format!(r#"{{ "instances": {{ "test": {{ "shutdown_report_path": "{}" }} }} }}"#, path.display()));
+        runtime.counters.record_request();
+        runtime.sd.lock().unwrap().subscriptions.entry((0x1234, 1)).or_default()
+            .push(crate::sd::machine::EventSubscriber { endpoint: "127.0.0.1:40000".parse().unwrap(), transport_proto: 0x11 });
+        let handle = runtime.handle();
+        assert_eq!(handle.shutdown_report().subscribers_abandoned, 1);
+
+        handle.stop();
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(json["instance"], "test");
+        assert_eq!(json["requests_served"], 1);
+        assert_eq!(json["subscribers_abandoned"], 1);
+
+        // Later stops and the drop do not report again
+        handle.stop();
+        drop(handle);
+        drop(runtime);
+        assert!(!path.exists());
+    }
+}
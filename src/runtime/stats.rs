@@ -0,0 +1,65 @@
+//! # Runtime Statistics
+//!
+//! [`RuntimeStats`] is a snapshot of the counters a runtime keeps while it runs,
+//! together with the current depth of its queues. Take one with
+//! `SomeIpRuntime::stats()` as often as a dashboard needs; counters only grow,
+//! so rates are differences between two snapshots.
+
+use crate::buffer::BufferPoolStats;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Messages sent and received for one service. TP-segmented messages count once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "serde-types", derive(serde::Deserialize))]
+pub struct ServiceTraffic {
+    /// Requests, responses, errors and notifications handed to a transport
+    pub sent: u64,
+    /// Requests, responses, errors and notifications received, after reassembly
+    pub received: u64,
+    /// Requests and fire-and-forget calls handled by the local service
+    pub requests_handled: u64,
+    /// Time spent in the handler for those, in microseconds
+    pub handler_time_us: u64,
+}
+
+/// Counters and queue depths of a runtime at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "serde-types", derive(serde::Deserialize))]
+pub struct RuntimeStats {
+    /// When the snapshot was taken, in microseconds on the shared [`Timestamp`] scale
+    ///
+    /// [`Timestamp`]: crate::clock::Timestamp
+    pub taken_at_us: u64,
+    pub uptime_ms: u64,
+    /// Traffic by service ID
+    pub services: BTreeMap<u16, ServiceTraffic>,
+    /// Requests and fire-and-forget calls handled by local services
+    pub requests_served: u64,
+    /// Failed sends, TP reassembly failures and handler deadline overruns
+    pub errors: u64,
+    /// Outgoing requests whose response did not arrive in time
+    pub request_timeouts: u64,
+    /// Outgoing requests refused because their service had `max_in_flight` outstanding
+    pub requests_rejected: u64,
+    pub tp_reassemblies: u64,
+    /// Received messages discarded, or refused with an ERROR, instead of being handled
+    pub packets_dropped: u64,
+    pub sd_offers_sent: u64,
+    /// OfferService entries received, including repetitions of known offers
+    pub sd_offers_received: u64,
+    /// Outgoing requests waiting for a response
+    pub pending_requests: usize,
+    /// Requests held in store-and-forward queues
+    pub queued_requests: usize,
+    /// TP messages with segments buffered
+    pub tp_in_progress: usize,
+    /// SD packets waiting for a later poll
+    pub sd_backlog: usize,
+    /// Remote service instances currently offered to us
+    pub sd_remote_services: usize,
+    /// Remote subscribers across our eventgroups
+    pub sd_subscribers: usize,
+    /// Message buffers of the process-wide pool
+    pub buffers: BufferPoolStats,
+}
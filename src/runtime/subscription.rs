@@ -0,0 +1,284 @@
+//! # Subscription Results
+//!
+//! `subscribe` sends a SubscribeEventgroup and waits for the provider's answer,
+//! resolving to a [`SubscriptionResult`]: acknowledged, not acknowledged (with
+//! a [`NackReason`]), or no answer within the acknowledgement timeout.
+//!
+//! Required services configured with `subscription_retry` retry automatically
+//! after a timeout and, unless `retry_on_nack` is false, after a NACK. Without
+//! it, a single attempt waits `sd.request_timeout_ms`.
+
+use super::config::SubscriptionRetryConfig;
+pub use crate::sd::machine::{NackReason, SubscriptionAdded, SubscriptionNack, SubscriptionState};
+use std::time::Duration;
+
+/// Outcome of a `subscribe` call, after any retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubscriptionResult {
+    Acked,
+    Nacked(NackReason),
+    /// No answer within the acknowledgement timeout
+    Timeout,
+}
+
+/// Acknowledgement timeout and retry settings of one required service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub ack_timeout: Duration,
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+    pub retry_on_nack: bool,
+}
+
+impl RetryPolicy {
+    pub fn from_config(cfg: &SubscriptionRetryConfig) -> Self {
+        RetryPolicy {
+            ack_timeout: Duration::from_millis(cfg.ack_timeout_ms),
+            max_retries: cfg.max_retries,
+            retry_delay: Duration::from_millis(cfg.retry_delay_ms),
+            retry_on_nack: cfg.retry_on_nack,
+        }
+    }
+
+    /// A single attempt waiting at most `ack_timeout`.
+    pub fn once(ack_timeout: Duration) -> Self {
+        RetryPolicy { ack_timeout, max_retries: 0, retry_delay: Duration::ZERO, retry_on_nack: false }
+    }
+
+    /// Whether to try again after `result` of attempt number `attempt` (0 for the first).
+    pub fn should_retry(&self, result: SubscriptionResult, attempt: u32) -> bool {
+        let retryable = match result {
+            SubscriptionResult::Acked => false,
+            SubscriptionResult::Timeout => true,
+            SubscriptionResult::Nacked(_) => self.retry_on_nack,
+        };
+        retryable && attempt < self.max_retries
+    }
+}
+
+/// An eventgroup subscription this instance made, kept to renew it after a provider reboot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OwnSubscription {
+    pub instance_id: u16,
+    pub ttl: u32,
+    pub iface_alias: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+    use crate::runtime::tests::{empty_runtime, runtime_with};
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_retry_decisions() {
+        let cfg: SubscriptionRetryConfig = serde_json::from_str(r#"{ "max_retries": 2, "retry_on_nack": false }"#).unwrap();
+        let policy = RetryPolicy::from_config(&cfg);
+        assert_eq!(policy.ack_timeout, Duration::from_millis(2000));
+        assert_eq!(policy.retry_delay, Duration::from_millis(500));
+
+        assert!(policy.should_retry(SubscriptionResult::Timeout, 0));
+        assert!(policy.should_retry(SubscriptionResult::Timeout, 1));
+        assert!(!policy.should_retry(SubscriptionResult::Timeout, 2));
+        assert!(!policy.should_retry(SubscriptionResult::Nacked(NackReason::Rejected), 0));
+        assert!(!policy.should_retry(SubscriptionResult::Acked, 0));
+
+        let once = RetryPolicy::once(Duration::from_secs(1));
+        assert!(!once.should_retry(SubscriptionResult::Timeout, 0));
+    }
+
+    #[test]
+    fn test_resubscribes_after_provider_reboot() {
+        use crate::sd::machine::{NackReason, PeerReboot, RemoteService, SubscriptionState};
+        use crate::sd::options::SdOption;
+
+        let runtime = empty_runtime("peer_reboot");
+        let provider: SocketAddr = "10.0.0.2:30490".parse().unwrap();
+        let offer = || RemoteService {
+            service_id: 0x1234,
+            instance_id: 1,
+            version_major: 1,
+            version_minor: 0,
+            endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::new(10, 0, 0, 2), port: 30501, transport_proto: 0x11 }],
+            last_seen: std::time::Instant::now(),
+            ttl: 0xFFFFFF,
+        };
+        runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), offer());
+        runtime.subscribe_eventgroup(0x1234, 1, 5, 3, "primary");
+        runtime.sd.lock().unwrap().pending_subscriptions.insert((0x1234, 5), SubscriptionState::Acked);
+
+        // SD removed the offer on detecting the reboot
+        {
+            let mut sd = runtime.sd.lock().unwrap();
+            sd.remote_services.clear();
+            sd.pending_subscriptions.insert((0x1234, 5), SubscriptionState::Nacked(NackReason::ServiceStopped));
+            sd.peer_reboots.push(PeerReboot { address: provider, services: vec![(0x1234, 1)] });
+        }
+        runtime.recover_from_peer_reboots();
+        runtime.subscribe_when_offered();
+        assert!(runtime.awaiting_offer.lock().unwrap().contains(&(0x1234, 5)));
+        assert_eq!(runtime.idle_timeout(false), crate::runtime::readiness::FALLBACK_TICK);
+
+        runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), offer());
+        runtime.subscribe_when_offered();
+        assert!(runtime.awaiting_offer.lock().unwrap().is_empty());
+        assert_eq!(runtime.sd.lock().unwrap().subscription_state(0x1234, 5), Some(SubscriptionState::Pending));
+    }
+
+    #[test]
+    fn test_configured_eventgroups_subscribed_once_offered() {
+        use crate::sd::machine::{RemoteService, SubscriptionState};
+        use crate::sd::options::SdOption;
+
+        let runtime = runtime_with("auto_subscribe", r#"{ "instances": { "test": { "required": {
+            "sensors": { "service_id": 4660, "instance_id": 1, "major_version": 1, "preferred_interface": "primary",
+                         "eventgroups": { "status": { "eventgroup_id": 1, "subscribe": true, "ttl": 3 },
+                                          "telemetry": { "eventgroup_id": 2 } } }
+        } } } }"#);
+        assert!(runtime.sd.lock().unwrap().pending_finds.contains_key(&(0x1234, 1)));
+        let sub = runtime.own_subscriptions.lock().unwrap()[&(0x1234, 1)].clone();
+        assert_eq!((sub.instance_id, sub.ttl, sub.iface_alias.as_str()), (1, 3, "primary"));
+
+        // Nothing is sent before the service is discovered
+        runtime.subscribe_when_offered();
+        assert_eq!(runtime.sd.lock().unwrap().subscription_state(0x1234, 1), None);
+
+        runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), RemoteService {
+            service_id: 0x1234,
+            instance_id: 1,
+            version_major: 1,
+            version_minor: 0,
+            endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: 30501, transport_proto: 0x11 }],
+            last_seen: std::time::Instant::now(),
+            ttl: 0xFFFFFF,
+        });
+        runtime.subscribe_when_offered();
+        assert_eq!(runtime.sd.lock().unwrap().subscription_state(0x1234, 1), Some(SubscriptionState::Pending));
+        assert_eq!(runtime.sd.lock().unwrap().subscription_state(0x1234, 2), None);
+        assert!(runtime.awaiting_offer.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_subscribe_required_resolves_config() {
+        let runtime = runtime_with("subscribe_required", r#"{ "instances": { "test": { "required": {
+            "sensors": { "service_id": 4660, "instance_id": 3, "major_version": 1, "preferred_interface": "primary",
+                         "eventgroups": { "telemetry": { "eventgroup_id": 2, "interface": "secondary" } } }
+        } } } }"#);
+        runtime.subscribe_required("sensors", 1, 5).unwrap();
+        runtime.subscribe_required("sensors", 2, 7).unwrap();
+        {
+            let own = runtime.own_subscriptions.lock().unwrap();
+            let (status, telemetry) = (&own[&(0x1234, 1)], &own[&(0x1234, 2)]);
+            assert_eq!((status.instance_id, status.ttl, status.iface_alias.as_str()), (3, 5, "primary"));
+            assert_eq!((telemetry.instance_id, telemetry.ttl, telemetry.iface_alias.as_str()), (3, 7, "secondary"));
+        }
+
+        // A TTL of 0 unsubscribes; unknown aliases are refused
+        runtime.subscribe_required("sensors", 2, 0).unwrap();
+        assert!(!runtime.own_subscriptions.lock().unwrap().contains_key(&(0x1234, 2)));
+        let err = runtime.subscribe_required("radar", 1, 5).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_subscription_nacks_reported() {
+        use crate::runtime::subscription::SubscriptionNack;
+
+        let runtime = runtime_with("nacks", r#"{ "instances": { "test": { "providing": {
+            "radar": { "service_id": 28673, "instance_id": 1, "major_version": 1,
+                       "eventgroups": { "objects": { "eventgroup_id": 1, "events": [32769] } } },
+            "open": { "service_id": 28674, "instance_id": 1, "major_version": 1 }
+        } } } }"#);
+        {
+            let sd = runtime.sd.lock().unwrap();
+            assert_eq!(sd.offered_eventgroups[&0x7001], std::collections::HashSet::from([1]));
+            assert!(!sd.offered_eventgroups.contains_key(&0x7002));
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        runtime.handle().on_subscription_nack(Box::new(move |nack| sink.lock().unwrap().push(nack.clone())));
+        let nack = SubscriptionNack { service_id: 0x1234, instance_id: 1, eventgroup_id: 6, provider: "10.0.0.2:30490".parse().unwrap() };
+        runtime.sd.lock().unwrap().subscription_nacks.push(nack.clone());
+        runtime.report_subscription_nacks();
+        runtime.report_subscription_nacks();
+        assert_eq!(*seen.lock().unwrap(), vec![nack]);
+    }
+
+    #[test]
+    fn test_subscription_added_callback_sends_initial_events() {
+        use crate::sd::machine::EventSubscriber;
+
+        let runtime = runtime_with("subscription_added", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "ep",
+                "providing": { "radar": { "service_id": 28673, "instance_id": 1, "major_version": 1 } }
+            } }
+        }"#);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        runtime.handle().on_subscription_added(Box::new(move |added| {
+            sink.lock().unwrap().push(added.clone());
+            vec![(0x8001, vec![7])]
+        }));
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        let subscriber = EventSubscriber { endpoint: socket.local_addr().unwrap(), transport_proto: 0x11 };
+        let other = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        other.set_nonblocking(true).unwrap();
+        let added = SubscriptionAdded { service_id: 0x7001, instance_id: 1, eventgroup_id: 1, subscriber };
+        {
+            let mut sd = runtime.sd.lock().unwrap();
+            sd.subscriptions.insert((0x7001, 1), vec![EventSubscriber { endpoint: other.local_addr().unwrap(), transport_proto: 0x11 }, subscriber]);
+            sd.subscriptions_added.push(added.clone());
+        }
+        runtime.send_initial_events();
+        runtime.send_initial_events();
+        assert_eq!(*seen.lock().unwrap(), vec![added]);
+
+        // Only the new subscriber receives the returned events
+        let mut buf = [0u8; 64];
+        let len = socket.recv(&mut buf).expect("initial event");
+        assert_eq!(SomeIpHeader::deserialize(&buf[..len]).unwrap().method_id, 0x8001);
+        assert_eq!(&buf[16..len], &[7]);
+        assert!(other.recv(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_subscribe_reports_outcome() {
+        use crate::runtime::subscription::{NackReason, SubscriptionState};
+
+        let runtime = runtime_with("subscribe", r#"{ "instances": { "test": { "required": {
+            "sensors": { "service_id": 4660, "instance_id": 1, "major_version": 1,
+                         "eventgroups": { "status": { "eventgroup_id": 1, "reliable": true } },
+                         "subscription_retry": { "ack_timeout_ms": 100, "max_retries": 2, "retry_delay_ms": 10, "retry_on_nack": false } }
+        } } } }"#);
+        let handle = runtime.handle();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+
+        // Not sendable: reported at once, and NACKs are not retried here
+        let start = std::time::Instant::now();
+        assert_eq!(rt.block_on(handle.subscribe(0x1234, 1, 1, 3, "primary")), SubscriptionResult::Nacked(NackReason::NotOffered));
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
+
+        // Unanswered: three attempts of 100 ms each
+        let start = std::time::Instant::now();
+        assert_eq!(rt.block_on(handle.subscribe(0x1234, 1, 2, 3, "primary")), SubscriptionResult::Timeout);
+        assert!(start.elapsed() >= std::time::Duration::from_millis(320));
+
+        let provider = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(30));
+                runtime.sd.lock().unwrap().pending_subscriptions.insert((0x1234, 2), SubscriptionState::Acked);
+            })
+        };
+        assert_eq!(rt.block_on(handle.subscribe(0x1234, 1, 2, 3, "primary")), SubscriptionResult::Acked);
+        provider.join().unwrap();
+    }
+}
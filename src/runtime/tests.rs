@@ -0,0 +1,1344 @@
+//! Runtime tests that go through the event loop, and the fixtures the tests of
+//! the runtime's modules share: [`runtime_with`] loads a runtime for instance
+//! `test` from an inline configuration.
+
+use super::*;
+use crate::codec::SomeIpHeader;
+use crate::logging::{LogContext, LogLevel};
+use crate::sd::EntryType;
+use std::sync::Mutex;
+
+pub(super) struct Dummy;
+
+impl RequestHandler for Dummy {
+    fn service_id(&self) -> u16 { 0x4242 }
+    fn major_version(&self) -> u8 { 1 }
+    fn minor_version(&self) -> u32 { 0 }
+    fn handle(&self, _header: &SomeIpHeader, _payload: &[u8]) -> HandlerResult { Ok(None) }
+}
+
+pub(super) fn empty_runtime(name: &str) -> Arc<SomeIpRuntime> {
+    runtime_with(name, r#"{ "instances": { "test": {} } }"#)
+}
+
+pub(super) fn runtime_with(name: &str, config: &str) -> Arc<SomeIpRuntime> {
+    let path = std::env::temp_dir().join(format!("fh_runtime_{}_{}.json", name, std::process::id()));
+    std::fs::write(&path, config).unwrap();
+    let runtime = SomeIpRuntime::load(path.to_str().unwrap(), "test");
+    let _ = std::fs::remove_file(&path);
+    runtime
+}
+
+#[test]
+fn test_runtime_logs_carry_context() {
+    struct Capture(Mutex<Vec<(LogContext, String)>>);
+    impl FusionLogger for Capture {
+        fn log(&self, _level: LogLevel, _component: &str, msg: &str) {
+            self.0.lock().unwrap().push((LogContext::default(), msg.to_string()));
+        }
+        fn log_with(&self, _level: LogLevel, _component: &str, context: &LogContext, msg: &str) {
+            self.0.lock().unwrap().push((context.clone(), msg.to_string()));
+        }
+    }
+    let capture = Arc::new(Capture(Mutex::new(Vec::new())));
+    let path = std::env::temp_dir().join(format!("fh_runtime_logctx_{}.json", std::process::id()));
+    std::fs::write(&path, r#"{ "instances": { "test": {} } }"#).unwrap();
+    let runtime = SomeIpRuntime::load_with_logger(path.to_str().unwrap(), "test", capture.clone());
+    let _ = std::fs::remove_file(&path);
+
+    runtime.disable_interface("eth9");
+    runtime.handle().register_notification_handler(0x4242, Box::new(Dummy));
+    runtime.process_commands();
+
+    let logs = capture.0.lock().unwrap().clone();
+    assert!(logs.iter().all(|(ctx, _)| ctx.instance.as_deref() == Some("test")));
+    let (ctx, _) = logs.iter().find(|(_, msg)| msg.contains("Cannot disable")).unwrap();
+    assert_eq!(ctx.interface.as_deref(), Some("eth9"));
+    let (ctx, _) = logs.iter().find(|(_, msg)| msg.contains("notification handler")).unwrap();
+    assert_eq!(ctx.service_id, Some(0x4242));
+}
+
+#[test]
+fn test_log_levels_from_config() {
+    struct Capture(Mutex<Vec<(LogLevel, String)>>);
+    impl FusionLogger for Capture {
+        fn log(&self, level: LogLevel, component: &str, _msg: &str) {
+            self.0.lock().unwrap().push((level, component.to_string()));
+        }
+    }
+    let capture = Arc::new(Capture(Mutex::new(Vec::new())));
+    let path = std::env::temp_dir().join(format!("fh_runtime_loglevels_{}.json", std::process::id()));
+    std::fs::write(&path, r#"{ "instances": { "test": {
+        "logging": { "level": "Error", "components": { "Runtime": "Warn" } }
+    } } }"#).unwrap();
+    let runtime = SomeIpRuntime::load_with_logger(path.to_str().unwrap(), "test", capture.clone());
+    let _ = std::fs::remove_file(&path);
+    let loaded = capture.0.lock().unwrap().len();
+
+    let logger = runtime.handle().get_logger();
+    logger.log(LogLevel::Warn, "App", "dropped");
+    logger.log(LogLevel::Error, "App", "kept");
+    runtime.disable_interface("eth9");
+    logger.set_level("Runtime", LogLevel::Error);
+    runtime.disable_interface("eth9");
+
+    let logs = capture.0.lock().unwrap()[loaded..].to_vec();
+    assert_eq!(logs, vec![(LogLevel::Error, "App".to_string()), (LogLevel::Warn, "Runtime".to_string())]);
+}
+
+#[test]
+fn test_find_service_starts_active_discovery() {
+    let runtime = runtime_with("find", r#"{ "instances": { "test": { "required": {
+        "math": { "service_id": 4662, "instance_id": 1, "major_version": 2 }
+    } } } }"#);
+    let handle = runtime.handle();
+    assert!(!handle.find_service("unknown"));
+    assert!(handle.find_service("math"));
+    let find = runtime.sd.lock().unwrap().pending_finds[&(4662, 1)].entry.clone();
+    assert_eq!((find.entry_type, find.major_version, find.minor_version), (EntryType::FindService, 2, 0xFFFF_FFFF));
+
+    // A request to a service not yet offered starts the search as well
+    runtime.sd.lock().unwrap().pending_finds.clear();
+    let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    assert!(rt.block_on(handle.request_service("math", 0x0001, &[1])).is_none());
+    assert!(runtime.sd.lock().unwrap().pending_finds.contains_key(&(4662, 1)));
+}
+
+#[test]
+fn test_tp_reassembly_limits_from_config() {
+    let runtime = runtime_with("tp", r#"{ "instances": { "test": {
+        "tp": { "reassembly_timeout_ms": 10, "max_buffer_bytes": 64 }
+    } } }"#);
+    let handle = runtime.handle();
+    let first = crate::codec::tp::TpHeader::new(0, true);
+    {
+        let mut reassembler = runtime.tp_reassembler.lock().unwrap();
+        let source = "127.0.0.1:30501".parse().unwrap();
+        assert!(reassembler.process_segment(source, 1, 1, &first, &[0u8; 48]).unwrap().is_none());
+        assert!(reassembler.process_segment(source, 2, 1, &first, &[0u8; 32]).is_err());
+    }
+    assert_eq!(handle.tp_stats().dropped, 1);
+
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    runtime.expire_tp_reassemblies();
+    let stats = handle.tp_stats();
+    assert_eq!((stats.expired, stats.in_progress, stats.buffered_bytes), (1, 0, 0));
+}
+
+#[test]
+fn test_try_load_returns_errors() {
+    use crate::runtime::{ConfigError, RuntimeError};
+
+    let path = std::env::temp_dir().join(format!("fh_runtime_try_load_{}.json", std::process::id()));
+    let try_load = |config: &str| {
+        std::fs::write(&path, config).unwrap();
+        SomeIpRuntime::try_load(path.to_str().unwrap(), "test")
+    };
+    assert!(matches!(try_load("{ not json"), Err(RuntimeError::Config(ConfigError::Parse(_)))));
+    assert!(matches!(try_load(r#"{ "instances": { "test": { "endpoint": "missing" } } }"#),
+        Err(RuntimeError::Config(ConfigError::Invalid(issues))) if issues.len() == 1));
+
+    let taken = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let result = try_load(&format!(r#"{{
+        "endpoints": {{ "server_ep": {{ "ip": "127.0.0.1", "port": {}, "version": 4, "protocol": "udp" }} }},
+        "instances": {{ "test": {{ "endpoint": "server_ep" }} }}
+    }}"#, port));
+    let _ = std::fs::remove_file(&path);
+    let Err(RuntimeError::Bind { what, source }) = result else { panic!("expected a bind error") };
+    assert_eq!(what, format!("endpoint 'server_ep' (127.0.0.1:{}/udp)", port));
+    assert_eq!(source.kind(), std::io::ErrorKind::AddrInUse);
+    assert!(matches!(SomeIpRuntime::try_load("/nonexistent/config.json", "test"), Err(RuntimeError::Config(ConfigError::Io(_)))));
+}
+
+#[test]
+fn test_request_to_tcp_target() {
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let target = listener.local_addr().unwrap();
+    let provider = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = [0u8; 19];
+        stream.read_exact(&mut req).unwrap();
+        let header = SomeIpHeader::deserialize(&req[..16]).unwrap();
+        let mut payload = req[16..].to_vec();
+        payload.reverse();
+        let mut res = SomeIpHeader::new(header.service_id, header.method_id, header.client_id, header.session_id, 0x80, payload.len() as u32)
+            .serialize().to_vec();
+        res.extend(payload);
+        stream.write_all(&res).unwrap();
+    });
+
+    let runtime = empty_runtime("tcp_request");
+    runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), RemoteService {
+        service_id: 0x1234,
+        instance_id: 1,
+        version_major: 1,
+        version_minor: 0,
+        endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: target.port(), transport_proto: 0x06 }],
+        last_seen: std::time::Instant::now(),
+        ttl: 0xFFFFFF,
+    });
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let handle = runtime.handle();
+    let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    let response = rt.block_on(handle.send_request_and_wait(0x1234, 0x0001, &[1, 2, 3], target));
+    runtime.stop();
+    event_loop.join().unwrap();
+    provider.join().unwrap();
+
+    assert_eq!(response, Some(vec![3, 2, 1]));
+}
+
+#[test]
+fn test_requests_carry_client_and_session_ids() {
+    use crate::runtime::ClientContext;
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+
+    struct EchoClient { context: ClientContext }
+    impl ServiceClient for EchoClient {
+        const SERVICE_ID: u16 = 0x1234;
+        fn new(context: ClientContext) -> Self { EchoClient { context } }
+    }
+
+    // Answers requests with an empty response and records (client, session, type) of everything
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let target = socket.local_addr().unwrap();
+    let provider = std::thread::spawn(move || {
+        let mut seen = Vec::new();
+        let mut buf = [0u8; 1500];
+        while let Ok((len, from)) = socket.recv_from(&mut buf) {
+            let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+            seen.push((header.client_id, header.session_id, header.message_type));
+            if header.message_type == 0x00 {
+                let res = SomeIpHeader::new(header.service_id, header.method_id, header.client_id, header.session_id, 0x80, 0);
+                socket.send_to(&res.serialize(), from).unwrap();
+            }
+        }
+        seen
+    });
+
+    let runtime = runtime_with("client_ids", r#"{
+        "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "ep",
+            "client_id": 257,
+            "required": { "echo": { "service_id": 4660, "instance_id": 1, "major_version": 1 } }
+        } }
+    }"#);
+    assert_eq!(runtime.handle().client_id(), 0x0101);
+    runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), RemoteService {
+        service_id: 0x1234,
+        instance_id: 1,
+        version_major: 1,
+        version_minor: 0,
+        endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: target.port(), transport_proto: 0x11 }],
+        last_seen: std::time::Instant::now(),
+        ttl: 0xFFFFFF,
+    });
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let context = runtime.get_client::<EchoClient>("echo").unwrap().context;
+    assert_eq!(context.client_id(), Some(0x0101));
+    let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    assert!(rt.block_on(context.request(0x0001, &[])).is_ok());
+    assert!(rt.block_on(context.request(0x0001, &[])).is_ok());
+    context.send(0x0002, &[1]).unwrap();
+    context.send(0x0002, &[2]).unwrap();
+    runtime.stop();
+    event_loop.join().unwrap();
+
+    // Sessions count up per method, starting at 1
+    assert_eq!(provider.join().unwrap(), vec![(0x0101, 1, 0x00), (0x0101, 2, 0x00), (0x0101, 1, 0x01), (0x0101, 2, 0x01)]);
+
+    // Without a configured client_id each runtime gets its own
+    let (a, b) = (empty_runtime("client_id_a").client_id(), empty_runtime("client_id_b").client_id());
+    assert!(a != 0 && b != 0 && a != b);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_shared_memory_negotiated_with_local_peer() {
+    use crate::sd::machine::{EventSubscriber, RemoteService};
+    use crate::sd::options::SdOption;
+    use crate::transport::{shm, ShmTransport, SomeIpTransport};
+
+    let Some(host) = shm::host_id() else { return };
+    let runtime = runtime_with("shm", r#"{
+        "interfaces": { "lo": { "name": "lo", "endpoints": {
+            "ctrl": { "ip": "127.0.0.1", "version": 4, "port": 0, "protocol": "udp" } } } },
+        "instances": { "test": { "unicast_bind": { "lo": "ctrl" }, "shared_memory": { "ring_size_kb": 64 } } }
+    }"#);
+    assert_eq!(runtime.shm_transports.len(), 1);
+    let local = runtime.bindings().udp[0].local_addr().unwrap();
+
+    let peer_udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let peer_addr = peer_udp.local_addr().unwrap();
+    let peer = ShmTransport::bind(peer_addr, 64 * 1024).unwrap();
+    peer.set_nonblocking(true).unwrap();
+    peer_udp.set_nonblocking(true).unwrap();
+    let offer = |items: &[(&str, &str)]| RemoteService {
+        service_id: 0x1234, instance_id: 1, version_major: 1, version_minor: 0,
+        endpoint: vec![
+            SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: peer_addr.port(), transport_proto: 0x11 },
+            SdOption::configuration(items).unwrap(),
+        ],
+        last_seen: std::time::Instant::now(), ttl: 0xFFFFFF,
+    };
+    let mut buf = [0u8; 1024];
+
+    // Offered from another host: plain UDP
+    runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), offer(&[("shm", "elsewhere")]));
+    let (tx, _rx) = tokio::sync::oneshot::channel();
+    runtime.transmit_request((0x1234, 0xFFFF), 1, vec![1, 2, 3], peer_addr, crate::runtime::Route::Udp(None), Some(crate::runtime::ResponseSender::Task(tx))).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(peer.receive(&mut buf).is_err());
+    assert_eq!(peer_udp.recv_from(&mut buf).unwrap(), (19, local));
+
+    // Offered from this host: the request goes through the peer's inbox
+    runtime.sd.lock().unwrap().remote_services.insert((0x1234, 1), offer(&[("shm", host.as_str())]));
+    let (tx, _rx) = tokio::sync::oneshot::channel();
+    runtime.transmit_request((0x1234, 0xFFFF), 1, vec![1, 2, 3], peer_addr, crate::runtime::Route::Udp(None), Some(crate::runtime::ResponseSender::Task(tx))).unwrap();
+    assert_eq!(peer.receive(&mut buf).unwrap(), (19, local));
+
+    // Subscribers with a live inbox receive events through it
+    runtime.sd.lock().unwrap().subscriptions.entry((0x1234, 1)).or_default()
+        .push(EventSubscriber { endpoint: peer_addr, transport_proto: 0x11 });
+    assert_eq!(runtime.send_notification(0x1234, 1, 0x8001, &[9]), 1);
+    assert_eq!(peer.receive(&mut buf).unwrap(), (17, local));
+}
+
+#[test]
+fn test_sd_keeps_offering_under_data_flood() {
+    use crate::runtime::config::SdConfig;
+    use crate::sd::entries::{EntryType, SdEntry};
+    use crate::sd::machine::LocalService;
+
+    struct Slow;
+
+    impl RequestHandler for Slow {
+        fn service_id(&self) -> u16 { 0x4242 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            Ok(Some(payload.to_vec()))
+        }
+    }
+
+    let runtime = runtime_with("sd_flood", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": { "endpoint": "server_ep" } }
+    }"#);
+    runtime.services.write().unwrap().insert((0x4242, 1), Arc::new(Slow));
+    let entry = SdEntry {
+        entry_type: EntryType::OfferService, index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+        service_id: 0x4242, instance_id: 1, major_version: 1, ttl: 3, minor_version: 0,
+    };
+    let config = SdConfig { initial_delay_min_ms: 1, initial_delay_max_ms: 5, repetition_max: 0, cyclic_delay_ms: 50, ..Default::default() };
+    let mut service = LocalService::with_config(entry, vec![], &config);
+    service.transition_to_initial_wait(std::time::Instant::now());
+    runtime.sd.lock().unwrap().local_services.insert((0x4242, 1), service);
+
+    // Far more requests than the event loop can serve in the measured second
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut msg = SomeIpHeader::new(0x4242, 0x0001, 0, 1, 0x00, 4).serialize().to_vec();
+    msg.extend_from_slice(&[1, 2, 3, 4]);
+    for _ in 0..50 {
+        socket.send_to(&msg, ("127.0.0.1", runtime.bound_port("server_ep").unwrap())).unwrap();
+    }
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let before = runtime.sd.lock().unwrap().offers_sent();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let offers = runtime.sd.lock().unwrap().offers_sent() - before;
+    runtime.stop();
+    event_loop.join().unwrap();
+
+    // Every 50 ms; polled between handler calls it would be at most one per 200 ms
+    assert!(offers >= 12, "only {} offers sent during the flood", offers);
+    assert!(runtime.counters.requests_served.load(std::sync::atomic::Ordering::Relaxed) < 10);
+}
+
+#[test]
+fn test_undeliverable_requests_answered_with_error() {
+    use crate::codec::ReturnCode;
+    use crate::runtime::echo::EchoService;
+
+    let runtime = runtime_with("errors", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16963, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    runtime.offer_service("echo", Box::new(EchoService::new(0x4243, 1, 0)));
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+    // Returns (message type, return code) of the answer, if any
+    let send = |header: SomeIpHeader, payload: &[u8]| {
+        let mut msg = header.serialize().to_vec();
+        msg.extend_from_slice(payload);
+        socket.send_to(&msg, server).unwrap();
+        let mut buf = [0u8; 1500];
+        let (len, _) = socket.recv_from(&mut buf).ok()?;
+        let answer = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        assert_eq!((answer.session_id, answer.message_type), (header.session_id, 0x81));
+        Some(answer.return_code)
+    };
+    let request = |service_id: u16, method_id: u16, session_id: u16, len: u32| {
+        SomeIpHeader::new(service_id, method_id, 0, session_id, 0x00, len)
+    };
+
+    assert_eq!(send(request(0x4999, 0x0001, 1, 0), &[]), Some(ReturnCode::UnknownService as u8));
+    assert_eq!(send(request(0x4243, 0x0042, 2, 0), &[]), Some(ReturnCode::UnknownMethod as u8));
+    assert_eq!(send(request(0x4243, 0x8001, 3, 0), &[]), Some(ReturnCode::UnknownMethod as u8));
+    // `delay` needs a 4-byte argument
+    assert_eq!(send(request(0x4243, 0x0002, 4, 2), &[0, 1]), Some(ReturnCode::MalformedMessage as u8));
+    // Length field claims more payload than was received
+    assert_eq!(send(request(0x4243, 0x0001, 5, 10), &[1, 2]), Some(ReturnCode::MalformedMessage as u8));
+    let mut wrong_version = request(0x4243, 0x0001, 6, 0);
+    wrong_version.protocol_version = 0x02;
+    assert_eq!(send(wrong_version, &[]), Some(ReturnCode::WrongProtocolVersion as u8));
+    // Fire-and-forget calls are dropped silently
+    assert_eq!(send(SomeIpHeader::new(0x4999, 0x0001, 0, 7, 0x01, 0), &[]), None);
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[test]
+fn test_fire_and_forget_not_answered() {
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+
+    let provider = runtime_with("ff_provider", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16963, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    let echo = EchoService::new(0x4243, 1, 0);
+    let served = echo.requests_served();
+    provider.offer_service("echo", Box::new(echo));
+    let client = runtime_with("ff_client", r#"{
+        "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": { "endpoint": "ep" } }
+    }"#);
+    let loops: Vec<_> = [provider.clone(), client.clone()].into_iter().map(|runtime| std::thread::spawn(move || runtime.run())).collect();
+
+    let server = std::net::SocketAddr::from(([127, 0, 0, 1], provider.bound_port("server_ep").unwrap()));
+    client.handle().send_fire_and_forget(0x4243, METHOD_ECHO, &[1, 2, 3], server).unwrap();
+    assert!(client.pending_requests.lock().unwrap().is_empty());
+    let start = std::time::Instant::now();
+    while served.load(std::sync::atomic::Ordering::Relaxed) == 0 && start.elapsed() < Duration::from_secs(2) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    // Give a response, if one were sent, time to arrive
+    std::thread::sleep(Duration::from_millis(100));
+    provider.stop();
+    client.stop();
+    loops.into_iter().for_each(|event_loop| event_loop.join().unwrap());
+
+    // The echo handler returns a payload, but a REQUEST_NO_RETURN is never answered
+    assert_eq!(served.load(std::sync::atomic::Ordering::Relaxed), 1);
+    assert_eq!(provider.stats().services[&0x4243].sent, 0);
+    assert_eq!(client.stats().services.get(&0x4243).map_or(0, |traffic| traffic.received), 0);
+}
+
+#[test]
+fn test_requests_dispatched_per_instance() {
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+    use std::sync::atomic::Ordering::Relaxed;
+
+    let provider = runtime_with("instances_provider", r#"{
+        "interfaces": { "lo": { "name": "lo", "endpoints": {
+            "a": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" },
+            "b": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } } } },
+        "instances": { "test": { "providing": {
+            "echo-1": { "service_id": 16963, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "a" } },
+            "echo-2": { "service_id": 16963, "instance_id": 2, "major_version": 1, "offer_on": { "lo": "b" } }
+        } } }
+    }"#);
+    // One handler type, offered as two instances
+    let (first, second) = (EchoService::new(0x4243, 1, 0), EchoService::new(0x4243, 1, 0));
+    let (served_first, served_second) = (first.requests_served(), second.requests_served());
+    provider.offer_service("echo-1", Box::new(first));
+    provider.offer_service("echo-2", Box::new(second));
+    let client = runtime_with("instances_client", r#"{
+        "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": { "endpoint": "ep" } }
+    }"#);
+    let loops: Vec<_> = [provider.clone(), client.clone()].into_iter().map(|runtime| std::thread::spawn(move || runtime.run())).collect();
+
+    let at = |endpoint: &str| std::net::SocketAddr::from(([127, 0, 0, 1], provider.bound_port(endpoint).unwrap()));
+    client.send_fire_and_forget(0x4243, METHOD_ECHO, &[1], at("a")).unwrap();
+    client.send_fire_and_forget(0x4243, METHOD_ECHO, &[2], at("b")).unwrap();
+    client.send_fire_and_forget(0x4243, METHOD_ECHO, &[3], at("b")).unwrap();
+    let start = std::time::Instant::now();
+    while served_first.load(Relaxed) + served_second.load(Relaxed) < 3 && start.elapsed() < Duration::from_secs(2) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    provider.stop();
+    client.stop();
+    loops.into_iter().for_each(|event_loop| event_loop.join().unwrap());
+    assert_eq!((served_first.load(Relaxed), served_second.load(Relaxed)), (1, 2));
+
+    // Withdrawing one instance leaves the other
+    assert!(provider.stop_offer_service("echo-1"));
+    assert!(provider.services.read().unwrap().contains_key(&(0x4243, 2)));
+    assert_eq!(provider.instance_endpoints.read().unwrap().values().copied().collect::<Vec<_>>(), vec![2]);
+}
+
+#[test]
+fn test_interface_versions_checked() {
+    use crate::codec::ReturnCode;
+    use crate::runtime::echo::METHOD_ECHO;
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+
+    let provider = runtime_with("version_provider", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16976, "instance_id": 1, "major_version": 2, "endpoint": "server_ep", "echo": { "heartbeat_ms": 0 } } }
+        } }
+    }"#);
+    let event_loop = {
+        let runtime = provider.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let server = std::net::SocketAddr::from(([127, 0, 0, 1], provider.bound_port("server_ep").unwrap()));
+    // Returns (message type, return code, interface version) of the answer
+    let call = |interface_version: u8, session_id: u16| {
+        let mut msg = SomeIpHeader::with_interface_version(0x4250, METHOD_ECHO, 0, session_id, 0x00, 1, interface_version).serialize().to_vec();
+        msg.push(7);
+        socket.send_to(&msg, server).unwrap();
+        let mut buf = [0u8; 1500];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        (header.message_type, header.return_code, header.interface_version)
+    };
+    assert_eq!(call(1, 1), (0x81, ReturnCode::WrongInterfaceVersion as u8, 1));
+    assert_eq!(call(2, 2), (0x80, 0x00, 2));
+    provider.stop();
+    event_loop.join().unwrap();
+
+    // Offers of another major version, or an older minor version, are not resolved
+    let client = runtime_with("version_client", r#"{ "instances": { "test": { "required": {
+        "echo": { "service_id": 16976, "instance_id": 1, "major_version": 2, "minor_version": 3 }
+    } } } }"#);
+    let offer = |version_major, version_minor| RemoteService {
+        service_id: 0x4250, instance_id: 1, version_major, version_minor,
+        endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: server.port(), transport_proto: 0x11 }],
+        last_seen: std::time::Instant::now(),
+        ttl: 0xFFFFFF,
+    };
+    for (major, minor, resolved) in [(1, 5, false), (2, 2, false), (2, 3, true), (2, 7, true)] {
+        let mut sd = client.sd.lock().unwrap();
+        sd.remote_services.insert((0x4250, 1), offer(major, minor));
+        assert_eq!(sd.get_service(0x4250, 1).is_some(), resolved, "offer {}.{}", major, minor);
+        assert_eq!(sd.get_service_preferring(0x4250, 0xFFFF, 0x11).is_some(), resolved, "offer {}.{}", major, minor);
+    }
+    // Requests carry the major version of the instance they go to
+    assert_eq!(client.interface_version(0x4250, 1, server), 2);
+    assert_eq!(client.interface_version(0x4251, 0xFFFF, server), SomeIpHeader::DEFAULT_INTERFACE_VERSION);
+}
+
+#[test]
+fn test_instances_required_at_different_majors() {
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+
+    let client = runtime_with("two_majors", r#"{ "instances": { "test": { "required": {
+        "math-v2": { "service_id": 4097, "instance_id": 3, "major_version": 2 },
+        "math-v1": { "service_id": 4097, "instance_id": 2, "major_version": 1 }
+    } } } }"#);
+    let at = |port| std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let offer = |instance_id, version_major, port| RemoteService {
+        service_id: 0x1001, instance_id, version_major, version_minor: 0,
+        endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port, transport_proto: 0x11 }],
+        last_seen: std::time::Instant::now(),
+        ttl: 0xFFFFFF,
+    };
+    {
+        let mut sd = client.sd.lock().unwrap();
+        sd.remote_services.insert((0x1001, 3), offer(3, 2, 30503));
+        sd.remote_services.insert((0x1001, 2), offer(2, 1, 30502));
+        assert_eq!(sd.get_service(0x1001, 3), Some((at(30503), 0x11)));
+        assert_eq!(sd.get_service(0x1001, 2), Some((at(30502), 0x11)));
+
+        // Each instance is held to its own major version
+        sd.remote_services.insert((0x1001, 2), offer(2, 2, 30502));
+        assert_eq!(sd.get_service(0x1001, 2), None);
+        sd.remote_services.clear();
+    }
+    // Without an offer, requests take the major version configured for their instance
+    assert_eq!(client.interface_version(0x1001, 3, at(30503)), 2);
+    assert_eq!(client.interface_version(0x1001, 2, at(30502)), 1);
+}
+
+#[test]
+fn test_browse_services_lists_every_offer() {
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+
+    let runtime = runtime_with("browse", r#"{ "instances": { "test": { "required": {
+        "radar": { "service_id": 4660, "instance_id": 1, "major_version": 2 }
+    } } } }"#);
+    // Listed even if not required, or of another version than required
+    for (service_id, version_major) in [(0x1234, 1), (0x7777, 3)] {
+        runtime.sd.lock().unwrap().remote_services.insert((service_id, 1), RemoteService {
+            service_id, instance_id: 1, version_major, version_minor: 0,
+            endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port: 30500, transport_proto: 0x06 }],
+            last_seen: std::time::Instant::now(),
+            ttl: 0xFFFFFF,
+        });
+    }
+    let listed = runtime.handle().browse_services();
+    let ids: Vec<(u16, u8)> = listed.iter().map(|s| (s.service_id, s.major_version)).collect();
+    assert_eq!(ids, [(0x1234, 1), (0x7777, 3)]);
+    assert_eq!((listed[0].endpoints[0].1, listed[0].ttl_remaining), (0x06, None));
+    let find = &runtime.sd.lock().unwrap().pending_finds[&(0xFFFF, 0xFFFF)].entry;
+    assert_eq!((find.entry_type, find.major_version, find.minor_version), (EntryType::FindService, 0xFF, 0xFFFF_FFFF));
+}
+
+#[test]
+fn test_handler_errors_sent_as_error_responses() {
+    use crate::codec::ReturnCode;
+
+    struct Picky;
+
+    impl RequestHandler for Picky {
+        fn service_id(&self) -> u16 { 0x4244 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+            match payload {
+                [] => Err(ReturnCode::NotReady),
+                _ => Ok(Some(payload.to_vec())),
+            }
+        }
+    }
+
+    let runtime = runtime_with("handler_errors", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "picky": { "service_id": 16964, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    runtime.offer_service("picky", Box::new(Picky));
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+    // Returns (message type, return code, payload) of the answer
+    let send = |session_id: u16, payload: &[u8]| {
+        let mut msg = SomeIpHeader::new(0x4244, 0x0001, 0, session_id, 0x00, payload.len() as u32).serialize().to_vec();
+        msg.extend_from_slice(payload);
+        socket.send_to(&msg, server).unwrap();
+        let mut buf = [0u8; 1500];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        let answer = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        assert_eq!(answer.session_id, session_id);
+        (answer.message_type, answer.return_code, buf[16..len].to_vec())
+    };
+
+    assert_eq!(send(1, &[]), (0x81, ReturnCode::NotReady as u8, vec![]));
+    assert_eq!(send(2, &[5, 6]), (0x80, ReturnCode::Ok as u8, vec![5, 6]));
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[test]
+fn test_offer_withdraw_and_reoffer_while_running() {
+    use crate::codec::ReturnCode;
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+
+    let runtime = runtime_with("reoffer", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16968, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    let handle = runtime.handle();
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+    let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+    let call = |session_id: u16| {
+        let mut msg = SomeIpHeader::new(0x4248, METHOD_ECHO, 1, session_id, 0x00, 1).serialize().to_vec();
+        msg.push(7);
+        socket.send_to(&msg, server).unwrap();
+        let mut buf = [0u8; 64];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        SomeIpHeader::deserialize(&buf[..len]).unwrap().return_code
+    };
+    let settle = || std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Offered after run() started
+    handle.offer_service("echo", Box::new(EchoService::new(0x4248, 1, 0)));
+    settle();
+    assert_eq!(call(1), ReturnCode::Ok as u8);
+
+    handle.stop_offer_service("echo");
+    settle();
+    assert_eq!(call(2), ReturnCode::UnknownService as u8);
+    assert!(!runtime.stop_offer_service("echo"));
+
+    handle.offer_service("echo", Box::new(EchoService::new(0x4248, 1, 0)));
+    settle();
+    assert_eq!(call(3), ReturnCode::Ok as u8);
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[test]
+fn test_tp_requests_from_two_senders_with_the_same_ids() {
+    use crate::codec::tp::TpHeader;
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+
+    let runtime = runtime_with("tp_senders", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16968, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    runtime.offer_service("echo", Box::new(EchoService::new(0x4248, 1, 0)));
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+    let senders: Vec<(std::net::UdpSocket, u8)> = [0xA, 0xB].into_iter().map(|fill| {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+        (socket, fill)
+    }).collect();
+    let segment = |offset: u32, more: bool, fill: u8| {
+        // Same client and session ID from both senders; TP request (0x20)
+        let mut msg = SomeIpHeader::new(0x4248, METHOD_ECHO, 1, 1, 0x20, 4 + 16).serialize().to_vec();
+        msg.extend_from_slice(&TpHeader::new(offset, more).serialize());
+        msg.extend_from_slice(&[fill; 16]);
+        msg
+    };
+    for (offset, more) in [(0, true), (16, false)] {
+        for (socket, fill) in &senders {
+            socket.send_to(&segment(offset, more, *fill), server).unwrap();
+        }
+    }
+    for (socket, fill) in &senders {
+        let mut buf = [0u8; 128];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[16..len], &[*fill; 32][..]);
+    }
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[test]
+fn test_shutdown_withdraws_and_drains() {
+    struct Sleepy;
+
+    impl RequestHandler for Sleepy {
+        fn service_id(&self) -> u16 { 0x4246 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            Ok(Some(payload.to_vec()))
+        }
+    }
+
+    let runtime = runtime_with("shutdown", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "worker_threads": 2,
+            "providing": { "sleepy": { "service_id": 16966, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    runtime.offer_service("sleepy", Box::new(Sleepy));
+    runtime.subscribe_eventgroup(0x4247, 1, 1, 3, "primary");
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+    let mut msg = SomeIpHeader::new(0x4246, 0x0001, 1, 1, 0x00, 1).serialize().to_vec();
+    msg.push(7);
+    socket.send_to(&msg, ("127.0.0.1", runtime.bound_port("server_ep").unwrap())).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    assert!(runtime.handle().shutdown(std::time::Duration::from_secs(2)));
+    event_loop.join().unwrap();
+
+    // The request in flight was answered before shutdown returned
+    let mut buf = [0u8; 64];
+    let (len, _) = socket.recv_from(&mut buf).unwrap();
+    assert_eq!(SomeIpHeader::deserialize(&buf[..len]).unwrap().return_code, 0x00);
+    assert_eq!(buf[len - 1], 7);
+    assert_eq!(runtime.sd.lock().unwrap().stop_offer_all(), 0);
+    assert!(runtime.own_subscriptions.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_shutdown_wakes_idle_sd_thread() {
+    let runtime = empty_runtime("shutdown_idle");
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+    // With nothing offered or sought, the SD thread waits out its longest idle period
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let started = std::time::Instant::now();
+    assert!(runtime.shutdown(std::time::Duration::from_millis(500)));
+    assert!(started.elapsed() < std::time::Duration::from_millis(500));
+    event_loop.join().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sd_changes_wake_sd_thread() {
+    let runtime = runtime_with("sd_wake", r#"{
+        "interfaces": { "lo": { "name": "lo", "sd": { "endpoint_v4": "sd" }, "endpoints": {
+            "sd": { "ip": "224.224.224.245", "port": 30499, "version": 4, "protocol": "udp" },
+            "a": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } } } },
+        "instances": { "test": { "providing": {
+            "radar": { "service_id": 16962, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "a" } }
+        } } }
+    }"#);
+    let waker = runtime.sd_waker.clone().unwrap();
+    let assert_woken = |what: &str| {
+        let started = std::time::Instant::now();
+        readiness::wait_fds(&[], Some(&waker), std::time::Duration::from_secs(1));
+        assert!(started.elapsed() < std::time::Duration::from_millis(500), "{} did not wake the SD thread", what);
+    };
+
+    runtime.offer_service("radar", Box::new(Dummy));
+    assert_woken("offer");
+    assert!(runtime.offer_eventgroup("radar", 1, &[0x8001], 0));
+    assert_woken("eventgroup offer");
+    assert!(runtime.stop_offer_service("radar"));
+    assert_woken("stop offer");
+    assert!(runtime.disable_interface("lo"));
+    assert_woken("interface shutdown");
+}
+
+#[test]
+fn test_e2e_protected_requests() {
+    use crate::codec::ReturnCode;
+    use crate::e2e::Profile04;
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+
+    let runtime = runtime_with("e2e", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16967, "instance_id": 1, "major_version": 1, "endpoint": "server_ep",
+                "e2e": { "0x0001": { "profile": "P04", "data_id": 305419896, "max_delta_counter": 2 } } } }
+        } }
+    }"#);
+    runtime.offer_service("echo", Box::new(EchoService::new(0x4247, 1, 0)));
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+    // Returns (message type, return code, payload) of the answer
+    let send = |session_id: u16, payload: &[u8]| {
+        let mut msg = SomeIpHeader::new(0x4247, METHOD_ECHO, 0, session_id, 0x00, payload.len() as u32).serialize().to_vec();
+        msg.extend_from_slice(payload);
+        socket.send_to(&msg, server).unwrap();
+        let mut buf = [0u8; 1500];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        let answer = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        (answer.message_type, answer.return_code, buf[16..len].to_vec())
+    };
+
+    let mut sender = Profile04::new(0x1234_5678, 2);
+    let mut receiver = Profile04::new(0x1234_5678, 2);
+    let first = sender.protect(&[1, 2, 3]);
+    let (message_type, return_code, response) = send(1, &first);
+    assert_eq!((message_type, return_code), (0x80, ReturnCode::Ok as u8));
+    // The echoed payload comes back under the provider's own protection
+    assert_eq!(receiver.check(&response), Ok(vec![1, 2, 3]));
+
+    assert_eq!(send(2, &first), (0x81, ReturnCode::E2eRepeated as u8, vec![]));
+    let mut corrupted = sender.protect(&[4]);
+    corrupted[12] ^= 0xFF;
+    assert_eq!(send(3, &corrupted), (0x81, ReturnCode::E2eNotAvailable as u8, vec![]));
+    assert_eq!(send(4, &[4]), (0x81, ReturnCode::E2eNotAvailable as u8, vec![]));
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[test]
+fn test_runtime_stats() {
+    use crate::runtime::echo::{EchoService, METHOD_ECHO};
+    use crate::runtime::stats::ServiceTraffic;
+
+    let runtime = runtime_with("stats", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16970, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    runtime.offer_service("echo", Box::new(EchoService::new(0x424A, 1, 0)));
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let server = std::net::SocketAddr::from(([127, 0, 0, 1], runtime.bound_port("server_ep").unwrap()));
+    let mut buf = [0u8; 1500];
+    for (session_id, service_id) in [(1, 0x424A), (2, 0x424A), (3, 0x4999)] {
+        let msg = SomeIpHeader::new(service_id, METHOD_ECHO, 0, session_id, 0x00, 0).serialize();
+        socket.send_to(&msg, server).unwrap();
+        socket.recv_from(&mut buf).unwrap();
+    }
+    // Too short to be a SOME/IP message
+    socket.send_to(&[0u8; 4], server).unwrap();
+    // Nothing listens on the target, so the request times out
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let handle = runtime.handle();
+    let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    let response = rt.block_on(handle.send_request_and_wait(0x424B, 1, &[], silent.local_addr().unwrap()));
+    assert!(response.is_none());
+
+    let stats = runtime.stats();
+    let echo = stats.services[&0x424A];
+    assert_eq!((echo.sent, echo.received, echo.requests_handled), (2, 2, 2));
+    // Refused with E_UNKNOWN_SERVICE
+    assert_eq!(stats.services[&0x4999], ServiceTraffic { sent: 1, received: 1, ..Default::default() });
+    assert_eq!(stats.services[&0x424B], ServiceTraffic { sent: 1, ..Default::default() });
+    assert_eq!(stats.requests_served, 2);
+    assert_eq!(stats.packets_dropped, 2);
+    assert_eq!(stats.request_timeouts, 1);
+    assert_eq!(stats.pending_requests, 0);
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[test]
+fn test_large_events_segmented_at_endpoint_mtu() {
+    use crate::codec::tp::{TpHeader, TpReassembler, TpSender};
+    use crate::sd::machine::EventSubscriber;
+
+    let runtime = runtime_with("event_mtu", r#"{
+        "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp", "mtu": 200, "tp_burst": 2, "tp_adaptive": true } },
+        "instances": { "test": {
+            "endpoint": "ep",
+            "providing": { "radar": { "service_id": 28674, "instance_id": 1, "major_version": 1 } }
+        } }
+    }"#);
+    let local = runtime.bindings().transports["ep"].local_addr().unwrap();
+    assert_eq!(runtime.bindings().tp_sender(local), TpSender { mtu: 200, pacing: None, burst: 2, adaptive: true });
+    assert!(runtime.offer_eventgroup("radar", 1, &[0x8001], 0));
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let subscriber = EventSubscriber { endpoint: socket.local_addr().unwrap(), transport_proto: 0x11 };
+    runtime.sd.lock().unwrap().subscriptions.insert((0x7002, 1), vec![subscriber]);
+
+    let payload: Vec<u8> = (0..500u32).map(|i| i as u8).collect();
+    assert_eq!(runtime.fire_event(0x7002, 0x8001, &payload), 1);
+
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let mut reassembler = TpReassembler::new();
+    let mut buf = [0u8; 1500];
+    let mut segments = 0;
+    let event = loop {
+        let len = socket.recv(&mut buf).unwrap();
+        assert!(len <= 200, "datagram of {} bytes", len);
+        let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        assert_eq!(header.message_type, 0x22);
+        segments += 1;
+        let tp = TpHeader::deserialize(&buf[16..20]).unwrap();
+        let src = socket.local_addr().unwrap();
+        if let Some(event) = reassembler.process_segment(src, 0x7002_8001, header.request_id().raw(), &tp, &buf[20..len]).unwrap() {
+            break event;
+        }
+    };
+    assert_eq!(segments, 3);
+    assert_eq!(event.as_slice(), &payload[..]);
+}
+
+#[test]
+fn test_field_value_sent_to_new_subscribers() {
+    use crate::sd::machine::EventSubscriber;
+    use crate::runtime::FieldHandle;
+
+    let runtime = runtime_with("field", r#"{
+        "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": {
+            "endpoint": "ep",
+            "providing": { "climate": { "service_id": 24577, "instance_id": 1, "major_version": 1,
+                "eventgroups": { "fields": { "eventgroup_id": 1, "events": [32786] } } } }
+        } }
+    }"#);
+    let field = FieldHandle::new(0x6001, Some(0x8012), 21u32);
+    field.bind(&runtime).unwrap();
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let subscriber = EventSubscriber { endpoint: socket.local_addr().unwrap(), transport_proto: 0x11 };
+    {
+        let mut sd = runtime.sd.lock().unwrap();
+        sd.subscriptions.insert((0x6001, 1), vec![subscriber]);
+        sd.subscriptions_added.push(SubscriptionAdded { service_id: 0x6001, instance_id: 1, eventgroup_id: 1, subscriber });
+    }
+    let mut buf = [0u8; 64];
+    let mut receive = || {
+        let len = socket.recv(&mut buf).expect("field notification");
+        let header = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+        (header.method_id, u32::from_be_bytes(buf[16..20].try_into().unwrap()))
+    };
+
+    // The current value on joining, then every change
+    runtime.send_initial_events();
+    assert_eq!(receive(), (0x8012, 21));
+    assert_eq!(field.set(22).unwrap(), 1);
+    assert_eq!(receive(), (0x8012, 22));
+    assert_eq!(field.set(22).unwrap(), 0);
+    assert_eq!(field.get(), 22);
+    assert_eq!(runtime.field_values.lock().unwrap().get(&(0x6001, 0x8012)), Some(&22u32.to_be_bytes().to_vec()));
+}
+
+#[test]
+fn test_event_arrival_timestamped() {
+    let runtime = runtime_with("event_time", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+        "instances": { "test": { "endpoint": "server_ep" } }
+    }"#);
+    let handle = runtime.handle();
+    handle.register_notification_handler(0x4242, Box::new(Dummy));
+    assert!(handle.last_event_at(0x4242, 0x8001).is_none());
+
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+    let before = Timestamp::now();
+    let mut msg = SomeIpHeader::new(0x4242, 0x8001, 0, 1, 0x02, 2).serialize().to_vec();
+    msg.extend_from_slice(&[1, 2]);
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.send_to(&msg, ("127.0.0.1", runtime.bound_port("server_ep").unwrap())).unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    let received = loop {
+        if let Some(ts) = handle.last_event_at(0x4242, 0x8001) {
+            break ts;
+        }
+        assert!(std::time::Instant::now() < deadline, "Notification not received");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    handle.stop();
+    event_loop.join().unwrap();
+
+    assert!(received >= before && received <= Timestamp::now());
+    assert!(handle.shutdown_report().taken_at_us >= received.as_micros());
+}
+
+#[test]
+fn test_tcp_notification_beyond_4k_received_whole() {
+    use std::io::Write;
+
+    struct Capture(Arc<Mutex<Vec<Vec<u8>>>>);
+    impl RequestHandler for Capture {
+        fn service_id(&self) -> u16 { 0x4242 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+            self.0.lock().unwrap().push(payload.to_vec());
+            Ok(None)
+        }
+    }
+
+    let runtime = empty_runtime("tcp_large_event");
+    let received = Arc::new(Mutex::new(Vec::new()));
+    runtime.register_notification_handler(0x4242, Box::new(Capture(received.clone())));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    runtime.tcp_client(listener.local_addr().unwrap()).unwrap();
+    let (mut provider, _) = listener.accept().unwrap();
+
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+    let payload: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+    let mut msg = SomeIpHeader::new(0x4242, 0x8001, 0, 1, 0x02, payload.len() as u32).serialize().to_vec();
+    msg.extend_from_slice(&payload);
+    provider.write_all(&msg).unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while received.lock().unwrap().is_empty() {
+        assert!(std::time::Instant::now() < deadline, "Notification not received");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    runtime.stop();
+    event_loop.join().unwrap();
+
+    assert_eq!(*received.lock().unwrap(), vec![payload]);
+}
+
+#[test]
+fn test_tcp_request_and_response_beyond_mtu_sent_whole() {
+    use std::io::{Read, Write};
+
+    struct Echo;
+    impl RequestHandler for Echo {
+        fn service_id(&self) -> u16 { 0x4242 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult { Ok(Some(payload.to_vec())) }
+    }
+
+    let runtime = runtime_with("tcp_large_response", r#"{
+        "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "tcp" } },
+        "instances": { "test": {
+            "endpoint": "server_ep",
+            "providing": { "echo": { "service_id": 16962, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+        } }
+    }"#);
+    runtime.offer_service("echo", Box::new(Echo));
+    let event_loop = {
+        let runtime = runtime.clone();
+        std::thread::spawn(move || runtime.run())
+    };
+
+    let mut client = std::net::TcpStream::connect(("127.0.0.1", runtime.bound_port("server_ep").unwrap())).unwrap();
+    client.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let payload: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+    let mut msg = SomeIpHeader::new(0x4242, 0x0001, 0, 1, 0x00, payload.len() as u32).serialize().to_vec();
+    msg.extend_from_slice(&payload);
+    client.write_all(&msg).unwrap();
+
+    // One unsegmented RESPONSE: TP is not used on TCP
+    let mut header = [0u8; 16];
+    client.read_exact(&mut header).unwrap();
+    let header = SomeIpHeader::deserialize(&header).unwrap();
+    assert_eq!((header.message_type, header.length as usize), (0x80, 8 + payload.len()));
+    let mut response = vec![0u8; payload.len()];
+    client.read_exact(&mut response).unwrap();
+    assert_eq!(response, payload);
+
+    runtime.stop();
+    event_loop.join().unwrap();
+}
+
+#[cfg(all(feature = "tls", target_os = "linux"))]
+#[test]
+fn test_request_to_tls_endpoint() {
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+
+    struct Reverse;
+    impl RequestHandler for Reverse {
+        fn service_id(&self) -> u16 { 0x4242 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult { Ok(Some(payload.iter().rev().copied().collect())) }
+    }
+
+    // Clients find TLS endpoints by address, so the port is fixed
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let interfaces = format!(r#""interfaces": {{ "lo": {{ "name": "lo", "sd": {{ "endpoint_v4": "sd" }}, "endpoints": {{
+        "sd": {{ "ip": "224.224.224.245", "port": 30499, "version": 4, "protocol": "udp" }},
+        "secure": {{ "ip": "127.0.0.1", "port": {}, "version": 4, "protocol": "tls",
+            "tls": {{ "cert": "{f}/server.pem", "key": "{f}/server.key", "ca": "{f}/ca.pem" }} }} }} }} }}"#, port, f = fixtures);
+    let provider = runtime_with("tls_provider", &format!(r#"{{ {},
+        "instances": {{ "test": {{ "providing": {{ "svc": {{ "service_id": 16962, "instance_id": 1, "major_version": 1, "offer_on": {{ "lo": "secure" }} }} }} }} }}
+    }}"#, interfaces));
+    provider.offer_service("svc", Box::new(Reverse));
+    // Offered with the TCP protocol ID
+    let offered = provider.sd.lock().unwrap().local_services[&(0x4242, 1)].endpoint_options.clone();
+    assert!(offered.iter().any(|opt| matches!(opt, SdOption::Ipv4Endpoint { port: p, transport_proto: 0x06, .. } if *p == port)), "{:?}", offered);
+
+    let client = runtime_with("tls_client", &format!(r#"{{ {}, "instances": {{ "test": {{}} }} }}"#, interfaces));
+    let target = SocketAddr::new(std::net::Ipv4Addr::LOCALHOST.into(), port);
+    client.sd.lock().unwrap().remote_services.insert((0x4242, 1), RemoteService {
+        service_id: 0x4242,
+        instance_id: 1,
+        version_major: 1,
+        version_minor: 0,
+        endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port, transport_proto: 0x06 }],
+        last_seen: std::time::Instant::now(),
+        ttl: 0xFFFFFF,
+    });
+    let loops: Vec<_> = [provider.clone(), client.clone()].into_iter()
+        .map(|runtime| std::thread::spawn(move || runtime.run()))
+        .collect();
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    let response = rt.block_on(client.handle().send_request_and_wait(0x4242, 0x0001, &[1, 2, 3], target));
+    let connections = client.tcp_clients.connections();
+    client.stop();
+    provider.stop();
+    for event_loop in loops {
+        event_loop.join().unwrap();
+    }
+
+    assert_eq!(response, Some(vec![3, 2, 1]));
+    assert!(connections.iter().any(|(addr, connection)| *addr == target && connection.is_tls()));
+}
+
+#[cfg(all(feature = "dtls", target_os = "linux"))]
+#[test]
+fn test_request_to_dtls_endpoint() {
+    use crate::sd::machine::RemoteService;
+    use crate::sd::options::SdOption;
+
+    struct Reverse;
+    impl RequestHandler for Reverse {
+        fn service_id(&self) -> u16 { 0x4343 }
+        fn major_version(&self) -> u8 { 1 }
+        fn minor_version(&self) -> u32 { 0 }
+        fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult { Ok(Some(payload.iter().rev().copied().collect())) }
+    }
+
+    // Senders find DTLS endpoints by address, so the provider's port is fixed
+    let port = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let interfaces = format!(r#""interfaces": {{ "lo": {{ "name": "lo", "sd": {{ "endpoint_v4": "sd" }}, "endpoints": {{
+        "sd": {{ "ip": "224.224.224.246", "port": 30498, "version": 4, "protocol": "udp" }},
+        "plain": {{ "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" }},
+        "secure": {{ "ip": "127.0.0.1", "port": {}, "version": 4, "protocol": "dtls",
+            "tls": {{ "cert": "{f}/server.pem", "key": "{f}/server.key", "ca": "{f}/ca.pem" }} }},
+        "peer": {{ "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "dtls",
+            "tls": {{ "cert": "{f}/client.pem", "key": "{f}/client.key", "ca": "{f}/ca.pem" }} }} }} }} }}"#, port, f = fixtures);
+    let provider = runtime_with("dtls_provider", &format!(r#"{{ {},
+        "instances": {{ "test": {{ "providing": {{ "svc": {{ "service_id": 17219, "instance_id": 1, "major_version": 1, "offer_on": {{ "lo": "secure" }} }} }} }} }}
+    }}"#, interfaces));
+    provider.offer_service("svc", Box::new(Reverse));
+    // Offered with the UDP protocol ID
+    let offered = provider.sd.lock().unwrap().local_services[&(0x4343, 1)].endpoint_options.clone();
+    assert!(offered.iter().any(|opt| matches!(opt, SdOption::Ipv4Endpoint { port: p, transport_proto: 0x11, .. } if *p == port)), "{:?}", offered);
+
+    // A plain UDP endpoint is bound first, and requests still leave from the DTLS one
+    let client = runtime_with("dtls_client", &format!(r#"{{ {}, "instances": {{ "test": {{ "unicast_bind": {{ "lo": "plain" }}, "endpoint": "peer" }} }} }}"#, interfaces));
+    let target = SocketAddr::new(std::net::Ipv4Addr::LOCALHOST.into(), port);
+    client.sd.lock().unwrap().remote_services.insert((0x4343, 1), RemoteService {
+        service_id: 0x4343,
+        instance_id: 1,
+        version_major: 1,
+        version_minor: 0,
+        endpoint: vec![SdOption::Ipv4Endpoint { address: std::net::Ipv4Addr::LOCALHOST, port, transport_proto: 0x11 }],
+        last_seen: std::time::Instant::now(),
+        ttl: 0xFFFFFF,
+    });
+    let plain = client.get_transport_v4().unwrap().local_addr().unwrap();
+    let loops: Vec<_> = [provider.clone(), client.clone()].into_iter()
+        .map(|runtime| std::thread::spawn(move || runtime.run()))
+        .collect();
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    let response = rt.block_on(client.handle().send_request_and_wait(0x4343, 0x0001, &[1, 2, 3], target));
+    let bindings = client.bindings();
+    client.stop();
+    provider.stop();
+    for event_loop in loops {
+        event_loop.join().unwrap();
+    }
+
+    assert_eq!(response, Some(vec![3, 2, 1]));
+    assert_eq!(bindings.dtls.len(), 1);
+    assert_ne!(bindings.dtls[0].local_addr().unwrap(), plain);
+}
+
+#[cfg(all(feature = "dtls", target_os = "linux"))]
+#[test]
+fn test_events_to_dtls_subscriber() {
+    use crate::sd::machine::EventSubscriber;
+    use crate::transport::{dtls, DtlsTransport, UdpTransport};
+
+    // The subscriber's DTLS endpoint, known to the provider by address. The provider starts
+    // the handshake, so the subscriber presents a certificate for its address.
+    let subscriber = UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap();
+    let endpoint = subscriber.local_addr().unwrap();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let runtime = runtime_with("dtls_events", &format!(r#"{{
+        "interfaces": {{ "lo": {{ "name": "lo", "endpoints": {{
+            "plain": {{ "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" }},
+            "secure": {{ "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "dtls",
+                "tls": {{ "cert": "{f}/server.pem", "key": "{f}/server.key", "ca": "{f}/ca.pem" }} }},
+            "subscriber": {{ "ip": "127.0.0.1", "port": {}, "version": 4, "protocol": "dtls",
+                "tls": {{ "cert": "{f}/server.pem", "key": "{f}/server.key", "ca": "{f}/ca.pem" }} }} }} }} }},
+        "instances": {{ "test": {{
+            "unicast_bind": {{ "lo": "plain" }},
+            "providing": {{ "radar": {{ "service_id": 28675, "instance_id": 1, "major_version": 1, "offer_on": {{ "lo": "secure" }} }} }}
+        }} }}
+    }}"#, endpoint.port(), f = fixtures));
+    let path = |name: &str| std::path::PathBuf::from(fixtures).join(name);
+    let subscriber = DtlsTransport::new(subscriber, dtls::context(&path("server.pem"), &path("server.key"), &path("ca.pem")).unwrap());
+    subscriber.set_nonblocking(true).unwrap();
+    assert!(runtime.offer_eventgroup("radar", 1, &[0x8001], 0));
+    runtime.sd.lock().unwrap().subscriptions.insert((0x7003, 1), vec![EventSubscriber { endpoint, transport_proto: 0x11 }]);
+    let event_loop = { let runtime = runtime.clone(); std::thread::spawn(move || runtime.run()) };
+
+    assert_eq!(runtime.fire_event(0x7003, 0x8001, &[7, 7]), 1);
+    let mut buf = [0u8; 64];
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    let received = loop {
+        match subscriber.receive(&mut buf) {
+            Ok((len, from)) => break Some((buf[..len].to_vec(), from)),
+            Err(_) if std::time::Instant::now() < deadline => std::thread::sleep(Duration::from_millis(2)),
+            Err(_) => break None,
+        }
+    };
+    runtime.stop();
+    event_loop.join().unwrap();
+
+    let (event, from) = received.expect("event over DTLS");
+    assert_eq!(SomeIpHeader::deserialize(&event).unwrap().method_id, 0x8001);
+    assert_eq!(&event[16..], &[7, 7]);
+    assert_eq!(from, runtime.bindings().dtls[0].local_addr().unwrap());
+}
@@ -1,5 +1,7 @@
 use std::thread;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
@@ -10,13 +12,22 @@ enum Message {
     Terminate,
 }
 
+/// Counts a job as finished when dropped, so a job that panics is counted too.
+struct Finished<'a>(&'a AtomicUsize);
+
+impl Drop for Finished<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 struct Worker {
     _id: usize,
     thread: Option<thread::JoinHandle<()>>,
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>, queued: Arc<AtomicUsize>) -> Worker {
         // Use a larger stack size (2 MiB) to accommodate LLVM coverage instrumentation
         // overhead, which can cause STATUS_STACK_BUFFER_OVERRUN with the default stack.
         let thread = thread::Builder::new()
@@ -26,7 +37,9 @@ impl Worker {
                 let message = receiver.lock().unwrap().recv();
                 match message {
                     Ok(Message::NewJob(job)) => {
-                        job();
+                        let _finished = Finished(&queued);
+                        // A panicking job is reported by the panic hook; the worker keeps serving its keys
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
                     }
                     Ok(Message::Terminate) => {
                         break;
@@ -50,6 +63,10 @@ pub struct ThreadPool {
     workers: Vec<Worker>,
     senders: Vec<mpsc::Sender<Message>>,
     size: usize,
+    /// Worker the next unkeyed job goes to
+    next: AtomicUsize,
+    /// Jobs submitted and not yet finished
+    queued: Arc<AtomicUsize>,
 }
 
 impl ThreadPool {
@@ -61,11 +78,12 @@ impl ThreadPool {
 
         let mut workers = Vec::with_capacity(size);
         let mut senders = Vec::with_capacity(size);
+        let queued = Arc::new(AtomicUsize::new(0));
 
         for id in 0..size {
             let (sender, receiver) = mpsc::channel();
             let receiver = Arc::new(Mutex::new(receiver));
-            workers.push(Worker::new(id, receiver));
+            workers.push(Worker::new(id, receiver, queued.clone()));
             senders.push(sender);
         }
 
@@ -73,14 +91,31 @@ impl ThreadPool {
             workers,
             senders,
             size,
+            next: AtomicUsize::new(0),
+            queued,
         }
     }
 
+    /// Number of worker threads.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Always false: a pool has at least one thread.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Jobs submitted and not yet finished, running ones included.
+    pub fn queued_jobs(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
     /// Execute a job.
     ///
     /// `key`: If Some(hashable), the job is routed to a stable thread based on the hash.
     /// This ensures sequential execution for that key.
-    /// If None, the job is distributed (currently Round Robin or simply hashed by 0/Random).
+    /// If None, jobs go to the workers in turn (round robin).
     pub fn execute<F, K>(&self, f: F, key: Option<K>)
     where
         F: FnOnce() + Send + 'static,
@@ -93,20 +128,15 @@ impl ThreadPool {
             k.hash(&mut hasher);
             (hasher.finish() as usize) % self.size
         } else {
-             // Basic round-robin or random could be better, but for now specific to first logic:
-             // To properly load balance "None" keys, we should rotate.
-             // Simplification: Hash of 0 implies "don't care" but stacks them on thread 0.
-             // Let's us rand or a counter if we want RR.
-             // For strict no-dep, we can use a atomic counter.
-             // For now: Just use 0. Warning: This biases non-keyed work to thread 0.
-             // Correct approach: Just pick one.
-             0
+            self.next.fetch_add(1, Ordering::Relaxed) % self.size
         };
 
-        self.senders[worker_idx].send(Message::NewJob(job)).unwrap();
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        // Workers outlive jobs that panic, so this only fails if a worker could not keep running
+        if self.senders[worker_idx].send(Message::NewJob(job)).is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
     }
-    
-    // Explicit round-robin dispatch for unkeyed tasks could be added (requires mutable state or atomic)
 }
 
 impl Drop for ThreadPool {
@@ -117,7 +147,11 @@ impl Drop for ThreadPool {
 
         for worker in &mut self.workers {
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                // A job may drop the last reference to the pool's owner; its own thread
+                // finishes on its own once the job returns
+                if thread.thread().id() != thread::current().id() {
+                    thread.join().unwrap();
+                }
             }
         }
     }
@@ -128,6 +162,9 @@ mod tests {
     use super::*;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::Duration;
+    use crate::codec::SomeIpHeader;
+    use crate::runtime::tests::runtime_with;
+    use crate::runtime::{HandlerResult, RequestHandler};
     
     #[test]
     fn test_threadpool_creation() {
@@ -231,6 +268,55 @@ mod tests {
         assert_eq!(counter.load(Ordering::SeqCst), 5);
     }
     
+    #[test]
+    fn test_unkeyed_jobs_spread_evenly() {
+        let pool = ThreadPool::new(4);
+        assert_eq!(pool.len(), 4);
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+
+        for _ in 0..12 {
+            let threads = Arc::clone(&threads);
+            let release_rx = Arc::clone(&release_rx);
+            pool.execute(move || {
+                threads.lock().unwrap().push(thread::current().name().unwrap().to_string());
+                let _ = release_rx.lock().unwrap().recv();
+            }, None::<usize>);
+        }
+        // Jobs block until released, so all of them are counted
+        assert_eq!(pool.queued_jobs(), 12);
+        drop(release_tx);
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while pool.queued_jobs() > 0 && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(pool.queued_jobs(), 0);
+        let threads = threads.lock().unwrap();
+        for id in 0..4 {
+            let name = format!("pool-worker-{}", id);
+            assert_eq!(threads.iter().filter(|t| **t == name).count(), 3, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_panicking_job_keeps_worker() {
+        let pool = ThreadPool::new(1);
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        pool.execute(|| panic!("handler failed"), Some("key"));
+        let c = Arc::clone(&counter);
+        pool.execute(move || { c.fetch_add(1, Ordering::SeqCst); }, Some("key"));
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while pool.queued_jobs() > 0 && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(pool.queued_jobs(), 0);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn test_different_key_types() {
         let pool = ThreadPool::new(4);
@@ -249,4 +335,104 @@ mod tests {
         thread::sleep(Duration::from_millis(100));
         assert_eq!(counter.load(Ordering::SeqCst), 3);
     }
+
+    #[test]
+    fn test_worker_threads_run_providers_concurrently() {
+        struct Sleepy;
+
+        impl RequestHandler for Sleepy {
+            fn service_id(&self) -> u16 { 0x4245 }
+            fn major_version(&self) -> u8 { 1 }
+            fn minor_version(&self) -> u32 { 0 }
+            fn handle(&self, _header: &SomeIpHeader, payload: &[u8]) -> HandlerResult {
+                std::thread::sleep(std::time::Duration::from_millis(payload[0] as u64));
+                Ok(Some(payload.to_vec()))
+            }
+        }
+
+        let runtime = runtime_with("workers", r#"{
+            "endpoints": { "server_ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": {
+                "endpoint": "server_ep",
+                "worker_threads": 4,
+                "providing": { "sleepy": { "service_id": 16965, "instance_id": 1, "major_version": 1, "endpoint": "server_ep" } }
+            } }
+        }"#);
+        runtime.offer_service("sleepy", Box::new(Sleepy));
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+        let server = ("127.0.0.1", runtime.bound_port("server_ep").unwrap());
+        let send = |client_id: u16, session_id: u16, sleep_ms: u8| {
+            let mut msg = SomeIpHeader::new(0x4245, 0x0001, client_id, session_id, 0x00, 1).serialize().to_vec();
+            msg.push(sleep_ms);
+            socket.send_to(&msg, server).unwrap();
+        };
+        // A slow request, then a fast one from the same client and fast ones from others
+        send(1, 1, 200);
+        send(1, 2, 0);
+        for client_id in 2..10 {
+            send(client_id, 1, 0);
+        }
+        let mut answers = Vec::new();
+        let mut buf = [0u8; 64];
+        for _ in 0..10 {
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            let answer = SomeIpHeader::deserialize(&buf[..len]).unwrap();
+            answers.push((answer.client_id, answer.session_id));
+        }
+
+        // Other clients are not held up by the slow request; its client is, in order
+        assert_ne!(answers[0], (1, 1));
+        let own: Vec<_> = answers.iter().filter(|(client_id, _)| *client_id == 1).collect();
+        assert_eq!(own, [&(1, 1), &(1, 2)]);
+
+        runtime.stop();
+        event_loop.join().unwrap();
+    }
+
+    #[test]
+    fn test_typed_event_callbacks_on_workers() {
+        let runtime = runtime_with("typed_events", r#"{
+            "endpoints": { "ep": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } },
+            "instances": { "test": { "endpoint": "ep", "worker_threads": 2 } }
+        }"#);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = runtime.handle();
+        handle.on_event(0x4248, 0x8001, move |distance: u32| {
+            let worker = std::thread::current().name().unwrap_or_default().to_string();
+            tx.send((distance, worker)).unwrap();
+        });
+        let event_loop = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.run())
+        };
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let notify = |event_id: u16, session_id: u16, payload: &[u8]| {
+            let mut msg = SomeIpHeader::new(0x4248, event_id, 0, session_id, 0x02, payload.len() as u32).serialize().to_vec();
+            msg.extend_from_slice(payload);
+            socket.send_to(&msg, ("127.0.0.1", runtime.bound_port("ep").unwrap())).unwrap();
+        };
+        notify(0x8001, 1, &7u32.to_be_bytes());
+        // Too short for a u32, and an event without a callback
+        notify(0x8001, 2, &[0, 1]);
+        notify(0x8002, 3, &9u32.to_be_bytes());
+        notify(0x8001, 4, &8u32.to_be_bytes());
+
+        let received: Vec<_> = (0..2).map(|_| rx.recv_timeout(Duration::from_secs(2)).unwrap()).collect();
+        std::thread::sleep(Duration::from_millis(50));
+        runtime.stop();
+        event_loop.join().unwrap();
+
+        // Decoded in order, on one worker
+        assert_eq!(received.iter().map(|(distance, _)| *distance).collect::<Vec<_>>(), vec![7, 8]);
+        assert!(received.iter().all(|(_, worker)| worker.starts_with("pool-worker") && *worker == received[0].1));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(runtime.stats().packets_dropped, 1);
+    }
 }
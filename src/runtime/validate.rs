@@ -0,0 +1,538 @@
+//! # Configuration Validation
+//!
+//! [`SystemConfig::validate`] checks the part of a configuration an instance
+//! uses before anything is bound: references to interfaces, endpoints and QoS
+//! profiles, protocol and address strings, multicast endpoints, and bound
+//! endpoints whose ports overlap. Every problem found is reported as a
+//! [`ConfigIssue`] naming where it is, as a dotted path into the JSON, and what
+//! is defined instead.
+//!
+//! [`read_config`] reads a file, resolves its variables and includes (see
+//! [`overrides`](super::overrides)), parses and validates it; `load` and
+//! `reload_config` go through it.
+
+use super::access::Subnet;
+use super::binding;
+use super::netif;
+use super::overrides;
+use super::config::{EndpointConfig, InstanceConfig, InterfaceConfig, SystemConfig};
+use crate::codec::tp::{MAX_MTU, MIN_MTU};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// One problem in a configuration. `at` is the dotted path of the offending entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigIssue {
+    /// Reference to an interface alias not defined under `interfaces`
+    UnknownInterface { at: String, alias: String, known: Vec<String> },
+    /// Reference to an endpoint not defined where it is looked up
+    UnknownEndpoint { at: String, name: String, known: Vec<String> },
+    /// Reference to a profile not defined under `qos_profiles`
+    UnknownQosProfile { at: String, name: String, known: Vec<String> },
+    /// Protocol other than `udp`, `tcp`, `tls` or `dtls`
+    InvalidProtocol { at: String, protocol: String },
+    /// Setting that needs a crate feature this build does not have
+    FeatureRequired { at: String, feature: &'static str },
+    /// `ip` is not an IP address
+    InvalidAddress { at: String, ip: String },
+    /// `version` is not 4 or 6, or not the family of `ip`
+    VersionMismatch { at: String, ip: String, version: u8 },
+    /// Endpoint used as a multicast group that cannot be one
+    InvalidMulticast { at: String, ip: String, reason: &'static str },
+    /// Multicast endpoint used where a socket is bound
+    MulticastBind { at: String, ip: String },
+    /// Two bound endpoints claim the same port, one on every address and one on a single address
+    PortConflict { first: String, second: String, protocol: String, port: u16 },
+    /// Two provided services with one service ID that are the same instance, or share an
+    /// endpoint so their requests cannot be told apart
+    InstanceConflict { first: String, second: String, service_id: u16, reason: &'static str },
+    /// `${NAME}` of a variable that is not set and has no default
+    UndefinedVariable { at: String, name: String },
+    /// `${` without a closing `}`
+    MalformedVariable { at: String, text: String },
+    /// `include` that is neither a file name nor a list of them
+    InvalidInclude { at: String },
+    /// Value of an imported (e.g. vsomeip) configuration that cannot be read as `expected`
+    InvalidValue { at: String, value: String, expected: &'static str },
+}
+
+fn defined(known: &[String]) -> String {
+    if known.is_empty() { "none defined".to_string() } else { format!("defined: {}", known.join(", ")) }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigIssue::UnknownInterface { at, alias, known } => write!(f, "{}: interface '{}' not found ({})", at, alias, defined(known)),
+            ConfigIssue::UnknownEndpoint { at, name, known } => write!(f, "{}: endpoint '{}' not found ({})", at, name, defined(known)),
+            ConfigIssue::UnknownQosProfile { at, name, known } => write!(f, "{}: QoS profile '{}' not found ({})", at, name, defined(known)),
+            ConfigIssue::InvalidProtocol { at, protocol } => write!(f, "{}: protocol '{}' is not 'udp', 'tcp', 'tls' or 'dtls'", at, protocol),
+            ConfigIssue::FeatureRequired { at, feature } => write!(f, "{}: requires fusion-hawking built with the '{}' feature", at, feature),
+            ConfigIssue::InvalidAddress { at, ip } => write!(f, "{}: '{}' is not an IP address", at, ip),
+            ConfigIssue::VersionMismatch { at, ip, version } => write!(f, "{}: version {} does not match address '{}'", at, version, ip),
+            ConfigIssue::InvalidMulticast { at, ip, reason } => write!(f, "{}: multicast endpoint '{}' {}", at, ip, reason),
+            ConfigIssue::MulticastBind { at, ip } => write!(f, "{}: '{}' is a multicast address and cannot be bound; use a unicast endpoint", at, ip),
+            ConfigIssue::PortConflict { first, second, protocol, port } => write!(f, "{} and {} both bind {} port {}", first, second, protocol, port),
+            ConfigIssue::InstanceConflict { first, second, service_id, reason } => write!(f, "{} and {} both offer service 0x{:04x} {}", first, second, service_id, reason),
+            ConfigIssue::UndefinedVariable { at, name } => write!(f, "{}: environment variable '{}' is not set; set it or give a default as ${{{}:-default}}", at, name, name),
+            ConfigIssue::MalformedVariable { at, text } => write!(f, "{}: '${{' without a closing '}}' in '{}'", at, text),
+            ConfigIssue::InvalidInclude { at } => write!(f, "{}: expected a file name or a list of file names", at),
+            ConfigIssue::InvalidValue { at, value, expected } => write!(f, "{}: expected {}, found {}", at, expected, value),
+        }
+    }
+}
+
+/// Why a configuration could not be used.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The file is not JSON of the configuration's structure
+    Parse(serde_json::Error),
+    /// A file included by the configuration could not be used
+    Include { path: String, source: Box<ConfigError> },
+    /// A file includes itself, directly or through other files
+    IncludeCycle { path: String },
+    /// The file does not define the instance
+    UnknownInstance { name: String, known: Vec<String> },
+    /// Everything wrong with the instance's part of the configuration
+    Invalid(Vec<ConfigIssue>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "cannot read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "invalid config: {}", e),
+            ConfigError::Include { path, source } => write!(f, "in included {}: {}", path, source),
+            ConfigError::IncludeCycle { path } => write!(f, "{} includes itself, directly or through other files", path),
+            ConfigError::UnknownInstance { name, known } => write!(f, "instance '{}' not found in config ({})", name, defined(known)),
+            ConfigError::Invalid(issues) => {
+                write!(f, "{} problem(s) in config:", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Include { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+/// Read `path` with its variables and includes resolved, parse it, and validate the part
+/// `instance_name` uses.
+pub fn read_config(path: &str, instance_name: &str) -> Result<SystemConfig, ConfigError> {
+    let system: SystemConfig = serde_json::from_value(overrides::resolve(Path::new(path))?)?;
+    system.validate(instance_name)?;
+    Ok(system)
+}
+
+fn sorted<S: AsRef<str>>(names: impl Iterator<Item = S>) -> Vec<String> {
+    let mut names: Vec<String> = names.map(|name| name.as_ref().to_string()).collect();
+    names.sort();
+    names
+}
+
+impl SystemConfig {
+    /// Check everything `instance_name` references, and the endpoints of the interfaces it uses.
+    pub fn validate(&self, instance_name: &str) -> Result<(), ConfigError> {
+        let Some(instance) = self.instances.get(instance_name) else {
+            return Err(ConfigError::UnknownInstance { name: instance_name.to_string(), known: sorted(self.instances.keys()) });
+        };
+        let mut checker = Checker::new(self, instance);
+        checker.instance(&format!("instances.{}", instance_name), instance);
+        if checker.issues.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(checker.issues)) }
+    }
+}
+
+struct Checker<'a> {
+    system: &'a SystemConfig,
+    /// Endpoints in scope by name, with the path of their definition
+    endpoints: HashMap<&'a str, (String, &'a EndpointConfig)>,
+    /// Path and definition of the endpoints bound, once each
+    bound: Vec<(String, &'a EndpointConfig)>,
+    issues: Vec<ConfigIssue>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(system: &'a SystemConfig, instance: &InstanceConfig) -> Self {
+        let mut checker = Checker { system, endpoints: HashMap::new(), bound: Vec::new(), issues: Vec::new() };
+        let mut definitions: Vec<(String, &'a str, &'a EndpointConfig)> = system.endpoints.iter()
+            .map(|(name, ep)| (format!("endpoints.{}", name), name.as_str(), ep))
+            .collect();
+        for alias in binding::interfaces(system, instance) {
+            let Some((alias, iface)) = system.interfaces.get_key_value(&alias) else { continue };
+            definitions.extend(iface.endpoints.iter().map(|(name, ep)| (format!("interfaces.{}.endpoints.{}", alias, name), name.as_str(), ep)));
+            checker.sd(alias, iface);
+            if let Some(subnet) = &iface.subnet
+                && Subnet::parse(subnet).is_none() {
+                checker.issues.push(ConfigIssue::InvalidValue { at: format!("interfaces.{}.subnet", alias), value: format!("\"{}\"", subnet), expected: "a network such as 192.168.1.0/24" });
+            }
+        }
+        definitions.sort_by(|a, b| a.0.cmp(&b.0));
+        for (at, name, ep) in definitions {
+            checker.definition(&at, ep);
+            checker.endpoints.insert(name, (at, ep));
+        }
+        checker
+    }
+
+    fn definition(&mut self, at: &str, ep: &EndpointConfig) {
+        if !ep.protocol.eq_ignore_ascii_case("udp") && !ep.is_stream() && !ep.is_dtls() {
+            self.issues.push(ConfigIssue::InvalidProtocol { at: format!("{}.protocol", at), protocol: ep.protocol.clone() });
+        }
+        if ep.is_tls() && cfg!(not(feature = "tls")) {
+            self.issues.push(ConfigIssue::FeatureRequired { at: format!("{}.protocol", at), feature: "tls" });
+        } else if ep.is_dtls() && cfg!(not(feature = "dtls")) {
+            self.issues.push(ConfigIssue::FeatureRequired { at: format!("{}.protocol", at), feature: "dtls" });
+        } else if (ep.is_tls() || ep.is_dtls()) && ep.tls.is_none() {
+            self.issues.push(ConfigIssue::InvalidValue { at: format!("{}.tls", at), value: "nothing".to_string(), expected: "the endpoint's certificate files" });
+        }
+        // Room for the SOME/IP and TP headers and one 16-byte segment
+        if let Some(mtu) = ep.mtu.filter(|&mtu| mtu < MIN_MTU) {
+            self.issues.push(ConfigIssue::InvalidValue { at: format!("{}.mtu", at), value: mtu.to_string(), expected: "at least 36 bytes" });
+        }
+        // Larger datagrams cannot be sent, nor received whole
+        if let Some(mtu) = ep.mtu.filter(|&mtu| mtu > MAX_MTU) {
+            self.issues.push(ConfigIssue::InvalidValue { at: format!("{}.mtu", at), value: mtu.to_string(), expected: "at most 65507 bytes" });
+        }
+        if ep.tp_burst == Some(0) {
+            self.issues.push(ConfigIssue::InvalidValue { at: format!("{}.tp_burst", at), value: "0".to_string(), expected: "at least 1 segment" });
+        }
+        // Interface endpoints may take their address from the host's interface
+        if ep.ip == netif::AUTO && at.starts_with("interfaces.") {
+            if ep.version != 4 && ep.version != 6 {
+                self.issues.push(ConfigIssue::VersionMismatch { at: format!("{}.version", at), ip: ep.ip.clone(), version: ep.version });
+            }
+            return;
+        }
+        match ep.ip.parse::<IpAddr>() {
+            Ok(ip) if (ip.is_ipv4() && ep.version == 4) || (ip.is_ipv6() && ep.version == 6) => {}
+            Ok(_) => self.issues.push(ConfigIssue::VersionMismatch { at: format!("{}.version", at), ip: ep.ip.clone(), version: ep.version }),
+            Err(_) => self.issues.push(ConfigIssue::InvalidAddress { at: format!("{}.ip", at), ip: ep.ip.clone() }),
+        }
+    }
+
+    fn sd(&mut self, alias: &str, iface: &InterfaceConfig) {
+        let Some(sd) = &iface.sd else { return };
+        for (key, name) in [("endpoint_v4", &sd.endpoint_v4), ("endpoint_v6", &sd.endpoint_v6)] {
+            let Some(name) = name else { continue };
+            let at = format!("interfaces.{}.sd.{}", alias, key);
+            match iface.endpoints.get(name) {
+                Some(ep) => self.multicast(&at, ep),
+                None => self.issues.push(ConfigIssue::UnknownEndpoint { at, name: name.clone(), known: sorted(iface.endpoints.keys()) }),
+            }
+        }
+    }
+
+    fn multicast(&mut self, at: &str, ep: &EndpointConfig) {
+        let reason = if !ep.ip.parse::<IpAddr>().is_ok_and(|ip| ip.is_multicast()) {
+            "is not a multicast address"
+        } else if !ep.protocol.eq_ignore_ascii_case("udp") {
+            "must use udp"
+        } else if ep.port == 0 {
+            "needs a non-zero port"
+        } else {
+            return;
+        };
+        self.issues.push(ConfigIssue::InvalidMulticast { at: at.to_string(), ip: ep.ip.clone(), reason });
+    }
+
+    fn interface(&mut self, at: String, alias: &str) {
+        if !self.system.interfaces.contains_key(alias) {
+            self.issues.push(ConfigIssue::UnknownInterface { at, alias: alias.to_string(), known: sorted(self.system.interfaces.keys()) });
+        }
+    }
+
+    fn endpoint(&mut self, at: &str, name: &str) -> Option<&'a EndpointConfig> {
+        let found = self.endpoints.get(name).map(|(_, ep)| *ep);
+        if found.is_none() {
+            self.issues.push(ConfigIssue::UnknownEndpoint { at: at.to_string(), name: name.to_string(), known: sorted(self.endpoints.keys()) });
+        }
+        found
+    }
+
+    /// An endpoint a socket is bound on.
+    fn bindable(&mut self, at: &str, name: &str) {
+        let Some(ep) = self.endpoint(at, name) else { return };
+        match ep.ip.parse::<IpAddr>() {
+            Ok(ip) if ip.is_multicast() => self.issues.push(ConfigIssue::MulticastBind { at: at.to_string(), ip: ep.ip.clone() }),
+            Ok(_) => {
+                let definition = self.endpoints[name].0.clone();
+                if !self.bound.iter().any(|(other, _)| *other == definition) {
+                    self.bound.push((definition, ep));
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    fn qos(&mut self, at: String, name: &Option<String>) {
+        if let Some(name) = name
+            && !self.system.qos_profiles.contains_key(name) {
+            self.issues.push(ConfigIssue::UnknownQosProfile { at, name: name.clone(), known: sorted(self.system.qos_profiles.keys()) });
+        }
+    }
+
+    fn instance(&mut self, at: &str, instance: &InstanceConfig) {
+        for (alias, name) in sorted_entries(&instance.unicast_bind) {
+            let at = format!("{}.unicast_bind.{}", at, alias);
+            self.interface(at.clone(), alias);
+            self.bindable(&at, name);
+        }
+        if let Some(name) = &instance.endpoint {
+            self.bindable(&format!("{}.endpoint", at), name);
+        }
+        for alias in &instance.interfaces {
+            self.interface(format!("{}.interfaces", at), alias);
+        }
+        for (alias, svc) in sorted_entries(&instance.providing) {
+            let at = format!("{}.providing.{}", at, alias);
+            for (iface, name) in sorted_entries(&svc.offer_on) {
+                let at = format!("{}.offer_on.{}", at, iface);
+                self.interface(at.clone(), iface);
+                self.bindable(&at, name);
+            }
+            if let Some(name) = &svc.endpoint {
+                self.endpoint(&format!("{}.endpoint", at), name);
+            }
+            for iface in &svc.interfaces {
+                self.interface(format!("{}.interfaces", at), iface);
+            }
+            if let Some(name) = &svc.multicast {
+                let at = format!("{}.multicast", at);
+                if let Some(ep) = self.endpoint(&at, name) {
+                    self.multicast(&at, ep);
+                }
+            }
+            self.qos(format!("{}.qos", at), &svc.qos);
+        }
+        for (alias, req) in sorted_entries(&instance.required) {
+            let at = format!("{}.required.{}", at, alias);
+            for iface in &req.find_on {
+                self.interface(format!("{}.find_on", at), iface);
+            }
+            if let Some(name) = &req.endpoint {
+                self.endpoint(&format!("{}.endpoint", at), name);
+            }
+            if let Some(name) = &req.local_endpoint {
+                let at = format!("{}.local_endpoint", at);
+                // TCP local endpoints are bound per connection
+                match self.endpoints.get(name.as_str()) {
+                    Some((_, ep)) if ep.is_stream() => {}
+                    _ => self.bindable(&at, name),
+                }
+            }
+            if let Some(limit) = req.request_limit.as_ref().filter(|limit| limit.max_in_flight == 0) {
+                self.issues.push(ConfigIssue::InvalidValue { at: format!("{}.request_limit.max_in_flight", at), value: limit.max_in_flight.to_string(), expected: "at least 1 request" });
+            }
+            self.qos(format!("{}.qos", at), &req.qos);
+        }
+        self.instance_conflicts(at, instance);
+        self.port_conflicts();
+    }
+
+    /// Instances of one service are told apart by the endpoint a request arrives at.
+    fn instance_conflicts(&mut self, at: &str, instance: &InstanceConfig) {
+        let providing = sorted_entries(&instance.providing);
+        for (i, (first, a)) in providing.iter().enumerate() {
+            for (second, b) in providing[i + 1..].iter().filter(|(_, b)| b.service_id == a.service_id) {
+                let reason = if a.instance_id == b.instance_id {
+                    "as the same instance"
+                } else if a.offer_on.values().any(|name| b.offer_on.values().any(|other| other == name)) {
+                    "at the same endpoint, so requests cannot be told apart"
+                } else {
+                    continue;
+                };
+                self.issues.push(ConfigIssue::InstanceConflict {
+                    first: format!("{}.providing.{}", at, first),
+                    second: format!("{}.providing.{}", at, second),
+                    service_id: a.service_id,
+                    reason,
+                });
+            }
+        }
+    }
+
+    fn port_conflicts(&mut self) {
+        for (i, (first, a)) in self.bound.iter().enumerate() {
+            for (second, b) in &self.bound[i + 1..] {
+                let (Ok(ip_a), Ok(ip_b)) = (a.ip.parse::<IpAddr>(), b.ip.parse::<IpAddr>()) else { continue };
+                // The same address is bound once and shared
+                if a.port == 0 || a.port != b.port || a.is_stream() != b.is_stream()
+                    || ip_a == ip_b || ip_a.is_ipv4() != ip_b.is_ipv4() || !(ip_a.is_unspecified() || ip_b.is_unspecified()) {
+                    continue;
+                }
+                self.issues.push(ConfigIssue::PortConflict { first: first.clone(), second: second.clone(), protocol: a.protocol.to_ascii_lowercase(), port: a.port });
+            }
+        }
+    }
+}
+
+fn sorted_entries<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<(&String, &V)> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::tests::runtime_with;
+
+    fn system(json: &str) -> SystemConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let system = system(r#"{
+            "endpoints": { "any": { "ip": "0.0.0.0", "port": 30500, "version": 4, "protocol": "udp" } },
+            "interfaces": { "lo": { "name": "lo", "sd": { "endpoint_v4": "sd", "endpoint_v6": "sd6" }, "endpoints": {
+                "ctrl": { "ip": "127.0.0.1", "port": 30500, "version": 4, "protocol": "udp" },
+                "data": { "ip": "127.0.0.1", "port": 0, "version": 6, "protocol": "sctp" },
+                "sd": { "ip": "127.0.0.1", "port": 30490, "version": 4, "protocol": "udp" },
+                "group": { "ip": "239.0.0.1", "port": 30501, "version": 4, "protocol": "udp", "mtu": 20, "tp_burst": 0 } } } },
+            "instances": { "test": {
+                "unicast_bind": { "lo": "ctrl" },
+                "endpoint": "any",
+                "providing": { "svc": { "service_id": 1, "instance_id": 1, "major_version": 1,
+                    "offer_on": { "lo": "group", "eth1": "data" }, "multicast": "ctrl", "qos": "fast" } },
+                "required": { "peer": { "service_id": 2, "instance_id": 1, "major_version": 1, "local_endpoint": "nowhere", "request_limit": { "max_in_flight": 0 } } }
+            } }
+        }"#);
+        let Err(ConfigError::Invalid(issues)) = system.validate("test") else { panic!("expected issues") };
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, [
+            "interfaces.lo.sd.endpoint_v4: multicast endpoint '127.0.0.1' is not a multicast address",
+            "interfaces.lo.sd.endpoint_v6: endpoint 'sd6' not found (defined: ctrl, data, group, sd)",
+            "interfaces.lo.endpoints.data.protocol: protocol 'sctp' is not 'udp', 'tcp', 'tls' or 'dtls'",
+            "interfaces.lo.endpoints.data.version: version 6 does not match address '127.0.0.1'",
+            "interfaces.lo.endpoints.group.mtu: expected at least 36 bytes, found 20",
+            "interfaces.lo.endpoints.group.tp_burst: expected at least 1 segment, found 0",
+            "instances.test.providing.svc.offer_on.eth1: interface 'eth1' not found (defined: lo)",
+            "instances.test.providing.svc.offer_on.lo: '239.0.0.1' is a multicast address and cannot be bound; use a unicast endpoint",
+            "instances.test.providing.svc.multicast: multicast endpoint '127.0.0.1' is not a multicast address",
+            "instances.test.providing.svc.qos: QoS profile 'fast' not found (none defined)",
+            "instances.test.required.peer.local_endpoint: endpoint 'nowhere' not found (defined: any, ctrl, data, group, sd)",
+            "instances.test.required.peer.request_limit.max_in_flight: expected at least 1 request, found 0",
+            "interfaces.lo.endpoints.ctrl and endpoints.any both bind udp port 30500",
+        ]);
+    }
+
+    #[test]
+    fn test_instances_of_one_service_need_their_own_endpoint() {
+        let system = system(r#"{
+            "interfaces": { "lo": { "name": "lo", "endpoints": {
+                "a": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" },
+                "b": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" } } } },
+            "instances": { "test": { "providing": {
+                "one": { "service_id": 1, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "a" } },
+                "two": { "service_id": 1, "instance_id": 2, "major_version": 1, "offer_on": { "lo": "a" } },
+                "three": { "service_id": 1, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "b" } },
+                "other": { "service_id": 2, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "a" } } } } }
+        }"#);
+        let Err(ConfigError::Invalid(issues)) = system.validate("test") else { panic!("expected issues") };
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, [
+            "instances.test.providing.one and instances.test.providing.three both offer service 0x0001 as the same instance",
+            "instances.test.providing.one and instances.test.providing.two both offer service 0x0001 at the same endpoint, so requests cannot be told apart",
+        ]);
+    }
+
+    #[test]
+    fn test_mtu_bounded_by_udp_payload() {
+        let system = system(r#"{
+            "interfaces": { "lo": { "name": "lo", "endpoints": {
+                "jumbo": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp", "mtu": 9000 },
+                "huge": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp", "mtu": 70000 } } } },
+            "instances": { "test": {} }
+        }"#);
+        let Err(ConfigError::Invalid(issues)) = system.validate("test") else { panic!("expected issues") };
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, ["interfaces.lo.endpoints.huge.mtu: expected at most 65507 bytes, found 70000"]);
+    }
+
+    #[test]
+    fn test_tls_endpoints_need_certificates() {
+        let system = system(r#"{
+            "interfaces": { "lo": { "name": "lo", "endpoints": {
+                "secure": { "ip": "127.0.0.1", "port": 30500, "version": 4, "protocol": "tls" } } } },
+            "instances": { "test": {} }
+        }"#);
+        let Err(ConfigError::Invalid(issues)) = system.validate("test") else { panic!("expected issues") };
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        let expected = if cfg!(feature = "tls") {
+            "interfaces.lo.endpoints.secure.tls: expected the endpoint's certificate files, found nothing"
+        } else {
+            "interfaces.lo.endpoints.secure.protocol: requires fusion-hawking built with the 'tls' feature"
+        };
+        assert_eq!(issues, [expected]);
+    }
+
+    #[test]
+    fn test_dtls_endpoints_need_certificates() {
+        let system = system(r#"{
+            "interfaces": { "lo": { "name": "lo", "endpoints": {
+                "secure": { "ip": "127.0.0.1", "port": 30500, "version": 4, "protocol": "dtls" } } } },
+            "instances": { "test": {} }
+        }"#);
+        let Err(ConfigError::Invalid(issues)) = system.validate("test") else { panic!("expected issues") };
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        let expected = if cfg!(feature = "dtls") {
+            "interfaces.lo.endpoints.secure.tls: expected the endpoint's certificate files, found nothing"
+        } else {
+            "interfaces.lo.endpoints.secure.protocol: requires fusion-hawking built with the 'dtls' feature"
+        };
+        assert_eq!(issues, [expected]);
+    }
+
+    #[test]
+    fn test_valid_config_and_unknown_instance() {
+        let system = system(r#"{
+            "interfaces": { "lo": { "name": "lo", "sd": { "endpoint_v4": "sd" }, "endpoints": {
+                "ctrl": { "ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp" },
+                "data": { "ip": "127.0.0.1", "port": 30500, "version": 4, "protocol": "TCP" },
+                "sd": { "ip": "224.224.224.245", "port": 30490, "version": 4, "protocol": "udp" } } } },
+            "instances": { "server": {
+                "unicast_bind": { "lo": "ctrl" },
+                "providing": { "svc": { "service_id": 1, "instance_id": 1, "major_version": 1, "offer_on": { "lo": "data" } } }
+            } }
+        }"#);
+        assert!(system.validate("server").is_ok());
+        let err = system.validate("client").unwrap_err();
+        assert_eq!(err.to_string(), "instance 'client' not found in config (defined: server)");
+    }
+
+    #[test]
+    #[should_panic(expected = "instances.test.required.ctrl.qos: QoS profile 'missing' not found")]
+    fn test_unknown_qos_profile_rejected() {
+        runtime_with("qos_missing", r#"{ "instances": { "test": { "required": {
+            "ctrl": { "service_id": 4660, "instance_id": 1, "major_version": 1, "qos": "missing" }
+        } } } }"#);
+    }
+}
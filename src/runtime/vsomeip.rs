@@ -0,0 +1,408 @@
+//! # vsomeip Configuration
+//!
+//! [`read_vsomeip`] reads a `vsomeip.json` and maps it onto a [`SystemConfig`], so
+//! deployments migrating from vsomeip keep their configuration files.
+//! `SomeIpRuntime::try_load_vsomeip` loads a runtime from one directly.
+//!
+//! | vsomeip | SystemConfig |
+//! |---|---|
+//! | `unicast`, `device` | interface `primary`, with the control endpoint `unicast` on that address |
+//! | `applications[]` `name`, `id` | an instance per application, with `client_id` |
+//! | `services[]` `service`, `instance` | `providing` of every instance, as `0x1234_0x5678` |
+//! | `unreliable`, `reliable` | endpoints `udp_30509`, `tcp_30510`; a service with both is offered on UDP |
+//! | `multicast`, `eventgroups[]` | the service's `multicast` endpoint and `eventgroups` |
+//! | `clients[]` `service`, `instance` | `required` of every instance, as `0x1234_0x5678` |
+//! | `service-discovery` | SD endpoint `sd` on `primary` and the instances' `sd` timing |
+//!
+//! Numbers may be JSON numbers or strings, decimal or `0x` hex, as in vsomeip.
+//! Environment variables and includes are resolved as for `config.json`.
+//!
+//! vsomeip decides in code which services an application offers and requests,
+//! so every instance provides all `services` and requires all `clients`.
+//! Offered services have major version 0, vsomeip's default, and clients accept
+//! any major version. Settings without a counterpart (logging, routing, security,
+//! magic cookies, client port ranges, ...) are listed in [`VsomeipConfig::ignored`].
+
+use super::config::{ClientConfig, EndpointConfig, InstanceConfig, InterfaceConfig, OfferedEventgroupConfig, SdConfig, ServiceConfig, SystemConfig};
+use super::overrides;
+use super::validate::{ConfigError, ConfigIssue};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Interface alias the vsomeip `unicast` address is configured under
+pub const INTERFACE: &str = "primary";
+
+/// A vsomeip configuration mapped onto the runtime's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VsomeipConfig {
+    pub system: SystemConfig,
+    /// Settings of the file without a counterpart, by path (e.g. `services[0].events`)
+    pub ignored: Vec<String>,
+}
+
+/// Read the vsomeip configuration at `path`.
+pub fn read_vsomeip(path: &str) -> Result<VsomeipConfig, ConfigError> {
+    convert(&overrides::resolve(Path::new(path))?)
+}
+
+/// Map the JSON of a vsomeip configuration. Values that cannot be read are reported together.
+pub fn convert(value: &Value) -> Result<VsomeipConfig, ConfigError> {
+    let mut reader = Reader::default();
+    let system = reader.system(value);
+    if reader.issues.is_empty() {
+        Ok(VsomeipConfig { system, ignored: reader.ignored })
+    } else {
+        Err(ConfigError::Invalid(reader.issues))
+    }
+}
+
+/// `value` as quoted in issues; missing values are null.
+fn describe(value: &Value) -> String {
+    if value.is_null() { "nothing".to_string() } else { value.to_string() }
+}
+
+fn service_alias(service: u16, instance: u16) -> String {
+    format!("0x{:04x}_0x{:04x}", service, instance)
+}
+
+#[derive(Default)]
+struct Reader {
+    issues: Vec<ConfigIssue>,
+    ignored: Vec<String>,
+}
+
+impl Reader {
+    fn system(&mut self, root: &Value) -> SystemConfig {
+        let Some(root) = self.object(root, "") else { return SystemConfig::default() };
+        self.unused(root, &["unicast", "device", "applications", "services", "clients", "service-discovery"], "");
+
+        let unicast = match root.get("unicast") {
+            Some(value) => self.text(value, "unicast", "an IP address").unwrap_or_default(),
+            None => "127.0.0.1".to_string(),
+        };
+        let device = root.get("device").and_then(|value| self.text(value, "device", "a network device name")).unwrap_or_default();
+        let mut interface = InterfaceConfig::new(&device).endpoint("unicast", EndpointConfig::udp(&unicast, 0));
+
+        let mut template = InstanceConfig::default();
+        template.unicast_bind.insert(INTERFACE.to_string(), "unicast".to_string());
+        for (i, service) in self.list(root, "services", "").iter().enumerate() {
+            let at = format!("services[{}]", i);
+            if let Some((alias, config)) = self.service(service, &at, &unicast, &mut interface) {
+                template.providing.insert(alias, config);
+            }
+        }
+        for (i, client) in self.list(root, "clients", "").iter().enumerate() {
+            let at = format!("clients[{}]", i);
+            let Some(entry) = self.object(client, &at) else { continue };
+            self.unused(entry, &["service", "instance"], &at);
+            if let (Some(service), Some(instance)) = (self.id(entry, "service", &at), self.id(entry, "instance", &at)) {
+                template.required.insert(service_alias(service, instance), ClientConfig::new(service, instance, 0xFF).find_on(INTERFACE));
+            }
+        }
+        if let Some(sd) = root.get("service-discovery") {
+            self.sd(sd, &mut template.sd, &mut interface);
+        } else {
+            interface = interface.endpoint("sd", EndpointConfig::udp("224.244.224.245", 30490)).sd_v4("sd");
+        }
+
+        let mut system = SystemConfig::default();
+        for (i, application) in self.list(root, "applications", "").iter().enumerate() {
+            let at = format!("applications[{}]", i);
+            let Some(entry) = self.object(application, &at) else { continue };
+            self.unused(entry, &["name", "id"], &at);
+            let Some(name) = self.text(entry.get("name").unwrap_or(&Value::Null), &format!("{}.name", at), "an application name") else { continue };
+            let mut instance = template.clone();
+            instance.client_id = entry.get("id").and_then(|id| self.number(id, &format!("{}.id", at), "a 16-bit client ID"));
+            system.instances.insert(name, instance);
+        }
+        system.interfaces.insert(INTERFACE.to_string(), interface);
+        system
+    }
+
+    fn service(&mut self, service: &Value, at: &str, unicast: &str, interface: &mut InterfaceConfig) -> Option<(String, ServiceConfig)> {
+        let entry = self.object(service, at)?;
+        self.unused(entry, &["service", "instance", "unreliable", "reliable", "multicast", "eventgroups"], at);
+        let (service_id, instance_id) = (self.id(entry, "service", at), self.id(entry, "instance", at));
+        let mut config = ServiceConfig::new(service_id?, instance_id?, 0);
+
+        let udp = entry.get("unreliable").and_then(|port| self.number::<u16>(port, &format!("{}.unreliable", at), "a port"));
+        let tcp = entry.get("reliable").and_then(|reliable| {
+            let at = format!("{}.reliable", at);
+            match reliable {
+                Value::Object(fields) => {
+                    self.unused(fields, &["port"], &at);
+                    let port = fields.get("port");
+                    self.number::<u16>(port.unwrap_or(&Value::Null), &format!("{}.port", at), "a port")
+                }
+                port => self.number::<u16>(port, &at, "a port"),
+            }
+        });
+        if let Some(port) = udp {
+            let name = format!("udp_{}", port);
+            interface.endpoints.insert(name.clone(), EndpointConfig::udp(unicast, port));
+            config = config.offer_on(INTERFACE, &name);
+        }
+        // Offers name one endpoint per interface
+        match tcp {
+            Some(_) if udp.is_some() => self.ignored.push(format!("{}.reliable", at)),
+            Some(port) => {
+                let name = format!("tcp_{}", port);
+                interface.endpoints.insert(name.clone(), EndpointConfig::tcp(unicast, port));
+                config = config.offer_on(INTERFACE, &name);
+            }
+            None => {}
+        }
+
+        if let Some(group) = entry.get("multicast") {
+            config.multicast = self.multicast(group, &format!("{}.multicast", at), interface);
+        }
+        for (i, eventgroup) in self.list(entry, "eventgroups", at).iter().enumerate() {
+            let at = format!("{}.eventgroups[{}]", at, i);
+            let Some(fields) = self.object(eventgroup, &at) else { continue };
+            self.unused(fields, &["eventgroup", "events", "multicast", "threshold"], &at);
+            let Some(eventgroup_id) = self.id(fields, "eventgroup", &at) else { continue };
+            let events = self.list(fields, "events", &at).iter().enumerate()
+                .filter_map(|(j, event)| self.number(event, &format!("{}.events[{}]", at, j), "an event ID"))
+                .collect();
+            let multicast_threshold = fields.get("threshold").and_then(|n| self.number(n, &format!("{}.threshold", at), "a subscriber count")).unwrap_or(0);
+            // The runtime sends all events of a service to one group
+            if let Some(group) = fields.get("multicast") {
+                let endpoint = self.multicast(group, &format!("{}.multicast", at), interface);
+                if config.multicast.is_none() {
+                    config.multicast = endpoint;
+                } else if endpoint != config.multicast {
+                    self.ignored.push(format!("{}.multicast", at));
+                }
+            }
+            config.eventgroups.insert(format!("0x{:04x}", eventgroup_id), OfferedEventgroupConfig { eventgroup_id, events, multicast_threshold });
+        }
+        Some((service_alias(config.service_id, config.instance_id), config))
+    }
+
+    /// Define the multicast endpoint of `{ "address", "port" }` and return its name.
+    fn multicast(&mut self, group: &Value, at: &str, interface: &mut InterfaceConfig) -> Option<String> {
+        let fields = self.object(group, at)?;
+        self.unused(fields, &["address", "port"], at);
+        let address = self.text(fields.get("address").unwrap_or(&Value::Null), &format!("{}.address", at), "a multicast address");
+        let port = self.number::<u16>(fields.get("port").unwrap_or(&Value::Null), &format!("{}.port", at), "a port");
+        let (address, port) = (address?, port?);
+        let name = format!("multicast_{}_{}", address, port);
+        interface.endpoints.insert(name.clone(), EndpointConfig::udp(&address, port));
+        Some(name)
+    }
+
+    fn sd(&mut self, sd: &Value, config: &mut SdConfig, interface: &mut InterfaceConfig) {
+        let Some(fields) = self.object(sd, "service-discovery") else { return };
+        let known = ["enable", "multicast", "port", "protocol", "initial_delay_min", "initial_delay_max", "repetitions_base_delay",
+            "repetitions_max", "cyclic_offer_delay", "ttl", "request_response_delay"];
+        self.unused(fields, &known, "service-discovery");
+        let at = |key: &str| format!("service-discovery.{}", key);
+
+        let number = |reader: &mut Reader, key: &str, expected: &'static str| fields.get(key).and_then(|value| reader.number::<u64>(value, &at(key), expected));
+        if let Some(ms) = number(self, "initial_delay_min", "milliseconds") { config.initial_delay_min_ms = ms; }
+        if let Some(ms) = number(self, "initial_delay_max", "milliseconds") { config.initial_delay_max_ms = ms; }
+        if let Some(ms) = number(self, "repetitions_base_delay", "milliseconds") { config.repetition_base_delay_ms = ms; }
+        if let Some(ms) = number(self, "cyclic_offer_delay", "milliseconds") { config.cyclic_delay_ms = ms; }
+        if let Some(ms) = number(self, "request_response_delay", "milliseconds") {
+            config.request_response_delay_max_ms = ms;
+            config.request_response_delay_min_ms = config.request_response_delay_min_ms.min(ms);
+        }
+        if let Some(n) = fields.get("repetitions_max").and_then(|value| self.number(value, &at("repetitions_max"), "a repetition count")) {
+            config.repetition_max = n;
+        }
+        if let Some(seconds) = fields.get("ttl").and_then(|value| self.number(value, &at("ttl"), "seconds")) {
+            config.ttl = seconds;
+        }
+
+        if let Some(protocol) = fields.get("protocol") && protocol.as_str() != Some("udp") {
+            self.issues.push(ConfigIssue::InvalidValue { at: at("protocol"), value: describe(protocol), expected: "\"udp\"" });
+        }
+        let enabled = match fields.get("enable") {
+            None => true,
+            Some(value) => self.flag(value, &at("enable")).unwrap_or(true),
+        };
+        if !enabled {
+            return;
+        }
+        let address = match fields.get("multicast") {
+            Some(value) => self.text(value, &at("multicast"), "a multicast address"),
+            None => Some("224.244.224.245".to_string()),
+        };
+        let port = match fields.get("port") {
+            Some(value) => self.number::<u16>(value, &at("port"), "a port"),
+            None => Some(30490),
+        };
+        let (Some(address), Some(port)) = (address, port) else { return };
+        let v6 = address.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_ipv6());
+        interface.endpoints.insert("sd".to_string(), EndpointConfig::udp(&address, port));
+        let listener = interface.sd.get_or_insert_with(Default::default);
+        if v6 { listener.endpoint_v6 = Some("sd".to_string()) } else { listener.endpoint_v4 = Some("sd".to_string()) }
+    }
+
+    /// Record keys of `fields` other than `known` as ignored.
+    fn unused(&mut self, fields: &Map<String, Value>, known: &[&str], at: &str) {
+        let mut unknown: Vec<String> = fields.keys()
+            .filter(|key| !known.contains(&key.as_str()))
+            .map(|key| if at.is_empty() { key.clone() } else { format!("{}.{}", at, key) })
+            .collect();
+        unknown.sort();
+        self.ignored.extend(unknown);
+    }
+
+    fn object<'a>(&mut self, value: &'a Value, at: &str) -> Option<&'a Map<String, Value>> {
+        let fields = value.as_object();
+        if fields.is_none() {
+            let at = if at.is_empty() { "(top level)" } else { at };
+            self.issues.push(ConfigIssue::InvalidValue { at: at.to_string(), value: describe(value), expected: "an object" });
+        }
+        fields
+    }
+
+    /// Entries of the array `key` of `fields`; none if it is absent.
+    fn list<'a>(&mut self, fields: &'a Map<String, Value>, key: &str, at: &str) -> &'a [Value] {
+        match fields.get(key) {
+            None => &[],
+            Some(Value::Array(items)) => items,
+            Some(value) => {
+                let at = if at.is_empty() { key.to_string() } else { format!("{}.{}", at, key) };
+                self.issues.push(ConfigIssue::InvalidValue { at, value: describe(value), expected: "a list" });
+                &[]
+            }
+        }
+    }
+
+    fn text(&mut self, value: &Value, at: &str, expected: &'static str) -> Option<String> {
+        let text = value.as_str().map(str::to_string);
+        if text.is_none() {
+            self.issues.push(ConfigIssue::InvalidValue { at: at.to_string(), value: describe(value), expected });
+        }
+        text
+    }
+
+    fn flag(&mut self, value: &Value, at: &str) -> Option<bool> {
+        let flag = match value {
+            Value::Bool(flag) => Some(*flag),
+            Value::String(text) => text.parse().ok(),
+            _ => None,
+        };
+        if flag.is_none() {
+            self.issues.push(ConfigIssue::InvalidValue { at: at.to_string(), value: describe(value), expected: "true or false" });
+        }
+        flag
+    }
+
+    /// Service, instance or eventgroup ID `key` of `fields`, which is required.
+    fn id(&mut self, fields: &Map<String, Value>, key: &str, at: &str) -> Option<u16> {
+        self.number(fields.get(key).unwrap_or(&Value::Null), &format!("{}.{}", at, key), "a 16-bit ID")
+    }
+
+    /// A JSON number, or a string of a decimal or `0x` hex number, that fits `T`.
+    fn number<T: TryFrom<u64>>(&mut self, value: &Value, at: &str, expected: &'static str) -> Option<T> {
+        let number = match value {
+            Value::Number(n) => n.as_u64(),
+            Value::String(text) => match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                None => text.parse().ok(),
+            },
+            _ => None,
+        };
+        let number = number.and_then(|n| T::try_from(n).ok());
+        if number.is_none() {
+            self.issues.push(ConfigIssue::InvalidValue { at: at.to_string(), value: describe(value), expected });
+        }
+        number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::SomeIpRuntime;
+
+    const SAMPLE: &str = r#"{
+        "unicast": "192.168.56.101",
+        "device": "eth0",
+        "logging": { "level": "debug", "console": "true" },
+        "applications": [ { "name": "service-sample", "id": "0x1277" }, { "name": "client-sample", "id": 4966 } ],
+        "services": [
+            { "service": "0x1234", "instance": "0x5678", "unreliable": "30509",
+              "multicast": { "address": "224.225.226.233", "port": "32344" },
+              "eventgroups": [ { "eventgroup": "0x4465", "events": [ "0x8777", "0x8778" ], "threshold": "1" } ] },
+            { "service": "0x1235", "instance": "0x0001", "reliable": { "port": "30510", "enable-magic-cookies": "false" } }
+        ],
+        "clients": [ { "service": "0x2000", "instance": "1", "unreliable": [ 40000, 40002 ] } ],
+        "routing": "service-sample",
+        "service-discovery": { "enable": "true", "multicast": "224.244.224.245", "port": "30490", "protocol": "udp",
+            "initial_delay_min": "10", "initial_delay_max": "100", "repetitions_base_delay": "200", "repetitions_max": "3",
+            "ttl": "3", "cyclic_offer_delay": "2000", "request_response_delay": "1500" }
+    }"#;
+
+    #[test]
+    fn test_convert_sample() {
+        let converted = convert(&serde_json::from_str(SAMPLE).unwrap()).unwrap();
+        let file: SystemConfig = serde_json::from_str(r#"{
+            "interfaces": { "primary": { "name": "eth0", "sd": { "endpoint_v4": "sd" }, "endpoints": {
+                "unicast": { "ip": "192.168.56.101", "port": 0, "version": 4, "protocol": "udp" },
+                "udp_30509": { "ip": "192.168.56.101", "port": 30509, "version": 4, "protocol": "udp" },
+                "tcp_30510": { "ip": "192.168.56.101", "port": 30510, "version": 4, "protocol": "tcp" },
+                "multicast_224.225.226.233_32344": { "ip": "224.225.226.233", "port": 32344, "version": 4, "protocol": "udp" },
+                "sd": { "ip": "224.244.224.245", "port": 30490, "version": 4, "protocol": "udp" } } } },
+            "instances": {}
+        }"#).unwrap();
+        assert_eq!(converted.system.interfaces, file.interfaces);
+
+        let instance = &converted.system.instances["service-sample"];
+        assert_eq!(instance.client_id, Some(0x1277));
+        assert_eq!(converted.system.instances["client-sample"].client_id, Some(4966));
+        assert_eq!(instance.unicast_bind["primary"], "unicast");
+        let events = &instance.providing["0x1234_0x5678"];
+        assert_eq!((events.service_id, events.instance_id, events.major_version), (0x1234, 0x5678, 0));
+        assert_eq!(events.offer_on["primary"], "udp_30509");
+        assert_eq!(events.multicast.as_deref(), Some("multicast_224.225.226.233_32344"));
+        assert_eq!(events.eventgroups["0x4465"], OfferedEventgroupConfig { eventgroup_id: 0x4465, events: vec![0x8777, 0x8778], multicast_threshold: 1 });
+        assert_eq!(instance.providing["0x1235_0x0001"].offer_on["primary"], "tcp_30510");
+        assert_eq!(instance.required["0x2000_0x0001"], ClientConfig::new(0x2000, 1, 0xFF).find_on("primary"));
+        let sd = &instance.sd;
+        assert_eq!((sd.repetition_base_delay_ms, sd.repetition_max, sd.cyclic_delay_ms, sd.ttl), (200, 3, 2000, 3));
+        assert_eq!((sd.request_response_delay_min_ms, sd.request_response_delay_max_ms), (10, 1500));
+
+        assert_eq!(converted.ignored, ["logging", "routing", "services[1].reliable.enable-magic-cookies", "clients[0].unreliable"]);
+        assert!(converted.system.validate("client-sample").is_ok());
+    }
+
+    #[test]
+    fn test_convert_reports_bad_values() {
+        let value = serde_json::json!({
+            "applications": [ { "id": "0x10" } ],
+            "services": [ { "service": "0x1234", "instance": "0x10000", "unreliable": "port" } ],
+            "service-discovery": { "protocol": "tcp", "ttl": -1 }
+        });
+        let Err(ConfigError::Invalid(issues)) = convert(&value) else { panic!("expected invalid values") };
+        let messages: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(messages, [
+            "services[0].instance: expected a 16-bit ID, found \"0x10000\"",
+            "service-discovery.ttl: expected seconds, found -1",
+            "service-discovery.protocol: expected \"udp\", found \"tcp\"",
+            "applications[0].name: expected an application name, found nothing",
+        ]);
+    }
+
+    #[test]
+    fn test_load_vsomeip() {
+        let path = std::env::temp_dir().join(format!("fh_vsomeip_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{
+            "unicast": "127.0.0.1",
+            "applications": [ { "name": "echo", "id": "0x1001" } ],
+            "services": [ { "service": "0x4248", "instance": "1", "unreliable": 0 } ],
+            "service-discovery": { "enable": "false" }
+        }"#).unwrap();
+        let path = path.to_str().unwrap();
+        let runtime = SomeIpRuntime::try_load_vsomeip(path, "echo").unwrap();
+        assert!(runtime.bound_port("udp_0").is_some_and(|port| port != 0));
+        assert!(runtime.instance_config().unwrap().providing.contains_key("0x4248_0x0001"));
+
+        let Err(e) = SomeIpRuntime::try_load_vsomeip(path, "other") else { panic!("expected an unknown application") };
+        assert!(e.to_string().contains("echo"), "{}", e);
+        let _ = std::fs::remove_file(path);
+    }
+}
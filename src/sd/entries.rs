@@ -5,6 +5,7 @@ use std::io::{Result, Write, Read};
 /// [PRS_SOMEIPSD_00019] Service Entry
 /// [PRS_SOMEIPSD_00020] Eventgroup Entry
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum EntryType {
     /// Find Service Entry (Type 1)
@@ -56,6 +57,7 @@ impl From<EntryType> for u8 {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 pub struct SdEntry {
     pub entry_type: EntryType,
     pub index_1: u8,
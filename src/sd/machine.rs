@@ -1,16 +1,26 @@
 use super::packet::SdPacket;
 use super::entries::{SdEntry, EntryType};
 use super::options::SdOption;
-use crate::transport::{UdpTransport, SomeIpTransport};
+use crate::transport::SomeIpTransport;
 use crate::codec::{SomeIpSerialize, SomeIpDeserialize, SomeIpHeader};
 use crate::runtime::config::SdConfig;
-use std::net::{SocketAddr, Ipv4Addr};
-use std::collections::HashMap;
+use crate::runtime::access::AccessPolicy;
+use crate::clock::{Clock, SystemClock, Timestamp};
+use crate::trace;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
 
 pub const DEFAULT_SD_PORT: u16 = 30490;
+/// [PRS_SOMEIPSD_00540] Unicast flag: the sender accepts unicast SD messages
+const SD_FLAG_UNICAST: u8 = 0x40;
+/// [PRS_SOMEIPSD_00150] Reboot flag: set until the sender's Session ID wraps for the first time
+const SD_FLAG_REBOOT: u8 = 0x80;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 pub enum ServicePhase {
     /// [PRS_SOMEIPSD_00011] Down Phase
     Down,
@@ -22,11 +32,26 @@ pub enum ServicePhase {
     Main,
 }
 
+impl ServicePhase {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ServicePhase::Down => "down",
+            ServicePhase::InitialWait => "initial_wait",
+            ServicePhase::Repetition => "repetition",
+            ServicePhase::Main => "main",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct LocalService {
     pub entry: SdEntry, // Template entry
     pub endpoint_options: Vec<SdOption>,
     pub phase: ServicePhase,
+    /// Interface aliases this service is offered on
+    pub interfaces: Vec<String>,
+    /// Set when the service was taken down because all of its interfaces were disabled
+    pub suspended: bool,
     
     // Timer state
     pub phase_start: Instant,
@@ -54,6 +79,8 @@ impl LocalService {
             entry,
             endpoint_options: options,
             phase: ServicePhase::Down,
+            interfaces: Vec::new(),
+            suspended: false,
             phase_start: Instant::now(),
             next_transmission: Instant::now() + Duration::from_secs(3600), // Far future
             repetition_count: 0,
@@ -69,69 +96,421 @@ impl LocalService {
     }
 
     /// [PRS_SOMEIPSD_00012] Initial Wait Phase
-    pub(crate) fn transition_to_initial_wait(&mut self) {
+    pub(crate) fn transition_to_initial_wait(&mut self, now: Instant) {
         self.phase = ServicePhase::InitialWait;
-        self.phase_start = Instant::now();
+        self.phase_start = now;
         
-        // Random delay between min and max
-        let range = self.initial_delay_max.as_millis().saturating_sub(self.initial_delay_min.as_millis()) as u64;
-        let range = if range == 0 { 1 } else { range };
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
-        let seed = now.as_nanos() as u64;
-        // Simple LCG (Linear Congruential Generator) for better distribution than raw modulo
-        // Constants from MMIX via Knuth
-        let mut rng = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
-        rng = rng.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
-        let random_millis = self.initial_delay_min.as_millis() as u64 + (rng % range);
-        
-        self.next_transmission = Instant::now() + Duration::from_millis(random_millis);
+        self.next_transmission = now + random_delay(self.initial_delay_min, self.initial_delay_max);
+        self.trace_phase(now);
     }
 
     /// [PRS_SOMEIPSD_00013] Repetition Phase
-    pub(crate) fn transition_to_repetition(&mut self) {
+    pub(crate) fn transition_to_repetition(&mut self, now: Instant) {
         self.phase = ServicePhase::Repetition;
-        self.phase_start = Instant::now();
+        self.phase_start = now;
         self.repetition_count = 0;
-        self.next_transmission = Instant::now(); // Send immediately upon entering
+        self.next_transmission = now; // Send immediately upon entering
+        self.trace_phase(now);
     }
 
     /// [PRS_SOMEIPSD_00014] Main Phase
-    pub(crate) fn transition_to_main(&mut self) {
+    pub(crate) fn transition_to_main(&mut self, now: Instant) {
         self.phase = ServicePhase::Main;
-        self.phase_start = Instant::now();
-        self.next_transmission = Instant::now(); 
+        self.phase_start = now;
+        self.next_transmission = now;
+        self.trace_phase(now);
+    }
+
+    /// [PRS_SOMEIPSD_00011] Down Phase (offer withdrawn)
+    pub(crate) fn transition_to_down(&mut self, now: Instant) {
+        self.phase = ServicePhase::Down;
+        self.phase_start = now;
+        self.trace_phase(now);
+    }
+
+    /// OfferService entry and options as currently advertised, leaving out endpoints on
+    /// disabled interfaces. The entry references all options from index 0.
+    fn offer(&self, disabled_ips: &[IpAddr]) -> (SdEntry, Vec<SdOption>) {
+        let mut entry = self.entry.clone();
+        entry.ttl = self.ttl;
+
+        let options: Vec<SdOption> = self.endpoint_options.iter()
+            .filter(|opt| match opt {
+                SdOption::Ipv4Endpoint { address, .. } => !disabled_ips.contains(&IpAddr::V4(*address)),
+                SdOption::Ipv6Endpoint { address, .. } => !disabled_ips.contains(&IpAddr::V6(*address)),
+                _ => true,
+            })
+            .cloned()
+            .collect();
+
+        entry.index_1 = 0;
+        entry.number_of_opts_1 = options.len() as u8;
+        entry.index_2 = 0;
+        entry.number_of_opts_2 = 0;
+        (entry, options)
+    }
+
+    /// Record a phase change on the shared timestamp scale, for correlation with logs and dumps.
+    fn trace_phase(&self, now: Instant) {
+        log::debug!(target: "SD", "[{}] Service 0x{:04x}.{:04x} entered {:?}",
+            Timestamp::from_instant(now), self.entry.service_id, self.entry.instance_id, self.phase);
+    }
+}
+
+/// Random delay between `min` and `max` (millisecond resolution).
+fn random_delay(min: Duration, max: Duration) -> Duration {
+    let range = max.as_millis().saturating_sub(min.as_millis()) as u64;
+    let range = if range == 0 { 1 } else { range };
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    // Simple LCG (Linear Congruential Generator) for better distribution than raw modulo
+    // Constants from MMIX via Knuth
+    let mut rng = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    rng = rng.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    min + Duration::from_millis(rng % range)
+}
+
+/// Time between renewals of a subscription with `ttl` seconds to live.
+fn renewal_interval(ttl: u32, percent: u32) -> Duration {
+    Duration::from_millis((ttl as u64 * 1000 * percent.clamp(1, 100) as u64 / 100).max(1))
+}
+
+/// Counters of SD packets received but not yet processed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct SdIngressStats {
+    /// Packets currently waiting for a later poll
+    pub backlog: usize,
+    /// Largest backlog seen
+    pub backlog_peak: usize,
+    pub processed: u64,
+    /// Packets left over at the end of a poll, counted once per poll they wait
+    pub carried_over: u64,
+    /// Polls that stopped at `max_packets_per_poll`
+    pub throttled_polls: u64,
+}
+
+/// Session ID and reboot flag of the SD messages we send [PRS_SOMEIPSD_00255].
+///
+/// The SD socket of an interface receives multicast and unicast alike, so receivers
+/// cannot tell the two apart; one counter covers all messages of this instance.
+#[derive(Debug)]
+pub(crate) struct SdSession {
+    next: u16,
+    rebooted: bool,
+}
+
+impl SdSession {
+    fn new() -> Self {
+        SdSession { next: 1, rebooted: true }
+    }
+
+    /// Session ID and flags for the next message. IDs run from 1 to 0xFFFF; the reboot
+    /// flag is cleared once they wrap.
+    fn advance(&mut self) -> (u16, u8) {
+        let current = (self.next, if self.rebooted { SD_FLAG_REBOOT } else { 0 });
+        if self.next == 0xFFFF {
+            self.next = 1;
+            self.rebooted = false;
+        } else {
+            self.next += 1;
+        }
+        current
     }
 }
 
+/// A remote node detected as rebooted from the reboot flag and Session ID of its SD messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerReboot {
+    /// Source address of the peer's SD messages
+    pub address: SocketAddr,
+    /// Services it offered, (ServiceId, InstanceId), now removed until offered again
+    pub services: Vec<(u16, u16)>,
+}
+
+/// A SubscribeEventgroupNack received for one of our subscriptions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionNack {
+    pub service_id: u16,
+    pub instance_id: u16,
+    pub eventgroup_id: u16,
+    /// Source address of the provider's SD message
+    pub provider: SocketAddr,
+}
+
+/// A subscriber admitted to one of our eventgroups (renewals excluded).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionAdded {
+    pub service_id: u16,
+    pub instance_id: u16,
+    pub eventgroup_id: u16,
+    pub subscriber: EventSubscriber,
+}
+
+/// Multicast endpoint a provider announced in the SubscribeEventgroupAck of one of our subscriptions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventMulticast {
+    pub service_id: u16,
+    pub eventgroup_id: u16,
+    pub group: SocketAddr,
+    /// Local address of the interface the ACK arrived on, to join the group on
+    pub interface: Option<IpAddr>,
+}
+
+/// A FindService we transmit until the service is offered or the repetitions run out.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingFind {
+    pub entry: SdEntry,
+    pub next_transmission: Instant,
+    /// FindService entries sent so far
+    pub sent: u32,
+}
+
+/// An OfferService owed to the sender of a FindService, sent once `due`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FindReply {
+    pub service: (u16, u16),
+    /// Interface the FindService arrived on
+    pub iface_alias: String,
+    /// Unicast destination, or `None` to answer via multicast
+    pub dest: Option<SocketAddr>,
+    pub due: Instant,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
 pub struct RemoteService {
     pub service_id: u16,
     pub instance_id: u16,
     pub version_major: u8,
     pub version_minor: u32,
     pub endpoint: Vec<SdOption>, // could be multiple options
+    #[cfg_attr(feature = "serde-types", serde(skip))]
     pub last_seen: Instant,
     pub ttl: u32,
 }
 
-#[derive(Debug)]
+impl RemoteService {
+    /// Value of a configuration item (SD Configuration option) attached to the offer.
+    /// Flag items without a value yield `Some("")`.
+    pub fn config(&self, key: &str) -> Option<String> {
+        self.config_items().into_iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// All configuration items attached to the offer, across all Configuration options.
+    pub fn config_items(&self) -> Vec<(String, String)> {
+        self.endpoint.iter().flat_map(|opt| opt.config_items()).collect()
+    }
+
+    /// Endpoints the service is offered at, with their transport protocol (0x06 TCP / 0x11 UDP).
+    pub fn endpoints(&self) -> Vec<(SocketAddr, u8)> {
+        self.endpoint.iter()
+            .filter_map(|opt| match opt {
+                SdOption::Ipv4Endpoint { address, port, transport_proto } => Some((SocketAddr::new(IpAddr::V4(*address), *port), *transport_proto)),
+                SdOption::Ipv6Endpoint { address, port, transport_proto } => Some((SocketAddr::new(IpAddr::V6(*address), *port), *transport_proto)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Time left at `now` of the TTL of the latest offer; `None` if it does not expire.
+    pub fn ttl_remaining(&self, now: Instant) -> Option<Duration> {
+        (self.ttl != TTL_INFINITE).then(|| (self.last_seen + Duration::from_secs(self.ttl as u64)).saturating_duration_since(now))
+    }
+}
+
+/// A service instance offered on the network, as listed by
+/// [`ServiceDiscovery::discovered_services`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct DiscoveredService {
+    pub service_id: u16,
+    pub instance_id: u16,
+    pub major_version: u8,
+    pub minor_version: u32,
+    /// Endpoints with their transport protocol (0x06 TCP / 0x11 UDP)
+    pub endpoints: Vec<(SocketAddr, u8)>,
+    /// TTL of the latest offer, in seconds
+    pub ttl: u32,
+    /// Time left of that TTL; `None` if the offer does not expire
+    pub ttl_remaining: Option<Duration>,
+}
+
+/// A remote endpoint subscribed to one of our eventgroups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventSubscriber {
+    pub endpoint: SocketAddr,
+    /// 0x06 TCP, 0x11 UDP (from the subscriber's endpoint option)
+    pub transport_proto: u8,
+}
+
+/// Progress of one of our eventgroup subscriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubscriptionState {
+    /// Sent, no answer yet
+    Pending,
+    Acked,
+    Nacked(NackReason),
+    /// Acknowledged once, but no renewal was acknowledged within the TTL
+    Expired,
+}
+
+/// SD TTL meaning "until the next reboot" [PRS_SOMEIPSD_00351]; never renewed or expired
+const TTL_INFINITE: u32 = 0xFFFFFF;
+
+/// Read-only view of the SD state, as returned by [`ServiceDiscovery::snapshot`].
+/// Times are relative to when it was taken.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct SdSnapshot {
+    /// Our offered services, ordered by service and instance ID
+    pub local_services: Vec<LocalServiceStatus>,
+    /// Discovered services, as listed by [`ServiceDiscovery::discovered_services`]
+    pub remote_services: Vec<DiscoveredService>,
+    /// Our eventgroup subscriptions, ordered by service and eventgroup ID
+    pub subscriptions: Vec<SubscriptionStatus>,
+}
+
+/// One of our offered services in an [`SdSnapshot`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct LocalServiceStatus {
+    pub service_id: u16,
+    pub instance_id: u16,
+    pub major_version: u8,
+    pub minor_version: u32,
+    pub phase: ServicePhase,
+    /// Interface aliases the service is offered on
+    pub interfaces: Vec<String>,
+    /// Time until the next OfferService; `None` while down
+    pub next_offer_in: Option<Duration>,
+}
+
+/// One of our eventgroup subscriptions in an [`SdSnapshot`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize))]
+pub struct SubscriptionStatus {
+    pub service_id: u16,
+    pub eventgroup_id: u16,
+    pub state: SubscriptionState,
+    /// Requested TTL in seconds; `None` for subscriptions that are not renewed
+    pub ttl: Option<u32>,
+    /// Time until the subscription is renewed
+    pub next_renewal_in: Option<Duration>,
+    /// Time left of the latest acknowledgement
+    pub ack_expires_in: Option<Duration>,
+}
+
+/// One of our eventgroup subscriptions, re-sent before the provider lets it lapse.
+#[derive(Debug, Clone)]
+pub(crate) struct ClientSubscription {
+    pub entry: SdEntry,
+    pub options: Vec<SdOption>,
+    pub next_renewal: Instant,
+    /// When the latest acknowledgement runs out
+    pub expires: Option<Instant>,
+}
+
+/// Why a subscription was not acknowledged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
+pub enum NackReason {
+    /// The provider answered with a SubscribeEventgroupNack (acknowledgement with TTL 0)
+    Rejected,
+    /// The provider stopped offering the service before answering
+    ServiceStopped,
+    /// Not sent: the service is not discovered, or not offered over the required transport
+    NotOffered,
+    /// Not sent: the TCP connection for a reliable subscription could not be opened
+    ConnectFailed,
+}
+
 pub struct SdListener {
     pub alias: String,
-    pub transport_v4: Option<UdpTransport>,
-    pub transport_v6: Option<UdpTransport>,
+    pub transport_v4: Option<Box<dyn SomeIpTransport>>,
+    pub transport_v6: Option<Box<dyn SomeIpTransport>>,
     pub multicast_group_v4: Option<SocketAddr>,
     pub multicast_group_v6: Option<SocketAddr>,
     pub local_ip_v4: Option<Ipv4Addr>,
     pub local_ip_v6: Option<std::net::Ipv6Addr>,
 }
 
+impl std::fmt::Debug for SdListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SdListener")
+            .field("alias", &self.alias)
+            .field("transport_v4", &self.transport_v4.as_ref().and_then(|t| t.local_addr().ok()))
+            .field("transport_v6", &self.transport_v6.as_ref().and_then(|t| t.local_addr().ok()))
+            .field("multicast_group_v4", &self.multicast_group_v4)
+            .field("multicast_group_v6", &self.multicast_group_v6)
+            .field("local_ip_v4", &self.local_ip_v4)
+            .field("local_ip_v6", &self.local_ip_v6)
+            .finish()
+    }
+}
+
 pub struct ServiceDiscovery {
     pub(crate) listeners: HashMap<String, SdListener>,
     pub(crate) local_services: HashMap<(u16, u16), LocalService>, // (ServiceId, InstanceId) -> Service
     pub(crate) remote_services: HashMap<(u16, u16), RemoteService>,
     // Event subscriptions: (ServiceId, EventgroupId) -> list of subscriber endpoints
-    pub(crate) subscriptions: HashMap<(u16, u16), Vec<SocketAddr>>,
-    pub(crate) pending_subscriptions: HashMap<(u16, u16), bool>,
+    pub(crate) subscriptions: HashMap<(u16, u16), Vec<EventSubscriber>>,
+    /// Our own subscriptions: (ServiceId, EventgroupId) -> state
+    pub(crate) pending_subscriptions: HashMap<(u16, u16), SubscriptionState>,
+    /// Our own subscriptions with a finite TTL, renewed from `poll`
+    pub(crate) client_subscriptions: HashMap<(u16, u16), ClientSubscription>,
+    /// Interface aliases taken out of service via `disable_interface`
+    pub(crate) disabled_interfaces: HashSet<String>,
+    /// Process offers that are our own, looped back via multicast
+    pub(crate) accept_self_offers: bool,
+    /// Time source for all SD timers
+    pub(crate) clock: Arc<dyn Clock>,
+    /// OfferService entries transmitted (StopOffers excluded)
+    pub(crate) offers_sent: AtomicU64,
+    /// OfferService entries received (StopOffers excluded)
+    pub(crate) offers_received: AtomicU64,
+    /// Maximum subscribers per offered service, across its eventgroups
+    pub(crate) subscriber_limits: HashMap<u16, usize>,
+    /// Subscriptions refused because a service was at its subscriber limit
+    pub(crate) subscriptions_rejected: HashMap<u16, u64>,
+    /// Allow-lists of offered services; subscriptions they refuse are NACKed
+    pub(crate) access_policies: HashMap<u16, Arc<AccessPolicy>>,
+    /// (major, minimum minor) version required of remote services, keyed by (service,
+    /// instance) with instance 0xFFFF for any; offers of other versions are kept but not resolved
+    pub(crate) required_versions: HashMap<(u16, u16), (u8, u32)>,
+    /// Eventgroups of offered services; subscriptions to other eventgroups are refused.
+    /// Services without an entry accept any eventgroup.
+    pub(crate) offered_eventgroups: HashMap<u16, HashSet<u16>>,
+    /// Multicast endpoints announced to UDP subscribers of our eventgroups, by (service, eventgroup)
+    pub(crate) eventgroup_multicast: HashMap<(u16, u16), SocketAddr>,
+    /// Window in which FindService entries are answered
+    pub(crate) request_response_delay: (Duration, Duration),
+    /// Offers owed to FindService senders
+    pub(crate) find_replies: Vec<FindReply>,
+    /// Active discovery: (ServiceId, InstanceId) -> FindService schedule
+    pub(crate) pending_finds: HashMap<(u16, u16), PendingFind>,
+    /// Phase timing of FindService repetitions
+    pub(crate) timing: SdConfig,
+    /// Received packets not yet processed, with Session ID, source and listener alias
+    pub(crate) backlog: VecDeque<(SdPacket, u16, SocketAddr, String)>,
+    /// Packets processed per poll and maximum backlog
+    pub(crate) ingress_limits: (usize, usize),
+    pub(crate) ingress_stats: SdIngressStats,
+    /// Session ID and reboot flag of our next SD message
+    pub(crate) session: std::sync::Mutex<SdSession>,
+    /// Last (Session ID, reboot flag) received from each peer
+    pub(crate) peer_sessions: HashMap<SocketAddr, (u16, bool)>,
+    /// SD source address of each remote service's latest offer
+    pub(crate) offer_sources: HashMap<(u16, u16), SocketAddr>,
+    /// Reboots detected since the last `take_peer_reboots`
+    pub(crate) peer_reboots: Vec<PeerReboot>,
+    /// NACKs received since the last `take_subscription_nacks`
+    pub(crate) subscription_nacks: Vec<SubscriptionNack>,
+    /// Multicast endpoints received since the last `take_event_multicast`
+    pub(crate) event_multicast: Vec<EventMulticast>,
+    /// Subscribers admitted since the last `take_subscriptions_added`
+    pub(crate) subscriptions_added: Vec<SubscriptionAdded>,
 }
 
 impl ServiceDiscovery {
@@ -142,9 +521,68 @@ impl ServiceDiscovery {
             remote_services: HashMap::new(),
             subscriptions: HashMap::new(),
             pending_subscriptions: HashMap::new(),
+            client_subscriptions: HashMap::new(),
+            disabled_interfaces: HashSet::new(),
+            accept_self_offers: false,
+            clock: Arc::new(SystemClock),
+            offers_sent: AtomicU64::new(0),
+            offers_received: AtomicU64::new(0),
+            subscriber_limits: HashMap::new(),
+            access_policies: HashMap::new(),
+            required_versions: HashMap::new(),
+            subscriptions_rejected: HashMap::new(),
+            offered_eventgroups: HashMap::new(),
+            eventgroup_multicast: HashMap::new(),
+            request_response_delay: (
+                Duration::from_millis(SdConfig::default().request_response_delay_min_ms),
+                Duration::from_millis(SdConfig::default().request_response_delay_max_ms),
+            ),
+            find_replies: Vec::new(),
+            pending_finds: HashMap::new(),
+            timing: SdConfig::default(),
+            backlog: VecDeque::new(),
+            ingress_limits: (SdConfig::default().max_packets_per_poll, SdConfig::default().max_backlog),
+            ingress_stats: SdIngressStats::default(),
+            session: std::sync::Mutex::new(SdSession::new()),
+            peer_sessions: HashMap::new(),
+            offer_sources: HashMap::new(),
+            peer_reboots: Vec::new(),
+            subscription_nacks: Vec::new(),
+            event_multicast: Vec::new(),
+            subscriptions_added: Vec::new(),
         }
     }
 
+    /// Replace the time source (e.g. with a virtual clock in simulations).
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Take the initial wait and repetition timing of FindService entries from `config`.
+    pub fn set_timing(&mut self, config: &SdConfig) {
+        self.timing = config.clone();
+    }
+
+    /// Answer FindService entries after a random delay between `min` and `max`.
+    pub fn set_request_response_delay(&mut self, min: Duration, max: Duration) {
+        self.request_response_delay = (min, max);
+    }
+
+    /// Process at most `max_packets_per_poll` received packets per `poll`, buffering up to
+    /// `max_backlog` for later polls. Further datagrams stay in the socket buffers.
+    pub fn set_ingress_limits(&mut self, max_packets_per_poll: usize, max_backlog: usize) {
+        self.ingress_limits = (max_packets_per_poll.max(1), max_backlog.max(max_packets_per_poll));
+    }
+
+    pub fn ingress_stats(&self) -> SdIngressStats {
+        SdIngressStats { backlog: self.backlog.len(), ..self.ingress_stats }
+    }
+
+    /// Choose whether our own offers, looped back via multicast, are processed as remote services.
+    pub fn set_accept_self_offers(&mut self, accept: bool) {
+        self.accept_self_offers = accept;
+    }
+
     pub fn add_listener(&mut self, listener: SdListener) {
         if let Some(ref t4) = listener.transport_v4 {
             let _ = t4.set_nonblocking(true);
@@ -208,43 +646,262 @@ impl ServiceDiscovery {
             minor_version: minor,
         };
 
+        // Offering an already-known service on a further interface extends it
+        if let Some(existing) = self.local_services.get_mut(&(service_id, instance_id))
+            && existing.phase != ServicePhase::Down
+        {
+            if !existing.interfaces.iter().any(|a| a == iface_alias) {
+                existing.interfaces.push(iface_alias.to_string());
+            }
+            for opt in options {
+                if !existing.endpoint_options.contains(&opt) {
+                    existing.endpoint_options.push(opt);
+                }
+            }
+            existing.entry.number_of_opts_1 = existing.endpoint_options.len() as u8;
+            return;
+        }
+
         let mut service = LocalService::new(entry, options);
+        service.interfaces.push(iface_alias.to_string());
         
-        // Start phase: Initial Wait
-        service.transition_to_initial_wait();
+        // Start phase: Initial Wait (unless the interface is currently out of service)
+        if self.disabled_interfaces.contains(iface_alias) {
+            service.suspended = true;
+        } else {
+            service.transition_to_initial_wait(self.clock.now());
+        }
         
         self.local_services.insert((service_id, instance_id), service);
     }
+
+    /// Attach configuration items (e.g. ECU name, build hash) to an offered service.
+    /// They are sent as a Configuration option with every subsequent offer, replacing
+    /// any previously attached items. Returns false if the service is not offered.
+    pub fn set_service_config(&mut self, service_id: u16, instance_id: u16, config: SdOption) -> bool {
+        let Some(service) = self.local_services.get_mut(&(service_id, instance_id)) else {
+            return false;
+        };
+        service.endpoint_options.retain(|opt| !matches!(opt, SdOption::Configuration { .. }));
+        service.endpoint_options.push(config);
+        service.entry.number_of_opts_1 = service.endpoint_options.len() as u8;
+        true
+    }
+
+    /// Take an interface out of service (maintenance mode).
+    ///
+    /// The SD listener on `iface_alias` stops processing and sending packets.
+    /// Services offered solely on this interface are withdrawn with a StopOffer
+    /// and suspended until `enable_interface` is called.
+    /// Returns false if no listener exists for the alias.
+    pub fn disable_interface(&mut self, iface_alias: &str) -> bool {
+        if !self.listeners.contains_key(iface_alias) {
+            return false;
+        }
+        if self.disabled_interfaces.contains(iface_alias) {
+            return true;
+        }
+
+        let mut stop_offers = Vec::new();
+        for service in self.local_services.values_mut() {
+            if !service.interfaces.iter().any(|a| a == iface_alias) {
+                continue;
+            }
+            let still_reachable = service.interfaces.iter()
+                .any(|a| a != iface_alias && !self.disabled_interfaces.contains(a));
+            if still_reachable || service.phase == ServicePhase::Down {
+                continue;
+            }
+            service.transition_to_down(self.clock.now());
+            service.suspended = true;
+
+            let mut entry = service.entry.clone();
+            entry.ttl = 0;
+            entry.index_1 = 0;
+            entry.number_of_opts_1 = service.endpoint_options.len() as u8;
+            entry.index_2 = 0;
+            entry.number_of_opts_2 = 0;
+            stop_offers.push((entry, service.endpoint_options.clone()));
+        }
+
+        // StopOffers go out on the interface being disabled before it falls silent
+        for (entry, options) in stop_offers {
+            let _ = self.send_packet_on(Some(iface_alias), entry, options);
+        }
+
+        self.backlog.retain(|(_, _, _, alias)| alias != iface_alias);
+        self.disabled_interfaces.insert(iface_alias.to_string());
+        true
+    }
+
+    /// Return an interface to service after `disable_interface`.
+    ///
+    /// Suspended services restart their offer phases from Initial Wait.
+    /// Returns false if no listener exists for the alias.
+    pub fn enable_interface(&mut self, iface_alias: &str) -> bool {
+        if !self.listeners.contains_key(iface_alias) {
+            return false;
+        }
+        if !self.disabled_interfaces.remove(iface_alias) {
+            return true;
+        }
+
+        let now = self.clock.now();
+        for service in self.local_services.values_mut() {
+            if service.suspended && service.interfaces.iter().any(|a| a == iface_alias) {
+                service.suspended = false;
+                service.transition_to_initial_wait(now);
+            }
+        }
+        true
+    }
+
+    /// Check whether an interface is currently in service.
+    pub fn is_interface_enabled(&self, iface_alias: &str) -> bool {
+        self.listeners.contains_key(iface_alias) && !self.disabled_interfaces.contains(iface_alias)
+    }
     
-    pub fn stop_offer_service(&mut self, service_id: u16, instance_id: u16) {
+    /// Withdraw an offer with a StopOffer and drop the subscribers of the service's
+    /// eventgroups, unless another instance of it is still offered. Returns false if the
+    /// service was not offered; it can be offered again with `offer_service`.
+    pub fn stop_offer_service(&mut self, service_id: u16, instance_id: u16) -> bool {
         // We need to mutate the service phase, then send a packet.
         // To avoid borrow issues, we separate the actions.
         let mut entry_to_send = None;
         let mut options_to_send = Vec::new();
 
-        if let Some(service) = self.local_services.get_mut(&(service_id, instance_id)) {
-            service.phase = ServicePhase::Down;
+        if let Some(service) = self.local_services.get_mut(&(service_id, instance_id))
+            && (service.phase != ServicePhase::Down || service.suspended)
+        {
+            service.transition_to_down(self.clock.now());
+            service.suspended = false;
             // Capture data for sending
             entry_to_send = Some(service.entry.clone());
             options_to_send = service.endpoint_options.clone();
         }
 
-        if let Some(mut entry) = entry_to_send {
-            // TTL 0 for StopOffer
-            entry.ttl = 0;
-            let _ = self.send_packet(entry, options_to_send);
+        let Some(mut entry) = entry_to_send else {
+            return false;
+        };
+        // TTL 0 for StopOffer
+        entry.ttl = 0;
+        let _ = self.send_packet(entry, options_to_send);
+
+        let still_offered = self.local_services.iter()
+            .any(|((sid, _), service)| *sid == service_id && (service.phase != ServicePhase::Down || service.suspended));
+        if !still_offered {
+            self.subscriptions.retain(|(sid, _), _| *sid != service_id);
         }
+        true
     }
     
+    /// Withdraw every service that is offered or about to be, e.g. on shutdown.
+    /// Returns how many were withdrawn.
+    pub fn stop_offer_all(&mut self) -> usize {
+        let keys: Vec<(u16, u16)> = self.local_services.keys().copied().collect();
+        keys.into_iter()
+            .filter(|(service_id, instance_id)| self.stop_offer_service(*service_id, *instance_id))
+            .count()
+    }
+
     pub fn find_service(&self, service_id: u16, instance_id: u16) -> Option<&RemoteService> {
         self.remote_services.get(&(service_id, instance_id))
     }
+
+    /// [PRS_SOMEIPSD_00351] Actively look for a service instead of waiting for its cyclic offer.
+    ///
+    /// After a random initial wait, a FindService is sent on all enabled interfaces and
+    /// repeated `repetition_max` times with doubling delays, stopping as soon as an offer
+    /// arrives. `instance_id` 0xFFFF, `major` 0xFF and `minor` 0xFFFFFFFF are wildcards, as is
+    /// `service_id` 0xFFFF, which asks for every service and repeats until out of repetitions.
+    /// Returns false if the service is already known.
+    pub fn find_service_request(&mut self, service_id: u16, instance_id: u16, major: u8, minor: u32) -> bool {
+        if self.is_discovered(service_id, instance_id) {
+            return false;
+        }
+        let now = self.clock.now();
+        let initial_wait = random_delay(
+            Duration::from_millis(self.timing.initial_delay_min_ms),
+            Duration::from_millis(self.timing.initial_delay_max_ms),
+        );
+        self.pending_finds.entry((service_id, instance_id)).or_insert_with(|| PendingFind {
+            entry: SdEntry {
+                entry_type: EntryType::FindService,
+                index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id, instance_id, major_version: major, ttl: self.timing.ttl, minor_version: minor,
+            },
+            next_transmission: now + initial_wait,
+            sent: 0,
+        });
+        true
+    }
+
+    fn is_discovered(&self, service_id: u16, instance_id: u16) -> bool {
+        self.remote_services.iter()
+            .any(|((sid, iid), remote)| *sid == service_id && (instance_id == 0xFFFF || *iid == instance_id) && self.is_compatible(remote))
+    }
+
+    /// Resolve only offers of `service_id`/`instance_id` with major version `major` and a minor
+    /// version of at least `minor`. Instance 0xFFFF applies to instances without a version of
+    /// their own; `major` 0xFF and `minor` 0xFFFFFFFF are wildcards.
+    pub fn set_required_version(&mut self, service_id: u16, instance_id: u16, major: u8, minor: u32) {
+        self.required_versions.insert((service_id, instance_id), (major, minor));
+    }
+
+    /// Forget the versions set with [`set_required_version`](Self::set_required_version).
+    pub fn clear_required_versions(&mut self) {
+        self.required_versions.clear();
+    }
+
+    /// Whether a discovered service is of the version required of it, if any.
+    pub fn is_compatible(&self, remote: &RemoteService) -> bool {
+        let required = self.required_versions.get(&(remote.service_id, remote.instance_id))
+            .or_else(|| self.required_versions.get(&(remote.service_id, 0xFFFF)));
+        required.is_none_or(|&(major, minor)| {
+            (major == 0xFF || remote.version_major == major) && (minor == 0xFFFF_FFFF || remote.version_minor >= minor)
+        })
+    }
+
+    /// The discovered instance of `service_id` offered at `endpoint`, if any; `instance_id`
+    /// 0xFFFF matches any instance.
+    pub fn find_service_at(&self, service_id: u16, instance_id: u16, endpoint: SocketAddr) -> Option<&RemoteService> {
+        self.remote_services.values().find(|remote| {
+            remote.service_id == service_id && (instance_id == 0xFFFF || remote.instance_id == instance_id) && remote.endpoint.iter().any(|opt| match opt {
+                SdOption::Ipv4Endpoint { address, port, .. } => SocketAddr::new(IpAddr::V4(*address), *port) == endpoint,
+                SdOption::Ipv6Endpoint { address, port, .. } => SocketAddr::new(IpAddr::V6(*address), *port) == endpoint,
+                _ => false,
+            })
+        })
+    }
+
+    /// Snapshot of all discovered services, ordered by service and instance ID.
+    pub fn remote_services(&self) -> Vec<RemoteService> {
+        let mut services: Vec<RemoteService> = self.remote_services.values().cloned().collect();
+        services.sort_by_key(|s| (s.service_id, s.instance_id));
+        services
+    }
+
+    /// Every discovered service instance, whatever its version, ordered by service and instance ID.
+    pub fn discovered_services(&self) -> Vec<DiscoveredService> {
+        let now = self.clock.now();
+        self.remote_services().into_iter()
+            .map(|remote| DiscoveredService {
+                service_id: remote.service_id,
+                instance_id: remote.instance_id,
+                major_version: remote.version_major,
+                minor_version: remote.version_minor,
+                endpoints: remote.endpoints(),
+                ttl: remote.ttl,
+                ttl_remaining: remote.ttl_remaining(now),
+            })
+            .collect()
+    }
     
     pub fn get_service(&self, service_id: u16, instance_id: u16) -> Option<(SocketAddr, u8)> {
         // [PRS_SOMEIPSD_00282] If instance_id is 0xFFFF, return first matching service_id
         if instance_id == 0xFFFF {
             for ((sid, _), remote) in &self.remote_services {
-                if *sid == service_id {
+                if *sid == service_id && self.is_compatible(remote) {
                      for opt in &remote.endpoint {
                          if let SdOption::Ipv4Endpoint { address, port, transport_proto } = opt {
                              return Some((SocketAddr::new(std::net::IpAddr::V4(*address), *port), *transport_proto));
@@ -256,7 +913,7 @@ impl ServiceDiscovery {
                 }
             }
         } else {
-            if let Some(remote) = self.remote_services.get(&(service_id, instance_id)) {
+            if let Some(remote) = self.remote_services.get(&(service_id, instance_id)).filter(|remote| self.is_compatible(remote)) {
                  for opt in &remote.endpoint {
                      if let SdOption::Ipv4Endpoint { address, port, transport_proto } = opt {
                          return Some((SocketAddr::new(std::net::IpAddr::V4(*address), *port), *transport_proto));
@@ -270,20 +927,43 @@ impl ServiceDiscovery {
         None
     }
 
-    pub fn subscribe_eventgroup(&mut self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str, port_v4: u16, port_v6: u16) {
-        let entry = SdEntry {
-            entry_type: EntryType::SubscribeEventgroup,
-            index_1: 0,
-            index_2: 0,
-            number_of_opts_1: 2,  
-            number_of_opts_2: 0,
-            service_id,
-            instance_id,
-            major_version: 0x01,
-            ttl,
-            minor_version: (eventgroup_id as u32) << 16,
-        };
+    /// Like [`get_service`](Self::get_service), but prefer an endpoint using `transport_proto`
+    /// (0x06 TCP / 0x11 UDP) when the service is offered over more than one transport.
+    pub fn get_service_preferring(&self, service_id: u16, instance_id: u16, transport_proto: u8) -> Option<(SocketAddr, u8)> {
+        let endpoints = self.remote_services.iter()
+            .filter(|((sid, iid), remote)| *sid == service_id && (instance_id == 0xFFFF || *iid == instance_id) && self.is_compatible(remote))
+            .flat_map(|(_, remote)| remote.endpoint.iter())
+            .filter_map(|opt| match opt {
+                SdOption::Ipv4Endpoint { address, port, transport_proto } => Some((SocketAddr::new(std::net::IpAddr::V4(*address), *port), *transport_proto)),
+                SdOption::Ipv6Endpoint { address, port, transport_proto } => Some((SocketAddr::new(std::net::IpAddr::V6(*address), *port), *transport_proto)),
+                _ => None,
+            });
+        let mut fallback = None;
+        for ep in endpoints {
+            if ep.1 == transport_proto {
+                return Some(ep);
+            }
+            fallback.get_or_insert(ep);
+        }
+        fallback
+    }
+
+    /// Transport protocol a discovered service is offered with at `endpoint`.
+    /// UDP wins if the same address is offered over both.
+    pub fn endpoint_protocol(&self, service_id: u16, endpoint: SocketAddr) -> Option<u8> {
+        let protos: Vec<u8> = self.remote_services.iter()
+            .filter(|((sid, _), _)| *sid == service_id)
+            .flat_map(|(_, remote)| remote.endpoint.iter())
+            .filter_map(|opt| match opt {
+                SdOption::Ipv4Endpoint { address, port, transport_proto } if SocketAddr::new(std::net::IpAddr::V4(*address), *port) == endpoint => Some(*transport_proto),
+                SdOption::Ipv6Endpoint { address, port, transport_proto } if SocketAddr::new(std::net::IpAddr::V6(*address), *port) == endpoint => Some(*transport_proto),
+                _ => None,
+            })
+            .collect();
+        if protos.contains(&0x11) { Some(0x11) } else { protos.first().copied() }
+    }
 
+    pub fn subscribe_eventgroup(&mut self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, iface_alias: &str, port_v4: u16, port_v6: u16) {
         let mut opts = Vec::new();
         if let Some(listener) = self.listeners.get(iface_alias) {
             if let Some(ip_v4) = listener.local_ip_v4 {
@@ -302,27 +982,141 @@ impl ServiceDiscovery {
             }
         }
 
-        self.pending_subscriptions.insert((service_id, eventgroup_id), false);
+        self.send_subscribe(service_id, instance_id, eventgroup_id, ttl, opts);
+    }
+
+    /// Subscribe to an eventgroup of a TCP-offered service.
+    /// `endpoint` is the local address of the connection already opened to the provider,
+    /// which the provider uses to deliver notifications over that same connection.
+    pub fn subscribe_eventgroup_reliable(&mut self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, endpoint: SocketAddr) {
+        let opt = match endpoint {
+            SocketAddr::V4(a) => SdOption::Ipv4Endpoint { address: *a.ip(), transport_proto: 0x06, port: a.port() },
+            SocketAddr::V6(a) => SdOption::Ipv6Endpoint { address: *a.ip(), transport_proto: 0x06, port: a.port() },
+        };
+        self.send_subscribe(service_id, instance_id, eventgroup_id, ttl, vec![opt]);
+    }
+
+    fn send_subscribe(&mut self, service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32, opts: Vec<SdOption>) {
+        let _span = trace::sd_subscribe(service_id, instance_id, eventgroup_id, ttl).entered();
+        let entry = SdEntry {
+            entry_type: EntryType::SubscribeEventgroup,
+            index_1: 0,
+            index_2: 0,
+            number_of_opts_1: opts.len() as u8,
+            number_of_opts_2: 0,
+            service_id,
+            instance_id,
+            major_version: 0x01,
+            ttl,
+            minor_version: (eventgroup_id as u32) << 16,
+        };
+
+        self.pending_subscriptions.insert((service_id, eventgroup_id), SubscriptionState::Pending);
+        if ttl > 0 && ttl != TTL_INFINITE {
+            let next_renewal = self.clock.now() + renewal_interval(ttl, self.timing.subscription_renewal_percent);
+            self.client_subscriptions.insert((service_id, eventgroup_id), ClientSubscription { entry: entry.clone(), options: opts.clone(), next_renewal, expires: None });
+        } else {
+            self.client_subscriptions.remove(&(service_id, eventgroup_id));
+        }
         let _ = self.send_packet(entry, opts);
     }
 
+    /// Re-send subscriptions that are due for renewal, and mark acknowledged ones whose
+    /// acknowledgement ran out as expired. Renewals keep the current state; a renewal that
+    /// is acknowledged after expiry makes the subscription acknowledged again.
+    fn renew_subscriptions(&mut self, now: Instant) {
+        let percent = self.timing.subscription_renewal_percent;
+        let mut renewals = Vec::new();
+        for (key, sub) in self.client_subscriptions.iter_mut() {
+            if sub.expires.is_some_and(|t| now >= t) && self.pending_subscriptions.get(key) == Some(&SubscriptionState::Acked) {
+                self.pending_subscriptions.insert(*key, SubscriptionState::Expired);
+                log::warn!(target: "SD", "Subscription to Service 0x{:04x} EventGroup {} expired", key.0, key.1);
+            }
+            if now >= sub.next_renewal {
+                sub.next_renewal = now + renewal_interval(sub.entry.ttl, percent);
+                renewals.push((sub.entry.clone(), sub.options.clone()));
+            }
+        }
+        for (entry, options) in renewals {
+            let _ = self.send_packet(entry, options);
+        }
+    }
+
+    /// Get the subscribers currently registered for one of our eventgroups.
+    pub fn get_subscribers(&self, service_id: u16, eventgroup_id: u16) -> Vec<EventSubscriber> {
+        self.subscriptions.get(&(service_id, eventgroup_id)).cloned().unwrap_or_default()
+    }
+
     /// Unsubscribe from an eventgroup (sends SubscribeEventgroup with TTL=0).
     pub fn unsubscribe_eventgroup(&mut self, service_id: u16, instance_id: u16, eventgroup_id: u16, iface_alias: &str) {
         self.subscribe_eventgroup(service_id, instance_id, eventgroup_id, 0, iface_alias, 0, 0);
         self.pending_subscriptions.remove(&(service_id, eventgroup_id));
+        self.client_subscriptions.remove(&(service_id, eventgroup_id));
     }
 
     /// Check if subscription was acknowledged.
     pub fn is_subscription_acked(&self, service_id: u16, eventgroup_id: u16) -> bool {
-        self.pending_subscriptions.get(&(service_id, eventgroup_id)).copied().unwrap_or(false)
+        self.subscription_state(service_id, eventgroup_id) == Some(SubscriptionState::Acked)
+    }
+
+    /// State of our subscription to an eventgroup, None if never subscribed or unsubscribed.
+    pub fn subscription_state(&self, service_id: u16, eventgroup_id: u16) -> Option<SubscriptionState> {
+        self.pending_subscriptions.get(&(service_id, eventgroup_id)).copied()
+    }
+
+    /// Snapshot of our offers, the services discovered and our subscriptions, for debugging
+    /// and health checks.
+    pub fn snapshot(&self) -> SdSnapshot {
+        let now = self.clock.now();
+        let mut local_services: Vec<LocalServiceStatus> = self.local_services.values()
+            .map(|service| LocalServiceStatus {
+                service_id: service.entry.service_id,
+                instance_id: service.entry.instance_id,
+                major_version: service.entry.major_version,
+                minor_version: service.entry.minor_version,
+                phase: service.phase,
+                interfaces: service.interfaces.clone(),
+                next_offer_in: (service.phase != ServicePhase::Down).then(|| service.next_transmission.saturating_duration_since(now)),
+            })
+            .collect();
+        local_services.sort_by_key(|s| (s.service_id, s.instance_id));
+        let mut subscriptions: Vec<SubscriptionStatus> = self.pending_subscriptions.iter()
+            .map(|(&(service_id, eventgroup_id), &state)| {
+                let renewed = self.client_subscriptions.get(&(service_id, eventgroup_id));
+                SubscriptionStatus {
+                    service_id,
+                    eventgroup_id,
+                    state,
+                    ttl: renewed.map(|sub| sub.entry.ttl),
+                    next_renewal_in: renewed.map(|sub| sub.next_renewal.saturating_duration_since(now)),
+                    ack_expires_in: renewed.and_then(|sub| sub.expires).map(|expires| expires.saturating_duration_since(now)),
+                }
+            })
+            .collect();
+        subscriptions.sort_by_key(|s| (s.service_id, s.eventgroup_id));
+        SdSnapshot { local_services, remote_services: self.discovered_services(), subscriptions }
+    }
+
+    /// Record a subscription that could not be sent at all.
+    pub fn reject_subscription(&mut self, service_id: u16, eventgroup_id: u16, reason: NackReason) {
+        self.pending_subscriptions.insert((service_id, eventgroup_id), SubscriptionState::Nacked(reason));
     }
 
     pub fn poll(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
         let mut packets_to_send = Vec::new();
 
+        // Endpoints on disabled interfaces must not be advertised elsewhere
+        let disabled_ips: Vec<IpAddr> = self.listeners.values()
+            .filter(|l| self.disabled_interfaces.contains(&l.alias))
+            .flat_map(|l| {
+                l.local_ip_v4.map(std::net::IpAddr::V4).into_iter()
+                    .chain(l.local_ip_v6.map(std::net::IpAddr::V6))
+            })
+            .collect();
+
         // 1. Process Outgoing (Local Services)
-        for (_, service) in self.local_services.iter_mut() {
+        for (&(service_id, instance_id), service) in self.local_services.iter_mut() {
             if service.phase == ServicePhase::Down {
                 continue;
             }
@@ -335,14 +1129,14 @@ impl ServiceDiscovery {
                     ServicePhase::InitialWait => {
                         // Mistake in previous edit: `service.transition_to_repetition()`!
                         // Let's fix it here properly.
-                        service.transition_to_repetition();
+                        service.transition_to_repetition(now);
                         should_send = true; 
                     }
                     ServicePhase::Repetition => {
                         should_send = true;
                         service.repetition_count += 1;
                         if service.repetition_count > service.repetition_max {
-                            service.transition_to_main();
+                            service.transition_to_main(now);
                         } else {
                             // Schedule next repetition
                             let multiplier = 2u32.pow(service.repetition_count - 1);
@@ -358,73 +1152,270 @@ impl ServiceDiscovery {
                 }
                 
                 if should_send {
-                     // Use configured TTL from service
-                     let mut entry = service.entry.clone();
-                     entry.ttl = service.ttl;
-                     
-                     // Update Option Referencing
-                     // We are sending 1 entry with all options.
-                     // So options start at index 0.
-                     entry.index_1 = 0;
-                     entry.number_of_opts_1 = service.endpoint_options.len() as u8;
-                     entry.index_2 = 0;
-                     entry.number_of_opts_2 = 0;
-                     
-                     packets_to_send.push((entry, service.endpoint_options.clone()));
+                     packets_to_send.push((service_id, instance_id, service.phase, service.offer(&disabled_ips)));
                 }
             }
         }
 
         // Send accumulated packets
-        for (entry, options) in packets_to_send {
+        for (service_id, instance_id, phase, (entry, options)) in packets_to_send {
+            let _span = trace::sd_offer(service_id, instance_id, phase.name()).entered();
             let _ = self.send_packet(entry, options);
         }
 
+        // Answer FindService entries whose request_response_delay has elapsed
+        let (due, waiting): (Vec<FindReply>, Vec<FindReply>) = std::mem::take(&mut self.find_replies)
+            .into_iter()
+            .partition(|reply| reply.due <= now);
+        self.find_replies = waiting;
+        for reply in due {
+            let Some(service) = self.local_services.get(&reply.service) else { continue };
+            if service.phase == ServicePhase::Down || self.disabled_interfaces.contains(&reply.iface_alias) {
+                continue;
+            }
+            let (entry, options) = service.offer(&disabled_ips);
+            let _ = match reply.dest {
+                Some(dest) => self.send_unicast(&reply.iface_alias, dest, entry, options),
+                None => self.send_packet_on(Some(&reply.iface_alias), entry, options),
+            };
+        }
+
+        // FindService repetitions of services not yet discovered
+        let mut finds = Vec::new();
+        let pending: Vec<(u16, u16)> = self.pending_finds.keys().copied().collect();
+        for key in pending {
+            if self.is_discovered(key.0, key.1) {
+                self.pending_finds.remove(&key);
+                continue;
+            }
+            let find = self.pending_finds.get_mut(&key).unwrap();
+            if now < find.next_transmission {
+                continue;
+            }
+            finds.push((key, find.sent, find.entry.clone()));
+            find.sent += 1;
+            if find.sent > self.timing.repetition_max {
+                // Out of repetitions; the cyclic offer will still be picked up
+                self.pending_finds.remove(&key);
+            } else {
+                let base = Duration::from_millis(self.timing.repetition_base_delay_ms);
+                find.next_transmission = now + base * 2u32.pow(find.sent - 1);
+            }
+        }
+        for ((service_id, instance_id), repetition, entry) in finds {
+            let _span = trace::sd_find(service_id, instance_id, repetition).entered();
+            let _ = self.send_packet(entry, vec![]);
+        }
+
+        self.renew_subscriptions(now);
+
         // 2. Process Incoming
-        let mut incoming_packets = Vec::new();
+        self.receive_into_backlog();
 
-        // Separate transport polling to avoid borrow conflict
-        {
-            let mut buf = [0u8; 1500];
-            for listener in self.listeners.values() {
-                // Poll IPv4
+        let (max_per_poll, _) = self.ingress_limits;
+        let count = self.backlog.len().min(max_per_poll);
+        let batch: Vec<_> = self.backlog.drain(..count).collect();
+        if !self.backlog.is_empty() {
+            self.ingress_stats.throttled_polls += 1;
+            self.ingress_stats.carried_over += self.backlog.len() as u64;
+        }
+        self.ingress_stats.processed += count as u64;
+
+        for (mut packet, session_id, src, iface_alias) in batch {
+            self.track_peer_session(src, session_id, packet.flags);
+            if !self.accept_self_offers {
+                self.drop_self_offers(&mut packet, src);
+            }
+            self.handle_incoming_packet(packet, src, &iface_alias);
+        }
+    }
+
+    /// Time until `poll` next has something to send or expire: an offer, a FindService
+    /// repetition, a delayed answer to a FindService, or a subscription renewal or expiry.
+    /// Zero while received entries wait in the backlog; `None` if no timer is running.
+    pub fn until_next_timer(&self) -> Option<Duration> {
+        if !self.backlog.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        let offers = self.local_services.values()
+            .filter(|service| service.phase != ServicePhase::Down)
+            .map(|service| service.next_transmission);
+        let replies = self.find_replies.iter().map(|reply| reply.due);
+        let finds = self.pending_finds.values().map(|find| find.next_transmission);
+        let subscriptions = self.client_subscriptions.values()
+            .flat_map(|sub| std::iter::once(sub.next_renewal).chain(sub.expires));
+        let next = offers.chain(replies).chain(finds).chain(subscriptions).min()?;
+        Some(next.saturating_duration_since(self.clock.now()))
+    }
+
+    /// Descriptors of the SD sockets, to block until one of them has a datagram.
+    /// `None` if some listener's transport cannot be waited on.
+    #[cfg(target_os = "linux")]
+    pub fn poll_fds(&self) -> Option<Vec<std::os::fd::RawFd>> {
+        let mut fds = Vec::new();
+        for listener in self.listeners.values() {
+            for transport in [&listener.transport_v4, &listener.transport_v6].into_iter().flatten() {
+                fds.extend(transport.poll_fds()?);
+            }
+        }
+        Some(fds)
+    }
+
+    /// Read datagrams into the backlog until it is full or the sockets are drained.
+    /// Listeners take turns one datagram at a time, so a storm on one interface
+    /// cannot crowd out the others.
+    fn receive_into_backlog(&mut self) {
+        let mut buf = [0u8; 1500];
+        let (_, max_backlog) = self.ingress_limits;
+        for listener in self.listeners.values() {
+            if self.disabled_interfaces.contains(&listener.alias) {
+                // Interface in maintenance: discard whatever arrived so nothing stale
+                // is processed once it is enabled again
                 if let Some(ref t4) = listener.transport_v4 {
-                    while let Ok((len, _addr)) = t4.receive(&mut buf) {
-                        if len > 16 {
-                            let mut payload_reader = &buf[16..len];
-                            if let Ok(packet) = SdPacket::deserialize(&mut payload_reader) {
-                                #[cfg(feature = "packet-dump")]
-                                packet.dump(_addr);
-                                incoming_packets.push(packet);
-                            }
-                        }
-                    }
+                    while t4.receive(&mut buf).is_ok() {}
                 }
-                // Poll IPv6
                 if let Some(ref t6) = listener.transport_v6 {
-                    while let Ok((len, _addr)) = t6.receive(&mut buf) {
-                        if len > 16 {
-                            let mut payload_reader = &buf[16..len];
-                            if let Ok(packet) = SdPacket::deserialize(&mut payload_reader) {
-                                #[cfg(feature = "packet-dump")]
-                                packet.dump(_addr);
-                                incoming_packets.push(packet);
-                            }
-                        }
-                    }
+                    while t6.receive(&mut buf).is_ok() {}
                 }
             }
         }
 
-        for packet in incoming_packets {
-            self.handle_incoming_packet(packet);
+        let transports: Vec<(&dyn SomeIpTransport, &str)> = self.listeners.values()
+            .filter(|listener| !self.disabled_interfaces.contains(&listener.alias))
+            .flat_map(|listener| [listener.transport_v4.as_deref(), listener.transport_v6.as_deref()]
+                .into_iter()
+                .flatten()
+                .map(move |t| (t, listener.alias.as_str())))
+            .collect();
+        let mut active = vec![true; transports.len()];
+        while self.backlog.len() < max_backlog && active.contains(&true) {
+            for (i, (transport, alias)) in transports.iter().enumerate() {
+                if !active[i] || self.backlog.len() >= max_backlog {
+                    continue;
+                }
+                let Ok((len, addr)) = transport.receive(&mut buf) else {
+                    active[i] = false;
+                    continue;
+                };
+                if len > 16 && let Ok(header) = SomeIpHeader::deserialize(&buf[..16]) {
+                    let mut payload_reader = &buf[16..len];
+                    if let Ok(packet) = SdPacket::deserialize(&mut payload_reader) {
+                        #[cfg(feature = "packet-dump")]
+                        packet.dump(addr);
+                        self.backlog.push_back((packet, header.session_id, addr, alias.to_string()));
+                    }
+                }
+            }
         }
+        self.ingress_stats.backlog_peak = self.ingress_stats.backlog_peak.max(self.backlog.len());
     }
 
-    fn send_packet(&self, entry: SdEntry, options: Vec<SdOption>) -> std::io::Result<()> {
-        let packet = SdPacket {
-            flags: 0x80,
-            entries: vec![entry],
+    /// Whether `ip` is the unicast address of one of our listeners.
+    fn is_local_address(&self, ip: std::net::IpAddr) -> bool {
+        self.listeners.values().any(|l| match ip {
+            std::net::IpAddr::V4(a) => l.local_ip_v4 == Some(a),
+            std::net::IpAddr::V6(a) => l.local_ip_v6 == Some(a),
+        })
+    }
+
+    /// Remove offer entries that are our own transmissions looped back via multicast.
+    ///
+    /// An offer is ours when it comes from one of our listener addresses and matches a
+    /// service we offer, including its endpoints. Other instances on the same host share
+    /// the source address but offer different instances or endpoints, so they are kept.
+    fn drop_self_offers(&self, packet: &mut SdPacket, src: SocketAddr) {
+        if !self.is_local_address(src.ip()) {
+            return;
+        }
+        let options = &packet.options;
+        packet.entries.retain(|entry| {
+            if entry.entry_type != EntryType::OfferService {
+                return true;
+            }
+            let Some(local) = self.local_services.get(&(entry.service_id, entry.instance_id)) else {
+                return true;
+            };
+            let start = entry.index_1 as usize;
+            let end = start + entry.number_of_opts_1 as usize;
+            let offered = options.get(start..end).unwrap_or(&[]);
+            !offered.iter().all(|opt| local.endpoint_options.contains(opt))
+        });
+    }
+
+    /// [PRS_SOMEIPSD_00273] Detect a reboot of the peer at `src` from the Session ID and
+    /// reboot flag of its message: the flag went from 0 to 1, or stayed 1 while the Session
+    /// ID did not increase. Peers sharing one of our addresses are skipped: instances on
+    /// this host send from the same SD socket address, so their sessions interleave.
+    fn track_peer_session(&mut self, src: SocketAddr, session_id: u16, flags: u8) {
+        if self.is_local_address(src.ip()) {
+            return;
+        }
+        let reboot = flags & SD_FLAG_REBOOT != 0;
+        let previous = self.peer_sessions.insert(src, (session_id, reboot));
+        let rebooted = match previous {
+            Some((_, false)) => reboot,
+            Some((last, true)) => reboot && last >= session_id,
+            None => false,
+        };
+        if rebooted {
+            self.handle_peer_reboot(src);
+        }
+    }
+
+    /// Forget what a rebooted peer offered and subscribed to; it starts over.
+    fn handle_peer_reboot(&mut self, src: SocketAddr) {
+        let services: Vec<(u16, u16)> = self.offer_sources.iter()
+            .filter(|(key, addr)| addr.ip() == src.ip() && self.remote_services.contains_key(key))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &services {
+            self.remote_services.remove(key);
+            self.offer_sources.remove(key);
+        }
+        for ((service_id, _), state) in self.pending_subscriptions.iter_mut() {
+            if services.iter().any(|(sid, _)| sid == service_id) {
+                *state = SubscriptionState::Nacked(NackReason::ServiceStopped);
+            }
+        }
+        self.client_subscriptions.retain(|(service_id, _), _| !services.iter().any(|(sid, _)| sid == service_id));
+        for subscribers in self.subscriptions.values_mut() {
+            subscribers.retain(|sub| sub.endpoint.ip() != src.ip());
+        }
+        log::warn!(target: "SD", "Peer {} rebooted; removed {} remote service(s) and its subscriptions", src, services.len());
+        self.peer_reboots.push(PeerReboot { address: src, services });
+    }
+
+    /// Reboots detected since the last call, oldest first.
+    pub fn take_peer_reboots(&mut self) -> Vec<PeerReboot> {
+        std::mem::take(&mut self.peer_reboots)
+    }
+
+    /// Answer a received entry: unicast to `dest` from the listener on `iface_alias` if
+    /// given, else via multicast.
+    fn send_answer(&self, iface_alias: &str, dest: Option<SocketAddr>, entry: SdEntry, options: Vec<SdOption>) -> std::io::Result<()> {
+        match dest {
+            Some(dest) => self.send_unicast(iface_alias, dest, entry, options),
+            None => self.send_packet(entry, options),
+        }
+    }
+
+    fn send_packet(&self, entry: SdEntry, options: Vec<SdOption>) -> std::io::Result<()> {
+        self.send_packet_on(None, entry, options)
+    }
+
+    /// Serialize one entry and its options as the next SD message of this instance.
+    /// The unicast flag stays clear: on Linux the SD socket is bound to the multicast
+    /// group and cannot receive unicast, and instances on one host share the SD port.
+    fn encode_next(&self, entry: SdEntry, options: Vec<SdOption>) -> std::io::Result<Vec<u8>> {
+        let (session_id, flags) = self.session.lock().unwrap().advance();
+        Self::encode(entry, options, session_id, flags)
+    }
+
+    /// Serialize one entry and its options as an SD message.
+    fn encode(entry: SdEntry, options: Vec<SdOption>, session_id: u16, flags: u8) -> std::io::Result<Vec<u8>> {
+        let packet = SdPacket {
+            flags,
+            entries: vec![entry],
             options,
         };
 
@@ -433,7 +1424,7 @@ impl ServiceDiscovery {
         
         let header = SomeIpHeader::new(
             0xFFFF, 0x8100, 
-            0x0000, 0x0001, 
+            0x0000, session_id, 
             0x02, 
             payload.len() as u32
         );
@@ -441,9 +1432,35 @@ impl ServiceDiscovery {
         let mut message = Vec::new();
         message.extend_from_slice(&header.serialize());
         message.extend_from_slice(&payload);
+        Ok(message)
+    }
+
+    /// Send an SD packet to `dest` from the listener on `iface_alias`.
+    fn send_unicast(&self, iface_alias: &str, dest: SocketAddr, entry: SdEntry, options: Vec<SdOption>) -> std::io::Result<()> {
+        let is_offer = entry.entry_type == EntryType::OfferService && entry.ttl > 0;
+        let listener = self.listeners.get(iface_alias)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No SD listener on interface"))?;
+        let transport = if dest.is_ipv4() { listener.transport_v4.as_ref() } else { listener.transport_v6.as_ref() }
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No SD transport for destination address family"))?;
+        transport.send(&self.encode_next(entry, options)?, Some(dest))?;
+        if is_offer {
+            self.offers_sent.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Send an SD packet on one listener (`Some(alias)`) or on all enabled listeners (`None`).
+    fn send_packet_on(&self, iface_alias: Option<&str>, entry: SdEntry, options: Vec<SdOption>) -> std::io::Result<()> {
+        let is_offer = entry.entry_type == EntryType::OfferService && entry.ttl > 0;
+        let message = self.encode_next(entry, options)?;
         
         // Send on all listeners
         for listener in self.listeners.values() {
+            match iface_alias {
+                Some(alias) if listener.alias != alias => continue,
+                None if self.disabled_interfaces.contains(&listener.alias) => continue,
+                _ => {}
+            }
             if let Some(ref t4) = listener.transport_v4 {
                 if let Some(mcast_v4) = listener.multicast_group_v4 {
                     let _ = t4.send(&message, Some(mcast_v4));
@@ -455,19 +1472,102 @@ impl ServiceDiscovery {
                 }
             }
         }
+        if is_offer {
+            self.offers_sent.fetch_add(1, Ordering::Relaxed);
+        }
         Ok(())
     }
 
-    fn handle_incoming_packet(&mut self, packet: SdPacket) {
+    /// Number of OfferService entries sent so far.
+    pub fn offers_sent(&self) -> u64 {
+        self.offers_sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of OfferService entries received so far.
+    pub fn offers_received(&self) -> u64 {
+        self.offers_received.load(Ordering::Relaxed)
+    }
+
+    /// Number of subscribers currently registered across all of our eventgroups.
+    pub fn subscriber_count(&self) -> usize {
+        self.subscriptions.values().map(Vec::len).sum()
+    }
+
+    /// Subscribers currently registered across the eventgroups of `service_id`.
+    pub fn service_subscriber_count(&self, service_id: u16) -> usize {
+        self.subscriptions.iter().filter(|((sid, _), _)| *sid == service_id).map(|(_, subs)| subs.len()).sum()
+    }
+
+    /// Refuse (NACK) new subscribers of `service_id` once it has `max` of them.
+    pub fn set_subscriber_limit(&mut self, service_id: u16, max: usize) {
+        self.subscriber_limits.insert(service_id, max);
+    }
+
+    /// NACK subscriptions to `service_id` that `policy` does not allow.
+    pub fn set_access_policy(&mut self, service_id: u16, policy: Arc<AccessPolicy>) {
+        self.access_policies.insert(service_id, policy);
+    }
+
+    /// Subscriptions to `service_id` refused because of its subscriber limit.
+    pub fn subscriptions_rejected(&self, service_id: u16) -> u64 {
+        self.subscriptions_rejected.get(&service_id).copied().unwrap_or(0)
+    }
+
+    /// Restrict subscriptions to `service_id` to `eventgroups`; others are answered with a NACK.
+    pub fn set_offered_eventgroups(&mut self, service_id: u16, eventgroups: impl IntoIterator<Item = u16>) {
+        self.offered_eventgroups.insert(service_id, eventgroups.into_iter().collect());
+    }
+
+    /// Accept subscriptions to `eventgroup_id` of `service_id`, which from now on
+    /// refuses eventgroups not added here or with `set_offered_eventgroups`.
+    pub fn add_offered_eventgroup(&mut self, service_id: u16, eventgroup_id: u16) {
+        self.offered_eventgroups.entry(service_id).or_default().insert(eventgroup_id);
+    }
+
+    /// Announce `group` in the SubscribeEventgroupAcks of UDP subscribers to the eventgroup,
+    /// or stop announcing a multicast endpoint (`None`).
+    pub fn set_eventgroup_multicast(&mut self, service_id: u16, eventgroup_id: u16, group: Option<SocketAddr>) {
+        match group {
+            Some(group) => { self.eventgroup_multicast.insert((service_id, eventgroup_id), group); }
+            None => { self.eventgroup_multicast.remove(&(service_id, eventgroup_id)); }
+        }
+    }
+
+    /// Multicast endpoints announced for our subscriptions since the last call.
+    pub fn take_event_multicast(&mut self) -> Vec<EventMulticast> {
+        std::mem::take(&mut self.event_multicast)
+    }
+
+    /// NACKs of our subscriptions received since the last call.
+    pub fn take_subscription_nacks(&mut self) -> Vec<SubscriptionNack> {
+        std::mem::take(&mut self.subscription_nacks)
+    }
+
+    /// Subscribers admitted to our eventgroups since the last call, after their ACK was sent.
+    pub fn take_subscriptions_added(&mut self) -> Vec<SubscriptionAdded> {
+        std::mem::take(&mut self.subscriptions_added)
+    }
+
+    /// Process an SD packet received from `src` on the listener `iface_alias`.
+    fn handle_incoming_packet(&mut self, packet: SdPacket, src: SocketAddr, iface_alias: &str) {
+        // [PRS_SOMEIPSD_00540] Senders with the unicast flag set get answers unicast
+        let unicast_dest = (packet.flags & SD_FLAG_UNICAST != 0).then_some(src);
         // Iterate entries
         for entry in packet.entries {
             match entry.entry_type {
                 EntryType::OfferService => {
                     if entry.ttl == 0 {
-                        // Stop Offer -> Remove service
+                        // Stop Offer -> Remove service; unanswered subscriptions to it fail
                         self.remote_services.remove(&(entry.service_id, entry.instance_id));
+                        self.offer_sources.remove(&(entry.service_id, entry.instance_id));
+                        for ((service_id, _), state) in self.pending_subscriptions.iter_mut() {
+                            if *service_id == entry.service_id && *state == SubscriptionState::Pending {
+                                *state = SubscriptionState::Nacked(NackReason::ServiceStopped);
+                            }
+                        }
                     } else {
                         // Offer Service -> Add/Update
+                        self.offers_received.fetch_add(1, Ordering::Relaxed);
                         // We need to resolve options referenced by indices.
                         // SdEntry has index_1, index_2, num_opts_1, num_opts_2.
                         // This indicates a range in the options array.
@@ -506,55 +1606,39 @@ impl ServiceDiscovery {
                             version_major: entry.major_version,
                             version_minor: entry.minor_version,
                             endpoint: service_opts,
-                            last_seen: Instant::now(),
+                            last_seen: self.clock.now(),
                             ttl: entry.ttl,
                         };
                         
 
                         
                         self.remote_services.insert((entry.service_id, entry.instance_id), remote);
+                        self.offer_sources.insert((entry.service_id, entry.instance_id), src);
                     }
                 },
                 EntryType::FindService => {
-                    // Check if we offer this service
-                    // Iterate and find matching service_id and instance_id (or Wildcard)
+                    // [PRS_SOMEIPSD_00423] Answer with an offer after a random request_response_delay,
+                    // unicast to the finder if it set the unicast flag
                     let matches: Vec<(u16, u16)> = self.local_services.iter()
                         .filter(|((sid, iid), service)| {
-                            *sid == entry.service_id && 
+                            (entry.service_id == 0xFFFF || entry.service_id == *sid) &&
                             (entry.instance_id == 0xFFFF || entry.instance_id == *iid) &&
-                            (service.phase == ServicePhase::Main || service.phase == ServicePhase::Repetition)
+                            (entry.major_version == 0xFF || entry.major_version == service.entry.major_version) &&
+                            (entry.minor_version == 0xFFFF_FFFF || entry.minor_version == service.entry.minor_version) &&
+                            (service.phase == ServicePhase::Main || service.phase == ServicePhase::Repetition) &&
+                            service.interfaces.iter().any(|a| a == iface_alias)
                         })
                         .map(|(k, _)| *k)
                         .collect();
 
-                    for k in matches {
-                        if let Some(service) = self.local_services.get(&k) {
-                            // Send unicast offer to the requester
-                            // We need the source address from the packet?
-                            // The current SD implementation processes packets but `handle_incoming_packet` 
-                            // doesn't take the source address as argument.
-                            // We need to change the signature of `handle_incoming_packet` or `ServiceDiscovery::poll`.
-                            
-                            // For now, since `handle_incoming_packet` iterates all entries, 
-                            // and we don't have source address passed down here easily without refactoring,
-                            // we might rely on Multicast Offer?
-                            // "If a server receives a FindService... it shall send an OfferService... using Unicast (if supported) or Multicast"
-                            
-                            // Let's trigger a multicast offer for simplicity and robustness first, 
-                            // or better, schedule a transmission?
-                            // Sending immediately might flood if many Finds arrive.
-                            // But for this task, let's just send the Offer packet we already have.
-                            
-                            let mut entry_to_send = service.entry.clone();
-                            entry_to_send.ttl = service.ttl;
-                             // Reset indices
-                            entry_to_send.index_1 = 0;
-                            entry_to_send.number_of_opts_1 = service.endpoint_options.len() as u8;
-                            entry_to_send.index_2 = 0;
-                            entry_to_send.number_of_opts_2 = 0;
-                            
-                            let _ = self.send_packet(entry_to_send, service.endpoint_options.clone());
+                    let now = self.clock.now();
+                    for service in matches {
+                        // Repeated finds within the window share one answer
+                        if self.find_replies.iter().any(|r| r.service == service && r.dest == unicast_dest && r.iface_alias == iface_alias) {
+                            continue;
                         }
+                        let (min, max) = self.request_response_delay;
+                        self.find_replies.push(FindReply { service, iface_alias: iface_alias.to_string(), dest: unicast_dest, due: now + random_delay(min, max) });
                     }
                 },
                 EntryType::SubscribeEventgroup => {
@@ -569,42 +1653,79 @@ impl ServiceDiscovery {
                         }
                     } else {
                         // Subscribe - extract subscriber endpoint from options
+                        let known_eventgroup = self.offered_eventgroups.get(&entry.service_id)
+                            .is_none_or(|eventgroups| eventgroups.contains(&eventgroup_id));
+                        if !known_eventgroup {
+                            log::warn!(target: "SD", "Refusing subscription from {} to unknown EventGroup {} of Service 0x{:04x}", src, eventgroup_id, entry.service_id);
+                        }
+                        let permitted = self.access_policies.get(&entry.service_id)
+                            .is_none_or(|policy| policy.allows_eventgroup(src.ip(), eventgroup_id));
+                        if !permitted {
+                            log::warn!(target: "SD", "Refusing subscription from {} to EventGroup {} of Service 0x{:04x}: not allowed by its access policy", src, eventgroup_id, entry.service_id);
+                        }
                         let start_idx = entry.index_1 as usize;
                         let end_idx = start_idx + entry.number_of_opts_1 as usize;
                         
                         if end_idx <= packet.options.len() {
                             for i in start_idx..end_idx {
-                                let subscriber_addr = match &packet.options[i] {
-                                    SdOption::Ipv4Endpoint { address, port, .. } => {
-                                        Some(SocketAddr::new(std::net::IpAddr::V4(*address), *port))
+                                let subscriber = match &packet.options[i] {
+                                    SdOption::Ipv4Endpoint { address, port, transport_proto } => {
+                                        Some(EventSubscriber {
+                                            endpoint: SocketAddr::new(std::net::IpAddr::V4(*address), *port),
+                                            transport_proto: *transport_proto,
+                                        })
                                     }
-                                    SdOption::Ipv6Endpoint { address, port, .. } => {
-                                        Some(SocketAddr::new(std::net::IpAddr::V6(*address), *port))
+                                    SdOption::Ipv6Endpoint { address, port, transport_proto } => {
+                                        Some(EventSubscriber {
+                                            endpoint: SocketAddr::new(std::net::IpAddr::V6(*address), *port),
+                                            transport_proto: *transport_proto,
+                                        })
                                     }
                                     _ => None
                                 };
 
-                                if let Some(addr) = subscriber_addr {
-                                    // Add to subscriptions
-                                    self.subscriptions
-                                        .entry((entry.service_id, eventgroup_id))
-                                        .or_insert_with(Vec::new)
-                                        .push(addr);
+                                if let Some(subscriber) = subscriber {
+                                    // Add to subscriptions (renewals keep a single record)
+                                    let renewal = self.subscriptions.get(&(entry.service_id, eventgroup_id))
+                                        .is_some_and(|subs| subs.contains(&subscriber));
+                                    let at_limit = self.subscriber_limits.get(&entry.service_id)
+                                        .is_some_and(|max| self.service_subscriber_count(entry.service_id) >= *max);
+                                    let accepted = known_eventgroup && permitted && (renewal || !at_limit);
+                                    if accepted && !renewal {
+                                        self.subscriptions.entry((entry.service_id, eventgroup_id)).or_default().push(subscriber);
+                                    } else if known_eventgroup && permitted && !accepted {
+                                        *self.subscriptions_rejected.entry(entry.service_id).or_default() += 1;
+                                    }
                                     
-                                    // Send SubscribeEventgroupAck
+                                    // Send SubscribeEventgroupAck (TTL 0 is a NACK); UDP subscribers
+                                    // learn the multicast endpoint events may switch to
+                                    let multicast = self.eventgroup_multicast.get(&(entry.service_id, eventgroup_id))
+                                        .filter(|group| accepted && subscriber.transport_proto == 0x11 && group.is_ipv4() == subscriber.endpoint.is_ipv4());
+                                    let options: Vec<SdOption> = multicast.map(|group| match group.ip() {
+                                        IpAddr::V4(address) => SdOption::Ipv4Multicast { address, transport_proto: 0x11, port: group.port() },
+                                        IpAddr::V6(address) => SdOption::Ipv6Multicast { address, transport_proto: 0x11, port: group.port() },
+                                    }).into_iter().collect();
                                     let ack_entry = SdEntry {
                                         entry_type: EntryType::SubscribeEventgroupAck,
                                         index_1: 0,
                                         index_2: 0,
-                                        number_of_opts_1: 0,
+                                        number_of_opts_1: options.len() as u8,
                                         number_of_opts_2: 0,
                                         service_id: entry.service_id,
                                         instance_id: entry.instance_id,
                                         major_version: entry.major_version,
-                                        ttl: entry.ttl,
+                                        ttl: if accepted { entry.ttl } else { 0 },
                                         minor_version: entry.minor_version,
                                     };
-                                    let _ = self.send_packet(ack_entry, vec![]);
+                                    let _ = self.send_answer(iface_alias, unicast_dest, ack_entry, options);
+                                    if accepted && !renewal {
+                                        self.subscriptions_added.push(SubscriptionAdded {
+                                            service_id: entry.service_id,
+                                            instance_id: entry.instance_id,
+                                            eventgroup_id,
+                                            subscriber,
+                                        });
+                                    }
                                 }
                             }
                         }
@@ -614,11 +1735,31 @@ impl ServiceDiscovery {
                     // Our subscription was acknowledged
                     let eventgroup_id = (entry.minor_version >> 16) as u16;
                     if entry.ttl > 0 {
-                        // ACK - mark subscription as active
-                        self.pending_subscriptions.insert((entry.service_id, eventgroup_id), true);
+                        // ACK - mark subscription as active until the acknowledged TTL runs out
+                        self.pending_subscriptions.insert((entry.service_id, eventgroup_id), SubscriptionState::Acked);
+                        if let Some(sub) = self.client_subscriptions.get_mut(&(entry.service_id, eventgroup_id)) {
+                            sub.expires = (entry.ttl != TTL_INFINITE).then(|| self.clock.now() + Duration::from_secs(entry.ttl as u64));
+                        }
+                        let start = entry.index_1 as usize;
+                        let options = packet.options.get(start..start + entry.number_of_opts_1 as usize).unwrap_or_default();
+                        for option in options {
+                            let (group, interface) = match option {
+                                SdOption::Ipv4Multicast { address, port, .. } => (SocketAddr::new(IpAddr::V4(*address), *port), self.listeners.get(iface_alias).and_then(|l| l.local_ip_v4).map(IpAddr::V4)),
+                                SdOption::Ipv6Multicast { address, port, .. } => (SocketAddr::new(IpAddr::V6(*address), *port), self.listeners.get(iface_alias).and_then(|l| l.local_ip_v6).map(IpAddr::V6)),
+                                _ => continue,
+                            };
+                            self.event_multicast.push(EventMulticast { service_id: entry.service_id, eventgroup_id, group, interface });
+                        }
                     } else {
-                        // NACK - mark subscription as failed
-                        self.pending_subscriptions.insert((entry.service_id, eventgroup_id), false);
+                        // NACK - mark subscription as failed and stop renewing it
+                        self.pending_subscriptions.insert((entry.service_id, eventgroup_id), SubscriptionState::Nacked(NackReason::Rejected));
+                        self.client_subscriptions.remove(&(entry.service_id, eventgroup_id));
+                        self.subscription_nacks.push(SubscriptionNack {
+                            service_id: entry.service_id,
+                            instance_id: entry.instance_id,
+                            eventgroup_id,
+                            provider: src,
+                        });
                     }
                 },
                 _ => {}
@@ -631,6 +1772,7 @@ impl ServiceDiscovery {
 mod tests {
     use super::*;
     use std::net::Ipv6Addr;
+    use crate::transport::UdpTransport;
 
     fn create_dummy_entry() -> SdEntry {
         SdEntry {
@@ -653,17 +1795,17 @@ mod tests {
         let mut service = LocalService::new(entry, vec![]);
 
         // Down -> InitialWait
-        service.transition_to_initial_wait();
+        service.transition_to_initial_wait(Instant::now());
         assert_eq!(service.phase, ServicePhase::InitialWait);
         assert!(service.next_transmission > Instant::now());
 
         // InitialWait -> Repetition
-        service.transition_to_repetition();
+        service.transition_to_repetition(Instant::now());
         assert_eq!(service.phase, ServicePhase::Repetition);
         assert_eq!(service.repetition_count, 0);
 
         // Repetition -> Main
-        service.transition_to_main();
+        service.transition_to_main(Instant::now());
         assert_eq!(service.phase, ServicePhase::Main);
     }
 
@@ -678,8 +1820,8 @@ mod tests {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(transport_v4),
-            transport_v6: Some(transport_v6),
+            transport_v4: Some(Box::new(transport_v4)),
+            transport_v6: Some(Box::new(transport_v6)),
             multicast_group_v4: Some(m_v4),
             multicast_group_v6: Some(m_v6),
             local_ip_v4: Some(local_ip),
@@ -706,6 +1848,32 @@ mod tests {
         assert!(not_found.is_none());
     }
 
+    #[test]
+    fn test_get_service_preferring_transport() {
+        let mut sd = ServiceDiscovery::new();
+        let address = Ipv4Addr::new(10, 0, 0, 1);
+        sd.remote_services.insert((0x5678, 1), RemoteService {
+            service_id: 0x5678,
+            instance_id: 1,
+            version_major: 1,
+            version_minor: 0,
+            endpoint: vec![
+                SdOption::Ipv4Endpoint { address, port: 30500, transport_proto: 0x11 },
+                SdOption::Ipv4Endpoint { address, port: 30501, transport_proto: 0x06 },
+            ],
+            last_seen: Instant::now(),
+            ttl: 10,
+        });
+
+        assert_eq!(sd.get_service_preferring(0x5678, 1, 0x06).map(|(ep, p)| (ep.port(), p)), Some((30501, 0x06)));
+        assert_eq!(sd.get_service_preferring(0x5678, 0xFFFF, 0x11).map(|(ep, p)| (ep.port(), p)), Some((30500, 0x11)));
+        assert_eq!(sd.endpoint_protocol(0x5678, SocketAddr::new(address.into(), 30501)), Some(0x06));
+        assert_eq!(sd.endpoint_protocol(0x5678, SocketAddr::new(address.into(), 30502)), None);
+        // Falls back to any endpoint when the preferred transport is not offered
+        sd.remote_services.get_mut(&(0x5678, 1)).unwrap().endpoint.remove(1);
+        assert_eq!(sd.get_service_preferring(0x5678, 1, 0x06).map(|(ep, p)| (ep.port(), p)), Some((30500, 0x11)));
+    }
+
 
     #[test]
     fn test_offer_timing_initial_wait() {
@@ -718,7 +1886,7 @@ mod tests {
         };
         
         let mut service = LocalService::with_config(entry, vec![], &config);
-        service.transition_to_initial_wait();
+        service.transition_to_initial_wait(Instant::now());
         
         // Should be at least 10ms after phase start
         assert!(service.next_transmission >= service.phase_start + Duration::from_millis(10));
@@ -732,7 +1900,7 @@ mod tests {
         let mut service = LocalService::new(entry, vec![]);
         
         // Transition to repetition
-        service.transition_to_repetition();
+        service.transition_to_repetition(Instant::now());
         assert_eq!(service.repetition_count, 0);
         // Should send immediately (or very close to now)
         assert!(service.next_transmission <= Instant::now() + Duration::from_millis(5));
@@ -749,8 +1917,8 @@ mod tests {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(transport_v4),
-            transport_v6: Some(transport_v6),
+            transport_v4: Some(Box::new(transport_v4)),
+            transport_v6: Some(Box::new(transport_v6)),
             multicast_group_v4: Some(m_v4),
             multicast_group_v6: Some(m_v6),
             local_ip_v4: Some(local_ip),
@@ -782,7 +1950,7 @@ mod tests {
             options: vec![],
         };
         
-        sd.handle_incoming_packet(packet);
+        sd.handle_incoming_packet(packet, peer(), "primary");
         
         // Service should be removed
         assert!(sd.find_service(0x1234, 1).is_none());
@@ -798,7 +1966,7 @@ mod tests {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(transport_v4),
+            transport_v4: Some(Box::new(transport_v4)),
             transport_v6: None,
             multicast_group_v4: Some(m_v4),
             multicast_group_v6: None,
@@ -827,7 +1995,7 @@ mod tests {
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
             transport_v4: None,
-            transport_v6: Some(transport_v6),
+            transport_v6: Some(Box::new(transport_v6)),
             multicast_group_v4: None,
             multicast_group_v6: Some(m_v6),
             local_ip_v4: None,
@@ -856,8 +2024,8 @@ mod tests {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(t4),
-            transport_v6: Some(t6),
+            transport_v4: Some(Box::new(t4)),
+            transport_v6: Some(Box::new(t6)),
             multicast_group_v4: Some(m4),
             multicast_group_v6: Some(m6),
             local_ip_v4: Some(ip4),
@@ -878,7 +2046,7 @@ mod tests {
         let mut sd = ServiceDiscovery::new();
         sd.add_listener(SdListener {
             alias: "primary".to_string(),
-            transport_v4: Some(transport_v4),
+            transport_v4: Some(Box::new(transport_v4)),
             transport_v6: None,
             multicast_group_v4: Some(m_v4),
             multicast_group_v6: None,
@@ -890,7 +2058,7 @@ mod tests {
         sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
         // Force transition to Main phase
         if let Some(service) = sd.local_services.get_mut(&(0x1234, 1)) {
-            service.transition_to_main();
+            service.transition_to_main(Instant::now());
         }
 
         // Simulate incoming FindService
@@ -906,8 +2074,857 @@ mod tests {
             options: vec![],
         };
 
-        // Handle it
-        sd.handle_incoming_packet(packet);
+        // Multicast find: answered on the multicast group once the delay has passed
+        sd.set_request_response_delay(Duration::from_secs(60), Duration::from_secs(60));
+        sd.handle_incoming_packet(packet.clone(), peer(), "primary");
+        // A repeated find within the window does not queue a second answer
+        sd.handle_incoming_packet(packet.clone(), peer(), "primary");
+        assert_eq!(sd.find_replies.len(), 1);
+        assert_eq!(sd.find_replies[0].service, (0x1234, 1));
+        assert_eq!(sd.find_replies[0].dest, None);
+        assert!(sd.find_replies[0].due >= Instant::now() + Duration::from_secs(59));
+
+        // Finds for other versions, or arriving on interfaces the service is not offered on, are ignored
+        let mut other = packet.clone();
+        other.entries[0].major_version = 2;
+        sd.handle_incoming_packet(other, peer(), "primary");
+        sd.handle_incoming_packet(packet, peer(), "secondary");
+        assert_eq!(sd.find_replies.len(), 1);
+    }
+
+    #[test]
+    fn test_find_service_answered_by_delayed_unicast() {
+        let finder = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        finder.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::LOCALHOST));
+        sd.set_request_response_delay(Duration::from_millis(50), Duration::from_millis(50));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().next_transmission = Instant::now() + Duration::from_secs(60);
+
+        let find = SdPacket {
+            flags: 0xC0,
+            entries: vec![SdEntry {
+                entry_type: EntryType::FindService,
+                index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 0xFFFF, major_version: 0xFF, ttl: 3, minor_version: 0xFFFF_FFFF,
+            }],
+            options: vec![],
+        };
+        sd.handle_incoming_packet(find, finder.local_addr().unwrap(), "primary");
+
+        // Nothing before the request_response_delay
+        sd.poll();
+        assert_eq!(sd.offers_sent(), 0);
+        std::thread::sleep(Duration::from_millis(60));
+        sd.poll();
+        assert_eq!(sd.offers_sent(), 1);
+        assert!(sd.find_replies.is_empty());
+
+        let mut buf = [0u8; 1500];
+        let (len, _) = finder.recv_from(&mut buf).unwrap();
+        let offer = SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap();
+        let entry = &offer.entries[0];
+        assert_eq!((entry.entry_type, entry.service_id, entry.instance_id), (EntryType::OfferService, 0x1234, 1));
+        assert_eq!((entry.index_1, entry.number_of_opts_1 as usize), (0, offer.options.len()));
+        assert!(matches!(offer.options[0], SdOption::Ipv4Endpoint { port: 30500, transport_proto: 0x11, .. }));
+    }
+
+    #[test]
+    fn test_wildcard_find_service_lists_everything() {
+        let finder = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        finder.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::LOCALHOST));
+        sd.set_request_response_delay(Duration::ZERO, Duration::ZERO);
+        for (service_id, port) in [(0x1234, 30500), (0x5678, 30501)] {
+            sd.offer_service(service_id, 1, 1, 0, "primary", port, 0x11, None);
+            let service = sd.local_services.get_mut(&(service_id, 1)).unwrap();
+            service.transition_to_main(Instant::now());
+            service.next_transmission = Instant::now() + Duration::from_secs(60);
+            service.ttl = 5;
+        }
+
+        // FindService for service 0xFFFF is answered with an offer of each
+        let find = SdPacket {
+            flags: 0xC0,
+            entries: vec![SdEntry {
+                entry_type: EntryType::FindService,
+                index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id: 0xFFFF, instance_id: 0xFFFF, major_version: 0xFF, ttl: 3, minor_version: 0xFFFF_FFFF,
+            }],
+            options: vec![],
+        };
+        sd.handle_incoming_packet(find, finder.local_addr().unwrap(), "primary");
+        sd.poll();
+        assert_eq!(sd.offers_sent(), 2);
+
+        // What the finder learns from them, with the TTL counting down from the offer
+        let mut browser = ServiceDiscovery::new();
+        let mut buf = [0u8; 1500];
+        for _ in 0..2 {
+            let (len, from) = finder.recv_from(&mut buf).unwrap();
+            let offer = SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap();
+            browser.handle_incoming_packet(offer, from, "primary");
+        }
+        let listed = browser.discovered_services();
+        let ids: Vec<(u16, u16, u8)> = listed.iter().map(|s| (s.service_id, s.instance_id, s.major_version)).collect();
+        assert_eq!(ids, [(0x1234, 1, 1), (0x5678, 1, 1)]);
+        assert_eq!(listed[1].endpoints, [(SocketAddr::from((Ipv4Addr::LOCALHOST, 30501)), 0x11)]);
+        let remaining = listed[0].ttl_remaining.unwrap();
+        assert!(listed[0].ttl == 5 && remaining <= Duration::from_secs(5) && remaining > Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_snapshot_reports_sd_state() {
+        let group = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut listener = listener_v4("primary", Ipv4Addr::LOCALHOST);
+        listener.multicast_group_v4 = Some(group.local_addr().unwrap());
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener);
+        sd.offer_service(0x1234, 1, 2, 7, "primary", 30500, 0x11, None);
+        sd.offer_service(0x1235, 1, 1, 0, "primary", 30501, 0x11, None);
+        sd.stop_offer_service(0x1235, 1);
+        sd.subscribe_eventgroup(0x5678, 1, 5, 4, "primary", 40000, 0);
+        sd.subscribe_eventgroup(0x5678, 1, 6, 4, "primary", 40000, 0);
+        sd.handle_incoming_packet(SdPacket {
+            flags: 0x00,
+            entries: vec![SdEntry {
+                entry_type: EntryType::SubscribeEventgroupAck, index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id: 0x5678, instance_id: 1, major_version: 1, ttl: 4, minor_version: 5 << 16,
+            }],
+            options: vec![],
+        }, peer(), "primary");
+
+        let snapshot = sd.snapshot();
+        let local: Vec<(u16, u8, u32, ServicePhase)> = snapshot.local_services.iter()
+            .map(|s| (s.service_id, s.major_version, s.minor_version, s.phase))
+            .collect();
+        assert_eq!(local, [(0x1234, 2, 7, ServicePhase::InitialWait), (0x1235, 1, 0, ServicePhase::Down)]);
+        assert_eq!(snapshot.local_services[0].interfaces, ["primary"]);
+        assert!(snapshot.local_services[0].next_offer_in.is_some());
+        assert_eq!(snapshot.local_services[1].next_offer_in, None);
+        assert!(snapshot.remote_services.is_empty());
+
+        let states: Vec<(u16, SubscriptionState, Option<u32>)> = snapshot.subscriptions.iter().map(|s| (s.eventgroup_id, s.state, s.ttl)).collect();
+        assert_eq!(states, [(5, SubscriptionState::Acked, Some(4)), (6, SubscriptionState::Pending, Some(4))]);
+        assert!(snapshot.subscriptions[0].ack_expires_in.is_some_and(|left| left > Duration::from_secs(3)));
+        assert_eq!(snapshot.subscriptions[1].ack_expires_in, None);
+        assert!(snapshot.subscriptions[1].next_renewal_in.is_some_and(|next| next <= Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn test_subscriptions_renewed_until_acks_lapse() {
+        let group = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        group.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut listener = listener_v4("primary", Ipv4Addr::LOCALHOST);
+        listener.multicast_group_v4 = Some(group.local_addr().unwrap());
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener);
+        let sent = || {
+            let mut buf = [0u8; 1500];
+            let (len, _) = group.recv_from(&mut buf).unwrap();
+            let entry = SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap().entries[0].clone();
+            (entry.entry_type, entry.ttl)
+        };
+        let ack = |ttl| SdPacket {
+            flags: 0x00,
+            entries: vec![SdEntry {
+                entry_type: EntryType::SubscribeEventgroupAck, index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl, minor_version: 5 << 16,
+            }],
+            options: vec![],
+        };
+
+        // TTL 4 s, renewed every 2 s (50 %)
+        sd.subscribe_eventgroup(0x1234, 1, 5, 4, "primary", 40000, 0);
+        assert_eq!(sent(), (EntryType::SubscribeEventgroup, 4));
+        sd.handle_incoming_packet(ack(4), peer(), "primary");
+        let start = Instant::now();
+        sd.renew_subscriptions(start + Duration::from_millis(1000));
+        sd.renew_subscriptions(start + Duration::from_millis(2100));
+        assert_eq!(sent(), (EntryType::SubscribeEventgroup, 4));
+        assert_eq!(sd.subscription_state(0x1234, 5), Some(SubscriptionState::Acked));
+
+        // Renewals went unanswered past the acknowledged TTL
+        sd.renew_subscriptions(start + Duration::from_millis(4100));
+        assert_eq!(sent(), (EntryType::SubscribeEventgroup, 4));
+        assert_eq!(sd.subscription_state(0x1234, 5), Some(SubscriptionState::Expired));
+        sd.handle_incoming_packet(ack(4), peer(), "primary");
+        assert!(sd.is_subscription_acked(0x1234, 5));
+
+        sd.unsubscribe_eventgroup(0x1234, 1, 5, "primary");
+        assert_eq!(sent(), (EntryType::SubscribeEventgroup, 0));
+        assert!(sd.client_subscriptions.is_empty());
+        sd.renew_subscriptions(start + Duration::from_secs(60));
+        group.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+        assert!(group.recv_from(&mut [0u8; 1500]).is_err());
+    }
+
+    #[test]
+    fn test_subscribe_ack_unicast_when_flagged() {
+        let subscriber = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        subscriber.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::LOCALHOST));
+
+        let subscribe = SdPacket {
+            flags: 0xC0,
+            entries: vec![SdEntry {
+                entry_type: EntryType::SubscribeEventgroup,
+                index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3, minor_version: 5 << 16,
+            }],
+            options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::LOCALHOST, transport_proto: 0x11, port: 40000 }],
+        };
+        sd.handle_incoming_packet(subscribe, subscriber.local_addr().unwrap(), "primary");
+
+        let mut buf = [0u8; 1500];
+        let (len, _) = subscriber.recv_from(&mut buf).unwrap();
+        let ack = SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap();
+        let entry = &ack.entries[0];
+        assert_eq!((entry.entry_type, entry.service_id, entry.ttl), (EntryType::SubscribeEventgroupAck, 0x1234, 3));
+        assert_eq!(ack.flags & SD_FLAG_UNICAST, 0);
+    }
+
+    #[test]
+    fn test_subscribe_records_transport_proto() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+
+        let entry = SdEntry {
+            entry_type: EntryType::SubscribeEventgroup,
+            index_1: 0, index_2: 0, number_of_opts_1: 2, number_of_opts_2: 0,
+            service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3,
+            minor_version: 5 << 16,
+        };
+        let packet = SdPacket {
+            flags: 0x00,
+            entries: vec![entry],
+            options: vec![
+                SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x06, port: 40000 },
+                SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x11, port: 40001 },
+            ],
+        };
+
+        // A renewal must not duplicate subscribers
+        sd.handle_incoming_packet(packet.clone(), peer(), "primary");
+        sd.handle_incoming_packet(packet, peer(), "primary");
+
+        let subs = sd.get_subscribers(0x1234, 5);
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].transport_proto, 0x06);
+        assert_eq!(subs[0].endpoint, "10.0.0.2:40000".parse().unwrap());
+        assert_eq!(subs[1].transport_proto, 0x11);
+        // Only the first subscription is reported as added
+        let added = sd.take_subscriptions_added();
+        assert_eq!(added.iter().map(|a| a.subscriber).collect::<Vec<_>>(), subs);
+        assert!(added.iter().all(|a| (a.service_id, a.instance_id, a.eventgroup_id) == (0x1234, 1, 5)));
+        assert!(sd.take_subscriptions_added().is_empty());
+    }
+
+    #[test]
+    fn test_subscriber_limit_rejects_new_subscribers() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        sd.set_subscriber_limit(0x1234, 1);
+
+        let subscribe = |eventgroup: u32, port: u16| SdPacket {
+            flags: 0x00,
+            entries: vec![SdEntry {
+                entry_type: EntryType::SubscribeEventgroup,
+                index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3,
+                minor_version: eventgroup << 16,
+            }],
+            options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x11, port }],
+        };
+
+        sd.handle_incoming_packet(subscribe(1, 40000), peer(), "primary");
+        // The limit spans eventgroups; renewals of admitted subscribers are still accepted
+        sd.handle_incoming_packet(subscribe(2, 40001), peer(), "primary");
+        sd.handle_incoming_packet(subscribe(1, 40000), peer(), "primary");
+
+        assert_eq!(sd.service_subscriber_count(0x1234), 1);
+        assert!(sd.get_subscribers(0x1234, 2).is_empty());
+        assert_eq!(sd.subscriptions_rejected(0x1234), 1);
+    }
+
+    #[test]
+    fn test_access_policy_nacks_subscriptions() {
+        use crate::runtime::config::AccessRule;
+
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        let rules: Vec<AccessRule> = serde_json::from_str(r#"[{ "source": "10.0.0.0/24", "eventgroups": [1] }]"#).unwrap();
+        let policy = Arc::new(AccessPolicy::from_config(&rules).0);
+        sd.set_access_policy(0x1234, policy.clone());
+
+        let subscribe = |eventgroup: u32| SdPacket {
+            flags: 0x00,
+            entries: vec![SdEntry {
+                entry_type: EntryType::SubscribeEventgroup,
+                index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3,
+                minor_version: eventgroup << 16,
+            }],
+            options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x11, port: 40000 }],
+        };
+
+        sd.handle_incoming_packet(subscribe(1), peer(), "primary");
+        sd.handle_incoming_packet(subscribe(2), peer(), "primary");
+        sd.handle_incoming_packet(subscribe(1), "10.0.1.2:30490".parse().unwrap(), "primary");
+
+        assert_eq!(sd.get_subscribers(0x1234, 1).len(), 1);
+        assert!(sd.get_subscribers(0x1234, 2).is_empty());
+        assert_eq!(policy.violations(), 2);
+        // Refusals by policy do not count against the subscriber limit
+        assert_eq!(sd.subscriptions_rejected(0x1234), 0);
     }
-}
 
+    #[test]
+    fn test_unknown_eventgroup_nacked() {
+        let subscriber = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        subscriber.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::LOCALHOST));
+        sd.set_offered_eventgroups(0x1234, [5]);
+
+        let mut answer_ttl = |eventgroup: u32| {
+            let subscribe = SdPacket {
+                flags: 0xC0,
+                entries: vec![SdEntry {
+                    entry_type: EntryType::SubscribeEventgroup,
+                    index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                    service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3, minor_version: eventgroup << 16,
+                }],
+                options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::LOCALHOST, transport_proto: 0x11, port: 40000 }],
+            };
+            sd.handle_incoming_packet(subscribe, subscriber.local_addr().unwrap(), "primary");
+            let mut buf = [0u8; 1500];
+            let (len, _) = subscriber.recv_from(&mut buf).unwrap();
+            let ack = SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap();
+            ack.entries[0].ttl
+        };
+        assert_eq!(answer_ttl(6), 0);
+        assert_eq!(answer_ttl(5), 3);
+        assert!(sd.get_subscribers(0x1234, 6).is_empty());
+        assert_eq!(sd.get_subscribers(0x1234, 5).len(), 1);
+        assert_eq!(sd.subscriptions_rejected(0x1234), 0);
+    }
+
+    #[test]
+    fn test_multicast_endpoint_announced_in_ack() {
+        let subscriber = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        subscriber.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let group: SocketAddr = "239.1.1.1:30500".parse().unwrap();
+        let mut provider = ServiceDiscovery::new();
+        provider.add_listener(listener_v4("primary", Ipv4Addr::LOCALHOST));
+        provider.set_eventgroup_multicast(0x1234, 5, Some(group));
+
+        let mut ack_for = |transport_proto: u8| {
+            let subscribe = SdPacket {
+                flags: 0xC0,
+                entries: vec![SdEntry {
+                    entry_type: EntryType::SubscribeEventgroup,
+                    index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                    service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3, minor_version: 5 << 16,
+                }],
+                options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::LOCALHOST, transport_proto, port: 40000 }],
+            };
+            provider.handle_incoming_packet(subscribe, subscriber.local_addr().unwrap(), "primary");
+            let mut buf = [0u8; 1500];
+            let (len, _) = subscriber.recv_from(&mut buf).unwrap();
+            SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap()
+        };
+        // TCP subscribers cannot receive multicast
+        assert!(ack_for(0x06).options.is_empty());
+        let ack = ack_for(0x11);
+        assert_eq!(ack.entries[0].number_of_opts_1, 1);
+        assert_eq!(ack.options, vec![SdOption::Ipv4Multicast { address: Ipv4Addr::new(239, 1, 1, 1), transport_proto: 0x11, port: 30500 }]);
+
+        // The subscriber side reports the group to join on the interface the ACK arrived on
+        let mut client = ServiceDiscovery::new();
+        client.add_listener(listener_v4("primary", Ipv4Addr::new(10, 0, 0, 1)));
+        client.handle_incoming_packet(ack, peer(), "primary");
+        assert_eq!(client.take_event_multicast(), vec![EventMulticast {
+            service_id: 0x1234, eventgroup_id: 5, group, interface: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+        }]);
+        assert!(client.take_event_multicast().is_empty());
+    }
+
+    #[test]
+    fn test_received_nacks_reported() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        sd.subscribe_eventgroup(0x1234, 1, 6, 3, "primary", 40000, 0);
+        let nack = SdPacket {
+            flags: 0x00,
+            entries: vec![SdEntry {
+                entry_type: EntryType::SubscribeEventgroupAck,
+                index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 0, minor_version: 6 << 16,
+            }],
+            options: vec![],
+        };
+        sd.handle_incoming_packet(nack, peer(), "primary");
+
+        assert_eq!(sd.take_subscription_nacks(), vec![SubscriptionNack { service_id: 0x1234, instance_id: 1, eventgroup_id: 6, provider: peer() }]);
+        assert!(sd.take_subscription_nacks().is_empty());
+    }
+
+    #[test]
+    fn test_subscription_state_transitions() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        let answer = |entry_type, service_id, ttl| SdPacket {
+            flags: 0x00,
+            entries: vec![SdEntry {
+                entry_type, index_1: 0, index_2: 0, number_of_opts_1: 0, number_of_opts_2: 0,
+                service_id, instance_id: 1, major_version: 1, ttl, minor_version: 5 << 16,
+            }],
+            options: vec![],
+        };
+
+        assert_eq!(sd.subscription_state(0x1234, 5), None);
+        sd.subscribe_eventgroup(0x1234, 1, 5, 3, "primary", 40000, 0);
+        assert_eq!(sd.subscription_state(0x1234, 5), Some(SubscriptionState::Pending));
+        sd.handle_incoming_packet(answer(EntryType::SubscribeEventgroupAck, 0x1234, 3), peer(), "primary");
+        assert!(sd.is_subscription_acked(0x1234, 5));
+
+        sd.subscribe_eventgroup(0x1234, 1, 5, 3, "primary", 40000, 0);
+        sd.handle_incoming_packet(answer(EntryType::SubscribeEventgroupAck, 0x1234, 0), peer(), "primary");
+        assert_eq!(sd.subscription_state(0x1234, 5), Some(SubscriptionState::Nacked(NackReason::Rejected)));
+
+        // A StopOffer fails only subscriptions still waiting for an answer
+        sd.subscribe_eventgroup(0x1234, 1, 5, 3, "primary", 40000, 0);
+        sd.subscribe_eventgroup(0x4321, 1, 5, 3, "primary", 40000, 0);
+        sd.handle_incoming_packet(answer(EntryType::SubscribeEventgroupAck, 0x4321, 3), peer(), "primary");
+        sd.handle_incoming_packet(answer(EntryType::OfferService, 0x1234, 0), peer(), "primary");
+        sd.handle_incoming_packet(answer(EntryType::OfferService, 0x4321, 0), peer(), "primary");
+        assert_eq!(sd.subscription_state(0x1234, 5), Some(SubscriptionState::Nacked(NackReason::ServiceStopped)));
+        assert!(sd.is_subscription_acked(0x4321, 5));
+    }
+
+    #[test]
+    fn test_session_ids_clear_reboot_flag_on_wrap() {
+        let mut session = SdSession::new();
+        assert_eq!(session.advance(), (1, SD_FLAG_REBOOT));
+        assert_eq!(session.advance(), (2, SD_FLAG_REBOOT));
+        session.next = 0xFFFF;
+        assert_eq!(session.advance(), (0xFFFF, SD_FLAG_REBOOT));
+        assert_eq!(session.advance(), (1, 0));
+    }
+
+    #[test]
+    fn test_peer_reboot_invalidates_services_and_subscribers() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        let packet = |entry_type, ttl, minor_version, options: Vec<SdOption>| SdPacket {
+            flags: SD_FLAG_REBOOT,
+            entries: vec![SdEntry {
+                entry_type, index_1: 0, index_2: 0, number_of_opts_1: options.len() as u8, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl, minor_version,
+            }],
+            options,
+        };
+        let endpoint = SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x11, port: 40000 };
+        let receive = |sd: &mut ServiceDiscovery, session_id, flags, packet: SdPacket| {
+            sd.track_peer_session(peer(), session_id, flags);
+            sd.handle_incoming_packet(packet, peer(), "primary");
+        };
+
+        receive(&mut sd, 1, SD_FLAG_REBOOT, packet(EntryType::OfferService, 3, 0, vec![endpoint.clone()]));
+        receive(&mut sd, 2, SD_FLAG_REBOOT, packet(EntryType::SubscribeEventgroup, 3, 5 << 16, vec![endpoint.clone()]));
+        sd.subscribe_eventgroup(0x1234, 1, 7, 3, "primary", 40000, 0);
+        receive(&mut sd, 3, SD_FLAG_REBOOT, packet(EntryType::SubscribeEventgroupAck, 3, 7 << 16, vec![]));
+        assert!(sd.is_subscription_acked(0x1234, 7));
+        assert!(sd.take_peer_reboots().is_empty());
+
+        // Session ID back to 1 with the reboot flag still set
+        receive(&mut sd, 1, SD_FLAG_REBOOT, packet(EntryType::FindService, 3, 0, vec![]));
+        assert_eq!(sd.take_peer_reboots(), vec![PeerReboot { address: peer(), services: vec![(0x1234, 1)] }]);
+        assert!(sd.find_service(0x1234, 1).is_none());
+        assert!(sd.get_subscribers(0x1234, 5).is_empty());
+        assert_eq!(sd.subscription_state(0x1234, 7), Some(SubscriptionState::Nacked(NackReason::ServiceStopped)));
+
+        // A wrap clears the flag; setting it again is a reboot
+        sd.track_peer_session(peer(), 0xFFFF, SD_FLAG_REBOOT);
+        sd.track_peer_session(peer(), 1, 0);
+        assert!(sd.take_peer_reboots().is_empty());
+        sd.track_peer_session(peer(), 1, SD_FLAG_REBOOT);
+        assert_eq!(sd.take_peer_reboots().len(), 1);
+
+        // Instances on this host share our address and are not tracked
+        let local: SocketAddr = "127.0.0.1:30490".parse().unwrap();
+        sd.track_peer_session(local, 5, SD_FLAG_REBOOT);
+        sd.track_peer_session(local, 1, SD_FLAG_REBOOT);
+        assert!(sd.take_peer_reboots().is_empty());
+    }
+
+    /// Source address of packets handed to `handle_incoming_packet`
+    fn peer() -> SocketAddr {
+        "10.0.0.2:30490".parse().unwrap()
+    }
+
+    fn listener_v4(alias: &str, ip: Ipv4Addr) -> SdListener {
+        SdListener {
+            alias: alias.to_string(),
+            transport_v4: Some(Box::new(UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap())),
+            transport_v6: None,
+            multicast_group_v4: Some("127.0.0.1:30490".parse().unwrap()),
+            multicast_group_v6: None,
+            local_ip_v4: Some(ip),
+            local_ip_v6: None,
+        }
+    }
+
+    #[test]
+    fn test_disable_interface_suspends_sole_offers() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+
+        assert!(sd.disable_interface("primary"));
+        assert!(!sd.is_interface_enabled("primary"));
+        let service = sd.local_services.get(&(0x1234, 1)).unwrap();
+        assert_eq!(service.phase, ServicePhase::Down);
+        assert!(service.suspended);
+
+        assert!(sd.enable_interface("primary"));
+        assert!(sd.is_interface_enabled("primary"));
+        let service = sd.local_services.get(&(0x1234, 1)).unwrap();
+        assert_eq!(service.phase, ServicePhase::InitialWait);
+        assert!(!service.suspended);
+    }
+
+    #[test]
+    fn test_disable_interface_keeps_multi_homed_offers() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        sd.add_listener(listener_v4("secondary", Ipv4Addr::new(127, 0, 0, 2)));
+
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.offer_service(0x1234, 1, 1, 0, "secondary", 30500, 0x11, None);
+        let service = sd.local_services.get(&(0x1234, 1)).unwrap();
+        assert_eq!(service.interfaces.len(), 2);
+        assert_eq!(service.endpoint_options.len(), 2);
+
+        assert!(sd.disable_interface("primary"));
+        let service = sd.local_services.get(&(0x1234, 1)).unwrap();
+        assert_ne!(service.phase, ServicePhase::Down);
+        assert!(!service.suspended);
+
+        // Losing the last interface suspends it
+        assert!(sd.disable_interface("secondary"));
+        assert!(sd.local_services.get(&(0x1234, 1)).unwrap().suspended);
+    }
+
+    #[test]
+    fn test_disable_unknown_interface() {
+        let mut sd = ServiceDiscovery::new();
+        assert!(!sd.disable_interface("missing"));
+        assert!(!sd.enable_interface("missing"));
+    }
+
+    #[test]
+    fn test_stop_offer_not_resumed_by_enable() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.stop_offer_service(0x1234, 1);
+
+        sd.disable_interface("primary");
+        sd.enable_interface("primary");
+        assert_eq!(sd.local_services.get(&(0x1234, 1)).unwrap().phase, ServicePhase::Down);
+    }
+
+    #[test]
+    fn test_offer_stop_offer_reoffer_cycle() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+        sd.subscriptions.entry((0x1234, 5)).or_default().push(EventSubscriber { endpoint: "127.0.0.1:40000".parse().unwrap(), transport_proto: 0x11 });
+
+        assert!(sd.stop_offer_service(0x1234, 1));
+        assert_eq!(sd.local_services.get(&(0x1234, 1)).unwrap().phase, ServicePhase::Down);
+        assert!(sd.get_subscribers(0x1234, 5).is_empty());
+        assert!(!sd.stop_offer_service(0x1234, 1));
+        assert!(!sd.stop_offer_service(0x9999, 1));
+
+        // Offering again starts over from the Initial Wait phase
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        assert_eq!(sd.local_services.get(&(0x1234, 1)).unwrap().phase, ServicePhase::InitialWait);
+    }
+
+    #[test]
+    fn test_stop_offer_all() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::new(127, 0, 0, 1)));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.offer_service(0x1235, 1, 1, 0, "primary", 30501, 0x11, None);
+        sd.stop_offer_service(0x1235, 1);
+
+        assert_eq!(sd.stop_offer_all(), 1);
+        assert!(sd.local_services.values().all(|s| s.phase == ServicePhase::Down));
+        assert_eq!(sd.stop_offer_all(), 0);
+    }
+
+    /// Listener whose "multicast group" is its own socket, so every send loops back.
+    fn looped_listener(alias: &str) -> SdListener {
+        let transport = UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = transport.local_addr().unwrap();
+        SdListener {
+            alias: alias.to_string(),
+            transport_v4: Some(Box::new(transport)),
+            transport_v6: None,
+            multicast_group_v4: Some(addr),
+            multicast_group_v6: None,
+            local_ip_v4: Some(Ipv4Addr::LOCALHOST),
+            local_ip_v6: None,
+        }
+    }
+
+    fn announce_and_receive(sd: &mut ServiceDiscovery) {
+        // First poll sends the offer, second picks up the looped-back copy
+        sd.poll();
+        std::thread::sleep(Duration::from_millis(20));
+        sd.poll();
+    }
+
+    #[test]
+    fn test_self_offer_ignored_by_default() {
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(looped_listener("primary"));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+
+        announce_and_receive(&mut sd);
+        assert!(sd.get_service(0x1234, 1).is_none());
+    }
+
+    #[test]
+    fn test_self_offer_accepted_when_configured() {
+        let mut sd = ServiceDiscovery::new();
+        sd.set_accept_self_offers(true);
+        sd.add_listener(looped_listener("primary"));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+
+        announce_and_receive(&mut sd);
+        assert_eq!(sd.get_service(0x1234, 1), Some(("127.0.0.1:30500".parse().unwrap(), 0x11)));
+        assert_eq!(sd.offers_received(), 1);
+    }
+
+    #[test]
+    fn test_offer_carries_config_items() {
+        let mut sd = ServiceDiscovery::new();
+        sd.set_accept_self_offers(true);
+        sd.add_listener(looped_listener("primary"));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        assert!(sd.set_service_config(0x1234, 1, SdOption::configuration(&[("ecu", "gw"), ("build", "old")]).unwrap()));
+        // Replaces, rather than adds to, the previous items
+        assert!(sd.set_service_config(0x1234, 1, SdOption::configuration(&[("ecu", "gw"), ("build", "3f2a9c")]).unwrap()));
+        assert!(!sd.set_service_config(0x9999, 1, SdOption::configuration::<&str, &str>(&[]).unwrap()));
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+
+        announce_and_receive(&mut sd);
+        let remote = sd.find_service(0x1234, 1).unwrap();
+        assert_eq!(remote.config("ecu").as_deref(), Some("gw"));
+        assert_eq!(remote.config("build").as_deref(), Some("3f2a9c"));
+        assert_eq!(remote.config("region"), None);
+        assert_eq!(remote.config_items().len(), 2);
+        // Endpoint resolution is unaffected by the extra option
+        assert_eq!(sd.get_service(0x1234, 1), Some(("127.0.0.1:30500".parse().unwrap(), 0x11)));
+    }
+
+    #[test]
+    fn test_offers_and_subscribers_counted() {
+        let mut sd = ServiceDiscovery::new();
+        sd.set_accept_self_offers(true);
+        sd.add_listener(looped_listener("primary"));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+        sd.local_services.get_mut(&(0x1234, 1)).unwrap().transition_to_main(Instant::now());
+        sd.poll();
+        assert_eq!(sd.offers_sent(), 1);
+        // StopOffer is not an offer
+        sd.stop_offer_service(0x1234, 1);
+        assert_eq!(sd.offers_sent(), 1);
+
+        assert_eq!(sd.subscriber_count(), 0);
+        sd.subscriptions.entry((0x1234, 1)).or_default().push(EventSubscriber { endpoint: "127.0.0.1:40000".parse().unwrap(), transport_proto: 0x11 });
+        assert_eq!(sd.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn test_find_service_request_repeats_until_offered() {
+        let observer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        observer.set_nonblocking(true).unwrap();
+        let mut sd = ServiceDiscovery::new();
+        let mut listener = listener_v4("primary", Ipv4Addr::LOCALHOST);
+        listener.multicast_group_v4 = Some(observer.local_addr().unwrap());
+        sd.add_listener(listener);
+        sd.set_timing(&SdConfig {
+            initial_delay_min_ms: 0, initial_delay_max_ms: 0,
+            repetition_base_delay_ms: 10, repetition_max: 2,
+            ..SdConfig::default()
+        });
+        let finds_seen = || {
+            let mut buf = [0u8; 1500];
+            let mut finds = 0;
+            while let Ok((len, _)) = observer.recv_from(&mut buf) {
+                let packet = SdPacket::deserialize(&mut std::io::Cursor::new(&buf[16..len])).unwrap();
+                assert_eq!(packet.entries[0].entry_type, EntryType::FindService);
+                assert_eq!((packet.entries[0].service_id, packet.entries[0].major_version), (0x1234, 1));
+                finds += 1;
+            }
+            finds
+        };
+
+        assert!(sd.find_service_request(0x1234, 0xFFFF, 1, 0xFFFF_FFFF));
+        sd.poll();
+        sd.poll();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(finds_seen(), 1);
+
+        // Repetitions after 10 ms and 20 ms, then the search ends
+        std::thread::sleep(Duration::from_millis(15));
+        sd.poll();
+        std::thread::sleep(Duration::from_millis(25));
+        sd.poll();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(finds_seen(), 2);
+        assert!(sd.pending_finds.is_empty());
+
+        // An offer ends the search early; known services are not searched for
+        assert!(sd.find_service_request(0x1234, 0xFFFF, 1, 0xFFFF_FFFF));
+        sd.remote_services.insert((0x1234, 3), RemoteService {
+            service_id: 0x1234, instance_id: 3, version_major: 1, version_minor: 0,
+            endpoint: vec![], last_seen: Instant::now(), ttl: 10,
+        });
+        sd.poll();
+        assert!(sd.pending_finds.is_empty());
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(finds_seen(), 0);
+        assert!(!sd.find_service_request(0x1234, 3, 1, 0xFFFF_FFFF));
+    }
+
+    #[test]
+    fn test_next_timer_follows_offers_and_finds() {
+        let observer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sd = ServiceDiscovery::new();
+        let mut listener = listener_v4("primary", Ipv4Addr::LOCALHOST);
+        listener.multicast_group_v4 = Some(observer.local_addr().unwrap());
+        sd.add_listener(listener);
+        sd.set_timing(&SdConfig { initial_delay_min_ms: 40, initial_delay_max_ms: 40, ..SdConfig::default() });
+        assert_eq!(sd.until_next_timer(), None);
+        #[cfg(target_os = "linux")]
+        assert_eq!(sd.poll_fds().map(|fds| fds.len()), Some(1));
+
+        // Initial Wait, the first repetition at once, then the next after the base delay
+        let timing = SdConfig::default();
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30501, 0x11, None);
+        let initial_wait = sd.until_next_timer().unwrap();
+        assert!(initial_wait <= Duration::from_millis(timing.initial_delay_max_ms));
+        std::thread::sleep(initial_wait);
+        sd.poll();
+        assert_eq!(sd.until_next_timer(), Some(Duration::ZERO));
+        sd.poll();
+        let repetition = sd.until_next_timer().unwrap();
+        assert!(repetition > Duration::ZERO && repetition <= Duration::from_millis(timing.repetition_base_delay_ms), "{:?}", repetition);
+
+        // Withdrawn offers have no timer; a search does
+        assert!(sd.stop_offer_service(0x1234, 1));
+        assert_eq!(sd.until_next_timer(), None);
+        assert!(sd.find_service_request(0x4321, 0xFFFF, 1, 0xFFFF_FFFF));
+        let search = sd.until_next_timer().unwrap();
+        assert!(search > Duration::from_millis(20) && search <= Duration::from_millis(40), "{:?}", search);
+    }
+
+    #[test]
+    fn test_sd_storm_processed_in_bounded_batches() {
+        let mut sd = ServiceDiscovery::new();
+        sd.set_ingress_limits(3, 5);
+        let listener = looped_listener("primary");
+        let addr = listener.multicast_group_v4.unwrap();
+        sd.add_listener(listener);
+
+        let storm = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        for instance_id in 1..=8 {
+            let entry = SdEntry {
+                entry_type: EntryType::OfferService,
+                index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id, major_version: 1, ttl: 3, minor_version: 0,
+            };
+            let option = SdOption::Ipv4Endpoint { address: Ipv4Addr::new(10, 0, 0, 2), transport_proto: 0x11, port: 30500 + instance_id };
+            storm.send_to(&ServiceDiscovery::encode(entry, vec![option], 1, 0x80).unwrap(), addr).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Five are buffered, three processed, the remaining three wait in the socket
+        sd.poll();
+        assert_eq!(sd.remote_services.len(), 3);
+        let stats = sd.ingress_stats();
+        assert_eq!((stats.backlog, stats.backlog_peak, stats.processed), (2, 5, 3));
+        assert_eq!((stats.carried_over, stats.throttled_polls), (2, 1));
+
+        sd.poll();
+        sd.poll();
+        assert_eq!(sd.remote_services.len(), 8);
+        let stats = sd.ingress_stats();
+        assert_eq!((stats.backlog, stats.processed), (0, 8));
+        // Two leftover after each of the first two polls
+        assert_eq!((stats.carried_over, stats.throttled_polls), (4, 2));
+    }
+
+    #[test]
+    fn test_same_host_instances_see_each_other() {
+        // Two instances on one host: same source address, different offers
+        let receiver = looped_listener("primary");
+        let receiver_addr = receiver.multicast_group_v4;
+        let mut sd_a = ServiceDiscovery::new();
+        sd_a.add_listener(receiver);
+        sd_a.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+
+        let mut sd_b = ServiceDiscovery::new();
+        let mut sender = listener_v4("primary", Ipv4Addr::LOCALHOST);
+        sender.multicast_group_v4 = receiver_addr;
+        sd_b.add_listener(sender);
+        // Same service, other instance
+        sd_b.offer_service(0x1234, 2, 1, 0, "primary", 30501, 0x11, None);
+        sd_b.local_services.get_mut(&(0x1234, 2)).unwrap().transition_to_main(Instant::now());
+        sd_b.poll();
+
+        std::thread::sleep(Duration::from_millis(20));
+        sd_a.poll();
+        assert_eq!(sd_a.get_service(0x1234, 2), Some(("127.0.0.1:30501".parse().unwrap(), 0x11)));
+    }
+
+    #[test]
+    fn test_same_host_duplicate_instance_not_treated_as_self() {
+        // Another process offering our service/instance on a different endpoint is a real peer
+        let mut sd = ServiceDiscovery::new();
+        sd.add_listener(listener_v4("primary", Ipv4Addr::LOCALHOST));
+        sd.offer_service(0x1234, 1, 1, 0, "primary", 30500, 0x11, None);
+
+        let mut packet = SdPacket {
+            flags: 0x80,
+            entries: vec![SdEntry {
+                entry_type: EntryType::OfferService,
+                index_1: 0, index_2: 0, number_of_opts_1: 1, number_of_opts_2: 0,
+                service_id: 0x1234, instance_id: 1, major_version: 1, ttl: 3, minor_version: 0,
+            }],
+            options: vec![SdOption::Ipv4Endpoint { address: Ipv4Addr::LOCALHOST, transport_proto: 0x11, port: 30600 }],
+        };
+        sd.drop_self_offers(&mut packet, "127.0.0.1:30490".parse().unwrap());
+        assert_eq!(packet.entries.len(), 1);
+
+        packet.options[0] = SdOption::Ipv4Endpoint { address: Ipv4Addr::LOCALHOST, transport_proto: 0x11, port: 30500 };
+        sd.drop_self_offers(&mut packet, "127.0.0.1:30490".parse().unwrap());
+        assert!(packet.entries.is_empty());
+    }
+}
@@ -21,6 +21,7 @@ pub mod transport_protocol {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 /// [PRS_SOMEIPSD_00021] Option Header Format
 pub enum SdOption {
     /// [PRS_SOMEIPSD_00307] IPv4 Endpoint Option (Type 0x04)
@@ -77,6 +78,56 @@ impl SdOption {
             SdOption::Unknown { type_id, .. } => *type_id,
         }
     }
+
+    /// Build a Configuration option from key/value items.
+    ///
+    /// [PRS_SOMEIPSD_00280] Items are encoded DNS-TXT style: one length byte followed by
+    /// `key=value`, terminated by a zero length byte. Keys must be non-empty printable
+    /// ASCII without `=`, and each item is limited to 127 bytes.
+    pub fn configuration<K: AsRef<str>, V: AsRef<str>>(items: &[(K, V)]) -> Result<SdOption> {
+        let mut config_string = String::new();
+        for (key, value) in items {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            if key.is_empty() || !key.bytes().all(|b| (0x20..=0x7E).contains(&b) && b != b'=') {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid configuration key '{}'", key)));
+            }
+            let item_len = key.len() + 1 + value.len();
+            if item_len > 127 {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Configuration item '{}' exceeds 127 bytes", key)));
+            }
+            config_string.push(item_len as u8 as char);
+            config_string.push_str(key);
+            config_string.push('=');
+            config_string.push_str(value);
+        }
+        config_string.push('\0');
+        Ok(SdOption::Configuration { config_string })
+    }
+
+    /// Key/value items of a Configuration option, in wire order. Items without `=`
+    /// (boolean flags) have an empty value. Returns an empty list for other option types.
+    pub fn config_items(&self) -> Vec<(String, String)> {
+        let SdOption::Configuration { config_string } = self else {
+            return Vec::new();
+        };
+        let bytes = config_string.as_bytes();
+        let mut items = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let len = bytes[pos] as usize;
+            pos += 1;
+            if len == 0 || pos + len > bytes.len() {
+                break;
+            }
+            let item = String::from_utf8_lossy(&bytes[pos..pos + len]);
+            pos += len;
+            match item.split_once('=') {
+                Some((key, value)) => items.push((key.to_string(), value.to_string())),
+                None => items.push((item.to_string(), String::new())),
+            }
+        }
+        items
+    }
 }
 
 impl SomeIpSerialize for SdOption {
@@ -297,4 +348,40 @@ mod tests {
             _ => panic!("Expected IPv6Endpoint, got {:?}", opt),
         }
     }
+
+    #[test]
+    fn test_configuration_items_roundtrip() {
+        let opt = SdOption::configuration(&[("ecu", "gateway-01"), ("build", "3f2a9c"), ("region", "eu")]).unwrap();
+
+        let mut bytes = Vec::new();
+        opt.serialize(&mut bytes).unwrap();
+        // Length 0x0028 covers reserved byte + three items + terminator
+        assert_eq!(&bytes[..4], &[0x00, 0x28, option_types::CONFIGURATION, 0x00]);
+        assert_eq!(&bytes[4..19], b"\x0eecu=gateway-01");
+        assert_eq!(*bytes.last().unwrap(), 0x00);
+
+        let decoded = SdOption::deserialize(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(decoded.config_items(), vec![
+            ("ecu".to_string(), "gateway-01".to_string()),
+            ("build".to_string(), "3f2a9c".to_string()),
+            ("region".to_string(), "eu".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_configuration_rejects_invalid_items() {
+        assert!(SdOption::configuration(&[("a=b", "c")]).is_err());
+        assert!(SdOption::configuration(&[("", "c")]).is_err());
+        assert!(SdOption::configuration(&[("key", "x".repeat(200))]).is_err());
+    }
+
+    #[test]
+    fn test_configuration_flag_item() {
+        // "\x05debug" is a boolean item without a value
+        let opt = SdOption::Configuration { config_string: "\x05debug\x06zone=3\0".to_string() };
+        assert_eq!(opt.config_items(), vec![
+            ("debug".to_string(), String::new()),
+            ("zone".to_string(), "3".to_string()),
+        ]);
+    }
 }
@@ -4,6 +4,7 @@ use crate::sd::options::SdOption;
 use std::io::{Result, Write, Read};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]
 /// [PRS_SOMEIPSD_00016] SD Header Format
 pub struct SdPacket {
     /// [PRS_SOMEIPSD_00278] Reboot Flag, Unicast Flag
@@ -108,7 +109,7 @@ impl SomeIpDeserialize for SdPacket {
 impl SdPacket {
     #[cfg(feature = "packet-dump")]
     pub fn dump(&self, addr: std::net::SocketAddr) {
-        log::debug!(target: "DUMP", "\n[DUMP] --- SD Message from {} ---", addr);
+        log::debug!(target: "DUMP", "\n[DUMP] [{}] --- SD Message from {} ---", crate::clock::Timestamp::now(), addr);
         for entry in &self.entries {
             log::debug!(target: "DUMP", "  [Entry] {:?}: Service=0x{:04X} Inst=0x{:04X} TTL={}", 
                 entry.entry_type, entry.service_id, entry.instance_id, entry.ttl);
@@ -134,6 +135,10 @@ impl SdPacket {
                 _ => log::debug!(target: "DUMP", "  [Option] {:?}", opt),
             }
         }
+        #[cfg(feature = "serde-types")]
+        if let Ok(json) = crate::json::to_pretty(self) {
+            log::debug!(target: "DUMP", "{}", json);
+        }
         log::debug!(target: "DUMP", "--------------------------------------\n");
     }
 }
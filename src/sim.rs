@@ -0,0 +1,154 @@
+//! # Simulation Harness (`test-utils` feature)
+//!
+//! Runs many SD nodes in one process over a [`VirtualNetwork`] driven by a
+//! [`VirtualClock`], so discovery behaviour with 100+ nodes can be checked
+//! in a unit test. Each node is a [`ServiceDiscovery`] instance with one
+//! listener (`"sim"`) on its own virtual IPv4 address.
+//!
+//! ```ignore
+//! let mut sim = Simulation::new(100, NetworkConditions::default());
+//! for i in 0..100 { sim.node_mut(i).offer_service(0x1000 + i as u16, 1, 1, 0, "sim", 30500, 0x11, None); }
+//! let converged = sim.run_until(Duration::from_millis(10), Duration::from_secs(5), |s| s.all_discovered(...));
+//! ```
+
+use crate::clock::Clock;
+use crate::sd::machine::{SdListener, ServiceDiscovery};
+use crate::transport::{NetworkConditions, NetworkStats, VirtualNetwork};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Manually advanced clock shared by all simulated nodes and the network.
+pub struct VirtualClock {
+    base: Instant,
+    offset_nanos: AtomicU64,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        VirtualClock { base: Instant::now(), offset_nanos: AtomicU64::new(0) }
+    }
+
+    pub fn advance(&self, dt: Duration) {
+        self.offset_nanos.fetch_add(dt.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Virtual time since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.offset_nanos.load(Ordering::SeqCst))
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.base + self.elapsed()
+    }
+}
+
+pub const SIM_SD_PORT: u16 = 30490;
+pub const SIM_MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(224, 224, 224, 245);
+
+pub struct Simulation {
+    pub clock: Arc<VirtualClock>,
+    pub network: Arc<VirtualNetwork>,
+    nodes: Vec<ServiceDiscovery>,
+}
+
+impl Simulation {
+    /// Create `count` nodes at 10.0.x.y sharing one SD multicast group.
+    pub fn new(count: usize, conditions: NetworkConditions) -> Self {
+        Self::with_seed(count, conditions, 0x5EED)
+    }
+
+    /// As [`Simulation::new`], with an explicit seed for loss/jitter.
+    pub fn with_seed(count: usize, conditions: NetworkConditions, seed: u64) -> Self {
+        let clock = Arc::new(VirtualClock::new());
+        let network = VirtualNetwork::new(clock.clone(), conditions, seed);
+        let group = SocketAddr::new(IpAddr::V4(SIM_MULTICAST_GROUP), SIM_SD_PORT);
+
+        let nodes = (0..count)
+            .map(|i| {
+                let ip = Self::node_ip(i);
+                let addr = SocketAddr::new(IpAddr::V4(ip), SIM_SD_PORT);
+                let transport = network.bind(addr).expect("duplicate simulated node address");
+                network.join_multicast(group, addr);
+
+                let mut sd = ServiceDiscovery::new();
+                sd.set_clock(clock.clone());
+                sd.add_listener(SdListener {
+                    alias: "sim".to_string(),
+                    transport_v4: Some(Box::new(transport)),
+                    transport_v6: None,
+                    multicast_group_v4: Some(group),
+                    multicast_group_v6: None,
+                    local_ip_v4: Some(ip),
+                    local_ip_v6: None,
+                });
+                sd
+            })
+            .collect();
+
+        Simulation { clock, network, nodes }
+    }
+
+    /// Virtual IPv4 address of node `index`.
+    pub fn node_ip(index: usize) -> Ipv4Addr {
+        Ipv4Addr::new(10, 0, (index / 250) as u8, (index % 250) as u8 + 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, index: usize) -> &ServiceDiscovery {
+        &self.nodes[index]
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut ServiceDiscovery {
+        &mut self.nodes[index]
+    }
+
+    /// Advance virtual time by `dt`, then poll every node once.
+    pub fn step(&mut self, dt: Duration) {
+        self.clock.advance(dt);
+        for node in &mut self.nodes {
+            node.poll();
+        }
+    }
+
+    /// Step in increments of `tick` until `done` holds or `timeout` of virtual time passes.
+    /// Returns the virtual time taken, or `None` on timeout.
+    pub fn run_until(&mut self, tick: Duration, timeout: Duration, mut done: impl FnMut(&Simulation) -> bool) -> Option<Duration> {
+        let start = self.clock.elapsed();
+        loop {
+            if done(self) {
+                return Some(self.clock.elapsed() - start);
+            }
+            if self.clock.elapsed() - start >= timeout {
+                return None;
+            }
+            self.step(tick);
+        }
+    }
+
+    /// Whether every node (except `owner`) has discovered the given service instance.
+    pub fn discovered_by_all(&self, service_id: u16, instance_id: u16, owner: usize) -> bool {
+        self.nodes.iter().enumerate()
+            .all(|(i, n)| i == owner || n.get_service(service_id, instance_id).is_some())
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.network.stats()
+    }
+}
@@ -0,0 +1,208 @@
+//! # Tracing
+//!
+//! Spans emitted with the `tracing` feature:
+//!
+//! - `dispatch` around serving each request, from running its handler to sending the response
+//! - `sd.offer`, `sd.find` and `sd.subscribe` around the SD entries sent for each phase
+//! - `tp.reassemble` from the first segment of a message to its completion or discard,
+//!   with the `outcome` recorded when it ends
+//!
+//! Request spans carry `service`, `method`, `client` and `session`, so a request, what
+//! its handler logs and its response share one correlation key. Without the feature,
+//! [`Span`] is a unit struct and the calls compile to nothing.
+
+use crate::codec::SomeIpHeader;
+use std::net::SocketAddr;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn entered(self) -> Self {
+        self
+    }
+
+    pub(crate) fn current() -> Self {
+        Span
+    }
+
+    pub(crate) fn record(&self, _field: &str, _value: &str) -> &Self {
+        self
+    }
+}
+
+/// Span around serving the request with `header` to an instance of its service.
+#[cfg(feature = "tracing")]
+pub(crate) fn dispatch(header: &SomeIpHeader, instance_id: u16) -> Span {
+    tracing::info_span!("dispatch", service = header.service_id, instance = instance_id, method = header.method_id,
+        client = header.client_id, session = header.session_id, message_type = header.message_type)
+}
+
+/// Span around the OfferService entry sent for a local service in SD phase `phase`.
+#[cfg(feature = "tracing")]
+pub(crate) fn sd_offer(service_id: u16, instance_id: u16, phase: &str) -> Span {
+    tracing::debug_span!("sd.offer", service = service_id, instance = instance_id, phase)
+}
+
+/// Span around a FindService entry; `repetition` is 0 for the first.
+#[cfg(feature = "tracing")]
+pub(crate) fn sd_find(service_id: u16, instance_id: u16, repetition: u32) -> Span {
+    tracing::debug_span!("sd.find", service = service_id, instance = instance_id, repetition)
+}
+
+/// Span around a SubscribeEventgroup entry; a `ttl` of 0 is a StopSubscribe.
+#[cfg(feature = "tracing")]
+pub(crate) fn sd_subscribe(service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32) -> Span {
+    tracing::debug_span!("sd.subscribe", service = service_id, instance = instance_id, eventgroup = eventgroup_id, ttl)
+}
+
+/// Span of reassembling the message `message_id`/`request_id` from `source`.
+#[cfg(feature = "tracing")]
+pub(crate) fn tp_reassemble(source: SocketAddr, message_id: u32, request_id: u32) -> Span {
+    tracing::debug_span!("tp.reassemble", service = (message_id >> 16) as u16, method = message_id as u16,
+        client = (request_id >> 16) as u16, session = request_id as u16, %source, outcome = tracing::field::Empty)
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn dispatch(_header: &SomeIpHeader, _instance_id: u16) -> Span {
+    Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn sd_offer(_service_id: u16, _instance_id: u16, _phase: &str) -> Span {
+    Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn sd_find(_service_id: u16, _instance_id: u16, _repetition: u32) -> Span {
+    Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn sd_subscribe(_service_id: u16, _instance_id: u16, _eventgroup_id: u16, _ttl: u32) -> Span {
+    Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn tp_reassemble(_source: SocketAddr, _message_id: u32, _request_id: u32) -> Span {
+    Span
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::codec::tp::{TpHeader, TpReassembler};
+    use crate::logging::{FusionLogger, LogContext, LogLevel, TracingLogger};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Names and fields of spans, and events with the span they were emitted in.
+    #[derive(Default)]
+    struct Recorded {
+        spans: Vec<(String, Vec<(String, String)>)>,
+        events: Vec<(Option<String>, Vec<(String, String)>)>,
+        current: Vec<usize>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Recorded>>);
+
+    struct Fields<'a>(&'a mut Vec<(String, String)>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Vec::new();
+            span.record(&mut Fields(&mut fields));
+            let mut recorded = self.0.lock().unwrap();
+            recorded.spans.push((span.metadata().name().to_string(), fields));
+            Id::from_u64(recorded.spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut recorded = self.0.lock().unwrap();
+            values.record(&mut Fields(&mut recorded.spans[span.into_u64() as usize - 1].1));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Vec::new();
+            event.record(&mut Fields(&mut fields));
+            let mut recorded = self.0.lock().unwrap();
+            let span = recorded.current.last().map(|&i| recorded.spans[i].0.clone());
+            recorded.events.push((span, fields));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.0.lock().unwrap().current.push(span.into_u64() as usize - 1);
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.0.lock().unwrap().current.pop();
+        }
+    }
+
+    fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        fields.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_dispatch_span_correlates_request_and_logs() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let header = SomeIpHeader::new(0x4242, 0x0001, 0x0010, 0x0007, 0x00, 0);
+            let _span = dispatch(&header, 1).entered();
+            TracingLogger.log_with(LogLevel::Warn, "Handler", &LogContext::default().with_service(0x4242), "slow");
+        });
+
+        let recorded = recorder.0.lock().unwrap();
+        let (name, fields) = &recorded.spans[0];
+        assert_eq!(name, "dispatch");
+        for (key, value) in [("service", "16962"), ("instance", "1"), ("method", "1"), ("client", "16"), ("session", "7")] {
+            assert_eq!(field(fields, key), Some(value), "{}", key);
+        }
+        // Logged lines become events inside the request's span
+        let (span, fields) = &recorded.events[0];
+        assert_eq!(span.as_deref(), Some("dispatch"));
+        assert_eq!(field(fields, "message"), Some("slow"));
+        assert_eq!(field(fields, "component"), Some("\"Handler\""));
+        assert_eq!(field(fields, "service"), Some("16962"));
+    }
+
+    #[test]
+    fn test_tp_reassembly_span_records_outcome() {
+        let recorder = Recorder::default();
+        let source: SocketAddr = "127.0.0.1:30509".parse().unwrap();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let mut reassembler = TpReassembler::new();
+            assert_eq!(reassembler.process_segment(source, 0x4242_0001, 0x0010_0007, &TpHeader::new(0, true), &[0; 16]), Ok(None));
+            assert!(reassembler.process_segment(source, 0x4242_0001, 0x0010_0007, &TpHeader::new(16, false), &[0; 4]).unwrap().is_some());
+            assert_eq!(reassembler.process_segment(source, 0x4242_0002, 0x0010_0008, &TpHeader::new(0, true), &[0; 16]), Ok(None));
+            assert!(reassembler.abandon(source, 0x4242_0002, 0x0010_0008));
+        });
+
+        let recorded = recorder.0.lock().unwrap();
+        let spans: Vec<_> = recorded.spans.iter().map(|(name, fields)| (name.as_str(), field(fields, "method"), field(fields, "session"), field(fields, "outcome"))).collect();
+        assert_eq!(spans, [
+            ("tp.reassemble", Some("1"), Some("7"), Some("\"complete\"")),
+            ("tp.reassemble", Some("2"), Some("8"), Some("\"abandoned\"")),
+        ]);
+    }
+}
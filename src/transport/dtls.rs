@@ -0,0 +1,364 @@
+//! # DTLS
+//!
+//! [`DtlsTransport`] wraps a [`UdpTransport`] and runs DTLS (OpenSSL) with every peer it
+//! exchanges datagrams with, with the `dtls` feature. Each peer address has its own
+//! session and handshake state:
+//!
+//! - Sending to a peer without a session starts a handshake as the client. Messages
+//!   sent before it finishes are queued and go out once it does.
+//! - A datagram from a peer without a session starts a handshake as the server.
+//! - A handshake that has not finished within [`HANDSHAKE_TIMEOUT`] is abandoned with
+//!   its queued messages; the next datagram to or from the peer starts a new one.
+//! - When both sides start at once, the side with the lower address stays the client.
+//! - A ClientHello on an established session (the peer restarted) replaces the session.
+//!
+//! Each SOME/IP message is one DTLS record in one datagram.
+
+use super::traits::SomeIpTransport;
+use super::udp::UdpTransport;
+use openssl::ssl::{ErrorCode, Ssl, SslContext, SslFiletype, SslMethod, SslOptions, SslStream, SslVerifyMode};
+use std::collections::{HashMap, VecDeque};
+use std::io::{ErrorKind, Read, Result, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time a handshake may take before it is abandoned.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest datagram of the handshake (bytes, default); application records are not split.
+pub const DEFAULT_HANDSHAKE_MTU: u32 = 1400;
+
+/// Messages queued per peer while its handshake runs; further sends are refused with `WouldBlock`.
+pub const MAX_QUEUED_MESSAGES: usize = 64;
+
+/// Largest datagram read from the socket.
+const MAX_DATAGRAM_LEN: usize = 65535;
+
+fn ssl_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+/// Context presenting the chain in `cert` with `key` and verifying peers against the CAs in `ca`.
+pub fn context(cert: &Path, key: &Path, ca: &Path) -> Result<SslContext> {
+    let invalid = |path: &Path, what: &str, e: openssl::error::ErrorStack| {
+        std::io::Error::new(ErrorKind::InvalidInput, format!("{} '{}': {}", what, path.display(), e))
+    };
+    let mut builder = SslContext::builder(SslMethod::dtls()).map_err(ssl_error)?;
+    builder.set_certificate_chain_file(cert).map_err(|e| invalid(cert, "certificates", e))?;
+    builder.set_private_key_file(key, SslFiletype::PEM).map_err(|e| invalid(key, "private key", e))?;
+    builder.check_private_key().map_err(|e| invalid(key, "private key", e))?;
+    builder.set_ca_file(ca).map_err(|e| invalid(ca, "CA certificates", e))?;
+    // The link MTU is set per session; the custom BIO cannot be asked for it
+    builder.set_options(SslOptions::NO_QUERY_MTU);
+    Ok(builder.build())
+}
+
+/// Whether `datagram` holds a ClientHello starting a new session: a handshake record of
+/// epoch 0 whose first message is of type 1.
+fn is_client_hello(datagram: &[u8]) -> bool {
+    datagram.len() > 13 && datagram[0] == 22 && datagram[3..5] == [0, 0] && datagram[13] == 1
+}
+
+/// Datagrams of one peer, as the byte stream OpenSSL reads and writes: every read returns
+/// one received datagram, every write sends one.
+struct Datagrams {
+    udp: Arc<UdpTransport>,
+    peer: SocketAddr,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let datagram = self.received.pop_front().ok_or_else(|| std::io::Error::from(ErrorKind::WouldBlock))?;
+        let len = datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+        Ok(len)
+    }
+}
+
+impl Write for Datagrams {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.udp.send(buf, Some(self.peer))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Session with one peer.
+struct Peer {
+    stream: SslStream<Datagrams>,
+    client: bool,
+    established: bool,
+    started: Instant,
+    /// Messages sent before the handshake finished
+    queued: Vec<Vec<u8>>,
+}
+
+impl Peer {
+    fn expired(&self, now: Instant) -> bool {
+        !self.established && now.duration_since(self.started) > HANDSHAKE_TIMEOUT
+    }
+
+    /// Continue the handshake. Once it finishes, the queued messages are sent.
+    fn handshake(&mut self) -> Result<()> {
+        if self.established {
+            return Ok(());
+        }
+        match self.stream.do_handshake() {
+            Ok(()) => {
+                self.established = true;
+                for message in std::mem::take(&mut self.queued) {
+                    self.stream.ssl_write(&message).map_err(ssl_error)?;
+                }
+                Ok(())
+            }
+            Err(e) if e.code() == ErrorCode::WANT_READ || e.code() == ErrorCode::WANT_WRITE => Ok(()),
+            Err(e) => Err(ssl_error(e)),
+        }
+    }
+}
+
+/// UDP transport running DTLS with each peer.
+pub struct DtlsTransport {
+    udp: Arc<UdpTransport>,
+    context: SslContext,
+    client_auth: bool,
+    server_name: Option<String>,
+    mtu: u32,
+    peers: Mutex<HashMap<SocketAddr, Peer>>,
+}
+
+impl DtlsTransport {
+    /// Run DTLS with `context` over `udp`; see [`context`].
+    pub fn new(udp: UdpTransport, context: SslContext) -> Self {
+        DtlsTransport {
+            udp: Arc::new(udp),
+            context,
+            client_auth: false,
+            server_name: None,
+            mtu: DEFAULT_HANDSHAKE_MTU,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Require peers that start a handshake to present a certificate issued by the CA.
+    pub fn set_client_auth(&mut self, client_auth: bool) {
+        self.client_auth = client_auth;
+    }
+
+    /// Name the certificate of peers this transport connects to must be valid for;
+    /// `None` (the default) checks the peer's IP address.
+    pub fn set_server_name(&mut self, server_name: Option<String>) {
+        self.server_name = server_name;
+    }
+
+    /// Largest datagram of the handshake (default [`DEFAULT_HANDSHAKE_MTU`]).
+    pub fn set_handshake_mtu(&mut self, mtu: u32) {
+        self.mtu = mtu;
+    }
+
+    /// Peers with a finished handshake.
+    pub fn established_peers(&self) -> Vec<SocketAddr> {
+        self.peers.lock().unwrap().iter().filter(|(_, p)| p.established).map(|(addr, _)| *addr).collect()
+    }
+
+    fn session(&self, peer: SocketAddr, client: bool) -> Result<Peer> {
+        let mut ssl = Ssl::new(&self.context).map_err(ssl_error)?;
+        ssl.set_mtu(self.mtu).map_err(ssl_error)?;
+        if client {
+            ssl.set_verify(SslVerifyMode::PEER);
+            match &self.server_name {
+                Some(name) => ssl.param_mut().set_host(name).map_err(ssl_error)?,
+                None => ssl.param_mut().set_ip(peer.ip()).map_err(ssl_error)?,
+            }
+            ssl.set_connect_state();
+        } else {
+            ssl.set_verify(if self.client_auth { SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT } else { SslVerifyMode::NONE });
+            ssl.set_accept_state();
+        }
+        let datagrams = Datagrams { udp: self.udp.clone(), peer, received: VecDeque::new() };
+        let stream = SslStream::new(ssl, datagrams).map_err(ssl_error)?;
+        Ok(Peer { stream, client, established: false, started: Instant::now(), queued: Vec::new() })
+    }
+
+    /// Hand a datagram from `from` to its session. Returns decrypted application data
+    /// written to `buffer`, if the datagram carried any.
+    fn deliver(&self, peers: &mut HashMap<SocketAddr, Peer>, from: SocketAddr, datagram: &[u8], buffer: &mut [u8]) -> Option<usize> {
+        let now = Instant::now();
+        let hello = is_client_hello(datagram);
+        let replace = match peers.get(&from) {
+            None => true,
+            Some(peer) if peer.expired(now) => true,
+            // A restarted peer, or both sides starting at once with us the higher address
+            Some(peer) if hello => peer.established || (peer.client && self.udp.local_addr().is_ok_and(|local| local > from)),
+            Some(_) => false,
+        };
+        if replace {
+            let queued = peers.remove(&from).filter(|p| !p.established).map(|p| p.queued).unwrap_or_default();
+            let mut peer = self.session(from, false).ok()?;
+            peer.queued = queued;
+            peers.insert(from, peer);
+        }
+        let peer = peers.get_mut(&from)?;
+        peer.stream.get_mut().received.push_back(datagram.to_vec());
+        if !peer.established {
+            if peer.handshake().is_err() {
+                peers.remove(&from);
+            }
+            return None;
+        }
+        match peer.stream.ssl_read(buffer) {
+            Ok(len) => Some(len),
+            Err(e) if e.code() == ErrorCode::WANT_READ => None,
+            // close_notify, or a broken session
+            Err(_) => {
+                peers.remove(&from);
+                None
+            }
+        }
+    }
+}
+
+impl SomeIpTransport for DtlsTransport {
+    fn send(&self, data: &[u8], destination: Option<SocketAddr>) -> Result<usize> {
+        let peer_addr = destination.ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "DTLS requires a destination address"))?;
+        let mut peers = self.peers.lock().unwrap();
+        if peers.get(&peer_addr).is_none_or(|p| p.expired(Instant::now())) {
+            peers.insert(peer_addr, self.session(peer_addr, true)?);
+        }
+        let peer = peers.get_mut(&peer_addr).expect("session inserted above");
+        if peer.established {
+            return peer.stream.ssl_write(data).map_err(ssl_error);
+        }
+        if peer.queued.len() >= MAX_QUEUED_MESSAGES {
+            return Err(std::io::Error::new(ErrorKind::WouldBlock, "DTLS handshake in progress"));
+        }
+        peer.queued.push(data.to_vec());
+        if let Err(e) = peer.handshake() {
+            peers.remove(&peer_addr);
+            return Err(e);
+        }
+        Ok(data.len())
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let mut datagram = vec![0u8; MAX_DATAGRAM_LEN];
+        loop {
+            let (len, from) = self.udp.receive(&mut datagram)?;
+            let mut peers = self.peers.lock().unwrap();
+            if let Some(len) = self.deliver(&mut peers, from, &datagram[..len], buffer) {
+                return Ok((len, from));
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.udp.local_addr()
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        self.udp.set_nonblocking(nonblocking)
+    }
+
+    /// Send close_notify to every established peer and drop all sessions.
+    fn close(&self) -> Result<()> {
+        for (_, mut peer) in self.peers.lock().unwrap().drain() {
+            if peer.established {
+                let _ = peer.stream.shutdown();
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn poll_fds(&self) -> Option<Vec<std::os::fd::RawFd>> {
+        self.udp.poll_fds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Certificates generated by tests/gen_tls_fixtures.py
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    fn transport(identity: &str) -> DtlsTransport {
+        let ctx = context(&fixture(&format!("{}.pem", identity)), &fixture(&format!("{}.key", identity)), &fixture("ca.pem")).unwrap();
+        let transport = DtlsTransport::new(UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap(), ctx);
+        transport.set_nonblocking(true).unwrap();
+        transport
+    }
+
+    /// Receive on both transports until `until` holds or a second passes. Returns what each received.
+    fn pump(a: &DtlsTransport, b: &DtlsTransport, until: impl Fn(&[Vec<u8>], &[Vec<u8>]) -> bool) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let (mut at_a, mut at_b) = (Vec::new(), Vec::new());
+        let mut buf = [0u8; 2048];
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while !until(&at_a, &at_b) && Instant::now() < deadline {
+            if let Ok((len, _)) = a.receive(&mut buf) {
+                at_a.push(buf[..len].to_vec());
+            }
+            if let Ok((len, _)) = b.receive(&mut buf) {
+                at_b.push(buf[..len].to_vec());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        (at_a, at_b)
+    }
+
+    #[test]
+    fn test_messages_queued_until_handshake_finishes() {
+        let client = transport("client");
+        let server = transport("server");
+        let server_addr = server.local_addr().unwrap();
+        let client_addr = client.local_addr().unwrap();
+
+        // Both go out once the handshake finishes, in order and encrypted
+        client.send(b"first", Some(server_addr)).unwrap();
+        client.send(b"second", Some(server_addr)).unwrap();
+        let (_, at_server) = pump(&client, &server, |_, b| b.len() == 2);
+        assert_eq!(at_server, vec![b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(client.established_peers(), vec![server_addr]);
+
+        server.send(b"reply", Some(client_addr)).unwrap();
+        let (at_client, _) = pump(&client, &server, |a, _| !a.is_empty());
+        assert_eq!(at_client, vec![b"reply".to_vec()]);
+    }
+
+    #[test]
+    fn test_server_name_checked_against_certificate() {
+        let mut client = transport("client");
+        client.set_server_name(Some("elsewhere.example".to_string()));
+        let server = transport("server");
+        client.send(b"secret", Some(server.local_addr().unwrap())).unwrap();
+        let (_, at_server) = pump(&client, &server, |_, b| !b.is_empty());
+        assert!(at_server.is_empty());
+        assert!(client.established_peers().is_empty());
+    }
+
+    #[test]
+    fn test_client_auth_requires_client_certificate() {
+        let mut server = transport("server");
+        server.set_client_auth(true);
+        let server_addr = server.local_addr().unwrap();
+
+        // The server's own certificate lacks the clientAuth usage, so it is refused as a client
+        let impostor = transport("server");
+        impostor.send(b"secret", Some(server_addr)).unwrap();
+        let (_, at_server) = pump(&impostor, &server, |_, b| !b.is_empty());
+        assert!(at_server.is_empty());
+
+        let client = transport("client");
+        client.send(b"hello", Some(server_addr)).unwrap();
+        let (_, at_server) = pump(&client, &server, |_, b| !b.is_empty());
+        assert_eq!(at_server, vec![b"hello".to_vec()]);
+    }
+}
@@ -0,0 +1,236 @@
+//! In-memory transport over a simulated network (`test-utils` feature).
+//!
+//! A [`VirtualNetwork`] routes datagrams between [`MemTransport`] endpoints
+//! with configurable latency, jitter and loss. Delivery times follow the
+//! network's [`Clock`], so a virtual clock makes runs independent of wall time.
+
+use super::traits::SomeIpTransport;
+use crate::clock::Clock;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Link characteristics applied to every datagram.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetworkConditions {
+    pub latency: Duration,
+    /// Extra random delay in `[0, jitter)` on top of `latency`
+    pub jitter: Duration,
+    /// Probability (0.0 - 1.0) that a datagram is lost, per receiver
+    pub loss: f64,
+}
+
+/// Traffic counters for the whole network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// `send` calls
+    pub sent: u64,
+    pub bytes_sent: u64,
+    /// Datagrams queued for a receiver (a multicast send counts once per member)
+    pub delivered: u64,
+    pub dropped: u64,
+}
+
+struct Datagram {
+    deliver_at: Instant,
+    src: SocketAddr,
+    data: Vec<u8>,
+}
+
+struct NetInner {
+    conditions: NetworkConditions,
+    inboxes: HashMap<SocketAddr, VecDeque<Datagram>>,
+    groups: HashMap<SocketAddr, Vec<SocketAddr>>,
+    rng: u64,
+    stats: NetworkStats,
+}
+
+impl NetInner {
+    /// xorshift64*, deterministic per network seed
+    fn next_random(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        (self.rng.wrapping_mul(0x2545F4914F6CDD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn enqueue(&mut self, now: Instant, src: SocketAddr, dest: SocketAddr, data: &[u8]) {
+        if !self.inboxes.contains_key(&dest) {
+            return;
+        }
+        if self.conditions.loss > 0.0 && self.next_random() < self.conditions.loss {
+            self.stats.dropped += 1;
+            return;
+        }
+        let jitter = self.conditions.jitter.mul_f64(self.next_random());
+        let deliver_at = now + self.conditions.latency + jitter;
+        self.stats.delivered += 1;
+        if let Some(inbox) = self.inboxes.get_mut(&dest) {
+            inbox.push_back(Datagram { deliver_at, src, data: data.to_vec() });
+        }
+    }
+}
+
+/// A simulated network connecting [`MemTransport`]s.
+pub struct VirtualNetwork {
+    clock: Arc<dyn Clock>,
+    inner: Mutex<NetInner>,
+}
+
+impl VirtualNetwork {
+    pub fn new(clock: Arc<dyn Clock>, conditions: NetworkConditions, seed: u64) -> Arc<Self> {
+        Arc::new(VirtualNetwork {
+            clock,
+            inner: Mutex::new(NetInner {
+                conditions,
+                inboxes: HashMap::new(),
+                groups: HashMap::new(),
+                rng: seed.max(1),
+                stats: NetworkStats::default(),
+            }),
+        })
+    }
+
+    /// Bind an endpoint at `addr`.
+    pub fn bind(self: &Arc<Self>, addr: SocketAddr) -> Result<MemTransport> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.inboxes.contains_key(&addr) {
+            return Err(Error::new(ErrorKind::AddrInUse, format!("{} already bound", addr)));
+        }
+        inner.inboxes.insert(addr, VecDeque::new());
+        Ok(MemTransport { network: self.clone(), addr })
+    }
+
+    /// Subscribe the endpoint at `member` to datagrams sent to `group`.
+    pub fn join_multicast(&self, group: SocketAddr, member: SocketAddr) {
+        let mut inner = self.inner.lock().unwrap();
+        let members = inner.groups.entry(group).or_default();
+        if !members.contains(&member) {
+            members.push(member);
+        }
+    }
+
+    pub fn set_conditions(&self, conditions: NetworkConditions) {
+        self.inner.lock().unwrap().conditions = conditions;
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.inner.lock().unwrap().stats
+    }
+
+    pub fn reset_stats(&self) {
+        self.inner.lock().unwrap().stats = NetworkStats::default();
+    }
+}
+
+/// Datagram endpoint on a [`VirtualNetwork`]. Unbinds itself when dropped.
+pub struct MemTransport {
+    network: Arc<VirtualNetwork>,
+    addr: SocketAddr,
+}
+
+impl SomeIpTransport for MemTransport {
+    fn send(&self, data: &[u8], destination: Option<SocketAddr>) -> Result<usize> {
+        let dest = destination.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "MemTransport requires a destination"))?;
+        let now = self.network.clock.now();
+        let mut inner = self.network.inner.lock().unwrap();
+        inner.stats.sent += 1;
+        inner.stats.bytes_sent += data.len() as u64;
+        if dest.ip().is_multicast() {
+            let members = inner.groups.get(&dest).cloned().unwrap_or_default();
+            for member in members {
+                inner.enqueue(now, self.addr, member, data);
+            }
+        } else {
+            inner.enqueue(now, self.addr, dest, data);
+        }
+        Ok(data.len())
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let now = self.network.clock.now();
+        let mut inner = self.network.inner.lock().unwrap();
+        let inbox = inner.inboxes.get_mut(&self.addr).ok_or_else(|| Error::from(ErrorKind::NotConnected))?;
+        let Some(pos) = inbox.iter().position(|d| d.deliver_at <= now) else {
+            return Err(Error::from(ErrorKind::WouldBlock));
+        };
+        let datagram = inbox.remove(pos).unwrap();
+        let len = datagram.data.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&datagram.data[..len]);
+        Ok((len, datagram.src))
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> Result<()> {
+        // Always non-blocking
+        Ok(())
+    }
+}
+
+impl Drop for MemTransport {
+    fn drop(&mut self) {
+        let mut inner = self.network.inner.lock().unwrap();
+        inner.inboxes.remove(&self.addr);
+        for members in inner.groups.values_mut() {
+            members.retain(|m| *m != self.addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+
+    fn network(conditions: NetworkConditions) -> Arc<VirtualNetwork> {
+        VirtualNetwork::new(Arc::new(SystemClock), conditions, 42)
+    }
+
+    #[test]
+    fn test_unicast_and_multicast() {
+        let net = network(NetworkConditions::default());
+        let a = net.bind("10.0.0.1:30490".parse().unwrap()).unwrap();
+        let b = net.bind("10.0.0.2:30490".parse().unwrap()).unwrap();
+        let group: SocketAddr = "224.224.224.245:30490".parse().unwrap();
+        net.join_multicast(group, a.local_addr().unwrap());
+        net.join_multicast(group, b.local_addr().unwrap());
+
+        let mut buf = [0u8; 16];
+        a.send(b"hi", Some(b.local_addr().unwrap())).unwrap();
+        assert_eq!(b.receive(&mut buf).unwrap(), (2, a.local_addr().unwrap()));
+        assert_eq!(b.receive(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+
+        b.send(b"all", Some(group)).unwrap();
+        assert_eq!(a.receive(&mut buf).unwrap().0, 3);
+        assert_eq!(b.receive(&mut buf).unwrap().0, 3); // multicast loops back
+        assert_eq!(net.stats().delivered, 3);
+    }
+
+    #[test]
+    fn test_rebind_after_drop() {
+        let net = network(NetworkConditions::default());
+        let addr: SocketAddr = "10.0.0.1:1".parse().unwrap();
+        let t = net.bind(addr).unwrap();
+        assert_eq!(net.bind(addr).err().unwrap().kind(), ErrorKind::AddrInUse);
+        drop(t);
+        assert!(net.bind(addr).is_ok());
+    }
+
+    #[test]
+    fn test_loss_is_applied() {
+        let net = network(NetworkConditions { loss: 0.5, ..Default::default() });
+        let a = net.bind("10.0.0.1:1".parse().unwrap()).unwrap();
+        let _b = net.bind("10.0.0.2:1".parse().unwrap()).unwrap();
+        for _ in 0..1000 {
+            a.send(b"x", Some("10.0.0.2:1".parse().unwrap())).unwrap();
+        }
+        let stats = net.stats();
+        assert_eq!(stats.delivered + stats.dropped, 1000);
+        assert!(stats.dropped > 400 && stats.dropped < 600, "dropped {}", stats.dropped);
+    }
+}
@@ -8,6 +8,11 @@
 //! - [`UdpTransport`] - UDP transport with multicast support
 //! - [`TcpTransport`] - TCP client for point-to-point connections
 //! - [`TcpServer`] - TCP server for accepting connections
+//! - [`TlsSession`] - TLS on TCP connections (`tls` feature)
+//! - `DtlsTransport` - DTLS on UDP, a session per peer (`dtls` feature)
+//! - [`TcpConnectionPool`] - Client connections per remote endpoint, reconnected with backoff
+//! - `MemTransport` - In-memory transport for simulations (`test-utils` feature)
+//! - `ShmTransport` - Shared-memory transport between processes on one host (Linux)
 //!
 //! ## Example
 //!
@@ -21,7 +26,23 @@
 pub mod traits;
 pub mod udp;
 pub mod tcp;
+pub mod pool;
+pub mod tls;
+#[cfg(feature = "dtls")]
+pub mod dtls;
+#[cfg(feature = "test-utils")]
+pub mod mem;
+#[cfg(target_os = "linux")]
+pub mod shm;
 
 pub use traits::*;
 pub use udp::*;
 pub use tcp::*;
+pub use pool::TcpConnectionPool;
+pub use tls::TlsSession;
+#[cfg(feature = "dtls")]
+pub use dtls::DtlsTransport;
+#[cfg(feature = "test-utils")]
+pub use mem::*;
+#[cfg(target_os = "linux")]
+pub use shm::ShmTransport;
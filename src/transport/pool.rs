@@ -0,0 +1,223 @@
+//! # TCP Connection Pool
+//!
+//! [`TcpConnectionPool`] keeps one non-blocking client connection per remote
+//! endpoint, shared by every request, subscription and event sent there. A
+//! connection that fails (reset, closed by the peer, write error) is dropped
+//! with [`TcpConnectionPool::drop_connection`], and the next
+//! [`TcpConnectionPool::get`] reconnects, from the same local address if the
+//! endpoint was first connected with [`TcpConnectionPool::get_from`]. Callers
+//! keep using the endpoint and never see the new connection.
+//!
+//! Failed connection attempts back off exponentially from the minimum to the
+//! maximum delay; until the delay has passed, `get` fails with `NotConnected`
+//! without trying. A configured handshake (e.g. an initial Magic Cookie) runs
+//! on every new connection before it is handed out.
+
+use super::tcp::TcpTransport;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Run on each new connection before it is pooled; an error fails the connection attempt.
+pub type Handshake = Arc<dyn Fn(&TcpTransport) -> Result<()> + Send + Sync>;
+
+/// Delay before the first reconnect attempt after a failed one
+pub const DEFAULT_BACKOFF_MIN: Duration = Duration::from_millis(100);
+/// Longest delay between reconnect attempts
+pub const DEFAULT_BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct PoolEntry {
+    connection: Option<Arc<TcpTransport>>,
+    /// Local address connections are opened from, if pinned
+    local: Option<SocketAddr>,
+    /// Consecutive failed connection attempts
+    failures: u32,
+    /// No connection attempt before this time
+    retry_at: Option<Instant>,
+}
+
+/// Client connections by remote endpoint, reconnected on demand.
+pub struct TcpConnectionPool {
+    entries: Mutex<HashMap<SocketAddr, PoolEntry>>,
+    backoff: (Duration, Duration),
+    handshake: Option<Handshake>,
+}
+
+impl Default for TcpConnectionPool {
+    fn default() -> Self {
+        TcpConnectionPool { entries: Mutex::new(HashMap::new()), backoff: (DEFAULT_BACKOFF_MIN, DEFAULT_BACKOFF_MAX), handshake: None }
+    }
+}
+
+impl TcpConnectionPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delays between failed connection attempts, doubling from `min` up to `max`.
+    pub fn set_backoff(&mut self, min: Duration, max: Duration) {
+        self.backoff = (min, max.max(min));
+    }
+
+    pub fn set_handshake(&mut self, handshake: Handshake) {
+        self.handshake = Some(handshake);
+    }
+
+    /// The connection to `target`, connecting if there is none.
+    pub fn get(&self, target: SocketAddr) -> Result<Arc<TcpTransport>> {
+        self.connection(target, None)
+    }
+
+    /// The connection to `target`, connecting from `local` if there is none.
+    /// Later reconnects to `target` are made from `local` too.
+    pub fn get_from(&self, local: SocketAddr, target: SocketAddr) -> Result<Arc<TcpTransport>> {
+        self.connection(target, Some(local))
+    }
+
+    fn connection(&self, target: SocketAddr, local: Option<SocketAddr>) -> Result<Arc<TcpTransport>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(target).or_default();
+        if let Some(connection) = &entry.connection {
+            return Ok(connection.clone());
+        }
+        entry.local = local.or(entry.local);
+        let now = Instant::now();
+        if let Some(retry_at) = entry.retry_at.filter(|at| *at > now) {
+            return Err(Error::new(ErrorKind::NotConnected, format!("reconnecting to {} in {} ms", target, (retry_at - now).as_millis())));
+        }
+        match self.connect(target, entry.local) {
+            Ok(connection) => {
+                let connection = Arc::new(connection);
+                *entry = PoolEntry { connection: Some(connection.clone()), local: entry.local, failures: 0, retry_at: None };
+                Ok(connection)
+            }
+            Err(e) => {
+                let (min, max) = self.backoff;
+                let delay = min.saturating_mul(1u32 << entry.failures.min(16)).min(max);
+                entry.failures += 1;
+                entry.retry_at = Some(now + delay);
+                Err(e)
+            }
+        }
+    }
+
+    fn connect(&self, target: SocketAddr, local: Option<SocketAddr>) -> Result<TcpTransport> {
+        let connection = match local {
+            Some(local) => TcpTransport::connect_from(local, target)?,
+            None => TcpTransport::connect(target)?,
+        };
+        if let Some(handshake) = &self.handshake {
+            handshake(&connection)?;
+        }
+        connection.set_nonblocking(true)?;
+        Ok(connection)
+    }
+
+    /// Drop the failed connection to `target`; the next `get` reconnects right away.
+    /// Returns whether there was one.
+    pub fn drop_connection(&self, target: SocketAddr) -> bool {
+        self.entries.lock().unwrap().get_mut(&target).is_some_and(|entry| entry.connection.take().is_some())
+    }
+
+    /// Forget the endpoints `keep` returns false for, with their connections and pinned local addresses.
+    pub fn retain(&self, mut keep: impl FnMut(&SocketAddr) -> bool) {
+        self.entries.lock().unwrap().retain(|target, _| keep(target));
+    }
+
+    pub fn is_connected(&self, target: SocketAddr) -> bool {
+        self.entries.lock().unwrap().get(&target).is_some_and(|entry| entry.connection.is_some())
+    }
+
+    /// Open connections, by remote endpoint.
+    pub fn connections(&self) -> Vec<(SocketAddr, Arc<TcpTransport>)> {
+        self.entries.lock().unwrap().iter()
+            .filter_map(|(target, entry)| entry.connection.clone().map(|c| (*target, c)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::SomeIpTransport;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_reconnects_after_drop() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        let pool = TcpConnectionPool::new();
+
+        let first = pool.get(target).unwrap();
+        assert!(Arc::ptr_eq(&first, &pool.get(target).unwrap()));
+        assert_eq!(pool.connections().len(), 1);
+
+        assert!(pool.drop_connection(target));
+        assert!(!pool.drop_connection(target));
+        assert!(!pool.is_connected(target));
+        let second = pool.get(target).unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(pool.is_connected(target));
+    }
+
+    #[test]
+    fn test_pinned_local_address_kept() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        // Pin to a free port, then reconnect to it
+        let local = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let pool = TcpConnectionPool::new();
+
+        assert_eq!(pool.get_from(local, target).unwrap().local_addr().unwrap(), local);
+        let (accepted, _) = listener.accept().unwrap();
+        drop(accepted);
+        pool.drop_connection(target);
+        assert_eq!(pool.get(target).unwrap().local_addr().unwrap(), local);
+    }
+
+    #[test]
+    fn test_failed_connects_back_off() {
+        // Nothing listens on a port just released
+        let target = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut pool = TcpConnectionPool::new();
+        pool.set_backoff(Duration::from_millis(50), Duration::from_millis(80));
+
+        assert_eq!(pool.get(target).err().map(|e| e.kind()), Some(ErrorKind::ConnectionRefused));
+        // Within the delay no attempt is made
+        assert_eq!(pool.get(target).err().map(|e| e.kind()), Some(ErrorKind::NotConnected));
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(pool.get(target).err().map(|e| e.kind()), Some(ErrorKind::ConnectionRefused));
+        // Doubled, but capped at the maximum
+        let retry_at = pool.entries.lock().unwrap()[&target].retry_at.unwrap();
+        assert!(retry_at - Instant::now() <= Duration::from_millis(80));
+        assert!(retry_at - Instant::now() > Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_handshake_runs_on_every_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let seen = count.clone();
+        let mut pool = TcpConnectionPool::new();
+        pool.set_handshake(Arc::new(move |_| {
+            seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }));
+
+        pool.get(target).unwrap();
+        pool.get(target).unwrap();
+        pool.drop_connection(target);
+        pool.get(target).unwrap();
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // A failing handshake fails the attempt
+        pool.set_handshake(Arc::new(|_| Err(Error::other("rejected"))));
+        pool.drop_connection(target);
+        assert!(pool.get(target).is_err());
+        assert!(!pool.is_connected(target));
+    }
+}
@@ -0,0 +1,417 @@
+//! Shared-memory transport for processes on the same host (Linux).
+//!
+//! Each [`ShmTransport`] owns an inbox: a ring buffer in a memory-mapped file
+//! under `/dev/shm`, named after the UDP address the transport shadows. Peers
+//! send by appending a record to the destination's inbox and waking it through
+//! a futex on the shared mapping, so payloads never touch the network stack.
+//! The source address reported by `receive` is the sender's shadowed UDP
+//! address, so replies and subscriptions work exactly as they do over UDP.
+//!
+//! Use between two nodes is negotiated in SD (see the runtime); whenever a
+//! peer's inbox cannot be opened the runtime falls back to UDP.
+
+use super::traits::SomeIpTransport;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Default inbox size in bytes.
+pub const DEFAULT_RING_SIZE: usize = 1024 * 1024;
+
+const MAGIC: u32 = 0x4648_524E; // "FHRN"
+const VERSION: u32 = 1;
+/// Per-record header: length (4), port (2), IP version (1), reserved (1), address (16)
+const RECORD_HEADER: usize = 24;
+
+/// Shared header at the start of every inbox mapping.
+#[repr(C)]
+struct RingHeader {
+    magic: AtomicU32,
+    version: u32,
+    /// Process that owns (reads) the inbox
+    owner_pid: u32,
+    _reserved: u32,
+    capacity: u64,
+    /// Total bytes ever written / read; offsets are these modulo `capacity`
+    head: AtomicU64,
+    tail: AtomicU64,
+    /// PID of the sender currently appending, 0 when free
+    write_lock: AtomicU32,
+    /// Bumped after every append; the futex word readers sleep on
+    seq: AtomicU32,
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<RingHeader>().next_multiple_of(64);
+
+/// Identifier of the running kernel, equal for all processes on this host.
+/// Peers advertise it in SD to detect that shared memory can be used.
+pub fn host_id() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/random/boot_id").ok().map(|s| s.trim().to_string())
+}
+
+/// Path of the inbox shadowing `addr`.
+pub fn ring_path(addr: SocketAddr) -> PathBuf {
+    let ip = addr.ip().to_string().replace(':', "_");
+    PathBuf::from(format!("/dev/shm/fusion-hawking-{}-{}", ip, addr.port()))
+}
+
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks for existence; EPERM still means the process exists
+    pid != 0 && (unsafe { libc::kill(pid as libc::pid_t, 0) } == 0
+        || Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+fn futex_wait(word: &AtomicU32, expected: u32, timeout: Duration) {
+    let ts = libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    };
+    // Not FUTEX_PRIVATE: the word lives in a mapping shared between processes
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAIT, expected, &ts as *const libc::timespec);
+    }
+}
+
+fn futex_wake(word: &AtomicU32) {
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAKE, i32::MAX);
+    }
+}
+
+/// A mapped inbox, either our own or a peer's.
+struct Ring {
+    ptr: *mut u8,
+    len: usize,
+    _file: File,
+}
+
+// The mapping is only accessed through atomics in the header and through
+// the head/tail protocol for the data area.
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    fn map(file: File, len: usize) -> Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, std::os::fd::AsRawFd::as_raw_fd(&file), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        Ok(Ring { ptr: ptr as *mut u8, len, _file: file })
+    }
+
+    /// Create (or take over a stale) inbox with `capacity` data bytes.
+    fn create(path: &PathBuf, capacity: usize) -> Result<Self> {
+        if let Ok(existing) = Self::open(path) {
+            return Err(Error::new(ErrorKind::AddrInUse, format!("inbox {} is owned by process {}", path.display(), existing.header().owner_pid)));
+        }
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let len = HEADER_SIZE + capacity;
+        file.set_len(len as u64)?;
+        let ring = Self::map(file, len)?;
+        let header = ring.ptr as *mut RingHeader;
+        unsafe {
+            (*header).version = VERSION;
+            (*header).owner_pid = std::process::id();
+            (*header).capacity = capacity as u64;
+        }
+        // Publish last: peers only use inboxes with a valid magic
+        ring.header().magic.store(MAGIC, Ordering::Release);
+        Ok(ring)
+    }
+
+    /// Open a live peer inbox.
+    fn open(path: &PathBuf) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len <= HEADER_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "inbox too small"));
+        }
+        let ring = Self::map(file, len)?;
+        let header = ring.header();
+        if header.magic.load(Ordering::Acquire) != MAGIC || header.version != VERSION
+            || header.capacity as usize != len - HEADER_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "not a fusion-hawking inbox"));
+        }
+        if !process_alive(header.owner_pid) {
+            return Err(Error::new(ErrorKind::NotConnected, "inbox owner is gone"));
+        }
+        Ok(ring)
+    }
+
+    fn header(&self) -> &RingHeader {
+        unsafe { &*(self.ptr as *const RingHeader) }
+    }
+
+    fn capacity(&self) -> usize {
+        self.len - HEADER_SIZE
+    }
+
+    /// Copy `data` into the data area at stream position `pos`, wrapping at the end.
+    fn write_at(&self, pos: u64, data: &[u8]) {
+        let cap = self.capacity();
+        let off = (pos % cap as u64) as usize;
+        let first = data.len().min(cap - off);
+        unsafe {
+            let base = self.ptr.add(HEADER_SIZE);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), base.add(off), first);
+            std::ptr::copy_nonoverlapping(data.as_ptr().add(first), base, data.len() - first);
+        }
+    }
+
+    fn read_at(&self, pos: u64, out: &mut [u8]) {
+        let cap = self.capacity();
+        let off = (pos % cap as u64) as usize;
+        let first = out.len().min(cap - off);
+        unsafe {
+            let base = self.ptr.add(HEADER_SIZE);
+            std::ptr::copy_nonoverlapping(base.add(off), out.as_mut_ptr(), first);
+            std::ptr::copy_nonoverlapping(base, out.as_mut_ptr().add(first), out.len() - first);
+        }
+    }
+
+    fn lock_writers(&self) {
+        let lock = &self.header().write_lock;
+        let me = std::process::id();
+        loop {
+            match lock.compare_exchange(0, me, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return,
+                // A sender that died mid-append must not block the inbox forever
+                Err(holder) if !process_alive(holder) => {
+                    let _ = lock.compare_exchange(holder, 0, Ordering::Relaxed, Ordering::Relaxed);
+                }
+                Err(_) => std::thread::yield_now(),
+            }
+        }
+    }
+
+    /// Append one record. Fails with `WouldBlock` when the inbox is full.
+    fn push(&self, src: SocketAddr, data: &[u8]) -> Result<()> {
+        let record_len = (RECORD_HEADER + data.len()).next_multiple_of(8);
+        if record_len > self.capacity() || data.len() > u32::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "message larger than the shared-memory inbox"));
+        }
+        let mut record = [0u8; RECORD_HEADER];
+        record[0..4].copy_from_slice(&(data.len() as u32).to_ne_bytes());
+        record[4..6].copy_from_slice(&src.port().to_ne_bytes());
+        match src.ip() {
+            IpAddr::V4(ip) => { record[6] = 4; record[8..12].copy_from_slice(&ip.octets()); }
+            IpAddr::V6(ip) => { record[6] = 6; record[8..24].copy_from_slice(&ip.octets()); }
+        }
+
+        let header = self.header();
+        self.lock_writers();
+        let head = header.head.load(Ordering::Relaxed);
+        let tail = header.tail.load(Ordering::Acquire);
+        let free = self.capacity() - (head - tail) as usize;
+        if record_len > free {
+            header.write_lock.store(0, Ordering::Release);
+            return Err(Error::new(ErrorKind::WouldBlock, "shared-memory inbox full"));
+        }
+        self.write_at(head, &record);
+        self.write_at(head + RECORD_HEADER as u64, data);
+        header.head.store(head + record_len as u64, Ordering::Release);
+        header.write_lock.store(0, Ordering::Release);
+
+        header.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&header.seq);
+        Ok(())
+    }
+
+    /// Take the oldest record, truncating the payload to `buffer`.
+    fn pop(&self, buffer: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        let header = self.header();
+        let tail = header.tail.load(Ordering::Relaxed);
+        if header.head.load(Ordering::Acquire) == tail {
+            return None;
+        }
+        let mut record = [0u8; RECORD_HEADER];
+        self.read_at(tail, &mut record);
+        let len = u32::from_ne_bytes(record[0..4].try_into().unwrap()) as usize;
+        let port = u16::from_ne_bytes(record[4..6].try_into().unwrap());
+        let ip = if record[6] == 6 {
+            IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&record[8..24]).unwrap()))
+        } else {
+            IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&record[8..12]).unwrap()))
+        };
+        let copy_len = len.min(buffer.len());
+        self.read_at(tail + RECORD_HEADER as u64, &mut buffer[..copy_len]);
+        header.tail.store(tail + (RECORD_HEADER + len).next_multiple_of(8) as u64, Ordering::Release);
+        Some((copy_len, SocketAddr::new(ip, port)))
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Shared-memory endpoint shadowing a UDP address on this host.
+pub struct ShmTransport {
+    local: SocketAddr,
+    inbox: Ring,
+    path: PathBuf,
+    /// Mapped peer inboxes by the address they shadow
+    peers: Mutex<HashMap<SocketAddr, Arc<Ring>>>,
+    nonblocking: AtomicBool,
+}
+
+impl ShmTransport {
+    /// Create the inbox for `local` with `capacity` bytes. `local` is normally the
+    /// address of a bound UDP transport, so peers can reach us under that address.
+    pub fn bind(local: SocketAddr, capacity: usize) -> Result<Self> {
+        let path = ring_path(local);
+        let inbox = Ring::create(&path, capacity)?;
+        Ok(ShmTransport { local, inbox, path, peers: Mutex::new(HashMap::new()), nonblocking: AtomicBool::new(false) })
+    }
+
+    fn peer(&self, dest: SocketAddr) -> Result<Arc<Ring>> {
+        let mut peers = self.peers.lock().unwrap();
+        if let Some(ring) = peers.get(&dest) {
+            if process_alive(ring.header().owner_pid) {
+                return Ok(ring.clone());
+            }
+            peers.remove(&dest);
+        }
+        let ring = Arc::new(Ring::open(&ring_path(dest))?);
+        peers.insert(dest, ring.clone());
+        Ok(ring)
+    }
+
+    /// Whether `dest` has a live inbox on this host.
+    pub fn can_reach(&self, dest: SocketAddr) -> bool {
+        dest != self.local && self.peer(dest).is_ok()
+    }
+}
+
+impl SomeIpTransport for ShmTransport {
+    fn send(&self, data: &[u8], destination: Option<SocketAddr>) -> Result<usize> {
+        let dest = destination.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "shared-memory transport requires a destination"))?;
+        let ring = self.peer(dest)?;
+        ring.push(self.local, data)?;
+        Ok(data.len())
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let seq = &self.inbox.header().seq;
+        loop {
+            let observed = seq.load(Ordering::Acquire);
+            if let Some(msg) = self.inbox.pop(buffer) {
+                return Ok(msg);
+            }
+            if self.nonblocking.load(Ordering::Relaxed) {
+                return Err(Error::new(ErrorKind::WouldBlock, "no message in shared-memory inbox"));
+            }
+            // Bounded wait so a wake-up lost to a dying sender cannot hang the reader
+            futex_wait(seq, observed, Duration::from_millis(100));
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for ShmTransport {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+
+    fn addr(port: u16) -> SocketAddr {
+        // Ports derived from the PID keep parallel test processes apart
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 9)), port.wrapping_add(std::process::id() as u16 % 1000 * 16))
+    }
+
+    #[test]
+    fn test_send_receive_roundtrip() {
+        let a = ShmTransport::bind(addr(40001), 64 * 1024).unwrap();
+        let b = ShmTransport::bind(addr(40002), 64 * 1024).unwrap();
+        b.set_nonblocking(true).unwrap();
+        assert!(a.can_reach(addr(40002)));
+        assert!(!a.can_reach(addr(40003)));
+
+        a.send(b"hello", Some(addr(40002))).unwrap();
+        a.send(&[7u8; 1000], Some(addr(40002))).unwrap();
+        let mut buf = [0u8; 2048];
+        assert_eq!(b.receive(&mut buf).unwrap(), (5, addr(40001)));
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(b.receive(&mut buf).unwrap().0, 1000);
+        assert_eq!(b.receive(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+
+        // Reply to the reported source
+        b.send(b"world", Some(addr(40001))).unwrap();
+        assert_eq!(a.receive(&mut buf).unwrap(), (5, addr(40002)));
+    }
+
+    #[test]
+    fn test_ring_wraps_and_reports_full() {
+        let a = ShmTransport::bind(addr(40011), 4096).unwrap();
+        let b = ShmTransport::bind(addr(40012), 4096).unwrap();
+        b.set_nonblocking(true).unwrap();
+        let mut buf = [0u8; 4096];
+        // Many more bytes than the ring holds, drained as we go
+        for i in 0..100u32 {
+            let msg = vec![i as u8; 1000 + i as usize];
+            a.send(&msg, Some(addr(40012))).unwrap();
+            let (len, _) = b.receive(&mut buf).unwrap();
+            assert_eq!(&buf[..len], &msg[..]);
+        }
+        let mut sent = 0;
+        while a.send(&[0u8; 1000], Some(addr(40012))).is_ok() {
+            sent += 1;
+        }
+        assert_eq!(sent, 4); // 1000-byte payloads fill 1024-byte records
+        assert_eq!(a.send(&[0u8; 1000], Some(addr(40012))).unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(a.send(&[0u8; 5000], Some(addr(40012))).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_blocking_receive_woken_by_sender() {
+        let a = ShmTransport::bind(addr(40021), 1 << 20).unwrap();
+        let b = Arc::new(ShmTransport::bind(addr(40022), 1 << 20).unwrap());
+        let reader = {
+            let b = b.clone();
+            thread::spawn(move || {
+                let mut buf = vec![0u8; 1 << 19];
+                let start = Instant::now();
+                let (len, _) = b.receive(&mut buf).unwrap();
+                (len, start.elapsed())
+            })
+        };
+        thread::sleep(Duration::from_millis(20));
+        a.send(&vec![1u8; 256 * 1024], Some(addr(40022))).unwrap();
+        let (len, waited) = reader.join().unwrap();
+        assert_eq!(len, 256 * 1024);
+        assert!(waited < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_inbox_removed_on_drop() {
+        let a = ShmTransport::bind(addr(40031), 4096).unwrap();
+        assert!(ring_path(addr(40031)).exists());
+        assert_eq!(ShmTransport::bind(addr(40031), 4096).err().map(|e| e.kind()), Some(ErrorKind::AddrInUse));
+        drop(a);
+        assert!(!ring_path(addr(40031)).exists());
+    }
+}
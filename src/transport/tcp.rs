@@ -1,12 +1,81 @@
 use super::traits::SomeIpTransport;
+use super::tls::TlsSession;
+use crate::codec::SomeIpHeader;
+use crate::buffer::{self, PooledBuffer};
 use std::net::{TcpStream, TcpListener, SocketAddr};
-use std::io::{Result, Read, Write, ErrorKind};
+use std::io::{IoSlice, Result, Read, Write, ErrorKind};
 use std::collections::HashMap;
+use std::cell::Cell;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 /// Minimum bytes needed to read the SOME/IP length field (service_id + method_id + length).
 const SOMEIP_HEADER_PREFIX: usize = 8;
 
+/// Size of a Magic Cookie message (header only)
+const MAGIC_COOKIE_LEN: usize = 16;
+
+/// Longest SOME/IP message accepted on a stream; a longer length field is a framing error.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Bytes read from a socket per receive call.
+const READ_CHUNK_LEN: usize = 64 * 1024;
+
+/// Default limit of unsent bytes queued per server connection before `send_to` pushes back.
+pub const DEFAULT_MAX_PENDING_WRITE: usize = 16 * 1024 * 1024;
+
+/// Write as much of `data` as the socket accepts without blocking. Returns the bytes written.
+fn write_available(mut stream: &TcpStream, data: &[u8]) -> Result<usize> {
+    let mut written = 0;
+    while written < data.len() {
+        match stream.write(&data[written..]) {
+            Ok(0) => return Err(std::io::Error::new(ErrorKind::WriteZero, "connection closed while writing")),
+            Ok(n) => written += n,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(written)
+}
+
+/// [`write_available`] through the connection's TLS session, if it has one.
+fn write_to(stream: &TcpStream, tls: Option<&mut TlsSession>, data: &[u8]) -> Result<usize> {
+    match tls {
+        Some(session) => session.write(stream, data),
+        None => write_available(stream, data),
+    }
+}
+
+/// Read from the connection, through its TLS session if it has one.
+fn read_from(mut stream: &TcpStream, tls: Option<&mut TlsSession>, buf: &mut [u8]) -> Result<usize> {
+    match tls {
+        Some(session) => session.read(stream, buf),
+        None => stream.read(buf),
+    }
+}
+
+/// Vectored [`write_available`]: write as much of `bufs` as the socket accepts without blocking.
+fn write_vectored_available(mut stream: &TcpStream, bufs: &[IoSlice<'_>]) -> Result<usize> {
+    let total: usize = bufs.iter().map(|b| b.len()).sum();
+    let mut slices = bufs.to_vec();
+    let mut remaining = &mut slices[..];
+    let mut written = 0;
+    while written < total {
+        match stream.write_vectored(remaining) {
+            Ok(0) => return Err(std::io::Error::new(ErrorKind::WriteZero, "connection closed while writing")),
+            Ok(n) => {
+                written += n;
+                IoSlice::advance_slices(&mut remaining, n);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(written)
+}
+
 /// Check if `buf` contains a complete SOME/IP message.
 /// Returns `Some(total_len)` if complete, `None` otherwise.
 fn someip_message_len(buf: &[u8]) -> Option<usize> {
@@ -18,24 +87,159 @@ fn someip_message_len(buf: &[u8]) -> Option<usize> {
     if buf.len() >= total { Some(total) } else { None }
 }
 
+/// Move the `msg_len`-byte message at the front of `buf` into `out`. A message longer than
+/// `out` is dropped whole and refused with `InvalidData` rather than cut short.
+fn take_message(buf: &mut Vec<u8>, msg_len: usize, out: &mut [u8]) -> Result<usize> {
+    let result = match out.get_mut(..msg_len) {
+        Some(out) => {
+            out.copy_from_slice(&buf[..msg_len]);
+            Ok(msg_len)
+        }
+        None => Err(std::io::Error::new(ErrorKind::InvalidData,
+            format!("{} byte message does not fit the {} byte receive buffer", msg_len, out.len()))),
+    };
+    buf.drain(..msg_len);
+    skip_to_frame(buf);
+    result
+}
+
+fn is_magic_cookie(bytes: &[u8]) -> bool {
+    bytes.len() >= MAGIC_COOKIE_LEN && bytes[..2] == [0xFF, 0xFF]
+        && SomeIpHeader::deserialize(bytes).is_ok_and(|h| h.is_magic_cookie())
+}
+
+/// Offset of the first Magic Cookie in `buf`.
+fn find_magic_cookie(buf: &[u8]) -> Option<usize> {
+    buf.windows(MAGIC_COOKIE_LEN).position(is_magic_cookie)
+}
+
+/// Whether `buf` cannot start with a SOME/IP message: its length field is out of range, or
+/// its protocol version is wrong and a Magic Cookie follows to resynchronize on.
+fn framing_error(buf: &[u8]) -> bool {
+    if buf.len() < SOMEIP_HEADER_PREFIX {
+        return false;
+    }
+    let length = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+    if !(8..=MAX_FRAME_LEN).contains(&length) {
+        return true;
+    }
+    buf.len() >= MAGIC_COOKIE_LEN && buf[12] != SomeIpHeader::SOMEIP_PROTOCOL_VERSION
+        && find_magic_cookie(&buf[MAGIC_COOKIE_LEN..]).is_some()
+}
+
+/// Drop leading Magic Cookies from a stream buffer and, after a framing error, everything
+/// up to the next Magic Cookie, so the buffer starts at a SOME/IP message.
+fn skip_to_frame(buf: &mut Vec<u8>) {
+    loop {
+        if is_magic_cookie(buf) {
+            buf.drain(..MAGIC_COOKIE_LEN);
+        } else if framing_error(buf) {
+            match find_magic_cookie(&buf[1..]) {
+                Some(offset) => { buf.drain(..offset + 1); }
+                None => {
+                    // Keep a tail that may be the start of the next cookie
+                    buf.drain(..buf.len().saturating_sub(MAGIC_COOKIE_LEN - 1));
+                    return;
+                }
+            }
+        } else {
+            return;
+        }
+    }
+}
+
+/// Periodic Magic Cookies sent on one connection.
+#[derive(Debug, Clone, Copy)]
+struct MagicCookies {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl MagicCookies {
+    fn new(interval: Duration) -> Self {
+        MagicCookies { interval, last: None }
+    }
+
+    /// Whether a cookie goes before the next message; the first message always gets one.
+    fn due(&mut self, now: Instant) -> bool {
+        let due = self.last.is_none_or(|last| now.duration_since(last) >= self.interval);
+        if due {
+            self.last = Some(now);
+        }
+        due
+    }
+}
+
+/// `data` preceded by a Magic Cookie of the given variant.
+fn with_magic_cookie(server: bool, data: &[u8]) -> PooledBuffer<'static> {
+    let mut framed = buffer::shared().get();
+    framed.extend_from_slice(&SomeIpHeader::magic_cookie(server).serialize());
+    framed.extend_from_slice(data);
+    framed
+}
+
 /// TCP client transport for SOME/IP
 pub struct TcpTransport {
     stream: TcpStream,
     /// Internal buffer for accumulating partial SOME/IP messages.
     recv_buf: Mutex<Vec<u8>>,
+    /// Client Magic Cookies sent before messages, if enabled
+    magic_cookies: Mutex<Option<MagicCookies>>,
+    /// Bytes of sent messages a non-blocking socket has not accepted yet
+    pending_write: Mutex<Vec<u8>>,
+    /// TLS session, once [`start_tls`](Self::start_tls) has run
+    tls: Mutex<Option<TlsSession>>,
 }
 
 impl TcpTransport {
     pub fn new(stream: TcpStream) -> Self {
-        TcpTransport { stream, recv_buf: Mutex::new(Vec::new()) }
+        TcpTransport {
+            stream,
+            recv_buf: Mutex::new(Vec::new()),
+            magic_cookies: Mutex::new(None),
+            pending_write: Mutex::new(Vec::new()),
+            tls: Mutex::new(None),
+        }
     }
     
     /// Connect to a remote SOME/IP server
     pub fn connect(addr: SocketAddr) -> Result<Self> {
-        let stream = TcpStream::connect(addr)?;
-        Ok(TcpTransport { stream, recv_buf: Mutex::new(Vec::new()) })
+        Ok(Self::new(TcpStream::connect(addr)?))
     }
     
+    /// Connect to a remote SOME/IP server from a fixed local address
+    pub fn connect_from(local: SocketAddr, addr: SocketAddr) -> Result<Self> {
+        use socket2::{Socket, Domain, Type, Protocol};
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        // Allow reconnecting from the same port while the previous connection is in TIME_WAIT
+        socket.set_reuse_address(true)?;
+        socket.bind(&local.into())?;
+        socket.connect(&addr.into())?;
+        Ok(Self::new(socket.into()))
+    }
+
+    /// Send a client Magic Cookie before the first message and then before the first
+    /// message after each `interval`; `None` sends none.
+    pub fn set_magic_cookie_interval(&self, interval: Option<Duration>) {
+        *self.magic_cookies.lock().unwrap() = interval.map(MagicCookies::new);
+    }
+
+    /// Run the TLS client handshake with a server whose certificate is valid for
+    /// `server_name`; everything sent and received afterwards is encrypted. The stream
+    /// must still be blocking.
+    #[cfg(feature = "tls")]
+    pub fn start_tls(&self, config: std::sync::Arc<rustls::ClientConfig>, server_name: rustls::pki_types::ServerName<'static>) -> Result<()> {
+        let mut session = TlsSession::client(config, server_name)?;
+        session.handshake(&self.stream)?;
+        *self.tls.lock().unwrap() = Some(session);
+        Ok(())
+    }
+
+    /// Whether the connection runs over TLS.
+    pub fn is_tls(&self) -> bool {
+        self.tls.lock().unwrap().is_some()
+    }
+
     /// Set non-blocking mode
     pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
         self.stream.set_nonblocking(nonblocking)
@@ -45,30 +249,86 @@ impl TcpTransport {
     pub fn peer_addr(&self) -> Result<SocketAddr> {
         self.stream.peer_addr()
     }
+
+    /// Write queued bytes the socket accepts now. Returns the bytes still queued.
+    pub fn flush(&self) -> Result<usize> {
+        let mut pending = self.pending_write.lock().unwrap();
+        let mut tls = self.tls.lock().unwrap();
+        let n = write_to(&self.stream, tls.as_mut(), &pending)?;
+        pending.drain(..n);
+        if let Some(session) = tls.as_mut() {
+            session.flush(&self.stream)?;
+        }
+        Ok(pending.len())
+    }
+
+    /// Write `bufs` as one message. Whatever the socket does not accept right away is
+    /// queued behind earlier messages and written by later sends, receives or `flush`,
+    /// so a message is never cut short on the stream.
+    fn write_message(&self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let mut pending = self.pending_write.lock().unwrap();
+        let mut tls = self.tls.lock().unwrap();
+        let n = write_to(&self.stream, tls.as_mut(), &pending)?;
+        pending.drain(..n);
+        let written = match tls.as_mut() {
+            _ if !pending.is_empty() => 0,
+            Some(session) => {
+                let mut written = 0;
+                for buf in bufs {
+                    let n = session.write(&self.stream, buf)?;
+                    written += n;
+                    if n < buf.len() { break; }
+                }
+                written
+            }
+            None => write_vectored_available(&self.stream, bufs)?,
+        };
+        let mut skip = written;
+        for buf in bufs {
+            let taken = skip.min(buf.len());
+            pending.extend_from_slice(&buf[taken..]);
+            skip -= taken;
+        }
+        Ok(bufs.iter().map(|b| b.len()).sum())
+    }
 }
 
 impl SomeIpTransport for TcpTransport {
     fn send(&self, data: &[u8], _destination: Option<SocketAddr>) -> Result<usize> {
-        (&self.stream).write(data)
+        if self.magic_cookies.lock().unwrap().as_mut().is_some_and(|c| c.due(Instant::now())) {
+            return self.write_message(&[IoSlice::new(&with_magic_cookie(false, data))]).map(|n| n - MAGIC_COOKIE_LEN);
+        }
+        self.write_message(&[IoSlice::new(data)])
+    }
+
+    fn send_vectored(&self, bufs: &[IoSlice<'_>], destination: Option<SocketAddr>) -> Result<usize> {
+        if self.magic_cookies.lock().unwrap().as_ref().is_some() {
+            // Cookies are framed by `send`
+            let mut data = buffer::shared().get();
+            for buf in bufs {
+                data.extend_from_slice(buf);
+            }
+            return self.send(&data, destination);
+        }
+        self.write_message(bufs)
     }
 
     fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)> {
         let peer = self.stream.peer_addr()?;
+        self.flush()?;
         // Read whatever is available into the internal buffer
-        let mut tmp = [0u8; 4096];
-        match (&self.stream).read(&mut tmp) {
+        let mut tmp = [0u8; READ_CHUNK_LEN];
+        let mut buf_ref = self.recv_buf.lock().unwrap();
+        match read_from(&self.stream, self.tls.lock().unwrap().as_mut(), &mut tmp) {
             Ok(0) => return Err(std::io::Error::new(ErrorKind::ConnectionReset, "Connection closed")),
-            Ok(n) => { self.recv_buf.lock().unwrap().extend_from_slice(&tmp[..n]); }
+            Ok(n) => { buf_ref.extend_from_slice(&tmp[..n]); }
             Err(e) if e.kind() == ErrorKind::WouldBlock => {}
             Err(e) => return Err(e),
         }
         // Check if we have a complete SOME/IP message
-        let mut buf_ref = self.recv_buf.lock().unwrap();
+        skip_to_frame(&mut buf_ref);
         if let Some(msg_len) = someip_message_len(&buf_ref) {
-            let copy_len = msg_len.min(buffer.len());
-            buffer[..copy_len].copy_from_slice(&buf_ref[..copy_len]);
-            buf_ref.drain(..msg_len);
-            Ok((copy_len, peer))
+            take_message(&mut buf_ref, msg_len, buffer).map(|len| (len, peer))
         } else {
             Err(std::io::Error::new(ErrorKind::WouldBlock, "Incomplete SOME/IP message"))
         }
@@ -81,6 +341,32 @@ impl SomeIpTransport for TcpTransport {
     fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
         self.stream.set_nonblocking(nonblocking)
     }
+
+    fn is_stream(&self) -> bool {
+        true
+    }
+
+    fn close(&self) -> Result<()> {
+        if let Some(session) = self.tls.lock().unwrap().as_mut() {
+            let _ = session.close(&self.stream);
+        }
+        match self.stream.shutdown(std::net::Shutdown::Both) {
+            Err(e) if e.kind() != ErrorKind::NotConnected => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// `None` while writes are queued or a complete message is still buffered, since the
+    /// socket will not signal either.
+    #[cfg(target_os = "linux")]
+    fn poll_fds(&self) -> Option<Vec<std::os::fd::RawFd>> {
+        if !self.pending_write.lock().unwrap().is_empty()
+            || self.tls.lock().unwrap().as_ref().is_some_and(TlsSession::wants_write)
+            || someip_message_len(&self.recv_buf.lock().unwrap()).is_some() {
+            return None;
+        }
+        Some(vec![std::os::fd::AsRawFd::as_raw_fd(&self.stream)])
+    }
 }
 
 /// A wrapper for TcpServer that implements SomeIpTransport trait
@@ -95,6 +381,11 @@ impl TcpServerTransport {
             server: Mutex::new(server),
         }
     }
+
+    /// Bytes queued for `addr` that the socket has not accepted yet.
+    pub fn pending_write_bytes(&self, addr: &SocketAddr) -> usize {
+        self.server.lock().unwrap().pending_write_bytes(addr)
+    }
 }
 
 
@@ -112,13 +403,14 @@ impl SomeIpTransport for TcpServerTransport {
     fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)> {
         let mut server = self.server.lock().unwrap();
         
-        // 1. Accept any waiting connections
+        // 1. Accept any waiting connections and push out queued writes
         let _ = server.poll_accept();
+        server.flush();
         
         // 2. Read available data into per-connection buffers
         let clients = server.connected_clients();
         for addr in &clients {
-            let mut tmp = [0u8; 4096];
+            let mut tmp = [0u8; READ_CHUNK_LEN];
             match server.raw_receive_from(&mut tmp, addr) {
                 Ok(0) => { server.disconnect(addr); continue; }
                 Ok(n) => { server.append_to_buffer(addr, &tmp[..n]); }
@@ -130,9 +422,7 @@ impl SomeIpTransport for TcpServerTransport {
         // 3. Check all buffers for a complete SOME/IP message
         for addr in &clients {
             if let Some(msg_len) = server.check_buffer(addr) {
-                let copy_len = msg_len.min(buffer.len());
-                server.drain_buffer(addr, copy_len, buffer);
-                return Ok((copy_len, *addr));
+                return server.drain_buffer(addr, msg_len, buffer).map(|len| (len, *addr));
             }
         }
         
@@ -148,6 +438,35 @@ impl SomeIpTransport for TcpServerTransport {
         let server = self.server.lock().unwrap();
         server.set_nonblocking(nonblocking)
     }
+
+    fn is_stream(&self) -> bool {
+        true
+    }
+
+    /// Drop every accepted connection; the listener stays open until dropped.
+    fn close(&self) -> Result<()> {
+        let mut server = self.server.lock().unwrap();
+        for addr in server.connected_clients() {
+            server.disconnect(&addr);
+        }
+        Ok(())
+    }
+
+    /// The listener and every connection; `None` while writes are queued or a complete
+    /// message is still buffered, since neither is signalled as readable.
+    #[cfg(target_os = "linux")]
+    fn poll_fds(&self) -> Option<Vec<std::os::fd::RawFd>> {
+        use std::os::fd::AsRawFd;
+        let server = self.server.lock().unwrap();
+        if server.pending_writes.values().any(|p| !p.is_empty())
+            || server.tls_sessions.values().any(TlsSession::wants_write)
+            || server.tcp_buffers.values().any(|b| someip_message_len(b).is_some()) {
+            return None;
+        }
+        let mut fds = vec![server.listener.as_raw_fd()];
+        fds.extend(server.connections.values().map(|s| s.as_raw_fd()));
+        Some(fds)
+    }
 }
 
 /// TCP server for accepting SOME/IP connections
@@ -156,6 +475,18 @@ pub struct TcpServer {
     connections: HashMap<SocketAddr, TcpStream>,
     /// Per-connection receive buffers for SOME/IP message reassembly.
     tcp_buffers: HashMap<SocketAddr, Vec<u8>>,
+    /// Per-connection bytes accepted by `send_to` but not yet written to the socket.
+    pending_writes: HashMap<SocketAddr, Vec<u8>>,
+    max_pending_write: usize,
+    /// Mode applied to the listener and to accepted connections
+    nonblocking: Cell<bool>,
+    /// Interval of the server Magic Cookies sent on each connection, if enabled
+    magic_cookie_interval: Option<Duration>,
+    magic_cookies: HashMap<SocketAddr, MagicCookies>,
+    /// Configuration of the TLS session started on each accepted connection, if enabled
+    #[cfg(feature = "tls")]
+    tls: Option<std::sync::Arc<rustls::ServerConfig>>,
+    tls_sessions: HashMap<SocketAddr, TlsSession>,
 }
 
 impl TcpServer {
@@ -166,12 +497,43 @@ impl TcpServer {
             listener,
             connections: HashMap::new(),
             tcp_buffers: HashMap::new(),
+            pending_writes: HashMap::new(),
+            max_pending_write: DEFAULT_MAX_PENDING_WRITE,
+            nonblocking: Cell::new(false),
+            magic_cookie_interval: None,
+            magic_cookies: HashMap::new(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            tls_sessions: HashMap::new(),
         })
     }
+
+    /// Send a server Magic Cookie before the first message to each connection and then
+    /// before the first message after each `interval`; `None` sends none.
+    pub fn set_magic_cookie_interval(&mut self, interval: Option<Duration>) {
+        self.magic_cookie_interval = interval;
+        self.magic_cookies.clear();
+    }
     
-    /// Set non-blocking mode for the listener
+    /// Run TLS with `config` on connections accepted from now on; `None` accepts plain TCP.
+    #[cfg(feature = "tls")]
+    pub fn set_tls(&mut self, config: Option<std::sync::Arc<rustls::ServerConfig>>) {
+        self.tls = config;
+    }
+
+    /// Set non-blocking mode for the listener and its connections
     pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
-        self.listener.set_nonblocking(nonblocking)
+        self.listener.set_nonblocking(nonblocking)?;
+        for stream in self.connections.values() {
+            stream.set_nonblocking(nonblocking)?;
+        }
+        self.nonblocking.set(nonblocking);
+        Ok(())
+    }
+
+    /// Limit of unsent bytes queued per connection (default [`DEFAULT_MAX_PENDING_WRITE`]).
+    pub fn set_max_pending_write(&mut self, bytes: usize) {
+        self.max_pending_write = bytes;
     }
     
     /// Get the local address the server is bound to
@@ -184,6 +546,11 @@ impl TcpServer {
     pub fn accept(&mut self) -> Result<Option<SocketAddr>> {
         match self.listener.accept() {
             Ok((stream, addr)) => {
+                stream.set_nonblocking(self.nonblocking.get())?;
+                #[cfg(feature = "tls")]
+                if let Some(config) = &self.tls {
+                    self.tls_sessions.insert(addr, TlsSession::server(config.clone())?);
+                }
                 self.connections.insert(addr, stream);
                 self.tcp_buffers.entry(addr).or_insert_with(Vec::new);
                 Ok(Some(addr))
@@ -206,30 +573,89 @@ impl TcpServer {
         new_connections
     }
     
-    /// Send data to a specific connected client
+    /// Send a message to a specific connected client.
+    ///
+    /// The whole message is always taken: what the socket does not accept right away is
+    /// queued and written by later calls to `send_to` or [`flush`](Self::flush), in order.
+    /// If the connection already has more than the pending-write limit queued, the message
+    /// is refused with `WouldBlock` so the caller can back off; nothing of it is sent.
     pub fn send_to(&mut self, data: &[u8], addr: &SocketAddr) -> Result<usize> {
-        if let Some(stream) = self.connections.get_mut(addr) {
-            stream.write(data)
-        } else {
-            Err(std::io::Error::new(ErrorKind::NotConnected, "Client not connected"))
+        let Some(stream) = self.connections.get_mut(addr) else {
+            return Err(std::io::Error::new(ErrorKind::NotConnected, "Client not connected"));
+        };
+        let pending = self.pending_writes.entry(*addr).or_default();
+        let mut tls = self.tls_sessions.get_mut(addr);
+        let message_len = data.len();
+        let framed;
+        let data = match self.magic_cookie_interval {
+            Some(interval) if self.magic_cookies.entry(*addr).or_insert_with(|| MagicCookies::new(interval)).due(Instant::now()) => {
+                framed = with_magic_cookie(true, data);
+                &framed[..]
+            }
+            _ => data,
+        };
+        let result = (|| {
+            if !pending.is_empty() {
+                let n = write_to(stream, tls.as_deref_mut(), pending)?;
+                pending.drain(..n);
+            }
+            // A message larger than the limit is still accepted onto an empty queue
+            if !pending.is_empty() && pending.len() + data.len() > self.max_pending_write {
+                return Err(std::io::Error::new(ErrorKind::WouldBlock, "Send queue full"));
+            }
+            let written = if pending.is_empty() { write_to(stream, tls.as_deref_mut(), data)? } else { 0 };
+            pending.extend_from_slice(&data[written..]);
+            Ok(message_len)
+        })();
+        if result.as_ref().is_err_and(|e| e.kind() != ErrorKind::WouldBlock) {
+            self.disconnect(addr);
         }
+        result
+    }
+
+    /// Write queued data to every connection whose socket accepts it.
+    /// Connections that fail are dropped. Returns the bytes still queued.
+    pub fn flush(&mut self) -> usize {
+        let mut failed = Vec::new();
+        for (addr, pending) in self.pending_writes.iter_mut().filter(|(_, p)| !p.is_empty()) {
+            let Some(stream) = self.connections.get_mut(addr) else { continue };
+            match write_to(stream, self.tls_sessions.get_mut(addr), pending) {
+                Ok(n) => { pending.drain(..n); }
+                Err(_) => failed.push(*addr),
+            }
+        }
+        // Records of TLS sessions, including handshake messages
+        for (addr, session) in self.tls_sessions.iter_mut().filter(|(_, s)| s.wants_write()) {
+            let Some(stream) = self.connections.get(addr) else { continue };
+            if session.flush(stream).is_err() {
+                failed.push(*addr);
+            }
+        }
+        for addr in failed {
+            self.disconnect(&addr);
+        }
+        self.pending_writes.values().map(Vec::len).sum()
+    }
+
+    /// Bytes queued for `addr` that the socket has not accepted yet.
+    pub fn pending_write_bytes(&self, addr: &SocketAddr) -> usize {
+        self.pending_writes.get(addr).map_or(0, Vec::len)
     }
     
     /// Receive data from a specific connected client.
     /// Returns a complete SOME/IP message if one is buffered, otherwise reads
     /// more data and returns WouldBlock until a full message is available.
+    /// A message longer than `buffer` is dropped and refused with `InvalidData`.
     pub fn receive_from(&mut self, buffer: &mut [u8], addr: &SocketAddr) -> Result<usize> {
         // Read whatever is available
         if let Some(stream) = self.connections.get_mut(addr) {
-            let mut tmp = [0u8; 4096];
-            match stream.read(&mut tmp) {
+            let mut tmp = [0u8; READ_CHUNK_LEN];
+            match read_from(stream, self.tls_sessions.get_mut(addr), &mut tmp) {
                 Ok(0) => {
                     self.tcp_buffers.remove(addr);
                     return Err(std::io::Error::new(ErrorKind::ConnectionReset, "EOF"));
                 }
-                Ok(n) => {
-                    self.tcp_buffers.entry(*addr).or_insert_with(Vec::new).extend_from_slice(&tmp[..n]);
-                }
+                Ok(n) => self.append_to_buffer(addr, &tmp[..n]),
                 Err(e) if e.kind() == ErrorKind::WouldBlock => {}
                 Err(e) => return Err(e),
             }
@@ -240,10 +666,7 @@ impl TcpServer {
         // Check if buffer has a complete SOME/IP message
         if let Some(conn_buf) = self.tcp_buffers.get_mut(addr) {
             if let Some(msg_len) = someip_message_len(conn_buf) {
-                let copy_len = msg_len.min(buffer.len());
-                buffer[..copy_len].copy_from_slice(&conn_buf[..copy_len]);
-                conn_buf.drain(..msg_len);
-                return Ok(copy_len);
+                return take_message(conn_buf, msg_len, buffer);
             }
         }
         Err(std::io::Error::new(ErrorKind::WouldBlock, "Incomplete SOME/IP message"))
@@ -252,15 +675,17 @@ impl TcpServer {
     /// Raw read from a connection (used by TcpServerTransport buffering layer).
     pub fn raw_receive_from(&mut self, buffer: &mut [u8], addr: &SocketAddr) -> Result<usize> {
         if let Some(stream) = self.connections.get_mut(addr) {
-            stream.read(buffer)
+            read_from(stream, self.tls_sessions.get_mut(addr), buffer)
         } else {
             Err(std::io::Error::new(ErrorKind::NotConnected, "Client not connected"))
         }
     }
 
-    /// Append data to a connection's buffer.
+    /// Append data to a connection's buffer, dropping Magic Cookies and unframed bytes.
     pub fn append_to_buffer(&mut self, addr: &SocketAddr, data: &[u8]) {
-        self.tcp_buffers.entry(*addr).or_insert_with(Vec::new).extend_from_slice(data);
+        let buf = self.tcp_buffers.entry(*addr).or_default();
+        buf.extend_from_slice(data);
+        skip_to_frame(buf);
     }
 
     /// Check if a connection buffer has a complete SOME/IP message.
@@ -268,19 +693,22 @@ impl TcpServer {
         self.tcp_buffers.get(addr).and_then(|buf| someip_message_len(buf))
     }
 
-    /// Drain bytes from a connection buffer into the output buffer.
-    pub fn drain_buffer(&mut self, addr: &SocketAddr, len: usize, out: &mut [u8]) {
-        if let Some(buf) = self.tcp_buffers.get_mut(addr) {
-            let copy_len = len.min(out.len()).min(buf.len());
-            out[..copy_len].copy_from_slice(&buf[..copy_len]);
-            buf.drain(..len);
+    /// Move the `len`-byte message that [`check_buffer`](Self::check_buffer) found on a
+    /// connection into `out`. A message longer than `out` is dropped and refused with `InvalidData`.
+    pub fn drain_buffer(&mut self, addr: &SocketAddr, len: usize, out: &mut [u8]) -> Result<usize> {
+        match self.tcp_buffers.get_mut(addr) {
+            Some(buf) => take_message(buf, len, out),
+            None => Err(std::io::Error::new(ErrorKind::NotConnected, "Client not connected")),
         }
     }
     
-    /// Remove a connection and its buffer
+    /// Remove a connection and its buffers
     pub fn disconnect(&mut self, addr: &SocketAddr) {
         self.connections.remove(addr);
         self.tcp_buffers.remove(addr);
+        self.pending_writes.remove(addr);
+        self.magic_cookies.remove(addr);
+        self.tls_sessions.remove(addr);
     }
     
     /// Get all connected client addresses
@@ -300,6 +728,13 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    /// A request with a valid header, unlike `wrap_someip`
+    fn someip_message(payload: &[u8]) -> Vec<u8> {
+        let mut msg = SomeIpHeader::new(0x1234, 0x0001, 0x0001, 0x0001, 0x00, payload.len() as u32).serialize().to_vec();
+        msg.extend_from_slice(payload);
+        msg
+    }
+
     fn wrap_someip(payload: &[u8]) -> Vec<u8> {
         let mut msg = vec![0u8; 16];
         // SOME/IP Length = 8 (rest of header) + payload length
@@ -405,6 +840,18 @@ mod tests {
         assert!(local.port() > 0);
     }
     
+    #[test]
+    fn test_tcp_transport_connect_from() {
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        // Reserve a free port, then connect from it
+        let local = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let client = TcpTransport::connect_from(local, server_addr).unwrap();
+        assert_eq!(client.local_addr().unwrap(), local);
+        assert_eq!(server.accept().unwrap(), Some(local));
+    }
+
     #[test]
     fn test_tcp_transport_peer_addr() {
         let server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
@@ -597,4 +1044,220 @@ mod tests {
         }
         assert_eq!(server.connection_count(), 0);
     }
+
+    /// Accept one raw client on a non-blocking server.
+    fn accept_one(server: &mut TcpServer) -> (TcpStream, SocketAddr) {
+        server.set_nonblocking(true).unwrap();
+        let client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Ok(Some(addr)) = server.accept() {
+                return (client, addr);
+            }
+            assert!(std::time::Instant::now() < deadline, "Timeout waiting for client connection");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_large_responses_not_truncated() {
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let (mut client, addr) = accept_one(&mut server);
+
+        // Three 4 MB framed messages while the client is not reading: the socket
+        // buffers cannot hold them, so the rest must be queued rather than dropped.
+        let messages: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| wrap_someip(&vec![i + 1; 4 * 1024 * 1024]))
+            .collect();
+        for msg in &messages {
+            assert_eq!(server.send_to(msg, &addr).unwrap(), msg.len());
+        }
+        assert!(server.pending_write_bytes(&addr) > 0);
+
+        let expected: usize = messages.iter().map(Vec::len).sum();
+        let reader = thread::spawn(move || {
+            let mut received = vec![0u8; expected];
+            client.read_exact(&mut received).unwrap();
+            received
+        });
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(20);
+        while server.flush() > 0 {
+            assert!(std::time::Instant::now() < deadline, "Timeout flushing queued writes");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let received = reader.join().unwrap();
+        let mut offset = 0;
+        for msg in &messages {
+            let length = u32::from_be_bytes(received[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            assert_eq!(length + 8, msg.len());
+            assert!(received[offset..offset + msg.len()] == msg[..]);
+            offset += msg.len();
+        }
+    }
+
+    #[test]
+    fn test_client_sends_whole_messages_when_nonblocking() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpTransport::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        // Header and payload go out as separate slices; the peer is not reading yet,
+        // so most of each message has to wait in the queue
+        let messages: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| wrap_someip(&vec![i + 1; 4 * 1024 * 1024]))
+            .collect();
+        for msg in &messages {
+            let (header, payload) = msg.split_at(16);
+            assert_eq!(client.send_vectored(&[IoSlice::new(header), IoSlice::new(payload)], None).unwrap(), msg.len());
+        }
+        assert!(client.flush().unwrap() > 0);
+
+        let expected: usize = messages.iter().map(Vec::len).sum();
+        let reader = thread::spawn(move || {
+            let mut received = vec![0u8; expected];
+            peer.read_exact(&mut received).unwrap();
+            received
+        });
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(20);
+        while client.flush().unwrap() > 0 {
+            assert!(std::time::Instant::now() < deadline, "Timeout flushing queued writes");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let received = reader.join().unwrap();
+        let sent: Vec<u8> = messages.concat();
+        assert!(received == sent);
+    }
+
+    #[test]
+    fn test_send_queue_back_pressure() {
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        server.set_max_pending_write(1024 * 1024);
+        let (_client, addr) = accept_one(&mut server);
+
+        // Fill the socket buffers and the queue; the client never reads
+        let msg = wrap_someip(&[0xAB; 512 * 1024]);
+        let mut refused = None;
+        for _ in 0..64 {
+            if let Err(e) = server.send_to(&msg, &addr) {
+                refused = Some(e.kind());
+                break;
+            }
+        }
+        assert_eq!(refused, Some(ErrorKind::WouldBlock));
+        let pending = server.pending_write_bytes(&addr);
+        assert!(pending > 0 && pending <= 1024 * 1024 + msg.len());
+        // Back-pressure leaves the connection up and the queue untouched
+        assert_eq!(server.connection_count(), 1);
+        assert_eq!(server.send_to(&msg, &addr).unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(server.pending_write_bytes(&addr), pending);
+
+        server.disconnect(&addr);
+        assert_eq!(server.pending_write_bytes(&addr), 0);
+    }
+
+    #[test]
+    fn test_magic_cookies_skipped_and_resynchronized() {
+        let cookie = SomeIpHeader::magic_cookie(false).serialize();
+        let addr: SocketAddr = "10.0.0.2:40000".parse().unwrap();
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let first = someip_message(&[1, 2, 3]);
+        let second = someip_message(&[4]);
+
+        // Cookies between messages are dropped
+        let mut stream = cookie.to_vec();
+        stream.extend_from_slice(&first);
+        stream.extend_from_slice(&cookie);
+        // Lost framing: a length field far beyond any message, then a cookie to resync on
+        stream.extend_from_slice(&[0xAA, 0xBB, 0xCC, 0xDD, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+        stream.extend_from_slice(&cookie);
+        stream.extend_from_slice(&second);
+        let mut out = [0u8; 64];
+        for chunk in stream.chunks(5) {
+            server.append_to_buffer(&addr, chunk);
+        }
+        let len = server.check_buffer(&addr).unwrap();
+        server.drain_buffer(&addr, len, &mut out).unwrap();
+        assert_eq!(&out[..len], &first[..]);
+        let len = server.check_buffer(&addr).unwrap();
+        server.drain_buffer(&addr, len, &mut out).unwrap();
+        assert_eq!(&out[..len], &second[..]);
+        assert!(server.check_buffer(&addr).is_none());
+
+        // Without a cookie to resync on, unframed bytes are discarded but for a possible cookie prefix
+        server.append_to_buffer(&addr, &[0, 0, 0, 0, 0, 0, 0, 1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9]);
+        server.append_to_buffer(&addr, &cookie);
+        server.append_to_buffer(&addr, &second);
+        let len = server.check_buffer(&addr).unwrap();
+        server.drain_buffer(&addr, len, &mut out).unwrap();
+        assert_eq!(&out[..len], &second[..]);
+    }
+
+    #[test]
+    fn test_message_longer_than_buffer_refused_whole() {
+        let addr: SocketAddr = "10.0.0.2:40000".parse().unwrap();
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let large = someip_message(&vec![0x5A; 100_000]);
+        let small = someip_message(&[7]);
+        for chunk in large.chunks(READ_CHUNK_LEN) {
+            server.append_to_buffer(&addr, chunk);
+        }
+        server.append_to_buffer(&addr, &small);
+        server.append_to_buffer(&addr, &large);
+
+        // Too long for the caller's buffer: dropped, not cut short, and framing holds
+        let mut out = [0u8; 4096];
+        let len = server.check_buffer(&addr).unwrap();
+        assert_eq!(server.drain_buffer(&addr, len, &mut out).unwrap_err().kind(), ErrorKind::InvalidData);
+        let len = server.check_buffer(&addr).unwrap();
+        assert_eq!(server.drain_buffer(&addr, len, &mut out).unwrap(), len);
+        assert_eq!(&out[..len], &small[..]);
+
+        let mut out = vec![0u8; 8 + MAX_FRAME_LEN];
+        let len = server.check_buffer(&addr).unwrap();
+        assert_eq!(server.drain_buffer(&addr, len, &mut out).unwrap(), len);
+        assert_eq!(&out[..len], &large[..]);
+        assert!(server.check_buffer(&addr).is_none());
+    }
+
+    #[test]
+    fn test_periodic_magic_cookies() {
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        server.set_magic_cookie_interval(Some(Duration::from_millis(100)));
+        server.set_nonblocking(true).unwrap();
+        let client = TcpTransport::connect(server.local_addr().unwrap()).unwrap();
+        client.set_magic_cookie_interval(Some(Duration::from_millis(100)));
+        let addr = loop {
+            if let Some(addr) = server.poll_accept().pop() { break addr; }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let msg = someip_message(&[7]);
+
+        // Client cookie before the first message only, within the interval
+        assert_eq!(client.send(&msg, None).unwrap(), msg.len());
+        assert_eq!(client.send(&msg, None).unwrap(), msg.len());
+        thread::sleep(Duration::from_millis(50));
+        let mut raw = [0u8; 128];
+        let n = server.raw_receive_from(&mut raw, &addr).unwrap();
+        assert_eq!(n, 16 + 2 * msg.len());
+        assert_eq!(raw[..16], SomeIpHeader::magic_cookie(false).serialize());
+
+        // Server cookies are dropped by the client's framing
+        assert_eq!(server.send_to(&msg, &addr).unwrap(), msg.len());
+        thread::sleep(Duration::from_millis(120));
+        server.send_to(&msg, &addr).unwrap();
+        client.set_nonblocking(true).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let mut buf = [0u8; 64];
+        for _ in 0..2 {
+            let (len, _) = client.receive(&mut buf).unwrap();
+            assert_eq!(&buf[..len], &msg[..]);
+        }
+        assert!(client.receive(&mut buf).is_err());
+        assert_eq!(client.recv_buf.lock().unwrap().len(), 0);
+    }
 }
@@ -0,0 +1,341 @@
+//! # TLS
+//!
+//! TLS over the TCP transports, with the `tls` feature. A [`TlsSession`] sits between a
+//! connection's SOME/IP framing and its socket: [`TcpServer`](super::TcpServer) starts
+//! one for every accepted connection once [`set_tls`](super::TcpServer::set_tls) is
+//! configured, and [`TcpTransport::start_tls`](super::TcpTransport::start_tls) runs the
+//! client handshake on a connected stream. Queued writes, Magic Cookies and framing
+//! work on the plaintext as on a plain TCP connection.
+//!
+//! Certificates, keys and CA bundles are read from PEM files by [`server_config`] and
+//! [`client_config`]. Without the feature, [`TlsSession`] has no values and the
+//! transports never hold one.
+
+#[cfg(feature = "tls")]
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "tls")]
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+#[cfg(feature = "tls")]
+use rustls::{ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection};
+#[cfg(feature = "tls")]
+use std::io::{ErrorKind, Read, Write};
+use std::io::Result;
+use std::net::TcpStream;
+#[cfg(feature = "tls")]
+use std::path::Path;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+
+/// Longest wait for the server's handshake messages in [`TlsSession::handshake`].
+#[cfg(feature = "tls")]
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// TLS state of one connection: plaintext is written to and read from the session,
+/// records go over the socket.
+#[cfg(feature = "tls")]
+pub struct TlsSession(Connection);
+
+#[cfg(not(feature = "tls"))]
+pub enum TlsSession {}
+
+#[cfg(feature = "tls")]
+fn tls_error(e: rustls::Error) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, e)
+}
+
+#[cfg(feature = "tls")]
+impl TlsSession {
+    /// Client side of a connection to a server with a certificate for `server_name`.
+    pub fn client(config: Arc<ClientConfig>, server_name: ServerName<'static>) -> Result<Self> {
+        Ok(TlsSession(ClientConnection::new(config, server_name).map_err(tls_error)?.into()))
+    }
+
+    /// Server side of an accepted connection.
+    pub fn server(config: Arc<ServerConfig>) -> Result<Self> {
+        Ok(TlsSession(ServerConnection::new(config).map_err(tls_error)?.into()))
+    }
+
+    /// Run the handshake to completion on a blocking `stream`, failing with `TimedOut` or
+    /// `WouldBlock` if the peer stalls for [`HANDSHAKE_TIMEOUT`].
+    pub fn handshake(&mut self, mut stream: &TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut result = Ok(());
+        while result.is_ok() && self.0.is_handshaking() {
+            result = self.0.complete_io(&mut stream).map(|_| ());
+        }
+        stream.set_read_timeout(None)?;
+        result
+    }
+
+    /// Read decrypted bytes into `buf`, reading records from `stream` as needed and
+    /// answering handshake messages. `Ok(0)` once the peer has closed the session.
+    pub fn read(&mut self, mut stream: &TcpStream, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            match self.0.reader().read(buf) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+            if self.0.read_tls(&mut stream)? == 0 {
+                return Ok(0);
+            }
+            let processed = self.0.process_new_packets().map_err(tls_error);
+            // Handshake replies, or the alert for a failure
+            self.flush(stream)?;
+            processed?;
+        }
+    }
+
+    /// Encrypt as much of `data` as the session buffers and write the records the
+    /// socket accepts without blocking. Returns the bytes of `data` taken; the rest is
+    /// left to the caller's queue, as for a plain socket.
+    pub fn write(&mut self, stream: &TcpStream, data: &[u8]) -> Result<usize> {
+        self.flush(stream)?;
+        let mut written = 0;
+        while written < data.len() {
+            match self.0.writer().write(&data[written..])? {
+                0 => break,
+                n => written += n,
+            }
+            self.flush(stream)?;
+        }
+        Ok(written)
+    }
+
+    /// Write buffered records the socket accepts without blocking.
+    pub fn flush(&mut self, mut stream: &TcpStream) -> Result<()> {
+        while self.0.wants_write() {
+            match self.0.write_tls(&mut stream) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether records are buffered that the socket has not accepted yet.
+    pub fn wants_write(&self) -> bool {
+        self.0.wants_write()
+    }
+
+    /// Send close_notify, as far as the socket accepts it.
+    pub fn close(&mut self, stream: &TcpStream) -> Result<()> {
+        self.0.send_close_notify();
+        self.flush(stream)
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+impl TlsSession {
+    pub fn read(&mut self, _stream: &TcpStream, _buf: &mut [u8]) -> Result<usize> {
+        match *self {}
+    }
+
+    pub fn write(&mut self, _stream: &TcpStream, _data: &[u8]) -> Result<usize> {
+        match *self {}
+    }
+
+    pub fn flush(&mut self, _stream: &TcpStream) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn wants_write(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn close(&mut self, _stream: &TcpStream) -> Result<()> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "tls")]
+fn invalid(path: &Path, what: &str, e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidInput, format!("{} '{}': {}", what, path.display(), e))
+}
+
+#[cfg(feature = "tls")]
+fn certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path).map_err(|e| invalid(path, "certificates", e))?
+        .collect::<std::result::Result<Vec<_>, _>>().map_err(|e| invalid(path, "certificates", e))?;
+    if certs.is_empty() {
+        return Err(invalid(path, "certificates", "no certificate found"));
+    }
+    Ok(certs)
+}
+
+#[cfg(feature = "tls")]
+fn private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| invalid(path, "private key", e))
+}
+
+#[cfg(feature = "tls")]
+fn roots(path: &Path) -> Result<Arc<RootCertStore>> {
+    let mut roots = RootCertStore::empty();
+    for cert in certificates(path)? {
+        roots.add(cert).map_err(|e| invalid(path, "CA certificate", e))?;
+    }
+    Ok(Arc::new(roots))
+}
+
+#[cfg(feature = "tls")]
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Server configuration presenting the chain in `cert` with `key`. With `client_ca`,
+/// clients must present a certificate issued by one of its CAs.
+#[cfg(feature = "tls")]
+pub fn server_config(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<Arc<ServerConfig>> {
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions().map_err(tls_error)?;
+    let builder = match client_ca {
+        Some(ca) => {
+            let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(roots(ca)?, provider())
+                .build().map_err(|e| invalid(ca, "CA certificate", e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder.with_single_cert(certificates(cert)?, private_key(key)?).map_err(|e| invalid(key, "private key", e))?;
+    Ok(Arc::new(config))
+}
+
+/// Client configuration trusting the CAs in `ca`, presenting `identity` (certificate
+/// chain and key) to servers that ask for one.
+#[cfg(feature = "tls")]
+pub fn client_config(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<Arc<ClientConfig>> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions().map_err(tls_error)?
+        .with_root_certificates(roots(ca)?);
+    let config = match identity {
+        Some((cert, key)) => builder.with_client_auth_cert(certificates(cert)?, private_key(key)?).map_err(|e| invalid(key, "private key", e))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Name a server certificate must be valid for: a DNS name or an IP address.
+#[cfg(feature = "tls")]
+pub fn server_name(name: &str) -> Result<ServerName<'static>> {
+    ServerName::try_from(name.to_string()).map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, format!("server name '{}': {}", name, e)))
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+    use crate::codec::SomeIpHeader;
+    use crate::transport::{SomeIpTransport, TcpServer, TcpServerTransport, TcpTransport};
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Instant;
+
+    /// Certificates generated by tests/gen_tls_fixtures.py
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    fn message(payload: &[u8]) -> Vec<u8> {
+        let mut msg = SomeIpHeader::new(0x1234, 0x0001, 0x0001, 0x0001, 0x00, payload.len() as u32).serialize().to_vec();
+        msg.extend_from_slice(payload);
+        msg
+    }
+
+    /// Server echoing every message until its client goes away. Returns the messages it received.
+    fn echo_server(client_ca: Option<&Path>) -> (SocketAddr, thread::JoinHandle<usize>) {
+        let mut server = TcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        server.set_tls(Some(server_config(&fixture("server.pem"), &fixture("server.key"), client_ca).unwrap()));
+        let addr = server.local_addr().unwrap();
+        let transport = TcpServerTransport::new(server);
+        transport.set_nonblocking(true).unwrap();
+        let handle = thread::spawn(move || {
+            let mut received = 0;
+            let mut buf = vec![0u8; 64 * 1024];
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while Instant::now() < deadline {
+                match transport.receive(&mut buf) {
+                    Ok((len, from)) => {
+                        received += 1;
+                        transport.send(&buf[..len], Some(from)).unwrap();
+                    }
+                    Err(_) => thread::sleep(Duration::from_millis(5)),
+                }
+            }
+            received
+        });
+        (addr, handle)
+    }
+
+    /// Receive one message within a second.
+    fn receive(client: &TcpTransport) -> Result<Vec<u8>> {
+        let mut buf = [0u8; 1024];
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            match client.receive(&mut buf) {
+                Ok((len, _)) => return Ok(buf[..len].to_vec()),
+                Err(e) if e.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn connect(addr: SocketAddr, name: &str, identity: Option<(&Path, &Path)>) -> Result<TcpTransport> {
+        let client = TcpTransport::connect(addr)?;
+        client.start_tls(client_config(&fixture("ca.pem"), identity)?, server_name(name)?)?;
+        client.set_nonblocking(true)?;
+        Ok(client)
+    }
+
+    #[test]
+    fn test_messages_exchanged_over_tls() {
+        let (addr, server) = echo_server(None);
+        let client = connect(addr, "127.0.0.1", None).unwrap();
+        assert!(client.is_tls());
+
+        let request = message(b"over tls");
+        client.send(&request, None).unwrap();
+        assert_eq!(receive(&client).unwrap(), request);
+        // Larger than one TLS record
+        let large = message(&[0x5A; 40_000]);
+        client.send(&large, None).unwrap();
+        let mut buf = vec![0u8; large.len()];
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let len = loop {
+            match client.receive(&mut buf) {
+                Ok((len, _)) => break len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+                Err(e) => panic!("{}", e),
+            }
+        };
+        assert_eq!(&buf[..len], &large[..]);
+        client.close().unwrap();
+        assert_eq!(server.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_server_certificate_verified_against_name() {
+        let (addr, server) = echo_server(None);
+        assert!(connect(addr, "localhost", None).is_ok());
+        assert!(connect(addr, "other.example", None).is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_auth_requires_client_certificate() {
+        let ca = fixture("ca.pem");
+        let (addr, server) = echo_server(Some(&ca));
+        let identity = (fixture("client.pem"), fixture("client.key"));
+        let client = connect(addr, "127.0.0.1", Some((&identity.0, &identity.1))).unwrap();
+        client.send(&message(b"known"), None).unwrap();
+        assert!(receive(&client).is_ok());
+
+        // TLS 1.3 clients finish before the server checks them; the server's alert follows
+        let anonymous = connect(addr, "127.0.0.1", None).unwrap();
+        anonymous.send(&message(b"unknown"), None).unwrap();
+        assert!(receive(&anonymous).is_err_and(|e| e.kind() != ErrorKind::WouldBlock));
+        assert_eq!(server.join().unwrap(), 1);
+    }
+}
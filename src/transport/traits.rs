@@ -1,4 +1,5 @@
-use std::io::Result;
+use crate::buffer;
+use std::io::{IoSlice, Result};
 use std::net::SocketAddr;
 
 /// Trait representing a SOME/IP transport channel.
@@ -8,8 +9,20 @@ pub trait SomeIpTransport: Send + Sync {
     /// For UDP, this might default to the last received address or a fixed target.
     fn send(&self, data: &[u8], destination: Option<SocketAddr>) -> Result<usize>;
 
+    /// Send the concatenation of `bufs` as one message, e.g. a header and a payload
+    /// kept in separate buffers. Sockets gather them in one system call; the default
+    /// copies them into a pooled buffer and calls `send`.
+    fn send_vectored(&self, bufs: &[IoSlice<'_>], destination: Option<SocketAddr>) -> Result<usize> {
+        let mut data = buffer::shared().get();
+        for buf in bufs {
+            data.extend_from_slice(buf);
+        }
+        self.send(&data, destination)
+    }
+
     /// Receive data from the network.
-    /// Returns the number of bytes read and the source address.
+    /// Returns the number of bytes read and the source address. Stream transports
+    /// refuse a message longer than `buffer` with `InvalidData` rather than truncate it.
     fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)>;
     
     /// Get the local socket address.
@@ -17,4 +30,24 @@ pub trait SomeIpTransport: Send + Sync {
 
     /// Set non-blocking mode.
     fn set_nonblocking(&self, nonblocking: bool) -> Result<()>;
+
+    /// Whether messages travel on a byte stream (TCP) rather than as datagrams. Streams
+    /// frame messages of any length, so SOME/IP-TP segmentation applies to datagrams only.
+    fn is_stream(&self) -> bool {
+        false
+    }
+
+    /// Close connections held by this transport, e.g. on shutdown. Datagram
+    /// transports have none; their socket closes when dropped.
+    fn close(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Descriptors that become readable when `receive` may have data, so an event loop
+    /// can block until traffic arrives. `None` (the default) means the transport has to
+    /// be polled periodically.
+    #[cfg(target_os = "linux")]
+    fn poll_fds(&self) -> Option<Vec<std::os::fd::RawFd>> {
+        None
+    }
 }
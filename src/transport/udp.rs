@@ -1,6 +1,6 @@
 use super::traits::SomeIpTransport;
 use std::net::{UdpSocket, SocketAddr, Ipv4Addr};
-use std::io::Result;
+use std::io::{IoSlice, Result};
 
 #[derive(Debug)]
 pub struct UdpTransport {
@@ -149,6 +149,12 @@ impl SomeIpTransport for UdpTransport {
         }
     }
 
+    fn send_vectored(&self, bufs: &[IoSlice<'_>], destination: Option<SocketAddr>) -> Result<usize> {
+        let dest = destination.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "UDP requires a destination address"))?;
+        // sendmsg / WSASendTo: one datagram from all buffers
+        socket2::SockRef::from(&self.socket).send_to_vectored(bufs, &dest.into())
+    }
+
     fn receive(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddr)> {
         self.socket.recv_from(buffer)
     }
@@ -160,6 +166,11 @@ impl SomeIpTransport for UdpTransport {
     fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
         self.socket.set_nonblocking(nonblocking)
     }
+
+    #[cfg(target_os = "linux")]
+    fn poll_fds(&self) -> Option<Vec<std::os::fd::RawFd>> {
+        Some(vec![std::os::fd::AsRawFd::as_raw_fd(&self.socket)])
+    }
 }
 
 #[cfg(test)]
@@ -192,6 +203,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_send_vectored_is_one_datagram() {
+        let receiver = UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap();
+        let sender = UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap();
+        let bufs = [IoSlice::new(b"head"), IoSlice::new(b""), IoSlice::new(b"payload")];
+        assert_eq!(sender.send_vectored(&bufs, Some(receiver.local_addr().unwrap())).unwrap(), 11);
+        assert!(sender.send_vectored(&bufs, None).is_err());
+
+        receiver.socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let mut buf = [0u8; 128];
+        let (len, _) = receiver.receive(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"headpayload");
+    }
+
     #[test]
     fn test_nonblocking_mode() {
         let transport = UdpTransport::new("127.0.0.1:0".parse().unwrap()).unwrap();
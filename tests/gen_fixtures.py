@@ -101,4 +101,82 @@ malformed_notification = bytes.fromhex(
 )
 write_fixture("malformed_notification.bin", malformed_notification)
 
+# --- CommonAPI / vsomeip Payload Captures ---
+# Request messages as emitted by vsomeip 3.x with the default CommonAPI-SomeIP
+# deployment: structs carry no length field, arrays use a 32-bit length field
+# unless the deployment overrides it, strings are UTF-8 with BOM and a
+# terminating NUL.
+
+# 8. Array<UInt32> [1, 2, 3] with 32-bit length field
+capi_array_u32 = bytes.fromhex(
+    "12340001"
+    "00000018"   # length = 24
+    "00010001"   # client=0x0001, session=1
+    "01010000"
+    "0000000c"   # array length = 12 bytes
+    "000000010000000200000003"
+)
+write_fixture("capi_array_u32.bin", capi_array_u32)
+
+# 9. Nested struct: { UInt16 id; { Int32 x; Int32 y } pos; Boolean active; Float temp }
+capi_nested_struct = bytes.fromhex(
+    "12340002"
+    "00000017"   # length = 23
+    "00010002"
+    "01010000"
+    "0007"       # id = 7
+    "fffffffb"   # pos.x = -5
+    "0000000a"   # pos.y = 10
+    "01"         # active = true
+    "41ac0000"   # temp = 21.5
+)
+write_fixture("capi_nested_struct.bin", capi_nested_struct)
+
+# 10. Array of structs { Int32 x; Int32 y }[2] with 32-bit length field
+capi_struct_array = bytes.fromhex(
+    "12340003"
+    "0000001c"   # length = 28
+    "00010003"
+    "01010000"
+    "00000010"   # array length = 16 bytes
+    "0000000100000002"
+    "fffffffffffffffe"
+)
+write_fixture("capi_struct_array.bin", capi_struct_array)
+
+# 11. String "Hello": 32-bit length, UTF-8 BOM, NUL terminator
+capi_string_utf8 = bytes.fromhex(
+    "12340004"
+    "00000015"   # length = 21
+    "00010004"
+    "01010000"
+    "00000009"   # string length = 9 bytes (BOM + 5 + NUL)
+    "efbbbf"
+    "48656c6c6f"
+    "00"
+)
+write_fixture("capi_string_utf8.bin", capi_string_utf8)
+
+# 12. Array<UInt8> [10, 11, 12] with 8-bit length field (deployment override)
+capi_array_len8 = bytes.fromhex(
+    "12340005"
+    "0000000c"   # length = 12
+    "00010005"
+    "01010000"
+    "03"         # array length = 3 bytes
+    "0a0b0c"
+)
+write_fixture("capi_array_len8.bin", capi_array_len8)
+
+# 13. Array<UInt16> [1, 2] with 16-bit length field (deployment override)
+capi_array_len16 = bytes.fromhex(
+    "12340006"
+    "0000000e"   # length = 14
+    "00010006"
+    "01010000"
+    "0004"       # array length = 4 bytes
+    "00010002"
+)
+write_fixture("capi_array_len16.bin", capi_array_len16)
+
 print(f"\nGenerated {len(os.listdir(FIXTURES_DIR))} fixture files in {FIXTURES_DIR}")
@@ -0,0 +1,49 @@
+"""
+Generate the certificates of the TLS endpoint tests (tests/fixtures/tls) with the
+openssl CLI: a CA, a server certificate for 127.0.0.1 and localhost, and a client
+certificate, all issued by the CA and valid for 100 years.
+"""
+import os
+import subprocess
+import tempfile
+
+TLS_DIR = os.path.join(os.path.dirname(__file__), "fixtures", "tls")
+os.makedirs(TLS_DIR, exist_ok=True)
+
+DAYS = "36500"
+
+
+def path(name):
+    return os.path.join(TLS_DIR, name)
+
+
+def openssl(*args):
+    subprocess.run(["openssl", *args], check=True, capture_output=True)
+
+
+def key(name):
+    openssl("genpkey", "-algorithm", "EC", "-pkeyopt", "ec_paramgen_curve:P-256", "-out", path(name))
+
+
+def issue(name, serial, subject, extensions):
+    key(f"{name}.key")
+    with tempfile.TemporaryDirectory() as tmp:
+        csr = os.path.join(tmp, f"{name}.csr")
+        ext = os.path.join(tmp, f"{name}.ext")
+        with open(ext, "w") as f:
+            f.write(extensions)
+        openssl("req", "-new", "-key", path(f"{name}.key"), "-subj", subject, "-out", csr)
+        openssl("x509", "-req", "-in", csr, "-CA", path("ca.pem"), "-CAkey", path("ca.key"),
+                "-set_serial", str(serial), "-days", DAYS, "-extfile", ext, "-out", path(f"{name}.pem"))
+    print(f"  Created {name}.pem / {name}.key")
+
+
+key("ca.key")
+openssl("req", "-x509", "-new", "-key", path("ca.key"), "-subj", "/CN=Fusion Hawking Test CA", "-days", DAYS,
+        "-addext", "basicConstraints=critical,CA:TRUE", "-addext", "keyUsage=critical,keyCertSign,cRLSign", "-out", path("ca.pem"))
+print("  Created ca.pem / ca.key")
+
+issue("server", 2, "/CN=localhost",
+      "basicConstraints=CA:FALSE\nkeyUsage=critical,digitalSignature\nextendedKeyUsage=serverAuth\nsubjectAltName=IP:127.0.0.1,DNS:localhost\n")
+issue("client", 3, "/CN=fusion-client",
+      "basicConstraints=CA:FALSE\nkeyUsage=critical,digitalSignature\nextendedKeyUsage=clientAuth\n")
@@ -57,6 +57,15 @@ class TestConfigValidator(unittest.TestCase):
         errors = validate_config(self.valid_config)
         self.assertTrue(any("invalid IP" in e for e in errors))
 
+    def test_auto_ip_and_subnet(self):
+        self.valid_config["interfaces"]["lo"]["endpoints"]["test_ep"]["ip"] = "auto"
+        self.valid_config["interfaces"]["lo"]["subnet"] = "127.0.0.0/8"
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["interfaces"]["lo"]["subnet"] = "127.0.0/x"
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("invalid subnet" in e for e in errors))
+
     def test_duplicate_service(self):
         # Add another instance providing same service
         self.valid_config["instances"]["inst2"] = {
@@ -78,5 +87,257 @@ class TestConfigValidator(unittest.TestCase):
         errors = validate_config(self.valid_config)
         self.assertTrue(any("references unknown endpoint" in e for e in errors))
 
+    def test_qos_profile_reference(self):
+        self.valid_config["qos_profiles"] = {"control": {"request_timeout_ms": 100, "transport": "udp"}}
+        self.valid_config["instances"]["test_inst"]["providing"]["test_svc"]["qos"] = "control"
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["providing"]["test_svc"]["qos"] = "bulk"
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("unknown QoS profile 'bulk'" in e for e in errors))
+
+    def test_local_endpoint_reference(self):
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"] = {
+            "service_id": 4097, "instance_id": 1, "major_version": 1,
+            "find_on": ["lo"], "local_endpoint": "test_ep"
+        }
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["local_endpoint"] = "client_ep"
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("unknown endpoint 'client_ep'" in e for e in errors))
+
+    def test_required_eventgroups(self):
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"] = {
+            "service_id": 4097, "instance_id": 1, "major_version": 1, "find_on": ["lo"],
+            "eventgroups": {"status": {"eventgroup_id": 1, "reliable": True}, "telemetry": {"eventgroup_id": 2}}
+        }
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["eventgroups"]["telemetry"]["eventgroup_id"] = 1
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("configures eventgroup 1 more than once" in e for e in errors))
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["eventgroups"]["telemetry"] = {"eventgroup_id": 2, "reliable": "yes"}
+        self.assertNotEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["eventgroups"]["telemetry"] = {"eventgroup_id": 2, "subscribe": True, "ttl": 3, "interface": "eth9"}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("eventgroup 'telemetry' references unknown interface 'eth9'" in e for e in errors))
+
+    def test_shared_memory(self):
+        self.valid_config["instances"]["test_inst"]["shared_memory"] = {}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["shared_memory"] = {"ring_size_kb": 0}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("ring_size_kb must be positive" in e for e in errors))
+
+        self.valid_config["instances"]["test_inst"]["shared_memory"] = {"ring_size": 64}
+        self.assertNotEqual(validate_config(self.valid_config), [])
+
+    def test_service_quota(self):
+        svc = self.valid_config["instances"]["test_inst"]["providing"]["test_svc"]
+        svc["quota"] = {"max_in_flight": 4, "max_subscribers": 16, "max_queue_bytes": 65536}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        svc["quota"]["max_in_flight"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("quota max_in_flight must be positive" in e for e in errors))
+
+        svc["quota"] = {"max_requests": 4}
+        self.assertNotEqual(validate_config(self.valid_config), [])
+
+    def test_subscription_retry(self):
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"] = {
+            "service_id": 4097, "instance_id": 1, "major_version": 1, "find_on": ["lo"],
+            "subscription_retry": {"ack_timeout_ms": 500, "max_retries": 2, "retry_on_nack": False}
+        }
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["subscription_retry"]["ack_timeout_ms"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("ack_timeout_ms must be positive" in e for e in errors))
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["subscription_retry"] = {"retries": 2}
+        self.assertNotEqual(validate_config(self.valid_config), [])
+
+    def test_request_retry(self):
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"] = {
+            "service_id": 4097, "instance_id": 1, "major_version": 1, "find_on": ["lo"],
+            "request_retry": {"timeout_ms": 500, "max_retries": 2, "backoff_ms": 50,
+                              "methods": {"0x0001": {"idempotent": True}, "2": {"idempotent": False}}}
+        }
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        retry = self.valid_config["instances"]["test_inst"]["required"]["test_client"]["request_retry"]
+        retry["timeout_ms"] = 0
+        retry["methods"]["get"] = {"idempotent": True}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("request_retry timeout_ms must be positive" in e for e in errors))
+        self.assertTrue(any("request_retry key 'get' is not a method ID" in e for e in errors))
+
+    def test_request_limit(self):
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"] = {
+            "service_id": 4097, "instance_id": 1, "major_version": 1, "find_on": ["lo"],
+            "request_limit": {"max_in_flight": 8, "wait": True}
+        }
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        self.valid_config["instances"]["test_inst"]["required"]["test_client"]["request_limit"]["max_in_flight"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("request_limit max_in_flight must be at least 1" in e for e in errors))
+
+    def test_request_response_delay(self):
+        sd = self.valid_config["instances"]["test_inst"]["sd"]
+        sd.update({"request_response_delay_min_ms": 20, "request_response_delay_max_ms": 50})
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        sd["request_response_delay_min_ms"] = 80
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("request_response_delay_min_ms exceeds" in e for e in errors))
+
+    def test_sd_ingress_limits(self):
+        sd = self.valid_config["instances"]["test_inst"]["sd"]
+        sd.update({"max_packets_per_poll": 32, "max_backlog": 512})
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        sd["max_packets_per_poll"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("sd max_packets_per_poll must be positive" in e for e in errors))
+
+    def test_subscription_renewal_percent(self):
+        sd = self.valid_config["instances"]["test_inst"]["sd"]
+        sd["subscription_renewal_percent"] = 75
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        sd["subscription_renewal_percent"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("subscription_renewal_percent must be between 1 and 100" in e for e in errors))
+
+    def test_tp_limits(self):
+        inst = self.valid_config["instances"]["test_inst"]
+        inst["tp"] = {"reassembly_timeout_ms": 2000, "max_buffer_bytes": 262144}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        inst["tp"]["reassembly_timeout_ms"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("tp reassembly_timeout_ms must be positive" in e for e in errors))
+
+        inst["tp"] = {"timeout_ms": 2000}
+        self.assertNotEqual(validate_config(self.valid_config), [])
+
+    def test_worker_threads(self):
+        inst = self.valid_config["instances"]["test_inst"]
+        inst["worker_threads"] = 4
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        inst["worker_threads"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("worker_threads must be positive" in e for e in errors))
+
+    def test_tcp_reconnect_backoff(self):
+        inst = self.valid_config["instances"]["test_inst"]
+        inst["tcp"] = {"reconnect_backoff_min_ms": 50, "reconnect_backoff_max_ms": 5000}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        inst["tcp"]["reconnect_backoff_min_ms"] = 6000
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("reconnect_backoff_min_ms exceeds" in e for e in errors))
+
+    def test_magic_cookie_interval(self):
+        ep = self.valid_config["interfaces"]["lo"]["endpoints"]["test_ep"]
+        ep["magic_cookie_interval_ms"] = 1000
+        self.valid_config["instances"]["test_inst"]["tcp"] = {"magic_cookie_interval_ms": 500}
+        ep["protocol"] = "tcp"
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        ep["protocol"] = "udp"
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("is not a tcp endpoint" in e for e in errors))
+
+    def test_mtu_and_tp_pacing(self):
+        ep = self.valid_config["interfaces"]["lo"]["endpoints"]["test_ep"]
+        ep.update({"mtu": 576, "tp_pacing_us": 200, "tp_burst": 4, "tp_adaptive": True})
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        ep["mtu"] = 20
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("below the minimum of 36 bytes" in e for e in errors))
+
+        ep["mtu"] = 70000
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("above the maximum of 65507 bytes" in e for e in errors))
+
+        ep["mtu"] = 576
+        ep["tp_burst"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("below the minimum of 1 segment" in e for e in errors))
+
+    def test_client_id(self):
+        instances = self.valid_config["instances"]
+        instances["test_inst"]["client_id"] = 0x0101
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        instances["test_inst"]["client_id"] = 0
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("client_id must be between" in e for e in errors))
+
+        instances["test_inst"]["client_id"] = 0x0101
+        instances["other_inst"] = {"client_id": 0x0101}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("Duplicate client_id 0x0101" in e for e in errors))
+
+    def test_e2e(self):
+        svc = self.valid_config["instances"]["test_inst"]["providing"]["test_svc"]
+        svc["e2e"] = {"0x8001": {"profile": "P04", "data_id": 0x12345678, "max_delta_counter": 3}}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        svc["e2e"] = {"0x8001": {"profile": "P01", "data_id": 0x12345678}}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("data_id exceeds the range of profile P01" in e for e in errors))
+
+        svc["e2e"] = {"event": {"profile": "P01", "data_id": 1, "max_delta_counter": 15}}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("e2e key 'event' is not a method or event ID" in e for e in errors))
+        self.assertTrue(any("max_delta_counter must be between 1 and 14" in e for e in errors))
+
+        svc["e2e"] = {"1": {"profile": "P02", "data_id": 1}}
+        self.assertTrue(validate_config(self.valid_config))
+
+    def test_access_rules(self):
+        svc = self.valid_config["instances"]["test_inst"]["providing"]["test_svc"]
+        svc["access"] = [{"source": "10.0.0.0/8", "methods": [1, 2]}, {"source": "fd00::1", "eventgroups": [1]}]
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        svc["access"] = [{"source": "10.0.0.0/33"}, {"source": "eth0"}]
+        errors = validate_config(self.valid_config)
+        self.assertEqual(len([e for e in errors if "access rule has invalid source" in e]), 2)
+
+        svc["access"] = [{"methods": [1]}]
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("access rule must be an object with a source" in e for e in errors))
+
+    def test_metrics_exporter(self):
+        inst = self.valid_config["instances"]["test_inst"]
+        inst["metrics"] = {"bind": "0.0.0.0", "port": 9100}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        inst["metrics"] = {"bind": "localhost", "port": 9100}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("metrics bind is not an IP address" in e for e in errors))
+
+        inst["metrics"] = {"bind": "127.0.0.1"}
+        self.assertTrue(validate_config(self.valid_config))
+
+    def test_log_levels(self):
+        inst = self.valid_config["instances"]["test_inst"]
+        inst["logging"] = {"level": "Info", "components": {"Runtime": "Warn", "MathService": "debug"}}
+        self.assertEqual(validate_config(self.valid_config), [])
+
+        inst["logging"] = {"components": {"Runtime": "Verbose"}}
+        errors = validate_config(self.valid_config)
+        self.assertTrue(any("Runtime" in e and "not in enum" in e for e in errors))
+
 if __name__ == '__main__':
     unittest.main()
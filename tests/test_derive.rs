@@ -0,0 +1,135 @@
+//! `#[derive(SomeIpSerialize, SomeIpDeserialize)]` layouts, checked byte for byte.
+
+use fusion_hawking::codec::{SeqRef, SomeIpDeserialize, SomeIpDeserializeRef, SomeIpSerialize};
+
+fn encode<T: SomeIpSerialize>(value: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    value.serialize(&mut buf).unwrap();
+    buf
+}
+
+fn decode<T: SomeIpDeserialize>(bytes: &[u8]) -> T {
+    let mut reader = bytes;
+    let value = T::deserialize(&mut reader).unwrap();
+    assert!(reader.is_empty(), "{} bytes left", reader.len());
+    value
+}
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+struct Point {
+    x: i16,
+    y: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+struct Route {
+    id: u8,
+    points: Vec<Point>,
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+struct Pair(u8, bool);
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+struct Marker;
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+#[someip(length_field = 2)]
+struct Status {
+    code: u8,
+    #[someip(length_field = 1)]
+    text: String,
+    #[someip(optional, align = 4)]
+    detail: Option<u32>,
+    #[someip(optional)]
+    points: Option<Vec<Point>>,
+}
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+#[someip(align = 4)]
+struct Aligned {
+    flag: bool,
+    value: u16,
+    #[someip(length_field = 2)]
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, SomeIpSerialize, SomeIpDeserialize)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+#[test]
+fn test_default_layout_matches_handwritten_codec() {
+    let route = Route { id: 7, points: vec![Point { x: 1, y: -1 }], name: "A".into() };
+    let mut expected = vec![7];
+    expected.extend([0, 0, 0, 4, 0, 1, 0xFF, 0xFF]);
+    expected.extend([0, 0, 0, 1, b'A']);
+    assert_eq!(encode(&route), expected);
+    assert_eq!(decode::<Route>(&expected), route);
+
+    assert_eq!(encode(&Pair(3, true)), vec![3, 1]);
+    assert_eq!(decode::<Pair>(&[3, 1]), Pair(3, true));
+    assert!(encode(&Marker).is_empty());
+    assert_eq!(decode::<Wrapper<Point>>(&[0, 2, 0, 3]), Wrapper { inner: Point { x: 2, y: 3 } });
+}
+
+#[test]
+fn test_length_fields_and_optional_members() {
+    let status = Status { code: 1, text: "ok".into(), detail: Some(9), points: None };
+    // length | flags | code | text with 1-byte length | padding to 8 | detail
+    let bytes = vec![0, 12, 0b01, 1, 2, b'o', b'k', 0, 0, 0, 0, 0, 0, 9];
+    assert_eq!(encode(&status), bytes);
+    assert_eq!(decode::<Status>(&bytes), status);
+
+    // Absent members take no space, padding included
+    let status = Status { code: 2, text: String::new(), detail: None, points: Some(vec![Point { x: 0, y: 5 }]) };
+    let bytes = vec![0, 11, 0b10, 2, 0, 0, 0, 0, 4, 0, 0, 0, 5];
+    assert_eq!(encode(&status), bytes);
+    assert_eq!(decode::<Status>(&bytes), status);
+}
+
+#[test]
+fn test_struct_length_field_skips_unknown_members() {
+    #[derive(Debug, PartialEq, SomeIpDeserialize)]
+    #[someip(length_field = 2)]
+    struct Old {
+        flags: u8,
+        code: u8,
+    }
+    // A newer peer appended a member this struct does not know
+    let bytes = vec![0, 4, 0, 3, 0xAB, 0xCD];
+    assert_eq!(decode::<Old>(&bytes), Old { flags: 0, code: 3 });
+}
+
+#[test]
+fn test_alignment() {
+    let value = Aligned { flag: true, value: 0x0102, data: vec![9] };
+    // flag | pad to 4 | value | pad to 8 | data with 2-byte length
+    let bytes = vec![1, 0, 0, 0, 1, 2, 0, 0, 0, 1, 9];
+    assert_eq!(encode(&value), bytes);
+    assert_eq!(decode::<Aligned>(&bytes), value);
+}
+
+#[derive(Debug, SomeIpDeserializeRef)]
+struct RouteView<'a> {
+    id: u8,
+    points: SeqRef<'a, Point>,
+    name: &'a str,
+}
+
+#[test]
+fn test_borrowed_view_reads_owned_layout() {
+    let route = Route { id: 7, points: vec![Point { x: 1, y: -1 }, Point { x: 2, y: 0 }], name: "A".into() };
+    let bytes = encode(&route);
+    let mut reader = &bytes[..];
+    let view = RouteView::deserialize_ref(&mut reader).unwrap();
+    assert!(reader.is_empty());
+    assert_eq!(view.id, 7);
+    assert_eq!(view.name, "A");
+    assert_eq!(view.points.to_vec().unwrap(), route.points);
+
+    // Owned structs decode through the same trait
+    assert_eq!(Route::deserialize_ref(&mut &bytes[..]).unwrap(), route);
+}
@@ -0,0 +1,84 @@
+import json
+import os
+import socket
+import struct
+import sys
+import tempfile
+import unittest
+
+PROJECT_ROOT = os.path.abspath(os.path.join(os.path.dirname(__file__), ".."))
+sys.path.insert(0, os.path.join(PROJECT_ROOT, 'src', 'python'))
+
+from fusion_hawking.runtime import ReturnCode
+from fusion_hawking import rust_core
+
+SERVICE_ID = 0x4251
+
+CONFIG = {
+    "endpoints": {"server_ep": {"ip": "127.0.0.1", "port": 0, "version": 4, "protocol": "udp"}},
+    "instances": {
+        "test": {
+            "endpoint": "server_ep",
+            "providing": {"reverse": {"service_id": SERVICE_ID, "instance_id": 1, "major_version": 1, "endpoint": "server_ep"}}
+        }
+    }
+}
+
+
+def handle(method_id, payload):
+    if method_id == 1:
+        return payload[::-1]
+    if method_id == 2:
+        raise ValueError("handler failure")
+    return ReturnCode.E_UNKNOWN_METHOD
+
+
+@unittest.skipUnless(rust_core.available(), "libfusion_hawking not built with the python feature (cargo build --features python)")
+class TestRustCore(unittest.TestCase):
+    def setUp(self):
+        with socket.socket(socket.AF_INET, socket.SOCK_DGRAM) as probe:
+            probe.bind(("127.0.0.1", 0))
+            self.port = probe.getsockname()[1]
+        config = json.loads(json.dumps(CONFIG))
+        config["endpoints"]["server_ep"]["port"] = self.port
+        fd, self.config_path = tempfile.mkstemp(suffix=".json")
+        with os.fdopen(fd, "w") as f:
+            json.dump(config, f)
+
+    def tearDown(self):
+        os.remove(self.config_path)
+
+    def call(self, port, method_id):
+        """(message type, return code, payload) of the answer to a raw request"""
+        with socket.socket(socket.AF_INET, socket.SOCK_DGRAM) as sock:
+            sock.settimeout(2.0)
+            payload = b"\x01\x02"
+            header = struct.pack(">HHIHHBBBB", SERVICE_ID, method_id, 8 + len(payload), 0, 1, 1, 1, 0x00, 0)
+            sock.sendto(header + payload, ("127.0.0.1", port))
+            data, _ = sock.recvfrom(1500)
+            return data[14], data[15], data[16:]
+
+    def test_unknown_instance(self):
+        with self.assertRaises(ValueError):
+            rust_core.RustRuntime(self.config_path, "missing")
+
+    def test_offer_unknown_alias(self):
+        with rust_core.RustRuntime(self.config_path, "test") as rt:
+            with self.assertRaises(KeyError):
+                rt.offer_service("unknown", handle)
+            self.assertIsNone(rt.get_client("reverse"))
+
+    def test_requests_through_rust_core(self):
+        with rust_core.RustRuntime(self.config_path, "test") as rt:
+            rt.offer_service("reverse", handle)
+            self.assertTrue(rt.start())
+            self.assertFalse(rt.start())
+            # The runtime requests its own service
+            self.assertEqual(rt.send_request_to(SERVICE_ID, 1, f"127.0.0.1:{self.port}", b"abc"), b"cba")
+            self.assertEqual(self.call(self.port, 1), (0x80, ReturnCode.E_OK, b"\x02\x01"))
+            self.assertEqual(self.call(self.port, 2), (0x81, ReturnCode.E_NOT_OK, b""))
+            self.assertEqual(self.call(self.port, 3), (0x81, ReturnCode.E_UNKNOWN_METHOD, b""))
+
+
+if __name__ == '__main__':
+    unittest.main()
@@ -0,0 +1,72 @@
+//! SD scale tests on the in-process simulation harness.
+//!
+//! Run with `cargo test --features test-utils --test test_sim_scale`.
+
+use fusion_hawking::sim::Simulation;
+use fusion_hawking::transport::NetworkConditions;
+use std::time::Duration;
+
+const NODES: usize = 100;
+const TICK: Duration = Duration::from_millis(10);
+
+fn offer_all(sim: &mut Simulation) {
+    for i in 0..sim.len() {
+        sim.node_mut(i).offer_service(0x1000 + i as u16, 1, 1, 0, "sim", 30500, 0x11, None);
+    }
+}
+
+fn converged(sim: &Simulation) -> bool {
+    (0..sim.len()).all(|i| sim.discovered_by_all(0x1000 + i as u16, 1, i))
+}
+
+#[test]
+fn test_discovery_converges_with_100_nodes() {
+    let mut sim = Simulation::new(NODES, NetworkConditions { latency: Duration::from_millis(1), ..Default::default() });
+    offer_all(&mut sim);
+
+    let elapsed = sim.run_until(TICK, Duration::from_secs(2), converged)
+        .expect("discovery did not converge");
+    // First offers go out after the initial wait (10-100ms by default)
+    assert!(elapsed <= Duration::from_millis(200), "converged after {:?}", elapsed);
+
+    // Every node sends exactly one multicast offer per transmission, no acks or retries
+    let stats = sim.stats();
+    assert!(stats.sent >= NODES as u64);
+    assert_eq!(stats.delivered, stats.sent * NODES as u64);
+}
+
+#[test]
+fn test_offer_traffic_volume_per_phase() {
+    let mut sim = Simulation::new(NODES, NetworkConditions::default());
+    offer_all(&mut sim);
+
+    // Initial wait + repetition phase (3 repetitions: 100, 200, 400ms) ends well within 1.5s
+    sim.run_until(TICK, Duration::from_millis(1500), |_| false);
+    let startup = sim.stats().sent;
+    // Per node: one offer when the initial wait expires, the first repetition right after it,
+    // three more repetitions, and an immediate offer on entering the main phase
+    assert!(startup <= NODES as u64 * 6, "{} offers during startup", startup);
+
+    // Main phase: one offer per node per cyclic delay (1s)
+    sim.network.reset_stats();
+    sim.run_until(TICK, Duration::from_secs(10), |_| false);
+    let per_second = sim.stats().sent as f64 / 10.0;
+    assert!((per_second - NODES as f64).abs() <= NODES as f64 * 0.1, "{} offers/s", per_second);
+}
+
+#[test]
+fn test_discovery_converges_under_loss() {
+    let conditions = NetworkConditions {
+        latency: Duration::from_millis(5),
+        jitter: Duration::from_millis(5),
+        loss: 0.2,
+    };
+    let mut sim = Simulation::new(NODES, conditions);
+    offer_all(&mut sim);
+
+    // Repetitions and cyclic offers make up for lost datagrams
+    let elapsed = sim.run_until(TICK, Duration::from_secs(10), converged)
+        .expect("discovery did not converge under 20% loss");
+    assert!(sim.stats().dropped > 0);
+    assert!(elapsed > Duration::from_millis(100));
+}
@@ -3,3 +3,9 @@ from ..models import Struct, Service
 class AbstractGenerator:
     def generate(self, structs: list[Struct], services: list[Service]) -> dict[str, str]:
         raise NotImplementedError
+
+    def _require_default_wire(self, structs: list[Struct], lang: str):
+        """Fail instead of silently emitting a different wire format than the IDL asks for."""
+        for s in structs:
+            if not s.wire.is_default():
+                raise ValueError(f"{s.name}: @wire bool representation is not supported by the {lang} generator")
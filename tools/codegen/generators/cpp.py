@@ -19,6 +19,7 @@ class CppGenerator(AbstractGenerator):
         return re.sub(r'(?<!^)(?=[A-Z])', '_', name).lower()
 
     def generate(self, structs: list[Struct], services: list[Service], output_dir: str = "build/generated") -> dict[str, str]:
+        self._require_default_wire(structs, "C++")
         files = {}
         cpp_dir = os.path.join(output_dir, "cpp")
 
@@ -4,6 +4,7 @@ import struct
 
 class PythonGenerator(AbstractGenerator):
     def generate(self, structs: list[Struct], services: list[Service], output_dir: str = "build/generated") -> dict[str, str]:
+        self._require_default_wire(structs, "Python")
         # 1. Bindings
         bind_lines = [
             "import struct",
@@ -81,6 +82,11 @@ class PythonGenerator(AbstractGenerator):
                 runtime_lines.append(f"        if mid == {m.id}:")
                 runtime_lines.append(f"            req = {svc.name}{method_pascal}Request.deserialize(payload)")
                 args_call = ", ".join([f"req.{f.name}" for f in m.args])
+                if getattr(m, 'fire_and_forget', False):
+                    # Called with REQUEST_NO_RETURN: no response is sent
+                    runtime_lines.append(f"            self.{m.name}({args_call})")
+                    runtime_lines.append("            return None")
+                    continue
                 runtime_lines.append(f"            result = self.{m.name}({args_call})")
                 res_name = f"{svc.name}{method_pascal}Response"
                 if m.ret_type.name != "None":
@@ -137,6 +143,10 @@ class PythonGenerator(AbstractGenerator):
                 runtime_lines.append(f"            cfg = self.runtime.config['required'].get(self.alias)")
                 runtime_lines.append(f"            if cfg and 'static_ip' in cfg:")
                 runtime_lines.append(f"                target = (cfg['static_ip'], cfg.get('static_port', 0))")
+                if getattr(m, 'fire_and_forget', False):
+                    runtime_lines.append("        if target:")
+                    runtime_lines.append(f"            self.runtime.send_request(self.SERVICE_ID, {m.id}, req.serialize(), target, msg_type=0x01)")
+                    continue
                 wait_for_res = m.ret_type.name != "None"
                 runtime_lines.append("        if target:")
                 runtime_lines.append(f"            res_payload = self.runtime.send_request(self.SERVICE_ID, {m.id}, req.serialize(), target, wait_for_response={wait_for_res})")
@@ -20,6 +20,7 @@ class RustGenerator(AbstractGenerator):
     def generate(self, structs: list[Struct], services: list[Service], output_dir: str = "build/generated") -> dict[str, str]:
         files = {}
         rust_dir = os.path.join(output_dir, "rust")
+        self._struct_names = {st.name for st in structs}
 
         # 1. Types file (shared @dataclass types)
         if structs:
@@ -60,6 +61,8 @@ class RustGenerator(AbstractGenerator):
             "",
             "use fusion_hawking::codec::{SomeIpSerialize, SomeIpDeserialize};",
             "#[allow(unused_imports)]",
+            "use fusion_hawking::codec::{SomeIpSchema, TypeSchema};",
+            "#[allow(unused_imports)]",
             "use std::io::{Result, Write, Read};",
             "",
         ]
@@ -77,6 +80,8 @@ class RustGenerator(AbstractGenerator):
             "",
             "use fusion_hawking::codec::{SomeIpSerialize, SomeIpDeserialize, SomeIpHeader};",
             "#[allow(unused_imports)]",
+            "use fusion_hawking::codec::{SomeIpSchema, TypeSchema};",
+            "#[allow(unused_imports)]",
             "use std::io::{Result, Write, Read, Cursor};",
             "#[allow(unused_imports)]",
             "use std::sync::Arc;",
@@ -110,6 +115,16 @@ class RustGenerator(AbstractGenerator):
             lines.append(self._generate_struct(Struct(event_name, e.args), event_name))
             lines.append("")
 
+        if svc.eventgroups:
+            # Events handed to the callback of the client's `subscribe`
+            lines.append("#[derive(Debug, Clone, PartialEq)]")
+            lines.append(f"pub enum {pasc}Event {{")
+            for e in svc.events:
+                event_pascal = self._to_pascal(e.name)
+                lines.append(f"    {event_pascal}({pasc}{event_pascal}Event),")
+            lines.append("}")
+            lines.append("")
+
         # Provider Trait
         lines.append(self._generate_provider_trait(svc, pasc))
 
@@ -125,33 +140,116 @@ class RustGenerator(AbstractGenerator):
         lines = []
         lines.append(f"#[allow(dead_code)]")
         lines.append(f"#[derive(Debug, Clone, PartialEq)]")
+        # JSON views for tooling; the consuming crate forwards the feature to fusion-hawking
+        lines.append('#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]')
         lines.append(f"pub struct {struct_name} {{")
         for f in s.fields:
             lines.append(f"    pub {f.name}: {self._rust_type(f.type)},")
         lines.append("}")
 
-        # Serialize
-        lines.append(f"impl SomeIpSerialize for {struct_name} {{")
-        writer_param = "_writer" if len(s.fields) == 0 else "writer"
-        lines.append(f"    fn serialize<W: Write>(&self, {writer_param}: &mut W) -> Result<()> {{")
+        if s.wire.is_default():
+            # Serialize
+            lines.append(f"impl SomeIpSerialize for {struct_name} {{")
+            writer_param = "_writer" if len(s.fields) == 0 else "writer"
+            lines.append(f"    fn serialize<W: Write>(&self, {writer_param}: &mut W) -> Result<()> {{")
+            for f in s.fields:
+                lines.append(f"        self.{f.name}.serialize(writer)?;")
+            lines.append("        Ok(())")
+            lines.append("    }")
+            lines.append("}")
+
+            # Deserialize
+            lines.append(f"impl SomeIpDeserialize for {struct_name} {{")
+            reader_param = "_reader" if len(s.fields) == 0 else "reader"
+            lines.append(f"    fn deserialize<R: Read>({reader_param}: &mut R) -> Result<Self> {{")
+            lines.append(f"        Ok({struct_name} {{")
+            for f in s.fields:
+                lines.append(f"            {f.name}: <{self._rust_type(f.type)}>::deserialize(reader)?,")
+            lines.append("        })")
+            lines.append("    }")
+            lines.append("}")
+        else:
+            lines.append(self._generate_wire_codec(s, struct_name))
+
+        # Schema (fingerprinted in diagnostic mode)
+        field_list = ",".join(f"{f.name}:{self._schema_type(s, f)}" for f in s.fields)
+        nested = []
         for f in s.fields:
-            lines.append(f"        self.{f.name}.serialize(writer)?;")
+            base = f.type
+            while base.inner:
+                base = base.inner
+            pascal = self._to_pascal(base.name)
+            if base.name in getattr(self, "_struct_names", set()) and pascal not in nested:
+                nested.append(pascal)
+        nested_str = ", ".join(f"<{n} as SomeIpSchema>::schema()" for n in nested)
+        lines.append(f"impl SomeIpSchema for {struct_name} {{")
+        lines.append("    fn schema() -> TypeSchema {")
+        lines.append(f"        TypeSchema {{ name: \"{struct_name}\", fields: \"{field_list}\", nested: vec![{nested_str}] }}")
+        lines.append("    }")
+        lines.append("}")
+        return "\n".join(lines)
+
+    def _wire_groups(self, s: Struct) -> list[list[Field]]:
+        """Split fields into encoding units: a bit field of consecutive bools, or a single field."""
+        groups = []
+        per_field = s.wire.bool_width * 8
+        for f in s.fields:
+            is_flag = f.type.name == "bool" and not f.type.inner
+            if (s.wire.bools == "bits" and is_flag and groups and groups[-1][0].type.name == "bool"
+                    and not groups[-1][0].type.inner and len(groups[-1]) < per_field):
+                groups[-1].append(f)
+            else:
+                groups.append([f])
+        return groups
+
+    def _generate_wire_codec(self, s: Struct, struct_name: str) -> str:
+        """Serialize/Deserialize impls for a struct with non-default bool representation."""
+        repr_mod = "fusion_hawking::codec::repr"
+        width = s.wire.bool_width
+        groups = self._wire_groups(s)
+        lines = []
+
+        lines.append(f"impl SomeIpSerialize for {struct_name} {{")
+        lines.append("    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {")
+        for g in groups:
+            f = g[0]
+            if f.type.name != "bool" or f.type.inner:
+                lines.append(f"        self.{f.name}.serialize(writer)?;")
+            elif s.wire.bools == "bits":
+                flags = ", ".join(f"self.{x.name}" for x in g)
+                lines.append(f"        {repr_mod}::write_bits(&[{flags}], {width}, writer)?;")
+            else:
+                lines.append(f"        {repr_mod}::write_bool(self.{f.name}, {width}, writer)?;")
         lines.append("        Ok(())")
         lines.append("    }")
         lines.append("}")
 
-        # Deserialize
         lines.append(f"impl SomeIpDeserialize for {struct_name} {{")
-        reader_param = "_reader" if len(s.fields) == 0 else "reader"
-        lines.append(f"    fn deserialize<R: Read>({reader_param}: &mut R) -> Result<Self> {{")
-        lines.append(f"        Ok({struct_name} {{")
-        for f in s.fields:
-            lines.append(f"            {f.name}: <{self._rust_type(f.type)}>::deserialize(reader)?,")
-        lines.append("        })")
+        lines.append("    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {")
+        for i, g in enumerate(groups):
+            f = g[0]
+            if f.type.name != "bool" or f.type.inner:
+                lines.append(f"        let {f.name} = <{self._rust_type(f.type)}>::deserialize(reader)?;")
+            elif s.wire.bools == "bits":
+                lines.append(f"        let bits_{i} = {repr_mod}::read_bits({len(g)}, {width}, reader)?;")
+                for j, x in enumerate(g):
+                    lines.append(f"        let {x.name} = bits_{i}[{j}];")
+            else:
+                lines.append(f"        let {f.name} = {repr_mod}::read_bool({width}, reader)?;")
+        names = ", ".join(f.name for f in s.fields)
+        lines.append(f"        Ok({struct_name} {{ {names} }})")
         lines.append("    }")
         lines.append("}")
         return "\n".join(lines)
 
+    def _schema_type(self, s: Struct, f: Field) -> str:
+        """Field type for the schema fingerprint; non-default bool encodings are part of it."""
+        rust = self._rust_type(f.type)
+        if s.wire.is_default() or f.type.name != "bool" or f.type.inner:
+            return rust
+        kind = "bits" if s.wire.bools == "bits" else "u"
+        return f"{rust}[{kind}{s.wire.bool_width * 8}]"
+
     def _generate_provider_trait(self, svc: Service, trait_name: str) -> str:
         lines = []
         lines.append(f"#[allow(dead_code)]")
@@ -160,6 +258,9 @@ class RustGenerator(AbstractGenerator):
             args_str = ", ".join([f"{a.name}: {self._rust_type(a.type)}" for a in m.args])
             ret_str = f" -> {self._rust_type(m.ret_type)}" if m.ret_type.name != "None" else ""
             lines.append(f"    fn {m.name}(&self, {args_str}){ret_str};")
+        # Fields are held by the provider; the server answers getters and setters from them
+        for f in svc.fields:
+            lines.append(f"    fn {f.name}(&self) -> &fusion_hawking::runtime::FieldHandle<{self._rust_type(f.type)}>;")
         lines.append("}")
         return "\n".join(lines)
 
@@ -178,6 +279,8 @@ class RustGenerator(AbstractGenerator):
             lines.append(f"    pub const METHOD_{m.name.upper()}: u16 = {m.id};")
         for e in svc.events:
             lines.append(f"    pub const EVENT_{e.name.upper()}: u16 = {e.id};")
+        for name, group_id in svc.eventgroups.items():
+            lines.append(f"    pub const EVENTGROUP_{name.upper()}: u16 = {group_id};")
         for f in svc.fields:
             if f.get_id:
                 lines.append(f"    pub const FIELD_GET_{f.name.upper()}: u16 = {f.get_id};")
@@ -187,18 +290,63 @@ class RustGenerator(AbstractGenerator):
                 lines.append(f"    pub const EVENT_{f.name.upper()}_NOTIFY: u16 = {f.notifier_id};")
         lines.append("}")
 
+        lines.append("")
+        lines.append("#[allow(dead_code)]")
+        lines.append(f"impl {svc_pascal}Server<()> {{")
+        lines.append("    /// Register payload schemas of all methods and events for diagnostic mode.")
+        lines.append("    pub fn register_schemas(runtime: &fusion_hawking::runtime::SomeIpRuntime) {")
+        for m in svc.methods:
+            method_pascal = self._to_pascal(m.name)
+            lines.append(f"        runtime.register_schema(Self::SERVICE_ID, Self::METHOD_{m.name.upper()}, false, {svc_pascal}{method_pascal}Request::schema());")
+            lines.append(f"        runtime.register_schema(Self::SERVICE_ID, Self::METHOD_{m.name.upper()}, true, {svc_pascal}{method_pascal}Response::schema());")
+        for e in svc.events:
+            event_pascal = self._to_pascal(e.name)
+            lines.append(f"        runtime.register_schema(Self::SERVICE_ID, Self::EVENT_{e.name.upper()}, false, {svc_pascal}{event_pascal}Event::schema());")
+        lines.append("    }")
+        # Events go to the subscribers of the eventgroups declared to carry them (config or offer_eventgroup)
+        for e in svc.events:
+            event_name = f"{svc_pascal}{self._to_pascal(e.name)}Event"
+            args_str = "".join([f", {a.name}: {self._rust_type(a.type)}" for a in e.args])
+            field_inits = ", ".join([a.name for a in e.args])
+            lines.append("")
+            lines.append(f"    /// Send the {e.name} event; returns the number of subscribers reached.")
+            lines.append(f"    pub fn fire_{e.name}(runtime: &fusion_hawking::runtime::SomeIpRuntime{args_str}) -> std::io::Result<usize> {{")
+            lines.append(f"        let event = {event_name} {{ {field_inits} }};")
+            lines.append("        let mut payload = Vec::new();")
+            lines.append("        event.serialize(&mut payload)?;")
+            lines.append(f"        Ok(runtime.fire_event(Self::SERVICE_ID, Self::EVENT_{e.name.upper()}, &payload))")
+            lines.append("    }")
+        for f in svc.fields:
+            notifier = f"Some(Self::EVENT_{f.name.upper()}_NOTIFY)" if f.notifier_id else "None"
+            lines.append("")
+            lines.append(f"    /// Field handle for {f.name}, to be returned by the provider.")
+            lines.append(f"    pub fn new_{f.name}(initial: {self._rust_type(f.type)}) -> fusion_hawking::runtime::FieldHandle<{self._rust_type(f.type)}> {{")
+            lines.append(f"        fusion_hawking::runtime::FieldHandle::new(Self::SERVICE_ID, {notifier}, initial)")
+            lines.append("    }")
+        lines.append("}")
+
         lines.append("")
         lines.append(f"impl<T: {svc_pascal}Provider> {svc_pascal}Server<T> {{")
         lines.append("    #[allow(dead_code)]")
         lines.append("    pub fn new(provider: Arc<T>) -> Self { Self { provider } }")
+        if svc.fields:
+            lines.append("")
+            lines.append("    /// Notify field changes through `runtime`, and send current values to new subscribers.")
+            lines.append("    #[allow(dead_code)]")
+            lines.append("    pub fn bind_fields(&self, runtime: &Arc<fusion_hawking::runtime::SomeIpRuntime>) -> std::io::Result<()> {")
+            for f in svc.fields:
+                lines.append(f"        self.provider.{f.name}().bind(runtime)?;")
+            lines.append("        Ok(())")
+            lines.append("    }")
         lines.append("}")
 
         lines.append(f"impl<T: {svc_pascal}Provider> fusion_hawking::runtime::RequestHandler for {svc_pascal}Server<T> {{")
         lines.append(f"    fn service_id(&self) -> u16 {{ {svc_pascal}Server::<()>::SERVICE_ID }}")
         lines.append(f"    fn major_version(&self) -> u8 {{ {svc_pascal}Server::<()>::MAJOR_VERSION as u8 }}")
         lines.append(f"    fn minor_version(&self) -> u32 {{ {svc_pascal}Server::<()>::MINOR_VERSION }}")
-        lines.append("    fn handle(&self, header: &SomeIpHeader, _payload: &[u8]) -> Option<Vec<u8>> {")
-        lines.append(f"        if header.service_id != {svc_pascal}Server::<()>::SERVICE_ID {{ return None; }}")
+        # Unknown methods and undecodable requests come back as errors, answered with an ERROR by the runtime
+        lines.append("    fn handle(&self, header: &SomeIpHeader, _payload: &[u8]) -> fusion_hawking::runtime::HandlerResult {")
+        lines.append(f"        if header.service_id != {svc_pascal}Server::<()>::SERVICE_ID {{ return Err(fusion_hawking::codec::ReturnCode::UnknownService); }}")
         lines.append("        match header.method_id {")
         for m in svc.methods:
             method_pascal = self._to_pascal(m.name)
@@ -207,20 +355,43 @@ class RustGenerator(AbstractGenerator):
             lines.append(f"            {svc_pascal}Server::<()>::METHOD_{m.name.upper()} => {{")
             lines.append(f"                let mut cursor = Cursor::new(_payload);")
             req_binding = "_req" if len(m.args) == 0 else "req"
-            lines.append(f"                if let Ok({req_binding}) = {req_name}::deserialize(&mut cursor) {{")
+            lines.append(f"                let {req_binding} = {req_name}::deserialize(&mut cursor).map_err(|_| fusion_hawking::codec::ReturnCode::MalformedMessage)?;")
             call_args = ", ".join([f"req.{a.name}" for a in m.args])
+            if getattr(m, 'fire_and_forget', False):
+                # Called with REQUEST_NO_RETURN: there is no response to build
+                lines.append(f"                self.provider.{m.name}({call_args});")
+                lines.append("                Ok(None)")
+                lines.append("            },")
+                continue
             if m.ret_type.name != "None":
-                lines.append(f"                    let result = self.provider.{m.name}({call_args});")
-                lines.append(f"                    let resp = {res_name} {{ result }};")
+                lines.append(f"                let result = self.provider.{m.name}({call_args});")
+                lines.append(f"                let resp = {res_name} {{ result }};")
             else:
-                lines.append(f"                    self.provider.{m.name}({call_args});")
-                lines.append(f"                    let resp = {res_name} {{}};")
-            lines.append("                    let mut out = Vec::new();")
-            lines.append("                    resp.serialize(&mut out).ok()?;")
-            lines.append("                    Some(out)")
-            lines.append("                } else { None }")
+                lines.append(f"                self.provider.{m.name}({call_args});")
+                lines.append(f"                let resp = {res_name} {{}};")
+            lines.append("                let mut out = Vec::new();")
+            lines.append("                resp.serialize(&mut out).map_err(|_| fusion_hawking::codec::ReturnCode::NotOk)?;")
+            lines.append("                Ok(Some(out))")
             lines.append("            },")
-        lines.append("            _ => None")
+        for f in svc.fields:
+            # Getters and setters reply with the value held after the call
+            value_type = self._rust_type(f.type)
+            if f.get_id:
+                lines.append(f"            {svc_pascal}Server::<()>::FIELD_GET_{f.name.upper()} => {{")
+                lines.append("                let mut out = Vec::new();")
+                lines.append(f"                self.provider.{f.name}().get().serialize(&mut out).map_err(|_| fusion_hawking::codec::ReturnCode::NotOk)?;")
+                lines.append("                Ok(Some(out))")
+                lines.append("            },")
+            if f.set_id:
+                lines.append(f"            {svc_pascal}Server::<()>::FIELD_SET_{f.name.upper()} => {{")
+                lines.append(f"                let value = <{value_type}>::deserialize(&mut Cursor::new(_payload)).map_err(|_| fusion_hawking::codec::ReturnCode::MalformedMessage)?;")
+                lines.append(f"                let field = self.provider.{f.name}();")
+                lines.append("                field.set(value).map_err(|_| fusion_hawking::codec::ReturnCode::NotOk)?;")
+                lines.append("                let mut out = Vec::new();")
+                lines.append("                field.get().serialize(&mut out).map_err(|_| fusion_hawking::codec::ReturnCode::NotOk)?;")
+                lines.append("                Ok(Some(out))")
+                lines.append("            },")
+        lines.append("            _ => Err(fusion_hawking::codec::ReturnCode::UnknownMethod)")
         lines.append("        }")
         lines.append("    }")
         lines.append("}")
@@ -230,13 +401,12 @@ class RustGenerator(AbstractGenerator):
         lines = []
         lines.append(f"#[allow(dead_code)]")
         lines.append(f"pub struct {svc_pascal}Client {{")
-        lines.append("    transport: Arc<dyn SomeIpTransport>,")
-        lines.append("    target: SocketAddr,")
+        lines.append("    context: fusion_hawking::runtime::ClientContext,")
         lines.append("}")
 
         lines.append(f"impl fusion_hawking::runtime::ServiceClient for {svc_pascal}Client {{")
         lines.append(f"    const SERVICE_ID: u16 = {svc.id};")
-        lines.append("    fn new(transport: Arc<dyn SomeIpTransport>, target: SocketAddr) -> Self { Self { transport, target } }")
+        lines.append("    fn new(context: fusion_hawking::runtime::ClientContext) -> Self { Self { context } }")
         lines.append("}")
 
         lines.append(f"#[allow(dead_code)]")
@@ -244,28 +414,86 @@ class RustGenerator(AbstractGenerator):
         lines.append(f"    pub const SERVICE_ID: u16 = {svc.id};")
         lines.append(f"    pub const MAJOR_VERSION: u32 = {svc.major_version};")
         lines.append(f"    pub const MINOR_VERSION: u32 = {svc.minor_version};")
+        for name, group_id in svc.eventgroups.items():
+            lines.append(f"    pub const EVENTGROUP_{name.upper()}: u16 = {group_id};")
+        lines.append("    /// Local address requests are sent from")
+        lines.append("    pub fn local_addr(&self) -> std::io::Result<SocketAddr> { self.context.local_addr() }")
 
         for m in svc.methods:
             method_pascal = self._to_pascal(m.name)
             args_str = ", ".join([f"{a.name}: {self._rust_type(a.type)}" for a in m.args])
             ret_type = f"std::io::Result<{self._rust_type(m.ret_type)}>" if m.ret_type.name != "None" else "std::io::Result<()>"
-
-            lines.append(f"    pub fn {m.name}(&self, {args_str}) -> {ret_type} {{")
             req_name = f"{svc_pascal}{method_pascal}Request"
+            res_name = f"{svc_pascal}{method_pascal}Response"
+            method_const = f"{svc_pascal}Server::<()>::METHOD_{m.name.upper()}"
             field_inits = ", ".join([f"{a.name}" for a in m.args])
+
+            if getattr(m, 'fire_and_forget', False):
+                # No response is expected: the runtime sends REQUEST_NO_RETURN with its Client ID and the next Session ID
+                lines.append(f"    pub fn {m.name}(&self, {args_str}) -> std::io::Result<()> {{")
+                lines.append(f"        let req = {req_name} {{ {field_inits} }};")
+                lines.append("        let mut payload = Vec::new();")
+                lines.append(f"        req.serialize(&mut payload)?;")
+                lines.append(f"        self.context.send({method_const}, &payload)")
+                lines.append("    }")
+                continue
+
+            # Request/response: the runtime assigns the session, matches the response and applies the QoS timeout
+            lines.append(f"    pub async fn {m.name}(&self, {args_str}) -> {ret_type} {{")
             lines.append(f"        let req = {req_name} {{ {field_inits} }};")
             lines.append("        let mut payload = Vec::new();")
             lines.append(f"        req.serialize(&mut payload)?;")
-            lines.append(f"        let header = SomeIpHeader::new(Self::SERVICE_ID, {svc_pascal}Server::<()>::METHOD_{m.name.upper()}, 0x1234, 0x01, 0x01, payload.len() as u32);")
-            lines.append("        let mut msg = header.serialize().to_vec();")
-            lines.append("        msg.extend(payload);")
-            lines.append("        self.transport.send(&msg, Some(self.target))?;")
-
             if m.ret_type.name != "None":
-                lines.append(f"        Err(std::io::Error::new(std::io::ErrorKind::Other, \"Sync RPC not yet implemented in client\"))")
+                lines.append(f"        let response = self.context.request({method_const}, &payload).await?;")
+                lines.append(f"        let res = {res_name}::deserialize(&mut Cursor::new(&response[..]))?;")
+                lines.append("        Ok(res.result)")
             else:
+                lines.append(f"        self.context.request({method_const}, &payload).await?;")
                 lines.append("        Ok(())")
             lines.append("    }")
+
+        for f in svc.fields:
+            value_type = self._rust_type(f.type)
+            if f.get_id:
+                lines.append(f"    pub async fn get_{f.name}(&self) -> std::io::Result<{value_type}> {{")
+                lines.append(f"        let response = self.context.request({svc_pascal}Server::<()>::FIELD_GET_{f.name.upper()}, &[]).await?;")
+                lines.append(f"        <{value_type}>::deserialize(&mut Cursor::new(&response[..]))")
+                lines.append("    }")
+            if f.set_id:
+                # The provider replies with the value it holds afterwards, which may differ from the one sent
+                lines.append(f"    pub async fn set_{f.name}(&self, value: {value_type}) -> std::io::Result<{value_type}> {{")
+                lines.append("        let mut payload = Vec::new();")
+                lines.append("        value.serialize(&mut payload)?;")
+                lines.append(f"        let response = self.context.request({svc_pascal}Server::<()>::FIELD_SET_{f.name.upper()}, &payload).await?;")
+                lines.append(f"        <{value_type}>::deserialize(&mut Cursor::new(&response[..]))")
+                lines.append("    }")
+        for e in svc.events:
+            # Decoded on the runtime's workers if it has any; `on_object_detected`, not `on_on_object_detected`
+            event_name = f"{svc_pascal}{self._to_pascal(e.name)}Event"
+            callback_name = e.name if e.name.startswith("on_") else f"on_{e.name}"
+            lines.append(f"    /// Call `callback` with each {e.name} event received.")
+            lines.append(f"    pub fn {callback_name}(runtime: &fusion_hawking::runtime::SomeIpRuntime, callback: impl Fn({event_name}) + Send + Sync + 'static) {{")
+            lines.append(f"        runtime.on_event(Self::SERVICE_ID, {svc_pascal}Server::<()>::EVENT_{e.name.upper()}, callback);")
+            lines.append("    }")
+        if svc.eventgroups:
+            # Instance and interface come from the required service's configuration
+            lines.append("    /// Subscribe to `eventgroup` (an `EVENTGROUP_` constant) as configured for the required")
+            lines.append("    /// service and call `callback` with each event it carries; a `ttl` of 0 unsubscribes.")
+            lines.append(f"    pub fn subscribe(&self, eventgroup: u16, ttl: u32, callback: impl Fn({svc_pascal}Event) + Send + Sync + 'static) -> std::io::Result<()> {{")
+            lines.append("        let callback = Arc::new(callback);")
+            lines.append("        match eventgroup {")
+            for name in svc.eventgroups:
+                lines.append(f"            Self::EVENTGROUP_{name.upper()} => {{")
+                for e in svc.events:
+                    if name not in e.eventgroups:
+                        continue
+                    lines.append("                let callback = callback.clone();")
+                    lines.append(f"                self.context.on_event({svc_pascal}Server::<()>::EVENT_{e.name.upper()}, move |event| callback({svc_pascal}Event::{self._to_pascal(e.name)}(event)))?;")
+                lines.append("            }")
+            lines.append("            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!(\"unknown eventgroup {}\", eventgroup))),")
+            lines.append("        }")
+            lines.append("        self.context.subscribe(eventgroup, ttl)")
+            lines.append("    }")
         lines.append("}")
         return "\n".join(lines)
 
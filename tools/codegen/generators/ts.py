@@ -11,6 +11,7 @@ import os
 
 class TsGenerator(AbstractGenerator):
     def generate(self, structs: list[Struct], services: list[Service], output_dir: str = "build/generated") -> dict[str, str]:
+        self._require_default_wire(structs, "TypeScript")
         files = {}
 
         # 1. Types file (shared types + serialization helpers)
@@ -80,6 +80,15 @@ def main():
         parser.error("Either --module or positional IDL files are required.")
         return
 
+    # Method/event ID spaces
+    from .validation import validate_service_ids, IdValidationError
+    try:
+        for warning in validate_service_ids(services):
+            print(f"[codegen] Warning: {warning}")
+    except IdValidationError as e:
+        print(f"[codegen] Error: {e}")
+        sys.exit(1)
+
     # ID validation (optional)
     try:
         from tools.id_manager.manager import IDManager
@@ -1,5 +1,5 @@
 from dataclasses import dataclass, field
-from typing import List, Optional, Tuple
+from typing import Dict, List, Optional, Tuple
 
 @dataclass
 class Type:
@@ -32,6 +32,7 @@ class Event:
     name: str
     id: int
     args: List[Field]
+    eventgroups: List[str] = field(default_factory=list)  # names of the service's eventgroups carrying it
 
 @dataclass
 class FieldSpec: # Named FieldSpec to avoid conflict with Field
@@ -51,8 +52,19 @@ class Service:
     fields: List[FieldSpec] = field(default_factory=list)
     major_version: int = 1
     minor_version: int = 0
+    eventgroups: Dict[str, int] = field(default_factory=dict)  # eventgroup IDs by name
+
+@dataclass
+class WireFormat:
+    """Representation of a struct's booleans on the wire (see @wire in the IDL)."""
+    bools: str = "byte"  # "byte": one integer per flag, "bits": consecutive flags share a bit field
+    bool_width: int = 1  # bytes per flag, or per bit field
+
+    def is_default(self) -> bool:
+        return self.bools == "byte" and self.bool_width == 1
 
 @dataclass
 class Struct:
     name: str
     fields: List[Field]
+    wire: WireFormat = field(default_factory=WireFormat)
@@ -13,9 +13,10 @@ import os
 import importlib
 import inspect
 import dataclasses
+import enum
 from typing import get_type_hints, List, Optional
 
-from .models import Service, Struct, Field, Type, Method, Event, FieldSpec
+from .models import Service, Struct, Field, Type, Method, Event, FieldSpec, WireFormat
 
 
 def _resolve_type(annotation) -> Type:
@@ -33,6 +34,10 @@ def _resolve_type(annotation) -> Type:
         if dataclasses.is_dataclass(annotation):
             return Type(annotation.__name__)
 
+        if issubclass(annotation, enum.IntEnum):
+            from fusion_hawking.idl import enum_wire_type
+            return Type(enum_wire_type(annotation))
+
         mapping = {
             int: 'int', float: 'float32', str: 'string', bool: 'bool', bytes: 'bytes'
         }
@@ -52,7 +57,12 @@ def _scan_dataclass(cls) -> Struct:
         hints = get_type_hints(cls)
         annotation = hints.get(f.name, f.type)
         fields.append(Field(f.name, _resolve_type(annotation)))
-    return Struct(cls.__name__, fields)
+    return Struct(cls.__name__, fields, _wire_format(getattr(cls, '_fusion_wire', {})))
+
+
+def _wire_format(options: dict) -> WireFormat:
+    """Struct-level options of an IDL @wire decorator."""
+    return WireFormat(bools=options.get('bools', 'byte'), bool_width=options.get('bool_width', 1))
 
 
 def _scan_service(cls) -> Service:
@@ -77,7 +87,7 @@ def _scan_service(cls) -> Service:
         for arg in einfo['args']:
             t = _type_from_info(arg['type'])
             args.append(Field(arg['name'], t))
-        events.append(Event(ename, einfo['id'], args))
+        events.append(Event(ename, einfo['id'], args, list(einfo.get('eventgroups', []))))
 
     for fname, finfo in cls._fusion_fields.items():
         t = _type_from_info(finfo['type'])
@@ -94,6 +104,7 @@ def _scan_service(cls) -> Service:
         methods, events, fields,
         cls._fusion_major,
         cls._fusion_minor,
+        dict(getattr(cls, '_fusion_eventgroups', {})),
     )
 
 
@@ -171,7 +182,7 @@ def _check_type_info(tinfo, structs, known_names):
         fields = []
         for f in tinfo.get('fields', []):
             fields.append(Field(f['name'], _type_from_info(f['type'])))
-        structs.append(Struct(tinfo['name'], fields))
+        structs.append(Struct(tinfo['name'], fields, _wire_format(tinfo.get('wire', {}))))
         known_names.add(tinfo['name'])
 
     if tinfo.get('inner'):
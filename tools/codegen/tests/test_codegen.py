@@ -9,7 +9,8 @@ sys.path.insert(0, os.getcwd())
 from tools.codegen.generators.rust import RustGenerator
 from tools.codegen.generators.python import PythonGenerator
 from tools.codegen.generators.cpp import CppGenerator
-from tools.codegen.models import Service, Struct, Type, Field, Method
+from tools.codegen.models import Service, Struct, Type, Field, Method, Event, FieldSpec, WireFormat
+from tools.codegen.validation import validate_service_ids, IdValidationError
 
 
 def _make_simple_service():
@@ -46,6 +47,17 @@ def _make_all_primitives():
     return [Struct("AllPrimitives", fields)], []
 
 
+def _make_packed_flags():
+    """Build a struct whose bools are packed into a bit field."""
+    fields = [
+        Field("ready", Type("bool", None)),
+        Field("fault", Type("bool", None)),
+        Field("code", Type("uint16", None)),
+        Field("armed", Type("bool", None)),
+    ]
+    return [Struct("Status", fields, WireFormat(bools="bits", bool_width=1))], []
+
+
 def _make_rpc_service():
     """Build a service with sync RPC and fire-and-forget methods."""
     int_type = Type("int", None)
@@ -53,6 +65,7 @@ def _make_rpc_service():
     none_type = Type("None", None)
     add_method = Method("add", 1, [Field("a", int_type), Field("b", int_type)], int_type)
     faf_method = Method("fire_and_forget", 2, [Field("msg", str_type)], none_type)
+    faf_method.fire_and_forget = True
     svc = Service(name="MathService", id=0x5678, methods=[add_method, faf_method], events=[], fields=[], major_version=1, minor_version=0)
     return [], [svc]
 
@@ -84,11 +97,13 @@ class TestGenerators(unittest.TestCase):
         # Check types file
         types_content = self.get_file(output, "rust/types.rs")
         self.assertIn("pub struct MyStruct", types_content)
+        self.assertIn('#[cfg_attr(feature = "serde-types", derive(serde::Serialize, serde::Deserialize))]', types_content)
         # Check service file
         svc_content = self.get_file(output, "rust/my_service.rs")
         self.assertIn("pub trait MyServiceProvider", svc_content)
         self.assertIn("pub struct MyServiceServer", svc_content)
         self.assertIn("pub struct MyServiceClient", svc_content)
+        self.assertIn("pub fn local_addr(&self) -> std::io::Result<SocketAddr>", svc_content)
 
     def test_rust_recursive_type(self):
         structs, services = _make_recursive_types()
@@ -97,6 +112,60 @@ class TestGenerators(unittest.TestCase):
         self.assertIn("pub struct PathCollection", types_content)
         self.assertIn("Vec<Vec<Point>>", types_content)
 
+    def test_rust_schema_impls(self):
+        structs, services = _make_recursive_types()
+        output = self.rust_gen.generate(structs, services)
+        types_content = self.get_file(output, "rust/types.rs")
+        self.assertIn("impl SomeIpSchema for PathCollection", types_content)
+        self.assertIn('fields: "paths:Vec<Vec<Point>>", nested: vec![<Point as SomeIpSchema>::schema()]', types_content)
+
+        structs, services = _make_rpc_service()
+        output = self.rust_gen.generate(structs, services)
+        svc_content = self.get_file(output, "rust/math_service.rs")
+        self.assertIn("pub fn register_schemas", svc_content)
+        self.assertIn("Self::METHOD_ADD, true, MathServiceAddResponse::schema()", svc_content)
+
+    def test_rust_fire_event_helpers(self):
+        int_type = Type("int", None)
+        svc = Service(name="Radar", id=0x7001, methods=[], events=[Event("on_object", 0x8001, [Field("distance", int_type)])], fields=[], major_version=1, minor_version=0)
+        svc_content = self.get_file(self.rust_gen.generate([], [svc]), "rust/radar.rs")
+        self.assertIn("pub fn fire_on_object(runtime: &fusion_hawking::runtime::SomeIpRuntime, distance: i32) -> std::io::Result<usize>", svc_content)
+        self.assertIn("let event = RadarOnObjectEvent { distance };", svc_content)
+        self.assertIn("Ok(runtime.fire_event(Self::SERVICE_ID, Self::EVENT_ON_OBJECT, &payload))", svc_content)
+        # Clients register typed callbacks; the event name already reads as a callback
+        self.assertIn("pub fn on_object(runtime: &fusion_hawking::runtime::SomeIpRuntime, callback: impl Fn(RadarOnObjectEvent) + Send + Sync + 'static)", svc_content)
+        self.assertIn("runtime.on_event(Self::SERVICE_ID, RadarServer::<()>::EVENT_ON_OBJECT, callback);", svc_content)
+        self.assertNotIn("pub fn subscribe(", svc_content)
+
+    def test_rust_eventgroup_subscribe(self):
+        int_type = Type("int", None)
+        svc = Service(name="Radar", id=0x7001, events=[Event("on_object", 0x8001, [Field("distance", int_type)], ["objects"])],
+                      eventgroups={"objects": 1, "status": 2})
+        svc_content = self.get_file(self.rust_gen.generate([], [svc]), "rust/radar.rs")
+        self.assertIn("pub enum RadarEvent {\n    OnObject(RadarOnObjectEvent),\n}", svc_content)
+        self.assertEqual(svc_content.count("pub const EVENTGROUP_OBJECTS: u16 = 1;"), 2)
+        self.assertIn("pub fn subscribe(&self, eventgroup: u16, ttl: u32, callback: impl Fn(RadarEvent) + Send + Sync + 'static) -> std::io::Result<()> {", svc_content)
+        self.assertIn("self.context.on_event(RadarServer::<()>::EVENT_ON_OBJECT, move |event| callback(RadarEvent::OnObject(event)))?;", svc_content)
+        self.assertIn("Self::EVENTGROUP_STATUS => {\n            }", svc_content)
+        self.assertIn("self.context.subscribe(eventgroup, ttl)", svc_content)
+
+    def test_rust_field_handles(self):
+        svc = Service(name="Climate", id=0x6001, methods=[], events=[],
+                      fields=[FieldSpec("target", 1, Type("int"), get_id=0x10, set_id=0x11, notifier_id=0x8012),
+                              FieldSpec("outside", 2, Type("float"), get_id=0x20)],
+                      major_version=1, minor_version=0)
+        svc_content = self.get_file(self.rust_gen.generate([], [svc]), "rust/climate.rs")
+        self.assertIn("fn target(&self) -> &fusion_hawking::runtime::FieldHandle<i32>;", svc_content)
+        self.assertIn("fusion_hawking::runtime::FieldHandle::new(Self::SERVICE_ID, Some(Self::EVENT_TARGET_NOTIFY), initial)", svc_content)
+        self.assertIn("fusion_hawking::runtime::FieldHandle::new(Self::SERVICE_ID, None, initial)", svc_content)
+        self.assertIn("self.provider.target().bind(runtime)?;", svc_content)
+        self.assertIn("ClimateServer::<()>::FIELD_SET_TARGET => {", svc_content)
+        self.assertIn("pub async fn set_target(&self, value: i32) -> std::io::Result<i32>", svc_content)
+        self.assertIn("pub async fn get_outside(&self) -> std::io::Result<f32>", svc_content)
+        # Read-only fields have no setter
+        self.assertNotIn("FIELD_SET_OUTSIDE", svc_content)
+        self.assertNotIn("set_outside", svc_content)
+
     def test_rust_primitives(self):
         structs, services = _make_all_primitives()
         output = self.rust_gen.generate(structs, services)
@@ -106,12 +175,74 @@ class TestGenerators(unittest.TestCase):
         self.assertIn("bool", types_content)  # bool
         self.assertIn("String", types_content)  # str
 
+    def test_rust_wire_bit_field(self):
+        structs, services = _make_packed_flags()
+        output = self.rust_gen.generate(structs, services)
+        types_content = self.get_file(output, "rust/types.rs")
+        # Consecutive flags share one bit field; a flag after another field starts a new one
+        self.assertIn("repr::write_bits(&[self.ready, self.fault], 1, writer)?;", types_content)
+        self.assertIn("repr::write_bits(&[self.armed], 1, writer)?;", types_content)
+        self.assertIn("let bits_0 = fusion_hawking::codec::repr::read_bits(2, 1, reader)?;", types_content)
+        self.assertIn('fields: "ready:bool[bits8],fault:bool[bits8],code:u16,armed:bool[bits8]"', types_content)
+
+        structs[0].wire = WireFormat(bools="byte", bool_width=4)
+        types_content = self.get_file(self.rust_gen.generate(structs, services), "rust/types.rs")
+        self.assertIn("repr::write_bool(self.ready, 4, writer)?;", types_content)
+        self.assertIn("let armed = fusion_hawking::codec::repr::read_bool(4, reader)?;", types_content)
+
+    def test_wire_unsupported_by_other_generators(self):
+        structs, services = _make_packed_flags()
+        with self.assertRaises(ValueError):
+            self.py_gen.generate(structs, services)
+        with self.assertRaises(ValueError):
+            self.cpp_gen.generate(structs, services)
+
+    def test_idl_wire_options(self):
+        sys.path.insert(0, os.path.join(os.getcwd(), "src", "python"))
+        from dataclasses import dataclass
+        from enum import IntEnum
+        from fusion_hawking.idl import wire, resolve_type_info
+        from tools.codegen.scanner import _scan_dataclass
+
+        @wire(width=2)
+        class Gear(IntEnum):
+            PARK = 0
+            DRIVE = 0x1FF
+
+        @wire(bools="bits")
+        @dataclass
+        class Status:
+            ready: bool
+            gear: Gear
+
+        self.assertEqual(resolve_type_info(Gear)['name'], "uint16")
+        struct = _scan_dataclass(Status)
+        self.assertEqual(struct.wire, WireFormat(bools="bits", bool_width=1))
+        self.assertEqual(struct.fields[1].type.name, "uint16")
+
+        with self.assertRaises(ValueError):
+            class Wide(IntEnum):
+                BIG = 0x100
+            resolve_type_info(Wide)
+        with self.assertRaises(ValueError):
+            wire(bool_width=3)
+
     def test_rust_rpc_methods(self):
         structs, services = _make_rpc_service()
         output = self.rust_gen.generate(structs, services)
         svc_content = self.get_file(output, "rust/math_service.rs")
-        self.assertIn("pub fn add", svc_content)
+        self.assertIn("pub async fn add", svc_content)
+        self.assertIn("MathServiceAddResponse::deserialize", svc_content)
+        self.assertIn("fn new(context: fusion_hawking::runtime::ClientContext) -> Self { Self { context } }", svc_content)
+        self.assertNotIn("not_attached", svc_content)
         self.assertIn("pub fn fire_and_forget", svc_content)
+        self.assertIn("self.context.send(MathServiceServer::<()>::METHOD_FIRE_AND_FORGET, &payload)", svc_content)
+        self.assertIn("self.provider.fire_and_forget(req.msg);\n                Ok(None)", svc_content)
+        self.assertNotIn("0x1234", svc_content)
+        self.assertIn("fn handle(&self, header: &SomeIpHeader, _payload: &[u8]) -> fusion_hawking::runtime::HandlerResult", svc_content)
+        self.assertIn("MathServiceAddRequest::deserialize(&mut cursor).map_err(|_| fusion_hawking::codec::ReturnCode::MalformedMessage)?", svc_content)
+        self.assertIn("_ => Err(fusion_hawking::codec::ReturnCode::UnknownMethod)", svc_content)
+        self.assertNotIn("fn validate", svc_content)
 
     # --- Python Generator ---
 
@@ -136,7 +267,9 @@ class TestGenerators(unittest.TestCase):
         output = self.py_gen.generate(structs, services)
         runtime = self.get_file(output, "python/runtime.py")
         self.assertIn("wait_for_response=True", runtime)
-        self.assertIn("wait_for_response=False", runtime)
+        # One-way methods are sent as REQUEST_NO_RETURN and not answered by the stub
+        self.assertIn("self.runtime.send_request(self.SERVICE_ID, 2, req.serialize(), target, msg_type=0x01)", runtime)
+        self.assertIn("            self.fire_and_forget(req.msg)\n            return None", runtime)
 
     # --- C++ Generator ---
 
@@ -217,5 +350,94 @@ except ImportError:
     pass  # AST parser removed — that's fine
 
 
+class TestJsonDefinitions(unittest.TestCase):
+    """JSON service definitions read by fusion-hawking-gen mirror their Python IDL."""
+
+    @staticmethod
+    def _type(t):
+        return f"List[{TestJsonDefinitions._type(t.inner)}]" if t.inner else t.name
+
+    @staticmethod
+    def _id(value):
+        return None if value is None else int(value, 0) if isinstance(value, str) else value
+
+    def _members(self, members):
+        return [(m.name, self._type(m.type)) for m in members]
+
+    def _json_members(self, members):
+        return [(m["name"], m["type"]) for m in members]
+
+    def test_automotive_pubsub(self):
+        import json
+        from tools.codegen.scanner import scan
+        structs, services = scan("examples.automotive_pubsub.idl", project_root=os.getcwd())
+        with open(os.path.join("examples", "automotive_pubsub", "idl", "automotive_pubsub.json")) as f:
+            definition = json.load(f)
+
+        self.assertEqual({s.name: self._members(s.fields) for s in structs},
+                         {s["name"]: self._json_members(s["fields"]) for s in definition["structs"]})
+        expected = {}
+        for svc in services:
+            expected[svc.name] = (svc.id, svc.major_version, svc.minor_version,
+                                  {m.name: (m.id, self._members(m.args), None if m.ret_type.name == "None" else self._type(m.ret_type)) for m in svc.methods},
+                                  {e.name: (e.id, self._members(e.args), e.eventgroups) for e in svc.events},
+                                  {f.name: (self._type(f.type), f.get_id, f.set_id, f.notifier_id) for f in svc.fields},
+                                  svc.eventgroups)
+        actual = {}
+        for svc in definition["services"]:
+            actual[svc["name"]] = (self._id(svc["id"]), svc.get("major_version", 1), svc.get("minor_version", 0),
+                                   {m["name"]: (self._id(m["id"]), self._json_members(m.get("args", [])), m.get("returns")) for m in svc.get("methods", [])},
+                                   {e["name"]: (self._id(e["id"]), self._json_members(e.get("args", [])), e.get("eventgroups", [])) for e in svc.get("events", [])},
+                                   {f["name"]: (f["type"], self._id(f.get("get_id")), self._id(f.get("set_id")), self._id(f.get("notifier_id"))) for f in svc.get("fields", [])},
+                                   {g["name"]: self._id(g["id"]) for g in svc.get("eventgroups", [])})
+        self.assertEqual(actual, expected)
+
+
+class TestIdValidation(unittest.TestCase):
+    """Method (0x0000-0x7fff) vs event (0x8000-0xffff) ID spaces."""
+
+    def _service(self, methods=(), events=(), fields=()):
+        return Service(name="Svc", id=0x1000, methods=list(methods), events=list(events), fields=list(fields))
+
+    def test_valid_ids_pass(self):
+        svc = self._service([Method("m", 1, [], Type("None"))], [Event("e", 0x8001, [])],
+                            [FieldSpec("f", 1, Type("int"), get_id=0x10, set_id=0x11, notifier_id=0x8012)])
+        self.assertEqual(validate_service_ids([svc]), [])
+
+    def test_event_id_normalized(self):
+        svc = self._service(events=[Event("e", 0x0001, [])],
+                            fields=[FieldSpec("f", 1, Type("int"), notifier_id=0x12)])
+        warnings = validate_service_ids([svc])
+        self.assertEqual(len(warnings), 2)
+        self.assertEqual(svc.events[0].id, 0x8001)
+        self.assertEqual(svc.fields[0].notifier_id, 0x8012)
+
+    def test_method_in_event_range_rejected(self):
+        svc = self._service([Method("m", 0x8001, [], Type("None"))])
+        with self.assertRaises(IdValidationError):
+            validate_service_ids([svc])
+
+    def test_collision_rejected(self):
+        svc = self._service([Method("m", 0x10, [], Type("None"))],
+                            fields=[FieldSpec("f", 1, Type("int"), get_id=0x10)])
+        with self.assertRaisesRegex(IdValidationError, "collides with method 'm'"):
+            validate_service_ids([svc])
+
+        # Normalized events collide too
+        svc = self._service(events=[Event("a", 0x8001, []), Event("b", 0x0001, [])])
+        with self.assertRaises(IdValidationError):
+            validate_service_ids([svc])
+
+    def test_eventgroups_checked(self):
+        svc = self._service(events=[Event("e", 0x8001, [], ["missing"])])
+        with self.assertRaisesRegex(IdValidationError, "unknown eventgroup 'missing'"):
+            validate_service_ids([svc])
+
+        svc = self._service()
+        svc.eventgroups = {"a": 1, "b": 1}
+        with self.assertRaisesRegex(IdValidationError, "uses ID 1 of eventgroup 'a'"):
+            validate_service_ids([svc])
+
+
 if __name__ == '__main__':
     unittest.main()
@@ -0,0 +1,73 @@
+"""
+ID space validation for parsed services.
+
+Per [PRS_SOMEIP_00245] the highest bit of the Method ID distinguishes methods
+(0x0000-0x7FFF) from events (0x8000-0xFFFF). Field getters/setters are methods,
+field notifiers are events.
+"""
+from .models import Service
+
+EVENT_ID_FLAG = 0x8000
+
+
+class IdValidationError(ValueError):
+    pass
+
+
+def validate_service_ids(services: list[Service]) -> list[str]:
+    """
+    Check and normalize method/event IDs in place.
+
+    Event and notifier IDs given without the high bit are normalized by setting
+    it (with a warning). Method/getter/setter IDs with the high bit set, and
+    duplicate IDs within a service, raise IdValidationError, as do eventgroups
+    sharing an ID and events naming an eventgroup the service does not declare.
+    Returns the list of warnings.
+    """
+    warnings = []
+    for svc in services:
+        used = {}  # id -> description
+
+        def claim(msg_id, what):
+            if msg_id in used:
+                raise IdValidationError(
+                    f"{svc.name}: ID 0x{msg_id:04x} of {what} collides with {used[msg_id]}")
+            used[msg_id] = what
+
+        def check_method(msg_id, what):
+            if msg_id & EVENT_ID_FLAG:
+                raise IdValidationError(
+                    f"{svc.name}: {what} uses ID 0x{msg_id:04x} in the event range (methods must be 0x0000-0x7fff)")
+            claim(msg_id, what)
+
+        def normalize_event(msg_id, what):
+            if not msg_id & EVENT_ID_FLAG:
+                fixed = msg_id | EVENT_ID_FLAG
+                warnings.append(f"{svc.name}: {what} ID 0x{msg_id:04x} normalized to 0x{fixed:04x} (events must be 0x8000-0xffff)")
+                msg_id = fixed
+            claim(msg_id, what)
+            return msg_id
+
+        groups = {}  # eventgroup id -> name
+        for name, group_id in svc.eventgroups.items():
+            if group_id in groups:
+                raise IdValidationError(
+                    f"{svc.name}: eventgroup '{name}' uses ID {group_id} of eventgroup '{groups[group_id]}'")
+            groups[group_id] = name
+
+        for m in svc.methods:
+            check_method(m.id, f"method '{m.name}'")
+        for f in svc.fields:
+            if f.get_id is not None:
+                check_method(f.get_id, f"getter of field '{f.name}'")
+            if f.set_id is not None:
+                check_method(f.set_id, f"setter of field '{f.name}'")
+        for e in svc.events:
+            e.id = normalize_event(e.id, f"event '{e.name}'")
+            for group in e.eventgroups:
+                if group not in svc.eventgroups:
+                    raise IdValidationError(f"{svc.name}: event '{e.name}' names unknown eventgroup '{group}'")
+        for f in svc.fields:
+            if f.notifier_id is not None:
+                f.notifier_id = normalize_event(f.notifier_id, f"notifier of field '{f.name}'")
+    return warnings
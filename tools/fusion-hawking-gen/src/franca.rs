@@ -0,0 +1,861 @@
+//! # Franca IDL Front-End
+//!
+//! Reads Franca interfaces (`.fidl`) with their CommonAPI SOME/IP deployment
+//! (`.fdepl`) into a [`Definition`], so services specified for CommonAPI/vsomeip
+//! generate fusion-hawking providers and clients.
+//!
+//! Supported in `.fidl`: `typeCollection`s and `interface`s with `version`,
+//! `struct`, `array`, `typedef`, `enumeration` (sent as `uint8`), `attribute`
+//! (`readonly`, `noSubscriptions`), `method` (`fireAndForget`) and `broadcast`.
+//! Methods with several `out` arguments return a struct `<Interface><Method>Out`
+//! holding them in order, which has the same wire format. `union`, `map`,
+//! polymorphic structs and `extends` are refused. Names of members, arguments,
+//! methods, broadcasts and attributes become snake_case. Strings use the
+//! CommonAPI default encoding, UTF-8 with byte order mark and terminator.
+//!
+//! The `.fdepl` provides the IDs (`SomeIpServiceID`, `SomeIpMethodID`,
+//! `SomeIpEventID`, `SomeIpGetterID`, `SomeIpSetterID`, `SomeIpNotifierID`), the
+//! eventgroups (`SomeIpEventGroups`, `SomeIpNotifierEventGroups`), and per
+//! provided instance `SomeIpInstanceID`, `SomeIpUnicastAddress` and the
+//! `SomeIp(Un)ReliableUnicastPort`s, which [`Franca::config`] maps to a runtime
+//! configuration fragment.
+
+use crate::model::{Definition, EventDef, EventgroupDef, FieldDef, Member, MethodDef, ServiceDef, StringRepr, StructDef, Type, WireFormat};
+use crate::rust::{pascal, snake};
+use crate::Error;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Services and types of a Franca model, with the deployment of its instances
+#[derive(Debug, Clone, Default)]
+pub struct Franca {
+    pub definition: Definition,
+    /// Event IDs of each eventgroup, by service name
+    pub eventgroups: HashMap<String, BTreeMap<u16, Vec<u16>>>,
+    pub instances: Vec<Instance>,
+}
+
+/// A provided service instance from the deployment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    pub service: String,
+    pub instance_id: u16,
+    pub address: Option<String>,
+    pub reliable_port: Option<u16>,
+    pub unreliable_port: Option<u16>,
+}
+
+/// Parse an interface model and its optional deployment. Interfaces need a
+/// deployment with their IDs; type collections do not.
+pub fn parse(fidl: &str, fdepl: Option<&str>) -> Result<Franca, Error> {
+    let model = Model::parse(fidl).map_err(|e| Error::Invalid(format!("fidl {}", e)))?;
+    let deployment = match fdepl {
+        Some(text) => Deployment::parse(text).map_err(|e| Error::Invalid(format!("fdepl {}", e)))?,
+        None => Deployment::default(),
+    };
+    let franca = model.resolve(&deployment).map_err(Error::Invalid)?;
+    franca.definition.validate().map_err(Error::Invalid)?;
+    Ok(franca)
+}
+
+impl Franca {
+    /// Runtime configuration fragment with an endpoint and a `providing` entry per
+    /// deployed instance, to be merged into an instance of the config file.
+    /// Instances with both ports are offered on the unreliable one.
+    pub fn config(&self) -> Value {
+        let mut endpoints = Map::new();
+        let mut providing = Map::new();
+        for inst in &self.instances {
+            let Some(svc) = self.definition.services.iter().find(|s| s.name == inst.service) else { continue };
+            let alias = snake(&svc.name);
+            let mut offer = json!({
+                "service_id": svc.id,
+                "instance_id": inst.instance_id,
+                "major_version": svc.major_version,
+                "minor_version": svc.minor_version,
+            });
+            if let Some(address) = &inst.address {
+                let version = if address.contains(':') { 6 } else { 4 };
+                let ports = [("udp", inst.unreliable_port), ("tcp", inst.reliable_port)];
+                for (protocol, port) in ports.iter().filter_map(|(p, port)| port.map(|port| (p, port))) {
+                    let name = format!("{}_{}", alias, protocol);
+                    endpoints.insert(name.clone(), json!({ "ip": address, "port": port, "version": version, "protocol": protocol }));
+                    if offer.get("endpoint").is_none() {
+                        offer["endpoint"] = json!(name);
+                    }
+                }
+            }
+            if let Some(groups) = self.eventgroups.get(&svc.name) {
+                let groups: Map<String, Value> = groups.iter()
+                    .map(|(id, events)| (format!("eventgroup_{}", id), json!({ "eventgroup_id": id, "events": events })))
+                    .collect();
+                offer["eventgroups"] = Value::Object(groups);
+            }
+            providing.insert(alias, offer);
+        }
+        json!({ "endpoints": endpoints, "providing": providing })
+    }
+}
+
+// --- Lexer ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Keyword or (qualified) name
+    Word(String),
+    Number(i64),
+    Text(String),
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line) = (0, 1);
+    let starts = |i: usize, s: &str| s.chars().enumerate().all(|(k, c)| chars.get(i + k) == Some(&c));
+    // Skip to just after `end`, counting lines
+    let skip_to = |i: &mut usize, line: &mut usize, end: &str| -> Result<(), String> {
+        let start = *line;
+        while *i < chars.len() && !starts(*i, end) {
+            if chars[*i] == '\n' { *line += 1; }
+            *i += 1;
+        }
+        if *i >= chars.len() { return Err(format!("line {}: unterminated '{}'", start, end)); }
+        *i += end.len();
+        Ok(())
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if starts(i, "//") {
+            while i < chars.len() && chars[i] != '\n' { i += 1; }
+        } else if starts(i, "/*") {
+            skip_to(&mut i, &mut line, "*/")?;
+        } else if starts(i, "<**") {
+            // Structured comment (annotations)
+            skip_to(&mut i, &mut line, "**>")?;
+        } else if c == '"' {
+            let start = i + 1;
+            i += 1;
+            skip_to(&mut i, &mut line, "\"")?;
+            tokens.push((Token::Text(chars[start..i - 1].iter().collect()), line));
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() { i += 1; }
+            let text: String = chars[start..i].iter().collect();
+            let (negative, digits) = match text.strip_prefix('-') { Some(d) => (true, d), None => (false, text.as_str()) };
+            let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => digits.parse(),
+            }.map_err(|_| format!("line {}: invalid number '{}'", line, text))?;
+            tokens.push((Token::Number(if negative { -value } else { value }), line));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '.' | '*')) { i += 1; }
+            tokens.push((Token::Word(chars[start..i].iter().collect()), line));
+        } else if "{}[]=,:()<>".contains(c) {
+            tokens.push((Token::Punct(c), line));
+            i += 1;
+        } else {
+            return Err(format!("line {}: unexpected '{}'", line, c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Result<Self, String> {
+        Ok(Parser { tokens: tokenize(text)?, pos: 0 })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map_or(1, |(_, l)| *l)
+    }
+
+    fn error<T>(&self, msg: impl std::fmt::Display) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line(), msg))
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        match self.tokens.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn at_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn at_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let at = self.at_punct(c);
+        if at { self.pos += 1; }
+        at
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let at = self.at_word(word);
+        if at { self.pos += 1; }
+        at
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), String> {
+        if self.eat_punct(c) { Ok(()) } else { self.error(format!("expected '{}'", c)) }
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Word(w)) => {
+                let w = w.clone();
+                self.pos += 1;
+                Ok(w)
+            }
+            _ => self.error("expected a name"),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, String> {
+        match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => self.error("expected a number"),
+        }
+    }
+
+    /// Skip a `{ .. }` block, nested blocks included
+    fn skip_block(&mut self) -> Result<(), String> {
+        self.expect_punct('{')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Punct('{') => depth += 1,
+                Token::Punct('}') => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Last segment of a qualified name
+fn local(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// CommonAPI SOME/IP sends strings as UTF-8 with byte order mark and terminator
+fn commonapi_wire() -> WireFormat {
+    WireFormat { strings: StringRepr::Utf8, ..WireFormat::default() }
+}
+
+// --- Interface model (.fidl) ---
+
+#[derive(Debug, Clone)]
+struct TypeRef {
+    name: String,
+    list: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Arg {
+    name: String,
+    ty: TypeRef,
+}
+
+#[derive(Debug, Default)]
+struct Interface {
+    name: String,
+    major: u32,
+    minor: u32,
+    methods: Vec<(String, bool, Vec<Arg>, Vec<Arg>)>,
+    broadcasts: Vec<(String, Vec<Arg>)>,
+    /// name, type, readonly, noSubscriptions
+    attributes: Vec<(String, TypeRef, bool, bool)>,
+}
+
+#[derive(Debug, Default)]
+struct Model {
+    structs: Vec<(String, Vec<Arg>)>,
+    /// `array` and `typedef` names
+    aliases: HashMap<String, TypeRef>,
+    enumerations: HashSet<String>,
+    interfaces: Vec<Interface>,
+}
+
+impl Model {
+    fn parse(text: &str) -> Result<Model, String> {
+        let mut p = Parser::new(text)?;
+        let mut model = Model::default();
+        while let Some(token) = p.peek().cloned() {
+            match token {
+                Token::Word(w) if w == "package" => {
+                    p.pos += 1;
+                    p.word()?;
+                }
+                Token::Word(w) if w == "import" => {
+                    // Imported models are not followed; their types must be repeated here
+                    while !matches!(p.next()?, Token::Text(_)) {}
+                }
+                Token::Word(w) if w == "typeCollection" => {
+                    p.pos += 1;
+                    if !p.at_punct('{') { p.word()?; }
+                    p.expect_punct('{')?;
+                    while !p.eat_punct('}') {
+                        if p.at_word("version") {
+                            p.pos += 1;
+                            p.skip_block()?;
+                        } else {
+                            model.type_definition(&mut p)?;
+                        }
+                    }
+                }
+                Token::Word(w) if w == "interface" => {
+                    p.pos += 1;
+                    let interface = model.interface(&mut p)?;
+                    model.interfaces.push(interface);
+                }
+                _ => return p.error("expected package, import, typeCollection or interface"),
+            }
+        }
+        Ok(model)
+    }
+
+    fn type_ref(p: &mut Parser) -> Result<TypeRef, String> {
+        let name = local(&p.word()?).to_string();
+        let list = p.eat_punct('[');
+        if list { p.expect_punct(']')?; }
+        Ok(TypeRef { name, list })
+    }
+
+    /// `Type name` pairs up to the closing brace
+    fn args(p: &mut Parser) -> Result<Vec<Arg>, String> {
+        p.expect_punct('{')?;
+        let mut args = Vec::new();
+        while !p.eat_punct('}') {
+            let ty = Model::type_ref(p)?;
+            args.push(Arg { name: p.word()?, ty });
+        }
+        Ok(args)
+    }
+
+    fn type_definition(&mut self, p: &mut Parser) -> Result<(), String> {
+        let kind = p.word()?;
+        if kind == "public" {
+            return self.type_definition(p);
+        }
+        let name = p.word()?;
+        match kind.as_str() {
+            "struct" => {
+                if p.at_word("polymorphic") || p.at_word("extends") {
+                    return p.error(format!("struct '{}': polymorphic structs and extends are not supported", name));
+                }
+                let members = Model::args(p)?;
+                self.structs.push((name, members));
+            }
+            "array" => {
+                if !p.eat_word("of") { return p.error("expected 'of'"); }
+                let inner = Model::type_ref(p)?;
+                if inner.list {
+                    return p.error(format!("array '{}': nested inline arrays are not supported", name));
+                }
+                self.aliases.insert(name, TypeRef { name: inner.name, list: true });
+            }
+            "typedef" => {
+                if !p.eat_word("is") { return p.error("expected 'is'"); }
+                let ty = Model::type_ref(p)?;
+                self.aliases.insert(name, ty);
+            }
+            "enumeration" => {
+                if p.at_word("extends") {
+                    return p.error(format!("enumeration '{}': extends is not supported", name));
+                }
+                p.skip_block()?;
+                self.enumerations.insert(name);
+            }
+            other => return p.error(format!("'{}' is not supported", other)),
+        }
+        Ok(())
+    }
+
+    fn interface(&mut self, p: &mut Parser) -> Result<Interface, String> {
+        let mut interface = Interface { name: local(&p.word()?).to_string(), major: 1, ..Default::default() };
+        if p.at_word("extends") || p.at_word("manages") {
+            return p.error(format!("interface '{}': extends and manages are not supported", interface.name));
+        }
+        p.expect_punct('{')?;
+        while !p.eat_punct('}') {
+            let Some(Token::Word(keyword)) = p.peek().cloned() else { return p.error("expected an interface element") };
+            match keyword.as_str() {
+                "version" => {
+                    p.pos += 1;
+                    p.expect_punct('{')?;
+                    while !p.eat_punct('}') {
+                        match p.word()?.as_str() {
+                            "major" => interface.major = p.number()? as u32,
+                            "minor" => interface.minor = p.number()? as u32,
+                            other => return p.error(format!("unexpected '{}' in version", other)),
+                        }
+                    }
+                }
+                "attribute" => {
+                    p.pos += 1;
+                    let ty = Model::type_ref(p)?;
+                    let name = p.word()?;
+                    let (mut readonly, mut no_subscriptions) = (false, false);
+                    loop {
+                        if p.eat_word("readonly") {
+                            readonly = true;
+                        } else if p.eat_word("noSubscriptions") {
+                            no_subscriptions = true;
+                        } else if !p.eat_word("noRead") {
+                            break;
+                        }
+                    }
+                    interface.attributes.push((name, ty, readonly, no_subscriptions));
+                }
+                "method" => {
+                    p.pos += 1;
+                    let name = p.word()?;
+                    let fire_and_forget = p.eat_word("fireAndForget");
+                    let (mut ins, mut outs) = (Vec::new(), Vec::new());
+                    p.expect_punct('{')?;
+                    while !p.eat_punct('}') {
+                        match p.word()?.as_str() {
+                            "in" => ins = Model::args(p)?,
+                            "out" => outs = Model::args(p)?,
+                            // Application errors are reported through the return code of the ERROR message
+                            "error" => if p.at_punct('{') { p.skip_block()? } else { p.word().map(drop)? },
+                            other => return p.error(format!("unexpected '{}' in method '{}'", other, name)),
+                        }
+                    }
+                    interface.methods.push((name, fire_and_forget, ins, outs));
+                }
+                "broadcast" => {
+                    p.pos += 1;
+                    let name = p.word()?;
+                    p.eat_word("selective");
+                    let mut outs = Vec::new();
+                    p.expect_punct('{')?;
+                    while !p.eat_punct('}') {
+                        match p.word()?.as_str() {
+                            "out" => outs = Model::args(p)?,
+                            other => return p.error(format!("unexpected '{}' in broadcast '{}'", other, name)),
+                        }
+                    }
+                    interface.broadcasts.push((name, outs));
+                }
+                "contract" => return p.error("contracts are not supported"),
+                _ => self.type_definition(p)?,
+            }
+        }
+        Ok(interface)
+    }
+
+    /// IDL type of a Franca type
+    fn resolve_type(&self, ty: &TypeRef) -> Result<Type, String> {
+        let base = match ty.name.as_str() {
+            "Int8" => Type::Named("int8".into()),
+            "Int16" => Type::Named("int16".into()),
+            "Int32" => Type::Named("int32".into()),
+            "Int64" => Type::Named("int64".into()),
+            "UInt8" => Type::Named("uint8".into()),
+            "UInt16" => Type::Named("uint16".into()),
+            "UInt32" => Type::Named("uint32".into()),
+            "UInt64" => Type::Named("uint64".into()),
+            "Boolean" => Type::Named("bool".into()),
+            "Float" => Type::Named("float32".into()),
+            "Double" => Type::Named("float64".into()),
+            "String" => Type::Named("string".into()),
+            "ByteBuffer" => Type::List(Box::new(Type::Named("uint8".into()))),
+            name if self.enumerations.contains(name) => Type::Named("uint8".into()),
+            name => match self.aliases.get(name) {
+                Some(alias) if alias.name == name => return Err(format!("type '{}' refers to itself", name)),
+                Some(alias) => self.resolve_type(alias)?,
+                None => Type::Named(name.to_string()),
+            },
+        };
+        Ok(if ty.list { Type::List(Box::new(base)) } else { base })
+    }
+
+    fn members(&self, args: &[Arg]) -> Result<Vec<Member>, String> {
+        args.iter().map(|a| Ok(Member::new(snake(&a.name), self.resolve_type(&a.ty)?))).collect()
+    }
+
+    fn resolve(&self, deployment: &Deployment) -> Result<Franca, String> {
+        let mut franca = Franca::default();
+        for (name, members) in &self.structs {
+            franca.definition.structs.push(StructDef { name: name.clone(), fields: self.members(members)?, wire: commonapi_wire() });
+        }
+        for interface in &self.interfaces {
+            let empty = Block::default();
+            let depl = deployment.interfaces.get(&interface.name).unwrap_or(&empty);
+            let what = format!("interface '{}'", interface.name);
+            let mut svc = ServiceDef {
+                name: interface.name.clone(),
+                id: depl.id("SomeIpServiceID", &what)?,
+                major_version: interface.major,
+                minor_version: interface.minor,
+                methods: Vec::new(),
+                events: Vec::new(),
+                fields: Vec::new(),
+                eventgroups: Vec::new(),
+                wire: commonapi_wire(),
+            };
+            let mut eventgroups: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+            for (name, fire_and_forget, ins, outs) in &interface.methods {
+                let block = depl.child("method", name).unwrap_or(&empty);
+                let id = block.id("SomeIpMethodID", &format!("{}: method '{}'", what, name))?;
+                let returns = match outs.as_slice() {
+                    [] => None,
+                    [out] => Some(self.resolve_type(&out.ty)?),
+                    outs => {
+                        let out_name = format!("{}{}Out", interface.name, pascal(name));
+                        franca.definition.structs.push(StructDef { name: out_name.clone(), fields: self.members(outs)?, wire: commonapi_wire() });
+                        Some(Type::Named(out_name))
+                    }
+                };
+                svc.methods.push(MethodDef { name: snake(name), id, args: self.members(ins)?, returns, fire_and_forget: *fire_and_forget });
+            }
+            for (name, outs) in &interface.broadcasts {
+                let block = depl.child("broadcast", name).unwrap_or(&empty);
+                let id = block.id("SomeIpEventID", &format!("{}: broadcast '{}'", what, name))?;
+                let groups = block.ids("SomeIpEventGroups")?;
+                for &group in &groups {
+                    eventgroups.entry(group).or_default().push(id);
+                }
+                let eventgroups = groups.iter().map(|group| group.to_string()).collect();
+                svc.events.push(EventDef { name: snake(name), id, args: self.members(outs)?, eventgroups });
+            }
+            for (name, ty, readonly, no_subscriptions) in &interface.attributes {
+                let block = depl.child("attribute", name).unwrap_or(&empty);
+                let notifier_id = if *no_subscriptions { None } else { block.optional_id("SomeIpNotifierID")? };
+                if let Some(id) = notifier_id {
+                    for group in block.ids("SomeIpNotifierEventGroups")? {
+                        eventgroups.entry(group).or_default().push(id);
+                    }
+                }
+                svc.fields.push(FieldDef {
+                    name: snake(name),
+                    ty: self.resolve_type(ty)?,
+                    get_id: block.optional_id("SomeIpGetterID")?,
+                    set_id: if *readonly { None } else { block.optional_id("SomeIpSetterID")? },
+                    notifier_id,
+                });
+            }
+            // Franca eventgroups have IDs only, which name them too (EVENTGROUP_1)
+            svc.eventgroups = eventgroups.keys().map(|&id| EventgroupDef { name: id.to_string(), id }).collect();
+            if !eventgroups.is_empty() {
+                franca.eventgroups.insert(svc.name.clone(), eventgroups);
+            }
+            franca.definition.services.push(svc);
+        }
+        for (service, block) in &deployment.instances {
+            if !self.interfaces.iter().any(|i| &i.name == service) {
+                return Err(format!("deployed instance of unknown interface '{}'", service));
+            }
+            let what = format!("instance of '{}'", service);
+            franca.instances.push(Instance {
+                service: service.clone(),
+                instance_id: block.id("SomeIpInstanceID", &what)?,
+                address: match block.props.get("SomeIpUnicastAddress") {
+                    Some(Value::String(address)) => Some(address.clone()),
+                    _ => None,
+                },
+                reliable_port: block.optional_id("SomeIpReliableUnicastPort")?,
+                unreliable_port: block.optional_id("SomeIpUnreliableUnicastPort")?,
+            });
+        }
+        Ok(franca)
+    }
+}
+
+// --- Deployment (.fdepl) ---
+
+/// Properties and nested elements of a deployment block
+#[derive(Debug, Default)]
+struct Block {
+    props: HashMap<String, Value>,
+    /// Keyword and name of each nested element (`method getX { .. }`)
+    children: Vec<(String, String, Block)>,
+}
+
+impl Block {
+    fn parse(p: &mut Parser) -> Result<Block, String> {
+        p.expect_punct('{')?;
+        let mut block = Block::default();
+        while !p.eat_punct('}') {
+            let key = p.word()?;
+            if p.eat_punct('=') {
+                let value = Block::value(p)?;
+                block.props.insert(key, value);
+            } else {
+                let name = local(&p.word()?).to_string();
+                block.children.push((key, name, Block::parse(p)?));
+            }
+        }
+        Ok(block)
+    }
+
+    fn value(p: &mut Parser) -> Result<Value, String> {
+        match p.next()? {
+            Token::Number(n) => Ok(json!(n)),
+            Token::Text(s) => Ok(json!(s)),
+            Token::Word(w) => Ok(json!(w)),
+            Token::Punct('{') => {
+                let mut items = Vec::new();
+                while !p.eat_punct('}') {
+                    items.push(Block::value(p)?);
+                    p.eat_punct(',');
+                }
+                Ok(Value::Array(items))
+            }
+            _ => p.error("expected a value"),
+        }
+    }
+
+    fn child(&self, keyword: &str, name: &str) -> Option<&Block> {
+        self.children.iter().find(|(k, n, _)| k == keyword && n == name).map(|(_, _, b)| b)
+    }
+
+    fn optional_id(&self, key: &str) -> Result<Option<u16>, String> {
+        match self.props.get(key) {
+            None => Ok(None),
+            Some(value) => value.as_u64().and_then(|v| u16::try_from(v).ok()).map(Some)
+                .ok_or_else(|| format!("{} = {} is not a 16-bit number", key, value)),
+        }
+    }
+
+    fn id(&self, key: &str, what: &str) -> Result<u16, String> {
+        self.optional_id(key)?.ok_or_else(|| format!("{} has no {} in the deployment", what, key))
+    }
+
+    /// A list of IDs, or a single one
+    fn ids(&self, key: &str) -> Result<Vec<u16>, String> {
+        let values = match self.props.get(key) {
+            None => return Ok(Vec::new()),
+            Some(Value::Array(values)) => values.clone(),
+            Some(value) => vec![value.clone()],
+        };
+        values.iter()
+            .map(|v| v.as_u64().and_then(|v| u16::try_from(v).ok()).ok_or_else(|| format!("{} = {} is not a list of 16-bit numbers", key, v)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Deployment {
+    /// Interface deployments by interface name
+    interfaces: HashMap<String, Block>,
+    /// Provided instances: interface name and instance deployment
+    instances: Vec<(String, Block)>,
+}
+
+impl Deployment {
+    fn parse(text: &str) -> Result<Deployment, String> {
+        let mut p = Parser::new(text)?;
+        let mut deployment = Deployment::default();
+        while p.peek().is_some() {
+            match p.word()?.as_str() {
+                "package" => drop(p.word()?),
+                "import" => while !matches!(p.next()?, Token::Text(_)) {},
+                "define" => {
+                    // define <specification> for <interface X | typeCollection X | provider as X> { .. }
+                    let mut header = Vec::new();
+                    while !p.at_punct('{') {
+                        header.push(p.word()?);
+                    }
+                    let block = Block::parse(&mut p)?;
+                    match header.iter().position(|w| w == "for").map(|i| &header[i + 1..]) {
+                        Some([kind, name]) if kind == "interface" => {
+                            deployment.interfaces.insert(local(name).to_string(), block);
+                        }
+                        Some([kind, ..]) if kind == "provider" => {
+                            for (keyword, name, instance) in block.children {
+                                if keyword == "instance" {
+                                    deployment.instances.push((name, instance));
+                                }
+                            }
+                        }
+                        Some([kind, ..]) if kind == "typeCollection" => {}
+                        _ => return p.error(format!("unsupported deployment '{}'", header.join(" "))),
+                    }
+                }
+                other => return p.error(format!("unexpected '{}'", other)),
+            }
+        }
+        Ok(deployment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIDL: &str = r#"
+        package org.example.radar
+
+        <** @description: Radar sensor **>
+        typeCollection RadarTypes {
+            version { major 1 minor 0 }
+            enumeration Quality { LOW = 0 HIGH = 1 }
+            struct Detection {
+                UInt32 objectId
+                Float rangeM
+                Quality quality
+            }
+            array Detections of Detection
+        }
+
+        interface Radar {
+            version { major 2 minor 1 }
+            attribute UInt32 detectionCount readonly
+            attribute Boolean enabled
+            method getDetections {
+                in { UInt16 maxCount }
+                out { Detections detections }
+            }
+            method divide {
+                in { Int32 a  Int32 b }
+                out { Int32 quotient  Int32 remainder }
+                error { DIVISION_BY_ZERO }
+            }
+            method reset fireAndForget { }
+            broadcast objectDetected {
+                out { RadarTypes.Detection detection  ByteBuffer raw }
+            }
+        }
+    "#;
+
+    const FDEPL: &str = r#"
+        import "platform:/plugin/org.genivi.commonapi.someip/deployment/CommonAPI-SOMEIP_deployment_spec.fdepl"
+        import "radar.fidl"
+
+        define org.genivi.commonapi.someip.deployment for interface org.example.radar.Radar {
+            SomeIpServiceID = 0x7001
+            attribute detectionCount {
+                SomeIpGetterID = 0x10
+                SomeIpSetterID = 0x11
+                SomeIpNotifierID = 0x8010
+                SomeIpNotifierEventGroups = { 1 }
+            }
+            attribute enabled { SomeIpGetterID = 0x12 SomeIpSetterID = 0x13 }
+            method getDetections { SomeIpMethodID = 1 }
+            method divide { SomeIpMethodID = 2 }
+            method reset { SomeIpMethodID = 3 }
+            broadcast objectDetected {
+                SomeIpEventID = 32769
+                SomeIpEventGroups = { 1, 2 }
+            }
+        }
+
+        define org.genivi.commonapi.someip.deployment for provider as RadarProvider {
+            instance org.example.radar.Radar {
+                InstanceId = "org.example.radar.Radar"
+                SomeIpInstanceID = 1
+                SomeIpUnicastAddress = "192.168.0.10"
+                SomeIpUnreliableUnicastPort = 30501
+                SomeIpReliableUnicastPort = 30500
+            }
+        }
+    "#;
+
+    fn named(name: &str) -> Type {
+        Type::Named(name.into())
+    }
+
+    #[test]
+    fn test_interface_with_deployment() {
+        let franca = parse(FIDL, Some(FDEPL)).unwrap();
+        let def = &franca.definition;
+        let detection = def.structs.iter().find(|s| s.name == "Detection").unwrap();
+        assert_eq!(detection.fields.iter().map(|f| (f.name.as_str(), f.ty.clone())).collect::<Vec<_>>(),
+            vec![("object_id", named("uint32")), ("range_m", named("float32")), ("quality", named("uint8"))]);
+
+        let svc = &def.services[0];
+        assert_eq!((svc.name.as_str(), svc.id, svc.major_version, svc.minor_version), ("Radar", 0x7001, 2, 1));
+        let get = &svc.methods[0];
+        assert_eq!((get.name.as_str(), get.id), ("get_detections", 1));
+        assert_eq!(get.args[0].name, "max_count");
+        assert_eq!(get.returns, Some(Type::List(Box::new(named("Detection")))));
+        // Several out arguments come back as one struct
+        assert_eq!(svc.methods[1].returns, Some(named("RadarDivideOut")));
+        let out = def.structs.iter().find(|s| s.name == "RadarDivideOut").unwrap();
+        assert_eq!(out.fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["quotient", "remainder"]);
+        assert!(svc.methods[2].fire_and_forget);
+        assert_eq!(svc.methods[2].returns, None);
+
+        let event = &svc.events[0];
+        assert_eq!((event.name.as_str(), event.id), ("object_detected", 0x8001));
+        assert_eq!(event.eventgroups, vec!["1", "2"]);
+        assert_eq!(svc.eventgroups.iter().map(|eg| (eg.name.as_str(), eg.id)).collect::<Vec<_>>(), vec![("1", 1), ("2", 2)]);
+        assert_eq!(event.args[1].ty, Type::List(Box::new(named("uint8"))));
+
+        // readonly drops the setter
+        let count = &svc.fields[0];
+        assert_eq!((count.get_id, count.set_id, count.notifier_id), (Some(0x10), None, Some(0x8010)));
+        let enabled = &svc.fields[1];
+        assert_eq!((enabled.get_id, enabled.set_id, enabled.notifier_id), (Some(0x12), Some(0x13), None));
+
+        assert_eq!(franca.instances, vec![Instance {
+            service: "Radar".into(),
+            instance_id: 1,
+            address: Some("192.168.0.10".into()),
+            reliable_port: Some(30500),
+            unreliable_port: Some(30501),
+        }]);
+        assert!(crate::generate(def).contains("pub async fn get_detections(&self, max_count: u16) -> std::io::Result<Vec<Detection>>"));
+    }
+
+    #[test]
+    fn test_deployment_config() {
+        let config = parse(FIDL, Some(FDEPL)).unwrap().config();
+        assert_eq!(config["endpoints"]["radar_udp"], json!({ "ip": "192.168.0.10", "port": 30501, "version": 4, "protocol": "udp" }));
+        assert_eq!(config["endpoints"]["radar_tcp"]["port"], 30500);
+        let offer = &config["providing"]["radar"];
+        assert_eq!(offer["service_id"], 0x7001);
+        assert_eq!(offer["instance_id"], 1);
+        assert_eq!(offer["major_version"], 2);
+        assert_eq!(offer["endpoint"], "radar_udp");
+        assert_eq!(offer["eventgroups"]["eventgroup_1"], json!({ "eventgroup_id": 1, "events": [0x8001, 0x8010] }));
+        assert_eq!(offer["eventgroups"]["eventgroup_2"], json!({ "eventgroup_id": 2, "events": [0x8001] }));
+    }
+
+    #[test]
+    fn test_franca_errors() {
+        let invalid = |fidl: &str, fdepl: Option<&str>| match parse(fidl, fdepl) {
+            Err(Error::Invalid(msg)) => msg,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(invalid(FIDL, None), "interface 'Radar' has no SomeIpServiceID in the deployment");
+        assert_eq!(invalid("interface A { method m { } }", Some("define s for interface A { SomeIpServiceID = 1 }")),
+            "interface 'A': method 'm' has no SomeIpMethodID in the deployment");
+        assert_eq!(invalid("typeCollection T {\n  union U { UInt8 a }\n}", None), "fidl line 2: 'union' is not supported");
+        assert_eq!(invalid("typeCollection T { struct S { Missing m } }", None), "field 'm' of struct 'S': unknown type 'Missing'");
+        assert_eq!(invalid("interface A { }", Some("define s for provider as P { instance B { SomeIpInstanceID = 1 } }")),
+            "interface 'A' has no SomeIpServiceID in the deployment");
+        assert!(parse("typeCollection T { struct S { UInt8 a } }", None).is_ok());
+    }
+}
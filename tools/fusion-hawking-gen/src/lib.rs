@@ -0,0 +1,309 @@
+//! # Fusion Hawking Code Generator
+//!
+//! Generates the Rust bindings of SOME/IP services from a JSON service
+//! definition (see [`model`]): the data types with their codec and schema impls,
+//! and per service a `Provider` trait, a `Server` dispatcher and an async
+//! `Client`. The generated API is the one the Python codegen (tools/codegen)
+//! emits for the same IDL.
+//!
+//! In a build script, with `fusion-hawking-gen` as a build dependency:
+//!
+//! ```no_run
+//! fusion_hawking_gen::compile("idl/services.json").expect("codegen failed");
+//! ```
+//!
+//! writes `$OUT_DIR/services.rs`, which the crate includes:
+//!
+//! ```ignore
+//! pub mod generated {
+//!     include!(concat!(env!("OUT_DIR"), "/services.rs"));
+//! }
+//! ```
+//!
+//! Franca interfaces (`.fidl`) with their SOME/IP deployment (`.fdepl`) go through
+//! [`compile_franca`] instead. The `fusion-hawking-gen` binary does the same from
+//! the command line.
+
+pub mod franca;
+pub mod model;
+mod rust;
+
+pub use model::Definition;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Not a valid definition document
+    Json(serde_json::Error),
+    /// Well-formed, but inconsistent (unknown type, colliding IDs, ...)
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Json(e) => write!(f, "invalid service definition: {}", e),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Parse and validate a service definition.
+pub fn parse(json: &str) -> Result<Definition, Error> {
+    let definition: Definition = serde_json::from_str(json).map_err(Error::Json)?;
+    definition.validate().map_err(Error::Invalid)?;
+    Ok(definition)
+}
+
+/// Read, parse and validate the service definition at `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<Definition, Error> {
+    parse(&fs::read_to_string(path)?)
+}
+
+/// Rust source of the bindings for `definition`.
+pub fn generate(definition: &Definition) -> String {
+    rust::generate(definition)
+}
+
+/// Generate the bindings for the definition at `input` into the file `output`.
+pub fn generate_file(input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<(), Error> {
+    write_if_changed(output.as_ref(), &generate(&load(input)?))
+}
+
+/// Read a Franca interface and its optional deployment (see [`franca`]).
+pub fn load_franca(fidl: impl AsRef<Path>, fdepl: Option<&Path>) -> Result<franca::Franca, Error> {
+    let deployment = fdepl.map(fs::read_to_string).transpose()?;
+    franca::parse(&fs::read_to_string(fidl)?, deployment.as_deref())
+}
+
+/// Build script helper: generate the bindings for the definition at `definition`
+/// (relative to the package root) into `$OUT_DIR/<file stem>.rs`, and have cargo
+/// rerun the script when the definition changes. Returns the generated file.
+pub fn compile(definition: impl AsRef<Path>) -> Result<PathBuf, Error> {
+    let definition = definition.as_ref();
+    let output = out_file(definition)?;
+    println!("cargo:rerun-if-changed={}", definition.display());
+    generate_file(definition, &output)?;
+    Ok(output)
+}
+
+/// [`compile`] for a Franca interface `fidl` with its deployment `fdepl`.
+pub fn compile_franca(fidl: impl AsRef<Path>, fdepl: impl AsRef<Path>) -> Result<PathBuf, Error> {
+    let (fidl, fdepl) = (fidl.as_ref(), fdepl.as_ref());
+    let output = out_file(fidl)?;
+    println!("cargo:rerun-if-changed={}", fidl.display());
+    println!("cargo:rerun-if-changed={}", fdepl.display());
+    write_if_changed(&output, &generate(&load_franca(fidl, Some(fdepl))?.definition))?;
+    Ok(output)
+}
+
+/// `$OUT_DIR/<stem of input>.rs`
+fn out_file(input: &Path) -> Result<PathBuf, Error> {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .ok_or_else(|| Error::Invalid("OUT_DIR is not set; compile() runs from a build script".into()))?;
+    let stem = input.file_stem()
+        .ok_or_else(|| Error::Invalid(format!("'{}' is not a file", input.display())))?;
+    Ok(Path::new(&out_dir).join(stem).with_extension("rs"))
+}
+
+fn write_if_changed(output: &Path, code: &str) -> Result<(), Error> {
+    // Leave an unchanged file alone so dependents are not rebuilt
+    if fs::read_to_string(output).ok().as_deref() != Some(code) {
+        fs::write(output, code)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITION: &str = r#"{
+        "structs": [
+            { "name": "RadarObject", "fields": [{ "name": "id", "type": "int" }, { "name": "range_m", "type": "float32" }] },
+            { "name": "Flags", "fields": [{ "name": "a", "type": "bool" }, { "name": "b", "type": "bool" }], "wire": { "bools": "bits" } }
+        ],
+        "services": [{
+            "name": "RadarService", "id": "0x7001", "minor_version": 2,
+            "methods": [
+                { "name": "get_objects", "id": 1, "args": [{ "name": "limit", "type": "uint16" }], "returns": "List[RadarObject]" },
+                { "name": "reset", "id": 2, "fire_and_forget": true }
+            ],
+            "events": [{ "name": "on_object_detected", "id": "0x8001", "args": [{ "name": "objects", "type": "List[RadarObject]" }], "eventgroups": ["objects"] }],
+            "fields": [{ "name": "detection_count", "type": "int", "get_id": "0x10", "notifier_id": "0x8012" }],
+            "eventgroups": [{ "name": "objects", "id": 1 }, { "name": "status", "id": 2 }]
+        }]
+    }"#;
+
+    #[test]
+    fn test_parse_definition() {
+        let def = parse(DEFINITION).unwrap();
+        let svc = &def.services[0];
+        assert_eq!((svc.id, svc.major_version, svc.minor_version), (0x7001, 1, 2));
+        assert_eq!(svc.methods[0].returns, Some(model::Type::List(Box::new(model::Type::Named("RadarObject".into())))));
+        assert!(svc.methods[1].fire_and_forget);
+        assert_eq!(svc.fields[0].get_id, Some(0x10));
+        assert_eq!(svc.fields[0].set_id, None);
+        assert!(!def.structs[1].wire.is_default());
+    }
+
+    #[test]
+    fn test_generate_bindings() {
+        let code = generate(&parse(DEFINITION).unwrap());
+        assert!(code.contains("pub mod types {"));
+        assert!(code.contains("pub mod radar_service {"));
+        assert!(code.contains("pub use radar_service::*;"));
+        assert!(code.contains("    use super::types::*;"));
+        assert!(code.contains("pub struct RadarObject {\n        pub id: i32,\n        pub range_m: f32,\n    }"));
+        assert!(code.contains("fusion_hawking::codec::repr::write_bits(&[self.a, self.b], 1, writer)?;"));
+        assert!(code.contains("fields: \"a:bool[bits8],b:bool[bits8]\""));
+        assert!(code.contains("nested: vec![<RadarObject as SomeIpSchema>::schema()]"));
+        // Provider, server and client
+        assert!(code.contains("fn get_objects(&self, limit: u16) -> Vec<RadarObject>;"));
+        assert!(code.contains("fn detection_count(&self) -> &fusion_hawking::runtime::FieldHandle<i32>;"));
+        assert!(code.contains("pub const SERVICE_ID: u16 = 0x7001;"));
+        assert!(code.contains("pub const MINOR_VERSION: u32 = 2;"));
+        assert!(code.contains("pub const EVENT_DETECTION_COUNT_NOTIFY: u16 = 0x8012;"));
+        assert!(!code.contains("FIELD_SET_DETECTION_COUNT"));
+        assert!(code.contains("pub fn fire_on_object_detected(runtime: &fusion_hawking::runtime::SomeIpRuntime, objects: Vec<RadarObject>) -> std::io::Result<usize>"));
+        assert!(code.contains("pub async fn get_objects(&self, limit: u16) -> std::io::Result<Vec<RadarObject>>"));
+        assert!(code.contains("pub fn reset(&self) -> std::io::Result<()> {"));
+        assert!(code.contains("self.context.send(RadarServiceServer::<()>::METHOD_RESET, &payload)"));
+        assert!(code.contains("self.provider.reset();\n                    Ok(None)"));
+        assert!(code.contains("pub async fn get_detection_count(&self) -> std::io::Result<i32>"));
+        assert!(code.contains("pub fn on_object_detected(runtime: &fusion_hawking::runtime::SomeIpRuntime, callback: impl Fn(RadarServiceOnObjectDetectedEvent) + Send + Sync + 'static) {"));
+        // Eventgroups: constants, an event enum and a subscribe helper registering their events
+        assert!(code.contains("pub enum RadarServiceEvent {\n        OnObjectDetected(RadarServiceOnObjectDetectedEvent),\n    }"));
+        assert_eq!(code.matches("pub const EVENTGROUP_OBJECTS: u16 = 1;").count(), 2);
+        assert!(code.contains("pub fn subscribe(&self, eventgroup: u16, ttl: u32, callback: impl Fn(RadarServiceEvent) + Send + Sync + 'static) -> std::io::Result<()> {"));
+        assert!(code.contains("Self::EVENTGROUP_OBJECTS => {\n                    let callback = callback.clone();\n                    self.context.on_event(RadarServiceServer::<()>::EVENT_ON_OBJECT_DETECTED, move |event| callback(RadarServiceEvent::OnObjectDetected(event)))?;\n                }"));
+        assert!(code.contains("Self::EVENTGROUP_STATUS => {\n                }"));
+    }
+
+    #[test]
+    fn test_string_encodings() {
+        let def = parse(r#"{
+            "structs": [{ "name": "Label", "fields": [{ "name": "text", "type": "string" }, { "name": "n", "type": "uint8" }], "wire": { "strings": "utf16le" } }],
+            "services": [{
+                "name": "Names", "id": 1, "wire": { "strings": "utf8", "string_length": 16 },
+                "methods": [{ "name": "rename", "id": 1, "args": [{ "name": "name", "type": "string" }, { "name": "flag", "type": "bool" }] }]
+            }]
+        }"#).unwrap();
+        let code = generate(&def);
+        let repr = "fusion_hawking::codec::repr";
+        assert!(code.contains(&format!("{}::write_string(&self.text, &{}::StringFormat::new({}::StringEncoding::Utf16Le), writer)?;", repr, repr, repr)));
+        assert!(code.contains(&format!("let name = {}::read_string(&{}::StringFormat::fixed({}::StringEncoding::Utf8, 16), reader)?;", repr, repr, repr)));
+        assert!(code.contains("self.n.serialize(writer)?;"));
+        assert!(code.contains("fields: \"text:String[utf16le],n:u8\""));
+        assert!(code.contains("fields: \"name:String[utf8;16],flag:bool\""));
+    }
+
+    #[test]
+    fn test_length_widths() {
+        let def = parse(r#"{
+            "structs": [{ "name": "Frame", "wire": { "length_width": 1 }, "fields": [
+                { "name": "data", "type": "List[uint16]" },
+                { "name": "name", "type": "string", "length_width": 2 },
+                { "name": "count", "type": "uint8" }
+            ] }],
+            "services": [{ "name": "Len", "id": 1, "methods": [{ "name": "put", "id": 1, "args": [{ "name": "bytes", "type": "List[uint8]", "length_width": 2 }] }] }]
+        }"#).unwrap();
+        let code = generate(&def);
+        let layout = "fusion_hawking::codec::layout";
+        assert!(code.contains(&format!("{}::write_resized(&self.data, 1, writer)?;", layout)));
+        assert!(code.contains(&format!("let name = {}::read_resized::<String, _>(2, reader)?;", layout)));
+        assert!(code.contains("self.count.serialize(writer)?;"));
+        assert!(code.contains(&format!("{}::write_resized(&self.bytes, 2, writer)?;", layout)));
+        assert!(code.contains("fields: \"data:Vec<u16>[len8],name:String[len16],count:u8\""));
+    }
+
+    #[test]
+    fn test_alignment() {
+        let def = parse(r#"{
+            "structs": [{ "name": "Point", "wire": { "align": 4 }, "fields": [
+                { "name": "a", "type": "uint8" },
+                { "name": "b", "type": "uint16", "align": 1 },
+                { "name": "c", "type": "List[uint8]" }
+            ] }],
+            "services": [{ "name": "Al", "id": 1, "wire": { "align": 2 }, "methods": [{ "name": "reset", "id": 1 }] }]
+        }"#).unwrap();
+        let code = generate(&def);
+        let layout = "fusion_hawking::codec::layout";
+        assert!(code.contains(&format!("let writer = &mut {}::Counted::new(writer);\n            {}::write_padding(writer, 4)?;\n            self.a.serialize(writer)?;\n            self.b.serialize(writer)?;", layout, layout)));
+        assert!(code.contains(&format!("{}::skip_padding(reader, 4)?;\n            let c = <Vec<u8>>::deserialize(reader)?;", layout)));
+        assert!(code.contains("fields: \"a:u8[align4],b:u16,c:Vec<u8>[align4]\""));
+        // Nothing to align in a request without arguments
+        assert!(code.contains("fn serialize<W: Write>(&self, _writer: &mut W) -> Result<()> {"));
+    }
+
+    #[test]
+    fn test_tlv_bindings() {
+        let def = parse(r#"{
+            "structs": [{ "name": "Status", "wire": { "tlv": true }, "fields": [
+                { "name": "code", "type": "uint16", "id": "0x10" },
+                { "name": "text", "type": "string", "optional": true },
+                { "name": "data", "type": "List[uint8]", "optional": true }
+            ] }]
+        }"#).unwrap();
+        assert_eq!(model::data_ids(&def.structs[0].fields).collect::<Vec<_>>(), vec![0x10, 1, 2]);
+        let code = generate(&def);
+        let tlv = "fusion_hawking::codec::tlv";
+        assert!(code.contains("pub text: Option<String>,"));
+        assert!(code.contains(&format!("{}::write_base(0x10, &self.code, writer)?;", tlv)));
+        assert!(code.contains(&format!("if let Some(value) = &self.text {{\n                    {}::write_prefixed(0x1, value, writer)?;", tlv)));
+        assert!(code.contains(&format!("{}::write_prefixed(0x2, value, writer)?;", tlv)));
+        assert!(code.contains("text = Some(tlv_member.decode_prefixed::<String>()?);"));
+        assert!(code.contains(&format!("Ok(Status {{ code: {}::required(code, \"code\")?, text, data }})", tlv)));
+        assert!(code.contains("fields: \"code:u16@16,text:Option<String>@1,data:Option<Vec<u8>>@2\""));
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        let invalid = |json: &str| match parse(json) {
+            Err(Error::Invalid(msg)) => msg,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(invalid(r#"{"structs": [{"name": "A", "fields": [{"name": "b", "type": "List[B]"}]}]}"#),
+            "field 'b' of struct 'A': unknown type 'B'");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "methods": [{"name": "m", "id": "0x8001"}]}]}"#),
+            "S: method 'm' uses ID 0x8001 in the event range (methods must be 0x0000-0x7fff)");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "events": [{"name": "e", "id": 1}]}]}"#),
+            "S: event 'e' uses ID 0x0001 (events must be 0x8000-0xffff)");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "methods": [{"name": "m", "id": 16}], "fields": [{"name": "f", "type": "int", "get_id": "0x10"}]}]}"#),
+            "S: ID 0x0010 of getter of field 'f' collides with method 'm'");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "wire": {"string_length": 0}}]}"#),
+            "S: string_length must be positive");
+        assert_eq!(invalid(r#"{"structs": [{"name": "A", "wire": {"tlv": true}, "fields": [{"name": "a", "type": "int"}, {"name": "b", "type": "int", "id": 0}]}]}"#),
+            "struct 'A': data ID 0x0 of 'b' is used twice");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "wire": {"tlv": true}, "events": [{"name": "e", "id": "0x8001", "args": [{"name": "a", "type": "int", "id": "0x1000"}]}]}]}"#),
+            "S: event 'e': data ID 0x1000 of 'a' exceeds 0xfff");
+        assert_eq!(invalid(r#"{"structs": [{"name": "A", "fields": [{"name": "a", "type": "int", "length_width": 2}]}]}"#),
+            "struct 'A': 'a' has no length field");
+        assert_eq!(invalid(r#"{"structs": [{"name": "A", "fields": [{"name": "a", "type": "int", "align": 3}]}]}"#),
+            "struct 'A': align of 'a' must be 1, 2, 4 or 8");
+        assert_eq!(invalid(r#"{"structs": [{"name": "A", "wire": {"length_width": 3}}]}"#),
+            "struct 'A': length_width must be 1, 2 or 4");
+        assert_eq!(invalid(r#"{"structs": [{"name": "A", "wire": {"tlv": true, "strings": "utf8"}}]}"#),
+            "struct 'A': tlv uses the default bool, string and length field encodings without alignment");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "events": [{"name": "e", "id": "0x8001", "eventgroups": ["g"]}]}]}"#),
+            "S: event 'e' names unknown eventgroup 'g'");
+        assert_eq!(invalid(r#"{"services": [{"name": "S", "id": 1, "eventgroups": [{"name": "a", "id": 1}, {"name": "b", "id": 1}]}]}"#),
+            "S: eventgroup 'b' (1) collides with eventgroup 'a' (1)");
+        assert!(matches!(parse(r#"{"services": [{"name": "S", "id": 70000}]}"#), Err(Error::Json(_))));
+        assert!(matches!(parse(r#"{"services": [{"name": "S", "id": 1, "typo": true}]}"#), Err(Error::Json(_))));
+    }
+}
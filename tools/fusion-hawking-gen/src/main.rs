@@ -0,0 +1,71 @@
+//! fusion-hawking-gen: generate Rust bindings from a service definition.
+//!
+//! Usage: fusion-hawking-gen <definition.json | interface.fidl> [-d <deployment.fdepl>]
+//!                           [-o <output.rs>] [--config <config.json>]
+//!
+//! Writes to stdout without `-o`. For Franca input, `--config` writes the runtime
+//! configuration fragment of the deployed instances.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: fusion-hawking-gen <definition.json | interface.fidl> [-d <deployment.fdepl>] [-o <output.rs>] [--config <config.json>]";
+
+#[derive(Default)]
+struct Args {
+    input: String,
+    deployment: Option<String>,
+    output: Option<String>,
+    config: Option<String>,
+}
+
+fn parse_args() -> Option<Args> {
+    let mut args = Args::default();
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-d" | "--deployment" => args.deployment = Some(iter.next()?),
+            "-o" | "--output" => args.output = Some(iter.next()?),
+            "--config" => args.config = Some(iter.next()?),
+            _ if arg.starts_with('-') || !args.input.is_empty() => return None,
+            _ => args.input = arg,
+        }
+    }
+    (!args.input.is_empty()).then_some(args)
+}
+
+fn run(args: &Args) -> Result<(), fusion_hawking_gen::Error> {
+    let franca = Path::new(&args.input).extension().is_some_and(|e| e == "fidl");
+    if !franca && (args.deployment.is_some() || args.config.is_some()) {
+        return Err(fusion_hawking_gen::Error::Invalid("-d and --config apply to Franca (.fidl) input".into()));
+    }
+    let definition = if franca {
+        let model = fusion_hawking_gen::load_franca(&args.input, args.deployment.as_deref().map(Path::new))?;
+        if let Some(config) = &args.config {
+            std::fs::write(config, format!("{:#}\n", model.config()))?;
+        }
+        model.definition
+    } else {
+        fusion_hawking_gen::load(&args.input)?
+    };
+    let code = fusion_hawking_gen::generate(&definition);
+    match &args.output {
+        Some(output) => std::fs::write(output, code)?,
+        None => print!("{}", code),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let Some(args) = parse_args() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}: {}", args.input, e);
+            ExitCode::FAILURE
+        }
+    }
+}
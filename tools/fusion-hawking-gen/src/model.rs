@@ -0,0 +1,499 @@
+//! # Service Definition
+//!
+//! The JSON form of the Python IDL (tools/codegen). IDs are numbers or hex
+//! strings such as `"0x7001"`; types are the IDL names (`int`, `uint16`,
+//! `float32`, `string`, `bool`, a struct name) or `List[T]`.
+//!
+//! ```json
+//! {
+//!   "structs": [{ "name": "Point", "fields": [{ "name": "x", "type": "float32" }] }],
+//!   "services": [{
+//!     "name": "MapService", "id": "0x7100",
+//!     "methods": [{ "name": "route", "id": 1, "args": [{ "name": "to", "type": "Point" }], "returns": "List[Point]" }],
+//!     "events": [{ "name": "on_position", "id": "0x8001", "args": [{ "name": "position", "type": "Point" }] }],
+//!     "fields": [{ "name": "zoom", "type": "uint8", "get_id": "0x10", "set_id": "0x11", "notifier_id": "0x8012" }]
+//!   }]
+//! }
+//! ```
+
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+
+/// The highest bit of a Method ID marks events [PRS_SOMEIP_00245]
+const EVENT_ID_FLAG: u16 = 0x8000;
+
+/// Data IDs are the low 12 bits of a TLV tag
+const MAX_DATA_ID: u16 = 0x0FFF;
+
+/// IDL names of the primitive types
+const PRIMITIVES: &[&str] = &[
+    "int", "int8", "int16", "int32", "int64", "uint8", "uint16", "uint32", "uint64",
+    "float", "float32", "float64", "double", "string", "str", "bool",
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Definition {
+    #[serde(default)]
+    pub structs: Vec<StructDef>,
+    #[serde(default)]
+    pub services: Vec<ServiceDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StructDef {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Vec<Member>,
+    #[serde(default)]
+    pub wire: WireFormat,
+}
+
+/// A struct field, or an argument of a method or event
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Member {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Type,
+    /// Data ID in TLV encoding; defaults to the position of the member
+    #[serde(default, deserialize_with = "optional_id")]
+    pub id: Option<u16>,
+    /// Generated as `Option<T>`; TLV encoding omits absent members
+    #[serde(default)]
+    pub optional: bool,
+    /// Length field bytes of a `List` or `string` member, overriding the `wire` of its struct
+    #[serde(default)]
+    pub length_width: Option<usize>,
+    /// Alignment of the member in bytes, overriding the `wire` of its struct
+    #[serde(default)]
+    pub align: Option<usize>,
+}
+
+impl Member {
+    pub fn new(name: impl Into<String>, ty: Type) -> Self {
+        Member { name: name.into(), ty, id: None, optional: false, length_width: None, align: None }
+    }
+
+    /// Alignment of this member in `wire`
+    pub fn align(&self, wire: &WireFormat) -> usize {
+        self.align.unwrap_or(wire.align)
+    }
+
+    /// Length field bytes of this member in `wire`
+    pub fn length_width(&self, wire: &WireFormat) -> usize {
+        self.length_width.unwrap_or(wire.length_width)
+    }
+
+    /// Whether the member is a container with a length field
+    pub fn has_length(&self) -> bool {
+        matches!(self.ty, Type::List(_)) || self.ty.is_string()
+    }
+}
+
+/// Data IDs of `members` in TLV encoding
+pub fn data_ids(members: &[Member]) -> impl Iterator<Item = u16> + '_ {
+    members.iter().enumerate().map(|(i, m)| m.id.unwrap_or(i as u16))
+}
+
+/// Representation of a struct's booleans and strings on the wire (`@wire` in
+/// the IDL for booleans)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WireFormat {
+    #[serde(default)]
+    pub bools: BoolRepr,
+    /// Bytes per flag, or per bit field
+    #[serde(default = "default_bool_width")]
+    pub bool_width: usize,
+    /// Encoding of `string` members
+    #[serde(default)]
+    pub strings: StringRepr,
+    /// Fixed size of `string` members in bytes instead of a length field
+    #[serde(default)]
+    pub string_length: Option<usize>,
+    /// Tag-length-value members inside a 4-byte length field, so peers skip
+    /// members they do not know
+    #[serde(default)]
+    pub tlv: bool,
+    /// Length field bytes of `List` and `string` members (1, 2 or 4)
+    #[serde(default = "default_length_width")]
+    pub length_width: usize,
+    /// Members are zero padded to start at a multiple of this many bytes,
+    /// counted from the start of the struct (1, 2, 4 or 8)
+    #[serde(default = "default_align")]
+    pub align: usize,
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        WireFormat { bools: BoolRepr::Byte, bool_width: 1, strings: StringRepr::Raw, string_length: None, tlv: false, length_width: 4, align: 1 }
+    }
+}
+
+impl WireFormat {
+    pub fn is_default(&self) -> bool {
+        *self == WireFormat::default()
+    }
+
+    /// Whether `bool` members need the `repr` bool codec
+    pub fn custom_bools(&self) -> bool {
+        self.bools != BoolRepr::Byte || self.bool_width != 1
+    }
+
+    /// Whether the default codec of `members` matches this format
+    pub fn is_default_for(&self, members: &[Member]) -> bool {
+        !self.tlv
+            && members.iter().all(|m| {
+                m.align(self) == 1
+                    && !(m.ty.is_bool() && self.custom_bools())
+                    && !(m.ty.is_string() && self.custom_strings())
+                    && !(m.has_length() && m.length_width(self) != 4)
+            })
+    }
+
+    /// Whether `string` members need the `repr` string codec
+    pub fn custom_strings(&self) -> bool {
+        self.strings != StringRepr::Raw || self.string_length.is_some()
+    }
+
+    fn validate(&self, what: &str) -> Result<(), String> {
+        if !matches!(self.bool_width, 1 | 2 | 4 | 8) {
+            return Err(format!("{}: bool_width must be 1, 2, 4 or 8", what));
+        }
+        if self.string_length == Some(0) {
+            return Err(format!("{}: string_length must be positive", what));
+        }
+        if !matches!(self.length_width, 1 | 2 | 4) {
+            return Err(format!("{}: length_width must be 1, 2 or 4", what));
+        }
+        if !matches!(self.align, 1 | 2 | 4 | 8) {
+            return Err(format!("{}: align must be 1, 2, 4 or 8", what));
+        }
+        if self.tlv && (self.custom_bools() || self.custom_strings() || self.length_width != 4 || self.align != 1) {
+            return Err(format!("{}: tlv uses the default bool, string and length field encodings without alignment", what));
+        }
+        Ok(())
+    }
+
+    /// Check the alignments and length field widths of `members`, their data IDs if they are
+    /// TLV-encoded, and that optional members use the default encodings of their type
+    fn validate_members(&self, members: &[Member], what: &str) -> Result<(), String> {
+        for m in members {
+            if let Some(align) = m.align {
+                if !matches!(align, 1 | 2 | 4 | 8) {
+                    return Err(format!("{}: align of '{}' must be 1, 2, 4 or 8", what, m.name));
+                }
+                if self.tlv && align != 1 {
+                    return Err(format!("{}: tlv members cannot be aligned ('{}')", what, m.name));
+                }
+            }
+            if let Some(width) = m.length_width {
+                if !matches!(width, 1 | 2 | 4) {
+                    return Err(format!("{}: length_width of '{}' must be 1, 2 or 4", what, m.name));
+                }
+                if !m.has_length() {
+                    return Err(format!("{}: '{}' has no length field", what, m.name));
+                }
+                if self.tlv && width != 4 {
+                    return Err(format!("{}: tlv uses the default length field of '{}'", what, m.name));
+                }
+            }
+        }
+        let custom = |m: &Member| {
+            (m.ty.is_bool() && self.custom_bools())
+                || (m.ty.is_string() && self.custom_strings())
+                || (m.has_length() && m.length_width(self) != 4)
+        };
+        if let Some(m) = members.iter().find(|m| m.optional && custom(m)) {
+            return Err(format!("{}: optional member '{}' cannot use a custom bool, string or length field encoding", what, m.name));
+        }
+        if !self.tlv {
+            return Ok(());
+        }
+        let mut seen = HashSet::new();
+        for (m, id) in members.iter().zip(data_ids(members)) {
+            if id > MAX_DATA_ID {
+                return Err(format!("{}: data ID 0x{:x} of '{}' exceeds 0x{:x}", what, id, m.name, MAX_DATA_ID));
+            }
+            if !seen.insert(id) {
+                return Err(format!("{}: data ID 0x{:x} of '{}' is used twice", what, id, m.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_bool_width() -> usize { 1 }
+
+fn default_length_width() -> usize { 4 }
+
+fn default_align() -> usize { 1 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoolRepr {
+    /// One integer per flag
+    #[default]
+    Byte,
+    /// Consecutive flags share a bit field
+    Bits,
+}
+
+/// Encoding of strings, matching `codec::repr::StringEncoding`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StringRepr {
+    /// Length field and UTF-8 bytes
+    #[default]
+    Raw,
+    /// UTF-8 with byte order mark and terminating zero
+    Utf8,
+    /// UTF-16LE with byte order mark and terminating zero
+    Utf16le,
+    /// UTF-16BE with byte order mark and terminating zero
+    Utf16be,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceDef {
+    pub name: String,
+    #[serde(deserialize_with = "id")]
+    pub id: u16,
+    #[serde(default = "default_major_version")]
+    pub major_version: u32,
+    #[serde(default)]
+    pub minor_version: u32,
+    #[serde(default)]
+    pub methods: Vec<MethodDef>,
+    #[serde(default)]
+    pub events: Vec<EventDef>,
+    #[serde(default)]
+    pub fields: Vec<FieldDef>,
+    #[serde(default)]
+    pub eventgroups: Vec<EventgroupDef>,
+    /// Representation used by the arguments and results of methods and events
+    #[serde(default)]
+    pub wire: WireFormat,
+}
+
+fn default_major_version() -> u32 { 1 }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MethodDef {
+    pub name: String,
+    #[serde(deserialize_with = "id")]
+    pub id: u16,
+    #[serde(default)]
+    pub args: Vec<Member>,
+    /// None for methods without a result
+    #[serde(default)]
+    pub returns: Option<Type>,
+    /// Sent as REQUEST_NO_RETURN; the client does not wait for a response
+    #[serde(default)]
+    pub fire_and_forget: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventDef {
+    pub name: String,
+    #[serde(deserialize_with = "id")]
+    pub id: u16,
+    #[serde(default)]
+    pub args: Vec<Member>,
+    /// Names of the service's eventgroups carrying the event
+    #[serde(default)]
+    pub eventgroups: Vec<String>,
+}
+
+/// A named eventgroup of a service
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventgroupDef {
+    pub name: String,
+    #[serde(deserialize_with = "id")]
+    pub id: u16,
+}
+
+/// A field held by the provider, with optional getter, setter and notifier
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Type,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub get_id: Option<u16>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub set_id: Option<u16>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub notifier_id: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Type {
+    /// A primitive or a struct
+    Named(String),
+    List(Box<Type>),
+}
+
+impl Type {
+    /// Name of the element type, through any lists
+    pub fn base(&self) -> &str {
+        match self {
+            Type::Named(name) => name,
+            Type::List(inner) => inner.base(),
+        }
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Type::Named(name) if name == "bool")
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, Type::Named(name) if name == "string" || name == "str")
+    }
+}
+
+impl TryFrom<String> for Type {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(inner) = text.strip_prefix("List[").and_then(|rest| rest.strip_suffix(']')) {
+            return Ok(Type::List(Box::new(Type::try_from(inner.to_string())?)));
+        }
+        let mut chars = text.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if valid { Ok(Type::Named(text.to_string())) } else { Err(format!("invalid type '{}'", text)) }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawId {
+    Number(u64),
+    Text(String),
+}
+
+impl RawId {
+    fn value(self) -> Result<u16, String> {
+        let value = match self {
+            RawId::Number(n) => n,
+            RawId::Text(text) => match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16).map_err(|_| format!("invalid ID '{}'", text))?,
+                None => text.parse().map_err(|_| format!("invalid ID '{}'", text))?,
+            },
+        };
+        u16::try_from(value).map_err(|_| format!("ID {} does not fit in 16 bits", value))
+    }
+}
+
+fn id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    RawId::deserialize(deserializer)?.value().map_err(serde::de::Error::custom)
+}
+
+fn optional_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    Option::<RawId>::deserialize(deserializer)?.map(RawId::value).transpose().map_err(serde::de::Error::custom)
+}
+
+impl Definition {
+    /// Check names, types and the ID spaces of each service: methods, getters and
+    /// setters use 0x0000-0x7fff, events and notifiers 0x8000-0xffff, and no ID is
+    /// used twice within a service. Eventgroups are unique by name and ID, and events
+    /// name declared eventgroups only.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut struct_names = HashSet::new();
+        for s in &self.structs {
+            if !struct_names.insert(s.name.as_str()) {
+                return Err(format!("struct '{}' is defined twice", s.name));
+            }
+            s.wire.validate(&format!("struct '{}'", s.name))?;
+            s.wire.validate_members(&s.fields, &format!("struct '{}'", s.name))?;
+        }
+        let check_type = |ty: &Type, what: &str| {
+            let base = ty.base();
+            if PRIMITIVES.contains(&base) || struct_names.contains(base) {
+                Ok(())
+            } else {
+                Err(format!("{}: unknown type '{}'", what, base))
+            }
+        };
+        for s in &self.structs {
+            for f in &s.fields {
+                check_type(&f.ty, &format!("field '{}' of struct '{}'", f.name, s.name))?;
+            }
+        }
+
+        let mut service_names = HashSet::new();
+        for svc in &self.services {
+            if !service_names.insert(svc.name.as_str()) {
+                return Err(format!("service '{}' is defined twice", svc.name));
+            }
+            svc.wire.validate(&svc.name)?;
+            let mut used: Vec<(u16, String)> = Vec::new();
+            let mut claim = |msg_id: u16, event: bool, what: String| {
+                if event && msg_id & EVENT_ID_FLAG == 0 {
+                    return Err(format!("{}: {} uses ID 0x{:04x} (events must be 0x8000-0xffff)", svc.name, what, msg_id));
+                }
+                if !event && msg_id & EVENT_ID_FLAG != 0 {
+                    return Err(format!("{}: {} uses ID 0x{:04x} in the event range (methods must be 0x0000-0x7fff)", svc.name, what, msg_id));
+                }
+                if let Some((_, other)) = used.iter().find(|(id, _)| *id == msg_id) {
+                    return Err(format!("{}: ID 0x{:04x} of {} collides with {}", svc.name, msg_id, what, other));
+                }
+                used.push((msg_id, what));
+                Ok(())
+            };
+            for m in &svc.methods {
+                let what = format!("method '{}'", m.name);
+                svc.wire.validate_members(&m.args, &format!("{}: {}", svc.name, what))?;
+                for a in &m.args {
+                    check_type(&a.ty, &format!("{} of {}", a.name, what))?;
+                }
+                if let Some(ty) = &m.returns {
+                    if m.fire_and_forget {
+                        return Err(format!("{}: fire-and-forget {} cannot return a value", svc.name, what));
+                    }
+                    check_type(ty, &what)?;
+                }
+                claim(m.id, false, what)?;
+            }
+            for f in &svc.fields {
+                check_type(&f.ty, &format!("field '{}'", f.name))?;
+                if let Some(id) = f.get_id {
+                    claim(id, false, format!("getter of field '{}'", f.name))?;
+                }
+                if let Some(id) = f.set_id {
+                    claim(id, false, format!("setter of field '{}'", f.name))?;
+                }
+                if let Some(id) = f.notifier_id {
+                    claim(id, true, format!("notifier of field '{}'", f.name))?;
+                }
+            }
+            for e in &svc.events {
+                let what = format!("event '{}'", e.name);
+                svc.wire.validate_members(&e.args, &format!("{}: {}", svc.name, what))?;
+                for a in &e.args {
+                    check_type(&a.ty, &format!("{} of {}", a.name, what))?;
+                }
+                claim(e.id, true, what)?;
+                if let Some(group) = e.eventgroups.iter().find(|g| !svc.eventgroups.iter().any(|eg| eg.name == **g)) {
+                    return Err(format!("{}: event '{}' names unknown eventgroup '{}'", svc.name, e.name, group));
+                }
+            }
+            for (i, eg) in svc.eventgroups.iter().enumerate() {
+                if let Some(other) = svc.eventgroups[..i].iter().find(|other| other.name == eg.name || other.id == eg.id) {
+                    return Err(format!("{}: eventgroup '{}' ({}) collides with eventgroup '{}' ({})", svc.name, eg.name, eg.id, other.name, other.id));
+                }
+            }
+        }
+        Ok(())
+    }
+}
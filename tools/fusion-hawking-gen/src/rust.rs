@@ -0,0 +1,709 @@
+//! Rust output: a `types` module with the shared structs and one module per
+//! service, all re-exported at the top level. Item names and signatures are those
+//! of the Python generator (tools/codegen/generators/rust.py).
+
+use crate::model::{data_ids, BoolRepr, Definition, Member, ServiceDef, StringRepr, Type, WireFormat};
+use std::collections::HashSet;
+
+const HEADER: &str = "// Auto-generated by fusion-hawking-gen -- DO NOT EDIT";
+const RUNTIME: &str = "fusion_hawking::runtime";
+const RETURN_CODE: &str = "fusion_hawking::codec::ReturnCode";
+
+#[derive(Default)]
+struct Code(String);
+
+impl Code {
+    fn line(&mut self, text: impl AsRef<str>) {
+        self.0.push_str(text.as_ref());
+        self.0.push('\n');
+    }
+
+    /// Append `body` as `pub mod name { .. }`
+    fn module(&mut self, name: &str, body: &str) {
+        self.line(format!("pub mod {} {{", name));
+        for line in body.lines() {
+            if line.is_empty() { self.line("") } else { self.line(format!("    {}", line)) }
+        }
+        self.line("}");
+        self.line("#[allow(unused_imports)]");
+        self.line(format!("pub use {}::*;", name));
+    }
+}
+
+pub(crate) fn generate(def: &Definition) -> String {
+    let generator = Generator {
+        structs: def.structs.iter().map(|s| s.name.as_str()).collect(),
+        tlv: def.structs.iter().filter(|s| s.wire.tlv).map(|s| s.name.as_str()).collect(),
+    };
+    let mut code = Code::default();
+    code.line(HEADER);
+    if !def.structs.is_empty() {
+        code.line("");
+        code.module("types", &generator.types_module(def));
+    }
+    for svc in &def.services {
+        code.line("");
+        code.module(&snake(&svc.name), &generator.service_module(svc, !def.structs.is_empty()));
+    }
+    code.0
+}
+
+pub(crate) fn pascal(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect()
+}
+
+pub(crate) fn snake(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn rust_type(ty: &Type) -> String {
+    match ty {
+        Type::List(inner) => format!("Vec<{}>", rust_type(inner)),
+        Type::Named(name) => match name.as_str() {
+            "int" | "int32" => "i32".into(),
+            "int8" => "i8".into(),
+            "int16" => "i16".into(),
+            "int64" => "i64".into(),
+            "uint8" => "u8".into(),
+            "uint16" => "u16".into(),
+            "uint32" => "u32".into(),
+            "uint64" => "u64".into(),
+            "float" | "float32" => "f32".into(),
+            "float64" | "double" => "f64".into(),
+            "string" | "str" => "String".into(),
+            "bool" => "bool".into(),
+            other => pascal(other),
+        },
+    }
+}
+
+/// Rust type of a struct member or argument
+fn member_type(m: &Member) -> String {
+    if m.optional { format!("Option<{}>", rust_type(&m.ty)) } else { rust_type(&m.ty) }
+}
+
+/// `name: Type` pairs of `members`, comma separated
+fn params(members: &[Member]) -> String {
+    members.iter().map(|m| format!("{}: {}", m.name, member_type(m))).collect::<Vec<_>>().join(", ")
+}
+
+/// `&self` followed by `members` as parameters
+fn self_params(members: &[Member]) -> String {
+    if members.is_empty() { "&self".into() } else { format!("&self, {}", params(members)) }
+}
+
+fn names(members: &[Member]) -> String {
+    members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+struct Generator<'a> {
+    structs: HashSet<&'a str>,
+    /// Structs in TLV encoding, which start with a length field
+    tlv: HashSet<&'a str>,
+}
+
+impl Generator<'_> {
+    fn types_module(&self, def: &Definition) -> String {
+        let mut code = Code::default();
+        code.line("use fusion_hawking::codec::{SomeIpSerialize, SomeIpDeserialize};");
+        code.line("#[allow(unused_imports)]");
+        code.line("use fusion_hawking::codec::{SomeIpSchema, TypeSchema};");
+        code.line("#[allow(unused_imports)]");
+        code.line("use std::io::{Result, Write, Read};");
+        for s in &def.structs {
+            code.line("");
+            self.structure(&mut code, &pascal(&s.name), &s.fields, &s.wire);
+        }
+        code.0
+    }
+
+    fn service_module(&self, svc: &ServiceDef, has_types: bool) -> String {
+        let p = pascal(&svc.name);
+        let mut code = Code::default();
+        code.line(format!("// Service: {} (ID: 0x{:04x})", svc.name, svc.id));
+        code.line("");
+        code.line("use fusion_hawking::codec::{SomeIpSerialize, SomeIpDeserialize, SomeIpHeader};");
+        code.line("#[allow(unused_imports)]");
+        code.line("use fusion_hawking::codec::{SomeIpSchema, TypeSchema};");
+        code.line("#[allow(unused_imports)]");
+        code.line("use std::io::{Result, Write, Read, Cursor};");
+        code.line("#[allow(unused_imports)]");
+        code.line("use std::sync::Arc;");
+        code.line("#[allow(unused_imports)]");
+        code.line("use fusion_hawking::transport::{UdpTransport, SomeIpTransport};");
+        code.line("#[allow(unused_imports)]");
+        code.line("use std::net::SocketAddr;");
+        if has_types {
+            code.line("#[allow(unused_imports)]");
+            code.line("use super::types::*;");
+        }
+
+        for m in &svc.methods {
+            let method = pascal(&m.name);
+            code.line("");
+            self.structure(&mut code, &format!("{}{}Request", p, method), &m.args, &svc.wire);
+            let result: Vec<Member> = m.returns.iter().map(|ty| Member::new("result", ty.clone())).collect();
+            self.structure(&mut code, &format!("{}{}Response", p, method), &result, &svc.wire);
+        }
+        for e in &svc.events {
+            code.line("");
+            self.structure(&mut code, &format!("{}{}Event", p, pascal(&e.name)), &e.args, &svc.wire);
+        }
+        if !svc.eventgroups.is_empty() {
+            // Events handed to the callback of the client's `subscribe`
+            code.line("");
+            code.line("#[derive(Debug, Clone, PartialEq)]");
+            code.line(format!("pub enum {}Event {{", p));
+            for e in &svc.events {
+                let event = pascal(&e.name);
+                code.line(format!("    {}({}{}Event),", event, p, event));
+            }
+            code.line("}");
+        }
+
+        code.line("");
+        provider_trait(&mut code, svc, &p);
+        code.line("");
+        server(&mut code, svc, &p);
+        code.line("");
+        client(&mut code, svc, &p);
+        code.0
+    }
+
+    /// A struct with its codec and schema impls
+    fn structure(&self, code: &mut Code, name: &str, fields: &[Member], wire: &WireFormat) {
+        code.line("#[allow(dead_code)]");
+        code.line("#[derive(Debug, Clone, PartialEq)]");
+        // JSON views for tooling; the consuming crate forwards the feature to fusion-hawking
+        code.line("#[cfg_attr(feature = \"serde-types\", derive(serde::Serialize, serde::Deserialize))]");
+        code.line(format!("pub struct {} {{", name));
+        for f in fields {
+            code.line(format!("    pub {}: {},", f.name, member_type(f)));
+        }
+        code.line("}");
+
+        if wire.tlv {
+            self.tlv_codec(code, name, fields);
+        } else if wire.is_default_for(fields) {
+            let (writer, reader) = if fields.is_empty() { ("_writer", "_reader") } else { ("writer", "reader") };
+            code.line(format!("impl SomeIpSerialize for {} {{", name));
+            code.line(format!("    fn serialize<W: Write>(&self, {}: &mut W) -> Result<()> {{", writer));
+            for f in fields {
+                code.line(format!("        self.{}.serialize(writer)?;", f.name));
+            }
+            code.line("        Ok(())");
+            code.line("    }");
+            code.line("}");
+            code.line(format!("impl SomeIpDeserialize for {} {{", name));
+            code.line(format!("    fn deserialize<R: Read>({}: &mut R) -> Result<Self> {{", reader));
+            code.line(format!("        Ok({} {{", name));
+            for f in fields {
+                code.line(format!("            {}: <{}>::deserialize(reader)?,", f.name, member_type(f)));
+            }
+            code.line("        })");
+            code.line("    }");
+            code.line("}");
+        } else {
+            wire_codec(code, name, fields, wire);
+        }
+
+        // Schema (fingerprinted in diagnostic mode)
+        let field_list = if wire.tlv {
+            fields.iter().zip(data_ids(fields)).map(|(f, id)| format!("{}:{}@{}", f.name, schema_type(f, wire), id)).collect::<Vec<_>>().join(",")
+        } else {
+            fields.iter().map(|f| format!("{}:{}", f.name, schema_type(f, wire))).collect::<Vec<_>>().join(",")
+        };
+        let mut nested: Vec<String> = Vec::new();
+        for f in fields {
+            let base = pascal(f.ty.base());
+            if self.structs.contains(f.ty.base()) && !nested.contains(&base) {
+                nested.push(base);
+            }
+        }
+        let nested = nested.iter().map(|n| format!("<{} as SomeIpSchema>::schema()", n)).collect::<Vec<_>>().join(", ");
+        code.line(format!("impl SomeIpSchema for {} {{", name));
+        code.line("    fn schema() -> TypeSchema {");
+        code.line(format!("        TypeSchema {{ name: \"{}\", fields: \"{}\", nested: vec![{}] }}", name, field_list, nested));
+        code.line("    }");
+        code.line("}");
+    }
+
+    /// Serialize/Deserialize impls for a struct in TLV encoding
+    fn tlv_codec(&self, code: &mut Code, name: &str, fields: &[Member]) {
+        let (layout, tlv) = ("fusion_hawking::codec::layout", "fusion_hawking::codec::tlv");
+        // Members with a length field of their own are wire type 4, other complex types get one
+        let prefixed = |ty: &Type| match ty {
+            Type::List(_) => true,
+            Type::Named(n) => ty.is_string() || self.tlv.contains(n.as_str()),
+        };
+        let is_struct = |ty: &Type| self.structs.contains(ty.base());
+
+        code.line(format!("impl SomeIpSerialize for {} {{", name));
+        code.line("    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {");
+        let writer = if fields.is_empty() { "_writer" } else { "writer" };
+        code.line(format!("        {}::write_framed(4, writer, |{}| {{", layout, writer));
+        for (f, id) in fields.iter().zip(data_ids(fields)) {
+            let write = if prefixed(&f.ty) { "write_prefixed" } else if is_struct(&f.ty) { "write_struct" } else { "write_base" };
+            if f.optional {
+                code.line(format!("            if let Some(value) = &self.{} {{", f.name));
+                code.line(format!("                {}::{}(0x{:x}, value, writer)?;", tlv, write, id));
+                code.line("            }");
+            } else {
+                code.line(format!("            {}::{}(0x{:x}, &self.{}, writer)?;", tlv, write, id, f.name));
+            }
+        }
+        code.line("            Ok(())");
+        code.line("        })");
+        code.line("    }");
+        code.line("}");
+
+        code.line(format!("impl SomeIpDeserialize for {} {{", name));
+        code.line("    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {");
+        code.line(format!("        {}::read_framed(4, reader, |reader| {{", layout));
+        for f in fields {
+            code.line(format!("            let mut {} = None;", f.name));
+        }
+        // Members with unknown data IDs are skipped
+        code.line("            while !reader.is_empty() {");
+        if fields.is_empty() {
+            code.line(format!("                {}::read_member(reader)?;", tlv));
+        } else {
+            code.line(format!("                let tlv_member = {}::read_member(reader)?;", tlv));
+            for (i, (f, id)) in fields.iter().zip(data_ids(fields)).enumerate() {
+                let decode = if prefixed(&f.ty) { "decode_prefixed" } else { "decode" };
+                let keyword = if i == 0 { "                if" } else { "                } else if" };
+                code.line(format!("{} tlv_member.data_id == 0x{:x} {{", keyword, id));
+                code.line(format!("                    {} = Some(tlv_member.{}::<{}>()?);", f.name, decode, rust_type(&f.ty)));
+            }
+            code.line("                }");
+        }
+        code.line("            }");
+        let values = fields
+            .iter()
+            .map(|f| if f.optional { f.name.clone() } else { format!("{}: {}::required({}, \"{}\")?", f.name, tlv, f.name, f.name) })
+            .collect::<Vec<_>>()
+            .join(", ");
+        code.line(format!("            Ok({} {{ {} }})", name, values));
+        code.line("        })");
+        code.line("    }");
+        code.line("}");
+    }
+}
+
+/// Split fields into encoding units: a bit field of consecutive bools, or a single field. A bool
+/// aligned on its own starts a new bit field.
+fn wire_groups<'m>(fields: &'m [Member], wire: &WireFormat) -> Vec<Vec<&'m Member>> {
+    let per_field = wire.bool_width * 8;
+    let mut groups: Vec<Vec<&Member>> = Vec::new();
+    for f in fields {
+        match groups.last_mut() {
+            Some(group) if wire.bools == BoolRepr::Bits && f.ty.is_bool() && f.align.is_none() && group[0].ty.is_bool() && group.len() < per_field => group.push(f),
+            _ => groups.push(vec![f]),
+        }
+    }
+    groups
+}
+
+/// `repr::StringFormat` of `wire`, with a `length_width`-byte length field
+fn string_format(wire: &WireFormat, length_width: usize) -> String {
+    let repr = "fusion_hawking::codec::repr";
+    let encoding = match wire.strings {
+        StringRepr::Raw => "Raw",
+        StringRepr::Utf8 => "Utf8",
+        StringRepr::Utf16le => "Utf16Le",
+        StringRepr::Utf16be => "Utf16Be",
+    };
+    match wire.string_length {
+        Some(length) => format!("{}::StringFormat::fixed({}::StringEncoding::{}, {})", repr, repr, encoding, length),
+        None if length_width != 4 => format!("{}::StringFormat::new({}::StringEncoding::{}).with_length_width({})", repr, repr, encoding, length_width),
+        None => format!("{}::StringFormat::new({}::StringEncoding::{})", repr, repr, encoding),
+    }
+}
+
+/// Serialize/Deserialize impls for a struct with non-default bool, string or length field representation,
+/// or aligned members
+fn wire_codec(code: &mut Code, name: &str, fields: &[Member], wire: &WireFormat) {
+    let (repr, layout) = ("fusion_hawking::codec::repr", "fusion_hawking::codec::layout");
+    let width = wire.bool_width;
+    let groups = wire_groups(fields, wire);
+    let string_format = |f: &Member| (f.ty.is_string() && wire.custom_strings()).then(|| string_format(wire, f.length_width(wire)));
+    let resized = |f: &Member| (f.has_length() && f.length_width(wire) != 4).then(|| f.length_width(wire));
+    let aligned = fields.iter().any(|f| f.align(wire) > 1);
+
+    code.line(format!("impl SomeIpSerialize for {} {{", name));
+    code.line("    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {");
+    if aligned {
+        code.line(format!("        let writer = &mut {}::Counted::new(writer);", layout));
+    }
+    for group in &groups {
+        let f = group[0];
+        if f.align(wire) > 1 {
+            code.line(format!("        {}::write_padding(writer, {})?;", layout, f.align(wire)));
+        }
+        if let Some(format) = string_format(f) {
+            code.line(format!("        {}::write_string(&self.{}, &{}, writer)?;", repr, f.name, format));
+        } else if let Some(length_width) = resized(f) {
+            code.line(format!("        {}::write_resized(&self.{}, {}, writer)?;", layout, f.name, length_width));
+        } else if !f.ty.is_bool() {
+            code.line(format!("        self.{}.serialize(writer)?;", f.name));
+        } else if wire.bools == BoolRepr::Bits {
+            let flags = group.iter().map(|x| format!("self.{}", x.name)).collect::<Vec<_>>().join(", ");
+            code.line(format!("        {}::write_bits(&[{}], {}, writer)?;", repr, flags, width));
+        } else {
+            code.line(format!("        {}::write_bool(self.{}, {}, writer)?;", repr, f.name, width));
+        }
+    }
+    code.line("        Ok(())");
+    code.line("    }");
+    code.line("}");
+
+    code.line(format!("impl SomeIpDeserialize for {} {{", name));
+    code.line("    fn deserialize<R: Read>(reader: &mut R) -> Result<Self> {");
+    if aligned {
+        code.line(format!("        let reader = &mut {}::Counted::new(reader);", layout));
+    }
+    for (i, group) in groups.iter().enumerate() {
+        let f = group[0];
+        if f.align(wire) > 1 {
+            code.line(format!("        {}::skip_padding(reader, {})?;", layout, f.align(wire)));
+        }
+        if let Some(format) = string_format(f) {
+            code.line(format!("        let {} = {}::read_string(&{}, reader)?;", f.name, repr, format));
+        } else if let Some(length_width) = resized(f) {
+            code.line(format!("        let {} = {}::read_resized::<{}, _>({}, reader)?;", f.name, layout, rust_type(&f.ty), length_width));
+        } else if !f.ty.is_bool() {
+            code.line(format!("        let {} = <{}>::deserialize(reader)?;", f.name, member_type(f)));
+        } else if wire.bools == BoolRepr::Bits {
+            code.line(format!("        let bits_{} = {}::read_bits({}, {}, reader)?;", i, repr, group.len(), width));
+            for (j, x) in group.iter().enumerate() {
+                code.line(format!("        let {} = bits_{}[{}];", x.name, i, j));
+            }
+        } else {
+            code.line(format!("        let {} = {}::read_bool({}, reader)?;", f.name, repr, width));
+        }
+    }
+    code.line(format!("        Ok({} {{ {} }})", name, names(fields)));
+    code.line("    }");
+    code.line("}");
+}
+
+/// Field type for the schema fingerprint, with its alignment
+fn schema_type(f: &Member, wire: &WireFormat) -> String {
+    let ty = encoded_type(f, wire);
+    match f.align(wire) {
+        1 => ty,
+        align => format!("{}[align{}]", ty, align),
+    }
+}
+
+/// Field type with non-default bool, string and length field encodings
+fn encoded_type(f: &Member, wire: &WireFormat) -> String {
+    let rust = member_type(f);
+    let length = (f.has_length() && f.length_width(wire) != 4).then(|| format!("len{}", f.length_width(wire) * 8));
+    if f.ty.is_bool() && wire.custom_bools() {
+        let kind = if wire.bools == BoolRepr::Bits { "bits" } else { "u" };
+        format!("{}[{}{}]", rust, kind, wire.bool_width * 8)
+    } else if f.ty.is_string() && wire.custom_strings() {
+        let encoding = format!("{:?}", wire.strings).to_lowercase();
+        match (wire.string_length, length) {
+            (Some(fixed), _) => format!("{}[{};{}]", rust, encoding, fixed),
+            (None, Some(length)) => format!("{}[{},{}]", rust, encoding, length),
+            (None, None) => format!("{}[{}]", rust, encoding),
+        }
+    } else if let Some(length) = length {
+        format!("{}[{}]", rust, length)
+    } else {
+        rust
+    }
+}
+
+fn provider_trait(code: &mut Code, svc: &ServiceDef, p: &str) {
+    code.line("#[allow(dead_code)]");
+    code.line(format!("pub trait {}Provider: Send + Sync {{", p));
+    for m in &svc.methods {
+        let ret = m.returns.as_ref().map(|ty| format!(" -> {}", rust_type(ty))).unwrap_or_default();
+        code.line(format!("    fn {}({}){};", m.name, self_params(&m.args), ret));
+    }
+    // Fields are held by the provider; the server answers getters and setters from them
+    for f in &svc.fields {
+        code.line(format!("    fn {}(&self) -> &{}::FieldHandle<{}>;", f.name, RUNTIME, rust_type(&f.ty)));
+    }
+    code.line("}");
+}
+
+fn server(code: &mut Code, svc: &ServiceDef, p: &str) {
+    code.line("#[allow(dead_code)]");
+    code.line(format!("pub struct {}Server<T> {{", p));
+    code.line("    provider: Arc<T>,");
+    code.line("}");
+    code.line("#[allow(dead_code)]");
+    code.line(format!("impl {}Server<()> {{", p));
+    code.line(format!("    pub const SERVICE_ID: u16 = 0x{:04x};", svc.id));
+    code.line(format!("    pub const MAJOR_VERSION: u32 = {};", svc.major_version));
+    code.line(format!("    pub const MINOR_VERSION: u32 = {};", svc.minor_version));
+    for m in &svc.methods {
+        code.line(format!("    pub const METHOD_{}: u16 = 0x{:04x};", m.name.to_uppercase(), m.id));
+    }
+    for e in &svc.events {
+        code.line(format!("    pub const EVENT_{}: u16 = 0x{:04x};", e.name.to_uppercase(), e.id));
+    }
+    for eg in &svc.eventgroups {
+        code.line(format!("    pub const EVENTGROUP_{}: u16 = {};", eg.name.to_uppercase(), eg.id));
+    }
+    for f in &svc.fields {
+        let upper = f.name.to_uppercase();
+        if let Some(id) = f.get_id {
+            code.line(format!("    pub const FIELD_GET_{}: u16 = 0x{:04x};", upper, id));
+        }
+        if let Some(id) = f.set_id {
+            code.line(format!("    pub const FIELD_SET_{}: u16 = 0x{:04x};", upper, id));
+        }
+        if let Some(id) = f.notifier_id {
+            code.line(format!("    pub const EVENT_{}_NOTIFY: u16 = 0x{:04x};", upper, id));
+        }
+    }
+    code.line("}");
+
+    code.line("");
+    code.line("#[allow(dead_code)]");
+    code.line(format!("impl {}Server<()> {{", p));
+    code.line("    /// Register payload schemas of all methods and events for diagnostic mode.");
+    code.line(format!("    pub fn register_schemas(runtime: &{}::SomeIpRuntime) {{", RUNTIME));
+    for m in &svc.methods {
+        let (upper, method) = (m.name.to_uppercase(), pascal(&m.name));
+        code.line(format!("        runtime.register_schema(Self::SERVICE_ID, Self::METHOD_{}, false, {}{}Request::schema());", upper, p, method));
+        code.line(format!("        runtime.register_schema(Self::SERVICE_ID, Self::METHOD_{}, true, {}{}Response::schema());", upper, p, method));
+    }
+    for e in &svc.events {
+        code.line(format!("        runtime.register_schema(Self::SERVICE_ID, Self::EVENT_{}, false, {}{}Event::schema());", e.name.to_uppercase(), p, pascal(&e.name)));
+    }
+    code.line("    }");
+    // Events go to the subscribers of the eventgroups declared to carry them (config or offer_eventgroup)
+    for e in &svc.events {
+        let args: String = e.args.iter().map(|a| format!(", {}: {}", a.name, member_type(a))).collect();
+        code.line("");
+        code.line(format!("    /// Send the {} event; returns the number of subscribers reached.", e.name));
+        code.line(format!("    pub fn fire_{}(runtime: &{}::SomeIpRuntime{}) -> std::io::Result<usize> {{", e.name, RUNTIME, args));
+        code.line(format!("        let event = {}{}Event {{ {} }};", p, pascal(&e.name), names(&e.args)));
+        code.line("        let mut payload = Vec::new();");
+        code.line("        event.serialize(&mut payload)?;");
+        code.line(format!("        Ok(runtime.fire_event(Self::SERVICE_ID, Self::EVENT_{}, &payload))", e.name.to_uppercase()));
+        code.line("    }");
+    }
+    for f in &svc.fields {
+        let ty = rust_type(&f.ty);
+        let notifier = match f.notifier_id {
+            Some(_) => format!("Some(Self::EVENT_{}_NOTIFY)", f.name.to_uppercase()),
+            None => "None".into(),
+        };
+        code.line("");
+        code.line(format!("    /// Field handle for {}, to be returned by the provider.", f.name));
+        code.line(format!("    pub fn new_{}(initial: {}) -> {}::FieldHandle<{}> {{", f.name, ty, RUNTIME, ty));
+        code.line(format!("        {}::FieldHandle::new(Self::SERVICE_ID, {}, initial)", RUNTIME, notifier));
+        code.line("    }");
+    }
+    code.line("}");
+
+    code.line("");
+    code.line(format!("impl<T: {}Provider> {}Server<T> {{", p, p));
+    code.line("    #[allow(dead_code)]");
+    code.line("    pub fn new(provider: Arc<T>) -> Self { Self { provider } }");
+    if !svc.fields.is_empty() {
+        code.line("");
+        code.line("    /// Notify field changes through `runtime`, and send current values to new subscribers.");
+        code.line("    #[allow(dead_code)]");
+        code.line(format!("    pub fn bind_fields(&self, runtime: &Arc<{}::SomeIpRuntime>) -> std::io::Result<()> {{", RUNTIME));
+        for f in &svc.fields {
+            code.line(format!("        self.provider.{}().bind(runtime)?;", f.name));
+        }
+        code.line("        Ok(())");
+        code.line("    }");
+    }
+    code.line("}");
+
+    let this = format!("{}Server::<()>", p);
+    code.line(format!("impl<T: {}Provider> {}::RequestHandler for {}Server<T> {{", p, RUNTIME, p));
+    code.line(format!("    fn service_id(&self) -> u16 {{ {}::SERVICE_ID }}", this));
+    code.line(format!("    fn major_version(&self) -> u8 {{ {}::MAJOR_VERSION as u8 }}", this));
+    code.line(format!("    fn minor_version(&self) -> u32 {{ {}::MINOR_VERSION }}", this));
+    // Unknown methods and undecodable requests come back as errors, answered with an ERROR by the runtime
+    code.line(format!("    fn handle(&self, header: &SomeIpHeader, _payload: &[u8]) -> {}::HandlerResult {{", RUNTIME));
+    code.line(format!("        if header.service_id != {}::SERVICE_ID {{ return Err({}::UnknownService); }}", this, RETURN_CODE));
+    code.line("        match header.method_id {");
+    for m in &svc.methods {
+        let method = pascal(&m.name);
+        code.line(format!("            {}::METHOD_{} => {{", this, m.name.to_uppercase()));
+        code.line("                let mut cursor = Cursor::new(_payload);");
+        let binding = if m.args.is_empty() { "_req" } else { "req" };
+        code.line(format!("                let {} = {}{}Request::deserialize(&mut cursor).map_err(|_| {}::MalformedMessage)?;", binding, p, method, RETURN_CODE));
+        let call_args = m.args.iter().map(|a| format!("req.{}", a.name)).collect::<Vec<_>>().join(", ");
+        if m.fire_and_forget {
+            // Called with REQUEST_NO_RETURN: there is no response to build
+            code.line(format!("                self.provider.{}({});", m.name, call_args));
+            code.line("                Ok(None)");
+            code.line("            },");
+            continue;
+        }
+        if m.returns.is_some() {
+            code.line(format!("                let result = self.provider.{}({});", m.name, call_args));
+            code.line(format!("                let resp = {}{}Response {{ result }};", p, method));
+        } else {
+            code.line(format!("                self.provider.{}({});", m.name, call_args));
+            code.line(format!("                let resp = {}{}Response {{}};", p, method));
+        }
+        code.line("                let mut out = Vec::new();");
+        code.line(format!("                resp.serialize(&mut out).map_err(|_| {}::NotOk)?;", RETURN_CODE));
+        code.line("                Ok(Some(out))");
+        code.line("            },");
+    }
+    for f in &svc.fields {
+        // Getters and setters reply with the value held after the call
+        let upper = f.name.to_uppercase();
+        if f.get_id.is_some() {
+            code.line(format!("            {}::FIELD_GET_{} => {{", this, upper));
+            code.line("                let mut out = Vec::new();");
+            code.line(format!("                self.provider.{}().get().serialize(&mut out).map_err(|_| {}::NotOk)?;", f.name, RETURN_CODE));
+            code.line("                Ok(Some(out))");
+            code.line("            },");
+        }
+        if f.set_id.is_some() {
+            code.line(format!("            {}::FIELD_SET_{} => {{", this, upper));
+            code.line(format!("                let value = <{}>::deserialize(&mut Cursor::new(_payload)).map_err(|_| {}::MalformedMessage)?;", rust_type(&f.ty), RETURN_CODE));
+            code.line(format!("                let field = self.provider.{}();", f.name));
+            code.line(format!("                field.set(value).map_err(|_| {}::NotOk)?;", RETURN_CODE));
+            code.line("                let mut out = Vec::new();");
+            code.line(format!("                field.get().serialize(&mut out).map_err(|_| {}::NotOk)?;", RETURN_CODE));
+            code.line("                Ok(Some(out))");
+            code.line("            },");
+        }
+    }
+    code.line(format!("            _ => Err({}::UnknownMethod)", RETURN_CODE));
+    code.line("        }");
+    code.line("    }");
+    code.line("}");
+}
+
+fn client(code: &mut Code, svc: &ServiceDef, p: &str) {
+    code.line("#[allow(dead_code)]");
+    code.line(format!("pub struct {}Client {{", p));
+    code.line(format!("    context: {}::ClientContext,", RUNTIME));
+    code.line("}");
+    code.line(format!("impl {}::ServiceClient for {}Client {{", RUNTIME, p));
+    code.line(format!("    const SERVICE_ID: u16 = 0x{:04x};", svc.id));
+    code.line(format!("    fn new(context: {}::ClientContext) -> Self {{ Self {{ context }} }}", RUNTIME));
+    code.line("}");
+
+    code.line("#[allow(dead_code)]");
+    code.line(format!("impl {}Client {{", p));
+    code.line(format!("    pub const SERVICE_ID: u16 = 0x{:04x};", svc.id));
+    code.line(format!("    pub const MAJOR_VERSION: u32 = {};", svc.major_version));
+    code.line(format!("    pub const MINOR_VERSION: u32 = {};", svc.minor_version));
+    for eg in &svc.eventgroups {
+        code.line(format!("    pub const EVENTGROUP_{}: u16 = {};", eg.name.to_uppercase(), eg.id));
+    }
+    code.line("    /// Local address requests are sent from");
+    code.line("    pub fn local_addr(&self) -> std::io::Result<SocketAddr> { self.context.local_addr() }");
+    for m in &svc.methods {
+        let method = pascal(&m.name);
+        let method_const = format!("{}Server::<()>::METHOD_{}", p, m.name.to_uppercase());
+        if m.fire_and_forget {
+            // No response is expected: the runtime sends REQUEST_NO_RETURN with its Client ID and the next Session ID
+            code.line(format!("    pub fn {}({}) -> std::io::Result<()> {{", m.name, self_params(&m.args)));
+            code.line(format!("        let req = {}{}Request {{ {} }};", p, method, names(&m.args)));
+            code.line("        let mut payload = Vec::new();");
+            code.line("        req.serialize(&mut payload)?;");
+            code.line(format!("        self.context.send({}, &payload)", method_const));
+            code.line("    }");
+            continue;
+        }
+        // Request/response: the runtime assigns the session, matches the response and applies the QoS timeout
+        let ret = m.returns.as_ref().map(rust_type).unwrap_or_else(|| "()".into());
+        code.line(format!("    pub async fn {}({}) -> std::io::Result<{}> {{", m.name, self_params(&m.args), ret));
+        code.line(format!("        let req = {}{}Request {{ {} }};", p, method, names(&m.args)));
+        code.line("        let mut payload = Vec::new();");
+        code.line("        req.serialize(&mut payload)?;");
+        if m.returns.is_some() {
+            code.line(format!("        let response = self.context.request({}, &payload).await?;", method_const));
+            code.line(format!("        let res = {}{}Response::deserialize(&mut Cursor::new(&response[..]))?;", p, method));
+            code.line("        Ok(res.result)");
+        } else {
+            code.line(format!("        self.context.request({}, &payload).await?;", method_const));
+            code.line("        Ok(())");
+        }
+        code.line("    }");
+    }
+    for f in &svc.fields {
+        let (ty, upper) = (rust_type(&f.ty), f.name.to_uppercase());
+        if f.get_id.is_some() {
+            code.line(format!("    pub async fn get_{}(&self) -> std::io::Result<{}> {{", f.name, ty));
+            code.line(format!("        let response = self.context.request({}Server::<()>::FIELD_GET_{}, &[]).await?;", p, upper));
+            code.line(format!("        <{}>::deserialize(&mut Cursor::new(&response[..]))", ty));
+            code.line("    }");
+        }
+        if f.set_id.is_some() {
+            // The provider replies with the value it holds afterwards, which may differ from the one sent
+            code.line(format!("    pub async fn set_{}(&self, value: {}) -> std::io::Result<{}> {{", f.name, ty, ty));
+            code.line("        let mut payload = Vec::new();");
+            code.line("        value.serialize(&mut payload)?;");
+            code.line(format!("        let response = self.context.request({}Server::<()>::FIELD_SET_{}, &payload).await?;", p, upper));
+            code.line(format!("        <{}>::deserialize(&mut Cursor::new(&response[..]))", ty));
+            code.line("    }");
+        }
+    }
+    for e in &svc.events {
+        // Decoded on the runtime's workers if it has any; `on_object_detected`, not `on_on_object_detected`
+        let callback = if e.name.starts_with("on_") { e.name.clone() } else { format!("on_{}", e.name) };
+        code.line(format!("    /// Call `callback` with each {} event received.", e.name));
+        code.line(format!("    pub fn {}(runtime: &{}::SomeIpRuntime, callback: impl Fn({}{}Event) + Send + Sync + 'static) {{", callback, RUNTIME, p, pascal(&e.name)));
+        code.line(format!("        runtime.on_event(Self::SERVICE_ID, {}Server::<()>::EVENT_{}, callback);", p, e.name.to_uppercase()));
+        code.line("    }");
+    }
+    if !svc.eventgroups.is_empty() {
+        // Instance and interface come from the required service's configuration
+        code.line("    /// Subscribe to `eventgroup` (an `EVENTGROUP_` constant) as configured for the required");
+        code.line("    /// service and call `callback` with each event it carries; a `ttl` of 0 unsubscribes.");
+        code.line(format!("    pub fn subscribe(&self, eventgroup: u16, ttl: u32, callback: impl Fn({}Event) + Send + Sync + 'static) -> std::io::Result<()> {{", p));
+        code.line("        let callback = Arc::new(callback);");
+        code.line("        match eventgroup {");
+        for eg in &svc.eventgroups {
+            code.line(format!("            Self::EVENTGROUP_{} => {{", eg.name.to_uppercase()));
+            for e in svc.events.iter().filter(|e| e.eventgroups.contains(&eg.name)) {
+                code.line("                let callback = callback.clone();");
+                code.line(format!("                self.context.on_event({}Server::<()>::EVENT_{}, move |event| callback({}Event::{}(event)))?;", p, e.name.to_uppercase(), p, pascal(&e.name)));
+            }
+            code.line("            }");
+        }
+        code.line("            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!(\"unknown eventgroup {}\", eventgroup))),");
+        code.line("        }");
+        code.line("        self.context.subscribe(eventgroup, ttl)");
+        code.line("    }");
+    }
+    code.line("}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(pascal("on_track_updated"), "OnTrackUpdated");
+        assert_eq!(pascal("RadarObject"), "RadarObject");
+        assert_eq!(snake("RadarService"), "radar_service");
+        assert_eq!(rust_type(&Type::List(Box::new(Type::Named("radar_object".into())))), "Vec<RadarObject>");
+        assert_eq!(rust_type(&Type::Named("double".into())), "f64");
+    }
+}
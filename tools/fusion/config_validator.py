@@ -4,6 +4,10 @@ import re
 from typing import List, Dict, Tuple, Any
 
 # --- JSON Schema Definition ---
+LOG_LEVEL = {"type": "string", "enum": [
+    "Debug", "Info", "Warn", "Error", "debug", "info", "warn", "error", "DEBUG", "INFO", "WARN", "ERROR"
+]}
+
 SCHEMA = {
     "type": "object",
     "required": ["instances", "interfaces"],
@@ -16,6 +20,7 @@ SCHEMA = {
                     "required": ["name", "endpoints"],
                     "properties": {
                         "name": {"type": "string"},
+                        "subnet": {"type": "string"},
                         "endpoints": {
                             "type": "object",
                             "patternProperties": {
@@ -26,7 +31,12 @@ SCHEMA = {
                                         "ip": {"type": "string"},
                                         "port": {"type": "integer"},
                                         "protocol": {"type": "string", "enum": ["udp", "tcp"]},
-                                        "version": {"type": "integer", "enum": [4, 6]}
+                                        "version": {"type": "integer", "enum": [4, 6]},
+                                        "magic_cookie_interval_ms": {"type": "integer"},
+                                        "mtu": {"type": "integer", "minimum": 36, "maximum": 65507},
+                                        "tp_pacing_us": {"type": "integer"},
+                                        "tp_burst": {"type": "integer", "minimum": 1},
+                                        "tp_adaptive": {"type": "boolean"}
                                     }
                                 }
                             }
@@ -51,6 +61,21 @@ SCHEMA = {
                 }
             }
         },
+        "qos_profiles": {
+            "type": "object",
+            "patternProperties": {
+                "^.*$": {
+                    "type": "object",
+                    "properties": {
+                        "request_timeout_ms": {"type": "integer"},
+                        "transport": {"type": "string", "enum": ["udp", "tcp"]},
+                        "tp_pacing_us": {"type": "integer"},
+                        "priority": {"type": "integer"}
+                    },
+                    "additionalProperties": False
+                }
+            }
+        },
         "instances": {
             "type": "object",
             "patternProperties": {
@@ -74,6 +99,65 @@ SCHEMA = {
                                         "instance_id": {"type": "integer"},
                                         "major_version": {"type": "integer"},
                                         "minor_version": {"type": "integer"},
+                                        "deadline_ms": {"type": "integer"},
+                                        "method_deadlines_ms": {
+                                            "type": "object",
+                                            "patternProperties": {
+                                                "^.*$": {"type": "integer"}
+                                            }
+                                        },
+                                        "isolate_after_overruns": {"type": "integer"},
+                                        "qos": {"type": "string"},
+                                        "echo": {
+                                            "type": "object",
+                                            "properties": {
+                                                "heartbeat_ms": {"type": "integer"},
+                                                "heartbeat_eventgroup": {"type": "integer"}
+                                            }
+                                        },
+                                        "extended_info": {
+                                            "type": "object",
+                                            "patternProperties": {
+                                                "^.*$": {"type": "string"}
+                                            }
+                                        },
+                                        "access": {
+                                            "type": "array",
+                                            "items": {
+                                                "type": "object",
+                                                "required": ["source"],
+                                                "properties": {
+                                                    "source": {"type": "string"},
+                                                    "methods": {"type": "array", "items": {"type": "integer"}},
+                                                    "eventgroups": {"type": "array", "items": {"type": "integer"}}
+                                                },
+                                                "additionalProperties": False
+                                            }
+                                        },
+                                        "quota": {
+                                            "type": "object",
+                                            "properties": {
+                                                "max_in_flight": {"type": "integer"},
+                                                "max_subscribers": {"type": "integer"},
+                                                "max_queue_bytes": {"type": "integer"}
+                                            },
+                                            "additionalProperties": False
+                                        },
+                                        "e2e": {
+                                            "type": "object",
+                                            "patternProperties": {
+                                                "^.*$": {
+                                                    "type": "object",
+                                                    "required": ["profile", "data_id"],
+                                                    "properties": {
+                                                        "profile": {"type": "string", "enum": ["P01", "P04"]},
+                                                        "data_id": {"type": "integer"},
+                                                        "max_delta_counter": {"type": "integer"}
+                                                    },
+                                                    "additionalProperties": False
+                                                }
+                                            }
+                                        },
                                         "offer_on": {
                                             "type": "object",
                                             "patternProperties": {
@@ -92,6 +176,7 @@ SCHEMA = {
                                                             "type": "array",
                                                             "items": {"type": "integer"}
                                                         },
+                                                        "multicast_threshold": {"type": "integer", "minimum": 0},
                                                         "multicast": {
                                                             "type": "object",
                                                             "patternProperties": {
@@ -123,19 +208,157 @@ SCHEMA = {
                                             "items": {"type": "string"}
                                         },
                                         "protocol": {"type": "string", "enum": ["udp", "tcp"]},
+                                        "qos": {"type": "string"},
+                                        "reorder": {
+                                            "type": "object",
+                                            "properties": {
+                                                "window": {"type": "integer"},
+                                                "max_hold_ms": {"type": "integer"}
+                                            }
+                                        },
+                                        "store_and_forward": {
+                                            "type": "object",
+                                            "properties": {
+                                                "max_queued": {"type": "integer"},
+                                                "ttl_ms": {"type": "integer"}
+                                            }
+                                        },
+                                        "local_endpoint": {"type": "string"},
+                                        "eventgroups": {
+                                            "type": "object",
+                                            "patternProperties": {
+                                                "^.*$": {
+                                                    "type": "object",
+                                                    "required": ["eventgroup_id"],
+                                                    "properties": {
+                                                        "eventgroup_id": {"type": "integer"},
+                                                        "reliable": {"type": "boolean"},
+                                                        "subscribe": {"type": "boolean"},
+                                                        "ttl": {"type": "integer"},
+                                                        "interface": {"type": "string"}
+                                                    },
+                                                    "additionalProperties": False
+                                                }
+                                            }
+                                        },
+                                        "subscription_retry": {
+                                            "type": "object",
+                                            "properties": {
+                                                "ack_timeout_ms": {"type": "integer"},
+                                                "max_retries": {"type": "integer"},
+                                                "retry_delay_ms": {"type": "integer"},
+                                                "retry_on_nack": {"type": "boolean"}
+                                            },
+                                            "additionalProperties": False
+                                        },
+                                        "request_limit": {
+                                            "type": "object",
+                                            "required": ["max_in_flight"],
+                                            "properties": {
+                                                "max_in_flight": {"type": "integer", "minimum": 1},
+                                                "wait": {"type": "boolean"}
+                                            },
+                                            "additionalProperties": False
+                                        },
+                                        "request_retry": {
+                                            "type": "object",
+                                            "properties": {
+                                                "timeout_ms": {"type": "integer"},
+                                                "max_retries": {"type": "integer"},
+                                                "backoff_ms": {"type": "integer"},
+                                                "max_backoff_ms": {"type": "integer"},
+                                                "methods": {
+                                                    "type": "object",
+                                                    "patternProperties": {
+                                                        "^.*$": {
+                                                            "type": "object",
+                                                            "properties": {
+                                                                "idempotent": {"type": "boolean"}
+                                                            },
+                                                            "additionalProperties": False
+                                                        }
+                                                    }
+                                                }
+                                            },
+                                            "additionalProperties": False
+                                        },
+                                        "e2e": {
+                                            "type": "object",
+                                            "patternProperties": {
+                                                "^.*$": {
+                                                    "type": "object",
+                                                    "required": ["profile", "data_id"],
+                                                    "properties": {
+                                                        "profile": {"type": "string", "enum": ["P01", "P04"]},
+                                                        "data_id": {"type": "integer"},
+                                                        "max_delta_counter": {"type": "integer"}
+                                                    },
+                                                    "additionalProperties": False
+                                                }
+                                            }
+                                        },
                                         "preferred_interface": {"type": "string"} # Deprecated but allow for now? No, stick to design.
                                     },
                                     "additionalProperties": False
                                 }
                             }
                         },
+                        "shutdown_report_path": {"type": "string"},
+                        "client_id": {"type": "integer"},
+                        "worker_threads": {"type": "integer"},
+                        "shared_memory": {
+                            "type": "object",
+                            "properties": {
+                                "ring_size_kb": {"type": "integer"}
+                            },
+                            "additionalProperties": False
+                        },
+                        "tp": {
+                            "type": "object",
+                            "properties": {
+                                "reassembly_timeout_ms": {"type": "integer"},
+                                "max_buffer_bytes": {"type": "integer"}
+                            },
+                            "additionalProperties": False
+                        },
+                        "tcp": {
+                            "type": "object",
+                            "properties": {
+                                "reconnect_backoff_min_ms": {"type": "integer"},
+                                "reconnect_backoff_max_ms": {"type": "integer"},
+                                "magic_cookie_interval_ms": {"type": "integer"}
+                            },
+                            "additionalProperties": False
+                        },
+                        "logging": {
+                            "type": "object",
+                            "properties": {
+                                "level": LOG_LEVEL,
+                                "components": {"type": "object", "patternProperties": {"^.+$": LOG_LEVEL}}
+                            },
+                            "additionalProperties": False
+                        },
+                        "metrics": {
+                            "type": "object",
+                            "required": ["port"],
+                            "properties": {
+                                "bind": {"type": "string"},
+                                "port": {"type": "integer"}
+                            },
+                            "additionalProperties": False
+                        },
                         "sd": {
                             "type": "object",
                             "properties": {
                                 "cycle_offer_ms": {"type": "integer"},
                                 "request_response_delay_ms": {"type": "integer"},
+                                "request_response_delay_min_ms": {"type": "integer"},
+                                "request_response_delay_max_ms": {"type": "integer"},
                                 "request_timeout_ms": {"type": "integer"},
-                                "multicast_hops": {"type": "integer"}
+                                "multicast_hops": {"type": "integer"},
+                                "max_packets_per_poll": {"type": "integer"},
+                                "max_backlog": {"type": "integer"},
+                                "subscription_renewal_percent": {"type": "integer"}
                             }
                         }
                     }
@@ -211,6 +434,25 @@ def validate_json_structure(data: Any, schema: Dict[str, Any], path: str = "") -
 
     return errors
 
+def _validate_e2e(entries: Dict[str, Any], owner: str) -> List[str]:
+    """Checks the `e2e` map of a providing or required service entry."""
+    errors = []
+    for key, cfg in entries.items():
+        try:
+            msg_id = int(key, 16) if key.lower().startswith("0x") else int(key)
+        except ValueError:
+            msg_id = -1
+        if not 0 <= msg_id <= 0xFFFF:
+            errors.append(f"{owner} e2e key '{key}' is not a method or event ID")
+        max_id = 0xFFFF if cfg["profile"] == "P01" else 0xFFFFFFFF
+        if not 0 <= cfg["data_id"] <= max_id:
+            errors.append(f"{owner} e2e '{key}' data_id exceeds the range of profile {cfg['profile']}")
+        max_delta = 14 if cfg["profile"] == "P01" else 0xFFFF
+        if not 1 <= cfg.get("max_delta_counter", 1) <= max_delta:
+            errors.append(f"{owner} e2e '{key}' max_delta_counter must be between 1 and {max_delta}")
+    return errors
+
+
 def validate_config(data: Dict[str, Any]) -> List[str]:
     """
     Validates the master configuration dictionary.
@@ -233,6 +475,7 @@ def validate_config(data: Dict[str, Any]) -> List[str]:
     
     interfaces = data.get("interfaces", {})
     instances = data.get("instances", {})
+    qos_profiles = data.get("qos_profiles", {})
 
     # 1. Validate Interfaces block
     for iface_key, iface_cfg in interfaces.items():
@@ -263,18 +506,35 @@ def validate_config(data: Dict[str, Any]) -> List[str]:
                 if srv_ep_name not in eps:
                     errors.append(f"Interface '{iface_key}' server {key} references unknown endpoint '{srv_ep_name}'")
 
-        # Validate IP addresses in endpoints
+        if "subnet" in iface_cfg:
+            try:
+                ipaddress.ip_network(iface_cfg["subnet"], strict=False)
+            except ValueError:
+                errors.append(f"Interface '{iface_key}' has invalid subnet: '{iface_cfg['subnet']}'")
+
+        # Validate IP addresses in endpoints ('auto' takes the address of the host interface)
         for ep_name, ep_cfg in eps.items():
             try:
-                ipaddress.ip_address(ep_cfg["ip"])
+                if ep_cfg["ip"] != "auto":
+                    ipaddress.ip_address(ep_cfg["ip"])
             except ValueError:
                 errors.append(f"Interface '{iface_key}' endpoint '{ep_name}' has invalid IP: '{ep_cfg['ip']}'")
+            if "magic_cookie_interval_ms" in ep_cfg and ep_cfg["protocol"] != "tcp":
+                errors.append(f"Interface '{iface_key}' endpoint '{ep_name}' sets magic_cookie_interval_ms but is not a tcp endpoint")
+            if ep_cfg.get("mtu", 36) < 36:
+                errors.append(f"Interface '{iface_key}' endpoint '{ep_name}' has mtu {ep_cfg['mtu']}, below the minimum of 36 bytes")
+            if ep_cfg.get("mtu", 36) > 65507:
+                errors.append(f"Interface '{iface_key}' endpoint '{ep_name}' has mtu {ep_cfg['mtu']}, above the maximum of 65507 bytes")
+            if ep_cfg.get("tp_burst", 1) < 1:
+                errors.append(f"Interface '{iface_key}' endpoint '{ep_name}' has tp_burst {ep_cfg['tp_burst']}, below the minimum of 1 segment")
 
     # 2. Validate Instances block
     # (service_id, instance_id, major_version) -> list of providers
     provided_services: Dict[Tuple[int, int, int], List[str]] = collections.defaultdict(list)
     # (iface, ip, port, protocol) -> list of users
     used_ports: Dict[Tuple[str, str, int, str], List[str]] = collections.defaultdict(list)
+    # client_id -> instances sending requests with it
+    client_ids: Dict[int, List[str]] = collections.defaultdict(list)
 
     for inst_name, inst_cfg in instances.items():
         # Validate SD Unicast Bindings
@@ -291,6 +551,43 @@ def validate_config(data: Dict[str, Any]) -> List[str]:
                 if ep["port"] != 0:
                     used_ports[(iface_key, ep["ip"], ep["port"], ep["protocol"].lower())].append(f"{inst_name}:SD")
 
+        shm_cfg = inst_cfg.get("shared_memory")
+        if shm_cfg is not None and shm_cfg.get("ring_size_kb", 1024) <= 0:
+            errors.append(f"Instance '{inst_name}' shared_memory ring_size_kb must be positive")
+
+        if "client_id" in inst_cfg:
+            client_id = inst_cfg["client_id"]
+            if not 0x0001 <= client_id <= 0xFFFF:
+                errors.append(f"Instance '{inst_name}' client_id must be between 0x0001 and 0xFFFF")
+            client_ids[client_id].append(inst_name)
+
+        if inst_cfg.get("worker_threads", 1) <= 0:
+            errors.append(f"Instance '{inst_name}' worker_threads must be positive")
+
+        for limit, value in inst_cfg.get("tp", {}).items():
+            if value <= 0:
+                errors.append(f"Instance '{inst_name}' tp {limit} must be positive")
+
+        inst_tcp = inst_cfg.get("tcp", {})
+        if inst_tcp.get("reconnect_backoff_min_ms", 100) > inst_tcp.get("reconnect_backoff_max_ms", 10000):
+            errors.append(f"Instance '{inst_name}' tcp reconnect_backoff_min_ms exceeds reconnect_backoff_max_ms")
+
+        inst_metrics = inst_cfg.get("metrics")
+        if isinstance(inst_metrics, dict):
+            try:
+                ipaddress.ip_address(inst_metrics.get("bind", "127.0.0.1"))
+            except ValueError:
+                errors.append(f"Instance '{inst_name}' metrics bind is not an IP address")
+
+        inst_sd = inst_cfg.get("sd", {})
+        if inst_sd.get("request_response_delay_min_ms", 10) > inst_sd.get("request_response_delay_max_ms", 100):
+            errors.append(f"Instance '{inst_name}' sd request_response_delay_min_ms exceeds request_response_delay_max_ms")
+        for limit in ("max_packets_per_poll", "max_backlog"):
+            if inst_sd.get(limit, 1) <= 0:
+                errors.append(f"Instance '{inst_name}' sd {limit} must be positive")
+        if not 1 <= inst_sd.get("subscription_renewal_percent", 50) <= 100:
+            errors.append(f"Instance '{inst_name}' sd subscription_renewal_percent must be between 1 and 100")
+
         # Providing Services
         if "providing" in inst_cfg:
             for svc_name, svc_cfg in inst_cfg["providing"].items():
@@ -301,6 +598,24 @@ def validate_config(data: Dict[str, Any]) -> List[str]:
 
                 provided_services[(sid, iid, major)].append(f"{inst_name}:{svc_name}")
 
+                if "qos" in svc_cfg and svc_cfg["qos"] not in qos_profiles:
+                    errors.append(f"Instance '{inst_name}' service '{svc_name}' references unknown QoS profile '{svc_cfg['qos']}'")
+
+                for limit, value in svc_cfg.get("quota", {}).items():
+                    if value <= 0:
+                        errors.append(f"Instance '{inst_name}' service '{svc_name}' quota {limit} must be positive")
+
+                errors.extend(_validate_e2e(svc_cfg.get("e2e", {}), f"Instance '{inst_name}' service '{svc_name}'"))
+
+                for rule in svc_cfg.get("access", []):
+                    if not isinstance(rule, dict) or not isinstance(rule.get("source"), str):
+                        errors.append(f"Instance '{inst_name}' service '{svc_name}' access rule must be an object with a source")
+                        continue
+                    try:
+                        ipaddress.ip_network(rule["source"], strict=False)
+                    except ValueError:
+                        errors.append(f"Instance '{inst_name}' service '{svc_name}' access rule has invalid source '{rule['source']}' (expected an address or subnet)")
+
                 for iface_key, ep_name in offer_on.items():
                     if iface_key not in interfaces:
                         errors.append(f"Instance '{inst_name}' service '{svc_name}' offer_on references unknown interface '{iface_key}'")
@@ -330,15 +645,54 @@ def validate_config(data: Dict[str, Any]) -> List[str]:
         if "required" in inst_cfg:
             for req_name, req_cfg in inst_cfg["required"].items():
                 find_on = req_cfg.get("find_on", [])
+                if "qos" in req_cfg and req_cfg["qos"] not in qos_profiles:
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' references unknown QoS profile '{req_cfg['qos']}'")
                 for if_key in find_on:
                     if if_key not in interfaces:
                         errors.append(f"Instance '{inst_name}' required service '{req_name}' find_on references unknown interface '{if_key}'")
+                local_ep = req_cfg.get("local_endpoint")
+                if local_ep is not None:
+                    known_eps = set(data.get("endpoints", {}))
+                    for iface_cfg in interfaces.values():
+                        known_eps.update(iface_cfg.get("endpoints", {}))
+                    if local_ep not in known_eps:
+                        errors.append(f"Instance '{inst_name}' required service '{req_name}' local_endpoint references unknown endpoint '{local_ep}'")
+                retry = req_cfg.get("subscription_retry", {})
+                if retry.get("ack_timeout_ms", 2000) <= 0:
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' subscription_retry ack_timeout_ms must be positive")
+                if retry.get("max_retries", 3) < 0:
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' subscription_retry max_retries must not be negative")
+                if req_cfg.get("request_limit", {}).get("max_in_flight", 1) < 1:
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' request_limit max_in_flight must be at least 1")
+                request_retry = req_cfg.get("request_retry", {})
+                if request_retry.get("timeout_ms", 2000) <= 0:
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' request_retry timeout_ms must be positive")
+                if request_retry.get("max_retries", 3) < 0:
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' request_retry max_retries must not be negative")
+                for key in request_retry.get("methods", {}):
+                    try:
+                        method_id = int(key, 16) if key.lower().startswith("0x") else int(key)
+                    except ValueError:
+                        method_id = -1
+                    if not 0 <= method_id <= 0xFFFF:
+                        errors.append(f"Instance '{inst_name}' required service '{req_name}' request_retry key '{key}' is not a method ID")
+                errors.extend(_validate_e2e(req_cfg.get("e2e", {}), f"Instance '{inst_name}' required service '{req_name}'"))
+                for eg_name, eg_cfg in req_cfg.get("eventgroups", {}).items():
+                    if "interface" in eg_cfg and eg_cfg["interface"] not in interfaces:
+                        errors.append(f"Instance '{inst_name}' required service '{req_name}' eventgroup '{eg_name}' references unknown interface '{eg_cfg['interface']}'")
+                eg_ids = [eg.get("eventgroup_id") for eg in req_cfg.get("eventgroups", {}).values()]
+                for eg_id in sorted({i for i in eg_ids if eg_ids.count(i) > 1}):
+                    errors.append(f"Instance '{inst_name}' required service '{req_name}' configures eventgroup {eg_id} more than once")
 
     # 3. Analyze Global Conflicts
     for (sid, iid, major), providers in provided_services.items():
         if len(providers) > 1:
             errors.append(f"Duplicate Service (ID: {sid}, Instance: {iid}, Major: {major}) provided by: {', '.join(providers)}")
             
+    for client_id, users in client_ids.items():
+        if len(users) > 1:
+            errors.append(f"Duplicate client_id 0x{client_id:04x} used by: {', '.join(users)}")
+
     for (iface, ip, port, proto), users in used_ports.items():
         insts = set(u.split(':')[0] for u in users)
         if len(insts) > 1: